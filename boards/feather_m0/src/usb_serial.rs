@@ -0,0 +1,165 @@
+//! USB CDC-ACM serial port
+//!
+//! [`UsbSerial`] bundles the USB bus allocator, the USB device and a
+//! `usbd-serial` [`SerialPort`], which are otherwise set up by hand in every
+//! program using the USB port as a serial console.
+//!
+//! ```no_run
+//! static mut USB_SERIAL: Option<bsp::UsbSerial> = None;
+//!
+//! let usb_serial = bsp::UsbSerial::new(
+//!     peripherals.USB,
+//!     &mut clocks,
+//!     &mut peripherals.PM,
+//!     pins.usb_dm,
+//!     pins.usb_dp,
+//! );
+//! unsafe { USB_SERIAL = Some(usb_serial) };
+//! bsp::UsbSerial::enable_interrupts();
+//!
+//! #[interrupt]
+//! fn USB() {
+//!     let usb_serial = unsafe { USB_SERIAL.as_mut().unwrap() };
+//!     if usb_serial.poll() {
+//!         let mut buf = [0; 64];
+//!         if let Ok(len) = usb_serial.read(&mut buf) {
+//!             usb_serial.write(&buf[..len]).ok();
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Like the Arduino core, [`UsbSerial`] resets the board into its bootloader
+//! when the host opens the port at 1200 baud and closes it, so that upload
+//! tools can flash new firmware without pressing the reset button.
+
+use hal::clock::GenericClockController;
+use hal::usb::dfu::DoubleTap;
+use hal::usb::usb_device::{prelude::*, UsbError};
+use hal::usb::UsbBus;
+use usbd_serial::{LineCoding, SerialPort, USB_CLASS_CDC};
+
+use super::{hal, pac, UsbDm, UsbDp};
+use pac::{interrupt, NVIC};
+
+/// Data rate requesting a reset into the bootloader
+pub const BOOTLOADER_BAUD: u32 = 1200;
+
+/// BOSSA bootloader, checking the last word of the 32 KiB of RAM
+const BOOTLOADER: DoubleTap = DoubleTap::bossa(0x2000_8000);
+
+/// USB CDC-ACM serial port
+///
+/// See the [module-level documentation](self) for more details.
+pub struct UsbSerial {
+    device: UsbDevice<'static, UsbBus>,
+    serial: SerialPort<'static, UsbBus>,
+    data_rate: u32,
+    on_line_coding: Option<fn(&LineCoding)>,
+}
+
+impl UsbSerial {
+    /// Set up the USB peripheral as a serial port
+    ///
+    /// The `USB` interrupt handler must call [`poll`](Self::poll), once
+    /// enabled with [`enable_interrupts`](Self::enable_interrupts).
+    ///
+    /// # Panics
+    ///
+    /// The USB bus allocator is stored in a `static` by
+    /// [`usb_allocator_static`](super::usb_allocator_static), so this
+    /// function panics if called more than once, or after it.
+    pub fn new(
+        usb: pac::USB,
+        clocks: &mut GenericClockController,
+        pm: &mut pac::PM,
+        dm: impl Into<UsbDm>,
+        dp: impl Into<UsbDp>,
+    ) -> Self {
+        let allocator = super::usb_allocator_static(usb, clocks, pm, dm, dp);
+        let serial = SerialPort::new(allocator);
+        let device = UsbDeviceBuilder::new(allocator, UsbVidPid(0x16c0, 0x27dd))
+            .manufacturer("Adafruit")
+            .product("Feather M0")
+            .serial_number("0")
+            .device_class(USB_CLASS_CDC)
+            .build();
+        Self {
+            device,
+            serial,
+            data_rate: 0,
+            on_line_coding: None,
+        }
+    }
+
+    /// Unmask the `USB` interrupt
+    ///
+    /// Call this function once the serial port is stored where the interrupt
+    /// handler reaches it, so that the handler never finds it missing.
+    pub fn enable_interrupts() {
+        // SAFETY: The USB interrupt handler only calls `poll`
+        unsafe { NVIC::unmask(interrupt::USB) };
+    }
+
+    /// Call `callback` whenever the host changes the line coding, such as
+    /// the data rate
+    pub fn set_line_coding_callback(&mut self, callback: fn(&LineCoding)) {
+        self.on_line_coding = Some(callback);
+    }
+
+    /// Service the USB peripheral, returning `true` when data may have been
+    /// received
+    ///
+    /// Call this function from the `USB` interrupt handler. It resets the
+    /// board into the bootloader when the host closes the port after
+    /// setting its data rate to [`BOOTLOADER_BAUD`].
+    pub fn poll(&mut self) -> bool {
+        let ready = self.device.poll(&mut [&mut self.serial]);
+
+        let line_coding = self.serial.line_coding();
+        if line_coding.data_rate() != self.data_rate {
+            self.data_rate = line_coding.data_rate();
+            if let Some(callback) = self.on_line_coding {
+                callback(line_coding);
+            }
+        }
+        if self.data_rate == BOOTLOADER_BAUD && !self.serial.dtr() {
+            reset_to_bootloader();
+        }
+
+        ready
+    }
+
+    /// Read received bytes into `buf`, returning their number
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, UsbError> {
+        self.serial.read(buf)
+    }
+
+    /// Queue the bytes of `buf` for transmission, returning the number of
+    /// bytes queued
+    pub fn write(&mut self, buf: &[u8]) -> Result<usize, UsbError> {
+        self.serial.write(buf)
+    }
+
+    /// Current line coding requested by the host
+    pub fn line_coding(&self) -> &LineCoding {
+        self.serial.line_coding()
+    }
+
+    /// Return whether the host has opened the port, raising `DTR`
+    pub fn dtr(&self) -> bool {
+        self.serial.dtr()
+    }
+
+    /// Access the underlying serial port
+    pub fn serial_port(&mut self) -> &mut SerialPort<'static, UsbBus> {
+        &mut self.serial
+    }
+}
+
+/// Reset the board into its bootloader
+pub fn reset_to_bootloader() -> ! {
+    // SAFETY: The board resets right away, so the program does not use the
+    // word overwritten anymore
+    unsafe { BOOTLOADER.reset() }
+}
@@ -7,6 +7,7 @@
 use bsp::hal;
 use cortex_m::asm;
 use feather_m4 as bsp;
+#[cfg(not(feature = "panic-log"))]
 use panic_halt as _;
 
 use hal::{
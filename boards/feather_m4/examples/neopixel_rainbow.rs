@@ -11,9 +11,10 @@
 use bsp::hal;
 use feather_m4 as bsp;
 
-#[cfg(not(feature = "use_semihosting"))]
+#[cfg(all(not(feature = "use_semihosting"), not(feature = "panic-log")))]
+#[cfg(not(feature = "panic-log"))]
 use panic_halt as _;
-#[cfg(feature = "use_semihosting")]
+#[cfg(all(feature = "use_semihosting", not(feature = "panic-log")))]
 use panic_semihosting as _;
 
 use bsp::entry;
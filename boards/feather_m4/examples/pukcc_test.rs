@@ -5,28 +5,23 @@ use bsp::ehal;
 use bsp::hal;
 use feather_m4 as bsp;
 
-#[cfg(not(feature = "use_semihosting"))]
+#[cfg(all(not(feature = "use_semihosting"), not(feature = "panic-log")))]
 use panic_halt as _;
-#[cfg(feature = "use_semihosting")]
+#[cfg(all(feature = "use_semihosting", not(feature = "panic-log")))]
 use panic_semihosting as _;
 
 use bsp::entry;
 use ehal::digital::v2::ToggleableOutputPin;
 use hal::clock::GenericClockController;
-use hal::pac::{interrupt, CorePeripherals, Peripherals};
-use hal::{pukcc::*, usb::UsbBus};
-
-use usb_device::bus::UsbBusAllocator;
-use usb_device::prelude::*;
-use usbd_serial::{SerialPort, USB_CLASS_CDC};
+use hal::logger::{self, log};
+use hal::pac::{interrupt, Peripherals};
+use hal::pukcc::*;
 
 use cortex_m::asm::delay as cycle_delay;
-use cortex_m::peripheral::NVIC;
 
 #[entry]
 fn main() -> ! {
     let mut peripherals = Peripherals::take().unwrap();
-    let mut core = CorePeripherals::take().unwrap();
     let mut clocks = GenericClockController::with_external_32kosc(
         peripherals.GCLK,
         &mut peripherals.MCLK,
@@ -37,45 +32,24 @@ fn main() -> ! {
     let pins = bsp::Pins::new(peripherals.PORT);
     let mut red_led = pins.d13.into_push_pull_output();
 
-    let bus_allocator = unsafe {
-        USB_ALLOCATOR = Some(bsp::usb_allocator(
-            pins.usb_dm,
-            pins.usb_dp,
-            peripherals.USB,
-            &mut clocks,
-            &mut peripherals.MCLK,
-        ));
-        USB_ALLOCATOR.as_ref().unwrap()
-    };
-
-    unsafe {
-        USB_SERIAL = Some(SerialPort::new(&bus_allocator));
-        USB_BUS = Some(
-            UsbDeviceBuilder::new(&bus_allocator, UsbVidPid(0x16c0, 0x27dd))
-                .manufacturer("Fake company")
-                .product("Serial port")
-                .serial_number("TEST")
-                .device_class(USB_CLASS_CDC)
-                .build(),
-        );
-    }
-
-    unsafe {
-        core.NVIC.set_priority(interrupt::USB_OTHER, 1);
-        core.NVIC.set_priority(interrupt::USB_TRCPT0, 1);
-        core.NVIC.set_priority(interrupt::USB_TRCPT1, 1);
-        NVIC::unmask(interrupt::USB_OTHER);
-        NVIC::unmask(interrupt::USB_TRCPT0);
-        NVIC::unmask(interrupt::USB_TRCPT1);
-    }
+    let usb_serial = cortex_m::singleton!(: bsp::UsbSerial = bsp::UsbSerial::new(
+        pins.usb_dm,
+        pins.usb_dp,
+        peripherals.USB,
+        &mut clocks,
+        &mut peripherals.MCLK,
+    ))
+    .unwrap();
+    logger::init(usb_serial, log::LevelFilter::Info).unwrap();
+    bsp::UsbSerial::enable_interrupts();
 
     let pukcc = Pukcc::enable(&mut peripherals.MCLK).unwrap();
 
     loop {
-        serial_writeln!("Column 1: Is generated signature identical to a reference signature?",);
-        serial_writeln!("Column 2: Is a signature valid according to PUKCC");
-        serial_writeln!("Column 3: Is a broken signature invalid according to PUKCC");
-        serial_writeln!("Test vector: {} samples", K_SIGNATURE_PAIRS.len());
+        log::info!("Column 1: Is generated signature identical to a reference signature?",);
+        log::info!("Column 2: Is a signature valid according to PUKCC");
+        log::info!("Column 3: Is a broken signature invalid according to PUKCC");
+        log::info!("Test vector: {} samples", K_SIGNATURE_PAIRS.len());
         for (i, (k, reference_signature)) in K_SIGNATURE_PAIRS.iter().enumerate() {
             let i = i + 1;
             let mut generated_signature = [0_u8; 64];
@@ -93,7 +67,7 @@ fn main() -> ! {
                     .map(|(&left, &right)| left == right)
                     .all(|r| r == true),
                 Err(e) => {
-                    serial_writeln!("Error during signature generation: {:?}", e);
+                    log::error!("Error during signature generation: {:?}", e);
                     false
                 }
             };
@@ -118,7 +92,7 @@ fn main() -> ! {
                 Err(_) => true,
                 Ok(_) => false,
             };
-            serial_writeln!(
+            log::info!(
                 "{:>2}: {:<5} | {:<5} | {:<5}",
                 i,
                 are_signatures_same,
@@ -132,117 +106,19 @@ fn main() -> ! {
     }
 }
 
-static mut USB_ALLOCATOR: Option<UsbBusAllocator<UsbBus>> = None;
-static mut USB_BUS: Option<UsbDevice<UsbBus>> = None;
-static mut USB_SERIAL: Option<SerialPort<UsbBus>> = None;
-
-/// Borrows the global singleton `UsbSerial` for a brief period with interrupts
-/// disabled
-///
-/// # Arguments
-/// `borrower`: The closure that gets run borrowing the global `UsbSerial`
-///
-/// # Safety
-/// the global singleton `UsbSerial` can be safely borrowed because we disable
-/// interrupts while it is being borrowed, guaranteeing that interrupt handlers
-/// like `USB` cannot mutate `UsbSerial` while we are as well.
-///
-/// # Panic
-/// If `init` has not been called and we haven't initialized our global
-/// singleton `UsbSerial`, we will panic.
-fn usbserial_get<T, R>(borrower: T) -> R
-where
-    T: Fn(&mut SerialPort<UsbBus>) -> R,
-{
-    usb_free(|_| unsafe {
-        let mut usb_serial = USB_SERIAL.as_mut().expect("UsbSerial not initialized");
-        borrower(&mut usb_serial)
-    })
-}
-
-/// Execute closure `f` in an interrupt-free context.
-///
-/// This as also known as a "critical section".
-#[inline]
-fn usb_free<F, R>(f: F) -> R
-where
-    F: FnOnce(&cortex_m::interrupt::CriticalSection) -> R,
-{
-    NVIC::mask(interrupt::USB_OTHER);
-    NVIC::mask(interrupt::USB_TRCPT0);
-    NVIC::mask(interrupt::USB_TRCPT1);
-
-    let r = f(unsafe { &cortex_m::interrupt::CriticalSection::new() });
-
-    unsafe {
-        NVIC::unmask(interrupt::USB_OTHER);
-        NVIC::unmask(interrupt::USB_TRCPT0);
-        NVIC::unmask(interrupt::USB_TRCPT1);
-    };
-
-    r
-}
-
-/// Writes the given message out over USB serial.
-///
-/// # Arguments
-/// * println args: variable arguments passed along to `core::write!`
-///
-/// # Warning
-/// as this function deals with a static mut, and it is also accessed in the
-/// USB interrupt handler, we both have unsafe code for unwrapping a static mut
-/// as well as disabling of interrupts while we do so.
-///
-/// # Safety
-/// the only time the static mut is used, we have interrupts disabled so we know
-/// we have sole access
-#[macro_export]
-macro_rules! serial_writeln {
-    ($($tt:tt)+) => {{
-        use core::fmt::Write;
-
-        let mut s: heapless::String<256> = heapless::String::new();
-        core::write!(&mut s, $($tt)*).unwrap();
-        usbserial_get(|usbserial| {
-            usbserial.write(s.as_bytes()).ok();
-            usbserial.write("\r\n".as_bytes()).ok();
-        });
-    }};
-}
-
-fn poll_usb() {
-    unsafe {
-        USB_BUS.as_mut().map(|usb_dev| {
-            USB_SERIAL.as_mut().map(|serial| {
-                usb_dev.poll(&mut [serial]);
-                let mut buf = [0u8; 64];
-
-                if let Ok(count) = serial.read(&mut buf) {
-                    for (i, c) in buf.iter().enumerate() {
-                        if i >= count {
-                            break;
-                        }
-                        serial.write(&[c.clone()]).unwrap();
-                    }
-                };
-            });
-        });
-    };
-}
-
 #[interrupt]
 fn USB_OTHER() {
-    poll_usb();
+    logger::poll();
 }
 
 #[interrupt]
 fn USB_TRCPT0() {
-    poll_usb();
+    logger::poll();
 }
 
 #[interrupt]
 fn USB_TRCPT1() {
-    poll_usb();
+    logger::poll();
 }
 
 const PRIVATE_KEY: [u8; 32] = [
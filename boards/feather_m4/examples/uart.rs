@@ -4,6 +4,7 @@
 #![no_main]
 
 use cortex_m::asm;
+#[cfg(not(feature = "panic-log"))]
 use panic_halt as _;
 
 use bsp::hal;
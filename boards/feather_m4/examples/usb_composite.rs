@@ -0,0 +1,149 @@
+//! Composite USB device: a CDC-ACM serial port echoing what it receives, and
+//! a HID mouse twitching up and down
+//!
+//! Each class allocates its endpoints from the same allocator, before the
+//! `UsbDevice` is built. The serial port uses an interrupt IN endpoint and a
+//! bulk IN/OUT pair, and the mouse an interrupt IN endpoint. The endpoints
+//! left over are printed over the serial port. Adding a mass storage class,
+//! with its own bulk IN/OUT pair, needs one IN and one OUT endpoint free, or
+//! two endpoint numbers free in both directions once double-buffering is
+//! enabled with `UsbBus::set_double_buffered`, as a double-buffered bulk
+//! endpoint uses up both directions of its number.
+
+#![no_std]
+#![no_main]
+
+use bsp::hal;
+use feather_m4 as bsp;
+
+#[cfg(all(not(feature = "use_semihosting"), not(feature = "panic-log")))]
+#[cfg(not(feature = "panic-log"))]
+use panic_halt as _;
+#[cfg(all(feature = "use_semihosting", not(feature = "panic-log")))]
+use panic_semihosting as _;
+
+use bsp::entry;
+use hal::clock::GenericClockController;
+use hal::pac::{interrupt, CorePeripherals, Peripherals};
+use hal::prelude::*;
+use hal::usb::UsbBus;
+
+use usb_device::prelude::*;
+use usb_device::UsbDirection;
+use usbd_hid::descriptor::generator_prelude::*;
+use usbd_hid::descriptor::MouseReport;
+use usbd_hid::hid_class::HIDClass;
+use usbd_serial::SerialPort;
+
+use core::fmt::Write;
+use cortex_m::asm::delay as cycle_delay;
+use cortex_m::interrupt::free as disable_interrupts;
+
+#[entry]
+fn main() -> ! {
+    let mut peripherals = Peripherals::take().unwrap();
+    let mut core = CorePeripherals::take().unwrap();
+    let mut clocks = GenericClockController::with_external_32kosc(
+        peripherals.GCLK,
+        &mut peripherals.MCLK,
+        &mut peripherals.OSC32KCTRL,
+        &mut peripherals.OSCCTRL,
+        &mut peripherals.NVMCTRL,
+    );
+    let pins = bsp::Pins::new(peripherals.PORT);
+    let mut red_led: bsp::RedLed = pins.d13.into();
+
+    let bus_allocator = bsp::usb_allocator_static(
+        pins.usb_dm,
+        pins.usb_dp,
+        peripherals.USB,
+        &mut clocks,
+        &mut peripherals.MCLK,
+    );
+
+    unsafe {
+        USB_SERIAL = Some(SerialPort::new(bus_allocator));
+        USB_HID = Some(HIDClass::new(bus_allocator, MouseReport::desc(), 60));
+        USB_BUS = Some(
+            UsbDeviceBuilder::new(bus_allocator, UsbVidPid(0x16c0, 0x27dd))
+                .manufacturer("Fake company")
+                .product("Composite device")
+                .serial_number("TEST")
+                .composite_with_iads()
+                .build(),
+        );
+    }
+
+    unsafe {
+        bsp::enable_usb_interrupts(&mut core.NVIC, 1);
+    }
+
+    let mut up = true;
+    loop {
+        cycle_delay(25 * 1024 * 1024);
+        red_led.toggle().unwrap();
+        let report = MouseReport {
+            x: 0,
+            y: if up { 4 } else { -4 },
+            buttons: 0,
+        };
+        up = !up;
+        disable_interrupts(|_| unsafe {
+            if let Some(hid) = USB_HID.as_mut() {
+                hid.push_input(&report).ok();
+            }
+            if let Some(serial) = USB_SERIAL.as_mut() {
+                let mut text = heapless::String::<64>::new();
+                write!(
+                    text,
+                    "endpoints free: {} in, {} out; buffer free: {}\r\n",
+                    UsbBus::endpoints_available(UsbDirection::In),
+                    UsbBus::endpoints_available(UsbDirection::Out),
+                    UsbBus::buffer_available(),
+                )
+                .ok();
+                serial.write(text.as_bytes()).ok();
+            }
+        });
+    }
+}
+
+static mut USB_BUS: Option<UsbDevice<UsbBus>> = None;
+static mut USB_SERIAL: Option<SerialPort<UsbBus>> = None;
+static mut USB_HID: Option<HIDClass<UsbBus>> = None;
+
+fn poll_usb() {
+    unsafe {
+        if let (Some(usb_dev), Some(serial), Some(hid)) =
+            (USB_BUS.as_mut(), USB_SERIAL.as_mut(), USB_HID.as_mut())
+        {
+            // Every class is polled together
+            if usb_dev.poll(&mut [serial, hid]) {
+                let mut buf = [0u8; 64];
+                if let Ok(count) = serial.read(&mut buf) {
+                    serial.write(&buf[..count]).ok();
+                }
+            }
+        }
+    };
+}
+
+#[interrupt]
+fn USB_OTHER() {
+    poll_usb();
+}
+
+#[interrupt]
+fn USB_SOF_HSOF() {
+    poll_usb();
+}
+
+#[interrupt]
+fn USB_TRCPT0() {
+    poll_usb();
+}
+
+#[interrupt]
+fn USB_TRCPT1() {
+    poll_usb();
+}
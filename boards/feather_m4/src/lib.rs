@@ -246,6 +246,11 @@ pub fn uart(
         .enable()
 }
 
+#[cfg(feature = "usb")]
+pub mod usb_serial;
+#[cfg(feature = "usb")]
+pub use usb_serial::UsbSerial;
+
 #[cfg(feature = "usb")]
 /// Convenience function for setting up USB
 pub fn usb_allocator(
@@ -263,3 +268,57 @@ pub fn usb_allocator(
     let (dm, dp) = (dm.into(), dp.into());
     UsbBusAllocator::new(UsbBus::new(usb_clock, mclk, dm, dp, usb))
 }
+
+#[cfg(feature = "usb")]
+static mut USB_ALLOCATOR: Option<UsbBusAllocator<UsbBus>> = None;
+
+#[cfg(feature = "usb")]
+/// Set up USB, storing the bus allocator in a `static`
+///
+/// The `UsbDevice` and its classes borrow the allocator for as long as the
+/// program runs. Composite devices create all their classes from the
+/// allocator returned, before building the `UsbDevice`; see the
+/// `usb_composite` example of the `feather_m4` BSP. [`UsbSerial`] uses this
+/// function as well.
+///
+/// # Panics
+///
+/// Panics if called more than once.
+pub fn usb_allocator_static(
+    dm: impl Into<UsbDm>,
+    dp: impl Into<UsbDp>,
+    usb: pac::USB,
+    clocks: &mut GenericClockController,
+    mclk: &mut pac::MCLK,
+) -> &'static UsbBusAllocator<UsbBus> {
+    // SAFETY: The allocator is only written once, before the USB interrupts
+    // are unmasked
+    unsafe {
+        assert!(USB_ALLOCATOR.is_none(), "USB allocator already created");
+        USB_ALLOCATOR = Some(usb_allocator(dm, dp, usb, clocks, mclk));
+        USB_ALLOCATOR.as_ref().unwrap()
+    }
+}
+
+#[cfg(feature = "usb")]
+/// Give the USB interrupts the same `priority`, and unmask them
+///
+/// The handlers of the `USB_OTHER`, `USB_SOF_HSOF`, `USB_TRCPT0` and
+/// `USB_TRCPT1` interrupts must poll the `UsbDevice` with all its classes.
+/// Sharing a priority keeps the handlers from preempting each other.
+///
+/// # Safety
+///
+/// Unmasking interrupts can break critical sections based on masking them.
+pub unsafe fn enable_usb_interrupts(nvic: &mut pac::NVIC, priority: u8) {
+    use pac::{interrupt, NVIC};
+
+    nvic.set_priority(interrupt::USB_OTHER, priority);
+    nvic.set_priority(interrupt::USB_SOF_HSOF, priority);
+    nvic.set_priority(interrupt::USB_TRCPT0, priority);
+    nvic.set_priority(interrupt::USB_TRCPT1, priority);
+    NVIC::unmask(interrupt::USB_OTHER);
+    NVIC::unmask(interrupt::USB_SOF_HSOF);
+    NVIC::unmask(interrupt::USB_TRCPT0);
+    NVIC::unmask(interrupt::USB_TRCPT1);
+}
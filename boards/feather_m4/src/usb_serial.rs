@@ -0,0 +1,216 @@
+//! USB CDC-ACM serial port
+//!
+//! [`UsbSerial`] bundles the USB bus allocator, the USB device and a
+//! `usbd-serial` [`SerialPort`], which are otherwise set up by hand in every
+//! program using the USB port as a serial console.
+//!
+//! ```no_run
+//! static mut USB_SERIAL: Option<bsp::UsbSerial> = None;
+//!
+//! let usb_serial = bsp::UsbSerial::new(
+//!     pins.usb_dm,
+//!     pins.usb_dp,
+//!     peripherals.USB,
+//!     &mut clocks,
+//!     &mut peripherals.MCLK,
+//! );
+//! unsafe { USB_SERIAL = Some(usb_serial) };
+//! bsp::UsbSerial::enable_interrupts();
+//!
+//! fn poll_usb() {
+//!     let usb_serial = unsafe { USB_SERIAL.as_mut().unwrap() };
+//!     if usb_serial.poll() {
+//!         let mut buf = [0; 64];
+//!         if let Ok(len) = usb_serial.read(&mut buf) {
+//!             usb_serial.write(&buf[..len]).ok();
+//!         }
+//!     }
+//! }
+//!
+//! #[interrupt]
+//! fn USB_OTHER() {
+//!     poll_usb();
+//! }
+//!
+//! #[interrupt]
+//! fn USB_TRCPT0() {
+//!     poll_usb();
+//! }
+//!
+//! #[interrupt]
+//! fn USB_TRCPT1() {
+//!     poll_usb();
+//! }
+//! ```
+//!
+//! With the `log` feature, [`UsbSerial`] is a [`Sink`] of the HAL logger, in
+//! which case the USB interrupt handlers call [`logger::poll`] instead.
+//!
+//! [`Sink`]: hal::logger::Sink
+//! [`logger::poll`]: hal::logger::poll
+//!
+//! Like the Arduino core, [`UsbSerial`] resets the board into its bootloader
+//! when the host opens the port at 1200 baud and closes it, so that upload
+//! tools can flash new firmware without pressing the reset button.
+
+use hal::clock::GenericClockController;
+use hal::usb::dfu::DoubleTap;
+use hal::usb::usb_device::{prelude::*, UsbError};
+use usbd_serial::{LineCoding, SerialPort, USB_CLASS_CDC};
+
+use super::{hal, pac, UsbBus, UsbDm, UsbDp};
+use pac::{interrupt, NVIC};
+
+/// Data rate requesting a reset into the bootloader
+pub const BOOTLOADER_BAUD: u32 = 1200;
+
+/// UF2 bootloader, checking the last word of the 192 KiB of RAM
+const BOOTLOADER: DoubleTap = DoubleTap::uf2(0x2003_0000);
+
+/// USB CDC-ACM serial port
+///
+/// See the [module-level documentation](self) for more details.
+pub struct UsbSerial {
+    device: UsbDevice<'static, UsbBus>,
+    serial: SerialPort<'static, UsbBus>,
+    data_rate: u32,
+    on_line_coding: Option<fn(&LineCoding)>,
+}
+
+impl UsbSerial {
+    /// Set up the USB peripheral as a serial port
+    ///
+    /// The `USB_OTHER`, `USB_TRCPT0` and `USB_TRCPT1` interrupt handlers must
+    /// call [`poll`](Self::poll), once enabled with
+    /// [`enable_interrupts`](Self::enable_interrupts).
+    ///
+    /// # Panics
+    ///
+    /// The USB bus allocator is stored in a `static` by
+    /// [`usb_allocator_static`](super::usb_allocator_static), so this
+    /// function panics if called more than once, or after it.
+    pub fn new(
+        dm: impl Into<UsbDm>,
+        dp: impl Into<UsbDp>,
+        usb: pac::USB,
+        clocks: &mut GenericClockController,
+        mclk: &mut pac::MCLK,
+    ) -> Self {
+        let allocator = super::usb_allocator_static(dm, dp, usb, clocks, mclk);
+        let serial = SerialPort::new(allocator);
+        let device = UsbDeviceBuilder::new(allocator, UsbVidPid(0x16c0, 0x27dd))
+            .manufacturer("Adafruit")
+            .product("Feather M4")
+            .serial_number("0")
+            .device_class(USB_CLASS_CDC)
+            .build();
+        Self {
+            device,
+            serial,
+            data_rate: 0,
+            on_line_coding: None,
+        }
+    }
+
+    /// Unmask the `USB_OTHER`, `USB_TRCPT0` and `USB_TRCPT1` interrupts
+    ///
+    /// Call this function once the serial port is stored where the interrupt
+    /// handlers reach it, so that the handlers never find it missing.
+    pub fn enable_interrupts() {
+        // SAFETY: The USB interrupt handlers only call `poll`
+        unsafe {
+            NVIC::unmask(interrupt::USB_OTHER);
+            NVIC::unmask(interrupt::USB_TRCPT0);
+            NVIC::unmask(interrupt::USB_TRCPT1);
+        }
+    }
+
+    /// Call `callback` whenever the host changes the line coding, such as
+    /// the data rate
+    pub fn set_line_coding_callback(&mut self, callback: fn(&LineCoding)) {
+        self.on_line_coding = Some(callback);
+    }
+
+    /// Service the USB peripheral, returning `true` when data may have been
+    /// received
+    ///
+    /// Call this function from the USB interrupt handlers. It resets the
+    /// board into the bootloader when the host closes the port after
+    /// setting its data rate to [`BOOTLOADER_BAUD`].
+    pub fn poll(&mut self) -> bool {
+        let ready = self.device.poll(&mut [&mut self.serial]);
+
+        let line_coding = self.serial.line_coding();
+        if line_coding.data_rate() != self.data_rate {
+            self.data_rate = line_coding.data_rate();
+            if let Some(callback) = self.on_line_coding {
+                callback(line_coding);
+            }
+        }
+        if self.data_rate == BOOTLOADER_BAUD && !self.serial.dtr() {
+            reset_to_bootloader();
+        }
+
+        ready
+    }
+
+    /// Read received bytes into `buf`, returning their number
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, UsbError> {
+        self.serial.read(buf)
+    }
+
+    /// Queue the bytes of `buf` for transmission, returning the number of
+    /// bytes queued
+    pub fn write(&mut self, buf: &[u8]) -> Result<usize, UsbError> {
+        self.serial.write(buf)
+    }
+
+    /// Current line coding requested by the host
+    pub fn line_coding(&self) -> &LineCoding {
+        self.serial.line_coding()
+    }
+
+    /// Return whether the host has opened the port, raising `DTR`
+    pub fn dtr(&self) -> bool {
+        self.serial.dtr()
+    }
+
+    /// Access the underlying serial port
+    pub fn serial_port(&mut self) -> &mut SerialPort<'static, UsbBus> {
+        &mut self.serial
+    }
+}
+
+#[cfg(feature = "log")]
+impl hal::logger::Sink for UsbSerial {
+    /// Queue `s` for transmission, dropping the bytes which don't fit in the
+    /// buffer of the serial port
+    fn write_str(&mut self, s: &str) {
+        let mut bytes = s.as_bytes();
+        while let Ok(len) = self.serial.write(bytes) {
+            bytes = &bytes[len..];
+            if len == 0 || bytes.is_empty() {
+                break;
+            }
+        }
+    }
+
+    fn poll(&mut self) {
+        if UsbSerial::poll(self) {
+            // Discard the received bytes, the port only outputs the log
+            let mut buf = [0; 64];
+            while let Ok(len) = self.serial.read(&mut buf) {
+                if len == 0 {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Reset the board into its bootloader
+pub fn reset_to_bootloader() -> ! {
+    // SAFETY: The board resets right away, so the program does not use the
+    // word overwritten anymore
+    unsafe { BOOTLOADER.reset() }
+}
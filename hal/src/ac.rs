@@ -0,0 +1,405 @@
+//! # AC - Analog Comparators
+//!
+//! The AC holds two comparators, each comparing a positive input to a
+//! negative input. Both inputs can be pins, and the negative input can also
+//! be the ground, the bandgap reference, the DAC output or a fraction of VDD
+//! produced by the VDD scaler of the comparator.
+//!
+//! The comparators can raise an interrupt or an event when their output
+//! toggles, rises or falls, and keep running in standby, which makes them
+//! able to wake the chip up. They can also be combined in window mode, to
+//! detect whether a common input is above, inside or below the window
+//! delimited by their other inputs.
+//!
+//! The driver does not configure the AC pins, which must be set to alternate
+//! function B beforehand.
+//!
+//! ```no_run
+//! use atsamd_hal::ac::{Ac, Comparator, Config, InterruptMode, NegativeInput, PositiveInput, Source};
+//!
+//! let gclk0 = clocks.gclk0();
+//! let dig_clock = clocks.ac_dig(&gclk0).unwrap();
+//! let ana_clock = clocks.ac_ana(&gclk0).unwrap();
+//! let mut ac = Ac::new(peripherals.AC, &mut peripherals.PM, &dig_clock, &ana_clock);
+//!
+//! // Zero-crossing detection of a signal centered on VDD / 2
+//! let mut config = Config::new(PositiveInput::PIN0, NegativeInput::VSCALE);
+//! config.scaler = 31;
+//! config.interrupt_mode = InterruptMode::TOGGLE;
+//! config.run_in_standby = true;
+//! ac.configure(Comparator::Comp0, config);
+//! ac.enable_interrupt(Source::Comp0);
+//! ```
+
+use crate::bus_clock::BusClock;
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+use crate::clock::{AcAnaClock, AcDigClock};
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+use crate::pac::{AC, PM};
+
+#[cfg(feature = "min-samd51g")]
+use crate::calibration;
+#[cfg(feature = "min-samd51g")]
+use crate::clock::AcClock;
+#[cfg(feature = "min-samd51g")]
+use crate::pac::{AC, MCLK};
+
+/// Filtering of the comparator output
+pub use crate::pac::ac::compctrl::FLEN_A as Filter;
+/// Comparator output edge raising interrupts and events
+pub use crate::pac::ac::compctrl::INTSEL_A as InterruptMode;
+/// Negative input of a comparator
+pub use crate::pac::ac::compctrl::MUXNEG_A as NegativeInput;
+/// Positive input of a comparator
+pub use crate::pac::ac::compctrl::MUXPOS_A as PositiveInput;
+/// Routing of the comparator output to its pin
+pub use crate::pac::ac::compctrl::OUT_A as Output;
+/// Window state change raising interrupts and events
+pub use crate::pac::ac::winctrl::WINTSEL0_A as WindowInterruptMode;
+
+/// Hysteresis level of a comparator
+#[cfg(feature = "min-samd51g")]
+pub use crate::pac::ac::compctrl::HYST_A as HysteresisLevel;
+
+/// Peripheral clock controller of the AC
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+pub type PowerManager = PM;
+/// Peripheral clock controller of the AC
+#[cfg(feature = "min-samd51g")]
+pub type PowerManager = MCLK;
+
+/// One of the two comparators
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparator {
+    /// Comparator 0
+    Comp0,
+    /// Comparator 1
+    Comp1,
+}
+
+impl Comparator {
+    #[inline]
+    fn index(self) -> usize {
+        match self {
+            Comparator::Comp0 => 0,
+            Comparator::Comp1 => 1,
+        }
+    }
+}
+
+/// Source of interrupts and events
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// Comparator 0 output, according to its [`InterruptMode`]
+    Comp0,
+    /// Comparator 1 output, according to its [`InterruptMode`]
+    Comp1,
+    /// Window state, according to the [`WindowInterruptMode`]
+    Window,
+}
+
+/// Position of the common input relative to the window
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowState {
+    /// Above the window
+    Above,
+    /// Inside the window
+    Inside,
+    /// Below the window
+    Below,
+}
+
+/// Comparator configuration
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Positive input
+    pub positive: PositiveInput,
+    /// Negative input
+    pub negative: NegativeInput,
+    /// VDD scaler output, `VDD * (scaler + 1) / 64`, used by the `VSCALE`
+    /// inputs. Must be lower than 64.
+    pub scaler: u8,
+    /// Enable hysteresis
+    pub hysteresis: bool,
+    /// Hysteresis level, when enabled
+    #[cfg(feature = "min-samd51g")]
+    pub hysteresis_level: HysteresisLevel,
+    /// Filtering of the output
+    pub filter: Filter,
+    /// Output edge raising interrupts and events
+    pub interrupt_mode: InterruptMode,
+    /// Routing of the output to the comparator pin
+    pub output: Output,
+    /// Keep comparing in standby. On SAMD11 and SAMD21 chips, this setting
+    /// is shared by both comparators.
+    pub run_in_standby: bool,
+}
+
+impl Config {
+    /// Continuously compare `positive` to `negative`, without hysteresis or
+    /// filtering, raising interrupts and events on every toggle
+    #[inline]
+    pub fn new(positive: PositiveInput, negative: NegativeInput) -> Self {
+        Self {
+            positive,
+            negative,
+            scaler: 0,
+            hysteresis: false,
+            #[cfg(feature = "min-samd51g")]
+            hysteresis_level: HysteresisLevel::HYST50,
+            filter: Filter::OFF,
+            interrupt_mode: InterruptMode::TOGGLE,
+            output: Output::OFF,
+            run_in_standby: false,
+        }
+    }
+}
+
+/// Analog Comparators
+pub struct Ac {
+    ac: AC,
+}
+
+impl Ac {
+    /// Enable the AC clocks and the AC
+    ///
+    /// Both comparators are disabled, see [`Ac::configure`].
+    #[cfg(any(feature = "samd11", feature = "samd21"))]
+    pub fn new(ac: AC, pm: &mut PowerManager, _clock: &AcDigClock, _analog: &AcAnaClock) -> Self {
+        AC::enable_bus_clock(pm);
+        let mut ac = Self { ac };
+        ac.reset();
+        ac.set_enabled(true);
+        ac
+    }
+
+    /// Enable the AC clock, calibrate and enable the AC
+    ///
+    /// Both comparators are disabled, see [`Ac::configure`].
+    #[cfg(feature = "min-samd51g")]
+    pub fn new(ac: AC, mclk: &mut PowerManager, _clock: &AcClock) -> Self {
+        AC::enable_bus_clock(mclk);
+        let mut ac = Self { ac };
+        ac.reset();
+        // SAFETY: The bias calibration is read from the NVM calibration area
+        ac.ac
+            .calib
+            .write(|w| unsafe { w.bias0().bits(calibration::ac_bias_cal()) });
+        ac.set_enabled(true);
+        ac
+    }
+
+    /// Disable the AC and its clock and return the register block
+    pub fn free(mut self, pm: &mut PowerManager) -> AC {
+        self.reset();
+        AC::disable_bus_clock(pm);
+        self.ac
+    }
+
+    #[inline]
+    fn reset(&mut self) {
+        self.ac.ctrla.write(|w| w.swrst().set_bit());
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        while self.ac.statusb.read().syncbusy().bit_is_set() {}
+        #[cfg(feature = "min-samd51g")]
+        while self.ac.syncbusy.read().swrst().bit_is_set() {}
+    }
+
+    #[inline]
+    fn set_enabled(&mut self, enabled: bool) {
+        self.ac.ctrla.modify(|_, w| w.enable().bit(enabled));
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        while self.ac.statusb.read().syncbusy().bit_is_set() {}
+        #[cfg(feature = "min-samd51g")]
+        while self.ac.syncbusy.read().enable().bit_is_set() {}
+    }
+
+    #[inline]
+    fn wait_compctrl(&self, comparator: Comparator) {
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        {
+            let _ = comparator;
+            while self.ac.statusb.read().syncbusy().bit_is_set() {}
+        }
+        #[cfg(feature = "min-samd51g")]
+        loop {
+            let syncbusy = self.ac.syncbusy.read();
+            let busy = match comparator {
+                Comparator::Comp0 => syncbusy.compctrl0().bit_is_set(),
+                Comparator::Comp1 => syncbusy.compctrl1().bit_is_set(),
+            };
+            if !busy {
+                break;
+            }
+        }
+    }
+
+    /// Configure and enable `comparator`, and wait for it to be ready
+    ///
+    /// # Panics
+    ///
+    /// Panics if the scaler value is not lower than 64.
+    pub fn configure(&mut self, comparator: Comparator, config: Config) {
+        assert!(config.scaler < 64);
+        let index = comparator.index();
+        self.disable(comparator);
+
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        if self.ac.ctrla.read().runstdby().bit() != config.run_in_standby {
+            // RUNSTDBY is enable-protected
+            self.set_enabled(false);
+            self.ac
+                .ctrla
+                .modify(|_, w| w.runstdby().bit(config.run_in_standby));
+            self.set_enabled(true);
+        }
+
+        // SAFETY: The scaler value was checked above
+        self.ac.scaler[index].write(|w| unsafe { w.value().bits(config.scaler) });
+        self.ac.compctrl[index].write(|w| {
+            w.muxpos().variant(config.positive);
+            w.muxneg().variant(config.negative);
+            w.speed().high();
+            w.intsel().variant(config.interrupt_mode);
+            w.flen().variant(config.filter);
+            w.out().variant(config.output);
+            #[cfg(any(feature = "samd11", feature = "samd21"))]
+            w.hyst().bit(config.hysteresis);
+            #[cfg(feature = "min-samd51g")]
+            {
+                w.hysten().bit(config.hysteresis);
+                w.hyst().variant(config.hysteresis_level);
+                w.runstdby().bit(config.run_in_standby);
+            }
+            w
+        });
+        self.wait_compctrl(comparator);
+        self.ac.compctrl[index].modify(|_, w| w.enable().set_bit());
+        self.wait_compctrl(comparator);
+
+        match comparator {
+            Comparator::Comp0 => while self.ac.statusb.read().ready0().bit_is_clear() {},
+            Comparator::Comp1 => while self.ac.statusb.read().ready1().bit_is_clear() {},
+        }
+    }
+
+    /// Disable `comparator`
+    #[inline]
+    pub fn disable(&mut self, comparator: Comparator) {
+        self.ac.compctrl[comparator.index()].modify(|_, w| w.enable().clear_bit());
+        self.wait_compctrl(comparator);
+    }
+
+    /// Current output of `comparator`, `true` when the positive input is
+    /// above the negative input
+    #[inline]
+    pub fn state(&self, comparator: Comparator) -> bool {
+        let status = self.ac.statusa.read();
+        match comparator {
+            Comparator::Comp0 => status.state0().bit(),
+            Comparator::Comp1 => status.state1().bit(),
+        }
+    }
+
+    /// Combine both comparators in window mode
+    ///
+    /// The positive inputs of both comparators must be connected together,
+    /// and the negative inputs define the window: comparator 0 sets the upper
+    /// limit and comparator 1 the lower limit.
+    pub fn enable_window(&mut self, mode: WindowInterruptMode) {
+        self.ac.winctrl.write(|w| {
+            w.wintsel0().variant(mode);
+            w.wen0().set_bit()
+        });
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        while self.ac.statusb.read().syncbusy().bit_is_set() {}
+        #[cfg(feature = "min-samd51g")]
+        while self.ac.syncbusy.read().winctrl().bit_is_set() {}
+    }
+
+    /// Leave window mode
+    pub fn disable_window(&mut self) {
+        self.ac.winctrl.write(|w| w.wen0().clear_bit());
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        while self.ac.statusb.read().syncbusy().bit_is_set() {}
+        #[cfg(feature = "min-samd51g")]
+        while self.ac.syncbusy.read().winctrl().bit_is_set() {}
+    }
+
+    /// Position of the common input relative to the window, if window mode is
+    /// enabled
+    pub fn window_state(&self) -> Option<WindowState> {
+        if self.ac.winctrl.read().wen0().bit_is_clear() {
+            return None;
+        }
+        let status = self.ac.statusa.read().wstate0();
+        if status.is_above() {
+            Some(WindowState::Above)
+        } else if status.is_inside() {
+            Some(WindowState::Inside)
+        } else {
+            Some(WindowState::Below)
+        }
+    }
+
+    /// Enable the interrupt raised by `source`
+    #[inline]
+    pub fn enable_interrupt(&mut self, source: Source) {
+        self.ac.intenset.write(|w| match source {
+            Source::Comp0 => w.comp0().set_bit(),
+            Source::Comp1 => w.comp1().set_bit(),
+            Source::Window => w.win0().set_bit(),
+        });
+    }
+
+    /// Disable the interrupt raised by `source`
+    #[inline]
+    pub fn disable_interrupt(&mut self, source: Source) {
+        self.ac.intenclr.write(|w| match source {
+            Source::Comp0 => w.comp0().set_bit(),
+            Source::Comp1 => w.comp1().set_bit(),
+            Source::Window => w.win0().set_bit(),
+        });
+    }
+
+    /// Check and clear the interrupt flag of `source`
+    pub fn check_and_clear_interrupt(&mut self, source: Source) -> bool {
+        let flags = self.ac.intflag.read();
+        let set = match source {
+            Source::Comp0 => flags.comp0().bit(),
+            Source::Comp1 => flags.comp1().bit(),
+            Source::Window => flags.win0().bit(),
+        };
+        if set {
+            self.ac.intflag.write(|w| match source {
+                Source::Comp0 => w.comp0().set_bit(),
+                Source::Comp1 => w.comp1().set_bit(),
+                Source::Window => w.win0().set_bit(),
+            });
+        }
+        set
+    }
+
+    /// Generate an event from `source`, to be routed by the event system
+    ///
+    /// The event control register is enable-protected, so the AC is briefly
+    /// disabled.
+    pub fn enable_event_output(&mut self, source: Source) {
+        self.set_event_output(source, true);
+    }
+
+    /// Stop generating events from `source`
+    pub fn disable_event_output(&mut self, source: Source) {
+        self.set_event_output(source, false);
+    }
+
+    fn set_event_output(&mut self, source: Source, enabled: bool) {
+        self.set_enabled(false);
+        self.ac.evctrl.modify(|_, w| match source {
+            Source::Comp0 => w.compeo0().bit(enabled),
+            Source::Comp1 => w.compeo1().bit(enabled),
+            Source::Window => w.wineo0().bit(enabled),
+        });
+        self.set_enabled(true);
+    }
+}
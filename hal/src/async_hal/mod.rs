@@ -0,0 +1,75 @@
+//! # Building blocks for `async` drivers
+//!
+//! The `async` drivers in this HAL are driven by peripheral interrupts. A
+//! driver future registers its [`Waker`] in an [`InterruptWaker`], enables the
+//! interrupts it is waiting on and returns [`Poll::Pending`]. The interrupt
+//! handler then disables the interrupts that fired and wakes the task, which
+//! re-polls the future.
+//!
+//! Interrupt handlers are not registered by the HAL. Each driver module
+//! provides an `on_interrupt` function that must be called from the
+//! corresponding interrupt handler(s), using whichever mechanism (RTIC task,
+//! `#[interrupt]` function, executor binding) suits the application.
+//!
+//! The `async` Cargo feature requires Rust 1.75 or later.
+//!
+//! [`Waker`]: core::task::Waker
+//! [`Poll::Pending`]: core::task::Poll::Pending
+
+use core::cell::RefCell;
+use core::task::Waker;
+
+use cortex_m::interrupt::{self, Mutex};
+
+/// Storage for a single [`Waker`], shared between a future and an interrupt
+/// handler
+///
+/// Both [`register`](InterruptWaker::register) and
+/// [`wake`](InterruptWaker::wake) run in a critical section, so this type
+/// works on thumbv6m targets, which lack atomic compare-and-swap
+/// instructions.
+pub struct InterruptWaker {
+    waker: Mutex<RefCell<Option<Waker>>>,
+}
+
+impl InterruptWaker {
+    /// Create a new, empty `InterruptWaker`
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            waker: Mutex::new(RefCell::new(None)),
+        }
+    }
+
+    /// Register a [`Waker`] to be woken by the next call to
+    /// [`wake`](InterruptWaker::wake)
+    ///
+    /// Any previously registered `Waker` is replaced, unless it would wake the
+    /// same task.
+    #[inline]
+    pub fn register(&self, waker: &Waker) {
+        interrupt::free(|cs| {
+            let mut slot = self.waker.borrow(cs).borrow_mut();
+            match slot.as_ref() {
+                Some(old) if old.will_wake(waker) => (),
+                _ => *slot = Some(waker.clone()),
+            }
+        });
+    }
+
+    /// Wake the registered [`Waker`], if any, and remove it
+    #[inline]
+    pub fn wake(&self) {
+        let waker = interrupt::free(|cs| self.waker.borrow(cs).borrow_mut().take());
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl Default for InterruptWaker {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,187 @@
+//! # BOD - Brown-out detector
+//!
+//! The BOD33 monitors the VDD supply, and either resets the chip or raises
+//! an interrupt when VDD falls below a threshold level. The interrupt leaves
+//! time to save state before the supply fails, provided the threshold is
+//! set above the reset level of the internal power-on reset.
+//!
+//! The BOD33 is configured from the user fuses at startup. [`configure`]
+//! replaces that configuration until the next reset. The BOD12, which
+//! monitors the core supply, is calibrated in the factory and is not user
+//! configurable.
+//!
+//! ```no_run
+//! use atsamd_hal::bod::{self, Action, Config};
+//!
+//! let mut config = Config::new(48);
+//! config.action = Action::INTERRUPT;
+//! bod::configure(&mut peripherals.SYSCTRL, &config);
+//! bod::enable_interrupt(&mut peripherals.SYSCTRL);
+//!
+//! // In the SYSCTRL interrupt handler
+//! if bod::check_and_clear_interrupt(&mut sysctrl) {
+//!     // VDD is low, save state to the NVM
+//! }
+//! ```
+//!
+//! The BOD33 detection interrupt is `SYSCTRL` on SAMD11 and SAMD21 chips, and
+//! `SUPC_BODDET` on SAMD51 and SAME5x chips.
+
+#[cfg(feature = "min-samd51g")]
+use crate::pac::supc::bod33;
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+use crate::pac::sysctrl::bod33;
+
+/// Peripheral owning the BOD33: SYSCTRL on SAMD11 and SAMD21 chips, SUPC on
+/// SAMD51 and SAME5x chips
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+pub type SupplyController = crate::pac::SYSCTRL;
+/// Peripheral owning the BOD33: SYSCTRL on SAMD11 and SAMD21 chips, SUPC on
+/// SAMD51 and SAME5x chips
+#[cfg(feature = "min-samd51g")]
+pub type SupplyController = crate::pac::SUPC;
+
+/// Action taken when VDD falls below the threshold level
+pub use bod33::ACTION_A as Action;
+/// Prescaler of the sampling clock, divided down from the 1 kHz output of the
+/// ULP32K oscillator
+pub use bod33::PSEL_A as Prescaler;
+
+/// BOD33 configuration
+#[derive(Clone, Copy)]
+pub struct Config {
+    /// Threshold level. See the electrical characteristics of the datasheet
+    /// for the voltage of each level.
+    pub level: u8,
+    /// Enable the hysteresis of approximately 50 mV around the threshold
+    #[cfg(any(feature = "samd11", feature = "samd21"))]
+    pub hysteresis: bool,
+    /// Hysteresis around the threshold, in levels, between 0 and 15
+    #[cfg(feature = "min-samd51g")]
+    pub hysteresis: u8,
+    /// Action taken when VDD falls below the threshold level
+    pub action: Action,
+    /// Keep monitoring VDD in standby
+    pub run_in_standby: bool,
+    /// Compare VDD to the threshold periodically, rather than continuously,
+    /// to lower the current consumption. On SAMD51 and SAME5x chips, this only
+    /// applies in standby.
+    pub sampling: bool,
+    /// Sampling clock prescaler
+    pub prescaler: Prescaler,
+}
+
+impl Config {
+    /// Create a configuration for threshold `level`, resetting the chip when
+    /// VDD falls below it, like the fuse default
+    pub fn new(level: u8) -> Self {
+        Self {
+            level,
+            #[cfg(any(feature = "samd11", feature = "samd21"))]
+            hysteresis: false,
+            #[cfg(feature = "min-samd51g")]
+            hysteresis: 0,
+            action: Action::RESET,
+            run_in_standby: false,
+            sampling: false,
+            prescaler: Prescaler::DIV4,
+        }
+    }
+}
+
+/// Configure and enable the BOD33
+///
+/// The BOD33 is disabled while it is reconfigured, then enabled again. This
+/// function returns once the BOD33 is ready.
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+pub fn configure(sysctrl: &mut SupplyController, config: &Config) {
+    disable(sysctrl);
+    sysctrl.bod33.write(|w| {
+        // SAFETY: LEVEL accepts any 6-bit value
+        unsafe { w.level().bits(config.level & 0x3f) };
+        w.hyst()
+            .bit(config.hysteresis)
+            .action()
+            .variant(config.action)
+            .runstdby()
+            .bit(config.run_in_standby)
+            .mode()
+            .bit(config.sampling)
+            .psel()
+            .variant(config.prescaler)
+    });
+    if config.sampling {
+        sysctrl.bod33.modify(|_, w| w.cen().set_bit());
+        while sysctrl.pclksr.read().b33srdy().bit_is_clear() {}
+    }
+    sysctrl.bod33.modify(|_, w| w.enable().set_bit());
+    while sysctrl.pclksr.read().b33srdy().bit_is_clear() {}
+    while sysctrl.pclksr.read().bod33rdy().bit_is_clear() {}
+}
+
+/// Configure and enable the BOD33
+///
+/// The BOD33 is disabled while it is reconfigured, then enabled again. This
+/// function returns once the BOD33 is ready.
+#[cfg(feature = "min-samd51g")]
+pub fn configure(supc: &mut SupplyController, config: &Config) {
+    disable(supc);
+    supc.bod33.modify(|_, w| {
+        // SAFETY: LEVEL accepts any 8-bit value, and HYST any 4-bit value
+        unsafe {
+            w.level().bits(config.level);
+            w.hyst().bits(config.hysteresis & 0xf);
+        }
+        w.action()
+            .variant(config.action)
+            .runstdby()
+            .bit(config.run_in_standby)
+            .stdbycfg()
+            .bit(config.sampling)
+            .psel()
+            .variant(config.prescaler)
+    });
+    supc.bod33.modify(|_, w| w.enable().set_bit());
+    while supc.status.read().b33srdy().bit_is_clear() {}
+    while supc.status.read().bod33rdy().bit_is_clear() {}
+}
+
+/// Disable the BOD33
+pub fn disable(reg: &mut SupplyController) {
+    reg.bod33.modify(|_, w| w.enable().clear_bit());
+    #[cfg(any(feature = "samd11", feature = "samd21"))]
+    while reg.pclksr.read().b33srdy().bit_is_clear() {}
+    #[cfg(feature = "min-samd51g")]
+    while reg.status.read().b33srdy().bit_is_clear() {}
+}
+
+/// Return whether VDD is currently below the threshold level
+pub fn is_detected(reg: &SupplyController) -> bool {
+    #[cfg(any(feature = "samd11", feature = "samd21"))]
+    return reg.pclksr.read().bod33det().bit_is_set();
+    #[cfg(feature = "min-samd51g")]
+    return reg.status.read().bod33det().bit_is_set();
+}
+
+/// Enable the detection interrupt, raised when VDD falls below the threshold
+/// level and the interrupt [`Action`] is configured
+pub fn enable_interrupt(reg: &mut SupplyController) {
+    reg.intenset.write(|w| w.bod33det().set_bit());
+}
+
+/// Disable the detection interrupt
+pub fn disable_interrupt(reg: &mut SupplyController) {
+    reg.intenclr.write(|w| w.bod33det().set_bit());
+}
+
+/// Return whether the detection interrupt flag is set, and clear it
+pub fn check_and_clear_interrupt(reg: &mut SupplyController) -> bool {
+    let set = reg.intflag.read().bod33det().bit_is_set();
+    clear_interrupt(reg);
+    set
+}
+
+/// Clear the detection interrupt flag
+pub fn clear_interrupt(reg: &mut SupplyController) {
+    reg.intflag.write(|w| w.bod33det().set_bit());
+}
@@ -0,0 +1,298 @@
+//! # Bus clocks - Peripheral clock gating
+//!
+//! Besides its generic clock, each peripheral is clocked by the APB bus it
+//! sits on, and DMA-capable peripherals by the AHB bus too. These bus clocks
+//! are gated in the PM on SAMD11 and SAMD21 chips, and in the MCLK on SAMD51
+//! and SAME5x chips. Some are enabled at reset, such as those of the
+//! SERCOMs on SAMD51 chips; the others must be enabled before accessing the
+//! peripheral registers.
+//!
+//! The peripherals implement [`BusClock`], which enables and disables their
+//! bus clocks. The drivers enable the bus clocks of their peripheral through
+//! it when created, and most disable them when freed. [`EnabledBusClock`]
+//! enables them on creation and disables them when dropped, for the
+//! application to hold for as long as it uses a peripheral without a driver:
+//!
+//! ```no_run
+//! use atsamd_hal::bus_clock::{BusClockMasks, EnabledBusClock};
+//!
+//! let clock = EnabledBusClock::<pac::TC3>::new(&mut peripherals.PM);
+//! // ...
+//! drop(clock);
+//!
+//! // Power audit
+//! let enabled = BusClockMasks::read(&peripherals.PM).count();
+//! ```
+
+use core::marker::PhantomData;
+
+use crate::pac;
+
+/// Controller of the bus clocks
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+pub type PowerManager = pac::PM;
+/// Controller of the bus clocks
+#[cfg(feature = "min-samd51g")]
+pub type PowerManager = pac::MCLK;
+
+/// Registers of the [`PowerManager`]
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+pub type BusClockRegisters = pac::pm::RegisterBlock;
+/// Registers of the [`PowerManager`]
+#[cfg(feature = "min-samd51g")]
+pub type BusClockRegisters = pac::mclk::RegisterBlock;
+
+/// A peripheral with bus clocks
+///
+/// The masks are shared by every peripheral, and may be modified from
+/// interrupt handlers through [`EnabledBusClock`] and the drivers releasing
+/// their peripheral. Implementations therefore modify them within a critical
+/// section, and so must any other code modifying them.
+pub trait BusClock {
+    /// Enable the bus clocks of the peripheral
+    fn enable_bus_clock(regs: &BusClockRegisters);
+
+    /// Disable the bus clocks of the peripheral
+    fn disable_bus_clock(regs: &BusClockRegisters);
+
+    /// Return whether the bus clocks of the peripheral are all enabled
+    fn is_bus_clock_enabled(regs: &BusClockRegisters) -> bool;
+}
+
+/// Disable the bus clocks of `P`, without the [`PowerManager`]
+///
+/// Used by the drivers releasing their peripheral, which don't own the
+/// [`PowerManager`].
+pub(crate) fn release<P: BusClock>() {
+    cortex_m::interrupt::free(|_| {
+        // SAFETY: The HAL only modifies the mask registers through `BusClock`,
+        // within critical sections, so this read-modify-write can't race with
+        // one through a `PowerManager` reference
+        P::disable_bus_clock(unsafe { &*PowerManager::ptr() })
+    });
+}
+
+/// Bus clocks of `P`, disabled when dropped
+///
+/// See the [module-level documentation](self) for more details.
+pub struct EnabledBusClock<P: BusClock> {
+    peripheral: PhantomData<P>,
+}
+
+impl<P: BusClock> EnabledBusClock<P> {
+    /// Enable the bus clocks of `P`
+    #[inline]
+    pub fn new(pm: &mut PowerManager) -> Self {
+        P::enable_bus_clock(pm);
+        Self {
+            peripheral: PhantomData,
+        }
+    }
+}
+
+impl<P: BusClock> Drop for EnabledBusClock<P> {
+    #[inline]
+    fn drop(&mut self) {
+        release::<P>();
+    }
+}
+
+/// Snapshot of the bus clock masks, one bit per enabled clock
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BusClockMasks {
+    /// AHB clocks
+    pub ahb: u32,
+    /// APBA clocks
+    pub apba: u32,
+    /// APBB clocks
+    pub apbb: u32,
+    /// APBC clocks
+    pub apbc: u32,
+    /// APBD clocks
+    #[cfg(feature = "min-samd51g")]
+    pub apbd: u32,
+}
+
+impl BusClockMasks {
+    /// Read the bus clock masks
+    #[inline]
+    pub fn read(pm: &PowerManager) -> Self {
+        Self {
+            ahb: pm.ahbmask.read().bits(),
+            apba: pm.apbamask.read().bits(),
+            apbb: pm.apbbmask.read().bits(),
+            apbc: pm.apbcmask.read().bits(),
+            #[cfg(feature = "min-samd51g")]
+            apbd: pm.apbdmask.read().bits(),
+        }
+    }
+
+    /// Number of enabled bus clocks
+    #[inline]
+    pub fn count(&self) -> u32 {
+        let count = self.ahb.count_ones()
+            + self.apba.count_ones()
+            + self.apbb.count_ones()
+            + self.apbc.count_ones();
+        #[cfg(feature = "min-samd51g")]
+        let count = count + self.apbd.count_ones();
+        count
+    }
+}
+
+macro_rules! bus_clock {
+    (
+        $(
+            $( #[$cfg:meta] )?
+            $Peripheral:ident: [ $( $mask:ident . $bit:ident ),+ ],
+        )+
+    ) => {
+        $(
+            $( #[$cfg] )?
+            impl BusClock for pac::$Peripheral {
+                #[inline]
+                fn enable_bus_clock(regs: &BusClockRegisters) {
+                    cortex_m::interrupt::free(|_| {
+                        $( regs.$mask.modify(|_, w| w.$bit().set_bit()); )+
+                    });
+                }
+
+                #[inline]
+                fn disable_bus_clock(regs: &BusClockRegisters) {
+                    cortex_m::interrupt::free(|_| {
+                        $( regs.$mask.modify(|_, w| w.$bit().clear_bit()); )+
+                    });
+                }
+
+                #[inline]
+                fn is_bus_clock_enabled(regs: &BusClockRegisters) -> bool {
+                    true $( && regs.$mask.read().$bit().bit_is_set() )+
+                }
+            }
+        )+
+    };
+}
+
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+bus_clock!(
+    GCLK: [apbamask.gclk_],
+    RTC: [apbamask.rtc_],
+    EIC: [apbamask.eic_],
+    WDT: [apbamask.wdt_],
+    DMAC: [ahbmask.dmac_, apbbmask.dmac_],
+    USB: [ahbmask.usb_, apbbmask.usb_],
+    EVSYS: [apbcmask.evsys_],
+    SERCOM0: [apbcmask.sercom0_],
+    SERCOM1: [apbcmask.sercom1_],
+    #[cfg(feature = "samd21")]
+    SERCOM2: [apbcmask.sercom2_],
+    #[cfg(feature = "samd21")]
+    SERCOM3: [apbcmask.sercom3_],
+    #[cfg(feature = "min-samd21g")]
+    SERCOM4: [apbcmask.sercom4_],
+    #[cfg(feature = "min-samd21g")]
+    SERCOM5: [apbcmask.sercom5_],
+    TCC0: [apbcmask.tcc0_],
+    #[cfg(feature = "samd11")]
+    TC1: [apbcmask.tc1_],
+    #[cfg(feature = "samd11")]
+    TC2: [apbcmask.tc2_],
+    #[cfg(feature = "samd21")]
+    TCC1: [apbcmask.tcc1_],
+    #[cfg(feature = "samd21")]
+    TCC2: [apbcmask.tcc2_],
+    #[cfg(feature = "samd21")]
+    TC3: [apbcmask.tc3_],
+    #[cfg(feature = "samd21")]
+    TC4: [apbcmask.tc4_],
+    #[cfg(feature = "samd21")]
+    TC5: [apbcmask.tc5_],
+    #[cfg(feature = "min-samd21j")]
+    TC6: [apbcmask.tc6_],
+    #[cfg(feature = "min-samd21j")]
+    TC7: [apbcmask.tc7_],
+    ADC: [apbcmask.adc_],
+    AC: [apbcmask.ac_],
+    DAC: [apbcmask.dac_],
+    #[cfg(feature = "samd21")]
+    I2S: [apbcmask.i2s_],
+);
+
+#[cfg(feature = "min-samd51g")]
+bus_clock!(
+    GCLK: [apbamask.gclk_],
+    RTC: [apbamask.rtc_],
+    EIC: [apbamask.eic_],
+    WDT: [apbamask.wdt_],
+    FREQM: [apbamask.freqm_],
+    SERCOM0: [apbamask.sercom0_],
+    SERCOM1: [apbamask.sercom1_],
+    TC0: [apbamask.tc0_],
+    TC1: [apbamask.tc1_],
+    DMAC: [ahbmask.dmac_],
+    SDHC0: [ahbmask.sdhc0_],
+    #[cfg(feature = "min-samd51n")]
+    SDHC1: [ahbmask.sdhc1_],
+    #[cfg(any(feature = "same51", feature = "same54"))]
+    CAN0: [ahbmask.can0_],
+    #[cfg(all(any(feature = "same51", feature = "same54"), feature = "min-samd51j"))]
+    CAN1: [ahbmask.can1_],
+    #[cfg(any(feature = "same53", feature = "same54"))]
+    GMAC: [ahbmask.gmac_, apbcmask.gmac_],
+    USB: [ahbmask.usb_, apbbmask.usb_],
+    EVSYS: [apbbmask.evsys_],
+    SERCOM2: [apbbmask.sercom2_],
+    SERCOM3: [apbbmask.sercom3_],
+    TCC0: [apbbmask.tcc0_],
+    TCC1: [apbbmask.tcc1_],
+    TC2: [apbbmask.tc2_],
+    TC3: [apbbmask.tc3_],
+    TCC2: [apbcmask.tcc2_],
+    #[cfg(feature = "min-samd51j")]
+    TCC3: [apbcmask.tcc3_],
+    #[cfg(feature = "min-samd51j")]
+    TC4: [apbcmask.tc4_],
+    #[cfg(feature = "min-samd51j")]
+    TC5: [apbcmask.tc5_],
+    PDEC: [apbcmask.pdec_],
+    AC: [apbcmask.ac_],
+    AES: [apbcmask.aes_],
+    TRNG: [apbcmask.trng_],
+    ICM: [ahbmask.icm_, apbcmask.icm_],
+    QSPI: [ahbmask.qspi_, ahbmask.qspi_2x_, apbcmask.qspi_],
+    CCL: [apbcmask.ccl_],
+    SERCOM4: [apbdmask.sercom4_],
+    SERCOM5: [apbdmask.sercom5_],
+    #[cfg(feature = "min-samd51n")]
+    SERCOM6: [apbdmask.sercom6_],
+    #[cfg(feature = "min-samd51n")]
+    SERCOM7: [apbdmask.sercom7_],
+    #[cfg(feature = "min-samd51j")]
+    TCC4: [apbdmask.tcc4_],
+    #[cfg(feature = "min-samd51n")]
+    TC6: [apbdmask.tc6_],
+    #[cfg(feature = "min-samd51n")]
+    TC7: [apbdmask.tc7_],
+    ADC0: [apbdmask.adc0_],
+    ADC1: [apbdmask.adc1_],
+    DAC: [apbdmask.dac_],
+    #[cfg(feature = "min-samd51j")]
+    I2S: [apbdmask.i2s_],
+    PCC: [apbdmask.pcc_],
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count() {
+        let masks = BusClockMasks {
+            ahb: 0b1011,
+            apba: 0b1,
+            apbc: 0xff00,
+            ..Default::default()
+        };
+        assert_eq!(masks.count(), 12);
+    }
+}
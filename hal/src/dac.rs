@@ -0,0 +1,302 @@
+//! # DAC - Digital-to-Analog Converter
+//!
+//! The DAC converts digital values to an output voltage:
+//!
+//! * On SAMD11 and SAMD21 chips, the single 10-bit channel outputs on VOUT
+//!   (PA02)
+//! * On SAMD51 and SAME5x chips, the two 12-bit channels output on VOUT0
+//!   (PA02) and VOUT1 (PA05)
+//!
+//! The driver does not configure the DAC pins, which must be set to alternate
+//! function B beforehand.
+//!
+//! Values can be written one at a time with [`Dac::write`]. When the `dma`
+//! feature is enabled, [`DacStream`] plays a whole buffer of samples through
+//! the DMAC instead, optionally in a loop. The samples are paced by the DMA
+//! trigger of a timer, usually the overflow of a TC or TCC running at the
+//! sample rate, so waveforms and simple audio are output without any CPU
+//! involvement.
+//!
+//! ```no_run
+//! use atsamd_hal::dac::{Channel, Dac, DacStream, Reference};
+//! use atsamd_hal::dmac::TriggerSource;
+//!
+//! let gclk0 = clocks.gclk0();
+//! let dac_clock = clocks.dac(&gclk0).unwrap();
+//! let dac = Dac::new(peripherals.DAC, &mut peripherals.PM, &dac_clock, Reference::AVCC, &[Channel::Ch0]);
+//!
+//! // A TC3 overflowing at 32 kHz paces the samples
+//! let mut timer = TimerCounter::tc3_(&tc3_clock, peripherals.TC3, &mut peripherals.PM);
+//! timer.start(32.khz());
+//!
+//! static mut SINE: [u16; 64] = [0; 64];
+//! let stream = DacStream::new(&dac, Channel::Ch0, channel, unsafe { &mut SINE }, TriggerSource::TC3_OVF, true);
+//! ```
+
+use crate::bus_clock::BusClock;
+use crate::clock::DacClock;
+
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+use crate::pac::{DAC, PM};
+
+#[cfg(feature = "min-samd51g")]
+use crate::pac::{dac::dacctrl::CCTRL_A, DAC, MCLK};
+
+#[cfg(feature = "dma")]
+use crate::dmac::{
+    channel::{AnyChannel, Busy, Channel as DmaChannel, Ready},
+    transfer::BufferPair,
+    Buffer, ChId, Error, Transfer, TriggerAction, TriggerSource,
+};
+
+/// Peripheral clock controller of the DAC
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+pub type PowerManager = PM;
+/// Peripheral clock controller of the DAC
+#[cfg(feature = "min-samd51g")]
+pub type PowerManager = MCLK;
+
+/// Voltage reference of the DAC
+pub use crate::pac::dac::ctrlb::REFSEL_A as Reference;
+
+/// Largest value accepted by the DAC
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+pub const MAX_VALUE: u16 = (1 << 10) - 1;
+/// Largest value accepted by the DAC
+#[cfg(feature = "min-samd51g")]
+pub const MAX_VALUE: u16 = (1 << 12) - 1;
+
+/// DAC output channel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    /// Channel 0, the only channel of SAMD11 and SAMD21 chips
+    Ch0,
+    /// Channel 1
+    #[cfg(feature = "min-samd51g")]
+    Ch1,
+}
+
+impl Channel {
+    #[cfg_attr(not(feature = "min-samd51g"), allow(dead_code))]
+    #[inline]
+    fn index(self) -> usize {
+        match self {
+            Channel::Ch0 => 0,
+            #[cfg(feature = "min-samd51g")]
+            Channel::Ch1 => 1,
+        }
+    }
+}
+
+/// Digital-to-Analog Converter
+pub struct Dac {
+    dac: DAC,
+}
+
+impl Dac {
+    /// Enable the DAC clock, and enable the DAC with the given reference and
+    /// output `channels`
+    pub fn new(
+        dac: DAC,
+        pm: &mut PowerManager,
+        clock: &DacClock,
+        reference: Reference,
+        channels: &[Channel],
+    ) -> Self {
+        DAC::enable_bus_clock(pm);
+
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        {
+            let _ = clock;
+            dac.ctrla.write(|w| w.swrst().set_bit());
+            while dac.status.read().syncbusy().bit_is_set() {}
+            dac.ctrlb.write(|w| {
+                w.refsel().variant(reference);
+                w.eoen().bit(channels.contains(&Channel::Ch0))
+            });
+            dac.ctrla.write(|w| w.enable().set_bit());
+            while dac.status.read().syncbusy().bit_is_set() {}
+        }
+
+        #[cfg(feature = "min-samd51g")]
+        {
+            dac.ctrla.write(|w| w.swrst().set_bit());
+            while dac.syncbusy.read().swrst().bit_is_set() {}
+            dac.ctrlb.write(|w| w.refsel().variant(reference));
+
+            // The current control depends on the frequency of the DAC clock
+            let cctrl = match clock.freq().0 {
+                0..=1_200_000 => CCTRL_A::CC100K,
+                1_200_001..=6_000_000 => CCTRL_A::CC1M,
+                _ => CCTRL_A::CC12M,
+            };
+            for channel in channels {
+                dac.dacctrl[channel.index()].write(|w| {
+                    w.cctrl().variant(cctrl);
+                    w.enable().set_bit()
+                });
+            }
+            dac.ctrla.write(|w| w.enable().set_bit());
+            while dac.syncbusy.read().enable().bit_is_set() {}
+            for channel in channels {
+                match channel {
+                    Channel::Ch0 => while dac.status.read().ready0().bit_is_clear() {},
+                    Channel::Ch1 => while dac.status.read().ready1().bit_is_clear() {},
+                }
+            }
+        }
+
+        Self { dac }
+    }
+
+    /// Disable the DAC and its clock and return the register block
+    pub fn free(self, pm: &mut PowerManager) -> DAC {
+        self.dac.ctrla.write(|w| w.swrst().set_bit());
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        {
+            while self.dac.status.read().syncbusy().bit_is_set() {}
+        }
+        #[cfg(feature = "min-samd51g")]
+        {
+            while self.dac.syncbusy.read().swrst().bit_is_set() {}
+        }
+        DAC::disable_bus_clock(pm);
+        self.dac
+    }
+
+    /// Convert `value` on `channel`
+    ///
+    /// Values above [`MAX_VALUE`] are truncated.
+    pub fn write(&mut self, channel: Channel, value: u16) {
+        let value = value & MAX_VALUE;
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        {
+            let _ = channel;
+            while self.dac.status.read().syncbusy().bit_is_set() {}
+            // SAFETY: The value fits in the data register
+            self.dac.data.write(|w| unsafe { w.data().bits(value) });
+        }
+        #[cfg(feature = "min-samd51g")]
+        {
+            let syncbusy = || {
+                let syncbusy = self.dac.syncbusy.read();
+                match channel {
+                    Channel::Ch0 => syncbusy.data0().bit_is_set(),
+                    Channel::Ch1 => syncbusy.data1().bit_is_set(),
+                }
+            };
+            while syncbusy() {}
+            // SAFETY: The value fits in the data register
+            self.dac.data[channel.index()].write(|w| unsafe { w.data().bits(value) });
+        }
+    }
+}
+
+//=============================================================================
+// DMA playback
+//=============================================================================
+
+/// DMA trigger action for DAC transfers
+#[cfg(all(feature = "dma", any(feature = "samd11", feature = "samd21")))]
+const TRIGGER_ACTION: TriggerAction = TriggerAction::BEAT;
+#[cfg(all(feature = "dma", feature = "min-samd51g"))]
+const TRIGGER_ACTION: TriggerAction = TriggerAction::BURST;
+
+/// Data register of a DAC channel, used as the destination of DMA transfers
+#[cfg(feature = "dma")]
+#[doc(hidden)]
+pub struct DacData(Channel);
+
+#[cfg(feature = "dma")]
+unsafe impl Buffer for DacData {
+    type Beat = u16;
+
+    #[inline]
+    fn dma_ptr(&mut self) -> *mut u16 {
+        // SAFETY: The data register is only written by the DMAC
+        let dac = unsafe { &*DAC::ptr() };
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        return dac.data.as_ptr();
+        #[cfg(feature = "min-samd51g")]
+        return dac.data[self.0.index()].as_ptr();
+    }
+
+    #[inline]
+    fn incrementing(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn buffer_len(&self) -> usize {
+        1
+    }
+}
+
+/// Playback of a buffer of samples on a DAC channel through the DMAC
+///
+/// Every DMA trigger, usually the overflow of a timer, writes the next
+/// sample into the data register of the channel. Samples must not exceed
+/// [`MAX_VALUE`].
+#[cfg(feature = "dma")]
+pub struct DacStream<'a, Id: ChId, B: Buffer<Beat = u16> + 'static> {
+    transfer: Transfer<DmaChannel<Id, Busy>, BufferPair<B, DacData>>,
+    circular: bool,
+    _dac: &'a Dac,
+}
+
+#[cfg(feature = "dma")]
+impl<'a, Id: ChId, B: Buffer<Beat = u16> + 'static> DacStream<'a, Id, B> {
+    /// Start playing `samples` on `channel`, one sample per `trigger`
+    ///
+    /// If `circular` is `true`, the playback loops until it is stopped.
+    /// Otherwise, the stream is complete once every sample has been played,
+    /// and can be restarted with another buffer with [`DacStream::play`].
+    pub fn new<Ch>(
+        _dac: &'a Dac,
+        channel: Channel,
+        dma_channel: Ch,
+        samples: B,
+        trigger: TriggerSource,
+        circular: bool,
+    ) -> Self
+    where
+        Ch: AnyChannel<Status = Ready, Id = Id>,
+    {
+        let transfer = Transfer::new(dma_channel, samples, DacData(channel), circular)
+            .expect("the data register accepts any buffer length")
+            .begin(trigger, TRIGGER_ACTION);
+        Self {
+            transfer,
+            circular,
+            _dac,
+        }
+    }
+
+    /// Check whether every sample has been played
+    ///
+    /// A circular stream is never complete.
+    #[inline]
+    pub fn is_complete(&mut self) -> bool {
+        self.transfer.complete()
+    }
+
+    /// Wait for the current buffer to be played, then start playing
+    /// `samples` and return the previous buffer
+    ///
+    /// Returns [`Error::InvalidState`] if the stream is circular, since its
+    /// current buffer never completes, and [`Error::LengthMismatch`] if the
+    /// lengths of the buffers don't match.
+    pub fn play(&mut self, samples: B) -> Result<B, Error> {
+        if self.circular {
+            return Err(Error::InvalidState);
+        }
+        while !self.transfer.complete() {}
+        self.transfer.recycle_destination(samples)
+    }
+
+    /// Stop playing and return the DMA channel and the samples
+    pub fn stop(self) -> (DmaChannel<Id, Ready>, B) {
+        let (channel, samples, _) = self.transfer.stop();
+        (channel, samples)
+    }
+}
@@ -1,4 +1,22 @@
 //! Delays
+//!
+//! [`Delay`] counts the cycles of the core clock with the SysTick, which it
+//! owns. [`TimerDelay`] counts the cycles of a free-running 32-bit timer
+//! counter instead: clocked by its own generator, it stays accurate when the
+//! core clock changes, and it hands out [`SharedDelay`]s, copies of which can
+//! wait concurrently, such as in several tasks.
+//!
+//! ```no_run
+//! use atsamd_hal::delay::TimerDelay;
+//! use atsamd_hal::timer::TimerCounter4_5;
+//!
+//! let gclk1 = clocks.gclk1();
+//! let tc45 = clocks.tc4_tc5(&gclk1).unwrap();
+//! let timer = TimerCounter4_5::tc4_tc5(&tc45, peripherals.TC4, peripherals.TC5, &mut pm);
+//! let delay = cortex_m::singleton!(: TimerDelay<TC4, TC5> = TimerDelay::new(timer)).unwrap();
+//! let mut shared = delay.shared();
+//! shared.delay_us(250u32);
+//! ```
 
 use cortex_m::peripheral::syst::SystClkSource;
 use cortex_m::peripheral::SYST;
@@ -6,6 +24,8 @@ use cortex_m::peripheral::SYST;
 use crate::clock::GenericClockController;
 use crate::ehal::blocking::delay::{DelayMs, DelayUs};
 use crate::time::Hertz;
+#[cfg(any(feature = "samd21", feature = "min-samd51g"))]
+use crate::timer::{Count32, TimerCounter32};
 
 /// System timer (SysTick) as a delay provider
 pub struct Delay {
@@ -28,6 +48,36 @@ impl Delay {
     pub fn free(self) -> SYST {
         self.syst
     }
+
+    /// Waits for `total_rvr` core clock cycles
+    fn delay_cycles(&mut self, mut total_rvr: u64) {
+        // The SysTick Reload Value register supports values between 1 and 0x00FFFFFF.
+        const MAX_RVR: u32 = 0x00FF_FFFF;
+
+        while total_rvr != 0 {
+            let current_rvr = total_rvr.min(MAX_RVR as u64) as u32;
+
+            self.syst.set_reload(current_rvr);
+            self.syst.clear_current();
+            self.syst.enable_counter();
+
+            // Update the tracking variable while we are waiting...
+            total_rvr -= current_rvr as u64;
+
+            while !self.syst.has_wrapped() {}
+
+            self.syst.disable_counter();
+        }
+    }
+}
+
+/// Follows the changes of the CPU clock, keeping the delays accurate
+#[cfg(feature = "min-samd51g")]
+impl crate::clock::CpuFrequencyListener for Delay {
+    #[inline]
+    fn cpu_frequency_changed(&mut self, freq: Hertz) {
+        self.sysclock = freq;
+    }
 }
 
 impl DelayMs<u32> for Delay {
@@ -50,29 +100,7 @@ impl DelayMs<u8> for Delay {
 
 impl DelayUs<u32> for Delay {
     fn delay_us(&mut self, us: u32) {
-        // The SysTick Reload Value register supports values between 1 and 0x00FFFFFF.
-        const MAX_RVR: u32 = 0x00FF_FFFF;
-
-        let mut total_rvr = us * (self.sysclock.0 / 1_000_000);
-
-        while total_rvr != 0 {
-            let current_rvr = if total_rvr <= MAX_RVR {
-                total_rvr
-            } else {
-                MAX_RVR
-            };
-
-            self.syst.set_reload(current_rvr);
-            self.syst.clear_current();
-            self.syst.enable_counter();
-
-            // Update the tracking variable while we are waiting...
-            total_rvr -= current_rvr;
-
-            while !self.syst.has_wrapped() {}
-
-            self.syst.disable_counter();
-        }
+        self.delay_cycles(us as u64 * (self.sysclock.0 / 1_000_000) as u64);
     }
 }
 
@@ -87,3 +115,175 @@ impl DelayUs<u8> for Delay {
         self.delay_us(us as u32)
     }
 }
+
+#[cfg(feature = "ehal1")]
+impl embedded_hal_1::delay::DelayNs for Delay {
+    fn delay_ns(&mut self, ns: u32) {
+        let cycles = ns as u64 * self.sysclock.0 as u64;
+        // Round up, a delay is never shorter than requested
+        self.delay_cycles((cycles + 999_999_999) / 1_000_000_000);
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        DelayUs::delay_us(self, us);
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.delay_cycles(ms as u64 * (self.sysclock.0 / 1_000) as u64);
+    }
+}
+
+/// Free-running 32-bit timer counter as a delay provider
+///
+/// Clock the timer counter from a generator other than gclk0, so that the
+/// delays don't depend on the core clock. The delays are rounded up to the
+/// next timer cycle, plus one, as they start anywhere within a cycle.
+#[cfg(any(feature = "samd21", feature = "min-samd51g"))]
+pub struct TimerDelay<M, S> {
+    timer: TimerCounter32<M, S>,
+}
+
+// SAFETY: The shared delays only read the counter. COUNT is synchronized
+// continuously, with READREQ.RCONT, so each read is a plain register read.
+#[cfg(feature = "samd21")]
+unsafe impl<M, S> Sync for TimerDelay<M, S> {}
+
+// SAFETY: The shared delays only read the counter. Each read issues the
+// READSYNC command and waits for it within a critical section, so that
+// concurrent reads don't interleave.
+#[cfg(feature = "min-samd51g")]
+unsafe impl<M, S> Sync for TimerDelay<M, S> {}
+
+#[cfg(any(feature = "samd21", feature = "min-samd51g"))]
+impl<M: Count32, S> TimerDelay<M, S> {
+    /// Start `timer` counting freely, as a delay provider
+    pub fn new(mut timer: TimerCounter32<M, S>) -> Self {
+        timer.start_free_running();
+        Self { timer }
+    }
+
+    /// Release the timer counter, still running
+    pub fn free(self) -> TimerCounter32<M, S> {
+        self.timer
+    }
+
+    /// Frequency of the timer counter
+    #[inline]
+    pub fn freq(&self) -> Hertz {
+        self.timer.freq()
+    }
+
+    /// Create a delay provider borrowing the timer counter, which can be
+    /// copied and used concurrently
+    #[inline]
+    pub fn shared(&self) -> SharedDelay<'_, M, S> {
+        SharedDelay { delay: self }
+    }
+
+    /// Waits for `ticks` timer cycles
+    fn delay_ticks(&self, ticks: u64) {
+        // The current cycle is partly elapsed
+        let mut remaining = ticks + 1;
+        let mut start = self.timer.count();
+        while remaining != 0 {
+            // Stay away from the wrap-around of the counter
+            let chunk = remaining.min(1 << 31) as u32;
+            while self.timer.count().wrapping_sub(start) < chunk {}
+            start = start.wrapping_add(chunk);
+            remaining -= chunk as u64;
+        }
+    }
+
+    /// Waits for `time` units of `1 / scale` seconds
+    #[inline]
+    fn delay_scaled(&self, time: u32, scale: u64) {
+        let cycles = time as u64 * self.timer.freq().0 as u64;
+        // Round up, a delay is never shorter than requested
+        self.delay_ticks((cycles + scale - 1) / scale);
+    }
+}
+
+/// Copyable delay provider, borrowing a [`TimerDelay`]
+#[cfg(any(feature = "samd21", feature = "min-samd51g"))]
+pub struct SharedDelay<'a, M, S> {
+    delay: &'a TimerDelay<M, S>,
+}
+
+#[cfg(any(feature = "samd21", feature = "min-samd51g"))]
+impl<M, S> Clone for SharedDelay<'_, M, S> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+#[cfg(any(feature = "samd21", feature = "min-samd51g"))]
+impl<M, S> Copy for SharedDelay<'_, M, S> {}
+
+macro_rules! timer_delay {
+    ( $( $Delay:ty => |$self:ident| $delay:expr; )+ ) => {
+        $(
+            #[cfg(any(feature = "samd21", feature = "min-samd51g"))]
+            impl<M: Count32, S> DelayMs<u32> for $Delay {
+                fn delay_ms(&mut $self, ms: u32) {
+                    $delay.delay_scaled(ms, 1_000);
+                }
+            }
+
+            #[cfg(any(feature = "samd21", feature = "min-samd51g"))]
+            impl<M: Count32, S> DelayMs<u16> for $Delay {
+                fn delay_ms(&mut self, ms: u16) {
+                    self.delay_ms(ms as u32);
+                }
+            }
+
+            #[cfg(any(feature = "samd21", feature = "min-samd51g"))]
+            impl<M: Count32, S> DelayMs<u8> for $Delay {
+                fn delay_ms(&mut self, ms: u8) {
+                    self.delay_ms(ms as u32);
+                }
+            }
+
+            #[cfg(any(feature = "samd21", feature = "min-samd51g"))]
+            impl<M: Count32, S> DelayUs<u32> for $Delay {
+                fn delay_us(&mut $self, us: u32) {
+                    $delay.delay_scaled(us, 1_000_000);
+                }
+            }
+
+            #[cfg(any(feature = "samd21", feature = "min-samd51g"))]
+            impl<M: Count32, S> DelayUs<u16> for $Delay {
+                fn delay_us(&mut self, us: u16) {
+                    self.delay_us(us as u32)
+                }
+            }
+
+            #[cfg(any(feature = "samd21", feature = "min-samd51g"))]
+            impl<M: Count32, S> DelayUs<u8> for $Delay {
+                fn delay_us(&mut self, us: u8) {
+                    self.delay_us(us as u32)
+                }
+            }
+
+            #[cfg(all(feature = "ehal1", any(feature = "samd21", feature = "min-samd51g")))]
+            impl<M: Count32, S> embedded_hal_1::delay::DelayNs for $Delay {
+                fn delay_ns(&mut $self, ns: u32) {
+                    $delay.delay_scaled(ns, 1_000_000_000);
+                }
+
+                fn delay_us(&mut self, us: u32) {
+                    DelayUs::delay_us(self, us);
+                }
+
+                fn delay_ms(&mut self, ms: u32) {
+                    DelayMs::delay_ms(self, ms);
+                }
+            }
+        )+
+    };
+}
+
+timer_delay!(
+    TimerDelay<M, S> => |self| self;
+    SharedDelay<'_, M, S> => |self| self.delay;
+);
@@ -0,0 +1,174 @@
+//! `async` support for DMA channels
+//!
+//! The `async` drivers that use the DMAC, such as the
+//! [`SpiFuture`](crate::sercom::v2::spi::SpiFuture), wait for the completion of
+//! their transfers on the channel interrupts. For this to work, the DMAC
+//! interrupt handler must call [`on_interrupt`]. For the SAMD11 & SAMD21
+//! chips, there is a single `DMAC` interrupt. SAMD51 & SAME5x chips provide
+//! one interrupt for each of the channels 0 to 3, `DMAC_0` to `DMAC_3`, and a
+//! shared one for every other channel, `DMAC_4`. [`on_interrupt`] must be
+//! called from all of them, as it services every channel.
+//!
+//! ```
+//! use atsamd_hal::dmac::async_api;
+//! use atsamd_hal::pac::interrupt;
+//!
+//! #[interrupt]
+//! fn DMAC() {
+//!     async_api::on_interrupt();
+//! }
+//! ```
+
+use core::future::poll_fn;
+use core::sync::atomic;
+use core::task::Poll;
+
+use crate::async_hal::InterruptWaker;
+use crate::pac::DMAC;
+
+use super::{
+    channel::{Channel, InterruptFlags, Ready},
+    dma_controller::{ChId, TriggerAction, TriggerSource},
+    transfer::{Buffer, BufferPair, Transfer},
+    Error, Result, NUM_CHANNELS,
+};
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NEW_WAKER: InterruptWaker = InterruptWaker::new();
+
+static WAKERS: [InterruptWaker; NUM_CHANNELS] = [NEW_WAKER; NUM_CHANNELS];
+
+/// Service the DMAC interrupts
+///
+/// The interrupts of every channel with pending interrupt flags are disabled,
+/// and the task awaiting on the channel, if any, is woken. The interrupt flags
+/// themselves are left untouched, to be checked and cleared by the awaiting
+/// task.
+///
+/// This function must be called from the DMAC interrupt handler(s) for the
+/// `async` drivers to make any progress. See the
+/// [module-level documentation](self) for more details.
+#[inline]
+pub fn on_interrupt() {
+    // SAFETY: Only the interrupt enable registers of the channels with pending
+    // interrupts are written to, with an atomic write-one-to-clear register.
+    let dmac = unsafe { &*DMAC::ptr() };
+    let pending = dmac.intstatus.read().bits();
+
+    for (id, waker) in WAKERS.iter().enumerate() {
+        if pending & (1 << id) == 0 {
+            continue;
+        }
+
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        {
+            // Restore the CHID register before returning, as the preempted
+            // context may be in the middle of accessing a channel
+            let old_id = dmac.chid.read().id().bits();
+            dmac.chid.write(|w| unsafe { w.id().bits(id as u8) });
+            let fired = dmac.chintflag.read().bits() & dmac.chintenset.read().bits();
+            dmac.chintenclr.write(|w| unsafe { w.bits(fired) });
+            dmac.chid.write(|w| unsafe { w.id().bits(old_id) });
+        }
+
+        #[cfg(feature = "min-samd51g")]
+        {
+            let channel = &dmac.channel[id];
+            let fired = channel.chintflag.read().bits() & channel.chintenset.read().bits();
+            channel.chintenclr.write(|w| unsafe { w.bits(fired) });
+        }
+
+        waker.wake();
+    }
+}
+
+impl<Id: ChId> Channel<Id, Ready> {
+    /// Start a one-shot transfer without consuming the channel
+    ///
+    /// The transfer is stopped when the returned [`TransferGuard`] is dropped.
+    ///
+    /// # Safety
+    ///
+    /// The `source` and `destination` buffers must remain valid until the
+    /// [`TransferGuard`] is dropped, and the [`TransferGuard`] must *never* be
+    /// leaked. Their lengths must be compatible, as for
+    /// [`Transfer::new_unchecked`].
+    #[inline]
+    pub(crate) unsafe fn start_transfer<S, D>(
+        &mut self,
+        mut source: S,
+        mut destination: D,
+        trig_src: TriggerSource,
+        trig_act: TriggerAction,
+    ) -> TransferGuard<'_, Id>
+    where
+        S: Buffer,
+        D: Buffer<Beat = S::Beat>,
+    {
+        Transfer::<Self, BufferPair<S, D>>::fill_descriptor(&mut source, &mut destination, false);
+
+        // Discard the flags left over by any previous transfer
+        self.check_and_clear_interrupts(transfer_flags());
+
+        // Memory barrier to prevent the compiler/CPU from re-ordering read/write
+        // operations beyond this fence.
+        // (see https://docs.rust-embedded.org/embedonomicon/dma.html#compiler-misoptimizations)
+        atomic::fence(atomic::Ordering::Release); //  ▲
+        self._start_private(trig_src, trig_act);
+
+        TransferGuard { channel: self }
+    }
+}
+
+/// Interrupt flags signaling the end of a one-shot transfer
+#[inline]
+fn transfer_flags() -> InterruptFlags {
+    InterruptFlags::new().with_tcmpl(true).with_terr(true)
+}
+
+/// Transfer started by [`Channel::start_transfer`]
+///
+/// The transfer is stopped when the [`TransferGuard`] is dropped, whether or
+/// not it has completed.
+pub(crate) struct TransferGuard<'a, Id: ChId> {
+    channel: &'a mut Channel<Id, Ready>,
+}
+
+impl<Id: ChId> TransferGuard<'_, Id> {
+    /// Wait for the transfer to complete
+    ///
+    /// Returns [`Error::TransferError`] if the DMAC signaled a bus error.
+    pub(crate) async fn wait(&mut self) -> Result<()> {
+        let flags = transfer_flags();
+        let result = poll_fn(|cx| {
+            WAKERS[Id::USIZE].register(cx.waker());
+            self.channel.enable_interrupts(flags);
+            let set = self.channel.check_and_clear_interrupts(flags);
+            if set.terr() {
+                self.channel.disable_interrupts(flags);
+                Poll::Ready(Err(Error::TransferError))
+            } else if set.tcmpl() {
+                self.channel.disable_interrupts(flags);
+                Poll::Ready(Ok(()))
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        atomic::fence(atomic::Ordering::Acquire); // ▼
+        result
+    }
+}
+
+impl<Id: ChId> Drop for TransferGuard<'_, Id> {
+    #[inline]
+    fn drop(&mut self) {
+        self.channel._stop_private();
+        self.channel.disable_interrupts(transfer_flags());
+
+        // Memory barrier to prevent the compiler/CPU from re-ordering read/write
+        // operations beyond this fence.
+        atomic::fence(atomic::Ordering::Acquire); // ▼
+    }
+}
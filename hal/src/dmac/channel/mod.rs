@@ -201,6 +201,42 @@ impl<Id: ChId, S: Status> Channel<Id, S> {
     fn _trigger_private(&mut self) {
         self.regs.swtrigctrl.write_bit(true);
     }
+
+    /// Configure the trigger source and action, then enable the channel
+    #[inline]
+    pub(super) fn _start_private(&mut self, trig_src: TriggerSource, trig_act: TriggerAction) {
+        // Configure the trigger source and trigger action
+        // SAFETY: This is actually safe because we are writing the correct enum value
+        // (imported from the PAC) into the register
+        unsafe {
+            #[cfg(any(feature = "samd11", feature = "samd21"))]
+            self.regs.chctrlb.modify(|_, w| {
+                w.trigsrc().bits(trig_src as u8);
+                w.trigact().bits(trig_act as u8)
+            });
+
+            #[cfg(feature = "min-samd51g")]
+            self.regs.chctrla.modify(|_, w| {
+                w.trigsrc().bits(trig_src as u8);
+                w.trigact().bits(trig_act as u8)
+            });
+        }
+
+        // Start channel
+        self.regs.chctrla.modify(|_, w| w.enable().set_bit());
+
+        // If trigger source is DISABLE, manually trigger transfer
+        if trig_src == TriggerSource::DISABLE {
+            self._trigger_private();
+        }
+    }
+
+    /// Disable the channel and wait until any ongoing transfer is stopped
+    #[inline]
+    pub(super) fn _stop_private(&mut self) {
+        self.regs.chctrla.modify(|_, w| w.enable().clear_bit());
+        while self.regs.chctrla.read().enable().bit_is_set() {}
+    }
 }
 
 /// These methods may only be used on a `Ready` DMA channel
@@ -249,30 +285,7 @@ impl<Id: ChId> Channel<Id, Ready> {
         trig_src: TriggerSource,
         trig_act: TriggerAction,
     ) -> Channel<Id, Busy> {
-        // Configure the trigger source and trigger action
-        // SAFETY: This is actually safe because we are writing the correct enum value
-        // (imported from the PAC) into the register
-        unsafe {
-            #[cfg(any(feature = "samd11", feature = "samd21"))]
-            self.regs.chctrlb.modify(|_, w| {
-                w.trigsrc().bits(trig_src as u8);
-                w.trigact().bits(trig_act as u8)
-            });
-
-            #[cfg(feature = "min-samd51g")]
-            self.regs.chctrla.modify(|_, w| {
-                w.trigsrc().bits(trig_src as u8);
-                w.trigact().bits(trig_act as u8)
-            });
-        }
-
-        // Start channel
-        self.regs.chctrla.modify(|_, w| w.enable().set_bit());
-
-        // If trigger source is DISABLE, manually trigger transfer
-        if trig_src == TriggerSource::DISABLE {
-            self._trigger_private();
-        }
+        self._start_private(trig_src, trig_act);
 
         Channel {
             regs: self.regs,
@@ -295,6 +308,13 @@ impl<Id: ChId> Channel<Id, Busy> {
         !self.regs.chctrla.read().enable().bit_is_set()
     }
 
+    /// Returns whether the channel is transferring a beat, a burst or a block,
+    /// depending on its trigger action
+    #[inline]
+    pub(crate) fn is_busy(&mut self) -> bool {
+        self.regs.chstatus.read().busy().bit_is_set()
+    }
+
     /// Stop transfer on channel whether or not the transfer has completed
     ///
     /// # Return
@@ -303,8 +323,7 @@ impl<Id: ChId> Channel<Id, Busy> {
     /// [`Transfer`](super::transfer::Transfer)
     #[inline]
     pub(crate) fn free(mut self) -> Channel<Id, Ready> {
-        self.regs.chctrla.modify(|_, w| w.enable().clear_bit());
-        while !self.xfer_complete() {}
+        self._stop_private();
         Channel {
             regs: self.regs,
             _status: PhantomData,
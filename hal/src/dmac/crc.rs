@@ -0,0 +1,192 @@
+//! # CRC engine
+//!
+//! The DMAC contains a CRC unit that can compute CRC-16 (CCITT) or CRC-32
+//! (IEEE 802.3) checksums. The unit can be fed by one of two sources:
+//!
+//! * The I/O interface: the CPU writes data to the `CRCDATAIN` register. This
+//!   is what [`Crc::compute`] uses to checksum a memory buffer, such as a
+//!   flash page.
+//! * A DMA channel: every beat moved by the channel is also fed into the CRC
+//!   unit. Use [`Crc::monitor_channel`] before starting a
+//!   [`Transfer`](super::Transfer) on that channel, then read the
+//!   [`checksum`](Crc::checksum) once the transfer has completed.
+//!
+//! The checksums follow these parameters, where `init` is the seed passed
+//! to [`Crc::compute`] or [`Crc::monitor_channel`]:
+//!
+//! | Variant    | Width | Poly         | refin | refout | xorout        |
+//! |------------|-------|--------------|-------|--------|---------------|
+//! | `CRC16`    | 16    | `0x1021`     | no    | no     | `0x0000`      |
+//! | `CRC32`    | 32    | `0x04C11DB7` | yes   | yes    | `0xFFFF_FFFF` |
+//!
+//! Seeded with `0xFFFF`, `CRC16` is CRC-16/CCITT-FALSE, with a check value of
+//! `0x29B1` for the ASCII string `"123456789"`. Seeded with `0xFFFF_FFFF`,
+//! `CRC32` is the standard CRC-32, with a check value of `0xCBF4_3926`. The
+//! CRC-32 checksum register reads back bit-reversed and complemented, so no
+//! post-processing is needed.
+//!
+//! A [`Crc`] is obtained by borrowing the [`DmaController`](super::DmaController) through
+//! [`DmaController::crc`](super::DmaController::crc). Only one source can feed the CRC unit at a time.
+//!
+//! ```no_run
+//! let mut dmac = DmaController::init(peripherals.DMAC, &mut peripherals.PM);
+//!
+//! let page: &[u8] = unsafe { core::slice::from_raw_parts(0x3F00 as *const u8, 64) };
+//! let checksum = dmac.crc().compute(CrcPolynomial::CRC32, 0xFFFF_FFFF, page);
+//! ```
+
+use super::{
+    channel::{Channel, Status},
+    dma_controller::ChId,
+};
+use crate::pac::DMAC;
+
+pub use crate::pac::dmac::crcctrl::CRCPOLY_A as CrcPolynomial;
+
+/// Offset added to a channel ID to select it as the CRC input source
+const CRCSRC_CHANNEL_OFFSET: u8 = 0x20;
+
+/// Handle to the DMAC CRC engine
+///
+/// Created by [`DmaController::crc`](super::DmaController::crc).
+pub struct Crc<'a> {
+    dmac: &'a mut DMAC,
+}
+
+impl<'a> Crc<'a> {
+    #[inline]
+    pub(super) fn new(dmac: &'a mut DMAC) -> Self {
+        Self { dmac }
+    }
+
+    /// Compute the CRC of a memory buffer through the I/O interface.
+    ///
+    /// The checksum register is seeded with `initial` before the first byte
+    /// is written, see the [module documentation](self) for the resulting
+    /// CRC parameters. The CRC engine is released once the checksum has been
+    /// read back, so it can be reused immediately afterwards.
+    #[inline]
+    pub fn compute(&mut self, polynomial: CrcPolynomial, initial: u32, data: &[u8]) -> u32 {
+        self.configure(polynomial, initial, |w| {
+            w.crcbeatsize().byte();
+            w.crcsrc().io()
+        });
+
+        for &byte in data {
+            // SAFETY: Any 32-bit value is valid for CRCDATAIN. Only the lower
+            // byte is used by the CRC engine given the BYTE beat size.
+            self.dmac
+                .crcdatain
+                .write(|w| unsafe { w.crcdatain().bits(byte as u32) });
+        }
+
+        let checksum = self.checksum();
+        self.disable();
+        checksum
+    }
+
+    /// Feed every beat moved by `channel` into the CRC engine.
+    ///
+    /// This must be called before the transfer is started, since data that
+    /// was moved beforehand is not accounted for. Once the transfer has
+    /// completed, read the result with [`checksum`](Self::checksum), then
+    /// release the engine with [`disable`](Self::disable).
+    #[inline]
+    pub fn monitor_channel<Id: ChId, S: Status>(
+        &mut self,
+        _channel: &Channel<Id, S>,
+        polynomial: CrcPolynomial,
+        initial: u32,
+    ) {
+        self.configure(polynomial, initial, |w| {
+            // SAFETY: CRCSRC values 0x20 and up select DMA channel N - 0x20 as
+            // the CRC source. The channel ID is guaranteed to exist by ChId.
+            unsafe { w.crcsrc().bits(CRCSRC_CHANNEL_OFFSET + Id::U8) }
+        });
+    }
+
+    /// Read the current value of the checksum register
+    #[inline]
+    pub fn checksum(&self) -> u32 {
+        self.dmac.crcchksum.read().crcchksum().bits()
+    }
+
+    /// Returns `true` if the checksum register currently reads zero.
+    ///
+    /// When the checksum of a buffer is appended to that buffer, computing
+    /// the CRC over the whole lot yields zero if the data is intact.
+    #[inline]
+    pub fn is_zero(&self) -> bool {
+        self.dmac.crcstatus.read().crczero().bit_is_set()
+    }
+
+    /// Disconnect the CRC engine from its input source
+    #[inline]
+    pub fn disable(&mut self) {
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        self.dmac.ctrl.modify(|_, w| w.crcenable().clear_bit());
+
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        self.dmac.crcctrl.modify(|_, w| w.crcsrc().noact());
+
+        #[cfg(feature = "min-samd51g")]
+        self.dmac.crcctrl.modify(|_, w| w.crcsrc().disable());
+
+        // The CRCBUSY flag must be cleared by software when the I/O interface
+        // was used as the source
+        self.dmac.crcstatus.write(|w| w.crcbusy().set_bit());
+    }
+
+    #[inline]
+    fn configure<F>(&mut self, polynomial: CrcPolynomial, initial: u32, source: F)
+    where
+        F: FnOnce(&mut crate::pac::dmac::crcctrl::W) -> &mut crate::pac::dmac::crcctrl::W,
+    {
+        self.disable();
+
+        // SAFETY: Any 32-bit value is a valid checksum seed
+        self.dmac
+            .crcchksum
+            .write(|w| unsafe { w.crcchksum().bits(initial) });
+
+        self.dmac.crcctrl.write(|w| {
+            w.crcpoly().variant(polynomial);
+            source(w)
+        });
+
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        self.dmac.ctrl.modify(|_, w| w.crcenable().set_bit());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    /// Bitwise CRC with the parameters documented for the CRC engine
+    fn reference(width: u32, poly: u32, init: u32, reflect: bool, xorout: u32, data: &[u8]) -> u32 {
+        let top = 1 << (width - 1);
+        let mask = u32::MAX >> (32 - width);
+        let mut crc = init;
+        for &byte in data {
+            let byte = if reflect { byte.reverse_bits() } else { byte };
+            crc ^= (byte as u32) << (width - 8);
+            for _ in 0..8 {
+                let feedback = if crc & top != 0 { poly } else { 0 };
+                crc = (crc << 1 ^ feedback) & mask;
+            }
+        }
+        if reflect {
+            crc = crc.reverse_bits() >> (32 - width);
+        }
+        crc ^ xorout
+    }
+
+    #[test]
+    fn check_values() {
+        let data = b"123456789";
+        assert_eq!(reference(16, 0x1021, 0xFFFF, false, 0, data), 0x29B1);
+        assert_eq!(
+            reference(32, 0x04C1_1DB7, 0xFFFF_FFFF, true, 0xFFFF_FFFF, data),
+            0xCBF4_3926
+        );
+    }
+}
@@ -39,8 +39,11 @@ pub use crate::pac::dmac::channel::{
 
 use super::{
     channel::{new_chan, Channel, Uninitialized},
+    crc::Crc,
     DESCRIPTOR_SECTION, WRITEBACK,
 };
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+use crate::bus_clock::BusClock;
 use crate::pac::{DMAC, PM};
 
 /// Trait representing a DMA channel ID
@@ -137,12 +140,9 @@ impl DmaController {
     #[inline]
     pub fn init(mut dmac: DMAC, _pm: &mut PM) -> Self {
         // ----- Initialize clocking ----- //
+        // Enable clocking
         #[cfg(any(feature = "samd11", feature = "samd21"))]
-        {
-            // Enable clocking
-            _pm.ahbmask.modify(|_, w| w.dmac_().set_bit());
-            _pm.apbbmask.modify(|_, w| w.dmac_().set_bit());
-        }
+        DMAC::enable_bus_clock(_pm);
 
         Self::swreset(&mut dmac);
 
@@ -221,6 +221,14 @@ impl DmaController {
         }
     }
 
+    /// Borrow the DMAC CRC engine.
+    ///
+    /// See the [`crc`](super::crc) module documentation for more details.
+    #[inline]
+    pub fn crc(&mut self) -> Crc<'_> {
+        Crc::new(&mut self.dmac)
+    }
+
     /// Release the DMAC and return the register block.
     ///
     /// **Note**: The [`Channels`] struct is consumed by this method. This means
@@ -233,12 +241,9 @@ impl DmaController {
 
         Self::swreset(&mut self.dmac);
 
+        // Disable the DMAC clocking
         #[cfg(any(feature = "samd11", feature = "samd21"))]
-        {
-            // Disable the DMAC clocking
-            _pm.apbbmask.modify(|_, w| w.dmac_().clear_bit());
-            _pm.ahbmask.modify(|_, w| w.dmac_().clear_bit());
-        }
+        DMAC::disable_bus_clock(_pm);
 
         // Release the DMAC
         self.dmac
@@ -0,0 +1,389 @@
+//! # Linked-list (scatter-gather) DMA transfers
+//!
+//! A [`Transfer`](super::Transfer) executes a single block transfer, described
+//! by a single descriptor. A [`LinkedTransfer`] chains multiple block
+//! transfers, one per buffer segment, into a single DMA transaction. Each
+//! descriptor points to the next one, so the DMAC sequences the whole
+//! transaction by itself, without any CPU intervention between segments.
+//!
+//! Two shapes of transfers are supported:
+//!
+//! * Gather ([`LinkedTransfer::new_gather`]): multiple source segments are
+//!   sent to a single destination, normally a peripheral. For instance, to
+//!   stream the lines of a framebuffer to an SPI display.
+//!
+//! * Scatter ([`LinkedTransfer::new_scatter`]): a single source, normally a
+//!   peripheral, is received into multiple destination segments.
+//!
+//! The first descriptor of the chain lives in the descriptor section managed
+//! by this module, just like for a [`Transfer`](super::Transfer). The others
+//! must be provided as a `&'static mut` slice of [`LinkedDescriptor`]s, with
+//! at least one descriptor per segment after the first. Segments must also be
+//! `'static`, so that neither the descriptors nor the buffers can be reclaimed
+//! while the DMAC is still using them.
+//!
+//! ```
+//! use atsamd_hal::dmac::{LinkedDescriptor, LinkedTransfer, TriggerAction};
+//! use atsamd_hal::sercom::v2::{Sercom, Sercom4};
+//!
+//! static mut LINES: [[u8; 240]; 4] = [[0; 240]; 4];
+//! static mut SEGMENTS: Option<[&'static mut [u8]; 4]> = None;
+//! static mut DESCRIPTORS: [LinkedDescriptor; 3] = [LinkedDescriptor::new(); 3];
+//!
+//! // Assume chan0 is a `Ready` channel, and spi a `Tx` `Spi` using SERCOM4
+//! let segments = unsafe {
+//!     let [a, b, c, d] = &mut LINES;
+//!     SEGMENTS.insert([a, b, c, d])
+//! };
+//! let xfer = LinkedTransfer::new_gather(chan0, segments, spi, unsafe { &mut DESCRIPTORS }, false)
+//!     .unwrap()
+//!     .begin(Sercom4::DMA_TX_TRIGGER, TriggerAction::BURST);
+//! let (chan0, segments, spi, descriptors) = xfer.wait();
+//! ```
+
+use core::ptr::{addr_of, addr_of_mut, null_mut};
+use core::sync::atomic;
+
+use super::{
+    channel::{AnyChannel, Busy, Channel, ChannelId, InterruptFlags, Ready},
+    dma_controller::{ChId, TriggerAction, TriggerSource},
+    transfer::{block_descriptor, write_first_descriptor, Buffer},
+    DmacDescriptor, Error, Result, DEFAULT_DESCRIPTOR, DESCRIPTOR_SECTION, WRITEBACK,
+};
+
+//==============================================================================
+// LinkedDescriptor
+//==============================================================================
+
+/// Storage for one descriptor of a [`LinkedTransfer`]
+///
+/// Descriptors are read by the DMAC while the transfer is running, so they
+/// must be allocated in a `static`. See the [module-level
+/// documentation](self).
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+pub struct LinkedDescriptor(DmacDescriptor);
+
+impl LinkedDescriptor {
+    /// Create an empty descriptor
+    #[inline]
+    pub const fn new() -> Self {
+        Self(DEFAULT_DESCRIPTOR)
+    }
+}
+
+impl Default for LinkedDescriptor {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//==============================================================================
+// LinkedTransfer
+//==============================================================================
+
+/// DMA transfer made of a chain of block transfers, owning the resources until
+/// the transfer is done and [`LinkedTransfer::wait`] is called.
+///
+/// `B` is the type of the buffer segments, and `P` the type of the single
+/// buffer, normally a peripheral, on the other end of every block transfer.
+pub struct LinkedTransfer<Chan, B, P>
+where
+    Chan: AnyChannel,
+    B: Buffer + 'static,
+    P: Buffer<Beat = B::Beat>,
+{
+    chan: Chan,
+    segments: &'static mut [B],
+    peripheral: P,
+    descriptors: &'static mut [LinkedDescriptor],
+    complete: bool,
+}
+
+impl<C, B, P> LinkedTransfer<C, B, P>
+where
+    C: AnyChannel<Status = Ready>,
+    B: Buffer + 'static,
+    P: Buffer<Beat = B::Beat> + 'static,
+{
+    /// Build a gather transfer, sending every segment of `sources`, in order,
+    /// to `destination`
+    ///
+    /// If `circular` is `true`, the transaction restarts with the first
+    /// segment after the last one, until it is stopped.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidDescriptorCount`] if `sources` is empty or if
+    /// there are fewer `descriptors` than segments after the first,
+    /// [`Error::LengthMismatch`] if a segment and `destination` both have a
+    /// length > 1, but are not of equal length, and [`Error::SegmentTooLong`]
+    /// if a segment or `destination` is longer than 65535 beats.
+    #[inline]
+    pub fn new_gather(
+        chan: C,
+        sources: &'static mut [B],
+        destination: P,
+        descriptors: &'static mut [LinkedDescriptor],
+        circular: bool,
+    ) -> Result<Self> {
+        Self::new(chan, sources, destination, descriptors, circular, true)
+    }
+
+    /// Build a scatter transfer, receiving from `source` into every segment of
+    /// `destinations`, in order
+    ///
+    /// If `circular` is `true`, the transaction restarts with the first
+    /// segment after the last one, until it is stopped.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidDescriptorCount`] if `destinations` is empty or
+    /// if there are fewer `descriptors` than segments after the first,
+    /// [`Error::LengthMismatch`] if a segment and `source` both have a
+    /// length > 1, but are not of equal length, and [`Error::SegmentTooLong`]
+    /// if a segment or `source` is longer than 65535 beats.
+    #[inline]
+    pub fn new_scatter(
+        chan: C,
+        source: P,
+        destinations: &'static mut [B],
+        descriptors: &'static mut [LinkedDescriptor],
+        circular: bool,
+    ) -> Result<Self> {
+        Self::new(chan, destinations, source, descriptors, circular, false)
+    }
+
+    fn new(
+        chan: C,
+        segments: &'static mut [B],
+        mut peripheral: P,
+        descriptors: &'static mut [LinkedDescriptor],
+        circular: bool,
+        gather: bool,
+    ) -> Result<Self> {
+        let count = segments.len();
+        if count == 0 || descriptors.len() < count - 1 {
+            return Err(Error::InvalidDescriptorCount);
+        }
+
+        let peripheral_len = peripheral.buffer_len();
+        let mismatch = segments.iter().any(|segment| {
+            let segment_len = segment.buffer_len();
+            segment_len > 1 && peripheral_len > 1 && segment_len != peripheral_len
+        });
+        if mismatch {
+            return Err(Error::LengthMismatch);
+        }
+        let max_len = u16::MAX as usize;
+        if peripheral_len > max_len || segments.iter().any(|s| s.buffer_len() > max_len) {
+            return Err(Error::SegmentTooLong);
+        }
+
+        let id = <C as AnyChannel>::Id::USIZE;
+        let linked = descriptors.as_mut_ptr();
+
+        for (index, segment) in segments.iter_mut().enumerate() {
+            // Descriptor `index - 1` of the user-provided slice describes
+            // segment `index`. The last segment either ends the transaction
+            // or loops back to the first one.
+            let descaddr = if index + 1 < count {
+                // SAFETY: There are at least `count - 1` linked descriptors
+                unsafe { addr_of_mut!((*linked.add(index)).0) }
+            } else if circular {
+                // SAFETY: We are only taking the address of the descriptor
+                unsafe { addr_of_mut!(DESCRIPTOR_SECTION[id]) }
+            } else {
+                null_mut()
+            };
+
+            let descriptor = if gather {
+                block_descriptor(segment, &mut peripheral, descaddr)
+            } else {
+                block_descriptor(&mut peripheral, segment, descaddr)
+            };
+
+            if index == 0 {
+                // SAFETY: The channel is `Ready`, hence disabled
+                unsafe { write_first_descriptor(id, descriptor) };
+            } else {
+                descriptors[index - 1] = LinkedDescriptor(descriptor);
+            }
+        }
+
+        Ok(Self {
+            chan,
+            segments,
+            peripheral,
+            descriptors,
+            complete: false,
+        })
+    }
+
+    /// Raise the transfer complete interrupt at the end of every block
+    /// transfer, instead of only at the end of the transaction
+    ///
+    /// Combined with a circular transfer, this signals every segment as soon
+    /// as it has been filled or emptied, see
+    /// [`LinkedTransfer::block_transfer_interrupt`].
+    #[inline]
+    pub fn with_block_interrupts(self) -> Self {
+        // Block action: raise an interrupt
+        const INT: u8 = 1;
+        let id = <C as AnyChannel>::Id::USIZE;
+        // SAFETY: The channel is `Ready`, so the DMAC isn't reading its first
+        // descriptor
+        unsafe {
+            let first = &mut *addr_of_mut!(DESCRIPTOR_SECTION[id]);
+            first.btctrl = first.btctrl.with_blockact(INT);
+        }
+        let linked = self.segments.len() - 1;
+        for descriptor in self.descriptors[..linked].iter_mut() {
+            descriptor.0.btctrl = descriptor.0.btctrl.with_blockact(INT);
+        }
+        self
+    }
+
+    /// Begin DMA transfer. If [TriggerSource::DISABLE](TriggerSource::DISABLE)
+    /// is used, a software trigger will be issued to the DMA channel to
+    /// launch the transfer.
+    #[inline]
+    pub fn begin(
+        self,
+        trig_src: TriggerSource,
+        trig_act: TriggerAction,
+    ) -> LinkedTransfer<Channel<ChannelId<C>, Busy>, B, P> {
+        // Memory barrier to prevent the compiler/CPU from re-ordering read/write
+        // operations beyond this fence.
+        // (see https://docs.rust-embedded.org/embedonomicon/dma.html#compiler-misoptimizations)
+        atomic::fence(atomic::Ordering::Release); //  ▲
+        let chan = self.chan.into().start(trig_src, trig_act);
+
+        LinkedTransfer {
+            chan,
+            segments: self.segments,
+            peripheral: self.peripheral,
+            descriptors: self.descriptors,
+            complete: false,
+        }
+    }
+}
+
+impl<Id, B, P> LinkedTransfer<Channel<Id, Busy>, B, P>
+where
+    Id: ChId,
+    B: Buffer + 'static,
+    P: Buffer<Beat = B::Beat>,
+{
+    /// Issue a software trigger request to the corresponding channel.
+    /// Note that is not guaranteed that the trigger request will register,
+    /// if a trigger request is already pending for the channel.
+    #[inline]
+    pub fn software_trigger(&mut self) {
+        self.chan.software_trigger();
+    }
+
+    /// Checks and clears the block transfer complete interrupt flag
+    ///
+    /// Unless [`LinkedTransfer::with_block_interrupts`] was used, the flag is
+    /// only set at the end of the whole chain.
+    #[inline]
+    pub fn block_transfer_interrupt(&mut self) -> bool {
+        self.chan
+            .check_and_clear_interrupts(InterruptFlags::new().with_tcmpl(true))
+            .tcmpl()
+    }
+
+    /// Segments of the transfer, whose contents the DMAC may be accessing
+    #[inline]
+    pub(crate) fn segments(&self) -> &[B] {
+        self.segments
+    }
+
+    /// Index of the segment the DMAC is transferring, for a circular
+    /// transfer
+    ///
+    /// This is found from the DMAC writeback section, which the DMAC updates
+    /// once the channel is no longer busy, such as while a beat-triggered
+    /// transfer waits for its next trigger. The channel is waited for, so this
+    /// must not be used while a block-triggered transfer is running.
+    #[inline]
+    pub(crate) fn active_segment(&mut self) -> usize {
+        while self.chan.is_busy() {}
+        let id = Id::USIZE;
+        // SAFETY: The writeback section is only read, with volatile reads, as
+        // it is written by the DMAC.
+        let (next, btcnt) = unsafe {
+            (
+                addr_of!(WRITEBACK[id].descaddr).read_volatile(),
+                addr_of!(WRITEBACK[id].btcnt).read_volatile(),
+            )
+        };
+        // Segment `index` links to descriptor `index` of the slice, and the
+        // last segment to the first descriptor
+        let last = self.segments.len() - 1;
+        let index = self.descriptors[..last]
+            .iter()
+            .position(|descriptor| core::ptr::eq(&descriptor.0, next))
+            .unwrap_or(last);
+        // The block transfer has ended, but the next descriptor has not been
+        // fetched yet
+        if btcnt == 0 {
+            (index + 1) % self.segments.len()
+        } else {
+            index
+        }
+    }
+
+    /// Check if the whole chain of block transfers has completed
+    ///
+    /// A circular transfer never completes.
+    #[inline]
+    pub fn complete(&mut self) -> bool {
+        if !self.complete {
+            self.complete = self.chan.xfer_complete();
+        }
+        self.complete
+    }
+
+    /// Wait for the DMA transfer to complete and release all owned
+    /// resources
+    ///
+    /// # Blocking: This method may block
+    #[inline]
+    #[allow(clippy::type_complexity)]
+    pub fn wait(
+        mut self,
+    ) -> (
+        Channel<Id, Ready>,
+        &'static mut [B],
+        P,
+        &'static mut [LinkedDescriptor],
+    ) {
+        while !self.complete() {}
+        self.stop()
+    }
+
+    /// Non-blocking; Immediately stop the DMA transfer and release all owned
+    /// resources
+    #[inline]
+    #[allow(clippy::type_complexity)]
+    pub fn stop(
+        self,
+    ) -> (
+        Channel<Id, Ready>,
+        &'static mut [B],
+        P,
+        &'static mut [LinkedDescriptor],
+    ) {
+        let chan = self.chan.free();
+
+        // Memory barrier to prevent the compiler/CPU from re-ordering read/write
+        // operations beyond this fence.
+        // (see https://docs.rust-embedded.org/embedonomicon/dma.html#compiler-misoptimizations)
+        atomic::fence(atomic::Ordering::Acquire); // ▼
+
+        (chan, self.segments, self.peripheral, self.descriptors)
+    }
+}
@@ -10,9 +10,9 @@
 //! functions, including memory-to-memory,
 //! memory-to-peripheral, peripheral-to-memory,
 //! and peripheral-to-peripheral transfers.
-//! One-shot and circular transfers are supported. More complex
-//! transfer configurations, including multi-buffer
-//! (linked-list descriptor) transfers, are not currently supported.
+//! One-shot and circular transfers are supported, as well as multi-buffer
+//! (linked-list descriptor) transfers through the [`linked`] module.
+//! The DMAC CRC engine is exposed through the [`crc`] module.
 //!
 //! Transfers are supported for `i8`, `u8`, `i16`, `u16`, `i32`, `u32` and `f32`
 //! beat sizes.
@@ -89,6 +89,11 @@
 //! the NVIC. You will be responsible for clearing the interrupt flags in the
 //! ISR.
 //!
+//! The exception are the `async` drivers built on top of the DMAC, available
+//! with the `async` feature. These rely on the interrupts of the channels they
+//! use, and require [`async_api::on_interrupt`] to be called from the DMAC
+//! interrupt handler(s).
+//!
 //! # About static lifetimes
 //!
 //! The safe API this driver offers requires all buffers (source and
@@ -254,11 +259,14 @@
 use modular_bitfield::prelude::*;
 
 pub use channel::*;
+pub use crc::*;
 pub use dma_controller::*;
+pub use linked::*;
 pub use transfer::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 /// Runtime errors that may occur when dealing with DMA transfers.
+#[non_exhaustive]
 pub enum Error {
     /// Supplied buffers both have lengths > 1 beat, but not equal to each other
     ///
@@ -271,6 +279,17 @@ pub enum Error {
 
     /// Operation is not valid in the current state of the object.
     InvalidState,
+
+    /// The DMAC signaled a bus error while executing the transfer.
+    TransferError,
+
+    /// A [`LinkedTransfer`] was given no segments, or fewer descriptors than
+    /// segments after the first one.
+    InvalidDescriptorCount,
+
+    /// A segment of a [`LinkedTransfer`] is longer than 65535 beats, the
+    /// maximum length of a block transfer.
+    SegmentTooLong,
 }
 
 /// Result for DMAC operations
@@ -382,8 +401,9 @@ pub const DEFAULT_DESCRIPTOR: DmacDescriptor = DmacDescriptor {
     descaddr: 0 as *mut _,
 };
 
-// Writeback section. This static variable should never be written to in an
-// interrupt or thread context.
+// Writeback section. This static variable is written by the DMAC, and only
+// seeded by `transfer::write_first_descriptor` while the channel is disabled.
+// It should never be written to anywhere else.
 #[doc(hidden)]
 static mut WRITEBACK: [DmacDescriptor; NUM_CHANNELS] = [DEFAULT_DESCRIPTOR; NUM_CHANNELS];
 // Descriptor section. This static variable should never be written to in an
@@ -392,5 +412,11 @@ static mut WRITEBACK: [DmacDescriptor; NUM_CHANNELS] = [DEFAULT_DESCRIPTOR; NUM_
 static mut DESCRIPTOR_SECTION: [DmacDescriptor; NUM_CHANNELS] = [DEFAULT_DESCRIPTOR; NUM_CHANNELS];
 
 pub mod channel;
+pub mod crc;
 pub mod dma_controller;
+pub mod linked;
 pub mod transfer;
+
+#[cfg(feature = "async")]
+#[clippy::msrv = "1.75"]
+pub mod async_api;
@@ -86,10 +86,13 @@
 use super::{
     channel::{AnyChannel, Busy, CallbackStatus, Channel, ChannelId, InterruptFlags, Ready},
     dma_controller::{ChId, TriggerAction, TriggerSource},
-    BlockTransferControl, DmacDescriptor, Error, Result, DESCRIPTOR_SECTION,
+    BlockTransferControl, DmacDescriptor, Error, Result, DESCRIPTOR_SECTION, WRITEBACK,
 };
 use crate::typelevel::{Is, Sealed};
-use core::{ptr::null_mut, sync::atomic};
+use core::{
+    ptr::{addr_of, null_mut},
+    sync::atomic,
+};
 use modular_bitfield::prelude::*;
 
 //==============================================================================
@@ -216,6 +219,72 @@ unsafe impl<T: Beat> Buffer for &mut T {
     }
 }
 
+/// Maximum length of a single block transfer, in beats
+#[cfg_attr(
+    not(any(feature = "async", feature = "min-samd51g")),
+    allow(dead_code)
+)]
+pub(crate) const MAX_TRANSFER_LENGTH: usize = u16::MAX as usize;
+
+/// Raw [`Buffer`] over memory not borrowed through a Rust reference, used by
+/// the drivers building their own transfers
+///
+/// It either points to a slice, or to a single beat used as the fixed source
+/// or destination of every beat of the transfer.
+pub(crate) struct RawBuffer<T: Beat> {
+    ptr: *mut T,
+    len: usize,
+    incrementing: bool,
+}
+
+impl<T: Beat> RawBuffer<T> {
+    /// Buffer pointing to a slice of `len` beats starting at `ptr`
+    #[cfg_attr(all(feature = "samd11", not(feature = "async")), allow(dead_code))]
+    #[inline]
+    pub(crate) fn slice(ptr: *mut T, len: usize) -> Self {
+        Self {
+            ptr,
+            len,
+            incrementing: len > 1,
+        }
+    }
+
+    /// Buffer pointing to a single beat at `ptr`, repeated `len` times
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    #[inline]
+    pub(crate) fn fixed(ptr: *mut T, len: usize) -> Self {
+        Self {
+            ptr,
+            len,
+            incrementing: false,
+        }
+    }
+}
+
+unsafe impl<T: Beat> Buffer for RawBuffer<T> {
+    type Beat = T;
+
+    #[inline]
+    fn dma_ptr(&mut self) -> *mut T {
+        if self.incrementing {
+            // SAFETY: The pointer stays one past the end of the slice
+            unsafe { self.ptr.add(self.len) }
+        } else {
+            self.ptr
+        }
+    }
+
+    #[inline]
+    fn incrementing(&self) -> bool {
+        self.incrementing
+    }
+
+    #[inline]
+    fn buffer_len(&self) -> usize {
+        self.len
+    }
+}
+
 //==============================================================================
 // BufferPair
 //==============================================================================
@@ -353,14 +422,14 @@ where
     }
 
     #[inline]
-    unsafe fn fill_descriptor(source: &mut S, destination: &mut D, circular: bool) {
+    pub(super) unsafe fn fill_descriptor(source: &mut S, destination: &mut D, circular: bool) {
         let id = <C as AnyChannel>::Id::USIZE;
 
         // Enable support for circular transfers. If circular_xfer is true,
         // we set the address of the "next" block descriptor to actually
         // be the same address as the current block descriptor.
         // Otherwise we set it to NULL, which terminates the transaction.
-        // TODO: Enable support for linked lists (?)
+        // Linked lists of descriptors are handled by `LinkedTransfer`.
         let descaddr = if circular {
             // SAFETY This is safe as we are only reading the descriptor's address,
             // and not actually writing any data to it. We also assume the descriptor
@@ -370,48 +439,76 @@ where
             null_mut()
         };
 
-        let src_ptr = source.dma_ptr();
-        let src_inc = source.incrementing();
-        let src_len = source.buffer_len();
-
-        let dst_ptr = destination.dma_ptr();
-        let dst_inc = destination.incrementing();
-        let dst_len = destination.buffer_len();
-
-        let length = core::cmp::max(src_len, dst_len);
-
-        // Channel::xfer_complete() tests the channel enable bit, which indicates
-        // that a transfer has completed iff the blockact field in btctrl is not
-        // set to SUSPEND.  We implicitly leave blockact set to NOACT here; if
-        // that changes Channel::xfer_complete() may need to be modified.
-        let btctrl = BlockTransferControl::new()
-            .with_srcinc(src_inc)
-            .with_dstinc(dst_inc)
-            .with_beatsize(S::Beat::BEATSIZE)
-            .with_valid(true);
-
-        let xfer_descriptor = DmacDescriptor {
-            // Next descriptor address:  0x0 terminates the transaction (no linked list),
-            // any other address points to the next block descriptor
-            descaddr,
-            // Source address: address of the last beat transfer source in block
-            srcaddr: src_ptr as *mut _,
-            // Destination address: address of the last beat transfer destination in block
-            dstaddr: dst_ptr as *mut _,
-            // Block transfer count: number of beats in block transfer
-            btcnt: length as u16,
-            // Block transfer control: Datasheet  section 19.8.2.1 p.329
-            btctrl,
-        };
+        write_first_descriptor(id, block_descriptor(source, destination, descaddr));
+    }
+}
 
-        // SAFETY this is safe as long as we ONLY write to the descriptor
-        // belonging to OUR channel. We assume this is the only place
-        // in the entire library that this section or the array
-        // will be written to.
-        DESCRIPTOR_SECTION[id] = xfer_descriptor;
+/// Build the descriptor of a single block transfer from `source` to
+/// `destination`, linked to the `descaddr` descriptor
+#[inline]
+pub(super) fn block_descriptor<S, D>(
+    source: &mut S,
+    destination: &mut D,
+    descaddr: *mut DmacDescriptor,
+) -> DmacDescriptor
+where
+    S: Buffer,
+    D: Buffer<Beat = S::Beat>,
+{
+    let src_ptr = source.dma_ptr();
+    let src_inc = source.incrementing();
+    let src_len = source.buffer_len();
+
+    let dst_ptr = destination.dma_ptr();
+    let dst_inc = destination.incrementing();
+    let dst_len = destination.buffer_len();
+
+    let length = core::cmp::max(src_len, dst_len);
+
+    // Channel::xfer_complete() tests the channel enable bit, which indicates
+    // that a transfer has completed iff the blockact field in btctrl is not
+    // set to SUSPEND.  We implicitly leave blockact set to NOACT here; if
+    // that changes Channel::xfer_complete() may need to be modified.
+    let btctrl = BlockTransferControl::new()
+        .with_srcinc(src_inc)
+        .with_dstinc(dst_inc)
+        .with_beatsize(S::Beat::BEATSIZE)
+        .with_valid(true);
+
+    DmacDescriptor {
+        // Next descriptor address:  0x0 terminates the transaction (no linked list),
+        // any other address points to the next block descriptor
+        descaddr,
+        // Source address: address of the last beat transfer source in block
+        srcaddr: src_ptr as *mut _,
+        // Destination address: address of the last beat transfer destination in block
+        dstaddr: dst_ptr as *mut _,
+        // Block transfer count: number of beats in block transfer
+        btcnt: length as u16,
+        // Block transfer control: Datasheet  section 19.8.2.1 p.329
+        btctrl,
     }
 }
 
+/// Write the first descriptor of the transfer of channel `id`
+///
+/// # Safety
+///
+/// The channel must be disabled.
+#[inline]
+pub(super) unsafe fn write_first_descriptor(id: usize, descriptor: DmacDescriptor) {
+    // SAFETY this is safe as long as we ONLY write to the descriptor
+    // belonging to OUR channel. We assume this is the only place
+    // in the entire library that this section or the array
+    // will be written to.
+    DESCRIPTOR_SECTION[id] = descriptor;
+
+    // Seed the writeback section with the same descriptor. The DMAC only
+    // updates it when the channel stops being serviced, so this keeps
+    // `Transfer::remaining_beats` meaningful until then.
+    WRITEBACK[id] = descriptor;
+}
+
 impl<C, S, D> Transfer<C, BufferPair<S, D>>
 where
     S: Buffer,
@@ -570,6 +667,22 @@ where
         self.complete
     }
 
+    /// Number of beats left in the current block transfer
+    ///
+    /// This is read from the DMAC writeback section, which the DMAC updates
+    /// every time the channel stops being serviced, e.g. while a beat-triggered
+    /// transfer waits for its next trigger. For a circular transfer, the
+    /// difference between the buffer length and the remaining beats is the
+    /// index of the next beat to be transferred.
+    #[inline]
+    pub fn remaining_beats(&self) -> usize {
+        let id = <C as AnyChannel>::Id::USIZE;
+        // SAFETY: The writeback section is only read, with a volatile read, as
+        // it is written by the DMAC.
+        let btcnt = unsafe { addr_of!(WRITEBACK[id].btcnt).read_volatile() };
+        btcnt as usize
+    }
+
     /// Checks and clears the block transfer complete interrupt flag
     #[inline]
     pub fn block_transfer_interrupt(&mut self) -> bool {
@@ -606,6 +719,7 @@ where
         let old_buffers = core::mem::replace(&mut self.buffers, new_buffers);
 
         self.chan.as_mut().restart();
+        self.complete = false;
 
         Ok((old_buffers.source, old_buffers.destination))
     }
@@ -631,6 +745,7 @@ where
         let old_destination = core::mem::replace(&mut self.buffers.destination, destination);
 
         self.chan.as_mut().restart();
+        self.complete = false;
 
         Ok(old_destination)
     }
@@ -656,6 +771,7 @@ where
         let old_source = core::mem::replace(&mut self.buffers.source, source);
 
         self.chan.as_mut().restart();
+        self.complete = false;
 
         Ok(old_source)
     }
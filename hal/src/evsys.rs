@@ -0,0 +1,829 @@
+//! # EVSYS - Event System
+//!
+//! The event system routes events from a peripheral, the generator, to one
+//! or more other peripherals, the users, without any CPU involvement. For
+//! example, the overflow of the RTC can start an ADC conversion, or an
+//! external interrupt can capture the counter of a TC.
+//!
+//! Events travel through channels. [`Evsys::allocate`] reserves a free
+//! channel and connects its [`Generator`], then [`Channel::connect`] routes
+//! the channel to any number of [`User`]s. Channels borrow the [`Evsys`], and
+//! are returned to it with [`Evsys::release`].
+//!
+//! Channels use one of three paths:
+//!
+//! * [`Path::Asynchronous`] events are routed combinatorially and can wake
+//!   the chip from standby, but filtering and edge detection are not
+//!   available
+//! * [`Path::Synchronous`] and [`Path::Resynchronized`] events are detected
+//!   on an edge of the generator output, and require the generic clock of the
+//!   channel (`Evsys0Clock` to `Evsys11Clock`) to be configured. On SAMD51
+//!   and SAME5x chips, only channels 0 to 11 support these paths.
+//!
+//! The event system only routes events: the generator must still be
+//! configured to output events, and the users to act on them, usually in the
+//! EVCTRL register of each peripheral.
+//!
+//! ```no_run
+//! use atsamd_hal::evsys::{Evsys, Generator, Path, User};
+//!
+//! let evsys = Evsys::new(peripherals.EVSYS, &mut peripherals.PM);
+//!
+//! // Start an ADC conversion on every RTC overflow
+//! let mut channel = evsys.allocate(Generator::RtcOverflow, Path::Asynchronous).unwrap();
+//! channel.connect(User::AdcStart);
+//! ```
+
+use core::cell::Cell;
+
+use crate::bus_clock::BusClock;
+
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+use crate::pac::{EVSYS, PM};
+
+#[cfg(feature = "min-samd51g")]
+use crate::pac::{EVSYS, MCLK};
+
+/// Peripheral clock controller of the EVSYS
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+pub type PowerManager = PM;
+/// Peripheral clock controller of the EVSYS
+#[cfg(feature = "min-samd51g")]
+pub type PowerManager = MCLK;
+
+/// Number of event channels
+#[cfg(feature = "samd11")]
+pub const NUM_CHANNELS: usize = 6;
+/// Number of event channels
+#[cfg(feature = "samd21")]
+pub const NUM_CHANNELS: usize = 12;
+/// Number of event channels
+#[cfg(feature = "min-samd51g")]
+pub const NUM_CHANNELS: usize = 32;
+
+/// Number of event channels supporting the synchronous and resynchronized
+/// paths
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+const NUM_SYNC_CHANNELS: usize = NUM_CHANNELS;
+#[cfg(feature = "min-samd51g")]
+const NUM_SYNC_CHANNELS: usize = 12;
+
+/// Number of user multiplexers
+#[cfg(feature = "samd11")]
+const NUM_USERS: u8 = 20;
+#[cfg(feature = "samd21")]
+const NUM_USERS: u8 = 29;
+#[cfg(feature = "min-samd51g")]
+const NUM_USERS: u8 = 67;
+
+/// EVSYS errors
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Every channel supporting the requested path is already allocated
+    NoChannelAvailable,
+}
+
+/// Edge of the generator output producing events
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    /// Rising edge
+    Rising,
+    /// Falling edge
+    Falling,
+    /// Both edges
+    Both,
+}
+
+/// Path of the events through a channel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Path {
+    /// Events are detected on `Edge`, synchronously with the generic clock
+    /// of the channel
+    Synchronous(Edge),
+    /// Events are detected on `Edge`, then resynchronized with the generic
+    /// clock of the channel
+    Resynchronized(Edge),
+    /// Events are routed without any synchronization
+    Asynchronous,
+}
+
+impl Path {
+    #[inline]
+    fn is_asynchronous(self) -> bool {
+        self == Path::Asynchronous
+    }
+}
+
+// The generator and user numbers come from the EVSYS chapter of the
+// datasheet of each chip family. Peripherals missing from smaller packages
+// keep their number, but never generate or use any event.
+
+/// Event generators
+#[cfg(feature = "samd11")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+#[allow(missing_docs)]
+pub enum Generator {
+    RtcCompare0 = 1,
+    RtcCompare1 = 2,
+    RtcOverflow = 3,
+    RtcPeriodic0 = 4,
+    RtcPeriodic1 = 5,
+    RtcPeriodic2 = 6,
+    RtcPeriodic3 = 7,
+    RtcPeriodic4 = 8,
+    RtcPeriodic5 = 9,
+    RtcPeriodic6 = 10,
+    RtcPeriodic7 = 11,
+    EicExtint0 = 12,
+    EicExtint1 = 13,
+    EicExtint2 = 14,
+    EicExtint3 = 15,
+    EicExtint4 = 16,
+    EicExtint5 = 17,
+    EicExtint6 = 18,
+    EicExtint7 = 19,
+    DmacCh0 = 20,
+    DmacCh1 = 21,
+    DmacCh2 = 22,
+    DmacCh3 = 23,
+    DmacCh4 = 24,
+    DmacCh5 = 25,
+    Tcc0Overflow = 26,
+    Tcc0Trigger = 27,
+    Tcc0Counter = 28,
+    Tcc0Match0 = 29,
+    Tcc0Match1 = 30,
+    Tcc0Match2 = 31,
+    Tcc0Match3 = 32,
+    Tc1Overflow = 33,
+    Tc1Match0 = 34,
+    Tc1Match1 = 35,
+    Tc2Overflow = 36,
+    Tc2Match0 = 37,
+    Tc2Match1 = 38,
+    AdcResultReady = 39,
+    AdcWindowMonitor = 40,
+    AcComparator0 = 41,
+    AcComparator1 = 42,
+    AcWindow0 = 43,
+    DacEmpty = 44,
+    PtcEndOfConversion = 45,
+    PtcWindowComparator = 46,
+}
+
+/// Event generators
+#[cfg(feature = "samd21")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+#[allow(missing_docs)]
+pub enum Generator {
+    RtcCompare0 = 1,
+    RtcCompare1 = 2,
+    RtcOverflow = 3,
+    RtcPeriodic0 = 4,
+    RtcPeriodic1 = 5,
+    RtcPeriodic2 = 6,
+    RtcPeriodic3 = 7,
+    RtcPeriodic4 = 8,
+    RtcPeriodic5 = 9,
+    RtcPeriodic6 = 10,
+    RtcPeriodic7 = 11,
+    EicExtint0 = 12,
+    EicExtint1 = 13,
+    EicExtint2 = 14,
+    EicExtint3 = 15,
+    EicExtint4 = 16,
+    EicExtint5 = 17,
+    EicExtint6 = 18,
+    EicExtint7 = 19,
+    EicExtint8 = 20,
+    EicExtint9 = 21,
+    EicExtint10 = 22,
+    EicExtint11 = 23,
+    EicExtint12 = 24,
+    EicExtint13 = 25,
+    EicExtint14 = 26,
+    EicExtint15 = 27,
+    DmacCh0 = 28,
+    DmacCh1 = 29,
+    DmacCh2 = 30,
+    DmacCh3 = 31,
+    Tcc0Overflow = 32,
+    Tcc0Trigger = 33,
+    Tcc0Counter = 34,
+    Tcc0Match0 = 35,
+    Tcc0Match1 = 36,
+    Tcc0Match2 = 37,
+    Tcc0Match3 = 38,
+    Tcc1Overflow = 39,
+    Tcc1Trigger = 40,
+    Tcc1Counter = 41,
+    Tcc1Match0 = 42,
+    Tcc1Match1 = 43,
+    Tcc2Overflow = 44,
+    Tcc2Trigger = 45,
+    Tcc2Counter = 46,
+    Tcc2Match0 = 47,
+    Tcc2Match1 = 48,
+    Tc3Overflow = 49,
+    Tc3Match0 = 50,
+    Tc3Match1 = 51,
+    Tc4Overflow = 52,
+    Tc4Match0 = 53,
+    Tc4Match1 = 54,
+    Tc5Overflow = 55,
+    Tc5Match0 = 56,
+    Tc5Match1 = 57,
+    Tc6Overflow = 58,
+    Tc6Match0 = 59,
+    Tc6Match1 = 60,
+    Tc7Overflow = 61,
+    Tc7Match0 = 62,
+    Tc7Match1 = 63,
+    AdcResultReady = 64,
+    AdcWindowMonitor = 65,
+    AcComparator0 = 66,
+    AcComparator1 = 67,
+    AcWindow0 = 68,
+    DacEmpty = 69,
+    PtcEndOfConversion = 70,
+    PtcWindowComparator = 71,
+}
+
+/// Event generators
+#[cfg(feature = "min-samd51g")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+#[allow(missing_docs)]
+pub enum Generator {
+    Xosc0Failure = 0x01,
+    Xosc1Failure = 0x02,
+    Xosc32kFailure = 0x03,
+    RtcPeriodic0 = 0x04,
+    RtcPeriodic1 = 0x05,
+    RtcPeriodic2 = 0x06,
+    RtcPeriodic3 = 0x07,
+    RtcPeriodic4 = 0x08,
+    RtcPeriodic5 = 0x09,
+    RtcPeriodic6 = 0x0A,
+    RtcPeriodic7 = 0x0B,
+    RtcCompare0 = 0x0C,
+    RtcCompare1 = 0x0D,
+    RtcCompare2 = 0x0E,
+    RtcCompare3 = 0x0F,
+    RtcTamper = 0x10,
+    RtcOverflow = 0x11,
+    EicExtint0 = 0x12,
+    EicExtint1 = 0x13,
+    EicExtint2 = 0x14,
+    EicExtint3 = 0x15,
+    EicExtint4 = 0x16,
+    EicExtint5 = 0x17,
+    EicExtint6 = 0x18,
+    EicExtint7 = 0x19,
+    EicExtint8 = 0x1A,
+    EicExtint9 = 0x1B,
+    EicExtint10 = 0x1C,
+    EicExtint11 = 0x1D,
+    EicExtint12 = 0x1E,
+    EicExtint13 = 0x1F,
+    EicExtint14 = 0x20,
+    EicExtint15 = 0x21,
+    DmacCh0 = 0x22,
+    DmacCh1 = 0x23,
+    DmacCh2 = 0x24,
+    DmacCh3 = 0x25,
+    PacAccessError = 0x26,
+    Tcc0Overflow = 0x29,
+    Tcc0Trigger = 0x2A,
+    Tcc0Counter = 0x2B,
+    Tcc0Match0 = 0x2C,
+    Tcc0Match1 = 0x2D,
+    Tcc0Match2 = 0x2E,
+    Tcc0Match3 = 0x2F,
+    Tcc0Match4 = 0x30,
+    Tcc0Match5 = 0x31,
+    Tcc1Overflow = 0x32,
+    Tcc1Trigger = 0x33,
+    Tcc1Counter = 0x34,
+    Tcc1Match0 = 0x35,
+    Tcc1Match1 = 0x36,
+    Tcc1Match2 = 0x37,
+    Tcc1Match3 = 0x38,
+    Tcc2Overflow = 0x39,
+    Tcc2Trigger = 0x3A,
+    Tcc2Counter = 0x3B,
+    Tcc2Match0 = 0x3C,
+    Tcc2Match1 = 0x3D,
+    Tcc2Match2 = 0x3E,
+    Tcc3Overflow = 0x3F,
+    Tcc3Trigger = 0x40,
+    Tcc3Counter = 0x41,
+    Tcc3Match0 = 0x42,
+    Tcc3Match1 = 0x43,
+    Tcc4Overflow = 0x44,
+    Tcc4Trigger = 0x45,
+    Tcc4Counter = 0x46,
+    Tcc4Match0 = 0x47,
+    Tcc4Match1 = 0x48,
+    Tc0Overflow = 0x49,
+    Tc0Match0 = 0x4A,
+    Tc0Match1 = 0x4B,
+    Tc1Overflow = 0x4C,
+    Tc1Match0 = 0x4D,
+    Tc1Match1 = 0x4E,
+    Tc2Overflow = 0x4F,
+    Tc2Match0 = 0x50,
+    Tc2Match1 = 0x51,
+    Tc3Overflow = 0x52,
+    Tc3Match0 = 0x53,
+    Tc3Match1 = 0x54,
+    Tc4Overflow = 0x55,
+    Tc4Match0 = 0x56,
+    Tc4Match1 = 0x57,
+    Tc5Overflow = 0x58,
+    Tc5Match0 = 0x59,
+    Tc5Match1 = 0x5A,
+    Tc6Overflow = 0x5B,
+    Tc6Match0 = 0x5C,
+    Tc6Match1 = 0x5D,
+    Tc7Overflow = 0x5E,
+    Tc7Match0 = 0x5F,
+    Tc7Match1 = 0x60,
+    PdecOverflow = 0x61,
+    PdecError = 0x62,
+    PdecDirection = 0x63,
+    PdecVelocity = 0x64,
+    PdecMatch0 = 0x65,
+    PdecMatch1 = 0x66,
+    Adc0ResultReady = 0x67,
+    Adc0WindowMonitor = 0x68,
+    Adc1ResultReady = 0x69,
+    Adc1WindowMonitor = 0x6A,
+    AcComparator0 = 0x6B,
+    AcComparator1 = 0x6C,
+    AcWindow0 = 0x6D,
+    DacEmpty0 = 0x6E,
+    DacEmpty1 = 0x6F,
+    DacResultReady0 = 0x70,
+    DacResultReady1 = 0x71,
+    GmacTimestampCompare = 0x72,
+    TrngReady = 0x73,
+    CclLutOut0 = 0x74,
+    CclLutOut1 = 0x75,
+    CclLutOut2 = 0x76,
+    CclLutOut3 = 0x77,
+}
+
+/// Event users
+#[cfg(feature = "samd11")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+#[allow(missing_docs)]
+pub enum User {
+    DmacCh0 = 0,
+    DmacCh1 = 1,
+    DmacCh2 = 2,
+    DmacCh3 = 3,
+    DmacCh4 = 4,
+    DmacCh5 = 5,
+    Tcc0Event0 = 6,
+    Tcc0Event1 = 7,
+    Tcc0Match0 = 8,
+    Tcc0Match1 = 9,
+    Tcc0Match2 = 10,
+    Tcc0Match3 = 11,
+    Tc1 = 12,
+    Tc2 = 13,
+    AdcStart = 14,
+    AdcFlush = 15,
+    AcStart0 = 16,
+    AcStart1 = 17,
+    DacStart = 18,
+    PtcStartConversion = 19,
+}
+
+/// Event users
+#[cfg(feature = "samd21")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+#[allow(missing_docs)]
+pub enum User {
+    DmacCh0 = 0,
+    DmacCh1 = 1,
+    DmacCh2 = 2,
+    DmacCh3 = 3,
+    Tcc0Event0 = 4,
+    Tcc0Event1 = 5,
+    Tcc0Match0 = 6,
+    Tcc0Match1 = 7,
+    Tcc0Match2 = 8,
+    Tcc0Match3 = 9,
+    Tcc1Event0 = 10,
+    Tcc1Event1 = 11,
+    Tcc1Match0 = 12,
+    Tcc1Match1 = 13,
+    Tcc2Event0 = 14,
+    Tcc2Event1 = 15,
+    Tcc2Match0 = 16,
+    Tcc2Match1 = 17,
+    Tc3 = 18,
+    Tc4 = 19,
+    Tc5 = 20,
+    Tc6 = 21,
+    Tc7 = 22,
+    AdcStart = 23,
+    AdcFlush = 24,
+    AcStart0 = 25,
+    AcStart1 = 26,
+    DacStart = 27,
+    PtcStartConversion = 28,
+}
+
+/// Event users
+#[cfg(feature = "min-samd51g")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+#[allow(missing_docs)]
+pub enum User {
+    RtcTamper = 0,
+    PortEvent0 = 1,
+    PortEvent1 = 2,
+    PortEvent2 = 3,
+    PortEvent3 = 4,
+    DmacCh0 = 5,
+    DmacCh1 = 6,
+    DmacCh2 = 7,
+    DmacCh3 = 8,
+    DmacCh4 = 9,
+    DmacCh5 = 10,
+    DmacCh6 = 11,
+    DmacCh7 = 12,
+    TalBreak = 13,
+    TraceStart = 14,
+    TraceStop = 15,
+    TraceTrigger = 16,
+    Tcc0Event0 = 17,
+    Tcc0Event1 = 18,
+    Tcc0Match0 = 19,
+    Tcc0Match1 = 20,
+    Tcc0Match2 = 21,
+    Tcc0Match3 = 22,
+    Tcc0Match4 = 23,
+    Tcc0Match5 = 24,
+    Tcc1Event0 = 25,
+    Tcc1Event1 = 26,
+    Tcc1Match0 = 27,
+    Tcc1Match1 = 28,
+    Tcc1Match2 = 29,
+    Tcc1Match3 = 30,
+    Tcc2Event0 = 31,
+    Tcc2Event1 = 32,
+    Tcc2Match0 = 33,
+    Tcc2Match1 = 34,
+    Tcc2Match2 = 35,
+    Tcc3Event0 = 36,
+    Tcc3Event1 = 37,
+    Tcc3Match0 = 38,
+    Tcc3Match1 = 39,
+    Tcc4Event0 = 40,
+    Tcc4Event1 = 41,
+    Tcc4Match0 = 42,
+    Tcc4Match1 = 43,
+    Tc0 = 44,
+    Tc1 = 45,
+    Tc2 = 46,
+    Tc3 = 47,
+    Tc4 = 48,
+    Tc5 = 49,
+    Tc6 = 50,
+    Tc7 = 51,
+    PdecEvent0 = 52,
+    PdecEvent1 = 53,
+    PdecEvent2 = 54,
+    Adc0Start = 55,
+    Adc0Flush = 56,
+    Adc1Start = 57,
+    Adc1Flush = 58,
+    AcStart0 = 59,
+    AcStart1 = 60,
+    DacStart0 = 61,
+    DacStart1 = 62,
+    CclLutIn0 = 63,
+    CclLutIn1 = 64,
+    CclLutIn2 = 65,
+    CclLutIn3 = 66,
+}
+
+/// Find the channel to allocate among the free channels in `allocated`
+///
+/// Asynchronous channels are allocated from the highest channel downwards,
+/// to keep the channels supporting the other paths available.
+fn find_free(allocated: u32, asynchronous: bool) -> Option<u8> {
+    let free = |&id: &u8| allocated & (1 << id) == 0;
+    if asynchronous {
+        (0..NUM_CHANNELS as u8).rev().find(free)
+    } else {
+        (0..NUM_SYNC_CHANNELS as u8).find(free)
+    }
+}
+
+/// Event System
+pub struct Evsys {
+    evsys: EVSYS,
+    allocated: Cell<u32>,
+}
+
+impl Evsys {
+    /// Enable the EVSYS clock and reset the event system
+    pub fn new(evsys: EVSYS, pm: &mut PowerManager) -> Self {
+        EVSYS::enable_bus_clock(pm);
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        evsys.ctrl.write(|w| w.swrst().set_bit());
+        #[cfg(feature = "min-samd51g")]
+        {
+            evsys.ctrla.write(|w| w.swrst().set_bit());
+            while evsys.ctrla.read().swrst().bit_is_set() {}
+        }
+        Self {
+            evsys,
+            allocated: Cell::new(0),
+        }
+    }
+
+    /// Disable the EVSYS clock and return the register block
+    ///
+    /// The channels borrow the event system, so they must all have been
+    /// dropped or released beforehand.
+    pub fn free(self, pm: &mut PowerManager) -> EVSYS {
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        self.evsys.ctrl.write(|w| w.swrst().set_bit());
+        #[cfg(feature = "min-samd51g")]
+        {
+            self.evsys.ctrla.write(|w| w.swrst().set_bit());
+            while self.evsys.ctrla.read().swrst().bit_is_set() {}
+        }
+        EVSYS::disable_bus_clock(pm);
+        self.evsys
+    }
+
+    /// Allocate a free channel, routing the events of `generator` through
+    /// `path`
+    ///
+    /// The channel has no user until [`Channel::connect`] is called.
+    pub fn allocate(&self, generator: Generator, path: Path) -> Result<Channel<'_>, Error> {
+        let allocated = self.allocated.get();
+        let id = find_free(allocated, path.is_asynchronous()).ok_or(Error::NoChannelAvailable)?;
+        self.allocated.set(allocated | 1 << id);
+        let channel = Channel {
+            evsys: self,
+            id,
+            generator,
+            path,
+        };
+        channel.configure(false);
+        Ok(channel)
+    }
+
+    /// Disconnect the generator and the users of `channel`, and make it
+    /// available again
+    pub fn release(&self, channel: Channel<'_>) {
+        for user in 0..NUM_USERS {
+            channel.disconnect_raw(user);
+        }
+        // SAFETY: Generator 0 disconnects the channel
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        self.evsys.channel.write(|w| unsafe {
+            w.channel().bits(channel.id);
+            w.evgen().bits(0)
+        });
+        #[cfg(feature = "min-samd51g")]
+        self.evsys.channel[channel.id as usize]
+            .channel
+            .write(|w| unsafe { w.evgen().bits(0) });
+        self.allocated
+            .set(self.allocated.get() & !(1 << channel.id));
+    }
+}
+
+/// Event channel, allocated by [`Evsys::allocate`]
+///
+/// Dropping a channel leaves it allocated and connected; return it to the
+/// event system with [`Evsys::release`] instead.
+pub struct Channel<'a> {
+    evsys: &'a Evsys,
+    id: u8,
+    generator: Generator,
+    path: Path,
+}
+
+impl Channel<'_> {
+    /// Each channel only writes its own registers, and the user multiplexers
+    /// it is connected to
+    #[inline]
+    fn evsys(&self) -> &EVSYS {
+        &self.evsys.evsys
+    }
+
+    /// Write the channel configuration, optionally generating a software
+    /// event on SAMD11 and SAMD21 chips
+    fn configure(&self, software_event: bool) {
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        {
+            use crate::pac::evsys::channel::{EDGSEL_A, PATH_A};
+            let (path, edge) = match self.path {
+                Path::Synchronous(edge) => (PATH_A::SYNCHRONOUS, Some(edge)),
+                Path::Resynchronized(edge) => (PATH_A::RESYNCHRONIZED, Some(edge)),
+                Path::Asynchronous => (PATH_A::ASYNCHRONOUS, None),
+            };
+            let edge = match edge {
+                Some(Edge::Rising) => EDGSEL_A::RISING_EDGE,
+                Some(Edge::Falling) => EDGSEL_A::FALLING_EDGE,
+                Some(Edge::Both) => EDGSEL_A::BOTH_EDGES,
+                None => EDGSEL_A::NO_EVT_OUTPUT,
+            };
+            // SAFETY: The channel and generator numbers are valid
+            self.evsys().channel.write(|w| unsafe {
+                w.channel().bits(self.id);
+                w.evgen().bits(self.generator as u8);
+                w.path().variant(path);
+                w.edgsel().variant(edge);
+                w.swevt().bit(software_event)
+            });
+        }
+
+        #[cfg(feature = "min-samd51g")]
+        {
+            let _ = software_event;
+            use crate::pac::evsys::channel::channel::{EDGSEL_A, PATH_A};
+            let (path, edge) = match self.path {
+                Path::Synchronous(edge) => (PATH_A::SYNCHRONOUS, Some(edge)),
+                Path::Resynchronized(edge) => (PATH_A::RESYNCHRONIZED, Some(edge)),
+                Path::Asynchronous => (PATH_A::ASYNCHRONOUS, None),
+            };
+            let edge = match edge {
+                Some(Edge::Rising) => EDGSEL_A::RISING_EDGE,
+                Some(Edge::Falling) => EDGSEL_A::FALLING_EDGE,
+                Some(Edge::Both) => EDGSEL_A::BOTH_EDGES,
+                None => EDGSEL_A::NO_EVT_OUTPUT,
+            };
+            // SAFETY: The generator number is valid
+            self.evsys().channel[self.id as usize]
+                .channel
+                .write(|w| unsafe {
+                    w.evgen().bits(self.generator as u8);
+                    w.path().variant(path);
+                    w.edgsel().variant(edge);
+                    w.runstdby().set_bit()
+                });
+        }
+    }
+
+    /// Channel number
+    #[inline]
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Generator connected to the channel
+    #[inline]
+    pub fn generator(&self) -> Generator {
+        self.generator
+    }
+
+    /// Route the events of the channel to `user`
+    ///
+    /// A user listens to a single channel, so `user` is disconnected from any
+    /// other channel.
+    pub fn connect(&mut self, user: User) {
+        // SAFETY: Channel n is selected by writing n + 1
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        self.evsys().user.write(|w| unsafe {
+            w.user().bits(user as u8);
+            w.channel().bits(self.id + 1)
+        });
+        #[cfg(feature = "min-samd51g")]
+        self.evsys().user[user as usize].write(|w| unsafe { w.channel().bits(self.id + 1) });
+    }
+
+    /// Stop routing the events of the channel to `user`
+    ///
+    /// Nothing happens if `user` listens to another channel.
+    #[inline]
+    pub fn disconnect(&mut self, user: User) {
+        self.disconnect_raw(user as u8);
+    }
+
+    fn disconnect_raw(&self, user: u8) {
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        {
+            // Reading the user multiplexer requires selecting it first. The
+            // selection is shared by every channel, so no other access may
+            // slip in between.
+            let evsys = self.evsys();
+            cortex_m::interrupt::free(|_| {
+                // SAFETY: Writing the USER byte alone only selects the user
+                unsafe {
+                    core::ptr::write_volatile(evsys.user.as_ptr() as *mut u8, user);
+                }
+                if evsys.user.read().channel().bits() == self.id + 1 {
+                    // SAFETY: Writing a zero channel disconnects the user
+                    evsys.user.write(|w| unsafe {
+                        w.user().bits(user);
+                        w.channel().bits(0)
+                    });
+                }
+            });
+        }
+        #[cfg(feature = "min-samd51g")]
+        {
+            let mux = &self.evsys().user[user as usize];
+            if mux.read().channel().bits() == self.id + 1 {
+                // SAFETY: Writing a zero channel disconnects the user
+                mux.write(|w| unsafe { w.channel().bits(0) });
+            }
+        }
+    }
+
+    /// Generate an event on the channel from software
+    #[inline]
+    pub fn trigger(&mut self) {
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        self.configure(true);
+        // SAFETY: Writing a one only triggers this channel
+        #[cfg(feature = "min-samd51g")]
+        self.evsys()
+            .swevt
+            .write(|w| unsafe { w.bits(1 << self.id) });
+    }
+
+    /// Check whether an event is still being handled by the users of the
+    /// channel
+    ///
+    /// Only synchronous and resynchronized channels report their status.
+    #[inline]
+    pub fn is_busy(&self) -> bool {
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        return self.evsys().chstatus.read().bits() & (1 << status_bit(self.id, 8)) != 0;
+        #[cfg(feature = "min-samd51g")]
+        return self.evsys().channel[self.id as usize]
+            .chstatus
+            .read()
+            .busych()
+            .bit_is_set();
+    }
+
+    /// Check whether every user of the channel is ready to handle a new
+    /// event
+    ///
+    /// Only synchronous and resynchronized channels report their status.
+    #[inline]
+    pub fn users_ready(&self) -> bool {
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        return self.evsys().chstatus.read().bits() & (1 << status_bit(self.id, 0)) != 0;
+        #[cfg(feature = "min-samd51g")]
+        return self.evsys().channel[self.id as usize]
+            .chstatus
+            .read()
+            .rdyusr()
+            .bit_is_set();
+    }
+}
+
+/// Bit of the status of a channel in CHSTATUS, for the field starting at
+/// `offset`
+///
+/// The status of channels 0 to 7 is in the lower half of the register, and
+/// the status of channels 8 to 11 in the upper half.
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+#[inline]
+fn status_bit(id: u8, offset: u8) -> u8 {
+    if id < 8 {
+        offset + id
+    } else {
+        16 + offset + id - 8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asynchronous_channels_are_allocated_from_the_top() {
+        let top = NUM_CHANNELS as u8 - 1;
+        assert_eq!(find_free(0, true), Some(top));
+        assert_eq!(find_free(1 << top, true), Some(top - 1));
+        assert_eq!(find_free(0, false), Some(0));
+        assert_eq!(find_free(0b1011, false), Some(2));
+    }
+
+    #[test]
+    fn allocation_fails_once_channels_are_exhausted() {
+        let all = u32::MAX >> (32 - NUM_CHANNELS);
+        let sync = u32::MAX >> (32 - NUM_SYNC_CHANNELS);
+        assert_eq!(find_free(all, true), None);
+        assert_eq!(find_free(sync, false), None);
+    }
+}
@@ -21,4 +21,7 @@ pub use pin::*;
 pub mod dynpin;
 pub use dynpin::*;
 
+pub mod group;
+pub use group::*;
+
 mod reg;
@@ -48,6 +48,19 @@
 //! let pa27: Pin<PA27, FloatingInput> = pa27.try_into().unwrap();
 //! ```
 //!
+//! [`DynPin::try_into_pin`] returns the [`DynPin`] back when the conversion
+//! fails, rather than an error.
+//!
+//! [`DynPinId`]s can be stored as pin numbers, for instance in configuration
+//! tables, and converted with [`DynPinId::number`] and
+//! [`DynPinId::from_number`].
+//!
+//! ```
+//! // Check the pin against the pin number stored in the configuration
+//! let led: DynPin = pins.pa17.into();
+//! assert_eq!(Some(led.id()), DynPinId::from_number(config.led_pin));
+//! ```
+//!
 //! # Embedded HAL traits
 //!
 //! This module implements all of the embedded HAL GPIO traits for [`DynPin`].
@@ -73,7 +86,7 @@ use super::reg::RegisterInterface;
 //==============================================================================
 
 /// Value-level `enum` for disabled configurations
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum DynDisabled {
     Floating,
     PullDown,
@@ -81,7 +94,7 @@ pub enum DynDisabled {
 }
 
 /// Value-level `enum` for input configurations
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum DynInput {
     Floating,
     PullDown,
@@ -89,7 +102,7 @@ pub enum DynInput {
 }
 
 /// Value-level `enum` for interrupt configurations
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum DynInterrupt {
     Floating,
     PullDown,
@@ -97,14 +110,14 @@ pub enum DynInterrupt {
 }
 
 /// Value-level `enum` for output configurations
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum DynOutput {
     PushPull,
     Readable,
 }
 
 /// Value-level `enum` for alternate peripheral function configurations
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum DynAlternate {
     B,
     C,
@@ -133,7 +146,7 @@ pub enum DynAlternate {
 //==============================================================================
 
 /// Value-level `enum` representing pin modes
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum DynPinMode {
     Disabled(DynDisabled),
     Input(DynInput),
@@ -194,7 +207,7 @@ dyn_alternate!(I, J, K, L, M, N);
 //==============================================================================
 
 /// Value-level `enum` for pin groups
-#[derive(PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum DynGroup {
     A,
     #[cfg(any(feature = "samd21", feature = "min-samd51g"))]
@@ -206,12 +219,53 @@ pub enum DynGroup {
 }
 
 /// Value-level `struct` representing pin IDs
-#[derive(PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct DynPinId {
     pub group: DynGroup,
     pub num: u8,
 }
 
+impl DynPinId {
+    /// Return the pin number, counting the pins of the previous groups, so
+    /// that `PA00` is 0 and `PB00` is 32
+    #[inline]
+    pub fn number(&self) -> u8 {
+        let group = match self.group {
+            DynGroup::A => 0,
+            #[cfg(any(feature = "samd21", feature = "min-samd51g"))]
+            DynGroup::B => 1,
+            #[cfg(feature = "min-samd51n")]
+            DynGroup::C => 2,
+            #[cfg(feature = "min-samd51p")]
+            DynGroup::D => 3,
+        };
+        group * 32 + self.num
+    }
+
+    /// Create the pin ID of a pin number, as returned by
+    /// [`number`](Self::number)
+    ///
+    /// Returns `None` if the group of the pin doesn't exist on the chip. The
+    /// pin itself may not exist, if it isn't bonded out in the package.
+    #[inline]
+    pub fn from_number(number: u8) -> Option<Self> {
+        let group = match number / 32 {
+            0 => DynGroup::A,
+            #[cfg(any(feature = "samd21", feature = "min-samd51g"))]
+            1 => DynGroup::B,
+            #[cfg(feature = "min-samd51n")]
+            2 => DynGroup::C,
+            #[cfg(feature = "min-samd51p")]
+            3 => DynGroup::D,
+            _ => return None,
+        };
+        Some(Self {
+            group,
+            num: number % 32,
+        })
+    }
+}
+
 //==============================================================================
 //  DynRegisters
 //==============================================================================
@@ -254,6 +308,7 @@ impl DynRegisters {
 ///
 /// [`DynPin`]s are not tracked and verified at compile-time, so run-time
 /// operations are fallible. This `enum` represents the corresponding errors.
+#[derive(Debug)]
 pub enum Error {
     /// The pin did not have the correct ID or mode for the requested operation
     InvalidPinType,
@@ -281,7 +336,7 @@ impl DynPin {
     /// must be at most one corresponding [`DynPin`] in existence at any given
     /// time.  Violating this requirement is `unsafe`.
     #[inline]
-    unsafe fn new(id: DynPinId, mode: DynPinMode) -> Self {
+    pub(crate) unsafe fn new(id: DynPinId, mode: DynPinMode) -> Self {
         DynPin {
             regs: DynRegisters::new(id),
             mode,
@@ -300,6 +355,23 @@ impl DynPin {
         self.mode
     }
 
+    /// Try to recreate a type-level [`Pin`], returning the [`DynPin`] back if
+    /// it doesn't have the ID or mode of the [`Pin`]
+    ///
+    /// Unlike [`TryFrom`], the pin isn't lost when the conversion fails, so
+    /// it can be converted to the expected mode with
+    /// [`into_mode`](Self::into_mode) and tried again.
+    #[inline]
+    pub fn try_into_pin<I: PinId, M: PinMode>(self) -> Result<Pin<I, M>, Self> {
+        if self.regs.id == I::DYN && self.mode == M::DYN {
+            // The `DynPin` is consumed, so it is safe to replace it with the
+            // corresponding `Pin`
+            Ok(unsafe { Pin::new() })
+        } else {
+            Err(self)
+        }
+    }
+
     /// Convert the pin to the requested [`DynPinMode`]
     #[inline]
     pub fn into_mode(&mut self, mode: DynPinMode) {
@@ -310,6 +382,12 @@ impl DynPin {
         }
     }
 
+    /// Record the mode of the pin, changed by a [`PortGroup`](super::PortGroup)
+    #[inline]
+    pub(super) fn record_mode(&mut self, mode: DynPinMode) {
+        self.mode = mode;
+    }
+
     /// Disable the pin and set it to float
     #[inline]
     pub fn into_floating_disabled(&mut self) {
@@ -384,6 +462,20 @@ impl DynPin {
         self.into_mode(DynPinMode::Alternate(config));
     }
 
+    /// Put each of `pins` in its lowest-power state, the reset state
+    ///
+    /// The input buffer, output driver and pull resistor of the pins are
+    /// disabled, so that they draw no current even if left unconnected. Use
+    /// this function on the pins unused by the application, for instance
+    /// before entering sleep.
+    #[inline]
+    pub fn disable_all(pins: &mut [DynPin]) {
+        for pin in pins {
+            pin.into_floating_disabled();
+            pin.set_drive_strength(false);
+        }
+    }
+
     /// Read the current drive strength of the pin.
     ///
     /// The drive strength is reset to normal on every change in pin mode.
@@ -487,13 +579,7 @@ where
     /// or refuse to perform it.
     #[inline]
     fn try_from(pin: DynPin) -> Result<Self, Error> {
-        if pin.regs.id == I::DYN && pin.mode == M::DYN {
-            // The `DynPin` is consumed, so it is safe to replace it with the
-            // corresponding `Pin`
-            Ok(unsafe { Self::new() })
-        } else {
-            Err(Error::InvalidPinType)
-        }
+        pin.try_into_pin().map_err(|_| Error::InvalidPinType)
     }
 }
 
@@ -546,3 +632,85 @@ impl StatefulOutputPin for DynPin {
         self._is_set_low()
     }
 }
+
+//==============================================================================
+// Embedded HAL 1.0 traits
+//==============================================================================
+
+#[cfg(feature = "ehal1")]
+impl embedded_hal_1::digital::Error for Error {
+    #[inline]
+    fn kind(&self) -> embedded_hal_1::digital::ErrorKind {
+        embedded_hal_1::digital::ErrorKind::Other
+    }
+}
+
+#[cfg(feature = "ehal1")]
+impl embedded_hal_1::digital::ErrorType for DynPin {
+    type Error = Error;
+}
+
+#[cfg(feature = "ehal1")]
+impl embedded_hal_1::digital::OutputPin for DynPin {
+    #[inline]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self._set_high()
+    }
+    #[inline]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self._set_low()
+    }
+}
+
+#[cfg(feature = "ehal1")]
+impl embedded_hal_1::digital::InputPin for DynPin {
+    #[inline]
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        self._is_high()
+    }
+    #[inline]
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self._is_low()
+    }
+}
+
+#[cfg(feature = "ehal1")]
+impl embedded_hal_1::digital::StatefulOutputPin for DynPin {
+    #[inline]
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        self._is_set_high()
+    }
+    #[inline]
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        self._is_set_low()
+    }
+    #[inline]
+    fn toggle(&mut self) -> Result<(), Self::Error> {
+        self._toggle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pin_numbers() {
+        let pa27 = DynPinId {
+            group: DynGroup::A,
+            num: 27,
+        };
+        assert_eq!(pa27.number(), 27);
+        assert_eq!(DynPinId::from_number(27), Some(pa27));
+        #[cfg(any(feature = "samd21", feature = "min-samd51g"))]
+        {
+            let pb03 = DynPinId {
+                group: DynGroup::B,
+                num: 3,
+            };
+            assert_eq!(pb03.number(), 35);
+            assert_eq!(DynPinId::from_number(35), Some(pb03));
+        }
+        assert_eq!(DynPinId::from_number(200), None);
+    }
+}
@@ -0,0 +1,159 @@
+//! # Multi-pin access to a PORT group
+//!
+//! Each [`Pin`](super::Pin) and [`DynPin`] accesses the PORT registers on its
+//! own, so driving a parallel bus, such as the 8-bit data bus of an LCD, one
+//! pin at a time costs one register access per pin, and the bus goes
+//! through intermediate values.
+//!
+//! A [`PortGroup`] takes ownership of a set of [`DynPin`]s of the same PORT
+//! group, and accesses them all at once through the group registers:
+//!
+//! - [`set_high`](PortGroup::set_high), [`set_low`](PortGroup::set_low) and
+//!   [`toggle`](PortGroup::toggle) write OUTSET, OUTCLR and OUTTGL
+//! - [`write`](PortGroup::write) sets the level of every output pin with a
+//!   single write of OUT
+//! - [`configure`](PortGroup::configure) changes the mode of every pin with
+//!   WRCONFIG
+//!
+//! Masks and values use the bit numbers of the pins in the group, bit `n`
+//! for pin `n`.
+//!
+//! ```
+//! // Data bus on PA16 to PA23
+//! let mut bus = PortGroup::new([
+//!     pins.pa16.into(),
+//!     pins.pa17.into(),
+//!     // ...
+//!     pins.pa23.into(),
+//! ])
+//! .unwrap();
+//! bus.configure(DYN_PUSH_PULL_OUTPUT);
+//! bus.write((byte as u32) << 16);
+//! ```
+
+use super::dynpin::*;
+use super::reg::GroupRegisters;
+
+/// Set of [`DynPin`]s of the same PORT group, accessed all at once
+///
+/// See the [module-level documentation](self) for more details.
+pub struct PortGroup<const N: usize> {
+    regs: GroupRegisters,
+    group: DynGroup,
+    mask: u32,
+    outputs: u32,
+    pins: [DynPin; N],
+}
+
+impl<const N: usize> PortGroup<N> {
+    /// Take ownership of `pins`
+    ///
+    /// Returns `pins` back if they don't all belong to the same PORT group,
+    /// or if there are none.
+    pub fn new(pins: [DynPin; N]) -> Result<Self, [DynPin; N]> {
+        let group = match pins.first() {
+            Some(pin) => pin.id().group,
+            None => return Err(pins),
+        };
+        if pins.iter().any(|pin| pin.id().group != group) {
+            return Err(pins);
+        }
+        let mask = Self::mask_of(&pins, |_| true);
+        let outputs = Self::mask_of(&pins, |pin| matches!(pin.mode(), DynPinMode::Output(_)));
+        Ok(Self {
+            // Safety: The pins are owned by the `PortGroup`
+            regs: unsafe { GroupRegisters::new(group, mask) },
+            group,
+            mask,
+            outputs,
+            pins,
+        })
+    }
+
+    fn mask_of(pins: &[DynPin], filter: impl Fn(&DynPin) -> bool) -> u32 {
+        pins.iter()
+            .filter(|pin| filter(pin))
+            .fold(0, |mask, pin| mask | 1 << pin.id().num)
+    }
+
+    /// PORT group of the pins
+    #[inline]
+    pub fn group(&self) -> DynGroup {
+        self.group
+    }
+
+    /// Mask of all the pins
+    #[inline]
+    pub fn mask(&self) -> u32 {
+        self.mask
+    }
+
+    /// Mask of the pins in an output mode
+    #[inline]
+    pub fn output_mask(&self) -> u32 {
+        self.outputs
+    }
+
+    /// Access the pins
+    #[inline]
+    pub fn pins(&self) -> &[DynPin; N] {
+        &self.pins
+    }
+
+    /// Convert every pin to `mode`
+    ///
+    /// The pins are configured with WRCONFIG, one write per half of the
+    /// group, rather than one per pin.
+    pub fn configure(&mut self, mode: DynPinMode) {
+        self.regs.change_mode(self.mask, mode);
+        for pin in self.pins.iter_mut() {
+            pin.record_mode(mode);
+        }
+        self.outputs = match mode {
+            DynPinMode::Output(_) => self.mask,
+            _ => 0,
+        };
+    }
+
+    /// Read the level of the pins
+    ///
+    /// Only the pins whose input buffer is enabled are read, the others read
+    /// as low.
+    #[inline]
+    pub fn read(&self) -> u32 {
+        self.regs.read_pins()
+    }
+
+    /// Set the output pins of `mask` high
+    #[inline]
+    pub fn set_high(&mut self, mask: u32) {
+        self.regs.set_pins(mask & self.outputs);
+    }
+
+    /// Set the output pins of `mask` low
+    #[inline]
+    pub fn set_low(&mut self, mask: u32) {
+        self.regs.clear_pins(mask & self.outputs);
+    }
+
+    /// Toggle the output pins of `mask`
+    #[inline]
+    pub fn toggle(&mut self, mask: u32) {
+        self.regs.toggle_pins(mask & self.outputs);
+    }
+
+    /// Set every output pin to the level of its bit of `value`, at once
+    ///
+    /// The pins change with a single write of OUT, done within a critical
+    /// section since OUT is shared with the other pins of the group.
+    #[inline]
+    pub fn write(&mut self, value: u32) {
+        self.regs.write_pins(self.outputs, value);
+    }
+
+    /// Release the pins
+    #[inline]
+    pub fn free(self) -> [DynPin; N] {
+        self.pins
+    }
+}
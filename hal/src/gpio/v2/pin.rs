@@ -356,6 +356,69 @@ pub struct Alternate<C: AlternateConfig> {
 
 impl<C: AlternateConfig> Sealed for Alternate<C> {}
 
+/// Event input of a PORT group
+///
+/// Each group has four event inputs, the EVSYS users
+/// [`PortEvent0`](crate::evsys::User::PortEvent0) to
+/// [`PortEvent3`](crate::evsys::User::PortEvent3), shared by every group.
+/// Each input acts on at most one pin per group.
+#[cfg(feature = "min-samd51g")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PortEvent {
+    /// Event input 0
+    Ev0 = 0,
+    /// Event input 1
+    Ev1 = 1,
+    /// Event input 2
+    Ev2 = 2,
+    /// Event input 3
+    Ev3 = 3,
+}
+
+#[cfg(feature = "min-samd51g")]
+impl From<PortEvent> for crate::evsys::User {
+    #[inline]
+    fn from(input: PortEvent) -> Self {
+        use crate::evsys::User;
+        match input {
+            PortEvent::Ev0 => User::PortEvent0,
+            PortEvent::Ev1 => User::PortEvent1,
+            PortEvent::Ev2 => User::PortEvent2,
+            PortEvent::Ev3 => User::PortEvent3,
+        }
+    }
+}
+
+/// Action of a [`PortEvent`] on its output pin
+#[cfg(feature = "min-samd51g")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum EventAction {
+    /// Output the level of the event
+    Out = 0,
+    /// Set the pin high on every event
+    Set = 1,
+    /// Set the pin low on every event
+    Clear = 2,
+    /// Toggle the pin on every event
+    Toggle = 3,
+}
+
+/// Pull resistor of a pin in an [`Alternate`] mode
+///
+/// The pull resistor only applies while the peripheral doesn't drive the pin
+/// as an output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pull {
+    /// No pull resistor
+    Floating,
+    /// Pull-down resistor
+    PullDown,
+    /// Pull-up resistor
+    PullUp,
+}
+
 //==============================================================================
 //  Pin modes
 //==============================================================================
@@ -660,6 +723,67 @@ where
     }
 }
 
+#[cfg(feature = "min-samd51g")]
+impl<I: PinId, C: OutputConfig> Pin<I, Output<C>> {
+    /// Let PORT event `input` act on the pin, without CPU involvement
+    ///
+    /// `input` is disconnected from any other pin of the group. It must be
+    /// connected to an event channel, see [`evsys`](crate::evsys). The
+    /// event configuration isn't reset by changes in pin mode; disable it
+    /// first with [`disable_event`](Self::disable_event).
+    ///
+    /// ```no_run
+    /// use atsamd_hal::gpio::v2::{EventAction, PortEvent};
+    ///
+    /// // Toggle PA17 on every TC0 overflow
+    /// let mut channel = evsys.allocate(Generator::Tc0Overflow, Path::Asynchronous).unwrap();
+    /// channel.connect(PortEvent::Ev0.into());
+    /// pa17.enable_event(PortEvent::Ev0, EventAction::Toggle);
+    /// ```
+    #[inline]
+    pub fn enable_event(&mut self, input: PortEvent, action: EventAction) {
+        self.regs.enable_event(input, action);
+    }
+
+    /// Disable PORT event `input`, if it acts on the pin
+    #[inline]
+    pub fn disable_event(&mut self, input: PortEvent) {
+        self.regs.disable_event(input);
+    }
+}
+
+impl<I: PinId, C: AlternateConfig> Pin<I, Alternate<C>> {
+    /// Set the pull resistor of the pin
+    ///
+    /// The pull resistor is reset to floating on every change in pin mode.
+    #[inline]
+    pub fn set_pull(&mut self, pull: Pull) {
+        self.regs.write_pull(pull);
+    }
+
+    /// Read whether the input buffer of the pin is enabled
+    #[inline]
+    pub fn get_input_buffer(&self) -> bool {
+        self.regs.read_input_buffer()
+    }
+
+    /// Enable or disable the input buffer of the pin
+    ///
+    /// With the input buffer enabled, the level of the pin can be read with
+    /// [`is_input_high`](Self::is_input_high) while the peripheral controls
+    /// it. The input buffer is reset to disabled on every change in pin mode.
+    #[inline]
+    pub fn set_input_buffer(&mut self, enabled: bool) {
+        self.regs.write_input_buffer(enabled);
+    }
+
+    /// Read the level of the pin, if its input buffer is enabled
+    #[inline]
+    pub fn is_input_high(&self) -> bool {
+        self._is_high()
+    }
+}
+
 //==============================================================================
 //  PinMode conversions
 //==============================================================================
@@ -962,6 +1086,89 @@ where
     }
 }
 
+//==============================================================================
+//  Embedded HAL 1.0 traits
+//==============================================================================
+
+#[cfg(feature = "ehal1")]
+impl<I, M> embedded_hal_1::digital::ErrorType for Pin<I, M>
+where
+    I: PinId,
+    M: PinMode,
+{
+    type Error = Infallible;
+}
+
+#[cfg(feature = "ehal1")]
+impl<I, C> embedded_hal_1::digital::OutputPin for Pin<I, Output<C>>
+where
+    I: PinId,
+    C: OutputConfig,
+{
+    #[inline]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self._set_high();
+        Ok(())
+    }
+    #[inline]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self._set_low();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ehal1")]
+impl<I> embedded_hal_1::digital::InputPin for Pin<I, ReadableOutput>
+where
+    I: PinId,
+{
+    #[inline]
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self._is_high())
+    }
+    #[inline]
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(self._is_low())
+    }
+}
+
+#[cfg(feature = "ehal1")]
+impl<I, C> embedded_hal_1::digital::InputPin for Pin<I, Input<C>>
+where
+    I: PinId,
+    C: InputConfig,
+{
+    #[inline]
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self._is_high())
+    }
+    #[inline]
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(self._is_low())
+    }
+}
+
+#[cfg(feature = "ehal1")]
+impl<I, C> embedded_hal_1::digital::StatefulOutputPin for Pin<I, Output<C>>
+where
+    I: PinId,
+    C: OutputConfig,
+{
+    #[inline]
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self._is_set_high())
+    }
+    #[inline]
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(self._is_set_low())
+    }
+    #[inline]
+    fn toggle(&mut self) -> Result<(), Self::Error> {
+        self._toggle();
+        Ok(())
+    }
+}
+
 //==============================================================================
 //  Pin definitions
 //==============================================================================
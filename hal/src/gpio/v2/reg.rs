@@ -6,12 +6,16 @@ use crate::pac::port::{
 
 #[cfg(feature = "min-samd51g")]
 use crate::pac::port::group::{
-    CTRL, DIR, DIRCLR, DIRSET, DIRTGL, IN, OUT, OUTCLR, OUTSET, OUTTGL, PINCFG, PMUX, WRCONFIG,
+    CTRL, DIR, DIRCLR, DIRSET, DIRTGL, EVCTRL, IN, OUT, OUTCLR, OUTSET, OUTTGL, PINCFG, PMUX,
+    WRCONFIG,
 };
 
 use crate::pac::PORT;
 
 use super::dynpin::*;
+#[cfg(feature = "min-samd51g")]
+use super::pin::{EventAction, PortEvent};
+use super::pin::Pull;
 
 //==============================================================================
 //  ModeFields
@@ -178,12 +182,37 @@ pub(super) struct GROUP {
     in_: IN,
     ctrl: CTRL,
     wrconfig: WRCONFIG,
+    #[cfg(any(feature = "samd11", feature = "samd21"))]
     _padding1: [u8; 4],
+    #[cfg(feature = "min-samd51g")]
+    evctrl: EVCTRL,
     pmux: [PMUX; 16],
     pincfg: [PINCFG; 32],
     _padding2: [u8; 32],
 }
 
+/// Pointer to the array of [`GROUP`] register blocks
+const GROUPS: *const GROUP = PORT::ptr() as *const _;
+
+/// Access the [`GROUP`] register block of `group`
+#[inline]
+fn group(group: DynGroup) -> &'static GROUP {
+    let offset = match group {
+        DynGroup::A => 0,
+        #[cfg(any(feature = "samd21", feature = "min-samd51g"))]
+        DynGroup::B => 1,
+        #[cfg(feature = "min-samd51n")]
+        DynGroup::C => 2,
+        #[cfg(feature = "min-samd51p")]
+        DynGroup::D => 3,
+    };
+    // Safety: It is safe to create shared references to each PAC register
+    // or register block, because all registers are wrapped in
+    // `UnsafeCell`s. We should never create unique references to the
+    // registers, to prevent any risk of UB.
+    unsafe { &*GROUPS.add(offset) }
+}
+
 //==============================================================================
 //  RegisterInterface
 //==============================================================================
@@ -223,25 +252,9 @@ pub(super) unsafe trait RegisterInterface {
     /// this type.
     fn id(&self) -> DynPinId;
 
-    /// Pointer to the array of [`GROUP`] register blocks
-    const GROUPS: *const GROUP = PORT::ptr() as *const _;
-
     #[inline]
     fn group(&self) -> &GROUP {
-        let offset = match self.id().group {
-            DynGroup::A => 0,
-            #[cfg(any(feature = "samd21", feature = "min-samd51g"))]
-            DynGroup::B => 1,
-            #[cfg(feature = "min-samd51n")]
-            DynGroup::C => 2,
-            #[cfg(feature = "min-samd51p")]
-            DynGroup::D => 3,
-        };
-        // Safety: It is safe to create shared references to each PAC register
-        // or register block, because all registers are wrapped in
-        // `UnsafeCell`s. We should never create unique references to the
-        // registers, to prevent any risk of UB.
-        unsafe { &*Self::GROUPS.add(offset) }
+        group(self.id().group)
     }
 
     #[inline]
@@ -367,4 +380,192 @@ pub(super) unsafe trait RegisterInterface {
     fn write_drive_strength(&mut self, bit: bool) {
         self.pincfg().modify(|_, w| w.drvstr().bit(bit));
     }
+
+    /// Let PORT event `input` act on the pin
+    ///
+    /// EVCTRL configures the four inputs of the group, so it is modified
+    /// within a critical section.
+    #[cfg(feature = "min-samd51g")]
+    #[inline]
+    fn enable_event(&mut self, input: PortEvent, action: EventAction) {
+        let shift = input as u32 * 8;
+        let config = self.id().num as u32 | (action as u32) << 5 | 1 << 7;
+        cortex_m::interrupt::free(|_| {
+            // Safety: Only the byte of `input` is changed, and the PID field
+            // holds the pin of this ID
+            self.group()
+                .evctrl
+                .modify(|r, w| unsafe { w.bits(r.bits() & !(0xff << shift) | config << shift) });
+        });
+    }
+
+    /// Disable PORT event `input`, if it acts on the pin
+    #[cfg(feature = "min-samd51g")]
+    #[inline]
+    fn disable_event(&mut self, input: PortEvent) {
+        let shift = input as u32 * 8;
+        let num = self.id().num as u32;
+        cortex_m::interrupt::free(|_| {
+            // Safety: Only the PORTEI bit of `input` is cleared
+            self.group().evctrl.modify(|r, w| unsafe {
+                if (r.bits() >> shift) & 0x1f == num {
+                    w.bits(r.bits() & !(1 << (shift + 7)))
+                } else {
+                    w.bits(r.bits())
+                }
+            });
+        });
+    }
+
+    /// Read whether the input buffer of a pin is enabled
+    #[inline]
+    fn read_input_buffer(&self) -> bool {
+        self.pincfg().read().inen().bit()
+    }
+
+    /// Enable or disable the input buffer of a pin
+    #[inline]
+    fn write_input_buffer(&mut self, bit: bool) {
+        self.pincfg().modify(|_, w| w.inen().bit(bit));
+    }
+
+    /// Write the pull resistor of a pin, which only applies while the pin is
+    /// not driven as an output
+    #[inline]
+    fn write_pull(&mut self, pull: Pull) {
+        match pull {
+            Pull::Floating => self.pincfg().modify(|_, w| w.pullen().clear_bit()),
+            Pull::PullDown | Pull::PullUp => {
+                self.write_pin(pull == Pull::PullUp);
+                self.pincfg().modify(|_, w| w.pullen().set_bit());
+            }
+        }
+    }
+}
+
+//==============================================================================
+//  GroupRegisters
+//==============================================================================
+
+/// Provide a register interface for a set of pins of a [`GROUP`]
+///
+/// This is the multi-pin equivalent of [`RegisterInterface`]. Every access
+/// is restricted to the pins of `mask`, which the owner of the `struct` must
+/// control.
+pub(super) struct GroupRegisters {
+    group: DynGroup,
+    mask: u32,
+}
+
+impl GroupRegisters {
+    /// Create a new instance of [`GroupRegisters`]
+    ///
+    /// # Safety
+    ///
+    /// Users must have exclusive control of the pins of `mask` in `group`,
+    /// for as long as the `struct` exists
+    #[inline]
+    pub(super) unsafe fn new(group: DynGroup, mask: u32) -> Self {
+        Self { group, mask }
+    }
+
+    #[inline]
+    fn group(&self) -> &GROUP {
+        group(self.group)
+    }
+
+    /// Change the mode of the pins of `mask`
+    ///
+    /// WRCONFIG configures the pins with a single write per half of the
+    /// group, see [`RegisterInterface::change_mode`].
+    #[inline]
+    pub(super) fn change_mode(&mut self, mask: u32, mode: DynPinMode) {
+        let mask = mask & self.mask;
+        let ModeFields {
+            dir,
+            inen,
+            pullen,
+            out,
+            pmuxen,
+            pmux,
+        } = mode.into();
+        for (hwsel, pinmask) in [(false, mask as u16), (true, (mask >> 16) as u16)] {
+            if pinmask == 0 {
+                continue;
+            }
+            // The bit patterns here are guaranteed to be safe, see
+            // `RegisterInterface::change_mode`
+            self.group().wrconfig.write(|w| unsafe {
+                w.hwsel().bit(hwsel);
+                w.wrpincfg().set_bit();
+                w.wrpmux().set_bit();
+                w.pmux().bits(pmux);
+                w.pullen().bit(pullen);
+                w.inen().bit(inen);
+                w.pmuxen().bit(pmuxen);
+                w.pinmask().bits(pinmask)
+            });
+        }
+        // Safety: DIRSET, DIRCLR, OUTSET & OUTCLR are "mask" registers, and we
+        // only write the bits of the controlled pins
+        unsafe {
+            if dir {
+                self.group().dirset.write(|w| w.bits(mask));
+            } else {
+                self.group().dirclr.write(|w| w.bits(mask));
+            }
+            if pullen && out {
+                self.group().outset.write(|w| w.bits(mask));
+            } else if pullen {
+                self.group().outclr.write(|w| w.bits(mask));
+            }
+        }
+    }
+
+    /// Read the logic level of the pins
+    #[inline]
+    pub(super) fn read_pins(&self) -> u32 {
+        self.group().in_.read().bits() & self.mask
+    }
+
+    /// Set the output level of the pins of `mask` high
+    #[inline]
+    pub(super) fn set_pins(&mut self, mask: u32) {
+        // Safety: OUTSET is a "mask" register, and we only write the bits of
+        // the controlled pins
+        unsafe { self.group().outset.write(|w| w.bits(mask & self.mask)) };
+    }
+
+    /// Set the output level of the pins of `mask` low
+    #[inline]
+    pub(super) fn clear_pins(&mut self, mask: u32) {
+        // Safety: OUTCLR is a "mask" register, and we only write the bits of
+        // the controlled pins
+        unsafe { self.group().outclr.write(|w| w.bits(mask & self.mask)) };
+    }
+
+    /// Toggle the output level of the pins of `mask`
+    #[inline]
+    pub(super) fn toggle_pins(&mut self, mask: u32) {
+        // Safety: OUTTGL is a "mask" register, and we only write the bits of
+        // the controlled pins
+        unsafe { self.group().outtgl.write(|w| w.bits(mask & self.mask)) };
+    }
+
+    /// Write the output level of all the pins of `mask` at once
+    ///
+    /// OUT is shared with the other pins of the group, so it is modified
+    /// within a critical section.
+    #[inline]
+    pub(super) fn write_pins(&mut self, mask: u32, value: u32) {
+        let mask = mask & self.mask;
+        cortex_m::interrupt::free(|_| {
+            // Safety: Only the bits of the controlled pins are changed. The
+            // other pins are written through OUTSET, OUTCLR and OUTTGL, or
+            // within a critical section, so they can't be modified in between
+            self.group()
+                .out
+                .modify(|r, w| unsafe { w.bits(r.bits() & !mask | value & mask) });
+        });
+    }
 }
@@ -0,0 +1,633 @@
+//! # I2S - Inter-IC Sound
+//!
+//! The I2S peripheral streams audio samples to and from external codecs,
+//! DACs and microphones. This module drives it as a bus master: the clock
+//! unit 0 generates the serial clock (SCK) and frame sync (FS) signals, and
+//! optionally a master clock (MCK) for the codec.
+//!
+//! Both transmission and reception are supported, using the standard I2S
+//! format with two slots per frame, the left channel first:
+//!
+//! * On SAMD21 chips, serializer 0 transmits on SD0, and serializer 1
+//!   receives on SD1
+//! * On SAMD51 and SAME5x chips, the transmitter uses SDO and the receiver
+//!   uses SDI
+//!
+//! Every sample occupies one 32-bit word, right-adjusted, except for 16-bit
+//! samples: the left and right samples of a frame are then packed in a single
+//! word, the left sample in the lower half.
+//!
+//! The driver does not configure the I2S pins, which must be set to alternate
+//! function G on SAMD21 chips, and J on SAMD51 and SAME5x chips.
+//!
+//! Samples can be transferred one at a time with [`I2s::write`] and
+//! [`I2s::read`]. When the `dma` feature is enabled, [`TxStream`] and
+//! [`RxStream`] stream whole buffers through the DMAC instead. Streams are
+//! double-buffered: a new buffer is handed to the stream as soon as the
+//! previous one is done, while the CPU fills or processes the other one.
+//!
+//! ```no_run
+//! use atsamd_hal::i2s::{Config, I2s, SampleSize, TxStream};
+//!
+//! let gclk0 = clocks.gclk0();
+//! let i2s_clock = clocks.i2s0(&gclk0).unwrap();
+//! let config = Config::new(48_000.hz(), SampleSize::Bits16);
+//! let i2s = I2s::new(peripherals.I2S, &mut peripherals.PM, &i2s_clock, config).unwrap();
+//!
+//! static mut BUFFERS: [[u32; 256]; 2] = [[0; 256]; 2];
+//! let [first, mut second] = unsafe { &mut BUFFERS };
+//!
+//! i2s.enable_tx();
+//! let mut stream = TxStream::new(&i2s, channel, first);
+//! loop {
+//!     fill_with_audio(second);
+//!     second = stream.send(second);
+//! }
+//! ```
+
+use crate::bus_clock::BusClock;
+use crate::clock::I2S0Clock;
+use crate::time::Hertz;
+
+#[cfg(feature = "samd21")]
+use crate::pac::{
+    i2s::clkctrl::SLOTSIZE_A,
+    i2s::serctrl::{DATASIZE_A, SERMODE_A},
+    I2S, PM,
+};
+
+#[cfg(feature = "min-samd51j")]
+use crate::pac::{
+    i2s::clkctrl::SLOTSIZE_A, i2s::rxctrl::DATASIZE_A as RX_DATASIZE_A, i2s::txctrl::DATASIZE_A,
+    I2S, MCLK,
+};
+
+pub mod pdm;
+
+#[cfg(feature = "dma")]
+use crate::dmac::{
+    channel::{AnyChannel, Busy, Channel, Ready},
+    transfer::BufferPair,
+    Buffer, ChId, Transfer, TriggerAction, TriggerSource,
+};
+
+/// Maximum value of the clock dividers, plus one
+#[cfg(feature = "samd21")]
+const MAX_DIVIDER: u32 = 32;
+#[cfg(feature = "min-samd51j")]
+const MAX_DIVIDER: u32 = 64;
+
+/// Peripheral clock controller of the I2S peripheral
+#[cfg(feature = "samd21")]
+pub type PowerManager = PM;
+/// Peripheral clock controller of the I2S peripheral
+#[cfg(feature = "min-samd51j")]
+pub type PowerManager = MCLK;
+
+/// Size of an audio sample
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SampleSize {
+    /// 16-bit samples, packed by frame in a single word
+    Bits16,
+    /// 24-bit samples, one per word
+    Bits24,
+    /// 32-bit samples, one per word
+    Bits32,
+}
+
+impl SampleSize {
+    /// Number of bits in a slot
+    #[inline]
+    fn bits(self) -> u32 {
+        match self {
+            SampleSize::Bits16 => 16,
+            SampleSize::Bits24 => 24,
+            SampleSize::Bits32 => 32,
+        }
+    }
+
+    #[inline]
+    fn slot_size(self) -> SLOTSIZE_A {
+        match self {
+            SampleSize::Bits16 => SLOTSIZE_A::_16,
+            SampleSize::Bits24 => SLOTSIZE_A::_24,
+            SampleSize::Bits32 => SLOTSIZE_A::_32,
+        }
+    }
+
+    #[inline]
+    fn data_size(self) -> DATASIZE_A {
+        match self {
+            SampleSize::Bits16 => DATASIZE_A::_16C,
+            SampleSize::Bits24 => DATASIZE_A::_24,
+            SampleSize::Bits32 => DATASIZE_A::_32,
+        }
+    }
+
+    #[cfg(feature = "min-samd51j")]
+    #[inline]
+    fn rx_data_size(self) -> RX_DATASIZE_A {
+        match self {
+            SampleSize::Bits16 => RX_DATASIZE_A::_16C,
+            SampleSize::Bits24 => RX_DATASIZE_A::_24,
+            SampleSize::Bits32 => RX_DATASIZE_A::_32,
+        }
+    }
+}
+
+/// I2S configuration
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Frame rate, in frames per second
+    pub sample_rate: Hertz,
+    /// Size of the samples
+    pub sample_size: SampleSize,
+    /// Ratio between the master clock and the sample rate, usually 256
+    ///
+    /// The master clock is only output when this is `Some`.
+    pub master_clock_ratio: Option<u32>,
+}
+
+impl Config {
+    /// Create a configuration that doesn't output a master clock
+    #[inline]
+    pub fn new(sample_rate: impl Into<Hertz>, sample_size: SampleSize) -> Self {
+        Self {
+            sample_rate: sample_rate.into(),
+            sample_size,
+            master_clock_ratio: None,
+        }
+    }
+}
+
+/// I2S errors
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The requested clocks can't be derived from the generic clock
+    ClockDivider,
+    /// The transmitter ran out of samples
+    Underrun,
+    /// A received sample was lost
+    Overrun,
+}
+
+/// Compute the divider value generating `target` from `input`, rounded to
+/// the nearest frequency
+fn clock_divider(input: u32, target: u32) -> Option<u8> {
+    if target == 0 {
+        return None;
+    }
+    let divider = (input + target / 2) / target;
+    if divider == 0 || divider > MAX_DIVIDER {
+        None
+    } else {
+        Some((divider - 1) as u8)
+    }
+}
+
+/// I2S master
+pub struct I2s {
+    i2s: I2S,
+}
+
+impl I2s {
+    /// Reset and configure the peripheral, and start generating the clocks
+    ///
+    /// Transmission and reception are disabled, see [`I2s::enable_tx`] and
+    /// [`I2s::enable_rx`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ClockDivider`] if the serial clock or the master
+    /// clock can't be derived from the frequency of `clock`.
+    pub fn new(
+        i2s: I2S,
+        pm: &mut PowerManager,
+        clock: &I2S0Clock,
+        config: Config,
+    ) -> Result<Self, Error> {
+        let gclk = clock.freq().0;
+        let sample_rate = config.sample_rate.0;
+        let sample_size = config.sample_size;
+        let sck_div =
+            clock_divider(gclk, sample_rate * 2 * sample_size.bits()).ok_or(Error::ClockDivider)?;
+        let mck_div = match config.master_clock_ratio {
+            Some(ratio) => {
+                Some(clock_divider(gclk, sample_rate * ratio).ok_or(Error::ClockDivider)?)
+            }
+            None => None,
+        };
+
+        let i2s = Self::reset(i2s, pm);
+        i2s.i2s.clkctrl[0].write(|w| {
+            w.slotsize().variant(sample_size.slot_size());
+            // SAFETY: Two slots per frame
+            unsafe { w.nbslots().bits(1) };
+            w.fswidth().half();
+            w.bitdelay().i2s();
+            w.mcksel().gclk();
+            w.scksel().mckdiv();
+            w.fssel().sckdiv();
+            // SAFETY: The dividers are checked against MAX_DIVIDER
+            unsafe { w.mckdiv().bits(sck_div) };
+            if let Some(mck_div) = mck_div {
+                w.mcken().set_bit();
+                unsafe { w.mckoutdiv().bits(mck_div) };
+            }
+            w
+        });
+
+        #[cfg(feature = "samd21")]
+        {
+            i2s.i2s.serctrl[0].write(|w| {
+                w.sermode().variant(SERMODE_A::TX);
+                w.clksel().clk0();
+                w.slotadj().left();
+                w.datasize().variant(sample_size.data_size())
+            });
+            i2s.i2s.serctrl[1].write(|w| {
+                w.sermode().variant(SERMODE_A::RX);
+                w.clksel().clk0();
+                w.slotadj().left();
+                w.datasize().variant(sample_size.data_size())
+            });
+        }
+        #[cfg(feature = "min-samd51j")]
+        {
+            i2s.i2s.txctrl.write(|w| {
+                w.sermode().tx();
+                w.clksel().clk0();
+                w.slotadj().left();
+                w.datasize().variant(sample_size.data_size())
+            });
+            i2s.i2s.rxctrl.write(|w| {
+                w.sermode().rx();
+                w.clksel().clk0();
+                w.slotadj().left();
+                w.datasize().variant(sample_size.rx_data_size())
+            });
+        }
+
+        i2s.start_clock();
+        Ok(i2s)
+    }
+
+    /// Enable the peripheral clock and reset the peripheral
+    fn reset(i2s: I2S, pm: &mut PowerManager) -> Self {
+        I2S::enable_bus_clock(pm);
+
+        i2s.ctrla.write(|w| w.swrst().set_bit());
+        while i2s.syncbusy.read().swrst().bit_is_set() {}
+        Self { i2s }
+    }
+
+    /// Enable the peripheral and start generating the clocks of clock unit 0
+    fn start_clock(&self) {
+        self.i2s
+            .ctrla
+            .write(|w| w.enable().set_bit().cken0().set_bit());
+        self.sync();
+    }
+
+    /// Disable the peripheral and return the register block
+    pub fn free(self, pm: &mut PowerManager) -> I2S {
+        self.i2s.ctrla.write(|w| w.swrst().set_bit());
+        while self.i2s.syncbusy.read().swrst().bit_is_set() {}
+
+        I2S::disable_bus_clock(pm);
+
+        self.i2s
+    }
+
+    #[inline]
+    fn sync(&self) {
+        while self.i2s.syncbusy.read().bits() != 0 {}
+    }
+
+    /// Start transmitting
+    ///
+    /// The transmitter sends zeros until samples are written.
+    #[inline]
+    pub fn enable_tx(&self) {
+        #[cfg(feature = "samd21")]
+        self.i2s.ctrla.modify(|_, w| w.seren0().set_bit());
+        #[cfg(feature = "min-samd51j")]
+        self.i2s.ctrla.modify(|_, w| w.txen().set_bit());
+        self.sync();
+    }
+
+    /// Stop transmitting
+    #[inline]
+    pub fn disable_tx(&self) {
+        #[cfg(feature = "samd21")]
+        self.i2s.ctrla.modify(|_, w| w.seren0().clear_bit());
+        #[cfg(feature = "min-samd51j")]
+        self.i2s.ctrla.modify(|_, w| w.txen().clear_bit());
+        self.sync();
+    }
+
+    /// Start receiving
+    #[inline]
+    pub fn enable_rx(&self) {
+        #[cfg(feature = "samd21")]
+        self.i2s.ctrla.modify(|_, w| w.seren1().set_bit());
+        #[cfg(feature = "min-samd51j")]
+        self.i2s.ctrla.modify(|_, w| w.rxen().set_bit());
+        self.sync();
+    }
+
+    /// Stop receiving
+    #[inline]
+    pub fn disable_rx(&self) {
+        #[cfg(feature = "samd21")]
+        self.i2s.ctrla.modify(|_, w| w.seren1().clear_bit());
+        #[cfg(feature = "min-samd51j")]
+        self.i2s.ctrla.modify(|_, w| w.rxen().clear_bit());
+        self.sync();
+    }
+
+    /// Check and clear the transmit underrun flag
+    #[inline]
+    pub fn take_underrun(&self) -> bool {
+        let underrun = self.i2s.intflag.read().txur0().bit_is_set();
+        if underrun {
+            self.i2s.intflag.write(|w| w.txur0().set_bit());
+        }
+        underrun
+    }
+
+    /// Check and clear the receive overrun flag
+    #[inline]
+    pub fn take_overrun(&self) -> bool {
+        #[cfg(feature = "samd21")]
+        let overrun = self.i2s.intflag.read().rxor1().bit_is_set();
+        #[cfg(feature = "min-samd51j")]
+        let overrun = self.i2s.intflag.read().rxor0().bit_is_set();
+        if overrun {
+            #[cfg(feature = "samd21")]
+            self.i2s.intflag.write(|w| w.rxor1().set_bit());
+            #[cfg(feature = "min-samd51j")]
+            self.i2s.intflag.write(|w| w.rxor0().set_bit());
+        }
+        overrun
+    }
+
+    /// Write the next sample word to the transmitter
+    ///
+    /// Returns [`Error::Underrun`] once, after the transmitter ran out of
+    /// samples.
+    pub fn write(&mut self, word: u32) -> nb::Result<(), Error> {
+        if self.take_underrun() {
+            return Err(nb::Error::Other(Error::Underrun));
+        }
+        if self.i2s.intflag.read().txrdy0().bit_is_clear() {
+            return Err(nb::Error::WouldBlock);
+        }
+        // SAFETY: Any value is a valid sample
+        unsafe { core::ptr::write_volatile(tx_data_ptr(), word) };
+        Ok(())
+    }
+
+    /// Read the next sample word from the receiver
+    ///
+    /// Returns [`Error::Overrun`] once, after a sample was lost.
+    pub fn read(&mut self) -> nb::Result<u32, Error> {
+        if self.take_overrun() {
+            return Err(nb::Error::Other(Error::Overrun));
+        }
+        #[cfg(feature = "samd21")]
+        let ready = self.i2s.intflag.read().rxrdy1().bit_is_set();
+        #[cfg(feature = "min-samd51j")]
+        let ready = self.i2s.intflag.read().rxrdy0().bit_is_set();
+        if !ready {
+            return Err(nb::Error::WouldBlock);
+        }
+        // SAFETY: Reading the data register has no other side effect than
+        // clearing the ready flag
+        Ok(unsafe { core::ptr::read_volatile(rx_data_ptr()) })
+    }
+}
+
+/// Address of the transmit data register
+#[inline]
+fn tx_data_ptr() -> *mut u32 {
+    // SAFETY: Only the address of the register is taken
+    let i2s = unsafe { &*I2S::ptr() };
+    #[cfg(feature = "samd21")]
+    let ptr = i2s.data[0].as_ptr();
+    #[cfg(feature = "min-samd51j")]
+    let ptr = i2s.txdata.as_ptr();
+    ptr
+}
+
+/// Address of the receive data register
+#[inline]
+fn rx_data_ptr() -> *mut u32 {
+    // SAFETY: Only the address of the register is taken
+    let i2s = unsafe { &*I2S::ptr() };
+    #[cfg(feature = "samd21")]
+    let ptr = i2s.data[1].as_ptr();
+    #[cfg(feature = "min-samd51j")]
+    let ptr = i2s.rxdata.as_ptr();
+    ptr
+}
+
+//=============================================================================
+// DMA streams
+//=============================================================================
+
+#[cfg(feature = "dma")]
+#[cfg(feature = "samd21")]
+const TX_TRIGGER: TriggerSource = TriggerSource::I2S_TX_0;
+#[cfg(feature = "dma")]
+#[cfg(feature = "samd21")]
+const RX_TRIGGER: TriggerSource = TriggerSource::I2S_RX_1;
+#[cfg(feature = "dma")]
+#[cfg(feature = "min-samd51j")]
+const TX_TRIGGER: TriggerSource = TriggerSource::I2S_TX_0;
+#[cfg(feature = "dma")]
+#[cfg(feature = "min-samd51j")]
+const RX_TRIGGER: TriggerSource = TriggerSource::I2S_RX_0;
+
+#[cfg(all(feature = "dma", feature = "samd21"))]
+const TRIGGER_ACTION: TriggerAction = TriggerAction::BEAT;
+#[cfg(all(feature = "dma", feature = "min-samd51j"))]
+const TRIGGER_ACTION: TriggerAction = TriggerAction::BURST;
+
+/// Transmit data register, as a DMA buffer
+#[cfg(feature = "dma")]
+#[doc(hidden)]
+pub struct TxData(());
+
+#[cfg(feature = "dma")]
+unsafe impl Buffer for TxData {
+    type Beat = u32;
+
+    #[inline]
+    fn dma_ptr(&mut self) -> *mut u32 {
+        tx_data_ptr()
+    }
+
+    #[inline]
+    fn incrementing(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn buffer_len(&self) -> usize {
+        1
+    }
+}
+
+/// Receive data register, as a DMA buffer
+#[cfg(feature = "dma")]
+#[doc(hidden)]
+pub struct RxData(());
+
+#[cfg(feature = "dma")]
+unsafe impl Buffer for RxData {
+    type Beat = u32;
+
+    #[inline]
+    fn dma_ptr(&mut self) -> *mut u32 {
+        rx_data_ptr()
+    }
+
+    #[inline]
+    fn incrementing(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn buffer_len(&self) -> usize {
+        1
+    }
+}
+
+/// Double-buffered DMA transmission
+///
+/// The stream borrows the [`I2s`], so that the peripheral can't be freed
+/// while the DMAC is using it. Every buffer must have the same length.
+#[cfg(feature = "dma")]
+pub struct TxStream<'a, Id: ChId, B: Buffer<Beat = u32> + 'static> {
+    transfer: Transfer<Channel<Id, Busy>, BufferPair<B, TxData>>,
+    _i2s: &'a I2s,
+}
+
+#[cfg(feature = "dma")]
+impl<'a, Id: ChId, B: Buffer<Beat = u32> + 'static> TxStream<'a, Id, B> {
+    /// Start transmitting `buffer`
+    ///
+    /// Don't forget to enable the transmitter, see [`I2s::enable_tx`].
+    #[inline]
+    pub fn new<Ch>(_i2s: &'a I2s, channel: Ch, buffer: B) -> Self
+    where
+        Ch: AnyChannel<Status = Ready, Id = Id>,
+    {
+        let transfer = Transfer::new(channel, buffer, TxData(()), false)
+            .expect("the data register accepts any buffer length")
+            .begin(TX_TRIGGER, TRIGGER_ACTION);
+        Self { transfer, _i2s }
+    }
+
+    /// Check whether the current buffer has been transmitted
+    ///
+    /// When it has, [`TxStream::send`] doesn't block.
+    #[inline]
+    pub fn is_ready(&mut self) -> bool {
+        self.transfer.complete()
+    }
+
+    /// Wait until the current buffer has been transmitted, start
+    /// transmitting `buffer` and return the transmitted buffer
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` is not as long as the current buffer.
+    #[inline]
+    pub fn send(&mut self, buffer: B) -> B {
+        while !self.transfer.complete() {}
+        self.transfer
+            .recycle_destination(buffer)
+            .expect("stream buffers must all have the same length")
+    }
+
+    /// Stop the stream immediately and return the DMA channel and the
+    /// current buffer
+    #[inline]
+    pub fn stop(self) -> (Channel<Id, Ready>, B) {
+        let (channel, buffer, _) = self.transfer.stop();
+        (channel, buffer)
+    }
+}
+
+/// Double-buffered DMA reception
+///
+/// The stream borrows the [`I2s`], so that the peripheral can't be freed
+/// while the DMAC is using it. Every buffer must have the same length.
+#[cfg(feature = "dma")]
+pub struct RxStream<'a, Id: ChId, B: Buffer<Beat = u32> + 'static> {
+    transfer: Transfer<Channel<Id, Busy>, BufferPair<RxData, B>>,
+    _i2s: &'a I2s,
+}
+
+#[cfg(feature = "dma")]
+impl<'a, Id: ChId, B: Buffer<Beat = u32> + 'static> RxStream<'a, Id, B> {
+    /// Start receiving into `buffer`
+    ///
+    /// Don't forget to enable the receiver, see [`I2s::enable_rx`].
+    #[inline]
+    pub fn new<Ch>(_i2s: &'a I2s, channel: Ch, buffer: B) -> Self
+    where
+        Ch: AnyChannel<Status = Ready, Id = Id>,
+    {
+        let transfer = Transfer::new(channel, RxData(()), buffer, false)
+            .expect("the data register accepts any buffer length")
+            .begin(RX_TRIGGER, TRIGGER_ACTION);
+        Self { transfer, _i2s }
+    }
+
+    /// Check whether the current buffer has been filled
+    ///
+    /// When it has, [`RxStream::receive`] doesn't block.
+    #[inline]
+    pub fn is_ready(&mut self) -> bool {
+        self.transfer.complete()
+    }
+
+    /// Wait until the current buffer has been filled, start receiving into
+    /// `buffer` and return the filled buffer
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` is not as long as the current buffer.
+    #[inline]
+    pub fn receive(&mut self, buffer: B) -> B {
+        while !self.transfer.complete() {}
+        self.transfer
+            .recycle_source(buffer)
+            .expect("stream buffers must all have the same length")
+    }
+
+    /// Stop the stream immediately and return the DMA channel and the
+    /// current buffer
+    #[inline]
+    pub fn stop(self) -> (Channel<Id, Ready>, B) {
+        let (channel, _, buffer) = self.transfer.stop();
+        (channel, buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_divider_rounds_to_nearest() {
+        // 48 kHz stereo 16-bit: 1.536 MHz bit clock
+        assert_eq!(clock_divider(12_288_000, 1_536_000), Some(7));
+        assert_eq!(clock_divider(12_000_000, 1_536_000), Some(7));
+        assert_eq!(clock_divider(500_000, 1_536_000), None);
+        assert_eq!(clock_divider(48_000_000, 1_000), None);
+    }
+}
@@ -0,0 +1,297 @@
+//! # PDM microphone capture
+//!
+//! In PDM mode, the I2S receiver samples one or two PDM microphones sharing
+//! a data line. The clock unit 0 generates the PDM clock on SCK, the left
+//! microphone is sampled on its rising edge and the right microphone on its
+//! falling edge.
+//!
+//! Received words alternate between the left and the right microphone. Every
+//! word holds 16 PDM bits in its lower half, the first received bit being the
+//! most significant one.
+//!
+//! When the `dma` feature is enabled, [`PdmCapture`] continuously captures
+//! the words into a ring buffer. [`CicDecimator`] turns the PDM bit stream of
+//! a microphone into PCM samples.
+//!
+//! ```no_run
+//! use atsamd_hal::i2s::{pdm::{CicDecimator, PdmCapture}, I2s};
+//!
+//! let gclk0 = clocks.gclk0();
+//! let i2s_clock = clocks.i2s0(&gclk0).unwrap();
+//! // 16 kHz PCM output with a decimation factor of 64
+//! let i2s = I2s::new_pdm(peripherals.I2S, &mut peripherals.MCLK, &i2s_clock, 1_024_000.hz())
+//!     .unwrap();
+//! i2s.enable_rx();
+//!
+//! static mut RING: [u32; 512] = [0; 512];
+//! let mut capture = PdmCapture::new(&i2s, channel, unsafe { &mut RING });
+//! let mut cic = CicDecimator::new(64);
+//!
+//! let mut words = [0; 32];
+//! loop {
+//!     let count = capture.read(&mut words);
+//!     // Only keep the left microphone
+//!     for word in words[..count].iter().step_by(2) {
+//!         if let Some(sample) = cic.push(*word as u16) {
+//!             process(sample);
+//!         }
+//!     }
+//! }
+//! ```
+
+use super::{clock_divider, Error, I2s, PowerManager};
+use crate::clock::I2S0Clock;
+use crate::pac::I2S;
+use crate::time::Hertz;
+
+#[cfg(feature = "dma")]
+use super::{RxData, RX_TRIGGER, TRIGGER_ACTION};
+#[cfg(feature = "dma")]
+use crate::dmac::{
+    channel::{AnyChannel, Busy, Channel, Ready},
+    transfer::{BufferPair, RawBuffer},
+    ChId, Transfer,
+};
+
+impl I2s {
+    /// Reset the peripheral, configure the receiver for PDM microphones and
+    /// start generating the PDM clock
+    ///
+    /// The PCM sample rate is `pdm_clock` divided by the decimation factor
+    /// of the filter, usually 64. Reception is disabled, see
+    /// [`I2s::enable_rx`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ClockDivider`] if the PDM clock can't be derived from
+    /// the frequency of `clock`.
+    pub fn new_pdm(
+        i2s: I2S,
+        pm: &mut PowerManager,
+        clock: &I2S0Clock,
+        pdm_clock: impl Into<Hertz>,
+    ) -> Result<Self, Error> {
+        let sck_div =
+            clock_divider(clock.freq().0, pdm_clock.into().0).ok_or(Error::ClockDivider)?;
+
+        let i2s = Self::reset(i2s, pm);
+        i2s.i2s.clkctrl[0].write(|w| {
+            w.slotsize()._32();
+            // SAFETY: Two slots per frame, one per microphone
+            unsafe { w.nbslots().bits(1) };
+            w.fswidth().half();
+            w.bitdelay().lj();
+            w.mcksel().gclk();
+            w.scksel().mckdiv();
+            w.fssel().sckdiv();
+            // SAFETY: The divider is checked against MAX_DIVIDER
+            unsafe { w.mckdiv().bits(sck_div) }
+        });
+
+        #[cfg(feature = "samd21")]
+        i2s.i2s.serctrl[1].write(|w| {
+            w.sermode().pdm2();
+            w.clksel().clk0();
+            w.datasize()._16()
+        });
+        #[cfg(feature = "min-samd51j")]
+        i2s.i2s.rxctrl.write(|w| {
+            w.sermode().pdm2();
+            w.clksel().clk0();
+            w.datasize()._16()
+        });
+
+        i2s.start_clock();
+        Ok(i2s)
+    }
+}
+
+//=============================================================================
+// Ring buffer capture
+//=============================================================================
+
+/// Continuous DMA capture of the received words into a ring buffer
+///
+/// The DMAC keeps overwriting the oldest words, so [`PdmCapture::read`] must
+/// be called before the ring buffer fills up to avoid losing data.
+#[cfg(feature = "dma")]
+pub struct PdmCapture<'a, Id: ChId> {
+    transfer: Transfer<Channel<Id, Busy>, BufferPair<RxData, RawBuffer<u32>>>,
+    ptr: *mut u32,
+    len: usize,
+    read_index: usize,
+    _i2s: &'a I2s,
+}
+
+#[cfg(feature = "dma")]
+impl<'a, Id: ChId> PdmCapture<'a, Id> {
+    /// Start capturing into `buffer`
+    ///
+    /// Don't forget to enable the receiver, see [`I2s::enable_rx`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` is empty or longer than 65535 words.
+    pub fn new<Ch>(_i2s: &'a I2s, channel: Ch, buffer: &'static mut [u32]) -> Self
+    where
+        Ch: AnyChannel<Status = Ready, Id = Id>,
+    {
+        assert!(!buffer.is_empty() && buffer.len() <= u16::MAX as usize);
+        let ptr = buffer.as_mut_ptr();
+        let len = buffer.len();
+        let ring = RawBuffer::slice(ptr, len);
+        let transfer = Transfer::new(channel, RxData(()), ring, true)
+            .expect("the data register accepts any buffer length")
+            .begin(RX_TRIGGER, TRIGGER_ACTION);
+        Self {
+            transfer,
+            ptr,
+            len,
+            read_index: 0,
+            _i2s,
+        }
+    }
+
+    /// Index of the next word written by the DMAC
+    #[inline]
+    fn write_index(&self) -> usize {
+        let len = self.len;
+        (len - self.transfer.remaining_beats()) % len
+    }
+
+    /// Number of captured words that haven't been read yet
+    #[inline]
+    pub fn available(&self) -> usize {
+        let len = self.len;
+        (self.write_index() + len - self.read_index) % len
+    }
+
+    /// Copy the oldest unread words into `words`, and return how many were
+    /// copied
+    pub fn read(&mut self, words: &mut [u32]) -> usize {
+        let count = self.available().min(words.len());
+        core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+        let len = self.len;
+        for word in words[..count].iter_mut() {
+            // SAFETY: The index is within the ring buffer, and the word has
+            // already been written by the DMAC
+            *word = unsafe { core::ptr::read_volatile(self.ptr.add(self.read_index)) };
+            self.read_index = (self.read_index + 1) % len;
+        }
+        count
+    }
+
+    /// Stop capturing and return the DMA channel and the ring buffer
+    pub fn stop(self) -> (Channel<Id, Ready>, &'static mut [u32]) {
+        let (channel, _, _) = self.transfer.stop();
+        // SAFETY: The ring was built from a `&'static mut [u32]`, which the
+        // DMAC doesn't access anymore
+        let buffer = unsafe { core::slice::from_raw_parts_mut(self.ptr, self.len) };
+        (channel, buffer)
+    }
+}
+
+//=============================================================================
+// CIC decimation
+//=============================================================================
+
+/// Order of the [`CicDecimator`]
+const CIC_ORDER: usize = 4;
+
+/// Fourth-order CIC decimation filter, turning a PDM bit stream into PCM
+/// samples
+///
+/// Every PDM bit counts as +1 when set and -1 when cleared. The filter
+/// outputs one sample every `decimation` bits, ranging from
+/// `-decimation^4` to `decimation^4`. A simple low-pass filter is usually
+/// applied afterwards to compensate for the droop of the CIC filter.
+#[derive(Clone, Debug)]
+pub struct CicDecimator {
+    integrators: [i32; CIC_ORDER],
+    combs: [i32; CIC_ORDER],
+    decimation: u32,
+    count: u32,
+}
+
+impl CicDecimator {
+    /// Create a filter outputting a sample every `decimation` bits
+    ///
+    /// # Panics
+    ///
+    /// Panics if `decimation` is not a multiple of 16 between 16 and 128.
+    pub fn new(decimation: u32) -> Self {
+        assert!(decimation % 16 == 0 && (16..=128).contains(&decimation));
+        Self {
+            integrators: [0; CIC_ORDER],
+            combs: [0; CIC_ORDER],
+            decimation,
+            count: 0,
+        }
+    }
+
+    /// Feed 16 PDM bits, most significant bit first
+    ///
+    /// Returns a PCM sample every `decimation / 16` calls.
+    pub fn push(&mut self, bits: u16) -> Option<i32> {
+        for bit in (0..16).rev() {
+            let mut value = if bits & (1 << bit) != 0 { 1 } else { -1 };
+            // The wrapping arithmetic is exact as long as the output fits
+            for integrator in self.integrators.iter_mut() {
+                *integrator = integrator.wrapping_add(value);
+                value = *integrator;
+            }
+        }
+
+        self.count += 16;
+        if self.count < self.decimation {
+            return None;
+        }
+        self.count = 0;
+
+        let mut value = self.integrators[CIC_ORDER - 1];
+        for comb in self.combs.iter_mut() {
+            let previous = *comb;
+            *comb = value;
+            value = value.wrapping_sub(previous);
+        }
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settle(cic: &mut CicDecimator, bits: u16) -> i32 {
+        let mut output = 0;
+        // The filter settles after `CIC_ORDER` output periods
+        for _ in 0..(CIC_ORDER as u32 + 1) * cic.decimation / 16 {
+            if let Some(sample) = cic.push(bits) {
+                output = sample;
+            }
+        }
+        output
+    }
+
+    #[test]
+    fn cic_full_scale() {
+        let mut cic = CicDecimator::new(64);
+        assert_eq!(settle(&mut cic, 0xFFFF), 64 * 64 * 64 * 64);
+        let mut cic = CicDecimator::new(128);
+        assert_eq!(settle(&mut cic, 0x0000), -128 * 128 * 128 * 128);
+    }
+
+    #[test]
+    fn cic_silence() {
+        let mut cic = CicDecimator::new(64);
+        assert_eq!(settle(&mut cic, 0xAAAA), 0);
+    }
+
+    #[test]
+    fn cic_output_rate() {
+        let mut cic = CicDecimator::new(32);
+        assert_eq!(cic.push(0xFFFF), None);
+        assert!(cic.push(0xFFFF).is_some());
+        assert_eq!(cic.push(0xFFFF), None);
+    }
+}
@@ -96,10 +96,32 @@ macro_rules! dbgprint {
     ($($arg:tt)*) => {{}};
 }
 
+#[cfg(feature = "device")]
+pub mod ac;
+#[cfg(feature = "device")]
+pub mod bod;
+#[cfg(feature = "device")]
+pub mod bus_clock;
+#[cfg(feature = "device")]
+pub mod dac;
 #[cfg(feature = "device")]
 pub mod delay;
 #[cfg(feature = "device")]
+pub mod evsys;
+#[cfg(feature = "device")]
 pub mod gpio;
+#[cfg(any(feature = "samd21", feature = "min-samd51j"))]
+pub mod i2s;
+#[cfg(feature = "log")]
+pub mod logger;
+#[cfg(feature = "min-samd51g")]
+pub mod mpu;
+pub mod neopixel;
+#[cfg(feature = "device")]
+pub mod onewire;
+pub mod panic_persist;
+#[cfg(feature = "device")]
+pub mod power;
 #[cfg(feature = "device")]
 pub mod prelude;
 #[cfg(feature = "device")]
@@ -109,6 +131,8 @@ pub mod sercom;
 pub mod sleeping_delay;
 #[cfg(feature = "device")]
 pub mod spi_common;
+#[cfg(feature = "stack")]
+pub mod stack;
 pub mod time;
 pub mod timer_params;
 pub mod timer_traits;
@@ -116,6 +140,9 @@ pub mod timer_traits;
 #[cfg(all(feature = "unproven", feature = "dma"))]
 pub mod dmac;
 
+#[cfg(feature = "async")]
+pub mod async_hal;
+
 #[cfg(all(feature = "usb", feature = "samd11"))]
 compile_error!("'usb' is enabled, but USB isn't supported on SAMD11");
 
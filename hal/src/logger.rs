@@ -0,0 +1,234 @@
+//! # Logger - `log` backend
+//!
+//! [`init`] installs a [`log`] logger, which writes the log records to a
+//! [`Sink`]:
+//!
+//! - [`Rtt`], the RTT up channel read by the debug probe, with the `use_rtt`
+//!   feature
+//! - [`Itm`], a stimulus port of the ITM, output on the SWO pin of SAMD51
+//!   and SAME5x chips
+//! - any other channel implementing [`Sink`], such as the USB serial port of
+//!   the BSPs
+//!
+//! Sinks needing to be serviced, such as USB devices, are polled with
+//! [`poll`], to be called from their interrupt handlers.
+//!
+//! ```no_run
+//! use atsamd_hal::logger::{self, log, Rtt};
+//!
+//! let sink = cortex_m::singleton!(: Rtt = Rtt::new()).unwrap();
+//! logger::init(sink, log::LevelFilter::Info).unwrap();
+//! log::info!("clocks configured");
+//! ```
+//!
+//! With the `panic-log` feature, the HAL provides the panic handler. It
+//! writes the panic message to the sink, then keeps polling it with
+//! interrupts disabled, so that buffered channels are flushed to the host.
+//! Along with the `panic-persist` feature, the sink is polled
+//! [`PANIC_FLUSH_POLLS`] times instead, before the panic handler of the
+//! [`panic_persist`](crate::panic_persist) module resets the chip.
+
+use core::cell::RefCell;
+use core::fmt::{self, Write};
+
+use cortex_m::interrupt;
+
+pub use log;
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+/// Output channel of the logger
+pub trait Sink {
+    /// Write `s` to the channel
+    ///
+    /// Called within a critical section, this function should not block
+    /// for long; data which doesn't fit in the channel may be dropped.
+    fn write_str(&mut self, s: &str);
+
+    /// Service the channel
+    ///
+    /// Called by [`poll`], and by the panic handler. Does nothing by default.
+    #[inline]
+    fn poll(&mut self) {}
+}
+
+/// Sink of the logger
+struct SinkCell(RefCell<Option<&'static mut dyn Sink>>);
+
+// SAFETY: The sink is only accessed within critical sections, on a single
+// core, so never concurrently
+unsafe impl Sync for SinkCell {}
+
+static SINK: SinkCell = SinkCell(RefCell::new(None));
+
+/// Call `f` with the sink, unless it is already in use, such as when logging
+/// from [`Sink::write_str`]
+fn with_sink(f: impl FnOnce(&mut dyn Sink)) {
+    interrupt::free(|_| {
+        if let Ok(mut sink) = SINK.0.try_borrow_mut() {
+            if let Some(sink) = sink.as_mut() {
+                f(*sink);
+            }
+        }
+    });
+}
+
+static LOGGER: Logger = Logger;
+
+struct Logger;
+
+impl Log for Logger {
+    #[inline]
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        // The records are filtered by the maximum level
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        with_sink(|sink| {
+            write_record(&mut SinkWriter(sink), record).ok();
+        });
+    }
+
+    #[inline]
+    fn flush(&self) {}
+}
+
+/// [`fmt::Write`] adapter of a [`Sink`]
+struct SinkWriter<'a>(&'a mut dyn Sink);
+
+impl Write for SinkWriter<'_> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_str(s);
+        Ok(())
+    }
+}
+
+fn write_record(w: &mut impl Write, record: &Record) -> fmt::Result {
+    write!(
+        w,
+        "[{:<5} {}] {}\r\n",
+        record.level(),
+        record.target(),
+        record.args()
+    )
+}
+
+/// Install the logger, writing the records up to `level` to `sink`
+///
+/// Returns an error if a logger is already installed.
+pub fn init(sink: &'static mut dyn Sink, level: LevelFilter) -> Result<(), SetLoggerError> {
+    interrupt::free(|_| {
+        // SAFETY: The logger is set within a critical section, so no other
+        // thread can set it at the same time
+        unsafe {
+            log::set_logger_racy(&LOGGER)?;
+            log::set_max_level_racy(level);
+        }
+        SINK.0.replace(Some(sink));
+        Ok(())
+    })
+}
+
+/// Service the sink of the logger
+///
+/// Call this function from the interrupt handlers of sinks needing it, such
+/// as USB devices.
+#[inline]
+pub fn poll() {
+    with_sink(|sink| sink.poll());
+}
+
+/// Non-blocking RTT sink
+///
+/// Records are dropped while the RTT buffer is full, such as when no debug
+/// probe is attached.
+#[cfg(feature = "use_rtt")]
+pub struct Rtt(jlink_rtt::NonBlockingOutput);
+
+#[cfg(feature = "use_rtt")]
+impl Rtt {
+    /// Create the RTT sink
+    #[inline]
+    pub fn new() -> Self {
+        Self(jlink_rtt::NonBlockingOutput::new())
+    }
+}
+
+#[cfg(feature = "use_rtt")]
+impl Default for Rtt {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "use_rtt")]
+impl Sink for Rtt {
+    #[inline]
+    fn write_str(&mut self, s: &str) {
+        self.0.write_str(s).ok();
+    }
+}
+
+/// ITM sink, writing to a stimulus port
+///
+/// The ITM and the SWO pin are configured by the debugger.
+#[cfg(feature = "min-samd51g")]
+pub struct Itm {
+    itm: crate::pac::ITM,
+    port: usize,
+}
+
+#[cfg(feature = "min-samd51g")]
+impl Itm {
+    /// Create the ITM sink, writing to stimulus `port`, usually 0
+    ///
+    /// # Panics
+    ///
+    /// Panics if `port` is higher than 31.
+    #[inline]
+    pub fn new(itm: crate::pac::ITM, port: usize) -> Self {
+        assert!(port < 32);
+        Self { itm, port }
+    }
+
+    /// Release the ITM
+    #[inline]
+    pub fn free(self) -> crate::pac::ITM {
+        self.itm
+    }
+}
+
+#[cfg(feature = "min-samd51g")]
+impl Sink for Itm {
+    #[inline]
+    fn write_str(&mut self, s: &str) {
+        cortex_m::itm::write_str(&mut self.itm.stim[self.port], s);
+    }
+}
+
+/// Number of times the sink is polled after the panic message, before the
+/// reset, with the `panic-persist` feature
+pub const PANIC_FLUSH_POLLS: u32 = 1_000_000;
+
+/// Write the panic message to the sink, then poll it `polls` times, or
+/// forever if `None`
+///
+/// Called by the panic handler, with interrupts disabled, so the sink is
+/// accessed within a critical section.
+#[cfg(feature = "panic-log")]
+pub(crate) fn log_panic(info: &core::panic::PanicInfo, polls: Option<u32>) {
+    // The sink is still borrowed if the panic occurred while writing to it
+    if let Ok(mut sink) = SINK.0.try_borrow_mut() {
+        if let Some(sink) = sink.as_mut() {
+            write!(SinkWriter(*sink), "panic: {}\r\n", info).ok();
+            match polls {
+                Some(polls) => (0..polls).for_each(|_| sink.poll()),
+                None => loop {
+                    sink.poll();
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,388 @@
+//! # MPU - Memory protection regions
+//!
+//! The Cortex-M4 MPU of the SAMD51 and SAME5x chips checks every access
+//! against up to eight regions, and raises a MemManage fault, or a HardFault
+//! if that fault isn't enabled, for the accesses they forbid. Faulting right
+//! away turns the silent corruptions of memory into bugs found in testing:
+//!
+//! - [`Mpu::guard_stack`] makes the bottom of the stack read-only, catching
+//!   stack overflows before they overwrite the static data, with the `stack`
+//!   feature
+//! - [`Mpu::protect_bootloader`] makes the bootloader read-only, below the
+//!   application flash given by the `memory.x` of the BSPs
+//! - [`Mpu::protect_user_page`] makes the NVM user page read-only, as stray
+//!   writes to the flash fill the page buffer of the NVM controller
+//! - [`Mpu::deny_peripheral`] forbids the accesses to a peripheral, for
+//!   example one owned by another task
+//! - [`Mpu::set_region`] configures any other [`Region`]
+//!
+//! The regions are used by privileged code too, and the default memory map
+//! applies elsewhere. When regions overlap, the one with the highest number
+//! applies.
+//!
+//! ```no_run
+//! use atsamd_hal::mpu::{Access, Mpu, Region};
+//! use atsamd_hal::stack::StackRegion;
+//!
+//! let mut mpu = Mpu::new(core.MPU);
+//! // With the `stack` feature
+//! mpu.guard_stack(&StackRegion::from_linker());
+//! mpu.protect_bootloader().unwrap();
+//! mpu.deny_peripheral(0, atsamd_hal::pac::SERCOM2::ptr()).unwrap();
+//! mpu.set_region(1, Region::sram(0x2000_0000, 1024).unwrap().access(Access::ReadOnly))
+//!     .unwrap();
+//! mpu.enable();
+//! ```
+
+use cortex_m::peripheral::MPU;
+
+#[cfg(feature = "stack")]
+use crate::stack::StackRegion;
+
+/// Number of MPU regions
+pub const REGIONS: u8 = 8;
+
+/// Region used by [`Mpu::guard_stack`]
+pub const STACK_GUARD_REGION: u8 = 7;
+/// Region used by [`Mpu::protect_bootloader`]
+pub const BOOTLOADER_REGION: u8 = 6;
+/// Region used by [`Mpu::protect_user_page`]
+pub const USER_PAGE_REGION: u8 = 5;
+
+/// Default size of the stack guard region, in bytes
+///
+/// An exception entry pushes 32 bytes, or 104 bytes with the FPU context, so
+/// a guard this large is not jumped over by a single frame.
+pub const STACK_GUARD_SIZE: u32 = 256;
+
+/// Size of the address range of a peripheral, in bytes
+pub const PERIPHERAL_SIZE: u32 = 1024;
+
+/// Address of the NVM user page
+const USER_PAGE_ADDR: u32 = 0x0080_4000;
+/// Size of the NVM user page, in bytes
+const USER_PAGE_SIZE: u32 = 512;
+
+/// Errors of the MPU configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The size isn't a power of 2 of at least 32 bytes
+    InvalidSize,
+    /// The base address isn't aligned to the size
+    Unaligned,
+    /// The region number is [`REGIONS`] or higher
+    InvalidRegion,
+}
+
+/// Accesses allowed in a [`Region`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// No access
+    None,
+    /// Reads only
+    ReadOnly,
+    /// Reads and writes
+    ReadWrite,
+}
+
+/// Memory type of a [`Region`], matching the default memory map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Memory {
+    /// Normal memory, write-through
+    Flash,
+    /// Normal shareable memory, write-back
+    Sram,
+    /// Shareable device memory
+    Device,
+}
+
+/// Memory region of the MPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    base: u32,
+    size: u32,
+    memory: Memory,
+    access: Access,
+    executable: bool,
+}
+
+impl Region {
+    fn new(base: u32, size: u32, memory: Memory, executable: bool) -> Result<Self, Error> {
+        if size < 32 || !size.is_power_of_two() {
+            return Err(Error::InvalidSize);
+        }
+        if base & (size - 1) != 0 {
+            return Err(Error::Unaligned);
+        }
+        Ok(Self {
+            base,
+            size,
+            memory,
+            access: Access::ReadWrite,
+            executable,
+        })
+    }
+
+    /// Region of the flash, executable
+    ///
+    /// The size must be a power of 2 of at least 32 bytes, and the base
+    /// address aligned to it.
+    #[inline]
+    pub fn flash(base: u32, size: u32) -> Result<Self, Error> {
+        Self::new(base, size, Memory::Flash, true)
+    }
+
+    /// Region of the RAM, not executable
+    ///
+    /// The size must be a power of 2 of at least 32 bytes, and the base
+    /// address aligned to it.
+    #[inline]
+    pub fn sram(base: u32, size: u32) -> Result<Self, Error> {
+        Self::new(base, size, Memory::Sram, false)
+    }
+
+    /// Region of the peripherals, not executable
+    ///
+    /// The size must be a power of 2 of at least 32 bytes, and the base
+    /// address aligned to it.
+    #[inline]
+    pub fn device(base: u32, size: u32) -> Result<Self, Error> {
+        Self::new(base, size, Memory::Device, false)
+    }
+
+    /// Set the accesses allowed, reads and writes by default
+    #[inline]
+    pub fn access(mut self, access: Access) -> Self {
+        self.access = access;
+        self
+    }
+
+    /// Set whether the instructions of the region can be executed
+    #[inline]
+    pub fn executable(mut self, executable: bool) -> Self {
+        self.executable = executable;
+        self
+    }
+
+    /// Value of the `RASR` register, enabling the region
+    fn rasr(&self) -> u32 {
+        let xn = !self.executable as u32;
+        let ap = match self.access {
+            Access::None => 0b000,
+            Access::ReadOnly => 0b110,
+            Access::ReadWrite => 0b011,
+        };
+        // TEX = 0, then the S, C and B bits
+        let attributes = match self.memory {
+            Memory::Flash => 0b010,
+            Memory::Sram => 0b111,
+            Memory::Device => 0b101,
+        };
+        let size = self.size.trailing_zeros() - 1;
+        xn << 28 | ap << 24 | attributes << 16 | size << 1 | 1
+    }
+}
+
+/// Memory protection unit
+///
+/// See the [module-level documentation](self) for more details.
+pub struct Mpu {
+    mpu: MPU,
+}
+
+impl Mpu {
+    /// Take the MPU, disabling it and its regions
+    pub fn new(mut mpu: MPU) -> Self {
+        Self::set_enabled(&mut mpu, false);
+        let mut mpu = Self { mpu };
+        for number in 0..REGIONS {
+            mpu.clear_region(number).ok();
+        }
+        mpu
+    }
+
+    /// Release the MPU, still configured
+    #[inline]
+    pub fn free(self) -> MPU {
+        self.mpu
+    }
+
+    /// Enable the MPU, with the default memory map as background region
+    #[inline]
+    pub fn enable(&mut self) {
+        Self::set_enabled(&mut self.mpu, true);
+    }
+
+    /// Disable the MPU
+    #[inline]
+    pub fn disable(&mut self) {
+        Self::set_enabled(&mut self.mpu, false);
+    }
+
+    fn set_enabled(mpu: &mut MPU, enabled: bool) {
+        cortex_m::asm::dmb();
+        // SAFETY: PRIVDEFENA keeps the default memory map for the accesses
+        // outside the regions
+        unsafe { mpu.ctrl.write(if enabled { 1 << 2 | 1 } else { 0 }) };
+        cortex_m::asm::dsb();
+        cortex_m::asm::isb();
+    }
+
+    /// Configure region `number`
+    pub fn set_region(&mut self, number: u8, region: Region) -> Result<(), Error> {
+        if number >= REGIONS {
+            return Err(Error::InvalidRegion);
+        }
+        cortex_m::asm::dmb();
+        // SAFETY: The region is checked on creation
+        unsafe {
+            self.mpu.rnr.write(number as u32);
+            self.mpu.rbar.write(region.base);
+            self.mpu.rasr.write(region.rasr());
+        }
+        cortex_m::asm::dsb();
+        cortex_m::asm::isb();
+        Ok(())
+    }
+
+    /// Disable region `number`
+    pub fn clear_region(&mut self, number: u8) -> Result<(), Error> {
+        if number >= REGIONS {
+            return Err(Error::InvalidRegion);
+        }
+        cortex_m::asm::dmb();
+        // SAFETY: Disabling a region only allows more accesses
+        unsafe {
+            self.mpu.rnr.write(number as u32);
+            self.mpu.rasr.write(0);
+        }
+        cortex_m::asm::dsb();
+        cortex_m::asm::isb();
+        Ok(())
+    }
+
+    /// Forbid every access to the peripheral whose registers are at
+    /// `registers`, such as `pac::SERCOM2::ptr()`, with region `number`
+    ///
+    /// The region spans the first [`PERIPHERAL_SIZE`] bytes from `registers`,
+    /// which hold the registers of a peripheral. Returns [`Error::Unaligned`]
+    /// if `registers` isn't aligned to [`PERIPHERAL_SIZE`].
+    pub fn deny_peripheral<T>(&mut self, number: u8, registers: *const T) -> Result<(), Error> {
+        let region = Region::device(registers as u32, PERIPHERAL_SIZE)?.access(Access::None);
+        self.set_region(number, region)
+    }
+
+    /// Make the lowest [`STACK_GUARD_SIZE`] bytes of `stack`, aligned up,
+    /// read-only, with region [`STACK_GUARD_REGION`]
+    ///
+    /// See [`guard_stack_with_size`](Self::guard_stack_with_size).
+    #[cfg(feature = "stack")]
+    #[inline]
+    pub fn guard_stack(&mut self, stack: &StackRegion) {
+        self.guard_stack_with_size(stack, STACK_GUARD_SIZE).unwrap();
+    }
+
+    /// Make the lowest `size` bytes of `stack`, aligned up to `size`,
+    /// read-only, with region [`STACK_GUARD_REGION`]
+    ///
+    /// `size` must be a power of 2 of at least 32 bytes. Returns
+    /// [`Error::InvalidSize`] if the guard doesn't fit in `stack`. Paint the
+    /// stack before guarding it.
+    ///
+    /// A stack overflow faults on the first write to the guard. The
+    /// exception entry then pushes its frame below the stack pointer, into
+    /// the guard, and faults again: the MemManage fault escalates to a
+    /// HardFault, whose entry faults in turn, and the core locks up until
+    /// reset. Enable the watchdog to recover from the lockup; the fault can't
+    /// be recorded, as no handler runs.
+    ///
+    /// [`StackRegion::from_linker`] starts at `__sheap`, the start of the
+    /// heap given by `cortex-m-rt`. With a heap allocator using the memory
+    /// from `__sheap`, the guard makes the bottom of the heap read-only;
+    /// guard a [`StackRegion`] starting above the heap instead.
+    #[cfg(feature = "stack")]
+    pub fn guard_stack_with_size(&mut self, stack: &StackRegion, size: u32) -> Result<(), Error> {
+        let guard = stack_guard(stack, size)?;
+        self.set_region(STACK_GUARD_REGION, guard)
+    }
+
+    /// Make the flash below the vector table of the application read-only,
+    /// with region [`BOOTLOADER_REGION`]
+    ///
+    /// The BSPs place the application after the bootloader, such as at 16K
+    /// on the Feather M4. Returns an error if the size of the bootloader isn't
+    /// a power of 2, and does nothing without bootloader.
+    #[cfg(not(test))]
+    pub fn protect_bootloader(&mut self) -> Result<(), Error> {
+        extern "C" {
+            static __vector_table: u32;
+        }
+        // SAFETY: Only the address of the symbol is used; taking it is safe
+        // from Rust 1.82
+        #[allow(unused_unsafe)]
+        let size = unsafe { core::ptr::addr_of!(__vector_table) } as u32;
+        if size == 0 {
+            return Ok(());
+        }
+        let region = Region::flash(0, size)?.access(Access::ReadOnly);
+        self.set_region(BOOTLOADER_REGION, region)
+    }
+
+    /// Make the NVM user page read-only, with region [`USER_PAGE_REGION`]
+    ///
+    /// Clear the region before writing the user page with the NVM driver.
+    pub fn protect_user_page(&mut self) {
+        let region = Region::flash(USER_PAGE_ADDR, USER_PAGE_SIZE)
+            .unwrap()
+            .access(Access::ReadOnly)
+            .executable(false);
+        self.set_region(USER_PAGE_REGION, region).unwrap();
+    }
+}
+
+/// Read-only region of the lowest `size` bytes of `stack`, aligned up
+#[cfg(feature = "stack")]
+fn stack_guard(stack: &StackRegion, size: u32) -> Result<Region, Error> {
+    if size < 32 || !size.is_power_of_two() {
+        return Err(Error::InvalidSize);
+    }
+    let base = (stack.bottom() as u32)
+        .checked_add(size - 1)
+        .ok_or(Error::InvalidSize)?
+        & !(size - 1);
+    if base as u64 + size as u64 > stack.top() as u64 {
+        return Err(Error::InvalidSize);
+    }
+    Ok(Region::sram(base, size)?.access(Access::ReadOnly))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region() {
+        assert_eq!(Region::sram(0x2000_0000, 48), Err(Error::InvalidSize));
+        assert_eq!(Region::sram(0x2000_0010, 32), Err(Error::Unaligned));
+        let region = Region::flash(0, 16 * 1024)
+            .unwrap()
+            .access(Access::ReadOnly);
+        // Read-only, normal write-through, 2^(13 + 1) bytes
+        assert_eq!(region.rasr(), 0x0602_001B);
+        let region = Region::device(0x4200_1400, 1024)
+            .unwrap()
+            .access(Access::None);
+        assert_eq!(region.rasr(), 0x1005_0013);
+    }
+
+    #[cfg(feature = "stack")]
+    #[test]
+    fn stack_guard() {
+        let stack = unsafe { StackRegion::new(0x2000_1010 as *mut u32, 0x2000_1400 as *mut u32) };
+        let guard = super::stack_guard(&stack, STACK_GUARD_SIZE).unwrap();
+        assert_eq!(guard.base, 0x2000_1100);
+        assert_eq!(guard.size, 256);
+        assert_eq!(super::stack_guard(&stack, 1024), Err(Error::InvalidSize));
+        assert_eq!(super::stack_guard(&stack, 100), Err(Error::InvalidSize));
+    }
+}
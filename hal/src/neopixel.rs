@@ -0,0 +1,183 @@
+//! # Neopixel - WS2812 LEDs driven by an SPI MOSI pin
+//!
+//! WS2812 LEDs read their data as pulses, whose width encodes each bit. Clocked
+//! at [`SPI_FREQUENCY`], every data bit becomes three SPI bits, `110` for a 1
+//! and `100` for a 0, so the SPI peripheral generates the timing whatever the
+//! CPU clock. Only the MOSI pin is connected to the LEDs.
+//!
+//! The encoded data can be written by any blocking SPI implementation with
+//! [`Neopixel`]:
+//!
+//! ```no_run
+//! use atsamd_hal::neopixel::{Neopixel, SPI_FREQUENCY};
+//!
+//! let spi = spi_master(&mut clocks, SPI_FREQUENCY, sercom, &mut pm, sck, mosi, miso);
+//! let mut leds = Neopixel::new(spi);
+//! leds.write([(255, 0, 0), (0, 255, 0), (0, 0, 255)].iter().copied())?;
+//! ```
+//!
+//! or, with the `dma` feature, by a DMA transfer to a [`v2::spi::Spi`] with
+//! [`write_with_dma`], so that the CPU is free while the LEDs are updated.
+//! The frame sent by the transfer must be `'static`, and is sized for the
+//! strip with [`frame_len`]:
+//!
+//! ```no_run
+//! use atsamd_hal::neopixel::{frame_len, write_with_dma};
+//!
+//! static mut FRAME: [u8; frame_len(3)] = [0; frame_len(3)];
+//!
+//! let frame = unsafe { &mut FRAME[..] };
+//! let colors = [(255, 0, 0), (0, 255, 0), (0, 0, 255)];
+//! let transfer = write_with_dma(spi, frame, colors.iter().copied(), channel, ());
+//! let (channel, frame, spi) = transfer.wait();
+//! ```
+//!
+//! [`v2::spi::Spi`]: crate::sercom::v2::spi::Spi
+
+use crate::ehal::blocking::spi::Write;
+use crate::time::Hertz;
+
+#[cfg(feature = "dma")]
+use crate::{
+    dmac::{
+        channel::{AnyChannel, Busy, CallbackStatus, Channel, Ready},
+        transfer::BufferPair,
+        Buffer, Transfer,
+    },
+    sercom::v2::spi::{self, Spi},
+};
+
+/// SPI clock frequency producing the WS2812 bit timing
+pub const SPI_FREQUENCY: Hertz = Hertz(2_400_000);
+
+/// Number of SPI bytes encoding one RGB LED
+pub const BYTES_PER_LED: usize = 9;
+
+/// Number of idle SPI bytes latching the colors into the LEDs, about 300 µs
+const RESET_BYTES: usize = 90;
+
+/// Encode the `(red, green, blue)` color of one LED into SPI bytes, in the
+/// green, red, blue order expected by WS2812 LEDs
+pub fn encode((red, green, blue): (u8, u8, u8)) -> [u8; BYTES_PER_LED] {
+    let mut bytes = [0; BYTES_PER_LED];
+    for (chunk, color) in bytes.chunks_exact_mut(3).zip([green, red, blue]) {
+        let bits = (0..8).rev().fold(0u32, |bits, i| {
+            bits << 3 | if color & (1 << i) != 0 { 0b110 } else { 0b100 }
+        });
+        chunk.copy_from_slice(&bits.to_be_bytes()[1..]);
+    }
+    bytes
+}
+
+/// Number of bytes of a frame holding the colors of `leds` LEDs, followed by
+/// the idle bytes latching them
+pub const fn frame_len(leds: usize) -> usize {
+    leds * BYTES_PER_LED + RESET_BYTES
+}
+
+/// Encode the `(red, green, blue)` colors of the LEDs into `frame`, starting
+/// from the one connected to the MOSI pin, and fill the rest of the frame with
+/// idle bytes to latch them
+///
+/// Colors that don't fit in the frame, leaving room for the latch, are ignored.
+/// Returns the number of LEDs encoded.
+pub fn encode_frame<I>(frame: &mut [u8], colors: I) -> usize
+where
+    I: IntoIterator<Item = (u8, u8, u8)>,
+{
+    let leds = frame.len().saturating_sub(RESET_BYTES) / BYTES_PER_LED;
+    let (data, idle) = frame.split_at_mut(leds * BYTES_PER_LED);
+    let mut count = 0;
+    for (chunk, color) in data.chunks_exact_mut(BYTES_PER_LED).zip(colors) {
+        chunk.copy_from_slice(&encode(color));
+        count += 1;
+    }
+    data[count * BYTES_PER_LED..].fill(0);
+    idle.fill(0);
+    count
+}
+
+/// Encode the `(red, green, blue)` colors of the LEDs into `frame` with
+/// [`encode_frame`], then start a DMA transfer sending it to `spi`, clocked at
+/// [`SPI_FREQUENCY`]
+///
+/// The LEDs are updated once the transfer completes, which returns the
+/// channel, the frame and the SPI.
+#[cfg(feature = "dma")]
+#[allow(clippy::type_complexity)]
+pub fn write_with_dma<C, A, Ch, W, I>(
+    spi: Spi<C, A>,
+    frame: &'static mut [u8],
+    colors: I,
+    channel: Ch,
+    waker: W,
+) -> Transfer<Channel<Ch::Id, Busy>, BufferPair<&'static mut [u8], Spi<C, A>>, W>
+where
+    C: spi::ValidConfig<Word = u8>,
+    A: spi::Transmit,
+    Spi<C, A>: Buffer<Beat = u8>,
+    Ch: AnyChannel<Status = Ready>,
+    W: FnOnce(CallbackStatus) + 'static,
+    I: IntoIterator<Item = (u8, u8, u8)>,
+{
+    encode_frame(frame, colors);
+    spi.send_with_dma(frame, channel, waker)
+}
+
+/// WS2812 LED strip, driven by the MOSI pin of an SPI clocked at
+/// [`SPI_FREQUENCY`]
+pub struct Neopixel<SPI> {
+    spi: SPI,
+}
+
+impl<SPI: Write<u8>> Neopixel<SPI> {
+    /// Drive LEDs with `spi`
+    pub fn new(spi: SPI) -> Self {
+        Self { spi }
+    }
+
+    /// Release the SPI
+    pub fn free(self) -> SPI {
+        self.spi
+    }
+
+    /// Write the `(red, green, blue)` colors of the LEDs, starting from the
+    /// one connected to the MOSI pin, then latch them
+    pub fn write<I>(&mut self, colors: I) -> Result<(), SPI::Error>
+    where
+        I: IntoIterator<Item = (u8, u8, u8)>,
+    {
+        for color in colors {
+            self.spi.write(&encode(color))?;
+        }
+        self.spi.write(&[0; RESET_BYTES])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_bits() {
+        // Green first, then red, then blue
+        let bytes = encode((0x00, 0xff, 0x80));
+        assert_eq!(bytes[..3], [0b1101_1011, 0b0110_1101, 0b1011_0110]);
+        assert_eq!(bytes[3..6], [0b1001_0010, 0b0100_1001, 0b0010_0100]);
+        assert_eq!(bytes[6..], [0b1101_0010, 0b0100_1001, 0b0010_0100]);
+    }
+
+    #[test]
+    fn encode_frame_latch() {
+        let mut frame = [0xff; frame_len(2) + 4];
+        let colors = [(1, 2, 3), (4, 5, 6), (7, 8, 9)];
+        assert_eq!(encode_frame(&mut frame, colors), 2);
+        assert_eq!(frame[..BYTES_PER_LED], encode((1, 2, 3)));
+        assert_eq!(frame[BYTES_PER_LED..2 * BYTES_PER_LED], encode((4, 5, 6)));
+        assert!(frame[2 * BYTES_PER_LED..].iter().all(|&byte| byte == 0));
+
+        let mut frame = [0xff; frame_len(2)];
+        assert_eq!(encode_frame(&mut frame, [(1, 2, 3)]), 1);
+        assert!(frame[BYTES_PER_LED..].iter().all(|&byte| byte == 0));
+    }
+}
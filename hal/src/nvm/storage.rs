@@ -0,0 +1,151 @@
+//! [`embedded_storage`] traits implementation for the internal flash
+//!
+//! The traits are implemented by [`FlashRegion`], a part of the flash set
+//! aside for storage, so that safe code can't erase or overwrite the running
+//! application or the bootloader. Offsets are relative to the start of the
+//! region.
+#![cfg_attr(
+    feature = "min-samd51g",
+    doc = "\nErasing and writing the boot protected or SmartEEPROM areas returns an error."
+)]
+
+use core::ops::Range;
+
+use embedded_storage::nor_flash::{
+    check_erase, check_read, check_write, ErrorType, NorFlash, NorFlashError, NorFlashErrorKind,
+    ReadNorFlash,
+};
+
+#[cfg(feature = "min-samd51g")]
+use super::{EraseGranularity, BLOCKSIZE};
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+use super::ROWSIZE;
+use super::{retrieve_flash_size, Error, Nvm, PAGESIZE};
+
+/// Size of the erase granularity of the flash, in bytes
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+const ERASE_SIZE: u32 = ROWSIZE;
+/// Size of the erase granularity of the flash, in bytes
+#[cfg(feature = "min-samd51g")]
+const ERASE_SIZE: u32 = BLOCKSIZE;
+
+impl NorFlashError for Error {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            Error::Alignment => NorFlashErrorKind::NotAligned,
+            Error::NonFlash => NorFlashErrorKind::OutOfBounds,
+            _ => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+impl From<NorFlashErrorKind> for Error {
+    fn from(kind: NorFlashErrorKind) -> Self {
+        match kind {
+            NorFlashErrorKind::NotAligned => Error::Alignment,
+            _ => Error::NonFlash,
+        }
+    }
+}
+
+/// Part of the flash exposed through the [`embedded_storage`] traits
+pub struct FlashRegion<'a> {
+    nvm: &'a mut Nvm,
+    range: Range<u32>,
+}
+
+impl<'a> FlashRegion<'a> {
+    /// Expose the `range` of flash addresses for storage
+    ///
+    /// Returns [`Error::Alignment`] if the bounds of `range` are not
+    /// multiples of the erase size, and [`Error::NonFlash`] if `range` is
+    /// empty or extends beyond the flash.
+    ///
+    /// # Safety
+    /// `range` must not contain code or data that is in use, such as the
+    /// running application or the bootloader.
+    pub unsafe fn new(nvm: &'a mut Nvm, range: Range<u32>) -> Result<Self, Error> {
+        if range.start % ERASE_SIZE != 0 || range.end % ERASE_SIZE != 0 {
+            return Err(Error::Alignment);
+        }
+        if range.start >= range.end || range.end > retrieve_flash_size() {
+            return Err(Error::NonFlash);
+        }
+        Ok(Self { nvm, range })
+    }
+
+    /// Return the flash addresses of the region
+    #[inline]
+    pub fn range(&self) -> Range<u32> {
+        self.range.clone()
+    }
+}
+
+impl ErrorType for FlashRegion<'_> {
+    type Error = Error;
+}
+
+impl ReadNorFlash for FlashRegion<'_> {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        check_read(self, offset, bytes.len())?;
+        let address = (self.range.start + offset) as usize;
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            // SAFETY: The whole range was checked to be inside the region
+            *byte = unsafe { core::ptr::read_volatile((address + i) as *const u8) };
+        }
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        (self.range.end - self.range.start) as usize
+    }
+}
+
+impl NorFlash for FlashRegion<'_> {
+    const WRITE_SIZE: usize = 4;
+    const ERASE_SIZE: usize = ERASE_SIZE as usize;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        check_erase(self, from, to)?;
+        let address = self.range.start + from;
+        let count = (to - from) / ERASE_SIZE;
+        // SAFETY: The rows are inside the region, which holds nothing in use
+        // by the contract of `new`
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        unsafe {
+            self.nvm.erase(address, count)
+        }
+        #[cfg(feature = "min-samd51g")]
+        unsafe {
+            self.nvm.erase(address, count, EraseGranularity::Block)
+        }
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        check_write(self, offset, bytes.len())?;
+
+        // `bytes` may not be word aligned, so go through an aligned buffer one
+        // page at a time
+        let mut buffer = [0_u32; (PAGESIZE / 4) as usize];
+        let mut address = self.range.start + offset;
+        let mut remaining = bytes;
+        while !remaining.is_empty() {
+            let page_remaining = (PAGESIZE - address % PAGESIZE) as usize;
+            let (chunk, rest) = remaining.split_at(page_remaining.min(remaining.len()));
+            let words = &mut buffer[..chunk.len() / 4];
+            for (word, bytes) in words.iter_mut().zip(chunk.chunks_exact(4)) {
+                *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            }
+
+            // SAFETY: The words are inside the region, which holds nothing in
+            // use by the contract of `new`
+            unsafe { self.nvm.write_from_slice(address, words)? };
+
+            address += chunk.len() as u32;
+            remaining = rest;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,632 @@
+//! # One-Wire - Dallas 1-Wire bus master
+//!
+//! 1-Wire devices, such as DS18B20 temperature sensors, share a single data
+//! line, pulled up to VDD. The master starts each time slot by pulling the
+//! line low; the length of the pulse writes a bit, and the devices answer by
+//! holding the line low while the master samples it.
+//!
+//! [`OneWire`] implements the protocol on top of a [`OneWireBus`], which
+//! generates the reset pulse and the time slots:
+//!
+//! - [`UartBus`] switches the baud rate of a UART, whose TX pin drives the
+//!   line through a Schottky diode or an open-drain buffer, and whose RX pin
+//!   reads it. The reset pulse is a `0xF0` frame at 9600 baud, and each time
+//!   slot a `0xFF` or `0x00` frame at 115200 baud; the frames received back
+//!   hold the answers of the devices. The SERCOM generates the timing, so
+//!   interrupts don't disturb the bus.
+//! - [`PinBus`] drives a [`DynPin`] low, and releases it as an input, with
+//!   the bit timing given by a [`DelayUs`] implementation, such as a timer.
+//!   Each time slot runs within a critical section.
+//!
+//! Besides the ROM commands addressing the devices, [`OneWire::search`]
+//! enumerates the ROM codes of all the devices on the bus:
+//!
+//! ```no_run
+//! use atsamd_hal::onewire::{OneWire, Search, UartBus};
+//!
+//! let mut bus = OneWire::new(UartBus::new(uart));
+//! let mut search = Search::new();
+//! while let Some(rom) = bus.search(&mut search)? {
+//!     if rom.family() == 0x28 {
+//!         // Start a DS18B20 temperature conversion
+//!         bus.select(&rom)?;
+//!         bus.write_byte(0x44)?;
+//!     }
+//! }
+//! ```
+//!
+//! [`DelayUs`]: crate::ehal::blocking::delay::DelayUs
+//! [`DynPin`]: crate::gpio::v2::DynPin
+
+use crate::ehal::serial::{Read, Write};
+use crate::sercom::v2::uart::{self, BaudMode, Duplex, Flags, Oversampling, Uart, ValidConfig};
+use crate::time::Hertz;
+
+#[cfg(feature = "unproven")]
+use crate::ehal::{
+    blocking::delay::DelayUs,
+    digital::v2::{InputPin, OutputPin},
+};
+#[cfg(feature = "unproven")]
+use crate::gpio::v2::{dynpin, DynPin};
+
+/// `READ ROM` command, addressing the only device on the bus
+pub const READ_ROM: u8 = 0x33;
+/// `MATCH ROM` command, addressing one device by its ROM code
+pub const MATCH_ROM: u8 = 0x55;
+/// `SKIP ROM` command, addressing all the devices on the bus
+pub const SKIP_ROM: u8 = 0xCC;
+/// `SEARCH ROM` command, enumerating the devices on the bus
+pub const SEARCH_ROM: u8 = 0xF0;
+/// `ALARM SEARCH` command, enumerating the devices in alarm state
+pub const ALARM_SEARCH: u8 = 0xEC;
+
+/// Bit-level access to a 1-Wire bus
+pub trait OneWireBus {
+    /// Errors of the bus
+    type Error;
+
+    /// Send a reset pulse, returning whether any device answered with a
+    /// presence pulse
+    fn reset(&mut self) -> Result<bool, Self::Error>;
+
+    /// Run one time slot, writing `bit` and returning the bit read on the
+    /// line
+    ///
+    /// A device can only answer when writing a 1, so reading a bit is
+    /// touching a 1.
+    fn touch_bit(&mut self, bit: bool) -> Result<bool, Self::Error>;
+}
+
+/// Errors of the 1-Wire master
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+    /// Error of the [`OneWireBus`]
+    Bus(E),
+    /// No device answered the reset pulse
+    NoPresence,
+    /// The CRC of the ROM code read is invalid
+    Crc,
+}
+
+impl<E> From<E> for Error<E> {
+    #[inline]
+    fn from(error: E) -> Self {
+        Error::Bus(error)
+    }
+}
+
+/// Dallas/Maxim CRC-8 of `bytes`, with polynomial `x^8 + x^5 + x^4 + 1`
+///
+/// The last byte of the ROM codes is the CRC of the others, so the CRC of a
+/// whole ROM code is 0.
+pub fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 1 != 0 {
+                crc >> 1 ^ 0x8C
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// 64-bit ROM code of a 1-Wire device, in the order it is transmitted: the
+/// family code, the serial number, then the CRC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rom(pub [u8; 8]);
+
+impl Rom {
+    /// Family code, identifying the type of device
+    #[inline]
+    pub fn family(&self) -> u8 {
+        self.0[0]
+    }
+
+    /// 48-bit serial number
+    #[inline]
+    pub fn serial(&self) -> [u8; 6] {
+        let mut serial = [0; 6];
+        serial.copy_from_slice(&self.0[1..7]);
+        serial
+    }
+
+    /// Return whether the CRC of the ROM code is valid
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        crc8(&self.0) == 0
+    }
+
+    #[inline]
+    fn bit(&self, index: usize) -> bool {
+        self.0[index / 8] & 1 << (index % 8) != 0
+    }
+
+    #[inline]
+    fn set_bit(&mut self, index: usize, bit: bool) {
+        if bit {
+            self.0[index / 8] |= 1 << (index % 8);
+        } else {
+            self.0[index / 8] &= !(1 << (index % 8));
+        }
+    }
+}
+
+/// State of a ROM search, carried over the calls to [`OneWire::search`]
+#[derive(Debug, Clone)]
+pub struct Search {
+    command: u8,
+    rom: Rom,
+    last_discrepancy: usize,
+    done: bool,
+}
+
+impl Search {
+    /// Search all the devices, with [`SEARCH_ROM`]
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_command(SEARCH_ROM)
+    }
+
+    /// Search the devices in alarm state, with [`ALARM_SEARCH`]
+    #[inline]
+    pub fn alarm() -> Self {
+        Self::with_command(ALARM_SEARCH)
+    }
+
+    #[inline]
+    fn with_command(command: u8) -> Self {
+        Self {
+            command,
+            rom: Rom::default(),
+            last_discrepancy: 0,
+            done: false,
+        }
+    }
+
+    /// Restart the search from the first device
+    #[inline]
+    pub fn restart(&mut self) {
+        *self = Self::with_command(self.command);
+    }
+}
+
+impl Default for Search {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 1-Wire bus master
+///
+/// See the [module-level documentation](self) for more details.
+pub struct OneWire<B> {
+    bus: B,
+}
+
+impl<B: OneWireBus> OneWire<B> {
+    /// Drive the 1-Wire `bus`
+    #[inline]
+    pub fn new(bus: B) -> Self {
+        Self { bus }
+    }
+
+    /// Release the bus
+    #[inline]
+    pub fn free(self) -> B {
+        self.bus
+    }
+
+    /// Send a reset pulse
+    ///
+    /// Returns [`Error::NoPresence`] if no device answered.
+    pub fn reset(&mut self) -> Result<(), Error<B::Error>> {
+        if self.bus.reset()? {
+            Ok(())
+        } else {
+            Err(Error::NoPresence)
+        }
+    }
+
+    /// Write one bit
+    #[inline]
+    pub fn write_bit(&mut self, bit: bool) -> Result<(), Error<B::Error>> {
+        self.bus.touch_bit(bit)?;
+        Ok(())
+    }
+
+    /// Read one bit
+    #[inline]
+    pub fn read_bit(&mut self) -> Result<bool, Error<B::Error>> {
+        Ok(self.bus.touch_bit(true)?)
+    }
+
+    /// Write one byte, least significant bit first
+    pub fn write_byte(&mut self, byte: u8) -> Result<(), Error<B::Error>> {
+        for i in 0..8 {
+            self.bus.touch_bit(byte & 1 << i != 0)?;
+        }
+        Ok(())
+    }
+
+    /// Read one byte, least significant bit first
+    pub fn read_byte(&mut self) -> Result<u8, Error<B::Error>> {
+        let mut byte = 0;
+        for i in 0..8 {
+            if self.bus.touch_bit(true)? {
+                byte |= 1 << i;
+            }
+        }
+        Ok(byte)
+    }
+
+    /// Write `bytes`
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error<B::Error>> {
+        bytes.iter().try_for_each(|&byte| self.write_byte(byte))
+    }
+
+    /// Read bytes into `buffer`
+    pub fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<(), Error<B::Error>> {
+        for byte in buffer {
+            *byte = self.read_byte()?;
+        }
+        Ok(())
+    }
+
+    /// Reset the bus and address the device with the `rom` code, with
+    /// [`MATCH_ROM`]
+    pub fn select(&mut self, rom: &Rom) -> Result<(), Error<B::Error>> {
+        self.reset()?;
+        self.write_byte(MATCH_ROM)?;
+        self.write_bytes(&rom.0)
+    }
+
+    /// Reset the bus and address all the devices, with [`SKIP_ROM`]
+    pub fn skip_rom(&mut self) -> Result<(), Error<B::Error>> {
+        self.reset()?;
+        self.write_byte(SKIP_ROM)
+    }
+
+    /// Reset the bus and read the ROM code of its only device, with
+    /// [`READ_ROM`]
+    ///
+    /// With more than one device on the bus, the codes collide, which is
+    /// usually caught by the CRC check.
+    pub fn read_rom(&mut self) -> Result<Rom, Error<B::Error>> {
+        self.reset()?;
+        self.write_byte(READ_ROM)?;
+        let mut rom = Rom::default();
+        self.read_bytes(&mut rom.0)?;
+        if rom.is_valid() {
+            Ok(rom)
+        } else {
+            Err(Error::Crc)
+        }
+    }
+
+    /// Find the ROM code of the next device, or `None` once all the devices
+    /// have been found
+    ///
+    /// At each bit where the ROM codes differ, the search follows the devices
+    /// with a 0 first, then those with a 1 on the next calls. Returns
+    /// [`Error::NoPresence`] if no device is on the bus, or answers the
+    /// search.
+    pub fn search(&mut self, search: &mut Search) -> Result<Option<Rom>, Error<B::Error>> {
+        if search.done {
+            return Ok(None);
+        }
+        self.reset()?;
+        self.write_byte(search.command)?;
+        let mut last_zero = 0;
+        for index in 0..64 {
+            let bit = self.bus.touch_bit(true)?;
+            let complement = self.bus.touch_bit(true)?;
+            let direction = match (bit, complement) {
+                (true, true) => {
+                    search.restart();
+                    return Err(Error::NoPresence);
+                }
+                // All the remaining devices have the same bit
+                (bit, complement) if bit != complement => bit,
+                // Discrepancy, take the 1 branch if the 0 branch was taken
+                // in the previous search
+                _ => {
+                    let position = index + 1;
+                    let direction = if position < search.last_discrepancy {
+                        search.rom.bit(index)
+                    } else {
+                        position == search.last_discrepancy
+                    };
+                    if !direction {
+                        last_zero = position;
+                    }
+                    direction
+                }
+            };
+            search.rom.set_bit(index, direction);
+            self.bus.touch_bit(direction)?;
+        }
+        search.last_discrepancy = last_zero;
+        search.done = last_zero == 0;
+        if search.rom.is_valid() {
+            Ok(Some(search.rom))
+        } else {
+            search.restart();
+            Err(Error::Crc)
+        }
+    }
+}
+
+/// Baud rate of the reset pulse of the [`UartBus`]
+pub const RESET_BAUD: Hertz = Hertz(9600);
+/// Baud rate of the time slots of the [`UartBus`]
+pub const SLOT_BAUD: Hertz = Hertz(115_200);
+
+/// Errors of the [`UartBus`]
+#[derive(Debug, Clone, Copy)]
+pub enum UartBusError {
+    /// Error of the UART
+    Uart(uart::Error),
+    /// The frame sent was not received back, as the RX pin doesn't read the
+    /// line
+    NoEcho,
+}
+
+impl From<uart::Error> for UartBusError {
+    #[inline]
+    fn from(error: uart::Error) -> Self {
+        UartBusError::Uart(error)
+    }
+}
+
+/// 1-Wire bus driven by a UART, switching its baud rate between the reset
+/// pulse and the time slots
+///
+/// See the [module-level documentation](self) for the wiring.
+pub struct UartBus<C: ValidConfig> {
+    uart: Uart<C, Duplex>,
+    baud: Hertz,
+}
+
+impl<C> UartBus<C>
+where
+    C: ValidConfig<Word = u8>,
+{
+    /// Drive the bus with `uart`, configured for 8-bit characters, one stop
+    /// bit and no parity
+    pub fn new(mut uart: Uart<C, Duplex>) -> Self {
+        uart.reconfigure(|c| c.set_baud(SLOT_BAUD, Self::BAUD_MODE));
+        Self {
+            uart,
+            baud: SLOT_BAUD,
+        }
+    }
+
+    /// Release the UART, at the baud rate of the time slots
+    pub fn free(mut self) -> Uart<C, Duplex> {
+        self.set_baud(SLOT_BAUD);
+        self.uart
+    }
+
+    const BAUD_MODE: BaudMode = BaudMode::Fractional(Oversampling::Bits16);
+
+    fn set_baud(&mut self, baud: Hertz) {
+        if self.baud != baud {
+            self.uart.reconfigure(|c| c.set_baud(baud, Self::BAUD_MODE));
+            self.baud = baud;
+        }
+    }
+
+    /// Write `frame`, and read it back from the line
+    ///
+    /// The frame is received while its stop bit is sent, so it must be
+    /// available once the transmission completes.
+    fn touch_frame(&mut self, frame: u8) -> Result<u8, UartBusError> {
+        self.uart.flush_rx_buffer();
+        self.uart.clear_flags(Flags::TXC);
+        nb::block!(self.uart.write(frame)).ok();
+        nb::block!(self.uart.flush()).ok();
+        match self.uart.read() {
+            Ok(frame) => Ok(frame),
+            Err(nb::Error::Other(error)) => Err(error.into()),
+            Err(nb::Error::WouldBlock) => Err(UartBusError::NoEcho),
+        }
+    }
+}
+
+impl<C> OneWireBus for UartBus<C>
+where
+    C: ValidConfig<Word = u8>,
+{
+    type Error = UartBusError;
+
+    fn reset(&mut self) -> Result<bool, UartBusError> {
+        self.set_baud(RESET_BAUD);
+        // The devices pull the line low during the upper bits of the frame
+        let presence = match self.touch_frame(0xF0) {
+            Ok(frame) => frame != 0xF0,
+            // A presence pulse running over the stop bit
+            Err(UartBusError::Uart(uart::Error::FrameError)) => true,
+            Err(error) => return Err(error),
+        };
+        self.set_baud(SLOT_BAUD);
+        Ok(presence)
+    }
+
+    fn touch_bit(&mut self, bit: bool) -> Result<bool, UartBusError> {
+        let frame = if bit { 0xFF } else { 0x00 };
+        // A device answering a 0 extends the start bit into the data bits
+        Ok(self.touch_frame(frame)? == 0xFF)
+    }
+}
+
+/// 1-Wire bus driven by a pin, with the bit timing of a delay
+///
+/// The pin is pulled low as a push-pull output, and released as an input
+/// with its pull-up enabled. The internal pull-up is too weak for most
+/// buses, which need an external pull-up resistor, usually 4.7kΩ.
+#[cfg(feature = "unproven")]
+pub struct PinBus<D> {
+    pin: DynPin,
+    delay: D,
+}
+
+#[cfg(feature = "unproven")]
+impl<D: DelayUs<u16>> PinBus<D> {
+    /// Drive the bus with `pin`, timed by `delay`
+    pub fn new(pin: impl Into<DynPin>, delay: D) -> Self {
+        let mut bus = Self {
+            pin: pin.into(),
+            delay,
+        };
+        bus.release();
+        bus
+    }
+
+    /// Release the pin and the delay
+    #[inline]
+    pub fn free(self) -> (DynPin, D) {
+        (self.pin, self.delay)
+    }
+
+    #[inline]
+    fn pull_low(&mut self) -> Result<(), dynpin::Error> {
+        self.pin.into_push_pull_output();
+        self.pin.set_low()
+    }
+
+    #[inline]
+    fn release(&mut self) {
+        self.pin.into_pull_up_input();
+    }
+}
+
+#[cfg(feature = "unproven")]
+impl<D: DelayUs<u16>> OneWireBus for PinBus<D> {
+    type Error = dynpin::Error;
+
+    fn reset(&mut self) -> Result<bool, dynpin::Error> {
+        self.pull_low()?;
+        self.delay.delay_us(480);
+        let presence = cortex_m::interrupt::free(|_| {
+            self.release();
+            self.delay.delay_us(70);
+            self.pin.is_low()
+        })?;
+        self.delay.delay_us(410);
+        Ok(presence)
+    }
+
+    fn touch_bit(&mut self, bit: bool) -> Result<bool, dynpin::Error> {
+        let read = cortex_m::interrupt::free(|_| {
+            self.pull_low()?;
+            if bit {
+                self.delay.delay_us(6);
+                self.release();
+                self.delay.delay_us(9);
+                self.pin.is_high()
+            } else {
+                self.delay.delay_us(60);
+                self.release();
+                Ok(false)
+            }
+        })?;
+        self.delay.delay_us(if bit { 55 } else { 10 });
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc() {
+        // Example of the Maxim application note 27
+        let rom = [0x02, 0x1C, 0xB8, 0x01, 0x00, 0x00, 0x00, 0xA2];
+        assert_eq!(crc8(&rom[..7]), 0xA2);
+        assert!(Rom(rom).is_valid());
+        assert!(!Rom([0x02, 0x1C, 0xB8, 0x01, 0x00, 0x00, 0x01, 0xA2]).is_valid());
+    }
+
+    /// Simulated devices answering a search
+    struct Devices {
+        roms: [Rom; 3],
+        count: usize,
+        active: [bool; 3],
+        slot: usize,
+    }
+
+    impl Devices {
+        fn new(roms: &[[u8; 7]]) -> Self {
+            let mut devices = Devices {
+                roms: [Rom::default(); 3],
+                count: roms.len(),
+                active: [true; 3],
+                slot: 0,
+            };
+            for (rom, bytes) in devices.roms.iter_mut().zip(roms) {
+                rom.0[..7].copy_from_slice(bytes);
+                rom.0[7] = crc8(bytes);
+            }
+            devices
+        }
+    }
+
+    impl OneWireBus for Devices {
+        type Error = ();
+
+        fn reset(&mut self) -> Result<bool, ()> {
+            self.active = [true; 3];
+            self.slot = 0;
+            Ok(self.count > 0)
+        }
+
+        fn touch_bit(&mut self, bit: bool) -> Result<bool, ()> {
+            // Skip the command, then read the bit, its complement, and write
+            // the direction of each ROM bit
+            let slot = self.slot;
+            self.slot += 1;
+            if slot < 8 {
+                return Ok(bit);
+            }
+            let (index, step) = ((slot - 8) / 3, (slot - 8) % 3);
+            let mut line = bit;
+            let devices = self.roms.iter().zip(&mut self.active).take(self.count);
+            for (rom, active) in devices.filter(|(_, active)| **active) {
+                match step {
+                    0 => line &= rom.bit(index),
+                    1 => line &= !rom.bit(index),
+                    _ => *active = rom.bit(index) == bit,
+                }
+            }
+            Ok(line)
+        }
+    }
+
+    #[test]
+    fn search() {
+        let mut bus = OneWire::new(Devices::new(&[
+            [0x28, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66],
+            [0x28, 0x10, 0x22, 0x33, 0x44, 0x55, 0x66],
+            [0x10, 0xAA, 0x00, 0x00, 0x00, 0x00, 0x01],
+        ]));
+        let roms = bus.bus.roms;
+        let mut search = Search::new();
+        let mut found = [Rom::default(); 3];
+        for rom in &mut found {
+            *rom = bus.search(&mut search).unwrap().unwrap();
+        }
+        assert_eq!(bus.search(&mut search), Ok(None));
+        // 0 branches first, from the least significant bit
+        assert_eq!(found, [roms[2], roms[1], roms[0]]);
+    }
+
+    #[test]
+    fn search_no_device() {
+        let mut bus = OneWire::new(Devices::new(&[]));
+        assert_eq!(bus.search(&mut Search::new()), Err(Error::NoPresence));
+    }
+}
@@ -0,0 +1,327 @@
+//! # Panic persistence - Panic and fault reports kept across resets
+//!
+//! A record reserved in the `.uninit` section of RAM, which the runtime
+//! doesn't initialize, survives the resets which keep the RAM powered: the
+//! system reset requested after a panic, the watchdog reset, or the external
+//! reset. [`record_panic`] and [`record_fault`] write the panic message or the
+//! registers stacked by a HardFault to it; after the reset, [`report`] returns
+//! the record, for example to be printed on the USB serial port of the board,
+//! and [`clear`] erases it.
+//!
+//! With the `panic-persist` feature, the HAL provides the panic handler. It
+//! records the panic message, then resets the chip. Along with the
+//! `panic-log` feature, it also writes the message to the sink of the
+//! [`logger`](crate::logger) module before the reset; with `panic-log` only,
+//! it writes the message and keeps polling the sink. The HardFault handler is
+//! defined by the application, with the `cortex-m-rt` crate:
+//!
+//! ```no_run
+//! use atsamd_hal::panic_persist::{self, FaultFrame};
+//! use cortex_m_rt::{exception, ExceptionFrame};
+//!
+//! #[exception]
+//! unsafe fn HardFault(frame: &ExceptionFrame) -> ! {
+//!     panic_persist::record_fault(&FaultFrame::from_stack(frame as *const _ as *const u32));
+//!     cortex_m::peripheral::SCB::sys_reset()
+//! }
+//!
+//! // After the reset
+//! if let Some(report) = panic_persist::report() {
+//!     writeln!(serial, "last run ended with: {}", report.message()).ok();
+//!     panic_persist::clear();
+//! }
+//! ```
+//!
+//! The record is lost when the RAM isn't powered, after a power-on reset, a
+//! brown-out reset, or a wake up from the hibernate and backup sleep modes.
+
+use core::fmt::{self, Write};
+use core::mem::{size_of, MaybeUninit};
+use core::ptr::{addr_of, addr_of_mut};
+
+use cortex_m::interrupt;
+
+/// Maximum length of the panic messages recorded, in bytes
+pub const MESSAGE_LEN: usize = 256;
+
+/// Marker of a record written by this module
+const MAGIC: u32 = 0x7061_6e63;
+
+/// Registers stacked on exception entry
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FaultFrame {
+    pub r0: u32,
+    pub r1: u32,
+    pub r2: u32,
+    pub r3: u32,
+    pub r12: u32,
+    pub lr: u32,
+    pub pc: u32,
+    pub xpsr: u32,
+}
+
+impl FaultFrame {
+    /// Read the frame stacked at `sp`
+    ///
+    /// # Safety
+    ///
+    /// `sp` must point to an exception frame, such as the one given to the
+    /// HardFault handler by `cortex-m-rt`.
+    #[inline]
+    pub unsafe fn from_stack(sp: *const u32) -> Self {
+        (sp as *const Self).read()
+    }
+}
+
+/// Fault status registers of the SCB, describing the cause of a fault
+#[cfg(feature = "min-samd51g")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FaultStatus {
+    /// Configurable fault status
+    pub cfsr: u32,
+    /// HardFault status
+    pub hfsr: u32,
+    /// MemManage fault address
+    pub mmfar: u32,
+    /// BusFault address
+    pub bfar: u32,
+}
+
+/// Kind of record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Panic, with its message
+    Panic,
+    /// HardFault, with the stacked registers
+    Fault,
+}
+
+/// Record of a panic or fault
+#[repr(C)]
+#[derive(Clone)]
+pub struct Report {
+    magic: u32,
+    kind: u32,
+    frame: FaultFrame,
+    status: [u32; 4],
+    len: u32,
+    message: [u8; MESSAGE_LEN],
+    checksum: u32,
+}
+
+impl Report {
+    /// Kind of record
+    #[inline]
+    pub fn kind(&self) -> Kind {
+        if self.kind == Kind::Fault as u32 {
+            Kind::Fault
+        } else {
+            Kind::Panic
+        }
+    }
+
+    /// Panic message, truncated to [`MESSAGE_LEN`] bytes, or a description
+    /// of the fault
+    pub fn message(&self) -> &str {
+        // The message is truncated at a character boundary when recorded
+        core::str::from_utf8(&self.message[..self.len as usize]).unwrap_or("")
+    }
+
+    /// Registers stacked by the HardFault
+    #[inline]
+    pub fn frame(&self) -> Option<FaultFrame> {
+        match self.kind() {
+            Kind::Fault => Some(self.frame),
+            Kind::Panic => None,
+        }
+    }
+
+    /// Fault status registers when the HardFault occurred
+    #[cfg(feature = "min-samd51g")]
+    #[inline]
+    pub fn status(&self) -> Option<FaultStatus> {
+        let [cfsr, hfsr, mmfar, bfar] = self.status;
+        self.frame().map(|_| FaultStatus {
+            cfsr,
+            hfsr,
+            mmfar,
+            bfar,
+        })
+    }
+
+    fn new(kind: Kind) -> Self {
+        Self {
+            magic: MAGIC,
+            kind: kind as u32,
+            frame: FaultFrame::default(),
+            status: [0; 4],
+            len: 0,
+            message: [0; MESSAGE_LEN],
+            checksum: 0,
+        }
+    }
+
+    /// Checksum of the fields preceding `checksum`
+    fn compute_checksum(&self) -> u32 {
+        let words = size_of::<Self>() / 4 - 1;
+        // SAFETY: The record is made of `u32`s and bytes, without padding
+        let words = unsafe { core::slice::from_raw_parts(self as *const _ as *const u32, words) };
+        words
+            .iter()
+            .fold(MAGIC, |sum, &word| sum.rotate_left(5) ^ word)
+    }
+
+    fn seal(&mut self) {
+        self.checksum = self.compute_checksum();
+    }
+
+    fn is_valid(&self) -> bool {
+        self.magic == MAGIC
+            && self.len as usize <= MESSAGE_LEN
+            && self.checksum == self.compute_checksum()
+    }
+
+    /// Writer of the message, truncating it to [`MESSAGE_LEN`] bytes
+    fn writer(&mut self) -> MessageWriter<'_> {
+        MessageWriter { report: self }
+    }
+}
+
+/// [`fmt::Write`] implementation filling the message of a [`Report`]
+struct MessageWriter<'a> {
+    report: &'a mut Report,
+}
+
+impl Write for MessageWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let len = self.report.len as usize;
+        let room = MESSAGE_LEN - len;
+        let mut end = s.len().min(room);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.report.message[len..len + end].copy_from_slice(&s.as_bytes()[..end]);
+        self.report.len += end as u32;
+        if end == s.len() {
+            Ok(())
+        } else {
+            // Stop formatting, the message is full
+            Err(fmt::Error)
+        }
+    }
+}
+
+#[link_section = ".uninit.atsamd_hal.panic_persist"]
+static mut RECORD: MaybeUninit<Report> = MaybeUninit::uninit();
+
+/// Write `report` to the record
+fn store(report: &Report) {
+    interrupt::free(|_| {
+        // SAFETY: The record is only accessed within critical sections
+        unsafe { addr_of_mut!(RECORD).write(MaybeUninit::new(report.clone())) };
+    });
+}
+
+/// Record the panic described by `info`
+pub fn record_panic(info: &core::panic::PanicInfo) {
+    let mut report = Report::new(Kind::Panic);
+    write!(report.writer(), "{}", info).ok();
+    report.seal();
+    store(&report);
+}
+
+/// Record a HardFault, with the registers stacked in `frame`
+///
+/// On SAMD51 and SAME5x chips, the fault status registers are recorded too.
+pub fn record_fault(frame: &FaultFrame) {
+    let mut report = Report::new(Kind::Fault);
+    report.frame = *frame;
+    #[cfg(feature = "min-samd51g")]
+    {
+        // SAFETY: The fault status registers are only read
+        let scb = unsafe { &*cortex_m::peripheral::SCB::PTR };
+        report.status = [
+            scb.cfsr.read(),
+            scb.hfsr.read(),
+            scb.mmfar.read(),
+            scb.bfar.read(),
+        ];
+    }
+    write!(report.writer(), "HardFault at {:#010x}", frame.pc).ok();
+    report.seal();
+    store(&report);
+}
+
+/// Return the record left by the previous run, if any
+pub fn report() -> Option<Report> {
+    interrupt::free(|_| {
+        // SAFETY: The record is only accessed within critical sections. Its
+        // fields are integers, so any content of the RAM is a valid record,
+        // which is rejected unless its checksum matches. The volatile read
+        // keeps the compiler from assuming the record is uninitialized.
+        let report = unsafe { addr_of!(RECORD).read_volatile().assume_init() };
+        report.is_valid().then(|| report)
+    })
+}
+
+/// Erase the record
+pub fn clear() {
+    interrupt::free(|_| {
+        // SAFETY: The record is only accessed within critical sections
+        unsafe { addr_of_mut!((*addr_of_mut!(RECORD).cast::<Report>()).magic).write(0) };
+    });
+}
+
+#[cfg(all(any(feature = "panic-persist", feature = "panic-log"), not(test)))]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    // Interrupts are never enabled again, so the record and the sink are
+    // accessed within a critical section
+    interrupt::disable();
+
+    #[cfg(feature = "panic-persist")]
+    {
+        record_panic(info);
+        #[cfg(feature = "panic-log")]
+        crate::logger::log_panic(info, Some(crate::logger::PANIC_FLUSH_POLLS));
+        cortex_m::peripheral::SCB::sys_reset()
+    }
+
+    #[cfg(not(feature = "panic-persist"))]
+    {
+        crate::logger::log_panic(info, None);
+        loop {
+            core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_truncated() {
+        let mut report = Report::new(Kind::Panic);
+        let mut writer = report.writer();
+        writer.write_str("x").unwrap();
+        while writer.write_str("é").is_ok() {}
+        // The last character doesn't fit entirely
+        assert_eq!(report.message().len(), MESSAGE_LEN - 1);
+        assert!(report.message().starts_with("xé"));
+    }
+
+    #[test]
+    fn checksum() {
+        let mut report = Report::new(Kind::Fault);
+        report.frame.pc = 0x1234;
+        write!(report.writer(), "fault").unwrap();
+        assert!(!report.is_valid());
+        report.seal();
+        assert!(report.is_valid());
+        assert_eq!(report.frame().map(|frame| frame.pc), Some(0x1234));
+        report.message[0] = b'F';
+        assert!(!report.is_valid());
+    }
+}
@@ -0,0 +1,201 @@
+//! # Power - Sleep modes
+//!
+//! The CPU sleeps with the WFI instruction, in the sleep mode configured
+//! with [`configure`]. The deeper the sleep mode, the more clocks, and thus
+//! peripherals, are stopped:
+//!
+//! * On SAMD11 and SAMD21 chips, [`SleepMode::Idle0`] to
+//!   [`SleepMode::Idle2`] stop the CPU, AHB and APB clocks in turn, while
+//!   [`SleepMode::Standby`] stops every clock source, except those configured
+//!   to run in standby.
+//! * On SAMD51 and SAME5x chips, [`SleepMode::Idle`] stops the CPU clock and
+//!   [`SleepMode::Standby`] stops every clock source, except those configured
+//!   to run in standby. [`SleepMode::Hibernate`], [`SleepMode::Backup`] and
+//!   [`SleepMode::Off`] power down most of the chip, which wakes up through a
+//!   reset.
+//!
+//! Peripherals keep working in standby when they, and the generic clock
+//! feeding them, are configured to run in standby. See
+//! [`GenericClockController::configure_standby`] for the generic clock
+//! generators, and the `run_in_standby` settings of each peripheral.
+//!
+//! Any `wfi` instruction uses the configured sleep mode, so
+//! [`SleepingDelay`](crate::sleeping_delay::SleepingDelay) sleeps in standby
+//! once configured. Paired with the RTC, which keeps counting in standby, it
+//! implements low power delays:
+//!
+//! ```no_run
+//! use atsamd_hal::power::{self, SleepMode};
+//! use atsamd_hal::rtc::Rtc;
+//! use atsamd_hal::sleeping_delay::SleepingDelay;
+//!
+//! static RTC_FIRED: AtomicBool = AtomicBool::new(false);
+//!
+//! // The 32 kHz generic clock generator keeps the RTC running in standby
+//! clocks.configure_standby(ClockGenId::GCLK1, true);
+//! let rtc = Rtc::count32_mode(peripherals.RTC, 32_768.hz(), &mut peripherals.PM);
+//!
+//! power::configure(&mut peripherals.PM, &mut core.SCB, SleepMode::Standby);
+//! let mut delay = SleepingDelay::new(rtc, &RTC_FIRED);
+//! delay.delay_ms(1_000u32);
+//! ```
+//!
+//! On SAMD51 and SAME5x chips, the core is supplied by either the internal LDO
+//! or the more efficient buck converter, selected with [`set_regulator`]. The
+//! buck converter requires an external inductor on the VSW pin. Unlike
+//! SAML21 chips, there are no performance levels to switch, so the regulator
+//! can be changed at any CPU frequency.
+//!
+//! [`GenericClockController::configure_standby`]: crate::clock::GenericClockController::configure_standby
+
+use cortex_m::peripheral::SCB;
+
+use crate::pac::PM;
+#[cfg(feature = "min-samd51g")]
+use crate::pac::SUPC;
+
+/// Retention of the backup RAM in hibernate and backup modes
+#[cfg(feature = "min-samd51g")]
+pub use crate::pac::pm::bkupcfg::BRAMCFG_A as BackupRamRetention;
+
+/// Main voltage regulator supplying the core
+#[cfg(feature = "min-samd51g")]
+pub use crate::pac::supc::vreg::SEL_A as Regulator;
+
+/// Sleep mode entered by the WFI instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SleepMode {
+    /// CPU clock stopped
+    #[cfg(any(feature = "samd11", feature = "samd21"))]
+    Idle0,
+    /// CPU and AHB clocks stopped
+    #[cfg(any(feature = "samd11", feature = "samd21"))]
+    Idle1,
+    /// CPU, AHB and APB clocks stopped
+    #[cfg(any(feature = "samd11", feature = "samd21"))]
+    Idle2,
+    /// CPU clock stopped
+    #[cfg(feature = "min-samd51g")]
+    Idle,
+    /// Every clock stopped, except those running in standby
+    Standby,
+    /// Core domain powered down, waking up through a reset. The backup
+    /// domain and the retained RAM sections are powered.
+    #[cfg(feature = "min-samd51g")]
+    Hibernate,
+    /// Only the backup domain powered, waking up through a reset
+    #[cfg(feature = "min-samd51g")]
+    Backup,
+    /// Whole chip powered down, waking up through an external reset
+    #[cfg(feature = "min-samd51g")]
+    Off,
+}
+
+/// Select the sleep mode entered by the next WFI instructions
+pub fn configure(pm: &mut PM, scb: &mut SCB, mode: SleepMode) {
+    #[cfg(any(feature = "samd11", feature = "samd21"))]
+    {
+        use crate::pac::pm::sleep::IDLE_A;
+        let idle = match mode {
+            SleepMode::Idle0 => Some(IDLE_A::CPU),
+            SleepMode::Idle1 => Some(IDLE_A::AHB),
+            SleepMode::Idle2 => Some(IDLE_A::APB),
+            SleepMode::Standby => None,
+        };
+        match idle {
+            Some(idle) => {
+                pm.sleep.write(|w| w.idle().variant(idle));
+                scb.clear_sleepdeep();
+            }
+            None => scb.set_sleepdeep(),
+        }
+    }
+
+    #[cfg(feature = "min-samd51g")]
+    {
+        use crate::pac::pm::sleepcfg::SLEEPMODE_A;
+        let _ = scb;
+        let mode = match mode {
+            SleepMode::Idle => SLEEPMODE_A::IDLE,
+            SleepMode::Standby => SLEEPMODE_A::STANDBY,
+            SleepMode::Hibernate => SLEEPMODE_A::HIBERNATE,
+            SleepMode::Backup => SLEEPMODE_A::BACKUP,
+            SleepMode::Off => SLEEPMODE_A::OFF,
+        };
+        pm.sleepcfg.write(|w| w.sleepmode().variant(mode));
+        // The sleep mode must be effective before executing WFI
+        while pm.sleepcfg.read().sleepmode().bits() != mode as u8 {}
+    }
+}
+
+/// Enter `mode` until the next interrupt
+///
+/// Interrupts which are enabled in the NVIC, and whose peripheral is still
+/// clocked in `mode`, wake the CPU. From [`SleepMode::Hibernate`],
+/// [`SleepMode::Backup`] and [`SleepMode::Off`], the chip wakes up through a
+/// reset, so this function does not return.
+#[inline]
+pub fn sleep(pm: &mut PM, scb: &mut SCB, mode: SleepMode) {
+    configure(pm, scb, mode);
+    cortex_m::asm::dsb();
+    cortex_m::asm::wfi();
+}
+
+/// Go back to sleep when returning from an interrupt handler to thread mode
+///
+/// Applications driven entirely by interrupts can then sleep after
+/// initialization, without an idle loop.
+#[inline]
+pub fn set_sleep_on_exit(scb: &mut SCB, enabled: bool) {
+    if enabled {
+        scb.set_sleeponexit();
+    } else {
+        scb.clear_sleeponexit();
+    }
+}
+
+/// Keep the I/O pins in their current state when waking up from
+/// [`SleepMode::Hibernate`] or [`SleepMode::Backup`], until retention is
+/// disabled again
+#[cfg(feature = "min-samd51g")]
+#[inline]
+pub fn set_io_retention(pm: &mut PM, enabled: bool) {
+    pm.ctrla.modify(|_, w| w.ioret().bit(enabled));
+}
+
+/// Select how much of the backup RAM is retained in [`SleepMode::Hibernate`]
+/// and [`SleepMode::Backup`]
+#[cfg(feature = "min-samd51g")]
+pub fn set_backup_ram_retention(pm: &mut PM, retention: BackupRamRetention) {
+    // SAFETY: Both registers encode the retention with the same values
+    pm.hibcfg
+        .modify(|_, w| unsafe { w.bramcfg().bits(retention as u8) });
+    pm.bkupcfg.write(|w| w.bramcfg().variant(retention));
+}
+
+/// Select the main voltage regulator, and wait until it is ready
+#[cfg(feature = "min-samd51g")]
+pub fn set_regulator(supc: &mut SUPC, regulator: Regulator) {
+    supc.vreg.modify(|_, w| w.sel().variant(regulator));
+    while supc.status.read().vregrdy().bit_is_clear() {}
+}
+
+/// Return the main voltage regulator currently selected
+#[cfg(feature = "min-samd51g")]
+pub fn regulator(supc: &SUPC) -> Regulator {
+    supc.vreg.read().sel().variant()
+}
+
+/// Scale the core voltage down in standby, and back up when waking up,
+/// in steps of `2^period` microseconds
+///
+/// Voltage scaling lowers the standby current, at the expense of the wake-up
+/// time. `None` disables voltage scaling. `period` is truncated to 3 bits.
+#[cfg(feature = "min-samd51g")]
+pub fn set_standby_voltage_scaling(supc: &mut SUPC, period: Option<u8>) {
+    supc.vreg.modify(|_, w| match period {
+        // SAFETY: VSPER accepts any 3-bit value
+        Some(period) => unsafe { w.vsen().set_bit().vsper().bits(period & 0b111) },
+        None => w.vsen().clear_bit(),
+    });
+}
@@ -5,6 +5,8 @@ pub use crate::gpio::v1::GpioExt as _atsamd21_hal_gpio_GpioExt;
 #[allow(deprecated)]
 pub use crate::spi_common::CommonSpi as _atsamd_hal_spi_common_CommonSpi;
 pub use crate::time::U32Ext as _atsamd21_hal_time_U32Ext;
+pub use fugit::ExtU32 as _fugit_ExtU32;
+pub use fugit::RateExtU32 as _fugit_RateExtU32;
 pub use crate::timer_traits::InterruptDrivenTimer as _atsamd_hal_timer_traits_InterruptDrivenTimer;
 
 // embedded-hal doesn’t yet have v2 in its prelude, so we need to
@@ -1,4 +1,5 @@
 //! Real-time clock/counter
+use crate::bus_clock::{self, BusClock};
 use crate::ehal::timer::{CountDown, Periodic};
 use crate::pac::rtc::{MODE0, MODE2};
 use crate::pac::RTC;
@@ -18,9 +19,16 @@ pub type Instant = fugit::Instant<u32, 1, 32_768>;
 #[cfg(feature = "rtic")]
 pub type Duration = fugit::Duration<u32, 1, 32_768>;
 #[cfg(feature = "rtic")]
+pub type Instant64 = fugit::Instant<u64, 1, 32_768>;
+#[cfg(feature = "rtic")]
+pub type Duration64 = fugit::Duration<u64, 1, 32_768>;
+#[cfg(feature = "rtic")]
 use rtic_monotonic::Monotonic;
 
 // SAMx5x imports
+/// Fields of the clock/calendar value matched by the alarm
+#[cfg(feature = "min-samd51g")]
+pub use crate::pac::rtc::mode2::mask0::SEL_A as AlarmMask;
 #[cfg(feature = "min-samd51g")]
 use crate::pac::{
     rtc::mode0::ctrla::PRESCALER_A, rtc::mode0::CTRLA as MODE0_CTRLA,
@@ -28,6 +36,9 @@ use crate::pac::{
 };
 
 // SAMD11/SAMD21 imports
+/// Fields of the clock/calendar value matched by the alarm
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+pub use crate::pac::rtc::mode2::mask::SEL_A as AlarmMask;
 #[cfg(any(feature = "samd11", feature = "samd21"))]
 use crate::pac::{
     rtc::mode0::ctrl::PRESCALER_A, rtc::mode0::CTRL as MODE0_CTRLA,
@@ -218,8 +229,9 @@ impl<Mode: RtcMode> Rtc<Mode> {
         self.into_mode()
     }
 
-    /// Releases the RTC resource
+    /// Disables the RTC bus clock and releases the RTC resource
     pub fn free(self) -> RTC {
+        bus_clock::release::<RTC>();
         self.rtc
     }
 }
@@ -228,7 +240,7 @@ impl Rtc<Count32Mode> {
     /// Configures the RTC in 32-bit counter mode with no prescaler (default
     /// state after reset) and the counter initialized to zero.
     pub fn count32_mode(rtc: RTC, rtc_clock_freq: Hertz, pm: &mut PM) -> Self {
-        pm.apbamask.modify(|_, w| w.rtc_().set_bit());
+        RTC::enable_bus_clock(pm);
 
         let mut new_rtc = Self {
             rtc,
@@ -268,6 +280,49 @@ impl Rtc<Count32Mode> {
         self.enable(true);
     }
 
+    /// Returns the frequency of the counter, the RTC clock divided by the
+    /// prescaler.
+    pub fn count_frequency(&self) -> Hertz {
+        let prescaler = self.mode0_ctrla().read().prescaler().bits();
+        // The prescaler divides by 2^n, from DIV1 = 1 on SAMx5x, where 0
+        // stops the counter
+        #[cfg(feature = "min-samd51g")]
+        let prescaler = prescaler.saturating_sub(1);
+        Hertz(self.rtc_clock_freq.0 >> prescaler)
+    }
+
+    /// Lets the counter run freely past the compare value, as configured by
+    /// [`into_count32_mode`](Rtc::into_count32_mode), undoing
+    /// [`CountDown::start`], and enables it
+    pub(crate) fn run_freely(&mut self) {
+        // MATCHCLR is enable-protected
+        self.enable(false);
+        self.mode0_ctrla().modify(|_, w| w.matchclr().clear_bit());
+        self.sync();
+        self.enable(true);
+    }
+
+    /// Sets the compare value. The compare flag is raised when the counter
+    /// reaches it, and the counter keeps running, unless started as a
+    /// countdown.
+    #[inline]
+    pub fn set_compare_value(&mut self, count: u32) {
+        self.sync();
+        self.mode0().comp[0].write(|w| unsafe { w.comp().bits(count) });
+        self.sync();
+    }
+
+    /// Returns whether the counter reached the compare value, and clears the
+    /// compare flag.
+    pub fn check_and_clear_compare(&mut self) -> bool {
+        let reached = self.mode0().intflag.read().cmp0().bit_is_set();
+        if reached {
+            // Writing a 1 clears the flag
+            self.mode0().intflag.write(|w| w.cmp0().set_bit());
+        }
+        reached
+    }
+
     /// This resets the internal counter and sets the prescaler to match the
     /// provided timeout. You should configure the prescaler using the longest
     /// timeout you plan to measure.
@@ -333,6 +388,304 @@ impl Rtc<ClockMode> {
         });
         self.sync();
     }
+
+    /// Sets the alarm to `time`. The alarm fires when the fields of the
+    /// clock/calendar value selected by `mask` match those of `time`, for
+    /// example every day at the same hour with [`AlarmMask::HHMMSS`].
+    pub fn set_alarm(&mut self, time: Datetime, mask: AlarmMask) {
+        self.mode2().alarm0.write(|w| unsafe {
+            w.second()
+                .bits(time.seconds)
+                .minute()
+                .bits(time.minutes)
+                .hour()
+                .bits(time.hours)
+                .day()
+                .bits(time.day)
+                .month()
+                .bits(time.month)
+                .year()
+                .bits(time.year)
+        });
+        self.sync();
+        self.mode2().mask0.write(|w| w.sel().variant(mask));
+        self.sync();
+    }
+
+    /// Returns the alarm value and mask.
+    pub fn alarm(&self) -> (Datetime, AlarmMask) {
+        let alarm = self.mode2().alarm0.read();
+        let time = Datetime {
+            seconds: alarm.second().bits(),
+            minutes: alarm.minute().bits(),
+            hours: alarm.hour().bits(),
+            day: alarm.day().bits(),
+            month: alarm.month().bits(),
+            year: alarm.year().bits(),
+        };
+        let mask = match self.mode2().mask0.read().sel().variant() {
+            crate::pac::generic::Variant::Val(mask) => mask,
+            crate::pac::generic::Variant::Res(_) => AlarmMask::OFF,
+        };
+        (time, mask)
+    }
+
+    /// Enables the alarm interrupt. This method only sets the RTC
+    /// configuration; it does not configure the interrupt controller.
+    pub fn enable_alarm_interrupt(&mut self) {
+        self.mode2().intenset.write(|w| w.alarm0().set_bit());
+    }
+
+    /// Disables the alarm interrupt.
+    pub fn disable_alarm_interrupt(&mut self) {
+        self.mode2().intenclr.write(|w| w.alarm0().set_bit());
+    }
+
+    /// Returns whether the alarm fired, and clears its flag.
+    pub fn check_and_clear_alarm(&mut self) -> bool {
+        let fired = self.mode2().intflag.read().alarm0().bit_is_set();
+        if fired {
+            // Writing a 1 clears the flag
+            self.mode2().intflag.write(|w| w.alarm0().set_bit());
+        }
+        fired
+    }
+}
+
+// --- Periodic intervals
+
+/// Periodic interval, a tap of the RTC prescaler dividing the RTC clock
+///
+/// With the 32.768 kHz RTC clock used by the rest of this module,
+/// [`PeriodicInterval::Div8`] ticks at 4096 Hz and
+/// [`PeriodicInterval::Div1024`] at 32 Hz. The prescaler keeps running in
+/// standby, while the RTC is enabled.
+#[repr(u8)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PeriodicInterval {
+    Div8 = 0,
+    Div16,
+    Div32,
+    Div64,
+    Div128,
+    Div256,
+    Div512,
+    Div1024,
+}
+
+impl PeriodicInterval {
+    /// Returns the frequency of the periodic interval for an RTC clock of
+    /// `rtc_clock_freq`
+    pub fn frequency(self, rtc_clock_freq: Hertz) -> Hertz {
+        Hertz(rtc_clock_freq.0 >> (self as u8 + 3))
+    }
+}
+
+impl<Mode: RtcMode> Rtc<Mode> {
+    /// Enables or disables the event output of `interval`, which can then
+    /// trigger other peripherals through the event system. The RTC is
+    /// briefly disabled, as the event configuration is enable-protected.
+    ///
+    /// Starting a countdown resets this configuration.
+    pub fn set_periodic_event(&mut self, interval: PeriodicInterval, enabled: bool) {
+        let mask = 1 << interval as u8;
+        self.enable(false);
+        self.mode0().evctrl.modify(|r, w| {
+            let bits = if enabled {
+                r.bits() | mask
+            } else {
+                r.bits() & !mask
+            };
+            // SAFETY: Only the event output of `interval` is modified
+            unsafe { w.bits(bits) }
+        });
+        self.enable(true);
+    }
+
+    /// Enables the interrupt of `interval`. This method only sets the RTC
+    /// configuration; it does not configure the interrupt controller.
+    #[cfg(feature = "min-samd51g")]
+    pub fn enable_periodic_interrupt(&mut self, interval: PeriodicInterval) {
+        // SAFETY: Writing a 0 has no effect
+        self.mode0()
+            .intenset
+            .write(|w| unsafe { w.bits(1 << interval as u8) });
+    }
+
+    /// Disables the interrupt of `interval`.
+    #[cfg(feature = "min-samd51g")]
+    pub fn disable_periodic_interrupt(&mut self, interval: PeriodicInterval) {
+        // SAFETY: Writing a 0 has no effect
+        self.mode0()
+            .intenclr
+            .write(|w| unsafe { w.bits(1 << interval as u8) });
+    }
+
+    /// Returns whether `interval` ticked, and clears its flag.
+    #[cfg(feature = "min-samd51g")]
+    pub fn check_and_clear_periodic(&mut self, interval: PeriodicInterval) -> bool {
+        let mask = 1 << interval as u8;
+        let ticked = self.mode0().intflag.read().bits() & mask != 0;
+        if ticked {
+            // SAFETY: Writing a 1 clears the flag
+            self.mode0().intflag.write(|w| unsafe { w.bits(mask) });
+        }
+        ticked
+    }
+}
+
+// --- Tamper detection and backup registers (SAMx5x)
+
+/// Action taken when a tamper input detects its active level
+#[cfg(feature = "min-samd51g")]
+pub use crate::pac::rtc::mode0::tampctrl::IN0ACT_A as TamperAction;
+
+/// Number of tamper inputs, INn pins
+#[cfg(feature = "min-samd51g")]
+pub const NUM_TAMPER_INPUTS: usize = 5;
+
+#[cfg(feature = "min-samd51g")]
+impl<Mode: RtcMode> Rtc<Mode> {
+    /// Configures tamper input `input` (0 to 4) to take `action` when it
+    /// detects a rising edge, or a falling edge when `rising` is false,
+    /// optionally debounced. The RTC is briefly disabled, as the tamper
+    /// configuration is enable-protected.
+    ///
+    /// With [`TamperAction::ACTL`], input 0 instead detects a mismatch between
+    /// itself and the OUT pin of the RTC.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `input` is not lower than [`NUM_TAMPER_INPUTS`].
+    pub fn configure_tamper(
+        &mut self,
+        input: usize,
+        action: TamperAction,
+        rising: bool,
+        debounce: bool,
+    ) {
+        assert!(input < NUM_TAMPER_INPUTS, "Invalid tamper input");
+        self.enable(false);
+        self.mode0().tampctrl.modify(|r, w| {
+            let mut bits =
+                r.bits() & !(0b11 << (2 * input) | 1 << (16 + input) | 1 << (24 + input));
+            bits |= (action as u32) << (2 * input);
+            bits |= (rising as u32) << (16 + input) | (debounce as u32) << (24 + input);
+            // SAFETY: Only the fields of `input` are modified
+            unsafe { w.bits(bits) }
+        });
+        self.enable(true);
+    }
+
+    /// Clears the backup registers and the general purpose registers,
+    /// respectively, when a tamper input captures an event. The RTC is
+    /// briefly disabled, as this configuration is enable-protected.
+    pub fn set_tamper_reset(&mut self, backup: bool, general_purpose: bool) {
+        self.enable(false);
+        self.mode0_ctrla()
+            .modify(|_, w| w.bktrst().bit(backup).gptrst().bit(general_purpose));
+        self.sync();
+        self.enable(true);
+    }
+
+    /// Returns the tamper inputs which detected an event, one bit per input
+    pub fn tamper_inputs(&self) -> u8 {
+        self.mode0().tampid.read().bits() as u8 & 0x1f
+    }
+
+    /// Clears the detected events of the tamper inputs set in `inputs`
+    pub fn clear_tamper_inputs(&mut self, inputs: u8) {
+        // SAFETY: Writing a 1 clears the flag of an input
+        self.mode0()
+            .tampid
+            .write(|w| unsafe { w.bits((inputs & 0x1f) as u32) });
+    }
+
+    /// Enables the tamper interrupt. This method only sets the RTC
+    /// configuration; it does not configure the interrupt controller.
+    pub fn enable_tamper_interrupt(&mut self) {
+        self.mode0().intenset.write(|w| w.tamper().set_bit());
+    }
+
+    /// Disables the tamper interrupt.
+    pub fn disable_tamper_interrupt(&mut self) {
+        self.mode0().intenclr.write(|w| w.tamper().set_bit());
+    }
+
+    /// Returns whether a tamper input captured an event, and clears the flag.
+    pub fn check_and_clear_tamper(&mut self) -> bool {
+        let tamper = self.mode0().intflag.read().tamper().bit_is_set();
+        if tamper {
+            // Writing a 1 clears the flag
+            self.mode0().intflag.write(|w| w.tamper().set_bit());
+        }
+        tamper
+    }
+
+    /// Returns general purpose register `n` (0 to 3). These registers are
+    /// powered in backup mode.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is higher than 3.
+    pub fn general_purpose(&self, n: usize) -> u32 {
+        self.mode0().gp[n].read().bits()
+    }
+
+    /// Writes general purpose register `n` (0 to 3)
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is higher than 3.
+    pub fn set_general_purpose(&mut self, n: usize, value: u32) {
+        // SAFETY: The register accepts any value
+        self.mode0().gp[n].write(|w| unsafe { w.bits(value) });
+        self.sync();
+    }
+
+    /// Returns backup register `n` (0 to 7). These registers are powered in
+    /// backup mode.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is higher than 7.
+    pub fn backup(&self, n: usize) -> u32 {
+        self.mode0().bkup[n].read().bits()
+    }
+
+    /// Writes backup register `n` (0 to 7)
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is higher than 7.
+    pub fn set_backup(&mut self, n: usize, value: u32) {
+        // SAFETY: The register accepts any value
+        self.mode0().bkup[n].write(|w| unsafe { w.bits(value) });
+    }
+}
+
+#[cfg(feature = "min-samd51g")]
+impl Rtc<ClockMode> {
+    /// Returns the clock/calendar value captured by the last tamper event
+    pub fn tamper_timestamp(&self) -> Datetime {
+        let timestamp = self.mode2().timestamp.read();
+        Datetime {
+            seconds: timestamp.second().bits(),
+            minutes: timestamp.minute().bits(),
+            hours: timestamp.hour().bits(),
+            day: timestamp.day().bits(),
+            month: timestamp.month().bits(),
+            year: timestamp.year().bits(),
+        }
+    }
+}
+
+#[cfg(feature = "min-samd51g")]
+impl Rtc<Count32Mode> {
+    /// Returns the counter value captured by the last tamper event
+    pub fn tamper_timestamp(&self) -> u32 {
+        self.mode0().timestamp.read().count().bits()
+    }
 }
 
 // --- Timer / Counter Functionality
@@ -486,3 +839,94 @@ impl Monotonic for Rtc<Count32Mode> {
         self.mode0().intflag.write(|w| w.cmp0().set_bit());
     }
 }
+
+/// 64-bit monotonic timebase, extending the 32-bit RTC counter in software
+///
+/// The counter wraps every 36 hours at 32.768 kHz, so this timebase counts
+/// the overflows of the counter to keep increasing. RTIC calls
+/// [`Monotonic::on_interrupt`] from the RTC interrupt to count them: when
+/// used outside of RTIC, call it from the RTC interrupt handler after
+/// [`Monotonic::reset`].
+#[cfg(feature = "rtic")]
+pub struct RtcMonotonic {
+    rtc: Rtc<Count32Mode>,
+    overflows: u32,
+}
+
+#[cfg(feature = "rtic")]
+impl RtcMonotonic {
+    /// Creates the timebase from an RTC in 32-bit counter mode, clocked at
+    /// 32.768 kHz.
+    pub fn new(rtc: Rtc<Count32Mode>) -> Self {
+        assert_eq!(rtc.rtc_clock_freq.0, 32_768, "RTC clk not 32.768 kHz!");
+        Self { rtc, overflows: 0 }
+    }
+
+    /// Releases the RTC.
+    pub fn free(self) -> Rtc<Count32Mode> {
+        self.rtc
+    }
+
+    #[inline]
+    fn ovf_pending(&self) -> bool {
+        self.rtc.mode0().intflag.read().ovf().bit_is_set()
+    }
+}
+
+#[cfg(feature = "rtic")]
+impl Monotonic for RtcMonotonic {
+    type Instant = Instant64;
+    type Duration = Duration64;
+
+    unsafe fn reset(&mut self) {
+        self.rtc.set_count32(0);
+        self.overflows = 0;
+        self.rtc
+            .mode0()
+            .intflag
+            .write(|w| w.ovf().set_bit().cmp0().set_bit());
+        self.rtc
+            .mode0()
+            .intenset
+            .write(|w| w.ovf().set_bit().cmp0().set_bit());
+    }
+
+    fn now(&mut self) -> Self::Instant {
+        let count = self.rtc.count32();
+        let ticks = if self.ovf_pending() {
+            // The counter overflowed since the last interrupt. Read it again,
+            // as it may have overflowed after the first read.
+            (u64::from(self.overflows) + 1) << 32 | u64::from(self.rtc.count32())
+        } else {
+            u64::from(self.overflows) << 32 | u64::from(count)
+        };
+        Self::Instant::from_ticks(ticks)
+    }
+
+    fn zero() -> Self::Instant {
+        Self::Instant::from_ticks(0)
+    }
+
+    fn set_compare(&mut self, instant: Self::Instant) {
+        let now = self.now().ticks();
+        // Instants after the next overflow are compared once the overflow is
+        // counted, so wake up right after it
+        let compare = if instant.ticks() >> 32 > now >> 32 {
+            0
+        } else {
+            instant.ticks() as u32
+        };
+        unsafe { self.rtc.mode0().comp[0].write(|w| w.comp().bits(compare)) }
+    }
+
+    fn clear_compare_flag(&mut self) {
+        self.rtc.mode0().intflag.write(|w| w.cmp0().set_bit());
+    }
+
+    fn on_interrupt(&mut self) {
+        if self.ovf_pending() {
+            self.rtc.mode0().intflag.write(|w| w.ovf().set_bit());
+            self.overflows = self.overflows.wrapping_add(1);
+        }
+    }
+}
@@ -9,6 +9,14 @@
 pub mod pads;
 pub use pads::*;
 
+#[cfg(feature = "async")]
+#[clippy::msrv = "1.75"]
+pub mod i2c_future;
+
+pub mod i2c_slave;
+pub mod i2c_speed;
+pub mod smbus;
+
 #[cfg(any(feature = "samd11", feature = "samd21"))]
 pub use crate::common::thumbv6m::sercom::v1::*;
 
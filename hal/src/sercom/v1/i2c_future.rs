@@ -0,0 +1,327 @@
+//! `async` I2C master driver
+//!
+//! Any I2C master type [`I2CMaster0`], [`I2CMaster1`], etc. can be turned into
+//! an [`I2cFuture`] with [`I2cMaster::into_future`]. [`I2cFuture`] implements
+//! the [`embedded_hal_async`] [`I2c`](embedded_hal_async::i2c::I2c) trait.
+//!
+//! A transaction is driven as a sequence of bus states: `START` (or repeated
+//! `START`) with the address, then one state per data byte, and finally
+//! `STOP`. Each state waits on the `MB` (master on bus), `SB` (slave on bus)
+//! or `ERROR` interrupt, then advances to the next one. The CPU is free to do
+//! other work while a byte is on the bus, since the interrupt handler only
+//! disables the fired interrupts and wakes the awaiting task.
+//!
+//! As with every `async` SERCOM driver, [`async_api::on_interrupt`] must be
+//! called from the SERCOM interrupt handler(s).
+//!
+//! ```
+//! use atsamd_hal::pac::interrupt;
+//! use atsamd_hal::sercom::v1::i2c_future::I2cMaster;
+//! use atsamd_hal::sercom::v2::{async_api, Sercom3};
+//! use embedded_hal_async::i2c::I2c;
+//!
+//! #[interrupt]
+//! fn SERCOM3() {
+//!     async_api::on_interrupt::<Sercom3>();
+//! }
+//!
+//! // Assume i2c is an I2CMaster3
+//! let mut i2c = i2c.into_future();
+//! let mut buffer = [0_u8; 6];
+//! i2c.write_read(0x1e, &[0x03], &mut buffer).await?;
+//! ```
+//!
+//! [`I2CMaster0`]: super::I2CMaster0
+//! [`I2CMaster1`]: super::I2CMaster1
+//! [`async_api::on_interrupt`]: crate::sercom::v2::async_api::on_interrupt
+
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embedded_hal_async::i2c::{self, Operation};
+
+use crate::pac::sercom0::I2CM;
+use crate::sercom::v2::async_api::{rx_waker, tx_waker};
+use crate::sercom::v2::Sercom;
+use crate::typelevel::Sealed;
+
+use super::I2CError;
+
+const BUS_STATE_UNKNOWN: u8 = 0;
+const BUS_STATE_BUSY: u8 = 3;
+
+const MASTER_ACT_READ: u8 = 2;
+const MASTER_ACT_STOP: u8 = 3;
+
+/// `INTFLAG` bits
+const MB: u8 = 0x01;
+const SB: u8 = 0x02;
+const ERROR: u8 = 0x80;
+
+/// Type class for the I2C master types that can be used by [`I2cFuture`]
+///
+/// This trait is implemented for [`I2CMaster0`], [`I2CMaster1`], etc.
+///
+/// [`I2CMaster0`]: super::I2CMaster0
+/// [`I2CMaster1`]: super::I2CMaster1
+pub trait I2cMaster: Sealed + Sized {
+    /// SERCOM used by the I2C master
+    type Sercom: Sercom;
+
+    /// Access the I2C master registers
+    #[doc(hidden)]
+    fn i2cm_regs(&self) -> &I2CM;
+
+    /// Turn the I2C master into an [`I2cFuture`]
+    #[inline]
+    fn into_future(self) -> I2cFuture<Self> {
+        I2cFuture::new(self)
+    }
+}
+
+/// `async` version of an I2C master
+///
+/// See the [module-level documentation](self) for more details.
+pub struct I2cFuture<M: I2cMaster> {
+    i2c: M,
+}
+
+impl<M: I2cMaster> I2cFuture<M> {
+    /// Create a new [`I2cFuture`] from an I2C master
+    ///
+    /// All SERCOM interrupts are disabled, as they are managed by the
+    /// [`I2cFuture`] from now on.
+    #[inline]
+    pub fn new(i2c: M) -> Self {
+        i2c.i2cm_regs()
+            .intenclr
+            .write(|w| unsafe { w.bits(MB | SB | ERROR) });
+        Self { i2c }
+    }
+
+    /// Return the underlying I2C master
+    #[inline]
+    pub fn free(self) -> M {
+        self.i2cm()
+            .intenclr
+            .write(|w| unsafe { w.bits(MB | SB | ERROR) });
+        self.i2c
+    }
+
+    #[inline]
+    fn i2cm(&self) -> &I2CM {
+        self.i2c.i2cm_regs()
+    }
+
+    /// Wait until any of the given `INTFLAG` bits is set, then return all set
+    /// bits among them
+    async fn wait_flags(&mut self, flags: u8) -> u8 {
+        poll_fn(|cx| {
+            // MB and SB are routed to the transmit side waker, ERROR to the
+            // receive side one
+            tx_waker::<M::Sercom>().register(cx.waker());
+            rx_waker::<M::Sercom>().register(cx.waker());
+            self.i2cm().intenset.write(|w| unsafe { w.bits(flags) });
+            let set = self.i2cm().intflag.read().bits() & flags;
+            if set == 0 {
+                Poll::Pending
+            } else {
+                self.i2cm().intenclr.write(|w| unsafe { w.bits(flags) });
+                Poll::Ready(set)
+            }
+        })
+        .await
+    }
+
+    /// Convert the `STATUS` register into a [`Result`], clearing the `ERROR`
+    /// interrupt flag
+    fn status_to_err(&mut self) -> Result<(), I2CError> {
+        let status = self.i2cm().status.read();
+        self.i2cm().intflag.write(|w| w.error().set_bit());
+        if status.arblost().bit_is_set() {
+            return Err(I2CError::ArbitrationLost);
+        }
+        if status.buserr().bit_is_set() {
+            return Err(I2CError::BusError);
+        }
+        if status.rxnack().bit_is_set() {
+            return Err(I2CError::Nack);
+        }
+        if status.lowtout().bit_is_set()
+            || status.sexttout().bit_is_set()
+            || status.mexttout().bit_is_set()
+        {
+            return Err(I2CError::Timeout);
+        }
+        Ok(())
+    }
+
+    fn wait_sync(&mut self) {
+        while self.i2cm().syncbusy.read().sysop().bit_is_set() {}
+    }
+
+    fn cmd_stop(&mut self) {
+        self.i2cm()
+            .ctrlb
+            .modify(|_, w| unsafe { w.cmd().bits(MASTER_ACT_STOP) });
+        self.wait_sync();
+    }
+
+    fn check_bus_state(&mut self) -> Result<(), I2CError> {
+        match self.i2cm().status.read().busstate().bits() {
+            BUS_STATE_UNKNOWN | BUS_STATE_BUSY => Err(I2CError::BusError),
+            _ => Ok(()),
+        }
+    }
+
+    /// `START` state of a write: send the address with the write bit
+    async fn start_write(&mut self, address: u8) -> Result<(), I2CError> {
+        self.check_bus_state()?;
+        self.i2cm()
+            .addr
+            .write(|w| unsafe { w.addr().bits((address as u16) << 1) });
+        self.wait_flags(MB | ERROR).await;
+        self.status_to_err()
+    }
+
+    /// `START` state of a read: send the address with the read bit and wait
+    /// for the first byte
+    async fn start_read(&mut self, address: u8) -> Result<(), I2CError> {
+        self.check_bus_state()?;
+        // ACK the received bytes until told otherwise
+        self.i2cm().ctrlb.modify(|_, w| w.ackact().clear_bit());
+        self.wait_sync();
+        self.i2cm()
+            .addr
+            .write(|w| unsafe { w.addr().bits(((address as u16) << 1) | 1) });
+        let flags = self.wait_flags(MB | SB | ERROR).await;
+        self.status_to_err()?;
+        // MB is only set for a read if the address was not acknowledged or
+        // arbitration was lost
+        if flags & MB != 0 {
+            return Err(I2CError::ArbitrationLost);
+        }
+        Ok(())
+    }
+
+    /// Data states of a write: send every byte and wait for it to be
+    /// acknowledged
+    async fn send_bytes(&mut self, bytes: &[u8]) -> Result<(), I2CError> {
+        for &byte in bytes {
+            self.i2cm().data.write(|w| unsafe { w.bits(byte) });
+            self.wait_flags(MB | ERROR).await;
+            self.status_to_err()?;
+        }
+        Ok(())
+    }
+
+    /// Data states of a read, spanning consecutive read operations
+    ///
+    /// The first byte must already be received. Every byte but the last one is
+    /// acknowledged. The last one is not acknowledged, which is then sent
+    /// along the following `STOP` or repeated `START`.
+    async fn fill_buffers(&mut self, operations: &mut [Operation<'_>]) -> Result<(), I2CError> {
+        let total: usize = operations
+            .iter()
+            .map(|op| match op {
+                Operation::Read(buffer) => buffer.len(),
+                Operation::Write(_) => 0,
+            })
+            .sum();
+        let mut index = 0;
+        for op in operations.iter_mut() {
+            if let Operation::Read(buffer) = op {
+                for byte in buffer.iter_mut() {
+                    if index != 0 {
+                        self.wait_flags(SB | ERROR).await;
+                        self.status_to_err()?;
+                    }
+                    index += 1;
+                    if index == total {
+                        // NACK the last byte to stop the slave from transmitting
+                        self.i2cm().ctrlb.modify(|_, w| w.ackact().set_bit());
+                        self.wait_sync();
+                        *byte = self.i2cm().data.read().bits();
+                    } else {
+                        *byte = self.i2cm().data.read().bits();
+                        self.i2cm().ctrlb.modify(|_, w| unsafe {
+                            w.ackact().clear_bit();
+                            w.cmd().bits(MASTER_ACT_READ)
+                        });
+                        self.wait_sync();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn do_transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), I2CError> {
+        // Consecutive operations of the same kind form one segment delimited by
+        // (repeated) STARTs
+        let mut start = 0;
+        while start < operations.len() {
+            let is_read = matches!(operations[start], Operation::Read(_));
+            let end = operations[start..]
+                .iter()
+                .position(|op| matches!(op, Operation::Read(_)) != is_read)
+                .map_or(operations.len(), |offset| start + offset);
+            let segment = &mut operations[start..end];
+            if is_read {
+                // Zero length reads are impossible on the bus, skip them
+                let empty = segment.iter().all(|op| match op {
+                    Operation::Read(buffer) => buffer.is_empty(),
+                    Operation::Write(_) => true,
+                });
+                if !empty {
+                    self.start_read(address).await?;
+                    self.fill_buffers(segment).await?;
+                }
+            } else {
+                self.start_write(address).await?;
+                for op in segment.iter() {
+                    if let Operation::Write(bytes) = op {
+                        self.send_bytes(bytes).await?;
+                    }
+                }
+            }
+            start = end;
+        }
+        Ok(())
+    }
+
+    /// Execute a sequence of operations as a single I2C transaction
+    ///
+    /// This follows the [`I2c::transaction`](embedded_hal_async::i2c::I2c)
+    /// contract: consecutive operations of the same kind are merged, a
+    /// repeated `START` is sent between operations of a different kind and a
+    /// `STOP` is sent at the end, or as soon as an error occurs.
+    pub async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), I2CError> {
+        let result = self.do_transaction(address, operations).await;
+        self.cmd_stop();
+        result
+    }
+}
+
+impl<M: I2cMaster> i2c::ErrorType for I2cFuture<M> {
+    type Error = I2CError;
+}
+
+impl<M: I2cMaster> i2c::I2c for I2cFuture<M> {
+    #[inline]
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        I2cFuture::transaction(self, address, operations).await
+    }
+}
@@ -0,0 +1,294 @@
+//! I2C slave (target) driver
+//!
+//! An [`I2cSlave`] answers to a 7-bit address, optionally widened with an
+//! address mask, and to the general call address when enabled. The SERCOM
+//! stretches the clock, holding SCL low, from every address match and data
+//! byte until the software responds, so the host waits for the application
+//! however long it takes.
+//!
+//! Transactions are interrupt-driven. Enable the interrupts with
+//! [`enable_interrupts`](I2cSlave::enable_interrupts), then call
+//! [`on_interrupt`](I2cSlave::on_interrupt) from the SERCOM interrupt
+//! handler, with an [`I2cSlaveHandler`] implementing the behaviour of the
+//! emulated device:
+//!
+//! ```no_run
+//! use atsamd_hal::sercom::v1::i2c_slave::{Direction, I2cSlave, I2cSlaveHandler};
+//!
+//! struct Registers {
+//!     regs: [u8; 16],
+//!     pointer: usize,
+//!     first: bool,
+//! }
+//!
+//! impl I2cSlaveHandler for Registers {
+//!     fn address_match(&mut self, _direction: Direction) -> bool {
+//!         self.first = true;
+//!         true
+//!     }
+//!
+//!     fn write(&mut self, byte: u8) -> bool {
+//!         // The first byte written selects the register
+//!         if core::mem::take(&mut self.first) {
+//!             self.pointer = byte as usize % 16;
+//!         } else {
+//!             self.regs[self.pointer] = byte;
+//!             self.pointer = (self.pointer + 1) % 16;
+//!         }
+//!         true
+//!     }
+//!
+//!     fn read(&mut self) -> u8 {
+//!         let byte = self.regs[self.pointer];
+//!         self.pointer = (self.pointer + 1) % 16;
+//!         byte
+//!     }
+//! }
+//!
+//! // The SERCOM core clock must be configured with the GenericClockController
+//! let mut i2c = I2cSlave::new(&mut peripherals.MCLK, sercom, sda, scl, 0x42);
+//! i2c.enable_interrupts();
+//!
+//! // In the SERCOM interrupt handler
+//! i2c.on_interrupt(&mut registers);
+//! ```
+
+#[cfg(feature = "min-samd51g")]
+use crate::pac::MCLK as APB_CLK_CTRL;
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+use crate::pac::PM as APB_CLK_CTRL;
+
+use crate::bus_clock;
+use crate::pac::sercom0::I2CS;
+use crate::sercom::v1::pads::CompatiblePad;
+use crate::sercom::v2::{Pad0, Pad1, Sercom};
+
+/// Acknowledge action, then wait for any start condition
+const CMD_WAIT_START: u8 = 2;
+/// Acknowledge action, then continue with the next byte
+const CMD_CONTINUE: u8 = 3;
+
+/// `INTFLAG` bits
+const PREC: u8 = 0x01;
+const AMATCH: u8 = 0x02;
+const DRDY: u8 = 0x04;
+const ERROR: u8 = 0x80;
+
+/// Direction of the transaction, as seen from the host
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// The host writes bytes to the slave
+    Write,
+    /// The host reads bytes from the slave
+    Read,
+}
+
+/// Behaviour of the emulated device, driven by [`I2cSlave::on_interrupt`]
+pub trait I2cSlaveHandler {
+    /// Called when the host addresses the slave, at the start of each
+    /// transaction and after each repeated start. Returns whether to
+    /// acknowledge the address.
+    ///
+    /// When the clock is stretched after the acknowledge, see
+    /// [`I2cSlave::set_stretch_before_ack`], the address has already been
+    /// acknowledged, and the value returned only applies to the next
+    /// acknowledge.
+    fn address_match(&mut self, direction: Direction) -> bool;
+
+    /// Called for each byte written by the host. Returns whether to
+    /// acknowledge the byte, or the next one when the clock is stretched
+    /// after the acknowledge, see [`I2cSlave::set_stretch_before_ack`]; the
+    /// host usually stops the transaction after a non-acknowledged byte.
+    fn write(&mut self, byte: u8) -> bool;
+
+    /// Called for each byte read by the host, returning the byte to send
+    fn read(&mut self) -> u8;
+
+    /// Called when the host ends the transaction with a stop condition
+    fn stop(&mut self) {}
+}
+
+/// I2C slave
+///
+/// See the [module-level documentation](self) for more details.
+pub struct I2cSlave<S, P0, P1> {
+    sercom: S,
+    sda: P0,
+    scl: P1,
+}
+
+impl<S, P0, P1> I2cSlave<S, P0, P1>
+where
+    S: Sercom,
+    P0: CompatiblePad<Sercom = S, PadNum = Pad0>,
+    P1: CompatiblePad<Sercom = S, PadNum = Pad1>,
+{
+    /// Configure the SERCOM as an I2C slave answering to the 7-bit
+    /// `address`, and enable it
+    ///
+    /// The SERCOM core clock must be configured beforehand. The slave
+    /// stretches the clock before acknowledging the address and each byte
+    /// written, so that the handler decides whether to acknowledge them.
+    /// Most SMBus-style devices need this, which is why the SERCOM default
+    /// of stretching after the acknowledge is not used.
+    pub fn new(apb: &mut APB_CLK_CTRL, mut sercom: S, sda: P0, scl: P1, address: u8) -> Self {
+        sercom.enable_apb_clock(apb);
+
+        let i2cs = sercom.i2cs();
+        i2cs.ctrla.write(|w| w.swrst().set_bit());
+        while i2cs.syncbusy.read().swrst().bit_is_set() {}
+
+        // Stretch the clock before the acknowledge
+        i2cs.ctrla.write(|w| w.mode().i2c_slave());
+        // SAFETY: ADDR accepts any 10-bit address, and ADDRMASK any 10-bit
+        // mask
+        i2cs.addr
+            .write(|w| unsafe { w.addr().bits((address & 0x7f) as u16) });
+
+        let mut slave = Self { sercom, sda, scl };
+        slave.enable();
+        slave
+    }
+
+    /// Disable the SERCOM and its bus clock and return it, along with the
+    /// pads
+    pub fn free(mut self) -> (S, P0, P1) {
+        self.disable();
+        bus_clock::release::<S>();
+        (self.sercom, self.sda, self.scl)
+    }
+
+    #[inline]
+    fn i2cs(&self) -> &I2CS {
+        self.sercom.i2cs()
+    }
+
+    fn enable(&mut self) {
+        self.i2cs().ctrla.modify(|_, w| w.enable().set_bit());
+        while self.i2cs().syncbusy.read().enable().bit_is_set() {}
+    }
+
+    fn disable(&mut self) {
+        self.i2cs().ctrla.modify(|_, w| w.enable().clear_bit());
+        while self.i2cs().syncbusy.read().enable().bit_is_set() {}
+    }
+
+    /// Set the address, and the mask of the address bits which are ignored
+    /// when matching, to answer to a range of addresses
+    pub fn set_address(&mut self, address: u8, mask: u8) {
+        self.disable();
+        // SAFETY: ADDR accepts any 10-bit address, and ADDRMASK any 10-bit
+        // mask
+        self.i2cs().addr.modify(|_, w| unsafe {
+            w.addr().bits((address & 0x7f) as u16);
+            w.addrmask().bits((mask & 0x7f) as u16)
+        });
+        self.enable();
+    }
+
+    /// Answer to the general call address 0, in addition to the configured
+    /// address
+    pub fn set_general_call(&mut self, enabled: bool) {
+        self.disable();
+        self.i2cs().addr.modify(|_, w| w.gencen().bit(enabled));
+        self.enable();
+    }
+
+    /// Select when the clock is stretched: before acknowledging the address
+    /// and each byte when `true`, the default, or after, when `false`
+    ///
+    /// Stretching after the acknowledge shortens the clock stretch, but the
+    /// address and each byte are then acknowledged before the handler is
+    /// called: the values returned by [`I2cSlaveHandler::address_match`] and
+    /// [`I2cSlaveHandler::write`] only apply to the next acknowledge, so an
+    /// address can't be rejected.
+    pub fn set_stretch_before_ack(&mut self, enabled: bool) {
+        self.disable();
+        self.i2cs().ctrla.modify(|_, w| w.sclsm().bit(!enabled));
+        self.enable();
+    }
+
+    /// Keep the slave running in standby, waking up the chip on address
+    /// match
+    pub fn set_run_in_standby(&mut self, enabled: bool) {
+        self.disable();
+        self.i2cs().ctrla.modify(|_, w| w.runstdby().bit(enabled));
+        self.enable();
+    }
+
+    /// Enable the address match, data ready, stop and error interrupts
+    /// handled by [`on_interrupt`](Self::on_interrupt)
+    pub fn enable_interrupts(&mut self) {
+        self.i2cs()
+            .intenset
+            .write(|w| unsafe { w.bits(PREC | AMATCH | DRDY | ERROR) });
+    }
+
+    /// Disable the interrupts handled by [`on_interrupt`](Self::on_interrupt)
+    pub fn disable_interrupts(&mut self) {
+        self.i2cs()
+            .intenclr
+            .write(|w| unsafe { w.bits(PREC | AMATCH | DRDY | ERROR) });
+    }
+
+    /// Handle the pending slave events with `handler`
+    ///
+    /// Call this function from the SERCOM interrupt handler. Every event
+    /// releases the clock stretch once handled.
+    pub fn on_interrupt(&mut self, handler: &mut impl I2cSlaveHandler) {
+        let i2cs = self.i2cs();
+        let flags = i2cs.intflag.read().bits();
+        let status = i2cs.status.read();
+        let direction = if status.dir().bit_is_set() {
+            Direction::Read
+        } else {
+            Direction::Write
+        };
+
+        if flags & ERROR != 0 {
+            // Writing a 1 clears the error status bits and the error flag
+            i2cs.status.write(|w| w.buserr().set_bit().coll().set_bit());
+            i2cs.intflag.write(|w| unsafe { w.bits(ERROR) });
+        }
+
+        if flags & AMATCH != 0 {
+            let ack = handler.address_match(direction);
+            self.command(ack, CMD_CONTINUE);
+        } else if flags & DRDY != 0 {
+            match direction {
+                Direction::Read => {
+                    if status.rxnack().bit_is_set() {
+                        // The host does not want any more data
+                        self.command(true, CMD_WAIT_START);
+                    } else {
+                        let byte = handler.read();
+                        // SAFETY: DATA accepts any byte
+                        i2cs.data.write(|w| unsafe { w.data().bits(byte as _) });
+                        self.command(true, CMD_CONTINUE);
+                    }
+                }
+                Direction::Write => {
+                    let data = i2cs.data.read().data().bits();
+                    #[cfg(feature = "min-samd51g")]
+                    let data = data as u8;
+                    let ack = handler.write(data);
+                    self.command(ack, CMD_CONTINUE);
+                }
+            }
+        }
+
+        if flags & PREC != 0 {
+            i2cs.intflag.write(|w| unsafe { w.bits(PREC) });
+            handler.stop();
+        }
+    }
+
+    /// Send an acknowledge, or a non-acknowledge, then execute `cmd`
+    fn command(&self, ack: bool, cmd: u8) {
+        // SAFETY: CMD accepts any 2-bit value
+        self.i2cs().ctrlb.modify(|_, w| unsafe {
+            w.ackact().bit(!ack);
+            w.cmd().bits(cmd)
+        });
+    }
+}
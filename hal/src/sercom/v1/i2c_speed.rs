@@ -0,0 +1,138 @@
+//! I2C bus speed modes
+//!
+//! The I2C master selects its speed mode from the requested bus frequency:
+//!
+//! * Up to 400 kHz, standard and fast modes;
+//! * Up to 1 MHz, fast mode plus;
+//! * Above 1 MHz, up to 3.4 MHz, high speed mode. Each transaction starts
+//!   with a master code sent in fast mode, so that slaves not supporting high
+//!   speed ignore the rest of the transaction. The generic clock must run at
+//!   least twice as fast as the bus.
+//!
+//! [`Speed`] provides the maximum frequency of each mode, and converts into a
+//! [`Hertz`] frequency accepted by the I2C master constructors:
+//!
+//! ```no_run
+//! use atsamd_hal::sercom::v1::i2c_speed::Speed;
+//!
+//! let i2c = I2CMaster3::new(&clock, Speed::FastPlus, sercom3, &mut pm, sda, scl);
+//! ```
+
+use crate::time::Hertz;
+
+/// `CTRLA.SPEED` value of the standard and fast modes
+pub(crate) const SPEED_FAST: u8 = 0;
+/// `CTRLA.SPEED` value of the fast mode plus
+pub(crate) const SPEED_FAST_PLUS: u8 = 1;
+/// `CTRLA.SPEED` value of the high speed mode
+pub(crate) const SPEED_HIGH: u8 = 2;
+
+/// I2C speed presets
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Speed {
+    /// Standard mode, 100 kHz
+    Standard,
+    /// Fast mode, 400 kHz
+    Fast,
+    /// Fast mode plus, 1 MHz
+    FastPlus,
+    /// High speed mode, 3.4 MHz
+    HighSpeed,
+}
+
+impl Speed {
+    /// Bus frequency of the preset
+    pub const fn frequency(self) -> Hertz {
+        match self {
+            Speed::Standard => Hertz(100_000),
+            Speed::Fast => Hertz(400_000),
+            Speed::FastPlus => Hertz(1_000_000),
+            Speed::HighSpeed => Hertz(3_400_000),
+        }
+    }
+}
+
+impl From<Speed> for Hertz {
+    #[inline]
+    fn from(speed: Speed) -> Hertz {
+        speed.frequency()
+    }
+}
+
+/// Register values configuring a bus frequency
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct BaudConfig {
+    /// `CTRLA.SPEED` value
+    pub speed: u8,
+    /// Whole `BAUD` register value
+    pub baud: u32,
+}
+
+/// Compute the register values generating `freq` from the `gclk` generic
+/// clock
+///
+/// In standard and fast modes, the `BAUD` field keeps its historical
+/// computation, ignoring the fixed 10 cycles of the datasheet formula. In
+/// fast mode plus, the SCL period is `10 + 2 * BAUD` cycles. In high speed
+/// mode, it is `2 + 2 * HSBAUD` cycles, while `BAUD` sets the frequency of the
+/// master code, at 400 kHz. Values out of range saturate at the slowest
+/// possible frequency, or the fastest.
+pub(crate) fn baud_config(gclk: Hertz, freq: Hertz) -> BaudConfig {
+    let half = |offset: u32, freq: u32| {
+        let baud = (gclk.0 / (2 * freq)).saturating_sub(offset);
+        baud.min(u8::MAX as u32)
+    };
+    if freq.0 <= Speed::Fast.frequency().0 {
+        BaudConfig {
+            speed: SPEED_FAST,
+            baud: half(1, freq.0),
+        }
+    } else if freq.0 <= Speed::FastPlus.frequency().0 {
+        BaudConfig {
+            speed: SPEED_FAST_PLUS,
+            baud: half(5, freq.0),
+        }
+    } else {
+        let master_code = half(1, Speed::Fast.frequency().0);
+        BaudConfig {
+            speed: SPEED_HIGH,
+            baud: master_code | half(1, freq.0) << 16,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fast_mode_keeps_legacy_baud() {
+        let config = baud_config(Hertz(48_000_000), Speed::Fast.into());
+        assert_eq!(config.speed, SPEED_FAST);
+        assert_eq!(config.baud, 59);
+    }
+
+    #[test]
+    fn fast_plus() {
+        let config = baud_config(Hertz(48_000_000), Speed::FastPlus.into());
+        assert_eq!(config.speed, SPEED_FAST_PLUS);
+        assert_eq!(10 + 2 * config.baud, 48);
+    }
+
+    #[test]
+    fn high_speed() {
+        let config = baud_config(Hertz(48_000_000), Speed::HighSpeed.into());
+        assert_eq!(config.speed, SPEED_HIGH);
+        assert_eq!(config.baud & 0xff, 59);
+        assert_eq!(config.baud >> 16, 6);
+    }
+
+    #[test]
+    fn saturates() {
+        assert_eq!(baud_config(Hertz(48_000_000), Hertz(10_000)).baud, 255);
+        assert_eq!(
+            baud_config(Hertz(1_000_000), Speed::FastPlus.into()).baud,
+            0
+        );
+    }
+}
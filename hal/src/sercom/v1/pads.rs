@@ -153,6 +153,10 @@ pad_alias!(Sercom6, Sercom7);
 pub trait CompatiblePad: Sealed {
     type Sercom: Sercom;
     type PadNum: PadNum;
+    /// [`PinId`] of the underlying pin
+    type Id: PinId;
+    /// [`PinMode`] of the underlying pin
+    type Mode: PinMode;
 }
 
 impl<S, N, P> CompatiblePad for Pad<S, N, P>
@@ -163,11 +167,15 @@ where
 {
     type Sercom = S;
     type PadNum = N;
+    type Id = P::Id;
+    type Mode = P::Mode;
 }
 
 impl<P: IsPad> CompatiblePad for P {
     type Sercom = P::Sercom;
     type PadNum = P::PadNum;
+    type Id = P::Id;
+    type Mode = P::Mode;
 }
 
 //==============================================================================
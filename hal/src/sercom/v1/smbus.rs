@@ -0,0 +1,425 @@
+//! SMBus and PMBus layer over an I2C master
+//!
+//! [`Smbus`] wraps any blocking I2C master, such as an
+//! [`I2CMaster`](super::I2CMaster), and implements the SMBus protocols used
+//! by battery gauges, PMBus power supplies and other SMBus devices. When
+//! packet error checking is enabled, every transaction carries the
+//! [`pec`] CRC-8 of the whole message, addresses included, which is appended
+//! to writes and checked on reads. Block reads also need the I2C master to
+//! implement [`BlockRead`], like [`I2CMaster`](super::I2CMaster) does.
+//!
+//! ```no_run
+//! use atsamd_hal::sercom::v1::smbus::Smbus;
+//!
+//! let mut smbus = Smbus::new(i2c);
+//! smbus.set_pec(true);
+//! // PMBus READ_VOUT
+//! let vout = smbus.read_word(0x40, 0x8b)?;
+//! // PMBus MFR_ID
+//! let mut id = [0; 32];
+//! let len = smbus.block_read(0x40, 0x99, &mut id)?;
+//! ```
+//!
+//! With host notify, a device reports an event by becoming the bus master
+//! and writing its address and a status word to the host address,
+//! [`HOST_ADDRESS`]. A SERCOM can be either a master or a slave, so host
+//! notify messages are received by an [`I2cSlave`] on a second SERCOM
+//! connected to the same bus, driven by a [`HostNotify`] handler.
+//!
+//! [`I2cSlave`]: super::i2c_slave::I2cSlave
+
+use crate::ehal::blocking::i2c::{Read, Write, WriteRead};
+
+use super::i2c_slave::{Direction, I2cSlaveHandler};
+
+/// Maximum number of data bytes of a block transfer
+pub const MAX_BLOCK_LEN: usize = 32;
+
+/// Address of the SMBus host, to which devices send host notify messages
+pub const HOST_ADDRESS: u8 = 0x08;
+
+/// Compute the packet error code of `bytes`, the SMBus CRC-8 with
+/// polynomial `x^8 + x^2 + x + 1`
+pub fn pec(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, &byte| pec_update(crc, byte))
+}
+
+fn pec_update(crc: u8, byte: u8) -> u8 {
+    (0..8).fold(crc ^ byte, |crc, _| {
+        if crc & 0x80 != 0 {
+            crc << 1 ^ 0x07
+        } else {
+            crc << 1
+        }
+    })
+}
+
+/// SMBus error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+    /// The I2C transaction failed
+    I2c(E),
+    /// The packet error code received does not match the message
+    Pec,
+    /// A block is longer than [`MAX_BLOCK_LEN`], or than the buffer
+    /// receiving it
+    BlockLength,
+}
+
+/// I2C master reading blocks whose length is given by their first byte
+///
+/// The SMBus block reads need the number of bytes to read to depend on the
+/// first byte received, which the `embedded-hal` traits can't express.
+pub trait BlockRead {
+    type Error;
+
+    /// Write `bytes` to `address`, then read a block into `buffer`, within the
+    /// same transaction: the length byte, then as many bytes as it gives,
+    /// plus `extra` bytes, not acknowledging the last one
+    ///
+    /// The bytes which don't fit in `buffer` aren't read. Returns the
+    /// number of bytes read, the length byte included.
+    fn write_read_block(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+        extra: usize,
+    ) -> Result<usize, Self::Error>;
+}
+
+/// SMBus master
+///
+/// See the [module-level documentation](self) for more details.
+pub struct Smbus<I2C> {
+    i2c: I2C,
+    pec: bool,
+}
+
+impl<I2C, E> Smbus<I2C>
+where
+    I2C: Read<Error = E> + Write<Error = E> + WriteRead<Error = E>,
+{
+    /// Wrap `i2c`, with packet error checking disabled
+    pub fn new(i2c: I2C) -> Self {
+        Self { i2c, pec: false }
+    }
+
+    /// Release the I2C master
+    pub fn free(self) -> I2C {
+        self.i2c
+    }
+
+    /// Enable or disable packet error checking
+    pub fn set_pec(&mut self, enabled: bool) {
+        self.pec = enabled;
+    }
+
+    /// Return whether packet error checking is enabled
+    pub fn pec(&self) -> bool {
+        self.pec
+    }
+
+    /// Write `command` and `data`, followed by the PEC when enabled
+    fn write(&mut self, address: u8, command: &[u8], data: &[u8]) -> Result<(), Error<E>> {
+        let mut buf = [0; MAX_BLOCK_LEN + 4];
+        let len = command.len() + data.len();
+        buf[..command.len()].copy_from_slice(command);
+        buf[command.len()..len].copy_from_slice(data);
+        let len = if self.pec {
+            buf[len] = pec_message(address, &buf[..len]);
+            len + 1
+        } else {
+            len
+        };
+        self.i2c.write(address, &buf[..len]).map_err(Error::I2c)
+    }
+
+    /// Write `command`, then read into `data`, checking the PEC when enabled
+    fn write_read(&mut self, address: u8, command: u8, data: &mut [u8]) -> Result<(), Error<E>> {
+        let mut buf = [0; MAX_BLOCK_LEN + 2];
+        let len = data.len() + self.pec as usize;
+        self.i2c
+            .write_read(address, &[command], &mut buf[..len])
+            .map_err(Error::I2c)?;
+        if self.pec {
+            let crc = pec(&[address << 1, command, address << 1 | 1]);
+            let crc = buf[..data.len()]
+                .iter()
+                .fold(crc, |crc, &b| pec_update(crc, b));
+            if crc != buf[data.len()] {
+                return Err(Error::Pec);
+            }
+        }
+        data.copy_from_slice(&buf[..data.len()]);
+        Ok(())
+    }
+
+    /// Quick command: address the device, writing no data
+    pub fn quick_command(&mut self, address: u8) -> Result<(), Error<E>> {
+        self.i2c.write(address, &[]).map_err(Error::I2c)
+    }
+
+    /// Send byte: write a single byte, without command code
+    pub fn send_byte(&mut self, address: u8, byte: u8) -> Result<(), Error<E>> {
+        self.write(address, &[], &[byte])
+    }
+
+    /// Receive byte: read a single byte, without command code
+    pub fn receive_byte(&mut self, address: u8) -> Result<u8, Error<E>> {
+        let mut buf = [0; 2];
+        let len = 1 + self.pec as usize;
+        self.i2c
+            .read(address, &mut buf[..len])
+            .map_err(Error::I2c)?;
+        if self.pec && pec(&[address << 1 | 1, buf[0]]) != buf[1] {
+            return Err(Error::Pec);
+        }
+        Ok(buf[0])
+    }
+
+    /// Write byte: write `byte` to `command`
+    pub fn write_byte(&mut self, address: u8, command: u8, byte: u8) -> Result<(), Error<E>> {
+        self.write(address, &[command], &[byte])
+    }
+
+    /// Read byte: read a byte from `command`
+    pub fn read_byte(&mut self, address: u8, command: u8) -> Result<u8, Error<E>> {
+        let mut buf = [0; 1];
+        self.write_read(address, command, &mut buf)?;
+        Ok(buf[0])
+    }
+
+    /// Write word: write `word` to `command`, least significant byte first
+    pub fn write_word(&mut self, address: u8, command: u8, word: u16) -> Result<(), Error<E>> {
+        self.write(address, &[command], &word.to_le_bytes())
+    }
+
+    /// Read word: read a word from `command`, least significant byte first
+    pub fn read_word(&mut self, address: u8, command: u8) -> Result<u16, Error<E>> {
+        let mut buf = [0; 2];
+        self.write_read(address, command, &mut buf)?;
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    /// Block write: write the length of `data`, then `data`, to `command`
+    pub fn block_write(&mut self, address: u8, command: u8, data: &[u8]) -> Result<(), Error<E>> {
+        if data.len() > MAX_BLOCK_LEN {
+            return Err(Error::BlockLength);
+        }
+        self.write(address, &[command, data.len() as u8], data)
+    }
+
+    /// Process call: write `word` to `command`, then read a word back
+    pub fn process_call(&mut self, address: u8, command: u8, word: u16) -> Result<u16, Error<E>> {
+        let mut buf = [0; 3];
+        let len = 2 + self.pec as usize;
+        let [low, high] = word.to_le_bytes();
+        self.i2c
+            .write_read(address, &[command, low, high], &mut buf[..len])
+            .map_err(Error::I2c)?;
+        if self.pec {
+            let crc = pec(&[
+                address << 1,
+                command,
+                low,
+                high,
+                address << 1 | 1,
+                buf[0],
+                buf[1],
+            ]);
+            if crc != buf[2] {
+                return Err(Error::Pec);
+            }
+        }
+        Ok(u16::from_le_bytes([buf[0], buf[1]]))
+    }
+}
+
+impl<I2C, E> Smbus<I2C>
+where
+    I2C: BlockRead<Error = E>,
+{
+    /// Block read: read a block from `command` into `data`, returning its
+    /// length
+    ///
+    /// The length byte received first gives the number of bytes read next,
+    /// followed by the PEC when enabled.
+    pub fn block_read(
+        &mut self,
+        address: u8,
+        command: u8,
+        data: &mut [u8],
+    ) -> Result<usize, Error<E>> {
+        let mut buf = [0; MAX_BLOCK_LEN + 2];
+        self.i2c
+            .write_read_block(address, &[command], &mut buf, self.pec as usize)
+            .map_err(Error::I2c)?;
+        let len = buf[0] as usize;
+        if len > MAX_BLOCK_LEN || len > data.len() {
+            return Err(Error::BlockLength);
+        }
+        if self.pec {
+            let crc = pec(&[address << 1, command, address << 1 | 1]);
+            let crc = buf[..len + 1]
+                .iter()
+                .fold(crc, |crc, &b| pec_update(crc, b));
+            if crc != buf[len + 1] {
+                return Err(Error::Pec);
+            }
+        }
+        data[..len].copy_from_slice(&buf[1..len + 1]);
+        Ok(len)
+    }
+}
+
+/// PEC of a write message to `address`
+fn pec_message(address: u8, bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(pec_update(0, address << 1), |crc, &b| pec_update(crc, b))
+}
+
+/// Host notify message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Notification {
+    /// 7-bit address of the device sending the notification
+    pub address: u8,
+    /// Status word sent by the device
+    pub status: u16,
+}
+
+/// [`I2cSlaveHandler`] receiving host notify messages
+///
+/// Drive an [`I2cSlave`](super::i2c_slave::I2cSlave) configured with the [`HOST_ADDRESS`] with this
+/// handler, then [`take`](Self::take) the notifications received. Read
+/// requests are not acknowledged, which requires the clock to be stretched
+/// before the acknowledge, the default of the slave.
+#[derive(Default)]
+pub struct HostNotify {
+    buf: [u8; 3],
+    len: usize,
+    notification: Option<Notification>,
+}
+
+impl HostNotify {
+    /// Create a handler, with no notification received
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; 3],
+            len: 0,
+            notification: None,
+        }
+    }
+
+    /// Return the last notification received, if any, and clear it
+    pub fn take(&mut self) -> Option<Notification> {
+        self.notification.take()
+    }
+}
+
+impl I2cSlaveHandler for HostNotify {
+    fn address_match(&mut self, direction: Direction) -> bool {
+        self.len = 0;
+        direction == Direction::Write
+    }
+
+    fn write(&mut self, byte: u8) -> bool {
+        if self.len < self.buf.len() {
+            self.buf[self.len] = byte;
+            self.len += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn read(&mut self) -> u8 {
+        0xff
+    }
+
+    fn stop(&mut self) {
+        if self.len == self.buf.len() {
+            self.notification = Some(Notification {
+                address: self.buf[0] >> 1,
+                status: u16::from_le_bytes([self.buf[1], self.buf[2]]),
+            });
+        }
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pec_check_value() {
+        // CRC-8/SMBUS check value
+        assert_eq!(pec(b"123456789"), 0xf4);
+        assert_eq!(pec(&[]), 0);
+    }
+
+    /// Device answering block reads with the length byte, the block and its
+    /// PEC, without limiting the bytes read
+    struct Device {
+        block: [u8; 3],
+    }
+
+    impl Read for Device {
+        type Error = ();
+        fn read(&mut self, _: u8, _: &mut [u8]) -> Result<(), ()> {
+            Err(())
+        }
+    }
+
+    impl Write for Device {
+        type Error = ();
+        fn write(&mut self, _: u8, _: &[u8]) -> Result<(), ()> {
+            Err(())
+        }
+    }
+
+    impl WriteRead for Device {
+        type Error = ();
+        fn write_read(&mut self, _: u8, _: &[u8], _: &mut [u8]) -> Result<(), ()> {
+            Err(())
+        }
+    }
+
+    impl BlockRead for Device {
+        type Error = ();
+        fn write_read_block(
+            &mut self,
+            address: u8,
+            bytes: &[u8],
+            buffer: &mut [u8],
+            extra: usize,
+        ) -> Result<usize, ()> {
+            let mut message = [address << 1, bytes[0], address << 1 | 1, 3, 0, 0, 0, 0];
+            message[4..7].copy_from_slice(&self.block);
+            message[7] = pec(&message[..7]);
+            let len = 1 + 3 + extra;
+            buffer[..len].copy_from_slice(&message[3..3 + len]);
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn block_read() {
+        let mut smbus = Smbus::new(Device { block: [1, 2, 3] });
+        smbus.set_pec(true);
+        let mut data = [0; MAX_BLOCK_LEN];
+        assert_eq!(smbus.block_read(0x40, 0x99, &mut data), Ok(3));
+        assert_eq!(data[..3], [1, 2, 3]);
+        assert_eq!(
+            smbus.block_read(0x40, 0x99, &mut data[..2]),
+            Err(Error::BlockLength)
+        );
+    }
+
+    #[test]
+    fn pec_write_message() {
+        assert_eq!(pec_message(0x5a, &[0x01, 0x02]), pec(&[0xb4, 0x01, 0x02]));
+    }
+}
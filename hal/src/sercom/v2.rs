@@ -27,6 +27,7 @@ use pac::{SERCOM6, SERCOM7};
 #[cfg(feature = "dma")]
 use crate::common::dmac::TriggerSource;
 
+use crate::bus_clock::BusClock;
 use crate::typelevel::Sealed;
 
 pub mod pad;
@@ -37,6 +38,9 @@ pub mod spi;
 pub mod spi_future;
 pub mod uart;
 
+#[cfg(feature = "async")]
+pub mod async_api;
+
 #[cfg(feature = "dma")]
 pub mod dma;
 
@@ -45,9 +49,11 @@ pub mod dma;
 //==============================================================================
 
 /// Type-level `enum` representing a Serial Communication Interface (SERCOM)
-pub trait Sercom: Sealed + Deref<Target = sercom0::RegisterBlock> {
+pub trait Sercom: Sealed + BusClock + Deref<Target = sercom0::RegisterBlock> {
     /// SERCOM number
     const NUM: usize;
+    /// Pointer to the SERCOM's register block
+    const PTR: *const sercom0::RegisterBlock;
     /// RX Trigger source for DMA transactions
     #[cfg(feature = "dma")]
     const DMA_RX_TRIGGER: TriggerSource;
@@ -55,11 +61,14 @@ pub trait Sercom: Sealed + Deref<Target = sercom0::RegisterBlock> {
     #[cfg(feature = "dma")]
     const DMA_TX_TRIGGER: TriggerSource;
     /// Enable the corresponding APB clock
-    fn enable_apb_clock(&mut self, ctrl: &APB_CLK_CTRL);
+    #[inline]
+    fn enable_apb_clock(&mut self, ctrl: &APB_CLK_CTRL) {
+        Self::enable_bus_clock(ctrl);
+    }
 }
 
 macro_rules! sercom {
-    ( $apbmask:ident: ($start:literal, $end:literal) ) => {
+    ( $start:literal, $end:literal ) => {
         seq!(N in $start..=$end {
             paste! {
                 /// Type alias for the corresponding SERCOM instance
@@ -67,14 +76,11 @@ macro_rules! sercom {
                 impl Sealed for Sercom #N {}
                 impl Sercom for Sercom #N {
                     const NUM: usize = N;
+                    const PTR: *const sercom0::RegisterBlock = SERCOM #N::ptr();
                     #[cfg(feature = "dma")]
                     const DMA_RX_TRIGGER: TriggerSource = TriggerSource::[<SERCOM #N _RX>];
                     #[cfg(feature = "dma")]
                     const DMA_TX_TRIGGER: TriggerSource = TriggerSource::[<SERCOM #N _TX>];
-                    #[inline]
-                    fn enable_apb_clock(&mut self, ctrl: &APB_CLK_CTRL) {
-                        ctrl.$apbmask.modify(|_, w| w.[<sercom #N _>]().set_bit());
-                    }
                 }
             }
         });
@@ -82,17 +88,17 @@ macro_rules! sercom {
 }
 
 #[cfg(any(feature = "samd11", feature = "samd21"))]
-sercom!(apbcmask: (0, 1));
+sercom!(0, 1);
 #[cfg(feature = "samd21")]
-sercom!(apbcmask: (2, 3));
+sercom!(2, 3);
 #[cfg(feature = "min-samd21g")]
-sercom!(apbcmask: (4, 5));
+sercom!(4, 5);
 
 #[cfg(feature = "min-samd51g")]
-sercom!(apbamask: (0, 1));
+sercom!(0, 1);
 #[cfg(feature = "min-samd51g")]
-sercom!(apbbmask: (2, 3));
+sercom!(2, 3);
 #[cfg(feature = "min-samd51g")]
-sercom!(apbdmask: (4, 5));
+sercom!(4, 5);
 #[cfg(feature = "min-samd51n")]
-sercom!(apbdmask: (6, 7));
+sercom!(6, 7);
@@ -0,0 +1,81 @@
+//! Interrupt handling shared by the `async` SERCOM drivers
+//!
+//! Every `async` SERCOM driver waits on interrupt flags. The application must
+//! call [`on_interrupt`] from the interrupt handler(s) of each [`Sercom`] used
+//! with an `async` driver. On SAMD11 and SAMD21 chips, each SERCOM has a
+//! single interrupt line (eg. `SERCOM0`). On SAMx5x chips, each SERCOM has four
+//! interrupt lines (eg. `SERCOM0_0` to `SERCOM0_3`), and [`on_interrupt`] must
+//! be called from all of them.
+//!
+//! ```
+//! use atsamd_hal::pac::interrupt;
+//! use atsamd_hal::sercom::v2::{async_api, Sercom0};
+//!
+//! #[interrupt]
+//! fn SERCOM0() {
+//!     async_api::on_interrupt::<Sercom0>();
+//! }
+//! ```
+//!
+//! The handler disables every enabled interrupt that has fired, then wakes
+//! the task waiting on it. The `INTFLAG` bits 0 and 1 (`DRE` and `TXC` for
+//! UART and SPI, `MB` and `SB` for I2C master) wake the transmit side, while
+//! all other bits wake the receive side. This allows the two halves of a split
+//! UART to be awaited from different tasks.
+
+use crate::async_hal::InterruptWaker;
+
+use super::Sercom;
+
+/// `INTFLAG` bits routed to the transmit side waker
+const TX_FLAGS: u8 = 0x03;
+
+/// Maximum number of SERCOM instances among supported chips
+const NUM_SERCOM: usize = 8;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NEW_WAKER: InterruptWaker = InterruptWaker::new();
+
+static RX_WAKERS: [InterruptWaker; NUM_SERCOM] = [NEW_WAKER; NUM_SERCOM];
+static TX_WAKERS: [InterruptWaker; NUM_SERCOM] = [NEW_WAKER; NUM_SERCOM];
+
+/// Waker woken by receive side interrupts of `S`
+#[inline]
+pub(crate) fn rx_waker<S: Sercom>() -> &'static InterruptWaker {
+    &RX_WAKERS[S::NUM]
+}
+
+/// Waker woken by transmit side interrupts of `S`
+#[inline]
+pub(crate) fn tx_waker<S: Sercom>() -> &'static InterruptWaker {
+    &TX_WAKERS[S::NUM]
+}
+
+/// SERCOM interrupt handler for the `async` drivers
+///
+/// Must be called from every interrupt handler of the [`Sercom`] `S`. See the
+/// [module-level documentation](self) for more details.
+#[inline]
+pub fn on_interrupt<S: Sercom>() {
+    // SAFETY: INTFLAG, INTENSET and INTENCLR share the same layout in every
+    // SERCOM mode. Only interrupts that already fired are disabled, which the
+    // waiting futures expect.
+    #[cfg(any(feature = "samd11", feature = "samd21"))]
+    let regs = unsafe { (*S::PTR).usart() };
+    #[cfg(feature = "min-samd51g")]
+    let regs = unsafe { (*S::PTR).usart_int() };
+
+    let enabled = regs.intenset.read().bits();
+    let fired = regs.intflag.read().bits() & enabled;
+    if fired == 0 {
+        return;
+    }
+    regs.intenclr.write(|w| unsafe { w.bits(fired) });
+
+    if fired & TX_FLAGS != 0 {
+        TX_WAKERS[S::NUM].wake();
+    }
+    if fired & !TX_FLAGS != 0 {
+        RX_WAKERS[S::NUM].wake();
+    }
+}
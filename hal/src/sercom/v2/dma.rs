@@ -3,12 +3,14 @@
 //! See the [`mod@uart`] and [`mod@spi`] modules for the corresponding DMA
 //! transfer implementations.
 
+use core::sync::atomic;
+
 use crate::{
     dmac::{
         self,
         channel::{AnyChannel, Busy, CallbackStatus, Channel, InterruptFlags, Ready},
         transfer::BufferPair,
-        Beat, Buffer, Transfer, TriggerAction,
+        Beat, Buffer, ChId, Transfer, TriggerAction,
     },
     sercom::v2::{
         spi::{self, Spi},
@@ -122,6 +124,149 @@ where
     }
 }
 
+//=============================================================================
+// UART circular DMA reception
+//=============================================================================
+
+/// Continuous UART reception into a ring buffer, using a circular DMA
+/// [`Transfer`]
+///
+/// Create this struct with [`Uart::receive_circular_with_dma`]. The DMAC
+/// writes every received word into the ring buffer, wrapping around at its
+/// end, without any CPU intervention. The next position written by the DMAC
+/// is tracked through the writeback section of the channel (see
+/// [`Transfer::remaining_beats`]), and the words received since the last read
+/// are copied out with [`read_available`](Self::read_available).
+///
+/// The ring buffer must be read often enough for the DMAC not to lap the
+/// reader. Words overwritten before being read are lost without notice, and
+/// the words returned by the following read are then unreliable.
+pub struct CircularReceive<Id, C, D>
+where
+    Id: ChId,
+    C: uart::ValidConfig,
+    C::Word: Beat,
+    D: uart::Receive,
+{
+    #[allow(clippy::type_complexity)]
+    transfer: Transfer<Channel<Id, Busy>, BufferPair<Uart<C, D>, &'static mut [C::Word]>>,
+    ring: *const C::Word,
+    len: usize,
+    read_index: usize,
+}
+
+// SAFETY: `ring` points into the ring buffer owned by the transfer, and is
+// only used to read the words written by the DMAC
+unsafe impl<Id, C, D> Send for CircularReceive<Id, C, D>
+where
+    Id: ChId,
+    C: uart::ValidConfig,
+    C::Word: Beat,
+    D: uart::Receive,
+    Transfer<Channel<Id, Busy>, BufferPair<Uart<C, D>, &'static mut [C::Word]>>: Send,
+{
+}
+
+impl<Id, C, D> CircularReceive<Id, C, D>
+where
+    Id: ChId,
+    C: uart::ValidConfig,
+    C::Word: Beat,
+    D: uart::Receive,
+{
+    /// Index of the next word the DMAC will write into the ring buffer
+    #[inline]
+    fn write_index(&self) -> usize {
+        let remaining = self.transfer.remaining_beats().min(self.len);
+        (self.len - remaining) % self.len
+    }
+
+    /// Number of words received, but not read yet
+    #[inline]
+    pub fn available(&self) -> usize {
+        (self.write_index() + self.len - self.read_index) % self.len
+    }
+
+    /// Copy the words received since the last read into `buffer`
+    ///
+    /// Returns the number of words copied, which is limited by the length of
+    /// `buffer`. The words that do not fit are kept for the next read.
+    pub fn read_available(&mut self, buffer: &mut [C::Word]) -> usize {
+        let count = self.available().min(buffer.len());
+
+        // Memory barrier to prevent the compiler/CPU from reading the ring
+        // buffer before the writeback section.
+        atomic::fence(atomic::Ordering::Acquire); // ▼
+
+        for slot in buffer[..count].iter_mut() {
+            // SAFETY: The index is always within the ring buffer, and the
+            // DMAC is done writing to it, since it is behind the write index.
+            *slot = unsafe { self.ring.add(self.read_index).read_volatile() };
+            self.read_index = (self.read_index + 1) % self.len;
+        }
+        count
+    }
+
+    /// Stop the reception and release the DMA channel, [`Uart`] and ring
+    /// buffer
+    #[inline]
+    #[allow(clippy::type_complexity)]
+    pub fn stop(self) -> (Channel<Id, Ready>, Uart<C, D>, &'static mut [C::Word]) {
+        self.transfer.stop()
+    }
+}
+
+impl<C, D> Uart<C, D>
+where
+    Self: Buffer<Beat = C::Word>,
+    C: uart::ValidConfig,
+    C::Word: Beat,
+    D: uart::Receive,
+{
+    /// Transform an [`Uart`] into a [`CircularReceive`] and start receiving
+    /// continuously into the provided ring buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ring buffer length is less than 2 or more than
+    /// [`u16::MAX`] words, the maximum length of a DMA block transfer.
+    #[inline]
+    pub fn receive_circular_with_dma<Ch>(
+        self,
+        ring: &'static mut [C::Word],
+        channel: Ch,
+    ) -> CircularReceive<Ch::Id, C, D>
+    where
+        Ch: AnyChannel<Status = Ready>,
+    {
+        let len = ring.len();
+        assert!(
+            (2..=u16::MAX as usize).contains(&len),
+            "Invalid ring buffer length"
+        );
+        let ring_ptr = ring.as_ptr();
+
+        #[cfg(feature = "min-samd51g")]
+        let trigger_action = TriggerAction::BURST;
+
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        let trigger_action = TriggerAction::BEAT;
+
+        // SAFETY: We use new_unchecked to avoid having to pass a 'static self as the
+        // source buffer. This is safe as long as we guarantee the destination
+        // buffer is static.
+        let transfer = unsafe { Transfer::new_unchecked(channel, self, ring, true) }
+            .begin(C::Sercom::DMA_RX_TRIGGER, trigger_action);
+
+        CircularReceive {
+            transfer,
+            ring: ring_ptr,
+            len,
+            read_index: 0,
+        }
+    }
+}
+
 //=============================================================================
 // SPI DMA transfers
 //=============================================================================
@@ -129,7 +274,6 @@ where
 unsafe impl<C, A> Buffer for Spi<C, A>
 where
     C: spi::ValidConfig,
-    C::OpMode: spi::MasterMode,
     C::Size: spi::AtomicSize<Word = C::Word>,
     C::Word: Beat,
     A: spi::Capability,
@@ -21,6 +21,14 @@
 //!   `PinMode` for a given SERCOM pad, while the [`Pad`] alias recovers the
 //!   configured [`Pin`] type.
 //!
+//! # Package variants
+//!
+//! The pad mappings follow the pins and SERCOM instances of each package.
+//! Pins that aren't bonded out, such as `PA12` on SAMD21E chips, have no
+//! [`PinId`], and the SERCOMs missing from a chip, such as `Sercom4` on
+//! SAMD21E chips, have no type. Using a pad that doesn't exist on the
+//! selected package therefore fails to compile, rather than at run-time.
+//!
 //! [`AlternateC`]: crate::gpio::v2::AlternateC
 //! [`AlternateD`]: crate::gpio::v2::AlternateD
 //! [type class]: crate::typelevel#type-classes
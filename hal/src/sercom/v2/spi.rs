@@ -260,6 +260,28 @@
 //! let rcvd: u16 = block!(spi.read());
 //! ```
 //!
+//! # Slave mode
+//!
+//! In [`Slave`] mode, the host drives the clock and the `SS` pad, and the
+//! `Spi` answers with the words written to it. The words to send must be
+//! written before the host clocks them out, so slaves usually enable
+//! [`preload`](Config::preload), to send a first word as soon as `SS` goes
+//! low, and [`ss_detect`](Config::ss_detect), to be notified of the start of
+//! a transaction with the [`SSL`](Flags::SSL) interrupt. The end of the
+//! transaction is signalled by the `TXC` flag, once `SS` goes high again.
+//!
+//! ```
+//! let mut spi = spi::Config::new(&mclk, sercom, pads, freq)
+//!     .op_mode::<Slave>()
+//!     .preload(true)
+//!     .ss_detect(true)
+//!     .enable();
+//! spi.enable_interrupts(Flags::SSL | Flags::TXC);
+//! ```
+//!
+//! Slaves also support DMA transfers, which feed the transmitter fast enough
+//! to respond to hosts at high baud rates.
+//!
 //! [`enable`]: Config::enable
 //! [`gpio`]: crate::gpio::v2
 //! [`Pin`]: crate::gpio::v2::pin::Pin
@@ -301,6 +323,71 @@ let (chan0, _, spi, _) = dma_transfer.wait();
 [`dmac`]: crate::dmac
 "
 )]
+#![cfg_attr(
+    feature = "async",
+    doc = "
+# `async` operation
+
+An [`Spi`] can be converted into an [`SpiFuture`] with
+[`into_future`](Spi::into_future). A `Duplex` [`SpiFuture`] in master mode
+implements the [`embedded_hal_async`] `SpiBus` trait for 8-bit words. Instead
+of busy-waiting on the `DRE` and `RXC` flags, it enables the corresponding
+interrupts and yields until [`async_api::on_interrupt`] wakes it up. Note that
+the `async` feature must be enabled.
+
+```
+use atsamd_hal::pac::interrupt;
+use atsamd_hal::sercom::v2::{async_api, Sercom0};
+use embedded_hal_async::spi::SpiBus;
+
+#[interrupt]
+fn SERCOM0() {
+    async_api::on_interrupt::<Sercom0>();
+}
+
+// Assume config is a valid Duplex SPI Config struct
+let mut spi = config.enable().into_future();
+let mut buf = [0x55_u8; 4];
+spi.transfer_in_place(&mut buf).await.unwrap();
+```
+
+[`async_api::on_interrupt`]: crate::sercom::v2::async_api::on_interrupt
+"
+)]
+#![cfg_attr(
+    all(feature = "async", feature = "dma"),
+    doc = "
+## `async` DMA transfers
+
+DMA channels can be handed to an [`SpiFuture`] with
+[`with_dma_channels`](SpiFuture::with_dma_channels). Transfers at least as long
+as the DMA threshold, [`DEFAULT_DMA_THRESHOLD`] words unless changed with
+[`set_dma_threshold`](SpiFuture::set_dma_threshold), are then executed by the
+DMAC, and shorter ones with interrupts. The completion of DMA transfers is
+signaled by the DMAC interrupt, whose handler must call
+[`dmac::async_api::on_interrupt`].
+
+```
+use atsamd_hal::dmac::{self, async_api};
+use atsamd_hal::pac::interrupt;
+
+#[interrupt]
+fn DMAC() {
+    async_api::on_interrupt();
+}
+
+// Assume rx_channel and tx_channel are `Ready` DMA channels
+let mut spi = config
+    .enable()
+    .into_future()
+    .with_dma_channels(rx_channel, tx_channel);
+let mut buf = [0_u8; 512];
+spi.read(&mut buf).await.unwrap();
+```
+
+[`dmac::async_api::on_interrupt`]: crate::dmac::async_api::on_interrupt
+"
+)]
 
 use core::convert::TryFrom;
 use core::marker::PhantomData;
@@ -309,6 +396,7 @@ use bitflags::bitflags;
 use embedded_hal::spi;
 pub use embedded_hal::spi::{Phase, Polarity, MODE_0, MODE_1, MODE_2, MODE_3};
 
+use crate::bus_clock;
 use crate::sercom::v2::*;
 use crate::time::Hertz;
 use crate::typelevel::{Is, NoneT, Sealed};
@@ -361,6 +449,16 @@ pub mod impl_ehal;
 #[path = "spi/impl_ehal_thumbv7em.rs"]
 pub mod impl_ehal;
 
+#[cfg(feature = "ehal1")]
+#[path = "spi/impl_ehal1.rs"]
+pub mod impl_ehal1;
+
+#[cfg(feature = "async")]
+#[clippy::msrv = "1.75"]
+mod impl_async;
+#[cfg(feature = "async")]
+pub use impl_async::*;
+
 //=============================================================================
 // BitOrder
 //=============================================================================
@@ -431,10 +529,17 @@ impl TryFrom<Status> for () {
 ///
 /// The SPI peripheral only has two error types, buffer overflow and transaction
 /// length error.
+#[non_exhaustive]
 #[derive(Debug)]
 pub enum Error {
     Overflow,
     LengthError,
+    /// The operation is not supported by the hardware, such as a delay
+    /// within a [`MasterHWSS`] transaction
+    Unsupported,
+    /// Error reported by the DMAC during an `async` DMA transfer
+    #[cfg(all(feature = "async", feature = "dma"))]
+    Dma(crate::dmac::Error),
 }
 
 //=============================================================================
@@ -726,11 +831,12 @@ where
         Config::default(self.regs.sercom, self.pads, self.freq)
     }
 
-    /// Consume the [`Config`], reset the peripheral, and return the [`Sercom`]
-    /// and [`Pads`]
+    /// Consume the [`Config`], reset the peripheral, disable its APB clock,
+    /// and return the [`Sercom`] and [`Pads`]
     #[inline]
     pub fn free(mut self) -> (P::Sercom, P) {
         self.regs.reset();
+        bus_clock::release::<P::Sercom>();
         (self.regs.sercom, self.pads)
     }
 
@@ -956,6 +1062,71 @@ where
     }
 }
 
+impl<P, Z> Config<P, Slave, Z>
+where
+    P: ValidPads,
+    Z: Size,
+{
+    /// Read the enable state of data preloading
+    ///
+    /// When enabled, a word written to the `DATA` register while `SS` is high
+    /// is moved to the shift register as soon as `SS` goes low, so the host
+    /// receives it with the first clock edge of the transaction.
+    #[inline]
+    pub fn get_preload(&self) -> bool {
+        self.regs.get_preload()
+    }
+
+    /// Enable or disable data preloading
+    ///
+    /// When enabled, a word written to the `DATA` register while `SS` is high
+    /// is moved to the shift register as soon as `SS` goes low, so the host
+    /// receives it with the first clock edge of the transaction.
+    #[inline]
+    pub fn set_preload(&mut self, enabled: bool) {
+        self.regs.set_preload(enabled);
+    }
+
+    /// Enable or disable data preloading using the builder API
+    ///
+    /// When enabled, a word written to the `DATA` register while `SS` is high
+    /// is moved to the shift register as soon as `SS` goes low, so the host
+    /// receives it with the first clock edge of the transaction.
+    #[inline]
+    pub fn preload(mut self, enabled: bool) -> Self {
+        self.set_preload(enabled);
+        self
+    }
+
+    /// Read the enable state of slave select low detection
+    ///
+    /// When enabled, the [`SSL`](Flags::SSL) flag is set when the host pulls
+    /// `SS` low, which can wake the chip from standby through its interrupt.
+    #[inline]
+    pub fn get_ss_detect(&self) -> bool {
+        self.regs.get_ss_detect()
+    }
+
+    /// Enable or disable slave select low detection
+    ///
+    /// When enabled, the [`SSL`](Flags::SSL) flag is set when the host pulls
+    /// `SS` low, which can wake the chip from standby through its interrupt.
+    #[inline]
+    pub fn set_ss_detect(&mut self, enabled: bool) {
+        self.regs.set_ss_detect(enabled);
+    }
+
+    /// Enable or disable slave select low detection using the builder API
+    ///
+    /// When enabled, the [`SSL`](Flags::SSL) flag is set when the host pulls
+    /// `SS` low, which can wake the chip from standby through its interrupt.
+    #[inline]
+    pub fn ss_detect(mut self, enabled: bool) -> Self {
+        self.set_ss_detect(enabled);
+        self
+    }
+}
+
 #[cfg(feature = "min-samd51g")]
 impl<P, M> Config<P, M, DynLength>
 where
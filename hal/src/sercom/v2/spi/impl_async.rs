@@ -0,0 +1,447 @@
+//! `async` SPI driver
+//!
+//! See the [`spi`](crate::sercom::v2::spi) module-level documentation.
+
+use core::future::poll_fn;
+use core::task::Poll;
+
+use embedded_hal_async::spi as ehal_spi;
+
+use crate::async_hal::InterruptWaker;
+use crate::sercom::v2::async_api::{rx_waker, tx_waker};
+use crate::typelevel::NoneT;
+
+#[cfg(feature = "dma")]
+use crate::dmac::{
+    transfer::{RawBuffer, MAX_TRANSFER_LENGTH},
+    channel::{AnyChannel, Ready},
+    TriggerAction,
+};
+#[cfg(feature = "dma")]
+use crate::sercom::v2::Sercom;
+
+#[cfg(feature = "dma")]
+use super::Size;
+use super::{Capability, DataWidth, Duplex, Error, Flags, MasterMode, Spi, ValidConfig};
+
+/// Default minimum length of a transfer, in words, for it to be executed with
+/// DMA
+#[cfg(feature = "dma")]
+pub const DEFAULT_DMA_THRESHOLD: usize = 16;
+
+impl<C, A> Spi<C, A>
+where
+    C: ValidConfig,
+    A: Capability,
+{
+    /// Turn an [`Spi`] into an [`SpiFuture`]
+    ///
+    /// All SPI interrupts are disabled, as they are managed by the
+    /// [`SpiFuture`] from now on.
+    #[inline]
+    pub fn into_future(mut self) -> SpiFuture<C, A> {
+        self.disable_interrupts(Flags::all());
+        SpiFuture {
+            spi: self,
+            rx_channel: NoneT,
+            tx_channel: NoneT,
+            dma_threshold: usize::MAX,
+        }
+    }
+}
+
+/// `async` version of [`Spi`]
+///
+/// Create this struct by calling [`into_future`](Spi::into_future) on an
+/// [`Spi`]. Optionally, DMA channels can be provided with
+/// [`with_dma_channels`](SpiFuture::with_dma_channels). See the
+/// [`spi`](crate::sercom::v2::spi) module-level documentation for more
+/// details.
+///
+/// Not to be confused with the interrupt-driven
+/// [`spi_future::SpiFuture`](crate::sercom::v2::spi_future::SpiFuture),
+/// which predates `async` support in the HAL.
+#[cfg_attr(not(feature = "dma"), allow(dead_code))]
+pub struct SpiFuture<C, A, R = NoneT, T = NoneT>
+where
+    C: ValidConfig,
+    A: Capability,
+{
+    spi: Spi<C, A>,
+    rx_channel: R,
+    tx_channel: T,
+    dma_threshold: usize,
+}
+
+impl<C, A> SpiFuture<C, A>
+where
+    C: ValidConfig,
+    A: Capability,
+{
+    /// Add DMA channels to the [`SpiFuture`]
+    ///
+    /// Transfers of at least [`DEFAULT_DMA_THRESHOLD`] words are then executed
+    /// by the DMAC, using `rx_channel` to read the received words, and
+    /// `tx_channel` to write the words to send. See
+    /// [`set_dma_threshold`](SpiFuture::set_dma_threshold) to change the
+    /// threshold.
+    #[cfg(feature = "dma")]
+    #[inline]
+    pub fn with_dma_channels<R, T>(self, rx_channel: R, tx_channel: T) -> SpiFuture<C, A, R, T>
+    where
+        R: AnyChannel<Status = Ready>,
+        T: AnyChannel<Status = Ready>,
+    {
+        SpiFuture {
+            spi: self.spi,
+            rx_channel,
+            tx_channel,
+            dma_threshold: DEFAULT_DMA_THRESHOLD,
+        }
+    }
+
+    /// Return the underlying [`Spi`]
+    #[inline]
+    pub fn free(mut self) -> Spi<C, A> {
+        self.spi.disable_interrupts(Flags::all());
+        self.spi
+    }
+}
+
+#[cfg(feature = "dma")]
+impl<C, A, R, T> SpiFuture<C, A, R, T>
+where
+    C: ValidConfig,
+    A: Capability,
+    R: AnyChannel<Status = Ready>,
+    T: AnyChannel<Status = Ready>,
+{
+    /// Set the minimum length of a transfer, in words, for it to be executed
+    /// with DMA
+    ///
+    /// Shorter transfers are executed with interrupts, as the cost of setting
+    /// up the DMA channels outweighs the gain.
+    #[inline]
+    pub fn set_dma_threshold(&mut self, threshold: usize) {
+        self.dma_threshold = threshold;
+    }
+
+    /// Return the underlying [`Spi`] and the DMA channels
+    #[inline]
+    pub fn free_dma(mut self) -> (Spi<C, A>, R, T) {
+        self.spi.disable_interrupts(Flags::all());
+        (self.spi, self.rx_channel, self.tx_channel)
+    }
+}
+
+impl<C, A, R, T> SpiFuture<C, A, R, T>
+where
+    C: ValidConfig<Word = u8>,
+    C::OpMode: MasterMode,
+    A: Capability,
+{
+    /// Wait until any of the given interrupt `flags` is set
+    ///
+    /// The interrupts are enabled while waiting. [`on_interrupt`] disables them
+    /// and calls the `waker` once they fire.
+    ///
+    /// [`on_interrupt`]: crate::sercom::v2::async_api::on_interrupt
+    async fn wait_flags(&mut self, flags: Flags, waker: &InterruptWaker) -> Flags {
+        poll_fn(|cx| {
+            waker.register(cx.waker());
+            self.spi.enable_interrupts(flags);
+            let set = self.spi.read_flags() & flags;
+            if set.is_empty() {
+                Poll::Pending
+            } else {
+                self.spi.disable_interrupts(flags);
+                Poll::Ready(set)
+            }
+        })
+        .await
+    }
+
+    /// Send one word and return the word received in exchange
+    async fn transfer_word(&mut self, word: u8) -> Result<u8, Error> {
+        self.wait_flags(Flags::DRE, tx_waker::<C::Sercom>()).await;
+        unsafe { self.spi.write_data(word as DataWidth) };
+        self.wait_flags(Flags::RXC, rx_waker::<C::Sercom>()).await;
+        self.check_errors()?;
+        Ok(unsafe { self.spi.read_data() } as u8)
+    }
+
+    /// Check the error status flags, clearing them if any is set
+    fn check_errors(&mut self) -> Result<(), Error> {
+        self.spi.read_flags_errors().map(|_| ()).map_err(|err| {
+            self.spi.clear_status(self.spi.read_status());
+            self.spi.clear_flags(Flags::ERROR);
+            err
+        })
+    }
+
+    /// Exchange `read.len()` or `write.len()` words, whichever is longer,
+    /// using interrupts
+    ///
+    /// Zeros are sent once `write` is exhausted, and the received words are
+    /// discarded once `read` is full.
+    async fn transfer_irq(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Error> {
+        let length = read.len().max(write.len());
+        for i in 0..length {
+            let word = self
+                .transfer_word(write.get(i).copied().unwrap_or(0))
+                .await?;
+            if let Some(slot) = read.get_mut(i) {
+                *slot = word;
+            }
+        }
+        Ok(())
+    }
+
+    /// Exchange the words of `words` in place, using interrupts
+    async fn transfer_in_place_irq(&mut self, words: &mut [u8]) -> Result<(), Error> {
+        for word in words.iter_mut() {
+            *word = self.transfer_word(*word).await?;
+        }
+        Ok(())
+    }
+}
+
+impl<C, A> SpiFuture<C, A>
+where
+    C: ValidConfig<Word = u8>,
+    C::OpMode: MasterMode,
+    A: Capability,
+{
+    /// Read `words`, while sending zeros
+    #[inline]
+    pub async fn read(&mut self, words: &mut [u8]) -> Result<(), Error> {
+        self.transfer_irq(words, &[]).await
+    }
+
+    /// Send `words`, discarding the received words
+    #[inline]
+    pub async fn write(&mut self, words: &[u8]) -> Result<(), Error> {
+        self.transfer_irq(&mut [], words).await
+    }
+
+    /// Send `write` while reading into `read`
+    ///
+    /// If the slices have different lengths, zeros are sent once `write` is
+    /// exhausted, and the received words are discarded once `read` is full.
+    #[inline]
+    pub async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Error> {
+        self.transfer_irq(read, write).await
+    }
+
+    /// Send `words`, replacing them with the received words
+    #[inline]
+    pub async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Error> {
+        self.transfer_in_place_irq(words).await
+    }
+}
+
+#[cfg(feature = "dma")]
+impl<C, A, R, T> SpiFuture<C, A, R, T>
+where
+    C: ValidConfig<Word = u8>,
+    C::OpMode: MasterMode,
+    C::Size: Size<Word = u8>,
+    A: Capability,
+    R: AnyChannel<Status = Ready>,
+    T: AnyChannel<Status = Ready>,
+{
+    /// Exchange `length` words with the DMAC
+    ///
+    /// `read` and `write` must be at least `length` words long, if provided.
+    /// Otherwise, the received words are discarded, or zeros are sent.
+    async fn transfer_dma(
+        &mut self,
+        read: Option<*mut u8>,
+        write: Option<*const u8>,
+        length: usize,
+    ) -> Result<(), Error> {
+        #[cfg(feature = "min-samd51g")]
+        let trigger_action = TriggerAction::BURST;
+
+        #[cfg(any(feature = "samd11", feature = "samd21"))]
+        let trigger_action = TriggerAction::BEAT;
+
+        let mut sink = 0_u8;
+        let mut zero = 0_u8;
+        let data = self.spi.data_ptr();
+        let mut offset = 0;
+
+        while offset < length {
+            let chunk = (length - offset).min(MAX_TRANSFER_LENGTH);
+            let rx_buffer = match read {
+                Some(ptr) => RawBuffer::slice(unsafe { ptr.add(offset) }, chunk),
+                None => RawBuffer::fixed(&mut sink, chunk),
+            };
+            let tx_buffer = match write {
+                Some(ptr) => RawBuffer::slice(unsafe { ptr.add(offset) } as *mut u8, chunk),
+                None => RawBuffer::fixed(&mut zero, chunk),
+            };
+
+            // SAFETY: The buffers, as well as the sink and zero words, outlive
+            // the guards, which stop the transfers when dropped. The DMAC only
+            // reads from the `write` slice, despite the pointer being `*mut`.
+            let mut rx_transfer = unsafe {
+                self.rx_channel.as_mut().start_transfer(
+                    RawBuffer::fixed(data, 1),
+                    rx_buffer,
+                    C::Sercom::DMA_RX_TRIGGER,
+                    trigger_action,
+                )
+            };
+            let mut tx_transfer = unsafe {
+                self.tx_channel.as_mut().start_transfer(
+                    tx_buffer,
+                    RawBuffer::fixed(data, 1),
+                    C::Sercom::DMA_TX_TRIGGER,
+                    trigger_action,
+                )
+            };
+
+            // Every word is sent by the time the last one is received
+            rx_transfer.wait().await.map_err(Error::Dma)?;
+            tx_transfer.wait().await.map_err(Error::Dma)?;
+            drop(tx_transfer);
+            drop(rx_transfer);
+
+            self.check_errors()?;
+            offset += chunk;
+        }
+        Ok(())
+    }
+
+    /// Read `words`, while sending zeros
+    pub async fn read(&mut self, words: &mut [u8]) -> Result<(), Error> {
+        if words.len() < self.dma_threshold {
+            self.transfer_irq(words, &[]).await
+        } else {
+            self.transfer_dma(Some(words.as_mut_ptr()), None, words.len())
+                .await
+        }
+    }
+
+    /// Send `words`, discarding the received words
+    pub async fn write(&mut self, words: &[u8]) -> Result<(), Error> {
+        if words.len() < self.dma_threshold {
+            self.transfer_irq(&mut [], words).await
+        } else {
+            self.transfer_dma(None, Some(words.as_ptr()), words.len())
+                .await
+        }
+    }
+
+    /// Send `write` while reading into `read`
+    ///
+    /// If the slices have different lengths, zeros are sent once `write` is
+    /// exhausted, and the received words are discarded once `read` is full.
+    pub async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Error> {
+        if read.len().max(write.len()) < self.dma_threshold {
+            return self.transfer_irq(read, write).await;
+        }
+
+        let common = read.len().min(write.len());
+        let (read, read_rest) = read.split_at_mut(common);
+        let (write, write_rest) = write.split_at(common);
+        self.transfer_dma(Some(read.as_mut_ptr()), Some(write.as_ptr()), common)
+            .await?;
+        if !read_rest.is_empty() {
+            self.read(read_rest).await
+        } else {
+            self.write(write_rest).await
+        }
+    }
+
+    /// Send `words`, replacing them with the received words
+    pub async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Error> {
+        if words.len() < self.dma_threshold {
+            self.transfer_in_place_irq(words).await
+        } else {
+            // The DMAC reads each word before the word received in exchange
+            // is written back
+            let ptr = words.as_mut_ptr();
+            self.transfer_dma(Some(ptr), Some(ptr), words.len()).await
+        }
+    }
+}
+
+impl<C, A, R, T> ehal_spi::ErrorType for SpiFuture<C, A, R, T>
+where
+    C: ValidConfig,
+    A: Capability,
+{
+    type Error = Error;
+}
+
+impl<C> ehal_spi::SpiBus<u8> for SpiFuture<C, Duplex>
+where
+    C: ValidConfig<Word = u8>,
+    C::OpMode: MasterMode,
+{
+    #[inline]
+    async fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        Self::read(self, words).await
+    }
+
+    #[inline]
+    async fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        Self::write(self, words).await
+    }
+
+    #[inline]
+    async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        Self::transfer(self, read, write).await
+    }
+
+    #[inline]
+    async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        Self::transfer_in_place(self, words).await
+    }
+
+    #[inline]
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        // Every operation waits for the last word to be received, at which
+        // point the transmission is complete
+        Ok(())
+    }
+}
+
+#[cfg(feature = "dma")]
+impl<C, R, T> ehal_spi::SpiBus<u8> for SpiFuture<C, Duplex, R, T>
+where
+    C: ValidConfig<Word = u8>,
+    C::OpMode: MasterMode,
+    C::Size: Size<Word = u8>,
+    R: AnyChannel<Status = Ready>,
+    T: AnyChannel<Status = Ready>,
+{
+    #[inline]
+    async fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        Self::read(self, words).await
+    }
+
+    #[inline]
+    async fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        Self::write(self, words).await
+    }
+
+    #[inline]
+    async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        Self::transfer(self, read, write).await
+    }
+
+    #[inline]
+    async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        Self::transfer_in_place(self, words).await
+    }
+
+    #[inline]
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        // Every operation waits for the last word to be received, at which
+        // point the transmission is complete
+        Ok(())
+    }
+}
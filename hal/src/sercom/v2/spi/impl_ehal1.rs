@@ -0,0 +1,225 @@
+//! Implement [`embedded_hal_1`] traits for [`Spi`] structs
+//!
+//! [`SpiBus`] is implemented for [`Duplex`] [`Spi`] structs in a
+//! [`MasterMode`], for every [`Word`](super::Word) type that implements
+//! [`spi::FullDuplex`](crate::ehal::spi::FullDuplex), i.e. for every
+//! transaction length completed in a single access to the `DATA` register.
+//! Each word is sent and received in turn, so the transfers never overflow.
+//!
+//! [`SpiDevice`] adds chip select management and transaction semantics on
+//! top of a bus. It is implemented by [`Spi`] structs in [`MasterHWSS`] mode,
+//! where the SERCOM drives the `SS` pad itself. With a chip select pin driven
+//! in software, wrap the [`Spi`] in one of the devices of the
+//! `embedded-hal-bus` crate, which accept any [`SpiBus`]: `ExclusiveDevice`
+//! for a single device, or `RefCellDevice` and `CriticalSectionDevice` to
+//! share one SERCOM between several devices.
+//!
+//! ```no_run
+//! use embedded_hal_1::spi::SpiDevice;
+//! use embedded_hal_bus::spi::ExclusiveDevice;
+//!
+//! let cs = pins.pa18.into_push_pull_output();
+//! let mut device = ExclusiveDevice::new(spi, cs, delay)?;
+//! let mut id = [0; 3];
+//! device.transaction(&mut [Operation::Write(&[0x9f]), Operation::Read(&mut id)])?;
+//! ```
+
+use embedded_hal_1::spi::{self as ehal_spi, ErrorKind, Operation, SpiBus, SpiDevice};
+use nb::{block, Error::WouldBlock};
+
+use crate::ehal::spi::FullDuplex;
+
+use super::{AnyConfig, Duplex, Error, MasterHWSS, MasterMode, Spi, ValidConfig};
+
+impl ehal_spi::Error for Error {
+    #[inline]
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Overflow => ErrorKind::Overrun,
+            Error::LengthError => ErrorKind::Other,
+            Error::Unsupported => ErrorKind::Other,
+            #[cfg(all(feature = "async", feature = "dma"))]
+            Error::Dma(_) => ErrorKind::Other,
+        }
+    }
+}
+
+impl<C> ehal_spi::ErrorType for Spi<C, Duplex>
+where
+    C: ValidConfig,
+{
+    type Error = Error;
+}
+
+impl<C> Spi<C, Duplex>
+where
+    C: ValidConfig,
+    C::Word: Copy,
+    Self: FullDuplex<C::Word, Error = Error>,
+{
+    /// Send `word`, and return the word received meanwhile
+    #[inline]
+    fn exchange(&mut self, word: C::Word) -> Result<C::Word, Error> {
+        block!(self.send(word))?;
+        block!(FullDuplex::read(self))
+    }
+}
+
+impl<C> SpiBus<C::Word> for Spi<C, Duplex>
+where
+    C: ValidConfig,
+    C::OpMode: MasterMode,
+    C::Word: Copy + Default + 'static,
+    Self: FullDuplex<C::Word, Error = Error>,
+{
+    #[inline]
+    fn read(&mut self, words: &mut [C::Word]) -> Result<(), Error> {
+        for word in words {
+            *word = self.exchange(C::Word::default())?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn write(&mut self, words: &[C::Word]) -> Result<(), Error> {
+        for &word in words {
+            self.exchange(word)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn transfer(&mut self, read: &mut [C::Word], write: &[C::Word]) -> Result<(), Error> {
+        for i in 0..read.len().max(write.len()) {
+            let word = write.get(i).copied().unwrap_or_default();
+            let word = self.exchange(word)?;
+            if let Some(r) = read.get_mut(i) {
+                *r = word;
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn transfer_in_place(&mut self, words: &mut [C::Word]) -> Result<(), Error> {
+        for word in words {
+            *word = self.exchange(*word)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> Result<(), Error> {
+        // Every operation waits for the last word to be received, at which
+        // point the transmission is complete
+        Ok(())
+    }
+}
+
+//=============================================================================
+// Hardware SS
+//=============================================================================
+
+/// Number of words needed by `op`
+fn op_len<W>(op: &Operation<'_, W>) -> usize {
+    match op {
+        Operation::Read(words) => words.len(),
+        Operation::Write(words) => words.len(),
+        Operation::Transfer(read, write) => read.len().max(write.len()),
+        Operation::TransferInPlace(words) => words.len(),
+        Operation::DelayNs(_) => 0,
+    }
+}
+
+/// Implement [`SpiDevice`] for [`Spi`] structs in [`MasterHWSS`] mode
+///
+/// The hardware asserts `SS` when the first word is written, and releases it
+/// once the transmitter runs out of data. Every operation of the transaction
+/// is therefore sent as one continuous stream of words, keeping the `DATA`
+/// register filled so `SS` stays asserted until the last word.
+///
+/// `SS` is also released whenever the stream stalls, for instance while an
+/// interrupt handler runs between two words. For devices that require `SS`
+/// to stay asserted for the whole transaction, run it within a critical
+/// section, or drive the chip select pin in software with the
+/// `ExclusiveDevice` of the `embedded-hal-bus` crate.
+///
+/// The hardware cannot hold `SS` during a delay either, so transactions
+/// containing an [`Operation::DelayNs`] return [`Error::Unsupported`],
+/// without sending anything.
+impl<C> SpiDevice<C::Word> for Spi<C, Duplex>
+where
+    C: ValidConfig + AnyConfig<OpMode = MasterHWSS>,
+    C::Word: Copy + Default + 'static,
+    Self: FullDuplex<C::Word, Error = Error>,
+{
+    fn transaction(&mut self, operations: &mut [Operation<'_, C::Word>]) -> Result<(), Error> {
+        if operations
+            .iter()
+            .any(|op| matches!(op, Operation::DelayNs(_)))
+        {
+            return Err(Error::Unsupported);
+        }
+
+        // Position of the next word to send and of the next word to receive,
+        // as an operation index and a word index within that operation
+        let (mut tx_op, mut tx_word) = (0, 0);
+        let (mut rx_op, mut rx_word) = (0, 0);
+        // Keep at most two words in flight, one in the shift register and one
+        // in DATA, so the receiver never overflows
+        let mut in_flight = 0;
+        loop {
+            while tx_op < operations.len() && tx_word >= op_len(&operations[tx_op]) {
+                tx_op += 1;
+                tx_word = 0;
+            }
+            while rx_op < operations.len() && rx_word >= op_len(&operations[rx_op]) {
+                rx_op += 1;
+                rx_word = 0;
+            }
+            if rx_op == operations.len() {
+                return Ok(());
+            }
+
+            if tx_op < operations.len() && in_flight < 2 {
+                let word = match &operations[tx_op] {
+                    Operation::Write(words) => words[tx_word],
+                    Operation::TransferInPlace(words) => words[tx_word],
+                    Operation::Transfer(_, write) => {
+                        write.get(tx_word).copied().unwrap_or_default()
+                    }
+                    _ => C::Word::default(),
+                };
+                match self.send(word) {
+                    Ok(()) => {
+                        tx_word += 1;
+                        in_flight += 1;
+                        continue;
+                    }
+                    Err(WouldBlock) => (),
+                    Err(nb::Error::Other(err)) => return Err(err),
+                }
+            }
+
+            match FullDuplex::read(self) {
+                Ok(word) => {
+                    match &mut operations[rx_op] {
+                        Operation::Read(words) | Operation::TransferInPlace(words) => {
+                            words[rx_word] = word
+                        }
+                        Operation::Transfer(read, _) => {
+                            if let Some(dest) = read.get_mut(rx_word) {
+                                *dest = word;
+                            }
+                        }
+                        _ => (),
+                    }
+                    rx_word += 1;
+                    in_flight -= 1;
+                }
+                Err(WouldBlock) => (),
+                Err(nb::Error::Other(err)) => return Err(err),
+            }
+        }
+    }
+}
@@ -254,6 +254,32 @@ impl<S: Sercom> Registers<S> {
         self.spi().ctrla.modify(|_, w| w.runstdby().bit(set));
     }
 
+    /// Get the enable state of slave data preloading
+    #[inline]
+    pub fn get_preload(&self) -> bool {
+        self.spi().ctrlb.read().ploaden().bit()
+    }
+
+    /// Set the enable state of slave data preloading
+    #[inline]
+    pub fn set_preload(&mut self, enabled: bool) {
+        self.spi().ctrlb.modify(|_, w| w.ploaden().bit(enabled));
+        while self.spi().syncbusy.read().ctrlb().bit_is_set() {}
+    }
+
+    /// Get the enable state of slave select low detection
+    #[inline]
+    pub fn get_ss_detect(&self) -> bool {
+        self.spi().ctrlb.read().ssde().bit()
+    }
+
+    /// Set the enable state of slave select low detection
+    #[inline]
+    pub fn set_ss_detect(&mut self, enabled: bool) {
+        self.spi().ctrlb.modify(|_, w| w.ssde().bit(enabled));
+        while self.spi().syncbusy.read().ctrlb().bit_is_set() {}
+    }
+
     /// Enable interrupts for the specified flags
     #[inline]
     pub fn enable_interrupts(&mut self, flags: Flags) {
@@ -233,6 +233,97 @@ type Pads = uart::PadsFromIds<Sercom0, PA09, PA09>;
 //! conditions. [This application note](https://www.silabs.com/documents/public/application-notes/an0059.0-uart-flow-control.pdf)
 //! provides more information about UART hardware flow control.
 //!
+//! # RS-485 (SAMx5x)
+//!
+//! On SAMx5x chips, the `RTS` pad can instead drive the transmit enable input
+//! of an RS-485 transceiver. [`Config::rs485`] enables RS-485 mode, in which
+//! `TE` is asserted while the UART transmits, and for a configurable guard
+//! time afterwards, so half-duplex buses need no manual GPIO control. RS-485
+//! mode requires a `RTS` pad, and is not available on SAMD11 and SAMD21
+//! chips.
+//!
+//! ```
+//! let config = uart::Config::new(&mclk, sercom, pads, freq)
+//!     .baud(115_200.hz(), BaudMode::Fractional(Oversampling::Bits16))
+//!     .rs485(Some(1));
+//! ```
+//!
+//! # IrDA
+//!
+//! IrDA encoding modulates the transmitted bits into short pulses, and
+//! demodulates the received pulses, so the SERCOM can drive an infrared
+//! transceiver directly. Enable it with
+//! [`irda_encoding`](Config::irda_encoding), or with
+//! [`irda_min_pulse`](Config::irda_min_pulse), which computes the receive
+//! pulse filter from a duration. IrDA requires 16x oversampling.
+//!
+//! ```
+//! use atsamd_hal::time::Nanoseconds;
+//!
+//! let uart = config
+//!     .baud(115_200.hz(), BaudMode::Arithmetic(Oversampling::Bits16))
+//!     .irda_min_pulse(Nanoseconds(1_000))
+//!     .enable();
+//! ```
+//!
+//! # LIN
+//!
+//! LIN slaves enable break detection and auto-baud with
+//! [`Config::auto_baud`]. A break field raises the `RXBRK` interrupt flag,
+//! then the SERCOM adjusts its baud rate to the sync field, and receives the
+//! protected identifier as a regular byte.
+//!
+//! On SAMx5x chips, LIN masters are configured with [`Config::lin_master`],
+//! then transmit each header with [`Uart::send_lin_header`]. The [`lin`]
+//! module computes the protected identifiers and the response checksums.
+//!
+//! ```
+//! use atsamd_hal::sercom::v2::uart::{lin, BreakLength, HeaderDelay};
+//!
+//! let mut uart = config
+//!     .baud(19_200.hz(), BaudMode::Fractional(Oversampling::Bits16))
+//!     .lin_master(Some((BreakLength::Bits13, HeaderDelay::Bits1)))
+//!     .enable();
+//! uart.send_lin_header(lin::protected_id(0x10));
+//! ```
+//!
+//! # Interrupt-driven buffering
+//!
+//! With the `buffered-uart` Cargo feature, the [`buffered`] module provides a
+//! [`BufferedUart`](buffered::BufferedUart), which queues the received and
+//! transmitted bytes in ring buffers serviced by the SERCOM interrupt. It has
+//! non-blocking `read`, `write` and `flush` methods, and implements the
+//! `embedded_io` traits.
+//!
+//! # Multidrop buses
+//!
+//! With a [`NineBit`] character size, the ninth bit can mark the address
+//! characters of a multidrop bus. The [`multidrop`] module encodes these
+//! characters, and filters the data characters addressed to this node.
+//!
+//! # ISO 7816 (SAMx5x)
+//!
+//! On SAMx5x chips, [`Config::iso7816`] configures the SERCOM as a smart card
+//! interface, using the T=0 or T=1 protocol, with the direct or inverse
+//! convention. The card exchanges characters on a single I/O line, so the
+//! [`Pads`] only contain a `TX` pad, and [`Config::enable_iso7816`] enables
+//! both directions. The [`iso7816`] module describes how to provide the clock
+//! of the card. A character refused by the card `max_iterations` times sets
+//! the `ITER` status flag.
+//!
+//! ```
+//! use atsamd_hal::sercom::v2::uart::iso7816::{self, Convention, Iso7816};
+//!
+//! let settings = Iso7816 {
+//!     convention: Convention::Inverse,
+//!     ..Default::default()
+//! };
+//! let mut card = uart::Config::new(&mclk, sercom, pads, freq)
+//!     .iso7816(Some(settings))
+//!     .card_clock(4.mhz().into(), iso7816::DEFAULT_CLOCK_RATE, 1)
+//!     .enable_iso7816();
+//! ```
+//!
 //! # Splitting
 //!
 //! A `Uart<C, Duplex>` can be split into its [`RxDuplex`] and [`TxDuplex`]
@@ -324,7 +415,6 @@ type Pads = uart::PadsFromIds<Sercom0, PA09, PA09>;
 //! # Non-supported advanced features
 //!
 //! * Synchronous mode (USART) is not supported
-//! * LIN mode is not supported (SAMx5x)
 //! * 32-bit extension mode is not supported (SAMx5x). If you need to transfer
 //!   slices, consider using the DMA methods instead. The `dma` Cargo feature
 //!   must be enabled.
@@ -375,13 +465,109 @@ let (chan0, tx_buffer, tx) = tx_dma.wait();
 let (chan1, rx, rx_buffer) = rx_dma.wait();
 ```
 
+## Continuous reception
+
+To receive a continuous stream without losing any byte, such as the NMEA
+sentences of a GPS receiver, [`receive_circular_with_dma`] starts a circular
+transfer into a `'static` ring buffer. The returned [`CircularReceive`] tracks
+the position of the DMAC in the ring buffer, and
+[`read_available`](CircularReceive::read_available) copies out the bytes
+received since the last call.
+
+```
+static mut RING: [u8; 256] = [0; 256];
+
+// Assume channel0 is a configured `dmac::Channel`, and rx a Uart<C, Rx>
+let mut receiver = rx.receive_circular_with_dma(unsafe { &mut RING }, channel0);
+
+let mut sentence = [0_u8; 82];
+loop {
+    let count = receiver.read_available(&mut sentence);
+    // Process sentence[..count]
+}
+```
+
 [`Buffer`]: crate::dmac::transfer::Buffer
+[`receive_circular_with_dma`]: Uart::receive_circular_with_dma
+[`CircularReceive`]: crate::sercom::v2::dma::CircularReceive
 [`send_with_dma`]: Uart::send_with_dma
 [`receive_with_dma`]: Uart::receive_with_dma
 [`dmac::Transfer`]: crate::dmac::Transfer
 [`Channel`]: crate::dmac::channel::Channel
 [`dmac`]: crate::dmac
 
+"
+)]
+#![cfg_attr(
+    feature = "async",
+    doc = "
+# `async` operation
+
+A [`Uart`] can be converted into a [`UartFuture`] with
+[`into_future`](Uart::into_future). [`UartFuture`] implements the
+[`embedded_io_async`] `Read` and `Write` traits. Instead of busy-waiting on
+the `RXC`, `DRE` and `TXC` flags, it enables the corresponding interrupts and
+yields until [`async_api::on_interrupt`] wakes it up. Only character sizes of
+8 bits or fewer are supported. Note that the `async` feature must be enabled.
+
+```
+use atsamd_hal::pac::interrupt;
+use atsamd_hal::sercom::v2::{async_api, Sercom0};
+use embedded_io_async::{Read, Write};
+
+#[interrupt]
+fn SERCOM0() {
+    async_api::on_interrupt::<Sercom0>();
+}
+
+// Assume config is a valid Duplex UART Config struct
+let uart = config.enable().into_future();
+
+// The halves can be awaited from different tasks
+let (mut rx, mut tx) = uart.split();
+let mut buf = [0_u8; 16];
+let count = rx.read(&mut buf).await.unwrap();
+tx.write_all(&buf[..count]).await.unwrap();
+```
+
+[`async_api::on_interrupt`]: crate::sercom::v2::async_api::on_interrupt
+
+"
+)]
+#![cfg_attr(
+    all(feature = "async", feature = "dma"),
+    doc = "
+## `async` DMA transfers
+
+For high data rates, handling one interrupt per byte can keep the CPU busy.
+DMA channels can be handed to a [`UartFuture`] with
+[`with_rx_dma_channel`](UartFuture::with_rx_dma_channel) and
+[`with_tx_dma_channel`](UartFuture::with_tx_dma_channel). The DMAC then sends
+the whole buffer passed to [`write`](UartFuture::write), and fills the whole
+buffer passed to [`read_exact`](UartFuture::read_exact). [`read`] keeps
+returning the bytes as soon as they are available, one interrupt at a time.
+The completion of DMA transfers is signaled by the DMAC interrupt, whose
+handler must call [`dmac::async_api::on_interrupt`].
+
+```
+use atsamd_hal::dmac::async_api;
+use atsamd_hal::pac::interrupt;
+use embedded_io_async::Write;
+
+#[interrupt]
+fn DMAC() {
+    async_api::on_interrupt();
+}
+
+// Assume channel0 is a `Ready` DMA channel
+let mut tx = config.enable().into_future().with_tx_dma_channel(channel0);
+tx.write_all(b\"Logging at 1 Mbaud without per-byte interrupts\\r\\n\")
+    .await
+    .unwrap();
+```
+
+[`read`]: UartFuture::read
+[`dmac::async_api::on_interrupt`]: crate::dmac::async_api::on_interrupt
 "
 )]
 
@@ -407,8 +593,26 @@ pub use flags::*;
 mod config;
 pub use config::*;
 
+#[cfg(feature = "min-samd51g")]
+pub mod iso7816;
+
+pub mod lin;
+#[cfg(feature = "min-samd51g")]
+pub use lin::{BreakLength, HeaderDelay};
+
+pub mod multidrop;
+
+#[cfg(feature = "buffered-uart")]
+pub mod buffered;
+
 pub mod impl_ehal;
 
+#[cfg(feature = "async")]
+#[clippy::msrv = "1.75"]
+mod impl_async;
+#[cfg(feature = "async")]
+pub use impl_async::*;
+
 use crate::{sercom::v2::*, typelevel::Sealed};
 use core::{convert::TryInto, marker::PhantomData};
 use num_traits::AsPrimitive;
@@ -965,3 +1169,22 @@ where
         self.config.as_mut().registers.write_data(data);
     }
 }
+
+#[cfg(feature = "min-samd51g")]
+impl<C, D> Uart<C, D>
+where
+    C: ValidConfig,
+    D: Transmit,
+{
+    /// Transmit a LIN header: a break field, the sync field and the
+    /// protected identifier `pid`, as returned by [`lin::protected_id`]
+    ///
+    /// The UART must be configured with [`Config::lin_master`]. This function
+    /// waits for the `DRE` flag before starting the header. The response
+    /// bytes are then written as usual.
+    #[inline]
+    pub fn send_lin_header(&mut self, pid: u8) {
+        while !self.read_flags().contains(Flags::DRE) {}
+        self.config.as_mut().registers.send_lin_header(pid);
+    }
+}
@@ -0,0 +1,326 @@
+//! Interrupt-driven UART with ring buffers
+//!
+//! A [`BufferedUart`] stores the received bytes, and the bytes waiting to be
+//! transmitted, in ring buffers serviced by the SERCOM interrupt, so that no
+//! byte is lost while the application is busy elsewhere. The buffers live in
+//! a [`Buffers`] struct, usually a `static`, whose sizes are chosen by its
+//! const generic parameters. A ring buffer of `N` bytes holds at most `N - 1`
+//! bytes.
+//!
+//! [`BufferedUart::new`] splits a [`Duplex`] [`Uart`] into two halves: the
+//! [`BufferedUart`] used by the application, and the [`UartHandler`] to be
+//! moved into the interrupt handler, which calls
+//! [`on_interrupt`](UartHandler::on_interrupt). The halves only share the
+//! ring buffers, which are lock-free, so neither needs a critical section.
+//!
+//! ```no_run
+//! use atsamd_hal::sercom::v2::uart::buffered::{BufferedUart, Buffers};
+//!
+//! let buffers = cortex_m::singleton!(: Buffers<64, 64> = Buffers::new()).unwrap();
+//! let (mut uart, handler) = BufferedUart::new(uart, buffers);
+//! // Move `handler` into the SERCOM interrupt handler, and call
+//! // `handler.on_interrupt()` from it
+//!
+//! uart.write(b"hello");
+//! let mut buf = [0; 16];
+//! let len = uart.read(&mut buf);
+//! ```
+
+use core::convert::Infallible;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use heapless::spsc::{Consumer, Producer, Queue};
+
+use super::{Duplex, Flags, Status, Uart, ValidConfig};
+use crate::pac::sercom0::RegisterBlock;
+use crate::sercom::v2::Sercom;
+
+/// Ring buffers of a [`BufferedUart`], receiving up to `RX - 1` bytes and
+/// transmitting up to `TX - 1` bytes
+pub struct Buffers<const RX: usize, const TX: usize> {
+    rx: Queue<u8, RX>,
+    tx: Queue<u8, TX>,
+    overrun: AtomicBool,
+}
+
+impl<const RX: usize, const TX: usize> Buffers<RX, TX> {
+    /// Create empty buffers
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            rx: Queue::new(),
+            tx: Queue::new(),
+            overrun: AtomicBool::new(false),
+        }
+    }
+}
+
+impl<const RX: usize, const TX: usize> Default for Buffers<RX, TX> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Application half of an interrupt-driven UART
+///
+/// See the [module-level documentation](self) for more details.
+pub struct BufferedUart<'a, S: Sercom, const RX: usize, const TX: usize> {
+    rx: Consumer<'a, u8, RX>,
+    tx: Producer<'a, u8, TX>,
+    overrun: &'a AtomicBool,
+    sent: bool,
+    sercom: PhantomData<S>,
+}
+
+/// Interrupt half of an interrupt-driven UART
+///
+/// See the [module-level documentation](self) for more details.
+pub struct UartHandler<'a, C: ValidConfig, const RX: usize, const TX: usize> {
+    uart: Uart<C, Duplex>,
+    rx: Producer<'a, u8, RX>,
+    tx: Consumer<'a, u8, TX>,
+    overrun: &'a AtomicBool,
+}
+
+/// Access the `USART` registers of the SERCOM
+#[cfg(any(feature = "samd11", feature = "samd21"))]
+fn usart(regs: &RegisterBlock) -> &crate::pac::sercom0::USART {
+    regs.usart()
+}
+
+/// Access the `USART_INT` registers of the SERCOM
+#[cfg(feature = "min-samd51g")]
+fn usart(regs: &RegisterBlock) -> &crate::pac::sercom0::USART_INT {
+    regs.usart_int()
+}
+
+impl<'a, S: Sercom, const RX: usize, const TX: usize> BufferedUart<'a, S, RX, TX> {
+    /// Split `uart` into its application and interrupt halves, using
+    /// `buffers`
+    ///
+    /// The `RXC` interrupt is enabled, and the `DRE` interrupt is enabled
+    /// whenever bytes are waiting to be transmitted. The SERCOM interrupt
+    /// must be unmasked in the NVIC.
+    #[inline]
+    pub fn new<C>(
+        mut uart: Uart<C, Duplex>,
+        buffers: &'a mut Buffers<RX, TX>,
+    ) -> (Self, UartHandler<'a, C, RX, TX>)
+    where
+        C: ValidConfig<Sercom = S, Word = u8>,
+    {
+        let (rx_producer, rx_consumer) = buffers.rx.split();
+        let (tx_producer, tx_consumer) = buffers.tx.split();
+        let overrun = &buffers.overrun;
+        uart.disable_interrupts(Flags::all());
+        uart.enable_interrupts(Flags::RXC);
+        let buffered = Self {
+            rx: rx_consumer,
+            tx: tx_producer,
+            overrun,
+            sent: false,
+            sercom: PhantomData,
+        };
+        let handler = UartHandler {
+            uart,
+            rx: rx_producer,
+            tx: tx_consumer,
+            overrun,
+        };
+        (buffered, handler)
+    }
+
+    /// Recombine the two halves, and return the [`Uart`]
+    ///
+    /// The bytes still buffered are dropped, and the UART interrupts
+    /// disabled.
+    #[inline]
+    pub fn join<C>(self, handler: UartHandler<'a, C, RX, TX>) -> Uart<C, Duplex>
+    where
+        C: ValidConfig<Sercom = S, Word = u8>,
+    {
+        let mut uart = handler.uart;
+        uart.disable_interrupts(Flags::all());
+        uart
+    }
+
+    /// Move received bytes into `buf`, returning their number
+    ///
+    /// Returns 0 if no byte was received.
+    #[inline]
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let mut len = 0;
+        for byte in buf.iter_mut() {
+            match self.rx.dequeue() {
+                Some(b) => *byte = b,
+                None => break,
+            }
+            len += 1;
+        }
+        len
+    }
+
+    /// Queue the bytes of `buf` for transmission, returning the number of
+    /// bytes queued
+    ///
+    /// Returns 0 if the transmit buffer is full.
+    #[inline]
+    pub fn write(&mut self, buf: &[u8]) -> usize {
+        let mut len = 0;
+        for &byte in buf {
+            if self.tx.enqueue(byte).is_err() {
+                break;
+            }
+            len += 1;
+        }
+        if len > 0 {
+            self.sent = true;
+            // SAFETY: INTENSET is a set-only register, so writing it does not
+            // race with the interrupt handler
+            let usart = usart(unsafe { &*S::PTR });
+            usart
+                .intenset
+                .write(|w| unsafe { w.bits(Flags::DRE.bits()) });
+        }
+        len
+    }
+
+    /// Wait until every byte queued has been transmitted
+    #[inline]
+    pub fn flush(&mut self) -> nb::Result<(), Infallible> {
+        // SAFETY: Reading INTFLAG has no side effect
+        let usart = usart(unsafe { &*S::PTR });
+        let sent = Flags::from_bits_truncate(usart.intflag.read().bits()).contains(Flags::TXC);
+        if self.tx.len() == 0 && (sent || !self.sent) {
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Return the number of bytes received, waiting to be read
+    #[inline]
+    pub fn available(&self) -> usize {
+        self.rx.len()
+    }
+
+    /// Return whether bytes were dropped because the receive buffer was
+    /// full, or because of a reception error, and clear the indication
+    #[inline]
+    pub fn take_overrun(&mut self) -> bool {
+        // Thumbv6m targets have no atomic swap. An overrun occurring between
+        // both accesses is merged into the one reported.
+        let overrun = self.overrun.load(Ordering::Relaxed);
+        if overrun {
+            self.overrun.store(false, Ordering::Relaxed);
+        }
+        overrun
+    }
+}
+
+impl<'a, C, const RX: usize, const TX: usize> UartHandler<'a, C, RX, TX>
+where
+    C: ValidConfig<Word = u8>,
+{
+    /// Service the ring buffers
+    ///
+    /// Call this function from the SERCOM interrupt handler.
+    #[inline]
+    pub fn on_interrupt(&mut self) {
+        let flags = self.uart.read_flags();
+
+        if flags.contains(Flags::RXC) {
+            let errors = self.uart.read_status();
+            // SAFETY: RXC is set
+            let byte = unsafe { self.uart.read_data() } as u8;
+            if !errors.is_empty() {
+                self.uart.clear_status(Status::all());
+                self.overrun.store(true, Ordering::Relaxed);
+            } else if self.rx.enqueue(byte).is_err() {
+                self.overrun.store(true, Ordering::Relaxed);
+            }
+        }
+
+        if flags.contains(Flags::DRE) {
+            match self.tx.dequeue() {
+                // SAFETY: DRE is set
+                Some(byte) => unsafe { self.uart.write_data(byte as _) },
+                None => {
+                    self.uart.disable_interrupts(Flags::DRE);
+                    // The application may have queued a byte after the
+                    // buffer was found empty
+                    if self.tx.ready() {
+                        self.uart.enable_interrupts(Flags::DRE);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a, S: Sercom, const RX: usize, const TX: usize> embedded_io::ErrorType
+    for BufferedUart<'a, S, RX, TX>
+{
+    type Error = Infallible;
+}
+
+impl<'a, S: Sercom, const RX: usize, const TX: usize> embedded_io::Read
+    for BufferedUart<'a, S, RX, TX>
+{
+    /// Wait for at least one byte, then move the received bytes into `buf`
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let len = BufferedUart::read(self, buf);
+            if len > 0 {
+                return Ok(len);
+            }
+        }
+    }
+}
+
+impl<'a, S: Sercom, const RX: usize, const TX: usize> embedded_io::ReadReady
+    for BufferedUart<'a, S, RX, TX>
+{
+    #[inline]
+    fn read_ready(&mut self) -> Result<bool, Infallible> {
+        Ok(self.rx.ready())
+    }
+}
+
+impl<'a, S: Sercom, const RX: usize, const TX: usize> embedded_io::Write
+    for BufferedUart<'a, S, RX, TX>
+{
+    /// Wait for room in the transmit buffer, then queue bytes of `buf`
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let len = BufferedUart::write(self, buf);
+            if len > 0 {
+                return Ok(len);
+            }
+        }
+    }
+
+    #[inline]
+    fn flush(&mut self) -> Result<(), Infallible> {
+        nb::block!(BufferedUart::flush(self))
+    }
+}
+
+impl<'a, S: Sercom, const RX: usize, const TX: usize> embedded_io::WriteReady
+    for BufferedUart<'a, S, RX, TX>
+{
+    #[inline]
+    fn write_ready(&mut self) -> Result<bool, Infallible> {
+        Ok(self.tx.ready())
+    }
+}
@@ -4,10 +4,12 @@ use super::{
     BaudMode, BitOrder, Capability, CharSize, CharSizeEnum, DataReg, DynCharSize, EightBit,
     FixedCharSize, Parity, Registers, StopBits, Uart, ValidConfig, ValidPads,
 };
+#[cfg(feature = "min-samd51g")]
+use super::{BreakLength, HeaderDelay};
 use crate::{
-    pac,
+    bus_clock, pac,
     sercom::v2::*,
-    time::Hertz,
+    time::{Hertz, Nanoseconds},
     typelevel::{Is, Sealed},
 };
 use core::marker::PhantomData;
@@ -126,11 +128,12 @@ where
         Config::default(self.registers.free(), self.pads, self.freq)
     }
 
-    /// Consume the [`Config`], reset the peripheral, and return the [`Sercom`]
-    /// and [`Pads`](super::Pads)
+    /// Consume the [`Config`], reset the peripheral, disable its APB clock,
+    /// and return the [`Sercom`] and [`Pads`](super::Pads)
     #[inline]
     pub fn free(mut self) -> (P::Sercom, P) {
         self.registers.swrst();
+        bus_clock::release::<P::Sercom>();
         (self.registers.free(), self.pads)
     }
 
@@ -374,6 +377,122 @@ where
     pub fn get_irda_encoding(&self) -> Option<u8> {
         self.registers.get_irda_encoding()
     }
+
+    /// Enable IrDA encoding, ignoring received pulses shorter than
+    /// `min_pulse` (builder pattern version)
+    ///
+    /// The pulse length of [`irda_encoding`](Self::irda_encoding) is computed
+    /// from the GCLK frequency of the [`Config`]. IrDA transmitters send
+    /// pulses of 3/16 of the bit period, and at least 1.41 µs.
+    #[inline]
+    pub fn irda_min_pulse(mut self, min_pulse: Nanoseconds) -> Self {
+        self.set_irda_min_pulse(min_pulse);
+        self
+    }
+
+    /// Enable IrDA encoding, ignoring received pulses shorter than
+    /// `min_pulse` (setter version)
+    ///
+    /// See [`irda_min_pulse`](Self::irda_min_pulse) for more details.
+    #[inline]
+    pub fn set_irda_min_pulse(&mut self, min_pulse: Nanoseconds) {
+        // Pulses of at least RXPL + 2 GCLK cycles are accepted
+        let cycles = (min_pulse.0 as u64 * self.freq.0 as u64 + 999_999_999) / 1_000_000_000;
+        let pulse_length = cycles.saturating_sub(2).min(u8::MAX as u64) as u8;
+        self.set_irda_encoding(Some(pulse_length));
+    }
+
+    /// Enable or disable break detection and auto-baud (builder pattern
+    /// version)
+    ///
+    /// When set, the receiver detects break fields, raising the `RXBRK`
+    /// flag, then measures the following sync field to update the baud rate,
+    /// as required by LIN slaves. An inconsistent sync field sets the `ISF`
+    /// status flag. Parity can be combined with auto-baud.
+    #[inline]
+    pub fn auto_baud(mut self, enabled: bool) -> Self {
+        self.set_auto_baud(enabled);
+        self
+    }
+
+    /// Enable or disable break detection and auto-baud (setter version)
+    ///
+    /// See [`auto_baud`](Self::auto_baud) for more details.
+    #[inline]
+    pub fn set_auto_baud(&mut self, enabled: bool) {
+        self.registers.set_auto_baud(enabled);
+    }
+
+    /// Get the current auto-baud setting
+    #[inline]
+    pub fn get_auto_baud(&self) -> bool {
+        self.registers.get_auto_baud()
+    }
+
+    /// Enable or disable LIN master mode (builder pattern version)
+    ///
+    /// In LIN master mode, [`Uart::send_lin_header`] transmits the break and
+    /// sync fields automatically, with the given break length and header
+    /// delay, before the identifier. LIN master mode replaces the parity and
+    /// auto-baud settings; `None` returns to a frame without parity.
+    #[cfg(feature = "min-samd51g")]
+    #[inline]
+    pub fn lin_master(mut self, header: Option<(BreakLength, HeaderDelay)>) -> Self {
+        self.set_lin_master(header);
+        self
+    }
+
+    /// Enable or disable LIN master mode (setter version)
+    ///
+    /// See [`lin_master`](Self::lin_master) for more details.
+    #[cfg(feature = "min-samd51g")]
+    #[inline]
+    pub fn set_lin_master(&mut self, header: Option<(BreakLength, HeaderDelay)>) {
+        self.registers.set_lin_master(header);
+    }
+
+    /// Get the current LIN master setting
+    #[cfg(feature = "min-samd51g")]
+    #[inline]
+    pub fn get_lin_master(&self) -> Option<(BreakLength, HeaderDelay)> {
+        self.registers.get_lin_master()
+    }
+}
+
+#[cfg(feature = "min-samd51g")]
+impl<P, C> Config<P, C>
+where
+    P: ValidPads,
+    P::Rts: SomePad,
+    C: CharSize,
+{
+    /// Enable or disable RS-485 mode (builder pattern version)
+    ///
+    /// In RS-485 mode, the `RTS` pad becomes the transmit enable (`TE`)
+    /// output, which is driven high while the UART transmits, to enable the
+    /// driver of an RS-485 transceiver. `TE` stays high for `guard_time` bit
+    /// periods, from 0 to 7, after the last stop bit. `None` returns the
+    /// `RTS` pad to hardware flow control.
+    #[inline]
+    pub fn rs485(mut self, guard_time: Option<u8>) -> Self {
+        self.set_rs485(guard_time);
+        self
+    }
+
+    /// Enable or disable RS-485 mode (setter version)
+    ///
+    /// See [`rs485`](Self::rs485) for more details.
+    #[inline]
+    pub fn set_rs485(&mut self, guard_time: Option<u8>) {
+        self.registers.set_rs485(guard_time);
+    }
+
+    /// Get the current RS-485 setting. The return type is the guard time
+    /// wrapped in an [`Option`].
+    #[inline]
+    pub fn get_rs485(&self) -> Option<u8> {
+        self.registers.get_rs485()
+    }
 }
 
 impl<P: ValidPads> Config<P, DynCharSize> {
@@ -48,17 +48,23 @@ const BUFOVF: u16 = 0x04;
 const CTS: u16 = 0x08;
 const ISF: u16 = 0x10;
 const COLL: u16 = 0x20;
+#[cfg(feature = "min-samd51g")]
+const ITER: u16 = 0x80;
 
 /// Status flags available for RX transactions
 pub const RX_STATUS_MASK: u16 = PERR | FERR | BUFOVF | ISF | COLL;
 /// Status flags available for Duplex transactions
+#[cfg(any(feature = "samd11", feature = "samd21"))]
 pub const DUPLEX_STATUS_MASK: u16 = RX_STATUS_MASK;
+/// Status flags available for Duplex transactions
+#[cfg(feature = "min-samd51g")]
+pub const DUPLEX_STATUS_MASK: u16 = RX_STATUS_MASK | ITER;
 
 bitflags! {
     /// Status flags for UART Rx transactions
     ///
     /// The available status flags are `PERR`, `FERR`, `BUFOVF`,
-    /// `CTS`, `ISF` and `COLL`.
+    /// `CTS`, `ISF`, `COLL` and, on SAMx5x chips, `ITER`.
     /// The binary format of the underlying bits exactly matches
     /// the STATUS bits.
     pub struct Status: u16 {
@@ -68,6 +74,8 @@ bitflags! {
         const CTS = CTS;
         const ISF = ISF;
         const COLL = COLL;
+        #[cfg(feature = "min-samd51g")]
+        const ITER = ITER;
     }
 }
 
@@ -76,6 +84,7 @@ bitflags! {
 //=============================================================================
 
 /// Errors available for UART transactions
+#[non_exhaustive]
 #[derive(Debug, Clone, Copy)]
 pub enum Error {
     /// Detected a parity error
@@ -88,6 +97,12 @@ pub enum Error {
     InconsistentSyncField,
     /// Detected a collision
     CollisionDetected,
+    /// An ISO 7816 character was refused the maximum number of times
+    #[cfg(feature = "min-samd51g")]
+    MaxIterations,
+    /// Error reported by the DMAC during an `async` DMA transfer
+    #[cfg(all(feature = "async", feature = "dma"))]
+    Dma(crate::dmac::Error),
 }
 
 impl TryFrom<Status> for () {
@@ -96,6 +111,10 @@ impl TryFrom<Status> for () {
     #[inline]
     fn try_from(errors: Status) -> Result<(), Error> {
         use Error::*;
+        #[cfg(feature = "min-samd51g")]
+        if errors.contains(Status::ITER) {
+            return Err(MaxIterations);
+        }
         if errors.contains(Status::PERR) {
             Err(ParityError)
         } else if errors.contains(Status::FERR) {
@@ -122,6 +141,10 @@ impl From<Error> for Status {
             Overflow => Status::BUFOVF,
             InconsistentSyncField => Status::ISF,
             CollisionDetected => Status::COLL,
+            #[cfg(feature = "min-samd51g")]
+            MaxIterations => Status::ITER,
+            #[cfg(all(feature = "async", feature = "dma"))]
+            Dma(_) => Status::empty(),
         }
     }
 }
@@ -0,0 +1,463 @@
+//! `async` UART driver
+//!
+//! See the [`uart`](crate::sercom::v2::uart) module-level documentation.
+
+use core::future::poll_fn;
+use core::task::Poll;
+
+use crate::async_hal::InterruptWaker;
+use crate::sercom::v2::async_api::{rx_waker, tx_waker};
+use crate::typelevel::NoneT;
+
+#[cfg(feature = "dma")]
+use crate::dmac::{
+    transfer::{RawBuffer, MAX_TRANSFER_LENGTH},
+    channel::{AnyChannel, Ready},
+    TriggerAction,
+};
+#[cfg(feature = "dma")]
+use crate::sercom::v2::Sercom;
+
+use super::{
+    Capability, ConfigSercom, DataReg, Duplex, Error, Flags, Receive, RxDuplex, Status, Transmit,
+    TxDuplex, Uart, ValidConfig,
+};
+
+impl<C, D> Uart<C, D>
+where
+    C: ValidConfig,
+    D: Capability,
+{
+    /// Turn a [`Uart`] into a [`UartFuture`]
+    ///
+    /// All UART interrupts are disabled, as they are managed by the
+    /// [`UartFuture`] from now on.
+    #[inline]
+    pub fn into_future(mut self) -> UartFuture<C, D> {
+        self.disable_interrupts(Flags::all());
+        UartFuture {
+            uart: self,
+            rx_channel: NoneT,
+            tx_channel: NoneT,
+        }
+    }
+}
+
+/// `async` version of [`Uart`]
+///
+/// Create this struct by calling [`into_future`](Uart::into_future) on a
+/// [`Uart`]. See the [`uart`](crate::sercom::v2::uart) module-level
+/// documentation for more details.
+#[cfg_attr(not(feature = "dma"), allow(dead_code))]
+pub struct UartFuture<C, D, R = NoneT, T = NoneT>
+where
+    C: ValidConfig,
+    D: Capability,
+{
+    uart: Uart<C, D>,
+    rx_channel: R,
+    tx_channel: T,
+}
+
+impl<C, D> UartFuture<C, D>
+where
+    C: ValidConfig,
+    D: Capability,
+{
+    /// Return the underlying [`Uart`]
+    #[inline]
+    pub fn free(mut self) -> Uart<C, D> {
+        self.uart.disable_interrupts(Flags::all());
+        self.uart
+    }
+}
+
+#[cfg(feature = "dma")]
+impl<C, D, T> UartFuture<C, D, NoneT, T>
+where
+    C: ValidConfig,
+    D: Receive,
+{
+    /// Add a DMA channel to receive data
+    ///
+    /// [`read_exact`](UartFuture::read_exact) then lets the DMAC fill the
+    /// whole buffer, instead of handling an interrupt for each byte.
+    #[inline]
+    pub fn with_rx_dma_channel<R>(self, rx_channel: R) -> UartFuture<C, D, R, T>
+    where
+        R: AnyChannel<Status = Ready>,
+    {
+        UartFuture {
+            uart: self.uart,
+            rx_channel,
+            tx_channel: self.tx_channel,
+        }
+    }
+}
+
+#[cfg(feature = "dma")]
+impl<C, D, R> UartFuture<C, D, R, NoneT>
+where
+    C: ValidConfig,
+    D: Transmit,
+{
+    /// Add a DMA channel to send data
+    ///
+    /// [`write`](UartFuture::write) then lets the DMAC send the whole buffer,
+    /// instead of handling an interrupt for each byte.
+    #[inline]
+    pub fn with_tx_dma_channel<T>(self, tx_channel: T) -> UartFuture<C, D, R, T>
+    where
+        T: AnyChannel<Status = Ready>,
+    {
+        UartFuture {
+            uart: self.uart,
+            rx_channel: self.rx_channel,
+            tx_channel,
+        }
+    }
+}
+
+#[cfg(feature = "dma")]
+impl<C, D, R, T> UartFuture<C, D, R, T>
+where
+    C: ValidConfig,
+    D: Capability,
+{
+    /// Return the underlying [`Uart`] and DMA channels
+    ///
+    /// The channels are [`NoneT`] if none were provided.
+    #[inline]
+    pub fn free_dma(mut self) -> (Uart<C, D>, R, T) {
+        self.uart.disable_interrupts(Flags::all());
+        (self.uart, self.rx_channel, self.tx_channel)
+    }
+}
+
+impl<C, D, R, T> UartFuture<C, D, R, T>
+where
+    C: ValidConfig,
+    D: Capability,
+{
+    /// Wait until any of the given interrupt `flags` is set
+    ///
+    /// The interrupts are enabled while waiting. [`on_interrupt`] disables them
+    /// and calls the `waker` once they fire.
+    ///
+    /// [`on_interrupt`]: crate::sercom::v2::async_api::on_interrupt
+    async fn wait_flags(&mut self, flags: Flags, waker: &InterruptWaker) -> Flags {
+        poll_fn(|cx| {
+            waker.register(cx.waker());
+            self.uart.enable_interrupts(flags);
+            let set = self.uart.read_flags() & flags;
+            if set.is_empty() {
+                Poll::Pending
+            } else {
+                self.uart.disable_interrupts(flags);
+                Poll::Ready(set)
+            }
+        })
+        .await
+    }
+}
+
+impl<C, R, T> UartFuture<C, Duplex, R, T>
+where
+    C: ValidConfig,
+{
+    /// Split the [`UartFuture`] into [`RxDuplex`] and [`TxDuplex`] halves
+    ///
+    /// Each half keeps the DMA channel it uses, if any.
+    #[inline]
+    #[allow(clippy::type_complexity)]
+    pub fn split(
+        self,
+    ) -> (
+        UartFuture<C, RxDuplex, R, NoneT>,
+        UartFuture<C, TxDuplex, NoneT, T>,
+    ) {
+        let (rx, tx) = self.uart.split();
+        (
+            UartFuture {
+                uart: rx,
+                rx_channel: self.rx_channel,
+                tx_channel: NoneT,
+            },
+            UartFuture {
+                uart: tx,
+                rx_channel: NoneT,
+                tx_channel: self.tx_channel,
+            },
+        )
+    }
+
+    /// Join [`RxDuplex`] and [`TxDuplex`] halves back into a full
+    /// `UartFuture<C, Duplex>`
+    #[inline]
+    pub fn join(
+        rx: UartFuture<C, RxDuplex, R, NoneT>,
+        tx: UartFuture<C, TxDuplex, NoneT, T>,
+    ) -> Self {
+        Self {
+            uart: Uart::join(rx.uart, tx.uart),
+            rx_channel: rx.rx_channel,
+            tx_channel: tx.tx_channel,
+        }
+    }
+}
+
+impl<C, D, R, T> UartFuture<C, D, R, T>
+where
+    C: ValidConfig<Word = u8>,
+    D: Receive,
+{
+    /// Read at least one byte into `buffer`
+    ///
+    /// Waits for the first byte, then copies every byte already available in
+    /// the receive buffer without waiting any further. Returns the number of
+    /// bytes read.
+    ///
+    /// When a receive error is detected, the corresponding status flag is
+    /// cleared and the error is returned. Previously read bytes, if any, are
+    /// returned first; the error is then reported by the next call.
+    pub async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+
+        self.wait_flags(Flags::RXC | Flags::ERROR, rx_waker::<ConfigSercom<C>>())
+            .await;
+
+        let mut count = 0;
+        for byte in buffer.iter_mut() {
+            match self.uart.read_flags_errors() {
+                Ok(flags) if flags.contains(Flags::RXC) => {
+                    *byte = unsafe { self.uart.read_data() } as u8;
+                    count += 1;
+                }
+                Ok(_) => break,
+                Err(_) if count > 0 => break,
+                Err(err) => {
+                    self.uart.clear_status(Status::from(err));
+                    self.uart.clear_flags(Flags::ERROR);
+                    return Err(err);
+                }
+            }
+        }
+        Ok(count)
+    }
+}
+
+impl<C, D, R, T> UartFuture<C, D, R, T>
+where
+    C: ValidConfig<Word = u8>,
+    D: Transmit,
+{
+    async fn write_irq(&mut self, buffer: &[u8]) -> Result<usize, Error> {
+        for &byte in buffer {
+            self.wait_flags(Flags::DRE, tx_waker::<ConfigSercom<C>>())
+                .await;
+            unsafe { self.uart.write_data(byte as DataReg) };
+        }
+        Ok(buffer.len())
+    }
+
+    /// Wait for the transmission to complete
+    ///
+    /// Waits on and clears the `TXC` flag, just like the blocking
+    /// [`Write::flush`](embedded_hal::serial::Write::flush) implementation.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        self.wait_flags(Flags::TXC, tx_waker::<ConfigSercom<C>>())
+            .await;
+        self.uart.clear_flags(Flags::TXC);
+        Ok(())
+    }
+}
+
+impl<C, D, R> UartFuture<C, D, R, NoneT>
+where
+    C: ValidConfig<Word = u8>,
+    D: Transmit,
+{
+    /// Write all bytes from `buffer`
+    ///
+    /// Each byte is written as soon as the `DATA` register is empty. Returns
+    /// once the last byte was written to the `DATA` register, which may be
+    /// before it was shifted out. Use [`flush`](UartFuture::flush) to wait
+    /// for the transmission to complete.
+    #[inline]
+    pub async fn write(&mut self, buffer: &[u8]) -> Result<usize, Error> {
+        self.write_irq(buffer).await
+    }
+}
+
+#[cfg(feature = "dma")]
+impl<C, D, R, T> UartFuture<C, D, R, T>
+where
+    C: ValidConfig<Word = u8>,
+    D: Transmit,
+    T: AnyChannel<Status = Ready>,
+{
+    /// Write all bytes from `buffer`, using the DMAC
+    ///
+    /// Returns once the DMAC wrote the last byte to the `DATA` register, which
+    /// may be before it was shifted out. Use [`flush`](UartFuture::flush) to
+    /// wait for the transmission to complete.
+    pub async fn write(&mut self, buffer: &[u8]) -> Result<usize, Error> {
+        for chunk in buffer.chunks(MAX_TRANSFER_LENGTH) {
+            // SAFETY: The buffer outlives the guard, which stops the transfer
+            // when dropped. The DMAC only reads from the buffer, despite the
+            // pointer being `*mut`.
+            let mut transfer = unsafe {
+                self.tx_channel.as_mut().start_transfer(
+                    RawBuffer::slice(chunk.as_ptr() as *mut u8, chunk.len()),
+                    RawBuffer::fixed(self.uart.data_ptr(), 1),
+                    ConfigSercom::<C>::DMA_TX_TRIGGER,
+                    trigger_action(),
+                )
+            };
+            transfer.wait().await.map_err(Error::Dma)?;
+        }
+        Ok(buffer.len())
+    }
+}
+
+#[cfg(feature = "dma")]
+impl<C, D, R, T> UartFuture<C, D, R, T>
+where
+    C: ValidConfig<Word = u8>,
+    D: Receive,
+    R: AnyChannel<Status = Ready>,
+{
+    /// Fill the whole `buffer`, using the DMAC
+    ///
+    /// Receive errors are checked once the buffer is full. If a receive error
+    /// is detected, the corresponding status flag is cleared and the error is
+    /// returned, in which case the contents of `buffer` are unreliable.
+    pub async fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        for chunk in buffer.chunks_mut(MAX_TRANSFER_LENGTH) {
+            // SAFETY: The buffer outlives the guard, which stops the transfer
+            // when dropped.
+            let mut transfer = unsafe {
+                self.rx_channel.as_mut().start_transfer(
+                    RawBuffer::fixed(self.uart.data_ptr(), 1),
+                    RawBuffer::slice(chunk.as_mut_ptr(), chunk.len()),
+                    ConfigSercom::<C>::DMA_RX_TRIGGER,
+                    trigger_action(),
+                )
+            };
+            transfer.wait().await.map_err(Error::Dma)?;
+            drop(transfer);
+
+            if let Err(err) = self.uart.read_flags_errors() {
+                self.uart.clear_status(Status::from(err));
+                self.uart.clear_flags(Flags::ERROR);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// DMAC trigger action to transfer one byte for each SERCOM trigger
+#[cfg(feature = "dma")]
+#[inline]
+fn trigger_action() -> TriggerAction {
+    #[cfg(feature = "min-samd51g")]
+    return TriggerAction::BURST;
+
+    #[cfg(any(feature = "samd11", feature = "samd21"))]
+    return TriggerAction::BEAT;
+}
+
+impl embedded_io::Error for Error {
+    #[inline]
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self {
+            Error::ParityError | Error::FrameError | Error::InconsistentSyncField => {
+                embedded_io::ErrorKind::InvalidData
+            }
+            Error::Overflow | Error::CollisionDetected => embedded_io::ErrorKind::Other,
+            #[cfg(feature = "min-samd51g")]
+            Error::MaxIterations => embedded_io::ErrorKind::Other,
+            #[cfg(feature = "dma")]
+            Error::Dma(_) => embedded_io::ErrorKind::Other,
+        }
+    }
+}
+
+impl<C, D, R, T> embedded_io::ErrorType for UartFuture<C, D, R, T>
+where
+    C: ValidConfig,
+    D: Capability,
+{
+    type Error = Error;
+}
+
+impl<C, D, T> embedded_io_async::Read for UartFuture<C, D, NoneT, T>
+where
+    C: ValidConfig<Word = u8>,
+    D: Receive,
+{
+    #[inline]
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        UartFuture::read(self, buf).await
+    }
+}
+
+#[cfg(feature = "dma")]
+impl<C, D, R, T> embedded_io_async::Read for UartFuture<C, D, R, T>
+where
+    C: ValidConfig<Word = u8>,
+    D: Receive,
+    R: AnyChannel<Status = Ready>,
+{
+    #[inline]
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        UartFuture::read(self, buf).await
+    }
+
+    #[inline]
+    async fn read_exact(
+        &mut self,
+        buf: &mut [u8],
+    ) -> Result<(), embedded_io::ReadExactError<Self::Error>> {
+        Self::read_exact(self, buf)
+            .await
+            .map_err(embedded_io::ReadExactError::Other)
+    }
+}
+
+impl<C, D, R> embedded_io_async::Write for UartFuture<C, D, R, NoneT>
+where
+    C: ValidConfig<Word = u8>,
+    D: Transmit,
+{
+    #[inline]
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        Self::write(self, buf).await
+    }
+
+    #[inline]
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        UartFuture::flush(self).await
+    }
+}
+
+#[cfg(feature = "dma")]
+impl<C, D, R, T> embedded_io_async::Write for UartFuture<C, D, R, T>
+where
+    C: ValidConfig<Word = u8>,
+    D: Transmit,
+    T: AnyChannel<Status = Ready>,
+{
+    #[inline]
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        Self::write(self, buf).await
+    }
+
+    #[inline]
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        UartFuture::flush(self).await
+    }
+}
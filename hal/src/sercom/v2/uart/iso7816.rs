@@ -0,0 +1,199 @@
+//! ISO 7816 smart card interface (SAMx5x)
+//!
+//! In ISO 7816 mode, the SERCOM exchanges characters with a smart card over a
+//! single bidirectional I/O line, connected to `PAD[0]`. Both directions
+//! share the line, so the [`Pads`](super::Pads) only contain a `TX` pad,
+//! and [`Config::enable_iso7816`] returns a [`Duplex`] [`Uart`]. Characters
+//! are made of 8 data bits and an even parity bit. With the T=0 protocol, the
+//! receiver signals parity errors by pulling the line low during the guard
+//! time, and the transmitter repeats the characters refused by the card.
+//!
+//! The card also needs a clock, from 1 to 5 MHz for most cards, which can be
+//! provided by a generic clock generator. Configuring a generator with
+//! [`configure_gclk_divider_and_source`] enables its output, so the clock is
+//! available on the `GCLK_IO` pins of the generator, in alternate function
+//! `M`. [`clock_divider`] computes the divider of the generator, and
+//! [`Config::card_clock`] sets the bit rate from the card clock.
+//!
+//! ```no_run
+//! use atsamd_hal::sercom::v2::uart::iso7816::{self, Iso7816};
+//!
+//! let div = iso7816::clock_divider(48.mhz().into(), 4.mhz().into());
+//! let gclk = clocks
+//!     .configure_gclk_divider_and_source(ClockGenId::GCLK2, div, ClockSource::DFLL, true)
+//!     .unwrap();
+//! let _clk = pins.pa16.into_mode::<AlternateM>();
+//!
+//! let pads = uart::Pads::default().tx(pins.pa04);
+//! let mut card = uart::Config::new(&mclk, sercom0, pads, freq)
+//!     .iso7816(Some(Iso7816::default()))
+//!     .card_clock(gclk.freq(), iso7816::DEFAULT_CLOCK_RATE, 1)
+//!     .enable_iso7816();
+//! ```
+//!
+//! [`configure_gclk_divider_and_source`]: crate::clock::GenericClockController::configure_gclk_divider_and_source
+
+use super::{BaudMode, Config, Duplex, Oversampling, Tx, Uart, ValidPads};
+use crate::time::Hertz;
+use core::marker::PhantomData;
+
+/// Clock rate conversion factor `F` of a card answering to reset without
+/// `TA1` byte
+pub const DEFAULT_CLOCK_RATE: u16 = 372;
+
+/// Transmission protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// Character-oriented protocol, in which parity errors are signalled
+    /// with a `NACK`, and the characters repeated
+    T0,
+    /// Block-oriented protocol, without `NACK`
+    T1,
+}
+
+/// Encoding of the characters, announced by the initial character of the
+/// answer to reset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Convention {
+    /// High level is logic one, least significant bit first
+    Direct,
+    /// Low level is logic one, most significant bit first
+    Inverse,
+}
+
+/// ISO 7816 settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Iso7816 {
+    /// Transmission protocol
+    pub protocol: Protocol,
+    /// Encoding of the characters
+    pub convention: Convention,
+    /// Extra guard time, from 0 to 7 bit periods, inserted by the
+    /// transmitter between consecutive characters
+    pub guard_time: u8,
+    /// With T=0, accept characters with parity errors instead of signalling
+    /// them with a `NACK`. The parity error is still reported.
+    pub inhibit_nack: bool,
+    /// With T=0, stop signalling parity errors once a character was refused
+    /// `max_iterations` times, and accept it
+    pub disable_successive_nack: bool,
+    /// With T=0, number of repetitions of a refused character, from 0 to 7,
+    /// before reporting an error
+    pub max_iterations: u8,
+}
+
+impl Default for Iso7816 {
+    /// T=0, direct convention, with the minimum guard time and a character
+    /// repeated up to 3 times
+    fn default() -> Self {
+        Self {
+            protocol: Protocol::T0,
+            convention: Convention::Direct,
+            guard_time: 2,
+            inhibit_nack: false,
+            disable_successive_nack: false,
+            max_iterations: 3,
+        }
+    }
+}
+
+/// Compute the bit rate from the `clock` frequency of the card, its clock
+/// rate conversion factor `f` and its baud rate adjustment factor `d`
+///
+/// A bit lasts `f / d` clock cycles, an elementary time unit. A `d` of 0 is
+/// handled as 1.
+pub fn bit_rate(clock: Hertz, f: u16, d: u16) -> Hertz {
+    let cycles = f.max(1) as u64;
+    Hertz((clock.0 as u64 * d.max(1) as u64 / cycles) as u32)
+}
+
+/// Compute the divider of a generic clock generator, producing the fastest
+/// card clock not exceeding `max` from the `source` frequency
+pub fn clock_divider(source: Hertz, max: Hertz) -> u16 {
+    let max = max.0.max(1);
+    let div = (source.0 + max - 1) / max;
+    div.clamp(1, u8::MAX as u32) as u16
+}
+
+impl<P> Config<P>
+where
+    P: ValidPads<Capability = Tx>,
+{
+    /// Enable or disable ISO 7816 mode (builder pattern version)
+    ///
+    /// ISO 7816 mode replaces the character size, parity, bit order and
+    /// auto-baud settings. `None` returns to an 8-bit, LSB-first frame
+    /// without parity.
+    #[inline]
+    pub fn iso7816(mut self, settings: Option<Iso7816>) -> Self {
+        self.set_iso7816(settings);
+        self
+    }
+
+    /// Enable or disable ISO 7816 mode (setter version)
+    ///
+    /// See [`iso7816`](Self::iso7816) for more details.
+    #[inline]
+    pub fn set_iso7816(&mut self, settings: Option<Iso7816>) {
+        self.registers.set_iso7816(settings);
+    }
+
+    /// Get the current ISO 7816 settings
+    #[inline]
+    pub fn get_iso7816(&self) -> Option<Iso7816> {
+        self.registers.get_iso7816()
+    }
+
+    /// Set the bit rate from the card clock (builder pattern version)
+    ///
+    /// See [`bit_rate`] for more details.
+    #[inline]
+    pub fn card_clock(mut self, clock: Hertz, f: u16, d: u16) -> Self {
+        self.set_card_clock(clock, f, d);
+        self
+    }
+
+    /// Set the bit rate from the card clock (setter version)
+    ///
+    /// See [`bit_rate`] for more details.
+    #[inline]
+    pub fn set_card_clock(&mut self, clock: Hertz, f: u16, d: u16) {
+        self.set_baud(
+            bit_rate(clock, f, d),
+            BaudMode::Arithmetic(Oversampling::Bits16),
+        );
+    }
+
+    /// Enable the SERCOM in ISO 7816 mode, transmitting and receiving on
+    /// the I/O line
+    #[inline]
+    pub fn enable_iso7816(mut self) -> Uart<Self, Duplex> {
+        self.registers.enable(true, true);
+        Uart {
+            config: self,
+            capability: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bit_rates() {
+        assert_eq!(
+            bit_rate(Hertz(3_571_200), DEFAULT_CLOCK_RATE, 1),
+            Hertz(9_600)
+        );
+        assert_eq!(bit_rate(Hertz(4_000_000), 512, 8), Hertz(62_500));
+        assert_eq!(bit_rate(Hertz(4_000_000), 372, 0), Hertz(10_752));
+    }
+
+    #[test]
+    fn clock_dividers() {
+        assert_eq!(clock_divider(Hertz(48_000_000), Hertz(4_000_000)), 12);
+        assert_eq!(clock_divider(Hertz(48_000_000), Hertz(5_000_000)), 10);
+        assert_eq!(clock_divider(Hertz(1_000_000), Hertz(4_000_000)), 1);
+    }
+}
@@ -0,0 +1,82 @@
+//! LIN frame helpers
+//!
+//! A LIN frame is made of a header, sent by the master, and a response, sent
+//! by the master or by a slave. The header is a break field, the `0x55` sync
+//! field, and the protected identifier returned by [`protected_id`]. The
+//! response is made of up to 8 data bytes followed by a checksum, computed by
+//! [`checksum`].
+
+/// Length of the break field transmitted by a LIN master
+#[cfg(feature = "min-samd51g")]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakLength {
+    /// 13 bit periods, the LIN minimum
+    Bits13,
+    /// 17 bit periods
+    Bits17,
+    /// 21 bit periods
+    Bits21,
+    /// 26 bit periods
+    Bits26,
+}
+
+/// Delay inserted by a LIN master between the break and sync fields, and
+/// between the sync field and the identifier
+#[cfg(feature = "min-samd51g")]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderDelay {
+    /// 1 bit period
+    Bits1,
+    /// 4 bit periods
+    Bits4,
+    /// 8 bit periods
+    Bits8,
+    /// 14 bit periods
+    Bits14,
+}
+
+/// Add the two parity bits to the 6-bit frame identifier `id`
+pub fn protected_id(id: u8) -> u8 {
+    let id = id & 0x3f;
+    let bit = |n: u8| (id >> n) & 1;
+    let p0 = bit(0) ^ bit(1) ^ bit(2) ^ bit(4);
+    let p1 = !(bit(1) ^ bit(3) ^ bit(4) ^ bit(5)) & 1;
+    id | p0 << 6 | p1 << 7
+}
+
+/// Compute the checksum of a frame response
+///
+/// The enhanced checksum of LIN 2.x covers the protected identifier `pid`
+/// along with the `data` bytes. The classic checksum of LIN 1.x, also used by
+/// diagnostic frames, only covers the `data` bytes; pass `None` as `pid` to
+/// compute it.
+pub fn checksum(pid: Option<u8>, data: &[u8]) -> u8 {
+    let sum = pid.iter().chain(data).fold(0u16, |sum, &byte| {
+        let sum = sum + byte as u16;
+        // Add the carry back
+        (sum & 0xff) + (sum >> 8)
+    });
+    !(sum as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protected_ids() {
+        assert_eq!(protected_id(0x00), 0x80);
+        assert_eq!(protected_id(0x01), 0xc1);
+        assert_eq!(protected_id(0x3c), 0x3c);
+        assert_eq!(protected_id(0x3d), 0x7d);
+    }
+
+    #[test]
+    fn checksums() {
+        let data = [0x4a, 0x55, 0x93, 0xe5];
+        assert_eq!(checksum(None, &data), 0xe6);
+        assert_eq!(checksum(Some(0x4a), &[0x55, 0x93, 0xe5]), 0xe6);
+    }
+}
@@ -0,0 +1,193 @@
+//! Multidrop bus helpers
+//!
+//! Legacy multidrop buses, such as the multiprocessor mode of 8051 and AVR
+//! UARTs, use 9-bit characters: the ninth bit marks the characters holding
+//! the address of a node, while the following data characters are only
+//! processed by the node addressed. Configure the UART with a
+//! [`NineBit`](super::NineBit) character size, then build the characters with
+//! [`Frame`].
+//!
+//! The SERCOM has no address recognition in UART mode, so every character
+//! raises the `RXC` flag. An [`AddressFilter`] decides which characters are
+//! meant for this node, and [`Uart::read_addressed`] only returns those,
+//! dropping the others from the receive interrupt handler.
+//!
+//! ```
+//! use atsamd_hal::sercom::v2::uart::multidrop::{AddressFilter, Frame};
+//!
+//! let mut uart = uart::Config::new(&mclk, sercom, pads, freq)
+//!     .char_size::<NineBit>()
+//!     .enable();
+//! let mut filter = AddressFilter::new(0x12).with_broadcast(0xff);
+//!
+//! // Address a node, then send it data
+//! nb::block!(uart.write(Frame::Address(0x34).into()));
+//! nb::block!(uart.write(Frame::Data(0x56).into()));
+//!
+//! // In the RXC interrupt handler
+//! if let Ok(byte) = uart.read_addressed(&mut filter) {
+//!     // Data received by this node
+//! }
+//! ```
+
+use super::{Error, Flags, Receive, Uart, ValidConfig};
+
+/// Ninth bit, set in address characters
+pub const ADDRESS_MARK: u16 = 0x100;
+
+/// Character of a multidrop bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frame {
+    /// Address of the node receiving the following data characters
+    Address(u8),
+    /// Data character
+    Data(u8),
+}
+
+impl From<u16> for Frame {
+    /// Decode a 9-bit character
+    #[inline]
+    fn from(word: u16) -> Frame {
+        let byte = word as u8;
+        if word & ADDRESS_MARK != 0 {
+            Frame::Address(byte)
+        } else {
+            Frame::Data(byte)
+        }
+    }
+}
+
+impl From<Frame> for u16 {
+    /// Encode a 9-bit character
+    #[inline]
+    fn from(frame: Frame) -> u16 {
+        match frame {
+            Frame::Address(address) => ADDRESS_MARK | address as u16,
+            Frame::Data(byte) => byte as u16,
+        }
+    }
+}
+
+/// Filter of the data characters addressed to a node
+///
+/// The filter is selected by an address character matching the address of
+/// the node, or the broadcast address, and deselected by any other address.
+/// It starts deselected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressFilter {
+    address: u8,
+    mask: u8,
+    broadcast: Option<u8>,
+    selected: bool,
+}
+
+impl AddressFilter {
+    /// Create a filter for the node `address`
+    #[inline]
+    pub const fn new(address: u8) -> Self {
+        Self {
+            address,
+            mask: 0xff,
+            broadcast: None,
+            selected: false,
+        }
+    }
+
+    /// Only compare the address bits set in `mask`, so that the node answers
+    /// to a group of addresses
+    #[inline]
+    pub const fn with_mask(mut self, mask: u8) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    /// Also accept the data characters sent to the `broadcast` address
+    #[inline]
+    pub const fn with_broadcast(mut self, broadcast: u8) -> Self {
+        self.broadcast = Some(broadcast);
+        self
+    }
+
+    /// Return whether the data characters are currently addressed to the
+    /// node
+    #[inline]
+    pub fn is_selected(&self) -> bool {
+        self.selected
+    }
+
+    /// Return whether `address` designates the node
+    #[inline]
+    pub fn matches(&self, address: u8) -> bool {
+        (address ^ self.address) & self.mask == 0 || self.broadcast == Some(address)
+    }
+
+    /// Process a received character, returning its data if it is addressed
+    /// to the node
+    ///
+    /// Address characters update the selection of the node, and are never
+    /// returned.
+    #[inline]
+    pub fn filter(&mut self, word: u16) -> Option<u8> {
+        match Frame::from(word) {
+            Frame::Address(address) => {
+                self.selected = self.matches(address);
+                None
+            }
+            Frame::Data(byte) if self.selected => Some(byte),
+            Frame::Data(_) => None,
+        }
+    }
+}
+
+impl<C, D> Uart<C, D>
+where
+    C: ValidConfig<Word = u16>,
+    D: Receive,
+{
+    /// Read a character through an [`AddressFilter`]
+    ///
+    /// Characters not addressed to the node are read and dropped, returning
+    /// [`WouldBlock`](nb::Error::WouldBlock) as if nothing was received.
+    #[inline]
+    pub fn read_addressed(&mut self, filter: &mut AddressFilter) -> nb::Result<u8, Error> {
+        let flags = self.read_flags_errors()?;
+        if !flags.contains(Flags::RXC) {
+            return Err(nb::Error::WouldBlock);
+        }
+        let word = unsafe { self.read_data() };
+        filter.filter(word as _).ok_or(nb::Error::WouldBlock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames() {
+        assert_eq!(u16::from(Frame::Address(0x12)), 0x112);
+        assert_eq!(u16::from(Frame::Data(0x12)), 0x012);
+        assert_eq!(Frame::from(0x1ab), Frame::Address(0xab));
+        assert_eq!(Frame::from(0x0ab), Frame::Data(0xab));
+    }
+
+    #[test]
+    fn filter() {
+        let mut filter = AddressFilter::new(0x12).with_broadcast(0xff);
+        assert_eq!(filter.filter(0x042), None);
+        assert_eq!(filter.filter(0x112), None);
+        assert!(filter.is_selected());
+        assert_eq!(filter.filter(0x042), Some(0x42));
+        assert_eq!(filter.filter(0x134), None);
+        assert_eq!(filter.filter(0x042), None);
+        assert_eq!(filter.filter(0x1ff), None);
+        assert_eq!(filter.filter(0x042), Some(0x42));
+    }
+
+    #[test]
+    fn masked_filter() {
+        let filter = AddressFilter::new(0x10).with_mask(0xf0);
+        assert!(filter.matches(0x1f));
+        assert!(!filter.matches(0x20));
+    }
+}
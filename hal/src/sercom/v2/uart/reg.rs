@@ -1,5 +1,10 @@
 //! Register-level access to UART configuration
 
+#[cfg(feature = "min-samd51g")]
+use super::{
+    iso7816::{Convention, Iso7816, Protocol},
+    BreakLength, HeaderDelay,
+};
 use super::{BaudMode, BitOrder, CharSizeEnum, Flags, Oversampling, Parity, Status, StopBits};
 
 use crate::pac;
@@ -13,6 +18,19 @@ use pac::sercom0::usart_int::ctrla::MODE_A;
 
 use crate::time::Hertz;
 
+/// Auto-baud bit of the `FORM` field
+const FORM_AUTO_BAUD: u8 = 0x4;
+/// `FORM` value of the LIN master mode
+#[cfg(feature = "min-samd51g")]
+const FORM_LIN_MASTER: u8 = 0x2;
+/// `LINCMD` value transmitting the break, sync and identifier fields when
+/// `DATA` is written
+#[cfg(feature = "min-samd51g")]
+const LINCMD_HEADER: u8 = 0x2;
+/// `FORM` value of the ISO 7816 mode
+#[cfg(feature = "min-samd51g")]
+const FORM_ISO7816: u8 = 0x7;
+
 pub(super) struct Registers<S: Sercom> {
     sercom: S,
 }
@@ -126,8 +144,8 @@ impl<S: Sercom> Registers<S> {
     /// Change the parity setting
     #[inline]
     pub(super) fn set_parity(&mut self, parity: Parity) {
-        // Use only the first two available settings in the FORM field.
-        // Ignore auto-baud options.
+        // Only change the parity bit of the FORM field, keeping the auto-baud
+        // setting
         let enabled = match parity {
             Parity::None => false,
             Parity::Odd => {
@@ -140,9 +158,10 @@ impl<S: Sercom> Registers<S> {
             }
         };
 
-        self.usart()
-            .ctrla
-            .modify(|_, w| unsafe { w.form().bits(enabled as u8) });
+        self.usart().ctrla.modify(|r, w| unsafe {
+            let auto_baud = r.form().bits() & FORM_AUTO_BAUD;
+            w.form().bits(auto_baud | enabled as u8)
+        });
     }
 
     /// Get the current parity setting
@@ -343,6 +362,182 @@ impl<S: Sercom> Registers<S> {
         }
     }
 
+    /// Enable or disable break detection and auto-baud, as used by LIN
+    /// slaves
+    #[inline]
+    pub(super) fn set_auto_baud(&mut self, enabled: bool) {
+        self.usart().ctrla.modify(|r, w| {
+            let parity = match r.form().bits() {
+                0x1 | 0x5 => 0x1,
+                _ => 0x0,
+            };
+            let auto_baud = if enabled { FORM_AUTO_BAUD } else { 0 };
+            // SAFETY: Both values are valid FORM settings
+            unsafe { w.form().bits(auto_baud | parity) }
+        });
+    }
+
+    /// Get the current auto-baud setting
+    #[inline]
+    pub(super) fn get_auto_baud(&self) -> bool {
+        matches!(self.usart().ctrla.read().form().bits(), 0x4 | 0x5)
+    }
+
+    /// Enable or disable LIN master mode, with the break length and header
+    /// delay of the headers
+    #[cfg(feature = "min-samd51g")]
+    #[inline]
+    pub(super) fn set_lin_master(&mut self, header: Option<(BreakLength, HeaderDelay)>) {
+        match header {
+            Some((break_length, header_delay)) => {
+                // SAFETY: BRKLEN and HDRDLY accept any 2-bit value
+                self.usart().ctrlc.modify(|_, w| unsafe {
+                    w.brklen().bits(break_length as u8);
+                    w.hdrdly().bits(header_delay as u8)
+                });
+                self.usart()
+                    .ctrla
+                    .modify(|_, w| unsafe { w.form().bits(FORM_LIN_MASTER) });
+            }
+            None => self.set_parity(Parity::None),
+        }
+    }
+
+    /// Get the current LIN master setting
+    #[cfg(feature = "min-samd51g")]
+    #[inline]
+    pub(super) fn get_lin_master(&self) -> Option<(BreakLength, HeaderDelay)> {
+        if self.usart().ctrla.read().form().bits() != FORM_LIN_MASTER {
+            return None;
+        }
+        let ctrlc = self.usart().ctrlc.read();
+        let break_length = match ctrlc.brklen().bits() {
+            0 => BreakLength::Bits13,
+            1 => BreakLength::Bits17,
+            2 => BreakLength::Bits21,
+            _ => BreakLength::Bits26,
+        };
+        let header_delay = match ctrlc.hdrdly().bits() {
+            0 => HeaderDelay::Bits1,
+            1 => HeaderDelay::Bits4,
+            2 => HeaderDelay::Bits8,
+            _ => HeaderDelay::Bits14,
+        };
+        Some((break_length, header_delay))
+    }
+
+    /// Transmit a LIN header, whose identifier is `pid`
+    #[cfg(feature = "min-samd51g")]
+    #[inline]
+    pub(super) fn send_lin_header(&mut self, pid: u8) {
+        // SAFETY: LINCMD_HEADER is a valid LIN command
+        self.usart()
+            .ctrlb
+            .modify(|_, w| unsafe { w.lincmd().bits(LINCMD_HEADER) });
+        while self.usart().syncbusy.read().ctrlb().bit_is_set() {}
+        // SAFETY: The caller waits for the DRE flag, like any other write
+        unsafe { self.write_data(pid as super::DataReg) };
+    }
+
+    /// Enable or disable RS-485 mode, where the `RTS` pad drives the
+    /// transmit enable output for `guard_time` bit periods after the last
+    /// stop bit
+    #[cfg(feature = "min-samd51g")]
+    #[inline]
+    pub(super) fn set_rs485(&mut self, guard_time: Option<u8>) {
+        use pac::sercom0::usart_int::ctrla::TXPO_A;
+        let txpo = match guard_time {
+            Some(_) => TXPO_A::TXPO_3,
+            None => TXPO_A::TXPO_2,
+        };
+        self.usart().ctrla.modify(|_, w| w.txpo().variant(txpo));
+        // SAFETY: GTIME accepts any 3-bit value
+        self.usart()
+            .ctrlc
+            .modify(|_, w| unsafe { w.gtime().bits(guard_time.unwrap_or(0) & 0b111) });
+    }
+
+    /// Get the current RS-485 guard time, or `None` if RS-485 mode is
+    /// disabled
+    #[cfg(feature = "min-samd51g")]
+    #[inline]
+    pub(super) fn get_rs485(&self) -> Option<u8> {
+        if self.usart().ctrla.read().txpo().bits() == 3 {
+            Some(self.usart().ctrlc.read().gtime().bits())
+        } else {
+            None
+        }
+    }
+
+    /// Enable or disable ISO 7816 mode
+    #[cfg(feature = "min-samd51g")]
+    #[inline]
+    pub(super) fn set_iso7816(&mut self, settings: Option<Iso7816>) {
+        match settings {
+            Some(settings) => {
+                let inverse = settings.convention == Convention::Inverse;
+                // SAFETY: FORM_ISO7816 is a valid FORM setting
+                self.usart().ctrla.modify(|_, w| unsafe {
+                    w.form().bits(FORM_ISO7816);
+                    // CMODE selects the protocol in ISO 7816 mode
+                    w.cmode().bit(settings.protocol == Protocol::T0);
+                    w.txinv().bit(inverse);
+                    w.rxinv().bit(inverse);
+                    // DORD is cleared for MSB-first
+                    w.dord().bit(!inverse)
+                });
+                // ISO 7816 characters have 8 bits and an even parity
+                self.usart()
+                    .ctrlb
+                    .modify(|_, w| unsafe { w.chsize().bits(0).pmode().clear_bit() });
+                // SAFETY: GTIME and MAXITER accept any 3-bit value
+                self.usart().ctrlc.modify(|_, w| unsafe {
+                    w.gtime().bits(settings.guard_time & 0b111);
+                    w.inack().bit(settings.inhibit_nack);
+                    w.dsnack().bit(settings.disable_successive_nack);
+                    w.maxiter().bits(settings.max_iterations & 0b111)
+                });
+            }
+            None => {
+                self.usart().ctrla.modify(|_, w| unsafe {
+                    w.form().bits(0);
+                    w.cmode().clear_bit();
+                    w.txinv().clear_bit();
+                    w.rxinv().clear_bit();
+                    w.dord().set_bit()
+                });
+            }
+        }
+    }
+
+    /// Get the current ISO 7816 settings, or `None` if ISO 7816 mode is
+    /// disabled
+    #[cfg(feature = "min-samd51g")]
+    #[inline]
+    pub(super) fn get_iso7816(&self) -> Option<Iso7816> {
+        let ctrla = self.usart().ctrla.read();
+        if ctrla.form().bits() != FORM_ISO7816 {
+            return None;
+        }
+        let ctrlc = self.usart().ctrlc.read();
+        Some(Iso7816 {
+            protocol: if ctrla.cmode().bit() {
+                Protocol::T0
+            } else {
+                Protocol::T1
+            },
+            convention: if ctrla.txinv().bit() {
+                Convention::Inverse
+            } else {
+                Convention::Direct
+            },
+            guard_time: ctrlc.gtime().bits(),
+            inhibit_nack: ctrlc.inack().bit(),
+            disable_successive_nack: ctrlc.dsnack().bit(),
+            max_iterations: ctrlc.maxiter().bits(),
+        })
+    }
+
     /// Clear specified interrupt flags
     #[inline]
     pub(super) fn clear_flags(&mut self, flags: Flags) {
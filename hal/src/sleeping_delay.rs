@@ -1,8 +1,34 @@
 //! Delays with WFI sleep while we wait using a timer
+//!
+//! The CPU sleeps in the mode selected with [`crate::power::configure`]. Use
+//! a timer which keeps running in that mode, such as the RTC in standby.
+//!
+//! [`SleepingDelay`] restarts the timer as a countdown, of at most one second
+//! at a time. [`RtcSleepingDelay`] keeps the counter of the RTC running, and
+//! sleeps until it reaches the end of the delay, however long:
+//!
+//! ```no_run
+//! use atsamd_hal::power::{self, SleepMode};
+//! use atsamd_hal::rtc::Rtc;
+//! use atsamd_hal::sleeping_delay::RtcSleepingDelay;
+//!
+//! power::configure(&mut peripherals.PM, &mut core.SCB, SleepMode::Standby);
+//! let rtc = Rtc::count32_mode(peripherals.RTC, 32_768.hz(), &mut peripherals.PM);
+//! unsafe { NVIC::unmask(interrupt::RTC) };
+//! let mut delay = RtcSleepingDelay::new(rtc);
+//!
+//! // One minute in standby, feeding the watchdog every second
+//! delay.delay_ms_with(60_000, 1_000, || {
+//!     watchdog.feed();
+//!     true
+//! });
+//! ```
 use core::sync::atomic;
 use cortex_m::asm;
 
 use crate::ehal::blocking::delay::{DelayMs, DelayUs};
+#[cfg(feature = "device")]
+use crate::rtc::{Count32Mode, Rtc};
 use crate::time::U32Ext;
 use crate::timer_traits::InterruptDrivenTimer;
 
@@ -74,3 +100,163 @@ where
         self.delay_us(ms.into() * 1_000_u32);
     }
 }
+
+#[cfg(feature = "ehal1")]
+impl<TIM> embedded_hal_1::delay::DelayNs for SleepingDelay<TIM>
+where
+    TIM: InterruptDrivenTimer,
+{
+    fn delay_ns(&mut self, ns: u32) {
+        // Round up to the next microsecond, a delay is never shorter than
+        // requested
+        DelayUs::delay_us(self, ns / 1_000 + (ns % 1_000 != 0) as u32);
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        DelayUs::delay_us(self, us);
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        DelayMs::delay_ms(self, ms);
+    }
+}
+
+/// Delay and sleep (WFI) until the RTC counter reaches the end of the delay
+///
+/// The counter is never reset: each delay sets the compare value to its end,
+/// so it lasts exactly the requested time, rounded up to the next cycles of
+/// the counter. The RTC interrupt must be unmasked in the NVIC, to wake the
+/// CPU; the delay clears it with interrupts disabled, so its handler doesn't
+/// run. The other interrupts are serviced while the delay goes on.
+#[cfg(feature = "device")]
+pub struct RtcSleepingDelay {
+    rtc: Rtc<Count32Mode>,
+}
+
+#[cfg(feature = "device")]
+impl RtcSleepingDelay {
+    /// Sleep with `rtc`, counting freely
+    ///
+    /// The counter is no longer cleared on compare match, if `rtc` was used
+    /// as a countdown, and the RTC is enabled.
+    pub fn new(mut rtc: Rtc<Count32Mode>) -> Self {
+        rtc.run_freely();
+        rtc.enable_interrupt();
+        Self { rtc }
+    }
+
+    /// Releases the RTC
+    pub fn free(mut self) -> Rtc<Count32Mode> {
+        self.rtc.disable_interrupt();
+        self.rtc.check_and_clear_compare();
+        self.rtc
+    }
+
+    /// Sleeps for `ms` milliseconds, waking up at least every `period_ms` to
+    /// call `f`, such as to feed the watchdog
+    ///
+    /// `f` is also called after the other interrupts waking the CPU. It
+    /// returns `false` to end the delay early; this method returns whether the
+    /// delay elapsed.
+    pub fn delay_ms_with<F>(&mut self, ms: u32, period_ms: u32, mut f: F) -> bool
+    where
+        F: FnMut() -> bool,
+    {
+        let ticks = self.ticks(ms, 1_000);
+        let period = self.ticks(period_ms, 1_000);
+        self.sleep_ticks(ticks, period, &mut f)
+    }
+
+    /// Number of counter cycles in `time` units of `1 / scale` seconds,
+    /// rounded up
+    #[inline]
+    fn ticks(&self, time: u32, scale: u64) -> u64 {
+        let cycles = time as u64 * self.rtc.count_frequency().0 as u64;
+        (cycles + scale - 1) / scale
+    }
+
+    /// Number of counter cycles left in the `chunk` cycles from `start`
+    #[inline]
+    fn cycles_left(&self, start: u32, chunk: u32) -> u32 {
+        chunk.saturating_sub(self.rtc.count32().wrapping_sub(start))
+    }
+
+    fn sleep_ticks(&mut self, ticks: u64, period: u64, f: &mut dyn FnMut() -> bool) -> bool {
+        // The current cycle is partly elapsed
+        let mut remaining = ticks + 1;
+        // Stay away from the wrap-around of the counter
+        let period = period.clamp(1, 1 << 31);
+        let mut start = self.rtc.count32();
+        while remaining != 0 {
+            let chunk = remaining.min(period) as u32;
+            let end = start.wrapping_add(chunk);
+            self.rtc.set_compare_value(end);
+            loop {
+                let (slept, left) = cortex_m::interrupt::free(|_| {
+                    self.rtc.check_and_clear_compare();
+                    // The counter may have passed the compare value while it
+                    // was synchronized, so the last cycles are polled
+                    let slept = self.cycles_left(start, chunk) > 2;
+                    if slept {
+                        asm::dsb();
+                        asm::wfi();
+                        self.rtc.check_and_clear_compare();
+                        cortex_m::peripheral::NVIC::unpend(crate::pac::Interrupt::RTC);
+                    }
+                    (slept, self.cycles_left(start, chunk))
+                });
+                if left == 0 {
+                    break;
+                }
+                // Another interrupt woke the CPU before the end of the chunk
+                if slept && !f() {
+                    return false;
+                }
+            }
+            start = end;
+            remaining -= chunk as u64;
+            if !f() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(feature = "device")]
+impl<TYPE> DelayUs<TYPE> for RtcSleepingDelay
+where
+    TYPE: Into<u32>,
+{
+    fn delay_us(&mut self, us: TYPE) {
+        let ticks = self.ticks(us.into(), NUM_US_IN_S as u64);
+        self.sleep_ticks(ticks, u64::MAX, &mut || true);
+    }
+}
+
+#[cfg(feature = "device")]
+impl<TYPE> DelayMs<TYPE> for RtcSleepingDelay
+where
+    TYPE: Into<u32>,
+{
+    fn delay_ms(&mut self, ms: TYPE) {
+        let ticks = self.ticks(ms.into(), 1_000);
+        self.sleep_ticks(ticks, u64::MAX, &mut || true);
+    }
+}
+
+#[cfg(all(feature = "ehal1", feature = "device"))]
+impl embedded_hal_1::delay::DelayNs for RtcSleepingDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        let ticks = self.ticks(ns, 1_000_000_000);
+        self.sleep_ticks(ticks, u64::MAX, &mut || true);
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        DelayUs::delay_us(self, us);
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        DelayMs::delay_ms(self, ms);
+    }
+}
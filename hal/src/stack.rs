@@ -0,0 +1,161 @@
+//! # Stack - Stack usage measurement
+//!
+//! The stack grows down from the top of the RAM towards the static data. To
+//! measure its maximum usage, [`StackRegion::paint`] fills the unused part of
+//! the stack with a pattern at startup; [`StackRegion::high_water_mark`]
+//! later finds the lowest word overwritten, giving the most stack ever used.
+//! Run the application through its worst cases, such as all the interrupts
+//! firing, to validate the memory budget of the smaller chips.
+//!
+//! ```no_run
+//! use atsamd_hal::stack::StackRegion;
+//!
+//! let stack = StackRegion::from_linker();
+//! unsafe { stack.paint() };
+//! // ...
+//! log::info!("stack: {} of {} bytes", stack.high_water_mark(), stack.size());
+//! ```
+//!
+//! This module is enabled by the `stack` feature. It relies on the symbols
+//! of the `cortex-m-rt` linker script.
+
+/// Pattern of the painted stack words
+pub const PAINT: u32 = 0xCCCC_CCCC;
+
+/// Size of the stack reserved for the caller of [`StackRegion::paint`], in
+/// bytes, which is left unpainted
+const PAINT_MARGIN: usize = 64;
+
+/// Memory region holding the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackRegion {
+    bottom: *mut u32,
+    top: *mut u32,
+}
+
+impl StackRegion {
+    /// Region between the end of the static data and the initial stack
+    /// pointer, given by the linker script of `cortex-m-rt`
+    ///
+    /// The region starts at `__sheap`, where `cortex-m-rt` places the heap.
+    /// With a heap allocator using that memory, build the region with
+    /// [`new`](Self::new) from the end of the heap instead, as
+    /// [`paint`](Self::paint) would overwrite the allocations.
+    #[cfg(not(test))]
+    pub fn from_linker() -> Self {
+        extern "C" {
+            static mut __sheap: u32;
+            static mut _stack_start: u32;
+        }
+        // SAFETY: The linker symbols bound the RAM left to the stack
+        unsafe {
+            Self::new(
+                core::ptr::addr_of_mut!(__sheap),
+                core::ptr::addr_of_mut!(_stack_start),
+            )
+        }
+    }
+
+    /// Region from `bottom` to `top`, excluded
+    ///
+    /// # Safety
+    ///
+    /// `bottom` and `top` must be word-aligned, with `bottom` below `top`,
+    /// and bound memory used by nothing but the stack.
+    #[inline]
+    pub unsafe fn new(bottom: *mut u32, top: *mut u32) -> Self {
+        Self { bottom, top }
+    }
+
+    /// Lowest word of the region
+    #[inline]
+    pub fn bottom(&self) -> *mut u32 {
+        self.bottom
+    }
+
+    /// End of the region, the initial stack pointer
+    #[inline]
+    pub fn top(&self) -> *mut u32 {
+        self.top
+    }
+
+    /// Size of the region, in bytes
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.top as usize - self.bottom as usize
+    }
+
+    /// Fill the region with [`PAINT`], from its bottom up to a few bytes
+    /// below the current stack pointer
+    ///
+    /// # Safety
+    ///
+    /// The stack must lie within the region. Call this function once at
+    /// startup, before using memory below the stack pointer for anything but
+    /// the stack. In particular, no heap may be allocated in the region: a
+    /// region from [`from_linker`](Self::from_linker) starts at `__sheap`, the
+    /// start of the heap.
+    #[cfg(not(test))]
+    #[inline(never)]
+    pub unsafe fn paint(&self) {
+        let sp = cortex_m::register::msp::read() as usize;
+        self.paint_below(sp.saturating_sub(PAINT_MARGIN) as *mut u32);
+    }
+
+    /// Fill the region with [`PAINT`], from its bottom up to `end`, excluded
+    unsafe fn paint_below(&self, end: *mut u32) {
+        let end = end.min(self.top);
+        let mut word = self.bottom;
+        while word < end {
+            word.write_volatile(PAINT);
+            word = word.add(1);
+        }
+    }
+
+    /// Maximum number of bytes of the stack used since it was painted
+    ///
+    /// The result is an upper bound: values pushed on the stack which
+    /// happen to match [`PAINT`] are counted as unused.
+    pub fn high_water_mark(&self) -> usize {
+        self.top as usize - self.lowest_used() as usize
+    }
+
+    /// Number of bytes of the stack never used since it was painted
+    #[inline]
+    pub fn free(&self) -> usize {
+        self.size() - self.high_water_mark()
+    }
+
+    /// Lowest word of the region which doesn't hold [`PAINT`]
+    fn lowest_used(&self) -> *mut u32 {
+        let mut word = self.bottom;
+        // SAFETY: The region is valid memory, by the contract of `new`
+        unsafe {
+            while word < self.top && word.read_volatile() == PAINT {
+                word = word.add(1);
+            }
+        }
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn high_water_mark() {
+        let mut ram = [0u32; 32];
+        let range = ram.as_mut_ptr_range();
+        let stack = unsafe { StackRegion::new(range.start, range.end) };
+        assert_eq!(stack.size(), 128);
+        unsafe { stack.paint_below(range.start.add(24)) };
+        // The 8 unpainted words, then 2 more used by the stack
+        unsafe {
+            range.start.add(22).write(0);
+            range.start.add(23).write(1);
+        }
+        assert_eq!(stack.high_water_mark(), 40);
+        assert_eq!(stack.free(), 88);
+    }
+}
@@ -1,10 +1,20 @@
 //! Analogue-to-Digital Conversion
+use crate::bus_clock::{self, BusClock};
+use crate::calibration;
 use crate::clock::GenericClockController;
 #[allow(deprecated)]
 use crate::gpio::v1;
 use crate::gpio::v2::*;
 use crate::hal::adc::{Channel, OneShot};
-use crate::pac::{adc, ADC, PM};
+use crate::pac::{adc, ADC, PM, SYSCTRL};
+
+#[cfg(feature = "dma")]
+use crate::dmac::{
+    self,
+    channel::{AnyChannel, Busy, InterruptFlags, Ready},
+    transfer::BufferPair,
+    Buffer, ChId, LinkedDescriptor, LinkedTransfer, Transfer, TriggerAction, TriggerSource,
+};
 
 /// Samples per reading
 pub use adc::avgctrl::SAMPLENUM_A as SampleRate;
@@ -20,6 +30,145 @@ pub use adc::inputctrl::GAIN_A as Gain;
 /// Reference voltage (or its source)
 pub use adc::refctrl::REFSEL_A as Reference;
 
+/// How conversions are accumulated into a result
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Accumulation {
+    /// A single conversion per result, at the given resolution
+    Single(Resolution),
+    /// Average the given number of conversions into a 12-bit result
+    Average(SampleRate),
+    /// Oversample and decimate conversions, increasing the effective
+    /// resolution
+    Oversample(Oversampling),
+}
+
+/// Effective resolution of oversampled results
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Oversampling {
+    /// 13-bit results, from 4 conversions
+    Bits13,
+    /// 14-bit results, from 16 conversions
+    Bits14,
+    /// 15-bit results, from 64 conversions
+    Bits15,
+    /// 16-bit results, from 256 conversions
+    Bits16,
+}
+
+impl Accumulation {
+    /// Resolution, number of accumulated samples and result adjustment
+    fn settings(self) -> (Resolution, SampleRate, u8) {
+        match self {
+            Accumulation::Single(resolution) => (resolution, SampleRate::_1, 0),
+            Accumulation::Average(samples) => {
+                // Table 32-3 (32.6.7) specifies the adjres values necessary for each
+                // SAMPLENUM value.
+                let adjres = match samples {
+                    SampleRate::_1 => 0,
+                    SampleRate::_2 => 1,
+                    SampleRate::_4 => 2,
+                    SampleRate::_8 => 3,
+                    _ => 4,
+                };
+                (Resolution::_16BIT, samples, adjres)
+            }
+            Accumulation::Oversample(oversampling) => {
+                let (samples, adjres) = match oversampling {
+                    Oversampling::Bits13 => (SampleRate::_4, 1),
+                    Oversampling::Bits14 => (SampleRate::_16, 2),
+                    Oversampling::Bits15 => (SampleRate::_64, 1),
+                    Oversampling::Bits16 => (SampleRate::_256, 0),
+                };
+                (Resolution::_16BIT, samples, adjres)
+            }
+        }
+    }
+}
+
+/// ADC configuration, applied with [`Adc::configure`]
+///
+/// The gain is applied to single-ended and differential inputs alike.
+///
+/// ```no_run
+/// use atsamd_hal::adc::{Accumulation, AdcConfig, Oversampling, Prescaler, Reference};
+///
+/// let config = AdcConfig::new()
+///     .prescaler(Prescaler::DIV16)
+///     .sample_length(10)
+///     .accumulation(Accumulation::Oversample(Oversampling::Bits16))
+///     .reference(Reference::AREFA);
+/// adc.configure(config);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct AdcConfig {
+    prescaler: Prescaler,
+    sample_length: u8,
+    accumulation: Accumulation,
+    reference: Reference,
+    gain: Gain,
+}
+
+impl AdcConfig {
+    /// Create the configuration set by the constructor of [`Adc`]
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            prescaler: Prescaler::DIV32,
+            sample_length: 5,
+            accumulation: Accumulation::Single(Resolution::_12BIT),
+            reference: Reference::INTVCC1,
+            gain: Gain::DIV2,
+        }
+    }
+
+    /// Set the prescaler of the ADC clock
+    #[inline]
+    pub fn prescaler(mut self, prescaler: Prescaler) -> Self {
+        self.prescaler = prescaler;
+        self
+    }
+
+    /// Set the sampling time, in half ADC clock cycles, minus one
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample_length` is greater than 63.
+    #[inline]
+    pub fn sample_length(mut self, sample_length: u8) -> Self {
+        assert!(sample_length < 64);
+        self.sample_length = sample_length;
+        self
+    }
+
+    /// Set how conversions are accumulated into a result
+    #[inline]
+    pub fn accumulation(mut self, accumulation: Accumulation) -> Self {
+        self.accumulation = accumulation;
+        self
+    }
+
+    /// Set the voltage reference
+    #[inline]
+    pub fn reference(mut self, reference: Reference) -> Self {
+        self.reference = reference;
+        self
+    }
+
+    /// Set the gain of the input stage
+    #[inline]
+    pub fn gain(mut self, gain: Gain) -> Self {
+        self.gain = gain;
+        self
+    }
+}
+
+impl Default for AdcConfig {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// `Adc` encapsulates the device ADC
 pub struct Adc<ADC> {
     adc: ADC,
@@ -33,7 +182,7 @@ impl Adc<ADC> {
     /// * 1/2 gain
     /// * 1/2 VDDANA reference voltage
     pub fn adc(adc: ADC, pm: &mut PM, clocks: &mut GenericClockController) -> Self {
-        pm.apbcmask.modify(|_, w| w.adc_().set_bit());
+        ADC::enable_bus_clock(pm);
 
         // set to 1 / (1 / (48000000 / 32) * 6) = 250000 SPS
         let gclk0 = clocks.gclk0();
@@ -111,6 +260,80 @@ impl Adc<ADC> {
         while self.adc.status.read().syncbusy().bit_is_set() {}
     }
 
+    /// Keep converting while the device is in standby, for conversions
+    /// triggered by events or free-running conversions waking the device
+    pub fn set_run_in_standby(&mut self, enabled: bool) {
+        self.adc.ctrla.modify(|_, w| w.runstdby().bit(enabled));
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+    }
+
+    /// Apply `config`, replacing the prescaler, sample length, accumulation,
+    /// reference and gain
+    pub fn configure(&mut self, config: AdcConfig) {
+        let (resolution, samples, adjres) = config.accumulation.settings();
+        self.adc.ctrlb.modify(|_, w| {
+            w.prescaler().variant(config.prescaler);
+            w.ressel().variant(resolution)
+        });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        // SAFETY: The sample length is checked when building the configuration
+        self.adc
+            .sampctrl
+            .write(|w| unsafe { w.samplen().bits(config.sample_length) });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.avgctrl.write(|w| {
+            w.samplenum().variant(samples);
+            // SAFETY: The adjustment matches the number of samples
+            unsafe { w.adjres().bits(adjres) }
+        });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.reference(config.reference);
+        self.gain(config.gain);
+    }
+
+    /// Reset the ADC, disable its bus clock and release it
+    ///
+    /// The pins converted by the ADC are borrowed by each conversion, so
+    /// their ownership is not affected.
+    pub fn free(self) -> ADC {
+        self.adc.ctrla.write(|w| w.swrst().set_bit());
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        bus_clock::release::<ADC>();
+        self.adc
+    }
+
+    /// Convert the voltage difference between `positive` and `negative`
+    ///
+    /// The result is signed, and uses the configured resolution.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `negative` is not one of the ADC inputs 0 to 7, which are the
+    /// only ones available as a negative input.
+    pub fn read_differential<P, N>(&mut self, _positive: &mut P, _negative: &mut N) -> i16
+    where
+        P: Channel<ADC, ID = u8>,
+        N: Channel<ADC, ID = u8>,
+    {
+        let (positive, negative) = (P::channel(), N::channel());
+        assert!(negative < 8);
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.inputctrl.modify(|_, w| unsafe {
+            w.muxpos().bits(positive);
+            w.muxneg().bits(negative)
+        });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.ctrlb.modify(|_, w| w.diffmode().set_bit());
+        self.power_up();
+        let result = self.convert();
+        self.power_down();
+        self.adc.ctrlb.modify(|_, w| w.diffmode().clear_bit());
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.inputctrl.modify(|_, w| w.muxneg().gnd());
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        result as i16
+    }
+
     fn power_up(&mut self) {
         while self.adc.status.read().syncbusy().bit_is_set() {}
         self.adc.ctrla.modify(|_, w| w.enable().set_bit());
@@ -141,6 +364,46 @@ impl Adc<ADC> {
     }
 }
 
+impl Adc<ADC> {
+    /// Read the internal temperature sensor, in hundredths of a degree
+    /// Celsius
+    ///
+    /// The temperature sensor is enabled, and the conversion is corrected
+    /// with the factory calibration values. The ADC is temporarily switched
+    /// to the configuration used during calibration: 12-bit resolution, 1V
+    /// internal reference and a gain of 1.
+    pub fn read_temperature(&mut self, sysctrl: &mut SYSCTRL) -> i32 {
+        sysctrl.vref.modify(|_, w| w.tsen().set_bit());
+
+        let ctrlb = self.adc.ctrlb.read().bits();
+        let refctrl = self.adc.refctrl.read().bits();
+        let inputctrl = self.adc.inputctrl.read().bits();
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.ctrlb.modify(|_, w| w.ressel()._12bit());
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.refctrl.modify(|_, w| w.refsel().int1v());
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.inputctrl.modify(|_, w| {
+            w.muxpos().temp();
+            w.gain()._1x()
+        });
+
+        self.power_up();
+        let result = self.convert();
+        self.power_down();
+
+        // SAFETY: The registers are restored to their previous values
+        self.adc.ctrlb.write(|w| unsafe { w.bits(ctrlb) });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.refctrl.write(|w| unsafe { w.bits(refctrl) });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.inputctrl.write(|w| unsafe { w.bits(inputctrl) });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+
+        calibration::temperature_cal().temperature(result)
+    }
+}
+
 impl<WORD, PIN> OneShot<ADC, WORD, PIN> for Adc<ADC>
 where
     WORD: From<u16>,
@@ -163,6 +426,271 @@ where
     }
 }
 
+//=============================================================================
+// DMA streaming
+//=============================================================================
+
+/// Result register of the ADC, used as the source of DMA transfers
+#[cfg(feature = "dma")]
+#[doc(hidden)]
+pub struct AdcResult<A>(core::marker::PhantomData<A>);
+
+#[cfg(feature = "dma")]
+unsafe impl Buffer for AdcResult<ADC> {
+    type Beat = u16;
+
+    #[inline]
+    fn dma_ptr(&mut self) -> *mut u16 {
+        // SAFETY: The result register is only read by the DMAC
+        unsafe { (*ADC::ptr()).result.as_ptr() }
+    }
+
+    #[inline]
+    fn incrementing(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn buffer_len(&self) -> usize {
+        1
+    }
+}
+
+/// Free-running conversions streamed by the DMAC into a ring of buffer
+/// segments
+///
+/// The DMAC fills the segments in order and loops back to the first one
+/// after the last one. The channel interrupt is raised every time a segment
+/// has been filled, see [`AdcStream::segment_complete`]. With two segments,
+/// this is a double buffer signalling every half.
+///
+/// A filled segment must be processed before the DMAC loops back to it.
+#[cfg(feature = "dma")]
+pub struct AdcStream<A, Id>
+where
+    Id: ChId,
+    AdcResult<A>: Buffer<Beat = u16>,
+{
+    adc: Adc<A>,
+    transfer: LinkedTransfer<dmac::Channel<Id, Busy>, &'static mut [u16], AdcResult<A>>,
+    next: usize,
+}
+
+#[cfg(feature = "dma")]
+impl Adc<ADC> {
+    /// Start converting `pin` in free-running mode, and stream the results
+    /// into `segments` with the DMAC
+    ///
+    /// `descriptors` must hold at least one descriptor per segment after the
+    /// first, see the [`linked`](crate::dmac::linked) module. The sample rate
+    /// depends on the prescaler, the sample length and the averaging.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer than two segments, or not enough
+    /// descriptors.
+    pub fn stream_with_dma<PIN, Ch>(
+        mut self,
+        _pin: &mut PIN,
+        mut channel: Ch,
+        segments: &'static mut [&'static mut [u16]],
+        descriptors: &'static mut [LinkedDescriptor],
+    ) -> AdcStream<ADC, Ch::Id>
+    where
+        PIN: Channel<ADC, ID = u8>,
+        Ch: AnyChannel<Status = Ready>,
+    {
+        assert!(segments.len() >= 2);
+        channel
+            .as_mut()
+            .enable_interrupts(InterruptFlags::new().with_tcmpl(true));
+        let transfer = LinkedTransfer::new_scatter(
+            channel,
+            AdcResult(core::marker::PhantomData),
+            segments,
+            descriptors,
+            true,
+        )
+        .expect("not enough descriptors for the segments")
+        .with_block_interrupts()
+        .begin(TriggerSource::ADC_RESRDY, TriggerAction::BEAT);
+
+        let chan = PIN::channel();
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc
+            .inputctrl
+            .modify(|_, w| unsafe { w.muxpos().bits(chan) });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.ctrlb.modify(|_, w| w.freerun().set_bit());
+        self.power_up();
+        self.adc.swtrig.modify(|_, w| w.start().set_bit());
+
+        AdcStream {
+            adc: self,
+            transfer,
+            next: 0,
+        }
+    }
+}
+
+#[cfg(feature = "dma")]
+impl<Id: ChId> AdcStream<ADC, Id> {
+    /// Clear the segment complete flag, and return the index of the oldest
+    /// filled segment not returned yet
+    ///
+    /// This is normally called from the DMAC interrupt handler, until it
+    /// returns `None`. The filled segments are found from the segment the
+    /// DMAC is filling, so that none is missed when the handler runs after
+    /// more than one segment was filled.
+    pub fn segment_complete(&mut self) -> Option<usize> {
+        self.transfer.block_transfer_interrupt();
+        if self.next == self.transfer.active_segment() {
+            return None;
+        }
+        let index = self.next;
+        self.next = (index + 1) % self.transfer.segments().len();
+        Some(index)
+    }
+
+    /// Copy the samples of segment `index` into `samples`, and return how
+    /// many were copied
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of range.
+    pub fn read_segment(&self, index: usize, samples: &mut [u16]) -> usize {
+        core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+        let segment = &self.transfer.segments()[index];
+        let count = segment.len().min(samples.len());
+        for (i, sample) in samples[..count].iter_mut().enumerate() {
+            // SAFETY: The index is within the segment
+            *sample = unsafe { core::ptr::read_volatile(segment.as_ptr().add(i)) };
+        }
+        count
+    }
+
+    /// Stop converting and streaming, and return the [`Adc`], the DMA
+    /// channel, the segments and the descriptors
+    #[allow(clippy::type_complexity)]
+    pub fn stop(
+        self,
+    ) -> (
+        Adc<ADC>,
+        dmac::Channel<Id, Ready>,
+        &'static mut [&'static mut [u16]],
+        &'static mut [LinkedDescriptor],
+    ) {
+        let mut adc = self.adc;
+        adc.power_down();
+        adc.adc.ctrlb.modify(|_, w| w.freerun().clear_bit());
+        while adc.adc.status.read().syncbusy().bit_is_set() {}
+        let (channel, segments, _, descriptors) = self.transfer.stop();
+        (adc, channel, segments, descriptors)
+    }
+}
+
+/// Hardware scan of consecutive inputs, with the results transferred by the
+/// DMAC
+///
+/// The ADC converts the inputs one after the other in free-running mode, and
+/// the DMAC reads the results. The CPU is not involved until every input has
+/// been converted.
+#[cfg(feature = "dma")]
+pub struct AdcScan<A, Id, B>
+where
+    Id: ChId,
+    AdcResult<A>: Buffer<Beat = u16>,
+    B: Buffer<Beat = u16> + 'static,
+{
+    adc: Adc<A>,
+    transfer: Transfer<dmac::Channel<Id, Busy>, BufferPair<AdcResult<A>, B>>,
+}
+
+#[cfg(feature = "dma")]
+impl Adc<ADC> {
+    /// Convert every input of `channels`, in order, and transfer the results
+    /// into `results`
+    ///
+    /// `channels` holds the ADC channel numbers, as returned by
+    /// [`Channel::channel`]; the pins must already be in alternate function
+    /// B. The ADC can only scan consecutive inputs, so every channel number
+    /// must follow the previous one. `results` must be as long as
+    /// `channels`. Use [`AdcScan::wait`] to wait for the results.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channels` is empty or not consecutive, or if `results`
+    /// doesn't have the same length as `channels`.
+    pub fn scan_with_dma<Ch, B>(
+        mut self,
+        channels: &[u8],
+        results: B,
+        channel: Ch,
+    ) -> AdcScan<ADC, Ch::Id, B>
+    where
+        Ch: AnyChannel<Status = Ready>,
+        B: Buffer<Beat = u16> + 'static,
+    {
+        assert!(!channels.is_empty() && channels.len() <= 16);
+        assert!(channels.windows(2).all(|pair| pair[1] == pair[0] + 1));
+        assert_eq!(results.buffer_len(), channels.len());
+
+        let transfer = Transfer::new(
+            channel,
+            AdcResult(core::marker::PhantomData),
+            results,
+            false,
+        )
+        .expect("the result register accepts any buffer length")
+        .begin(TriggerSource::ADC_RESRDY, TriggerAction::BEAT);
+
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.inputctrl.modify(|_, w| unsafe {
+            w.muxpos().bits(channels[0]);
+            w.inputscan().bits(channels.len() as u8 - 1);
+            w.inputoffset().bits(0)
+        });
+        while self.adc.status.read().syncbusy().bit_is_set() {}
+        self.adc.ctrlb.modify(|_, w| w.freerun().set_bit());
+        self.power_up();
+        self.adc.swtrig.modify(|_, w| w.start().set_bit());
+
+        AdcScan {
+            adc: self,
+            transfer,
+        }
+    }
+}
+
+#[cfg(feature = "dma")]
+impl<Id, B> AdcScan<ADC, Id, B>
+where
+    Id: ChId,
+    B: Buffer<Beat = u16> + 'static,
+{
+    /// Check whether every input has been converted
+    #[inline]
+    pub fn complete(&mut self) -> bool {
+        self.transfer.complete()
+    }
+
+    /// Wait for every input to be converted, and return the [`Adc`], the DMA
+    /// channel and the results
+    pub fn wait(self) -> (Adc<ADC>, dmac::Channel<Id, Ready>, B) {
+        let (channel, _, results) = self.transfer.wait();
+        let mut adc = self.adc;
+        adc.power_down();
+        adc.adc.ctrlb.modify(|_, w| w.freerun().clear_bit());
+        while adc.adc.status.read().syncbusy().bit_is_set() {}
+        adc.adc.inputctrl.modify(|_, w| unsafe {
+            w.inputscan().bits(0);
+            w.inputoffset().bits(0)
+        });
+        while adc.adc.status.read().syncbusy().bit_is_set() {}
+        (adc, channel, results)
+    }
+}
+
 macro_rules! adc_pins {
     (
         $(
@@ -0,0 +1,459 @@
+//! # Bootloader building blocks
+//!
+//! A bootloader occupies the start of the flash, protected by the `BOOTPROT`
+//! fuse, and starts the application stored after it. This module provides
+//! the pieces needed to write one:
+//!
+//! - [`Application`] checks the vector table of the application, then jumps
+//!   to it.
+//! - [`bootprot_size`] and [`bootprot_for_size`] convert between the
+//!   `BOOTPROT` fuse, read and programmed through
+//!   [`Nvm::bootprot`](crate::nvm::Nvm::bootprot) and
+//!   [`Nvm::set_bootprot`](crate::nvm::Nvm::set_bootprot), and the size of
+//!   the protected area.
+//! - [`FlashWriter`] programs a received image into the application region,
+//!   erasing the rows as it reaches them.
+//! - [`Uf2Block`] parses the blocks of a UF2 file.
+//!
+//! ```no_run
+//! use atsamd_hal::bootloader::{Application, FlashWriter, Uf2Block, UF2_FAMILY_ID};
+//! use atsamd_hal::nvm::Nvm;
+//!
+//! const APP_START: u32 = 0x2000;
+//!
+//! let app = Application::new(APP_START);
+//! if !update_requested && app.is_valid() {
+//!     unsafe { app.jump() };
+//! }
+//!
+//! let mut nvm = Nvm::new(peripherals.NVMCTRL);
+//! let mut writer = FlashWriter::new(&mut nvm, APP_START..0x4_0000);
+//! // For each block of 512 bytes received
+//! if let Some(block) = Uf2Block::parse(&buf) {
+//!     if block.is_for_family(UF2_FAMILY_ID) {
+//!         unsafe { writer.write(block.target_address, block.payload) }.unwrap();
+//!     }
+//! }
+//! ```
+
+use core::ops::Range;
+
+use crate::nvm::{self, retrieve_flash_size, Nvm, ROWSIZE};
+
+/// Start address of the SRAM
+pub const RAM_START: u32 = 0x2000_0000;
+
+/// Size of the largest SRAM of the family
+pub const RAM_MAX_SIZE: u32 = 32 * 1024;
+
+/// Alignment of a vector table, required by `VTOR`
+pub const VECTOR_TABLE_ALIGN: u32 = 256;
+
+/// UF2 family ID of the SAMD21
+pub const UF2_FAMILY_ID: u32 = 0x68ed_2b88;
+
+/// Convert a `BOOTPROT` fuse value to the size of the protected area
+///
+/// * 7 = no boot protection
+/// * 512 bytes << (6 - value) = protected size
+#[inline]
+pub fn bootprot_size(bootprot: u8) -> u32 {
+    match bootprot & 0x7 {
+        7 => 0,
+        value => 512 << (6 - value),
+    }
+}
+
+/// Find the `BOOTPROT` fuse value protecting the smallest area of at least
+/// `size` bytes
+///
+/// Returns `None` if `size` exceeds the largest protected area, 32KiB.
+#[inline]
+pub fn bootprot_for_size(size: u32) -> Option<u8> {
+    (0..=7).rev().find(|&value| bootprot_size(value) >= size)
+}
+
+/// Application image in flash, starting with its vector table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Application {
+    address: u32,
+}
+
+impl Application {
+    /// Refer to the application whose vector table is at `address`
+    #[inline]
+    pub const fn new(address: u32) -> Self {
+        Self { address }
+    }
+
+    /// Return the address of the vector table
+    #[inline]
+    pub fn address(&self) -> u32 {
+        self.address
+    }
+
+    /// Read the initial stack pointer, the first word of the vector table
+    #[inline]
+    pub fn stack_pointer(&self) -> u32 {
+        // SAFETY: The whole flash is readable
+        unsafe { core::ptr::read_volatile(self.address as *const u32) }
+    }
+
+    /// Read the reset vector, the second word of the vector table
+    #[inline]
+    pub fn reset_vector(&self) -> u32 {
+        // SAFETY: The whole flash is readable
+        unsafe { core::ptr::read_volatile((self.address as *const u32).add(1)) }
+    }
+
+    /// Check that the vector table looks like the one of an application
+    ///
+    /// The vector table must be aligned for `VTOR`, its stack pointer must
+    /// point into the SRAM, and its reset vector to Thumb code in the flash
+    /// after the vector table. Erased flash fails these checks.
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        let flash_end = retrieve_flash_size();
+        if self.address % VECTOR_TABLE_ALIGN != 0 || self.address >= flash_end {
+            return false;
+        }
+        is_valid_vector_table(
+            self.address,
+            flash_end,
+            self.stack_pointer(),
+            self.reset_vector(),
+        )
+    }
+
+    /// Start the application
+    ///
+    /// The interrupts are disabled and their pending flags cleared, SysTick
+    /// stopped, and `VTOR` pointed at the vector table of the application,
+    /// before loading its stack pointer and jumping to its reset vector.
+    ///
+    /// # Safety
+    ///
+    /// The application must be valid, see [`is_valid`](Self::is_valid). The
+    /// peripherals used by the bootloader should be returned to their reset
+    /// state beforehand, as the application expects.
+    #[inline]
+    pub unsafe fn jump(&self) -> ! {
+        let nvic = &*cortex_m::peripheral::NVIC::PTR;
+        nvic.icer[0].write(u32::MAX);
+        nvic.icpr[0].write(u32::MAX);
+        let syst = &*cortex_m::peripheral::SYST::PTR;
+        syst.csr.write(0);
+        let scb = &*cortex_m::peripheral::SCB::PTR;
+        scb.vtor.write(self.address);
+        cortex_m::asm::dsb();
+        cortex_m::asm::isb();
+        cortex_m::asm::bootload(self.address as *const u32)
+    }
+}
+
+/// Check the initial stack pointer `sp` and reset vector `reset` read from
+/// the vector table at `address`
+fn is_valid_vector_table(address: u32, flash_end: u32, sp: u32, reset: u32) -> bool {
+    let sp_valid = sp % 4 == 0 && sp > RAM_START && sp <= RAM_START + RAM_MAX_SIZE;
+    let reset_address = reset & !1;
+    let reset_valid = reset & 1 == 1 && reset_address > address && reset_address < flash_end;
+    sp_valid && reset_valid
+}
+
+/// Shrink `region` to the whole units of `size` bytes it contains
+fn whole_units(region: Range<u32>, size: u32) -> Range<u32> {
+    let start = region.start.saturating_add(size - 1) / size * size;
+    let end = region.end - region.end % size;
+    start..end.max(start)
+}
+
+/// Errors of a [`FlashWriter`]
+#[derive(Debug)]
+pub enum Error {
+    /// The data falls outside of the application region
+    OutOfRegion,
+    /// The address or the length of the data is not a multiple of 4
+    Alignment,
+    /// Erasing or writing the flash failed
+    Nvm(nvm::Error),
+}
+
+impl From<nvm::Error> for Error {
+    #[inline]
+    fn from(error: nvm::Error) -> Self {
+        Error::Nvm(error)
+    }
+}
+
+/// Programmer of an image received in chunks, such as UF2 blocks
+///
+/// Each row of the application region is erased when first written. Chunks
+/// must be written in increasing address order, or the whole region erased
+/// first with [`erase_all`](Self::erase_all), since writing a row erased
+/// earlier does not erase it again.
+pub struct FlashWriter<'a> {
+    nvm: &'a mut Nvm,
+    region: Range<u32>,
+    erased_until: u32,
+}
+
+impl<'a> FlashWriter<'a> {
+    /// Program images into the `region` of the flash
+    ///
+    /// `region` is shrunk to the whole rows it contains, rounding its start
+    /// up and its end down, so that the writer never erases outside of it.
+    #[inline]
+    pub fn new(nvm: &'a mut Nvm, region: Range<u32>) -> Self {
+        let region = whole_units(region, ROWSIZE);
+        Self {
+            nvm,
+            erased_until: region.start,
+            region,
+        }
+    }
+
+    /// Return the application region
+    #[inline]
+    pub fn region(&self) -> Range<u32> {
+        self.region.clone()
+    }
+
+    /// Erase the whole application region
+    ///
+    /// # Safety
+    /// The application region must not contain code or data that is in use.
+    #[inline]
+    pub unsafe fn erase_all(&mut self) -> Result<(), Error> {
+        let rows = (self.region.end - self.erased_until) / ROWSIZE;
+        if rows > 0 {
+            self.nvm.erase(self.erased_until, rows)?;
+        }
+        self.erased_until = self.region.end;
+        Ok(())
+    }
+
+    /// Write `data` at `address`, erasing the rows reached for the first
+    /// time
+    ///
+    /// # Safety
+    /// The application region must not contain code or data that is in use.
+    pub unsafe fn write(&mut self, address: u32, data: &[u8]) -> Result<(), Error> {
+        if address % 4 != 0 || data.len() % 4 != 0 {
+            return Err(Error::Alignment);
+        }
+        let end = u32::try_from(data.len())
+            .ok()
+            .and_then(|len| address.checked_add(len))
+            .ok_or(Error::OutOfRegion)?;
+        if address < self.region.start || end > self.region.end {
+            return Err(Error::OutOfRegion);
+        }
+
+        if end > self.erased_until {
+            let start = self.erased_until.max(address - address % ROWSIZE);
+            let rows = (end - start + ROWSIZE - 1) / ROWSIZE;
+            self.nvm.erase(start, rows)?;
+            self.erased_until = start + rows * ROWSIZE;
+        }
+
+        let mut words = [0_u32; (ROWSIZE / 4) as usize];
+        let mut address = address;
+        for chunk in data.chunks(ROWSIZE as usize) {
+            let words = &mut words[..chunk.len() / 4];
+            for (word, bytes) in words.iter_mut().zip(chunk.chunks_exact(4)) {
+                *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            }
+            self.nvm.write_from_slice(address, words)?;
+            address += chunk.len() as u32;
+        }
+        Ok(())
+    }
+}
+
+/// First magic number of a UF2 block
+const UF2_MAGIC_START0: u32 = 0x0a32_4655;
+/// Second magic number of a UF2 block
+const UF2_MAGIC_START1: u32 = 0x9e5d_5157;
+/// Final magic number of a UF2 block
+const UF2_MAGIC_END: u32 = 0x0ab1_6f30;
+/// Size of a UF2 block
+pub const UF2_BLOCK_SIZE: usize = 512;
+/// Maximum payload of a UF2 block
+const UF2_MAX_PAYLOAD: usize = 476;
+
+/// The block is not meant for the main flash
+pub const UF2_FLAG_NOT_MAIN_FLASH: u32 = 0x0000_0001;
+/// The block is part of a file container
+pub const UF2_FLAG_FILE_CONTAINER: u32 = 0x0000_1000;
+/// The block holds a family ID
+pub const UF2_FLAG_FAMILY_ID: u32 = 0x0000_2000;
+
+/// Block of a UF2 file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Uf2Block<'a> {
+    /// Flags of the block
+    pub flags: u32,
+    /// Flash address of the payload
+    pub target_address: u32,
+    /// Data to program
+    pub payload: &'a [u8],
+    /// Index of the block in the file
+    pub block_number: u32,
+    /// Number of blocks in the file
+    pub block_count: u32,
+    /// Family ID, or file size for file containers
+    pub family_id: u32,
+}
+
+impl<'a> Uf2Block<'a> {
+    /// Parse a block of [`UF2_BLOCK_SIZE`] bytes
+    ///
+    /// Returns `None` if `block` is shorter, its magic numbers are wrong or
+    /// its payload too long.
+    pub fn parse(block: &'a [u8]) -> Option<Self> {
+        if block.len() < UF2_BLOCK_SIZE {
+            return None;
+        }
+        let word = |index: usize| {
+            let bytes = &block[index * 4..index * 4 + 4];
+            u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+        };
+        if word(0) != UF2_MAGIC_START0
+            || word(1) != UF2_MAGIC_START1
+            || word(UF2_BLOCK_SIZE / 4 - 1) != UF2_MAGIC_END
+        {
+            return None;
+        }
+        let payload_size = word(4) as usize;
+        if payload_size > UF2_MAX_PAYLOAD {
+            return None;
+        }
+        Some(Self {
+            flags: word(2),
+            target_address: word(3),
+            payload: &block[32..32 + payload_size],
+            block_number: word(5),
+            block_count: word(6),
+            family_id: word(7),
+        })
+    }
+
+    /// Return whether the payload is meant to be programmed into the flash
+    /// of the family `family_id`
+    ///
+    /// Blocks without a family ID are accepted by any family.
+    #[inline]
+    pub fn is_for_family(&self, family_id: u32) -> bool {
+        self.flags & (UF2_FLAG_NOT_MAIN_FLASH | UF2_FLAG_FILE_CONTAINER) == 0
+            && (self.flags & UF2_FLAG_FAMILY_ID == 0 || self.family_id == family_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bootprot_sizes() {
+        assert_eq!(bootprot_size(7), 0);
+        assert_eq!(bootprot_size(6), 512);
+        assert_eq!(bootprot_size(2), 8 * 1024);
+        assert_eq!(bootprot_size(0), 32 * 1024);
+        assert_eq!(bootprot_for_size(0), Some(7));
+        assert_eq!(bootprot_for_size(8 * 1024), Some(2));
+        assert_eq!(bootprot_for_size(8 * 1024 + 1), Some(1));
+        assert_eq!(bootprot_for_size(64 * 1024), None);
+    }
+
+    #[test]
+    fn flash_writer_region() {
+        assert_eq!(whole_units(0x2000..0x4_0000, ROWSIZE), 0x2000..0x4_0000);
+        assert_eq!(whole_units(0x2001..0x3_ffff, ROWSIZE), 0x2100..0x3_ff00);
+        assert_eq!(whole_units(0x2001..0x2100, ROWSIZE), 0x2100..0x2100);
+        assert_eq!(whole_units(0x2001..0x2002, ROWSIZE), 0x2100..0x2100);
+    }
+
+    #[test]
+    fn vector_tables() {
+        let flash_end = 0x4_0000;
+        assert!(is_valid_vector_table(
+            0x2000,
+            flash_end,
+            0x2000_8000,
+            0x20c1
+        ));
+        // Erased flash
+        assert!(!is_valid_vector_table(
+            0x2000,
+            flash_end,
+            u32::MAX,
+            u32::MAX
+        ));
+        // ARM reset vector
+        assert!(!is_valid_vector_table(
+            0x2000,
+            flash_end,
+            0x2000_8000,
+            0x20c0
+        ));
+        // Reset vector inside the bootloader
+        assert!(!is_valid_vector_table(
+            0x2000,
+            flash_end,
+            0x2000_8000,
+            0x01c1
+        ));
+        // Stack pointer outside of the SRAM
+        assert!(!is_valid_vector_table(
+            0x2000,
+            flash_end,
+            0x2001_0000,
+            0x20c1
+        ));
+    }
+
+    fn uf2_block(flags: u32, family_id: u32) -> [u8; UF2_BLOCK_SIZE] {
+        let mut block = [0; UF2_BLOCK_SIZE];
+        let words = [
+            UF2_MAGIC_START0,
+            UF2_MAGIC_START1,
+            flags,
+            0x2000,
+            256,
+            1,
+            2,
+            family_id,
+        ];
+        for (bytes, word) in block.chunks_exact_mut(4).zip(words.iter()) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+        block[32] = 0xab;
+        block[UF2_BLOCK_SIZE - 4..].copy_from_slice(&UF2_MAGIC_END.to_le_bytes());
+        block
+    }
+
+    #[test]
+    fn uf2_blocks() {
+        let block = uf2_block(UF2_FLAG_FAMILY_ID, UF2_FAMILY_ID);
+        let parsed = Uf2Block::parse(&block).unwrap();
+        assert_eq!(parsed.target_address, 0x2000);
+        assert_eq!(parsed.payload.len(), 256);
+        assert_eq!(parsed.payload[0], 0xab);
+        assert_eq!((parsed.block_number, parsed.block_count), (1, 2));
+        assert!(parsed.is_for_family(UF2_FAMILY_ID));
+
+        let block = uf2_block(UF2_FLAG_FAMILY_ID, 0x1234_5678);
+        assert!(!Uf2Block::parse(&block)
+            .unwrap()
+            .is_for_family(UF2_FAMILY_ID));
+        let block = uf2_block(0, 0);
+        assert!(Uf2Block::parse(&block)
+            .unwrap()
+            .is_for_family(UF2_FAMILY_ID));
+
+        let mut block = uf2_block(0, 0);
+        block[0] = 0;
+        assert_eq!(Uf2Block::parse(&block), None);
+        assert_eq!(Uf2Block::parse(&block[..256]), None);
+    }
+}
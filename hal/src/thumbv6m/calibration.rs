@@ -59,3 +59,89 @@ pub fn usb_trim_cal() -> u8 {
     #[cfg(feature = "samd21")]
     return cal_with_errata(4, 23, 7, 7, 3) as u8;
 }
+
+/// Factory calibration of the temperature sensor, read from the temperature
+/// log row
+///
+/// The calibration values were measured with the ADC in 12-bit mode, using
+/// the 1V internal reference and a gain of 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TemperatureCalibration {
+    /// Room calibration temperature, in hundredths of a degree Celsius
+    pub room_temperature: i32,
+    /// Hot calibration temperature, in hundredths of a degree Celsius
+    pub hot_temperature: i32,
+    /// Actual voltage of the 1V internal reference at the room temperature,
+    /// in microvolts
+    pub room_int1v: i32,
+    /// Actual voltage of the 1V internal reference at the hot temperature, in
+    /// microvolts
+    pub hot_int1v: i32,
+    /// Temperature sensor conversion at the room temperature
+    pub room_adc: u16,
+    /// Temperature sensor conversion at the hot temperature
+    pub hot_adc: u16,
+}
+
+impl TemperatureCalibration {
+    /// Compute the temperature, in hundredths of a degree Celsius, from the
+    /// temperature sensor conversion
+    ///
+    /// The voltage of the 1V internal reference drifts with the temperature,
+    /// so a coarse temperature is computed first, and then refined using the
+    /// interpolated reference voltage. See 37.11.8.2 Device Temperature
+    /// Measurement in the SAMD21 datasheet.
+    pub fn temperature(&self, adc: u16) -> i32 {
+        let t_room = i64::from(self.room_temperature);
+        let t_hot = i64::from(self.hot_temperature);
+        let int1v_room = i64::from(self.room_int1v);
+        let int1v_hot = i64::from(self.hot_int1v);
+        // Voltages, scaled by 4095 / 1µV
+        let v_room = i64::from(self.room_adc) * int1v_room;
+        let v_hot = i64::from(self.hot_adc) * int1v_hot;
+        let interpolate = |v: i64| t_room + (t_hot - t_room) * (v - v_room) / (v_hot - v_room);
+
+        let coarse = interpolate(i64::from(adc) * 1_000_000);
+        let int1v = int1v_room + (int1v_hot - int1v_room) * (coarse - t_room) / (t_hot - t_room);
+        interpolate(i64::from(adc) * int1v) as i32
+    }
+}
+
+/// Temperature sensor calibration values
+pub fn temperature_cal() -> TemperatureCalibration {
+    // The temperature log row is at address 0x00806030
+    let temperature = |int_shift, dec_shift| {
+        cal(0x10, int_shift, 0xff) as i32 * 100 + cal(0x10, dec_shift, 0xf) as i32 * 10
+    };
+    // The reference voltage is stored as a signed deviation from 1V, in
+    // millivolts
+    let int1v = |offset, shift| 1_000_000 - i32::from(cal(offset, shift, 0xff) as u8 as i8) * 1000;
+    TemperatureCalibration {
+        room_temperature: temperature(0, 8),
+        hot_temperature: temperature(12, 20),
+        room_int1v: int1v(0x10, 24),
+        hot_int1v: int1v(0x14, 0),
+        room_adc: cal(0x14, 8, 0xfff) as u16,
+        hot_adc: cal(0x14, 20, 0xfff) as u16,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TemperatureCalibration;
+
+    #[test]
+    fn temperature_corrects_reference_drift() {
+        // The 1V reference drops by 2mV between the calibration points
+        let cal = TemperatureCalibration {
+            room_temperature: 2500,
+            hot_temperature: 8500,
+            room_int1v: 1_000_000,
+            hot_int1v: 998_000,
+            room_adc: 2731,
+            hot_adc: 3328,
+        };
+        assert_eq!(cal.temperature(2731), 2500);
+        assert_eq!(cal.temperature(2980), 5005);
+    }
+}
@@ -3,6 +3,12 @@
 //! before you can set up most of the peripherals on the atsamd21 device.
 //! The other types in this module are used to enforce at compile time
 //! that the peripherals have been correctly configured.
+
+#[cfg(feature = "samd21")]
+pub mod gclk_io;
+pub mod osc32k;
+
+use crate::bus_clock::BusClock;
 use crate::pac::gclk::clkctrl::GEN_A::*;
 use crate::pac::gclk::clkctrl::ID_A::*;
 use crate::pac::gclk::genctrl::SRC_A::*;
@@ -137,7 +143,7 @@ impl GenericClockController {
         sysctrl: &mut SYSCTRL,
         nvmctrl: &mut NVMCTRL,
     ) -> Self {
-        Self::new_48mhz_from_32khz(gclk, pm, sysctrl, nvmctrl, false)
+        Self::new_48mhz_from_32khz(gclk, pm, sysctrl, nvmctrl, DfllReference::Osc32k)
     }
 
     /// Reset the clock controller, configure the system to run
@@ -148,7 +154,33 @@ impl GenericClockController {
         sysctrl: &mut SYSCTRL,
         nvmctrl: &mut NVMCTRL,
     ) -> Self {
-        Self::new_48mhz_from_32khz(gclk, pm, sysctrl, nvmctrl, true)
+        Self::new_48mhz_from_32khz(gclk, pm, sysctrl, nvmctrl, DfllReference::Xosc32k)
+    }
+
+    /// Reset the clock controller, configure the system to run
+    /// at 48Mhz with the DFLL48 locked to the USB start-of-frame,
+    /// and reset various clock dividers.
+    ///
+    /// This needs no 32khz crystal. Until the host sends start-of-frame
+    /// packets, every millisecond once the device is enumerated, the DFLL48
+    /// runs from its factory calibration, within a few percent of 48Mhz.
+    /// gclk1 runs from the internal 32khz oscillator.
+    ///
+    /// USB clock recovery is opt-in, as the other constructors don't enable
+    /// it on SAMD21 due to instability issues around USB bus resets: no
+    /// start-of-frame is sent during a bus reset or while the bus is
+    /// suspended, so the frequency may drift until the host resumes sending
+    /// them. Use it only where the USB connection is the reference clock,
+    /// and prefer [`with_external_32kosc`](Self::with_external_32kosc) when a
+    /// 32khz crystal is fitted.
+    #[cfg(feature = "usb")]
+    pub fn with_usb_clock_recovery(
+        gclk: GCLK,
+        pm: &mut PM,
+        sysctrl: &mut SYSCTRL,
+        nvmctrl: &mut NVMCTRL,
+    ) -> Self {
+        Self::new_48mhz_from_32khz(gclk, pm, sysctrl, nvmctrl, DfllReference::UsbSof)
     }
 
     fn new_48mhz_from_32khz(
@@ -156,8 +188,9 @@ impl GenericClockController {
         pm: &mut PM,
         sysctrl: &mut SYSCTRL,
         nvmctrl: &mut NVMCTRL,
-        use_external_crystal: bool,
+        reference: DfllReference,
     ) -> Self {
+        let use_external_crystal = reference == DfllReference::Xosc32k;
         let mut state = State { gclk };
 
         set_flash_to_half_auto_wait_state(nvmctrl);
@@ -182,7 +215,7 @@ impl GenericClockController {
         // Feed 32khz into the DFLL48
         state.enable_clock_generator(DFLL48, GCLK1);
         // Enable the DFLL48
-        configure_and_enable_dfll48m(sysctrl, use_external_crystal);
+        configure_and_enable_dfll48m(sysctrl, reference);
         // Feed DFLL48 into the main clock
         state.set_gclk_divider_and_source(GCLK0, 1, DFLL48M, true);
         // We are now running at 48Mhz
@@ -471,7 +504,7 @@ fn set_flash_manual_write(nvmctrl: &mut NVMCTRL) {
 }
 
 fn enable_gclk_apb(pm: &mut PM) {
-    pm.apbamask.modify(|_, w| w.gclk_().set_bit());
+    GCLK::enable_bus_clock(pm);
 }
 
 /// Turn on the internal 32hkz oscillator
@@ -517,8 +550,21 @@ fn wait_for_dfllrdy(sysctrl: &mut SYSCTRL) {
     while sysctrl.pclksr.read().dfllrdy().bit_is_clear() {}
 }
 
+/// Reference of the dfll48m in closed loop mode
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DfllReference {
+    /// The internal 32khz oscillator, through gclk1
+    Osc32k,
+    /// The external 32khz crystal, through gclk1
+    Xosc32k,
+    /// The 1Khz USB start-of-frame
+    #[cfg(feature = "usb")]
+    UsbSof,
+}
+
 /// Configure the dfll48m to operate at 48Mhz
-fn configure_and_enable_dfll48m(sysctrl: &mut SYSCTRL, use_external_crystal: bool) {
+fn configure_and_enable_dfll48m(sysctrl: &mut SYSCTRL, reference: DfllReference) {
+    let use_external_crystal = reference == DfllReference::Xosc32k;
     // Turn it off while we configure it.
     // Note that we need to turn off on-demand mode and
     // disable it here, rather than just reseting the ctrl
@@ -557,6 +603,41 @@ fn configure_and_enable_dfll48m(sysctrl: &mut SYSCTRL, use_external_crystal: boo
             w.fine().bits(fine)
         });
 
+        #[cfg(feature = "usb")]
+        if reference == DfllReference::UsbSof {
+            sysctrl.dfllmul.write(|w| unsafe {
+                w.cstep().bits(1);
+                w.fstep().bits(1);
+                // scaling factor for 1Khz SOF signal.
+                w.mul().bits((48_000_000u32 / 1000) as u16)
+            });
+
+            // Turn it on
+            sysctrl.dfllctrl.write(|w| {
+                // always on
+                w.ondemand().clear_bit();
+
+                // closed loop mode
+                w.mode().set_bit();
+
+                // chill cycle disable
+                w.ccdis().set_bit();
+
+                // usb correction, opted into despite the instability
+                // around USB bus resets, see `with_usb_clock_recovery`
+                w.usbcrm().set_bit();
+
+                // bypass coarse lock (have calibration data)
+                w.bplckc().set_bit()
+            });
+
+            wait_for_dfllrdy(sysctrl);
+            sysctrl.dfllctrl.modify(|_, w| w.enable().set_bit());
+            // The lock waits for the host, so don't wait for it
+            wait_for_dfllrdy(sysctrl);
+            return;
+        }
+
         sysctrl.dfllmul.write(|w| unsafe {
             w.cstep().bits(coarse / 4);
             w.fstep().bits(10);
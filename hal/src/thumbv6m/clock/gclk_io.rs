@@ -0,0 +1,150 @@
+//! Clock generator output to pins
+//!
+//! Each clock generator drives a few `GCLK_IO` pins, in alternate function
+//! `H`, to clock external devices, such as audio codecs and ADCs, or to look
+//! at the clock tree with a scope. The pins implement [`GclkIo`], which gives
+//! their generator.
+//!
+//! [`GenericClockController::configure_gclk_out`] configures the generator
+//! of a pin, with a source and divider, and outputs it on the pin.
+//! [`GenericClockController::gclk_out`] outputs a generator already
+//! configured, such as gclk0.
+//!
+//! ```no_run
+//! use atsamd_hal::clock::ClockSource;
+//!
+//! // 12MHz on PA16, GCLK_IO[2]
+//! let mclk_out = clocks
+//!     .configure_gclk_out(4, ClockSource::DFLL48M, true, pins.pa16)
+//!     .unwrap();
+//! assert_eq!(mclk_out.freq().0, 12_000_000);
+//! ```
+
+use crate::gpio::v2::pin::*;
+use crate::time::Hertz;
+
+use super::{ClockGenId, ClockSource, GClock, GenericClockController};
+
+/// Pin mode of the `GCLK_IO` pins
+pub type GclkIoMode = AlternateH;
+
+/// A `GCLK_IO` pin
+pub trait GclkIo: PinId {
+    /// Clock generator output on the pin
+    const GCLK: ClockGenId;
+}
+
+macro_rules! gclk_io {
+    ( $( $( #[$cfg:meta] )? ($Id:ident, $GCLK:ident), )+ ) => {
+        $(
+            $( #[$cfg] )?
+            impl GclkIo for $Id {
+                const GCLK: ClockGenId = ClockGenId::$GCLK;
+            }
+        )+
+    };
+}
+
+gclk_io!(
+    (PA10, GCLK4),
+    (PA11, GCLK5),
+    (PA14, GCLK0),
+    (PA15, GCLK1),
+    (PA16, GCLK2),
+    (PA17, GCLK3),
+    #[cfg(feature = "min-samd21g")]
+    (PA20, GCLK4),
+    #[cfg(feature = "min-samd21g")]
+    (PA21, GCLK5),
+    (PA22, GCLK6),
+    (PA23, GCLK7),
+    (PA27, GCLK0),
+    (PA28, GCLK0),
+    (PA30, GCLK0),
+    #[cfg(feature = "min-samd21g")]
+    (PB10, GCLK4),
+    #[cfg(feature = "min-samd21g")]
+    (PB11, GCLK5),
+    #[cfg(feature = "min-samd21j")]
+    (PB12, GCLK6),
+    #[cfg(feature = "min-samd21j")]
+    (PB13, GCLK7),
+    #[cfg(feature = "min-samd21j")]
+    (PB14, GCLK0),
+    #[cfg(feature = "min-samd21j")]
+    (PB15, GCLK1),
+    #[cfg(feature = "min-samd21j")]
+    (PB16, GCLK2),
+    #[cfg(feature = "min-samd21j")]
+    (PB17, GCLK3),
+    #[cfg(feature = "min-samd21g")]
+    (PB22, GCLK0),
+    #[cfg(feature = "min-samd21g")]
+    (PB23, GCLK1),
+);
+
+/// A clock generator output on its `GCLK_IO` pin
+///
+/// See the [module-level documentation](self) for more details.
+pub struct GclkOut<I: GclkIo> {
+    pin: Pin<I, GclkIoMode>,
+    freq: Hertz,
+}
+
+impl<I: GclkIo> GclkOut<I> {
+    /// Frequency of the output
+    #[inline]
+    pub fn freq(&self) -> Hertz {
+        self.freq
+    }
+
+    /// Release the pin
+    ///
+    /// The generator keeps running.
+    #[inline]
+    pub fn free(self) -> Pin<I, GclkIoMode> {
+        self.pin
+    }
+}
+
+impl GenericClockController {
+    /// Configure the clock generator of `pin` with the specified divider and
+    /// source, and output it on `pin`
+    ///
+    /// See [`configure_gclk_divider_and_source`] for the arguments.
+    /// Returns `None` if the clock generator has already been configured.
+    ///
+    /// [`configure_gclk_divider_and_source`]: Self::configure_gclk_divider_and_source
+    pub fn configure_gclk_out<I: GclkIo>(
+        &mut self,
+        divider: u16,
+        src: ClockSource,
+        improve_duty_cycle: bool,
+        pin: impl AnyPin<Id = I>,
+    ) -> Option<GclkOut<I>> {
+        let gclk =
+            self.configure_gclk_divider_and_source(I::GCLK, divider, src, improve_duty_cycle)?;
+        let freq = self.gclks[u8::from(gclk.gclk) as usize];
+        Some(GclkOut {
+            pin: pin.into().into_mode(),
+            freq,
+        })
+    }
+
+    /// Output `gclk`, already configured, on `pin`
+    ///
+    /// Returns `pin` back if it isn't a `GCLK_IO` pin of `gclk`.
+    pub fn gclk_out<I: GclkIo, P: AnyPin<Id = I>>(
+        &mut self,
+        gclk: &GClock,
+        pin: P,
+    ) -> Result<GclkOut<I>, P> {
+        if gclk.gclk != I::GCLK {
+            return Err(pin);
+        }
+        Ok(GclkOut {
+            pin: pin.into().into_mode(),
+            freq: gclk.freq,
+        })
+    }
+}
@@ -0,0 +1,101 @@
+//! 32kHz oscillator trimming
+//!
+//! The OSCULP32K is calibrated at the factory; its calibration is loaded from
+//! the NVM at reset. Read it with [`osculp32k_calibration`] before trimming,
+//! to store it or restore it later. [`set_osculp32k_calibration`] trims the
+//! oscillator at runtime, for example to minimize the drift of the RTC
+//! measured during production test.
+//!
+//! [`configure_xosc32k`] sets the start-up time and the automatic amplitude
+//! control of the XOSC32K, matching the crystal. The start-up time applies
+//! the next time the oscillator starts.
+//!
+//! Both registers can be locked until the next reset with [`lock_osculp32k`]
+//! and [`lock_xosc32k`].
+
+use crate::pac::SYSCTRL;
+
+/// Highest calibration value of the OSCULP32K
+pub const OSCULP32K_CALIB_MAX: u8 = 0x1f;
+
+/// Errors of the 32kHz oscillator configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The register is locked until the next reset
+    Locked,
+    /// The calibration value is above [`OSCULP32K_CALIB_MAX`]
+    OutOfRange,
+}
+
+/// Start-up time of the XOSC32K, in cycles of the OSCULP32K
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Xosc32kStartup {
+    /// 122µs
+    Cycle1 = 0,
+    /// 1ms
+    Cycle32 = 1,
+    /// 62.6ms
+    Cycle2048 = 2,
+    /// 125ms
+    Cycle4096 = 3,
+    /// 500ms
+    Cycle16384 = 4,
+    /// 1s
+    Cycle32768 = 5,
+    /// 2s
+    Cycle65536 = 6,
+    /// 4s
+    Cycle131072 = 7,
+}
+
+/// Current calibration of the OSCULP32K
+#[inline]
+pub fn osculp32k_calibration(sysctrl: &SYSCTRL) -> u8 {
+    sysctrl.osculp32k.read().calib().bits()
+}
+
+/// Trim the OSCULP32K; a higher value raises its frequency
+pub fn set_osculp32k_calibration(sysctrl: &mut SYSCTRL, calib: u8) -> Result<(), Error> {
+    if calib > OSCULP32K_CALIB_MAX {
+        return Err(Error::OutOfRange);
+    }
+    if sysctrl.osculp32k.read().wrtlock().bit_is_set() {
+        return Err(Error::Locked);
+    }
+    // SAFETY: The value is checked above
+    sysctrl
+        .osculp32k
+        .modify(|_, w| unsafe { w.calib().bits(calib) });
+    Ok(())
+}
+
+/// Lock the OSCULP32K configuration until the next reset
+#[inline]
+pub fn lock_osculp32k(sysctrl: &mut SYSCTRL) {
+    sysctrl.osculp32k.modify(|_, w| w.wrtlock().set_bit());
+}
+
+/// Set the start-up time of the XOSC32K, and enable its automatic amplitude
+/// control, which lowers its consumption once started
+pub fn configure_xosc32k(
+    sysctrl: &mut SYSCTRL,
+    startup: Xosc32kStartup,
+    amplitude_control: bool,
+) -> Result<(), Error> {
+    if sysctrl.xosc32k.read().wrtlock().bit_is_set() {
+        return Err(Error::Locked);
+    }
+    sysctrl.xosc32k.modify(|_, w| {
+        // SAFETY: The value comes from the enum above
+        unsafe { w.startup().bits(startup as u8) };
+        w.aampen().bit(amplitude_control)
+    });
+    Ok(())
+}
+
+/// Lock the XOSC32K configuration until the next reset
+#[inline]
+pub fn lock_xosc32k(sysctrl: &mut SYSCTRL) {
+    sysctrl.xosc32k.modify(|_, w| w.wrtlock().set_bit());
+}
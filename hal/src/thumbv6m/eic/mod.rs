@@ -1,15 +1,19 @@
+use crate::bus_clock::BusClock;
 use crate::clock::EicClock;
 use crate::pac;
 
 pub mod pin;
 
+#[cfg(feature = "async")]
+pub mod async_api;
+
 pub struct EIC {
     eic: pac::EIC,
 }
 
 impl EIC {
     pub fn init(pm: &mut pac::PM, _clock: EicClock, eic: pac::EIC) -> Self {
-        pm.apbamask.modify(|_, w| w.eic_().set_bit());
+        pac::EIC::enable_bus_clock(pm);
 
         eic.ctrl.modify(|_, w| w.enable().set_bit());
         while eic.status.read().syncbusy().bit_is_set() {
@@ -19,3 +23,28 @@ impl EIC {
         EIC { eic }
     }
 }
+
+/// Replace the sense configuration of external interrupt `num`
+///
+/// The configuration can be changed while the EIC is enabled.
+fn set_sense(num: usize, sense: pin::Sense) {
+    let shift = 4 * (num & 0b111);
+    cortex_m::interrupt::free(|_| {
+        // SAFETY: Only the sense field of `num` is modified, in a critical
+        // section
+        let config = unsafe { &(*pac::EIC::ptr()).config[num >> 3] };
+        config.modify(|r, w| unsafe {
+            w.bits(r.bits() & !(0b111 << shift) | (sense as u32) << shift)
+        });
+    });
+}
+
+/// Block until external interrupt `num` detects `sense`
+fn wait(num: usize, sense: pin::Sense) {
+    set_sense(num, sense);
+    // SAFETY: Only the flag of `num` is cleared
+    let eic = unsafe { &*pac::EIC::ptr() };
+    eic.intflag.write(|w| unsafe { w.bits(1 << num) });
+    while eic.intflag.read().bits() & (1 << num) == 0 {}
+    eic.intflag.write(|w| unsafe { w.bits(1 << num) });
+}
@@ -4,6 +4,8 @@ use crate::gpio::{
     self, v2::AnyPin, v2::FloatingInterrupt, v2::Pin, v2::PinId, v2::PinMode,
     v2::PullDownInterrupt, v2::PullUpInterrupt, Port,
 };
+#[cfg(feature = "samd21")]
+use crate::gpio::v2::{Interrupt, InterruptConfig, PA08};
 use crate::pac;
 
 /// The EicPin trait makes it more ergonomic to convert a gpio pin into an EIC
@@ -147,6 +149,58 @@ crate::paste::item! {
                 }
             });
         }
+
+        /// Block until the external interrupt detects `sense`
+        ///
+        /// The sense configuration of the external interrupt is replaced by
+        /// `sense`, and its flag is cleared before and after waiting.
+        pub fn wait(&mut self, sense: Sense) {
+            super::wait($num, sense);
+        }
+
+        /// Wait for the external interrupt to detect `sense`
+        ///
+        /// The sense configuration of the external interrupt is replaced by
+        /// `sense`. The EIC interrupt must be handled by
+        /// [`on_interrupt`](super::async_api::on_interrupt).
+        #[cfg(feature = "async")]
+        pub async fn wait_for(&mut self, sense: Sense) {
+            super::async_api::wait($num, sense).await
+        }
+    }
+
+    #[cfg(feature = "async")]
+    impl<GPIO: AnyPin> embedded_hal_1::digital::ErrorType for [<$PadType $num>]<GPIO> {
+        type Error = core::convert::Infallible;
+    }
+
+    /// Portable `async` waiting, see [`wait_for`](Self::wait_for)
+    #[cfg(feature = "async")]
+    impl<GPIO: AnyPin> embedded_hal_async::digital::Wait for [<$PadType $num>]<GPIO> {
+        async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+            self.wait_for(Sense::HIGH).await;
+            Ok(())
+        }
+
+        async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+            self.wait_for(Sense::LOW).await;
+            Ok(())
+        }
+
+        async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+            self.wait_for(Sense::RISE).await;
+            Ok(())
+        }
+
+        async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+            self.wait_for(Sense::FALL).await;
+            Ok(())
+        }
+
+        async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+            self.wait_for(Sense::BOTH).await;
+            Ok(())
+        }
     }
 
     $(
@@ -181,6 +235,61 @@ crate::paste::item! {
     };
 }
 
+/// The non-maskable interrupt, on PA08
+///
+/// Unlike the external interrupts, the NMI cannot be masked, and is handled
+/// by the `NonMaskableInt` exception handler instead of the EIC interrupt
+/// handler. Configure the pin with
+/// [`into_floating_interrupt`](Pin::into_floating_interrupt) or one of the
+/// pulled variants beforehand.
+#[cfg(feature = "samd21")]
+pub struct Nmi<C: InterruptConfig> {
+    pin: Pin<PA08, Interrupt<C>>,
+}
+
+#[cfg(feature = "samd21")]
+impl<C: InterruptConfig> Nmi<C> {
+    /// Use `pin` as the NMI pin
+    pub fn new(pin: Pin<PA08, Interrupt<C>>) -> Self {
+        Self { pin }
+    }
+
+    /// Return the pin
+    ///
+    /// The NMI is not disabled, set its sense to [`Sense::NONE`] beforehand
+    /// if needed.
+    pub fn free(self) -> Pin<PA08, Interrupt<C>> {
+        self.pin
+    }
+
+    /// Set the condition triggering the NMI, [`Sense::NONE`] disabling it
+    pub fn sense(&mut self, eic: &mut super::EIC, sense: Sense) {
+        // SAFETY: The NMI sense configuration has the same values as the
+        // external interrupts sense configuration
+        eic.eic
+            .nmictrl
+            .modify(|_, w| unsafe { w.nmisense().bits(sense as u8) });
+    }
+
+    /// Enable or disable the majority vote filter of the NMI
+    pub fn filter(&mut self, eic: &mut super::EIC, filter: bool) {
+        eic.eic.nmictrl.modify(|_, w| w.nmifilten().bit(filter));
+    }
+
+    /// Check whether the NMI fired
+    pub fn is_interrupt(&mut self) -> bool {
+        unsafe { &(*pac::EIC::ptr()) }.nmiflag.read().nmi().bit_is_set()
+    }
+
+    /// Clear the NMI flag, usually from the `NonMaskableInt` exception
+    /// handler
+    pub fn clear_interrupt(&mut self) {
+        unsafe { &(*pac::EIC::ptr()) }
+            .nmiflag
+            .write(|w| w.nmi().set_bit());
+    }
+}
+
 impl<I, M> ExternalInterrupt for Pin<I, M>
 where
     I: PinId,
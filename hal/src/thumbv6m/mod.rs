@@ -6,8 +6,10 @@ pub use reset_cause::*;
 mod serial_number;
 pub use serial_number::*;
 
+pub mod bootloader;
 pub mod calibration;
 pub mod clock;
+pub mod nvm;
 pub mod timer;
 
 #[cfg(feature = "unproven")]
@@ -0,0 +1,278 @@
+//! # Non-volatile Memory Controller
+//!
+//! This module allows users to erase and write the internal flash through the
+//! non-volatile memory controller.
+//!
+//! The flash is organized in rows of four pages. Erasing is done one row at a
+//! time, while writing is done one page at a time through the page buffer.
+//! The driver configures the controller for manual page writes, so partially
+//! filled page buffers are only committed when the driver requests it.
+//!
+//! Module features:
+//! - Erase & write over non-volatile memory in a device.
+//! - Read and program the `BOOTPROT` fuse of the user row
+//! - [`FlashRegion`], an `embedded_storage` `NorFlash` implementation over
+//!   a part of the flash, when the `embedded-storage` feature is enabled
+
+#![warn(missing_docs)]
+
+use core::ops::Range;
+
+use crate::pac::nvmctrl::ctrla::CMD_A;
+use crate::pac::NVMCTRL;
+
+#[cfg(feature = "embedded-storage")]
+#[path = "../nvm/storage.rs"]
+mod storage;
+#[cfg(feature = "embedded-storage")]
+pub use storage::FlashRegion;
+
+/// Size of a page in bytes
+pub const PAGESIZE: u32 = 64;
+
+/// Size of a row in bytes, the erase granularity
+pub const ROWSIZE: u32 = PAGESIZE * 4;
+
+/// Address of the NVM user row
+pub const USER_ROW_ADDR: u32 = 0x0080_4000;
+
+/// Mask of the `BOOTPROT` fuse in the first word of the user row
+const BOOTPROT_MASK: u32 = 0x7;
+
+/// Retrieve the total flash size using HW registers
+#[inline]
+pub fn retrieve_flash_size() -> u32 {
+    // SAFETY: PARAM is a read-only register
+    let nvm = unsafe { &*NVMCTRL::ptr() };
+    let nvm_params = nvm.param.read();
+    let page_size = 8 << nvm_params.psz().bits() as u32;
+    nvm_params.nvmp().bits() as u32 * page_size
+}
+
+/// Non-volatile memory controller
+pub struct Nvm {
+    /// PAC peripheral
+    nvm: NVMCTRL,
+}
+
+/// Errors generated by the NVM peripheral
+#[derive(Debug)]
+pub enum PeripheralError {
+    /// NVM error, an invalid command or bad keyword was given
+    NvmError,
+    /// Locked error, the target region is locked
+    LockError,
+    /// Programming error
+    ProgrammingError,
+}
+
+/// Driver errors
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum Error {
+    /// Address range outside of flash
+    NonFlash,
+    /// Errors generated by hardware
+    Peripheral(PeripheralError),
+    /// An alignment requirement was not fulfilled
+    Alignment,
+}
+
+/// NVM result type
+pub type Result<T> = core::result::Result<T, Error>;
+
+impl Nvm {
+    /// Create a new NVM controller
+    ///
+    /// Automatic page writes are disabled, so that pages are only written
+    /// when the driver has filled the page buffer.
+    #[inline]
+    pub fn new(nvm: NVMCTRL) -> Self {
+        nvm.ctrlb.modify(|_, w| w.manw().set_bit());
+        Self { nvm }
+    }
+
+    /// Release the NVM controller
+    #[inline]
+    pub fn free(self) -> NVMCTRL {
+        self.nvm
+    }
+
+    /// Determine if the controller is ready to accept a new command
+    #[inline]
+    pub fn is_ready(&self) -> bool {
+        self.nvm.intflag.read().ready().bit_is_set()
+    }
+
+    /// Set address for the next command
+    ///
+    /// The controller expects a 16-bit word address.
+    fn set_address(&mut self, address: u32) {
+        // SAFETY: Any flash address is valid
+        unsafe {
+            self.nvm.addr.write(|w| w.addr().bits(address >> 1));
+        }
+    }
+
+    /// Execute a command, wait until it is done
+    fn command_sync(&mut self, command: CMD_A) {
+        while !self.is_ready() {}
+        self.nvm
+            .ctrla
+            .write(|w| w.cmdex().key().cmd().variant(command));
+        while !self.is_ready() {}
+    }
+
+    /// Read the peripheral state to check error flags and clear them
+    /// afterwards
+    fn manage_error_states(&mut self) -> Result<()> {
+        let status = self.nvm.status.read();
+        let state = if status.locke().bit_is_set() {
+            Err(Error::Peripheral(PeripheralError::LockError))
+        } else if status.proge().bit_is_set() {
+            Err(Error::Peripheral(PeripheralError::ProgrammingError))
+        } else if status.nvme().bit_is_set() {
+            Err(Error::Peripheral(PeripheralError::NvmError))
+        } else {
+            Ok(())
+        };
+
+        // Clear error flags
+        self.nvm.status.write(|w| {
+            w.locke().set_bit();
+            w.proge().set_bit();
+            w.nvme().set_bit()
+        });
+        self.nvm.intflag.write(|w| w.error().set_bit());
+        state
+    }
+
+    /// Write to flash memory from a slice
+    ///
+    /// If `destination_address` is not word-aligned, an error is returned.
+    ///
+    /// # Safety
+    /// The destination must not contain code or data that is in use.
+    #[inline]
+    pub unsafe fn write_from_slice(
+        &mut self,
+        destination_address: u32,
+        source_slice: &[u32],
+    ) -> Result<()> {
+        if destination_address % 4 != 0 {
+            return Err(Error::Alignment);
+        }
+
+        let length = source_slice.len() as u32 * 4;
+        let write_addresses = destination_address..(destination_address + length);
+        if self.contains_non_flash_memory_area(&write_addresses) {
+            return Err(Error::NonFlash);
+        }
+
+        self.command_sync(CMD_A::PBC);
+        // Track whether we have unwritten data in the page buffer
+        let mut dirty = false;
+        let mut page_address = destination_address - destination_address % PAGESIZE;
+
+        for (destination_address, word) in write_addresses.step_by(4).zip(source_slice) {
+            // The data is placed in the page buffer. Memory is not written until
+            // the write page command is issued.
+            core::ptr::write_volatile(destination_address as *mut u32, *word);
+            dirty = true;
+
+            // If we are about to cross a page boundary, write the page buffer to
+            // flash
+            if destination_address % PAGESIZE == PAGESIZE - 4 {
+                self.set_address(page_address);
+                self.command_sync(CMD_A::WP);
+                self.manage_error_states()?;
+                self.command_sync(CMD_A::PBC);
+                dirty = false;
+                page_address += PAGESIZE;
+            }
+        }
+
+        if dirty {
+            self.set_address(page_address);
+            self.command_sync(CMD_A::WP);
+            self.manage_error_states()?;
+        }
+
+        Ok(())
+    }
+
+    /// Erase `rows` rows of flash memory, starting with the row containing
+    /// `address`
+    ///
+    /// # Safety
+    /// The erased rows must not contain code or data that is in use.
+    #[inline]
+    pub unsafe fn erase(&mut self, address: u32, rows: u32) -> Result<()> {
+        let row_address = address - address % ROWSIZE;
+        let range_to_erase = row_address..(row_address + rows * ROWSIZE);
+
+        if self.contains_non_flash_memory_area(&range_to_erase) {
+            return Err(Error::NonFlash);
+        }
+
+        for address in range_to_erase.step_by(ROWSIZE as usize) {
+            self.set_address(address);
+            self.command_sync(CMD_A::ER);
+            self.manage_error_states()?;
+        }
+
+        Ok(())
+    }
+
+    /// Read the `BOOTPROT` fuse, selecting the size of the boot protected
+    /// area
+    ///
+    /// * 7 = no boot protection
+    /// * 512 bytes << (6 - value) = protected size
+    #[inline]
+    pub fn bootprot(&self) -> u8 {
+        // SAFETY: The user row is always readable
+        let fuses = unsafe { core::ptr::read_volatile(USER_ROW_ADDR as *const u32) };
+        (fuses & BOOTPROT_MASK) as u8
+    }
+
+    /// Program the `BOOTPROT` fuse
+    ///
+    /// The rest of the user row is preserved. The new protection is applied
+    /// on the next reset.
+    ///
+    /// # Safety
+    /// The user row is erased then written back, so a reset in between
+    /// leaves its fuses, such as the watchdog and brown-out detector
+    /// settings, erased.
+    pub unsafe fn set_bootprot(&mut self, bootprot: u8) -> Result<()> {
+        let mut row = [0_u32; (ROWSIZE / 4) as usize];
+        let user_row = USER_ROW_ADDR as *const u32;
+        for (i, word) in row.iter_mut().enumerate() {
+            *word = core::ptr::read_volatile(user_row.add(i));
+        }
+        row[0] = (row[0] & !BOOTPROT_MASK) | (bootprot as u32 & BOOTPROT_MASK);
+
+        self.set_address(USER_ROW_ADDR);
+        self.command_sync(CMD_A::EAR);
+        self.manage_error_states()?;
+
+        let words_per_page = (PAGESIZE / 4) as usize;
+        for (index, page) in row.chunks_exact(words_per_page).enumerate() {
+            let page_address = USER_ROW_ADDR + index as u32 * PAGESIZE;
+            self.command_sync(CMD_A::PBC);
+            for (offset, &word) in page.iter().enumerate() {
+                core::ptr::write_volatile((page_address as *mut u32).add(offset), word);
+            }
+            self.set_address(page_address);
+            self.command_sync(CMD_A::WAP);
+            self.manage_error_states()?;
+        }
+
+        Ok(())
+    }
+
+    fn contains_non_flash_memory_area(&self, input: &Range<u32>) -> bool {
+        input.end > retrieve_flash_size()
+    }
+}
@@ -1,3 +1,4 @@
+use crate::bus_clock::{self, BusClock};
 use crate::clock;
 use crate::hal::{Pwm, PwmPin};
 use crate::time::Hertz;
@@ -14,7 +15,7 @@ use crate::pac::{TC6, TC7};
 // Timer/Counter (TCx)
 
 macro_rules! pwm {
-    ($($TYPE:ident: ($TC:ident, $clock:ident, $apmask:ident, $apbits:ident, $wrapper:ident),)+) => {
+    ($($TYPE:ident: ($TC:ident, $clock:ident, $wrapper:ident),)+) => {
         $(
 
 pub struct $TYPE {
@@ -35,7 +36,7 @@ impl $TYPE {
         {
             let count = tc.count16();
             let params = TimerParams::new(freq, clock.freq().0);
-            pm.$apmask.modify(|_, w| w.$apbits().set_bit());
+            $TC::enable_bus_clock(pm);
             count.ctrla.write(|w| w.swrst().set_bit());
             while count.ctrla.read().bits() & 1 != 0 {}
             count.ctrla.modify(|_, w| w.enable().clear_bit());
@@ -64,6 +65,19 @@ impl $TYPE {
         }
     }
 
+    /// Reset the TC, stopping the output, disable its bus clock and release
+    /// it
+    pub fn free(self) -> $TC {
+        let count = self.tc.count16();
+        count.ctrla.write(|w| w.swrst().set_bit());
+        while count.status.read().syncbusy().bit_is_set() {}
+        // the SVD erroneously marks swrst as write-only, so we
+        // need to manually read the bit here
+        while count.ctrla.read().bits() & 1 != 0 {}
+        bus_clock::release::<$TC>();
+        self.tc
+    }
+
     pub fn set_period<P>(&mut self, period: P)
     where
         P: Into<Hertz>
@@ -91,9 +105,16 @@ impl $TYPE {
 
     pub fn get_period(&self) -> Hertz {
         let count = self.tc.count16();
-        let divisor = count.ctrla.read().prescaler().bits();
+        let divisor = TimerParams::prescaler_divider(count.ctrla.read().prescaler().bits());
         let top = count.cc[0].read().cc().bits();
-        Hertz(self.clock_freq.0 / divisor as u32 / (top + 1) as u32)
+        Hertz(self.clock_freq.0 / divisor / (top as u32 + 1))
+    }
+
+    /// Returns the number of duty cycle steps achieved at the current
+    /// period, which depends on the prescaler computed for that period.
+    pub fn get_resolution(&self) -> u32 {
+        let count = self.tc.count16();
+        count.cc[0].read().cc().bits() as u32 + 1
     }
 }
 
@@ -128,25 +149,42 @@ impl PwmPin for $TYPE {
     }
 }
 
+#[cfg(feature = "ehal1")]
+impl embedded_hal_1::pwm::ErrorType for $TYPE {
+    type Error = core::convert::Infallible;
+}
+
+#[cfg(feature = "ehal1")]
+impl embedded_hal_1::pwm::SetDutyCycle for $TYPE {
+    fn max_duty_cycle(&self) -> u16 {
+        PwmPin::get_max_duty(self)
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+        PwmPin::set_duty(self, duty);
+        Ok(())
+    }
+}
+
 )+}}
 
 #[cfg(feature = "samd11")]
 pwm! {
-    Pwm1: (TC1, Tc1Tc2Clock, apbcmask, tc1_, Pwm1Wrapper),
-    Pwm2: (TC2, Tc1Tc2Clock, apbcmask, tc2_, Pwm2Wrapper),
+    Pwm1: (TC1, Tc1Tc2Clock, Pwm1Wrapper),
+    Pwm2: (TC2, Tc1Tc2Clock, Pwm2Wrapper),
 }
 
 #[cfg(feature = "samd21")]
 pwm! {
-    Pwm3: (TC3, Tcc2Tc3Clock, apbcmask, tc3_, Pwm3Wrapper),
-    Pwm4: (TC4, Tc4Tc5Clock, apbcmask, tc4_, Pwm4Wrapper),
-    Pwm5: (TC5, Tc4Tc5Clock, apbcmask, tc5_, Pwm5Wrapper),
+    Pwm3: (TC3, Tcc2Tc3Clock, Pwm3Wrapper),
+    Pwm4: (TC4, Tc4Tc5Clock, Pwm4Wrapper),
+    Pwm5: (TC5, Tc4Tc5Clock, Pwm5Wrapper),
 }
 
 #[cfg(feature = "samd21j")]
 pwm! {
-    Pwm6: (TC6, Tc6Tc7Clock, apbcmask, tc6_, Pwm6Wrapper),
-    Pwm7: (TC7, Tc6Tc7Clock, apbcmask, tc7_, Pwm7Wrapper),
+    Pwm6: (TC6, Tc6Tc7Clock, Pwm6Wrapper),
+    Pwm7: (TC7, Tc6Tc7Clock, Pwm7Wrapper),
 }
 
 // Timer/Counter for Control Applications (TCCx)
@@ -159,8 +197,70 @@ pub enum Channel {
     _3,
 }
 
+/// Single channel of a TCC PWM, implementing the embedded-hal 1.0
+/// [`SetDutyCycle`](embedded_hal_1::pwm::SetDutyCycle) trait
+///
+/// The TCC counters are wider than the 16-bit duty cycles of the trait, so
+/// duty cycles are scaled to the period when it exceeds 16 bits.
+#[cfg(feature = "ehal1")]
+pub struct PwmChannel<'a, P> {
+    pwm: &'a mut P,
+    channel: Channel,
+}
+
+#[cfg(feature = "ehal1")]
+impl<'a, P: Pwm<Channel = Channel, Duty = u32>> PwmChannel<'a, P> {
+    /// Borrows `channel` of `pwm`
+    pub fn new(pwm: &'a mut P, channel: Channel) -> Self {
+        Self { pwm, channel }
+    }
+}
+
+#[cfg(feature = "ehal1")]
+impl<P> embedded_hal_1::pwm::ErrorType for PwmChannel<'_, P> {
+    type Error = core::convert::Infallible;
+}
+
+#[cfg(feature = "ehal1")]
+impl<P: Pwm<Channel = Channel, Duty = u32>> embedded_hal_1::pwm::SetDutyCycle
+    for PwmChannel<'_, P>
+{
+    fn max_duty_cycle(&self) -> u16 {
+        self.pwm.get_max_duty().min(u16::MAX as u32) as u16
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+        let top = self.pwm.get_max_duty();
+        let duty = if top <= u16::MAX as u32 {
+            duty as u32
+        } else {
+            (duty as u64 * top as u64 / u16::MAX as u64) as u32
+        };
+        self.pwm.set_duty(self.channel, duty);
+        Ok(())
+    }
+}
+
+/// Recoverable fault of a TCC, triggered by the match/capture event input
+/// of channel 0 (fault A) or channel 1 (fault B)
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum RecoverableFault {
+    A,
+    B,
+}
+
+/// Output behaviour while a recoverable fault is active
+pub use crate::pac::tcc0::fctrla::HALT_A as FaultHalt;
+
+/// Non-recoverable fault of a TCC, triggered by the event input 0 or 1
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum NonRecoverableFault {
+    Input0,
+    Input1,
+}
+
 macro_rules! pwm_tcc {
-    ($($TYPE:ident: ($TCC:ident, $clock:ident, $apmask:ident, $apbits:ident, $wrapper:ident),)+) => {
+    ($($TYPE:ident: ($TCC:ident, $clock:ident, $wrapper:ident),)+) => {
         $(
 
 pub struct $TYPE {
@@ -180,7 +280,7 @@ impl $TYPE {
         let freq = freq.into();
         {
             let params = TimerParams::new(freq, clock.freq().0);
-            pm.$apmask.modify(|_, w| w.$apbits().set_bit());
+            $TCC::enable_bus_clock(pm);
             tcc.ctrla.write(|w| w.swrst().set_bit());
             while tcc.syncbusy.read().swrst().bit_is_set() {}
             tcc.ctrlbclr.write(|w| w.dir().set_bit() );
@@ -211,6 +311,174 @@ impl $TYPE {
             tcc,
         }
     }
+
+    /// Reset the TCC, stopping the outputs, disable its bus clock and release
+    /// it
+    pub fn free(self) -> $TCC {
+        self.tcc.ctrla.write(|w| w.swrst().set_bit());
+        while self.tcc.syncbusy.read().swrst().bit_is_set() {}
+        bus_clock::release::<$TCC>();
+        self.tcc
+    }
+
+    /// Inserts dead time between the complementary outputs `WO[n]` and
+    /// `WO[n + WO_NUM / 2]` of the channels set in `channels`, bit `n` for
+    /// channel `n` up to channel 3. The low side output is delayed by
+    /// `low_side` cycles, and the high side output by `high_side` cycles.
+    /// Not every TCC instance implements dead-time insertion, see the
+    /// datasheet. The TCC is briefly disabled, as the configuration is
+    /// enable-protected.
+    pub fn set_dead_time(&mut self, channels: u8, low_side: u8, high_side: u8) {
+        self.disable_protected(|tcc| {
+            tcc.wexctrl.modify(|r, w| unsafe {
+                // SAFETY: DTIEN0-3 are the bits 8 to 11
+                w.bits(r.bits() & !(0xf << 8) | ((channels & 0xf) as u32) << 8);
+                w.dtls().bits(low_side).dths().bits(high_side)
+            });
+        });
+    }
+
+    /// Overrides the outputs set in `outputs`, bit `n` for `WO[n]`, with the
+    /// corresponding bits in `values`. Outputs cleared in `outputs` follow
+    /// their waveform again.
+    pub fn set_pattern(&mut self, outputs: u8, values: u8) {
+        // SAFETY: PGE0-7 are the bits 0 to 7, PGV0-7 the bits 8 to 15
+        self.tcc
+            .patt
+            .write(|w| unsafe { w.bits(outputs as u16 | (values as u16) << 8) });
+        while self.tcc.syncbusy.read().patt().bit_is_set() {}
+    }
+
+    /// Enables a recoverable fault, triggered by the event routed to the
+    /// match/capture event input of channel 0 for fault A, or of channel 1
+    /// for fault B, such as the output of an analog comparator.
+    /// With `restart`, the counter restarts when the fault is released.
+    /// The TCC is briefly disabled, as the configuration is
+    /// enable-protected.
+    pub fn enable_recoverable_fault(
+        &mut self,
+        fault: RecoverableFault,
+        halt: FaultHalt,
+        restart: bool,
+    ) {
+        self.disable_protected(|tcc| match fault {
+            RecoverableFault::A => {
+                tcc.fctrla.write(|w| {
+                    w.src().enable();
+                    w.halt().variant(halt);
+                    w.restart().bit(restart)
+                });
+                tcc.evctrl.modify(|_, w| w.mcei0().set_bit());
+            }
+            RecoverableFault::B => {
+                // Both fault registers encode the halt action with the same
+                // values
+                tcc.fctrlb.write(|w| {
+                    w.src().enable();
+                    w.halt().bits(halt as u8);
+                    w.restart().bit(restart)
+                });
+                tcc.evctrl.modify(|_, w| w.mcei1().set_bit());
+            }
+        });
+    }
+
+    /// Enables the non-recoverable `fault`, triggered by the event routed
+    /// to its event input. On a fault, the outputs set in `outputs` are
+    /// driven to the levels set in `values` until the fault is cleared: bit
+    /// `n` of `outputs` selects `WO[n]`, and bit `n` of `values` is the
+    /// level of `WO[n]`, high when set. The TCC is briefly disabled, as the
+    /// configuration is enable-protected.
+    pub fn enable_non_recoverable_fault(
+        &mut self,
+        fault: NonRecoverableFault,
+        outputs: u8,
+        values: u8,
+    ) {
+        self.disable_protected(|tcc| {
+            tcc.drvctrl.modify(|r, w| unsafe {
+                // SAFETY: NRE0-7 are the bits 0 to 7, NRV0-7 the bits 8 to 15
+                w.bits(r.bits() & !0xffff | outputs as u32 | (values as u32) << 8)
+            });
+            tcc.evctrl.modify(|_, w| match fault {
+                NonRecoverableFault::Input0 => w.evact0().fault().tcei0().set_bit(),
+                NonRecoverableFault::Input1 => w.evact1().fault().tcei1().set_bit(),
+            });
+        });
+    }
+
+    /// Returns the fault states, bit 0 for fault A, bit 1 for fault B, and
+    /// bits 2 and 3 for the non-recoverable faults 0 and 1.
+    pub fn faults(&self) -> u8 {
+        (self.tcc.status.read().bits() >> 12) as u8 & 0xf
+    }
+
+    /// Clears the fault states set in `faults`, as returned by
+    /// [`faults`](Self::faults), once the fault inputs are released.
+    pub fn clear_faults(&mut self, faults: u8) {
+        // SAFETY: Writing a 1 clears a fault state
+        self.tcc
+            .status
+            .write(|w| unsafe { w.bits(((faults & 0xf) as u32) << 12) });
+    }
+
+    /// Sets the duty cycle of `channel` through its buffer register. The new
+    /// value is copied to the channel at the next update condition, at the
+    /// end of the period, unless updates are locked with
+    /// [`lock_update`](Self::lock_update), so the output never glitches.
+    pub fn set_duty_buffered(&mut self, channel: Channel, duty: u32) {
+        let ccb = self.tcc.ccb();
+        ccb[channel as usize].write(|w| unsafe { w.ccb().bits(duty) });
+    }
+
+    /// Sets the period, in counter cycles, through its buffer register. The
+    /// new value is copied to the counter top at the next update condition.
+    /// `cycles` should keep the prescaler selected for the current period.
+    pub fn set_top_buffered(&mut self, cycles: u32) {
+        self.tcc.perb().write(|w| unsafe { w.bits(cycles) });
+    }
+
+    /// Holds back the buffer registers written by
+    /// [`set_duty_buffered`](Self::set_duty_buffered) and
+    /// [`set_top_buffered`](Self::set_top_buffered), until
+    /// [`unlock_update`](Self::unlock_update) is called.
+    pub fn lock_update(&mut self) {
+        self.tcc.ctrlbset.write(|w| w.lupd().set_bit());
+        while self.tcc.syncbusy.read().ctrlb().bit_is_set() {}
+    }
+
+    /// Releases the buffer registers, which are all copied together at the
+    /// next update condition.
+    pub fn unlock_update(&mut self) {
+        self.tcc.ctrlbclr.write(|w| w.lupd().set_bit());
+        while self.tcc.syncbusy.read().ctrlb().bit_is_set() {}
+    }
+
+    /// Runs `f` with updates locked, so that every buffer register it writes
+    /// takes effect at the same period boundary.
+    pub fn update_atomically<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        self.lock_update();
+        let result = f(self);
+        self.unlock_update();
+        result
+    }
+
+    /// Returns the number of duty cycle steps achieved at the current
+    /// period, which depends on the prescaler computed for that period.
+    pub fn get_resolution(&self) -> u32 {
+        self.tcc.per().read().bits() + 1
+    }
+
+    fn disable_protected(&mut self, f: impl FnOnce(&$TCC)) {
+        let enabled = self.tcc.ctrla.read().enable().bit_is_set();
+        self.tcc.ctrla.modify(|_, w| w.enable().clear_bit());
+        while self.tcc.syncbusy.read().enable().bit_is_set() {}
+        f(&self.tcc);
+        if enabled {
+            self.tcc.ctrla.modify(|_, w| w.enable().set_bit());
+            while self.tcc.syncbusy.read().enable().bit_is_set() {}
+        }
+    }
 }
 
 impl Pwm for $TYPE {
@@ -227,9 +495,9 @@ impl Pwm for $TYPE {
     }
 
     fn get_period(&self) -> Self::Time {
-        let divisor = self.tcc.ctrla.read().prescaler().bits();
+        let divisor = TimerParams::prescaler_divider(self.tcc.ctrla.read().prescaler().bits());
         let top = self.tcc.per().read().bits();
-        Hertz(self.clock_freq.0 / divisor as u32 / (top + 1) as u32)
+        Hertz(self.clock_freq.0 / divisor / (top + 1))
     }
 
     fn get_duty(&self, channel: Self::Channel) -> Self::Duty {
@@ -278,12 +546,12 @@ impl Pwm for $TYPE {
 
 #[cfg(feature = "samd11")]
 pwm_tcc! {
-    Pwm0: (TCC0, Tcc0Clock, apbcmask, tcc0_, Pwm0Wrapper),
+    Pwm0: (TCC0, Tcc0Clock, Pwm0Wrapper),
 }
 
 #[cfg(feature = "samd21")]
 pwm_tcc! {
-    Pwm0: (TCC0, Tcc0Tcc1Clock, apbcmask, tcc0_, Pwm0Wrapper),
-    Pwm1: (TCC1, Tcc0Tcc1Clock, apbcmask, tcc1_, Pwm1Wrapper),
-    Pwm2: (TCC2, Tcc2Tc3Clock, apbcmask, tcc2_, Pwm2Wrapper),
+    Pwm0: (TCC0, Tcc0Tcc1Clock, Pwm0Wrapper),
+    Pwm1: (TCC1, Tcc0Tcc1Clock, Pwm1Wrapper),
+    Pwm2: (TCC2, Tcc2Tc3Clock, Pwm2Wrapper),
 }
@@ -6,6 +6,7 @@
 
 use core::marker::PhantomData;
 
+use crate::bus_clock::{self, BusClock};
 use crate::clock;
 use crate::hal::spi::{FullDuplex, Mode, Phase, Polarity};
 use crate::pac::sercom0::SPI;
@@ -110,7 +111,7 @@ where
 /// Also defines the valid "pad to spi function" mappings for this instance so
 /// that construction is restricted to correct configurations.
 macro_rules! spi_master {
-    ($Type:ident: ($Sercom:ident, $SERCOM:ident, $powermask:ident, $clock:ident)) => {
+    ($Type:ident: ($Sercom:ident, $SERCOM:ident, $clock:ident)) => {
         $crate::paste::item! {
             pub type [<$Type Padout>]<MISO, MOSI, SCLK> = Padout<$Sercom, MISO, MOSI, SCLK>;
         }
@@ -162,8 +163,7 @@ macro_rules! spi_master {
                 let padout = padout.into();
 
                 // Power up the peripheral bus clock.
-                // safe because we're exclusively owning SERCOM
-                pm.apbcmask.modify(|_, w| w.$powermask().set_bit());
+                $SERCOM::enable_bus_clock(pm);
 
                 // reset the sercom instance
                 sercom.spi().ctrla.modify(|_, w| w.swrst().set_bit());
@@ -229,8 +229,10 @@ macro_rules! spi_master {
             }
 
             /// Tear down the SPI instance and yield the constituent pins and
-            /// SERCOM instance.  No explicit de-initialization is performed.
+            /// SERCOM instance.  Only the bus clock of the SERCOM is
+            /// disabled.
             pub fn free(self) -> (Padout<$Sercom, MISO, MOSI, SCK>, $SERCOM) {
+                bus_clock::release::<$SERCOM>();
                 (self.padout, self.sercom)
             }
         }
@@ -284,13 +286,13 @@ macro_rules! spi_master {
     };
 }
 
-spi_master!(SPIMaster0: (Sercom0, SERCOM0, sercom0_, Sercom0CoreClock));
-spi_master!(SPIMaster1: (Sercom1, SERCOM1, sercom1_, Sercom1CoreClock));
+spi_master!(SPIMaster0: (Sercom0, SERCOM0, Sercom0CoreClock));
+spi_master!(SPIMaster1: (Sercom1, SERCOM1, Sercom1CoreClock));
 #[cfg(feature = "samd21")]
-spi_master!(SPIMaster2: (Sercom2, SERCOM2, sercom2_, Sercom2CoreClock));
+spi_master!(SPIMaster2: (Sercom2, SERCOM2, Sercom2CoreClock));
 #[cfg(feature = "samd21")]
-spi_master!(SPIMaster3: (Sercom3, SERCOM3, sercom3_, Sercom3CoreClock));
+spi_master!(SPIMaster3: (Sercom3, SERCOM3, Sercom3CoreClock));
 #[cfg(feature = "min-samd21g")]
-spi_master!(SPIMaster4: (Sercom4, SERCOM4, sercom4_, Sercom4CoreClock));
+spi_master!(SPIMaster4: (Sercom4, SERCOM4, Sercom4CoreClock));
 #[cfg(feature = "min-samd21g")]
-spi_master!(SPIMaster5: (Sercom5, SERCOM5, sercom5_, Sercom5CoreClock));
+spi_master!(SPIMaster5: (Sercom5, SERCOM5, Sercom5CoreClock));
@@ -4,6 +4,7 @@
     Please use the `sercom::v2::uart` module instead."
 )]
 
+use crate::bus_clock::{self, BusClock};
 use crate::clock;
 use crate::hal::blocking::serial::{write::Default, Write};
 use crate::hal::serial;
@@ -197,7 +198,7 @@ where
 /// Also defines the valid "pad to uart function" mappings for this instance so
 /// that construction is restricted to valid configurations.
 macro_rules! uart {
-    ($Type:ident: ($Sercom:ident, $SERCOM:ident, $powermask:ident, $clock:ident)) => {
+    ($Type:ident: ($Sercom:ident, $SERCOM:ident, $clock:ident)) => {
         $crate::paste::item! {
             pub type [<$Type Padout>]<RX, TX, RTS, CTS> = Padout<$Sercom, RX, TX, RTS, CTS>;
             pub type [<$Type TxPadout>]<TX, RTS> = TxPadout<$Sercom, TX, RTS>;
@@ -236,7 +237,7 @@ macro_rules! uart {
                     Padout<$Sercom, RX, TX, RTS, CTS>: RxpoTxpo {
                     let padout = padout.into();
 
-                    pm.apbcmask.modify(|_, w| w.$powermask().set_bit());
+                    $SERCOM::enable_bus_clock(pm);
 
                     // Lots of union fields which require unsafe access
                     unsafe {
@@ -305,10 +306,34 @@ macro_rules! uart {
                     }
                 }
 
+                /// Disable the bus clock of the SERCOM and release it with
+                /// the pads
                 pub fn free(self) -> (Padout<$Sercom, RX, TX, RTS, CTS>, $SERCOM) {
+                    bus_clock::release::<$SERCOM>();
                     (self.padout, self.sercom)
                 }
 
+                /// Enable or disable IrDA encoding
+                ///
+                /// With IrDA encoding, the transmitter sends each 0 bit as a
+                /// pulse lasting 3/16 of the bit period, suitable to drive an
+                /// infrared transceiver directly. The receiver ignores
+                /// pulses shorter than `pulse_length + 2` GCLK cycles.
+                pub fn set_irda_encoding(&mut self, pulse_length: Option<u8>) {
+                    // SAFETY: The UART owns the SERCOM
+                    let usart = unsafe { self.usart() };
+                    usart.ctrla.modify(|_, w| w.enable().clear_bit());
+                    while usart.syncbusy.read().enable().bit_is_set() {}
+                    if let Some(pulse_length) = pulse_length {
+                        // SAFETY: RXPL accepts any byte
+                        usart.rxpl.write(|w| unsafe { w.rxpl().bits(pulse_length) });
+                    }
+                    usart.ctrlb.modify(|_, w| w.enc().bit(pulse_length.is_some()));
+                    while usart.syncbusy.read().ctrlb().bit_is_set() {}
+                    usart.ctrla.modify(|_, w| w.enable().set_bit());
+                    while usart.syncbusy.read().enable().bit_is_set() {}
+                }
+
                 /// Splits the UART into transmit and receive halves
                 pub fn split(self) -> ([<$Type Tx>]<TX, RTS>, [<$Type Rx>]<RX, CTS>) {
                     let (tx_pads, rx_pads) = self.padout.split();
@@ -494,16 +519,16 @@ macro_rules! uart {
     };
 }
 
-uart!(UART0: (Sercom0, SERCOM0, sercom0_, Sercom0CoreClock));
-uart!(UART1: (Sercom1, SERCOM1, sercom1_, Sercom1CoreClock));
+uart!(UART0: (Sercom0, SERCOM0, Sercom0CoreClock));
+uart!(UART1: (Sercom1, SERCOM1, Sercom1CoreClock));
 #[cfg(feature = "samd21")]
-uart!(UART2: (Sercom2, SERCOM2, sercom2_, Sercom2CoreClock));
+uart!(UART2: (Sercom2, SERCOM2, Sercom2CoreClock));
 #[cfg(feature = "samd21")]
-uart!(UART3: (Sercom3, SERCOM3, sercom3_, Sercom3CoreClock));
+uart!(UART3: (Sercom3, SERCOM3, Sercom3CoreClock));
 #[cfg(feature = "min-samd21g")]
-uart!(UART4: (Sercom4, SERCOM4, sercom4_, Sercom4CoreClock));
+uart!(UART4: (Sercom4, SERCOM4, Sercom4CoreClock));
 #[cfg(feature = "min-samd21g")]
-uart!(UART5: (Sercom5, SERCOM5, sercom5_, Sercom5CoreClock));
+uart!(UART5: (Sercom5, SERCOM5, Sercom5CoreClock));
 
 const SHIFT: u8 = 32;
 
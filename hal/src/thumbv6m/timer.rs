@@ -1,9 +1,10 @@
 //! Working with timer counter hardware
+use crate::bus_clock::{self, BusClock};
 use crate::ehal::timer::{CountDown, Periodic};
 #[cfg(feature = "samd11")]
 use crate::pac::tc1::COUNT16;
 #[cfg(feature = "samd21")]
-use crate::pac::tc3::COUNT16;
+use crate::pac::tc3::{COUNT16, COUNT32};
 #[allow(unused)]
 #[cfg(feature = "samd11")]
 use crate::pac::{PM, TC1};
@@ -13,22 +14,37 @@ use crate::pac::{PM, TC3, TC4, TC5};
 use crate::timer_params::TimerParams;
 
 use crate::clock;
-use crate::time::{Hertz, Nanoseconds};
+use crate::time::{Hertz, Microseconds, Nanoseconds};
 use crate::timer_traits::InterruptDrivenTimer;
 use void::Void;
 
 use cortex_m::asm::delay as cycle_delay;
 
+/// Offset of the COUNT register, synchronized continuously in free-running
+/// mode
+#[cfg(feature = "samd21")]
+const COUNT_OFFSET: u8 = 0x10;
+
 // Note:
-// TC3 + TC4 can be paired to make a 32-bit counter
-// TC5 + TC6 can be paired to make a 32-bit counter
+// TC4 + TC5 can be paired to make a 32-bit counter
+// TC6 + TC7 can be paired to make a 32-bit counter
+
+/// Error of [`TimerCounter::start_square_wave`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SquareWaveError {
+    /// The frequency is 0
+    ZeroFrequency,
+    /// The period is too long for the 16-bit counter, even with the largest
+    /// prescaler
+    FrequencyTooLow,
+}
 
 /// A generic hardware timer counter.
-/// The counters are exposed in 16-bit mode only.
-/// The hardware allows configuring the 8-bit mode
-/// and pairing up some instances to run in 32-bit
-/// mode, but that functionality is not currently
-/// exposed by this hal implementation.
+/// The counters are exposed in 16-bit mode, or paired
+/// up to run in 32-bit mode with [`TimerCounter32`].
+/// The hardware also allows configuring the 8-bit mode,
+/// but that functionality is not currently exposed
+/// by this hal implementation.
 /// TimerCounter implements both the `Periodic` and
 /// the `CountDown` embedded_hal timer traits.
 /// Before a hardware timer can be used, it must first
@@ -115,6 +131,181 @@ where
     }
 }
 
+/// Pulse measurement performed on the signal received by the
+/// event input of a timer counter, see
+/// [`TimerCounter::start_capture`]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CaptureMode {
+    /// Capture the period into CC0 and the pulse width into CC1
+    PeriodPulseWidth,
+    /// Capture the pulse width into CC0 and the period into CC1
+    PulseWidthPeriod,
+}
+
+/// Division of the timer clock counted by a capture, see
+/// [`TimerCounter::start_capture`]
+#[cfg(feature = "samd11")]
+pub use crate::pac::tc1::count16::ctrla::PRESCALER_A as Prescaler;
+/// Division of the timer clock counted by a capture, see
+/// [`TimerCounter::start_capture`]
+#[cfg(feature = "samd21")]
+pub use crate::pac::tc3::count16::ctrla::PRESCALER_A as Prescaler;
+
+/// Period and pulse width of a signal, in timer counter cycles
+///
+/// The counter wraps around after 65535 cycles, so longer periods or pulses
+/// read modulo 65536; select a larger [`Prescaler`] to measure them.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Capture {
+    /// Cycles between two edges of the same polarity
+    pub period: u16,
+    /// Cycles during which the signal is high, or low if the capture is
+    /// inverted
+    pub pulse_width: u16,
+}
+
+impl<TC> TimerCounter<TC>
+where
+    TC: Count16,
+{
+    /// Measures the period and pulse width of the signal received by the
+    /// event input of this timer counter, counting at the timer clock
+    /// frequency divided by `prescaler`. `invert` measures the low pulses of
+    /// the signal instead.
+    ///
+    /// The signal is usually routed from a pin through the EIC and the
+    /// event system, with the EIC event output enabled for the pin, and an
+    /// event channel connecting the EIC line to the timer counter user.
+    ///
+    /// Only the TC instances capture: the TCC instances are left to
+    /// [`pwm`](crate::pwm), whose driver owns them for waveform generation.
+    pub fn start_capture(&mut self, mode: CaptureMode, prescaler: Prescaler, invert: bool) {
+        let count = self.tc.count_16();
+
+        // Disable the timer while we reconfigure it
+        count.ctrla.modify(|_, w| w.enable().clear_bit());
+        while count.status.read().syncbusy().bit_is_set() {}
+
+        count.ctrla.write(|w| w.swrst().set_bit());
+        while count.status.read().syncbusy().bit_is_set() {}
+        // the SVD erroneously marks swrst as write-only, so we
+        // need to manually read the bit here
+        while count.ctrla.read().bits() & 1 != 0 {}
+
+        count.evctrl.write(|w| {
+            match mode {
+                CaptureMode::PeriodPulseWidth => w.evact().ppw(),
+                CaptureMode::PulseWidthPeriod => w.evact().pwp(),
+            };
+            w.tcinv().bit(invert);
+            w.tcei().set_bit()
+        });
+
+        // Capture into both channels
+        count
+            .ctrlc
+            .write(|w| w.cpten0().set_bit().cpten1().set_bit());
+        while count.status.read().syncbusy().bit_is_set() {}
+
+        count.ctrla.modify(|_, w| {
+            w.prescaler().variant(prescaler);
+            w.enable().set_bit();
+            w.runstdby().set_bit()
+        });
+        while count.status.read().syncbusy().bit_is_set() {}
+    }
+
+    /// Returns the last period and pulse width captured, once both
+    /// channels captured a new value since the previous call.
+    pub fn read_capture(&mut self) -> nb::Result<Capture, Void> {
+        let count = self.tc.count_16();
+        let flags = count.intflag.read();
+        if flags.mc0().bit_is_clear() || flags.mc1().bit_is_clear() {
+            return Err(nb::Error::WouldBlock);
+        }
+        // Reading the channels clears their flags
+        let cc0 = count.cc[0].read().cc().bits();
+        let cc1 = count.cc[1].read().cc().bits();
+        let capture = if count.evctrl.read().evact().is_pwp() {
+            Capture {
+                period: cc1,
+                pulse_width: cc0,
+            }
+        } else {
+            Capture {
+                period: cc0,
+                pulse_width: cc1,
+            }
+        };
+        Ok(capture)
+    }
+
+    /// Generates a square wave of frequency `freq` on the `WO[0]` output of
+    /// this timer counter, and returns the frequency actually achieved,
+    /// which is the closest one the timer clock divides down to.
+    ///
+    /// In match frequency mode, `WO[0]` toggles every time the counter
+    /// reaches CC0, so the duty cycle is always 50 %. The pin must be
+    /// configured for the timer counter alternate function. The output
+    /// keeps running, without any interrupt, until [`stop`](Self::stop)
+    /// or another `start` method is called.
+    ///
+    /// Returns an error, leaving the timer untouched, if `freq` is 0, or if
+    /// its half period exceeds 65535 cycles of the timer clock divided by
+    /// 1024, the largest prescaler.
+    pub fn start_square_wave(&mut self, freq: Hertz) -> Result<Hertz, SquareWaveError> {
+        if freq.0 == 0 {
+            return Err(SquareWaveError::ZeroFrequency);
+        }
+        // The output toggles twice per period
+        let params = TimerParams::checked_new(Hertz(freq.0.saturating_mul(2)), self.freq.0)
+            .ok_or(SquareWaveError::FrequencyTooLow)?;
+        let divider = params.divider;
+        let cycles = params.cycles.max(1);
+        let count = self.tc.count_16();
+
+        // Disable the timer while we reconfigure it
+        count.ctrla.modify(|_, w| w.enable().clear_bit());
+        while count.status.read().syncbusy().bit_is_set() {}
+
+        count.ctrla.write(|w| w.swrst().set_bit());
+        while count.status.read().syncbusy().bit_is_set() {}
+        // the SVD erroneously marks swrst as write-only, so we
+        // need to manually read the bit here
+        while count.ctrla.read().bits() & 1 != 0 {}
+
+        // The counter counts from 0 to CC0 included
+        count.cc[0].write(|w| unsafe { w.cc().bits((cycles - 1) as u16) });
+
+        count.ctrla.modify(|_, w| {
+            match divider {
+                1 => w.prescaler().div1(),
+                2 => w.prescaler().div2(),
+                4 => w.prescaler().div4(),
+                8 => w.prescaler().div8(),
+                16 => w.prescaler().div16(),
+                64 => w.prescaler().div64(),
+                256 => w.prescaler().div256(),
+                1024 => w.prescaler().div1024(),
+                _ => unreachable!(),
+            };
+            w.wavegen().mfrq();
+            w.enable().set_bit();
+            w.runstdby().set_bit()
+        });
+        while count.status.read().syncbusy().bit_is_set() {}
+
+        Ok(Hertz(self.freq.0 / divider as u32 / cycles / 2))
+    }
+
+    /// Stops the counter, along with any waveform it generates.
+    pub fn stop(&mut self) {
+        let count = self.tc.count_16();
+        count.ctrla.modify(|_, w| w.enable().clear_bit());
+        while count.status.read().syncbusy().bit_is_set() {}
+    }
+}
+
 impl<TC> InterruptDrivenTimer for TimerCounter<TC>
 where
     TC: Count16,
@@ -156,8 +347,7 @@ impl TimerCounter<$TC>
     /// Note that some hardware timer instances share the same clock
     /// generator instance and thus will be clocked at the same rate.
     pub fn $pm(clock: &clock::$clock, tc: $TC, pm: &mut PM) -> Self {
-        // this is safe because we're constrained to just the tc3 bit
-        pm.apbcmask.modify(|_, w| w.$pm().set_bit());
+        $TC::enable_bus_clock(pm);
         {
             let count = tc.count_16();
 
@@ -188,6 +378,224 @@ tc! {
     TimerCounter5: (TC5, tc5_, Tc4Tc5Clock),
 }
 
+/// Two timer counters paired up into a 32-bit timer counter.
+/// The even-numbered master instance controls the counter,
+/// while the odd-numbered slave instance holds its upper 16 bits
+/// and cannot be used on its own until the pair is freed.
+/// TimerCounter32 implements both the `Periodic` and
+/// the `CountDown` embedded_hal timer traits, with timeouts
+/// in microseconds. Without prescaling, the timeouts reach
+/// `2^32` cycles, about 89 seconds at 48 MHz, and longer ones
+/// are truncated.
+#[cfg(feature = "samd21")]
+pub struct TimerCounter32<M, S> {
+    freq: Hertz,
+    master: M,
+    slave: S,
+}
+
+/// This is a helper trait to make it easier to make most of the
+/// TimerCounter32 impl generic.  It doesn't make too much sense to
+/// to try to implement this trait outside of this module.
+#[cfg(feature = "samd21")]
+pub trait Count32 {
+    fn count_32(&self) -> &COUNT32;
+}
+
+#[cfg(feature = "samd21")]
+impl<M: BusClock, S: BusClock> TimerCounter32<M, S> {
+    /// Disables the bus clocks of the master and slave timer counter
+    /// instances and releases them.
+    pub fn free(self) -> (M, S) {
+        bus_clock::release::<M>();
+        bus_clock::release::<S>();
+        (self.master, self.slave)
+    }
+}
+
+#[cfg(feature = "samd21")]
+impl<M, S> TimerCounter32<M, S>
+where
+    M: Count32,
+{
+    /// Frequency of the counter
+    #[inline]
+    pub fn freq(&self) -> Hertz {
+        self.freq
+    }
+
+    /// Start counting up freely from 0, wrapping around after `2^32`
+    /// cycles, for [`count`](Self::count) to measure time
+    ///
+    /// This mode is used by [`TimerDelay`](crate::delay::TimerDelay).
+    pub fn start_free_running(&mut self) {
+        let count = self.master.count_32();
+
+        // Disable the timer while we reconfigure it
+        count.ctrla.modify(|_, w| w.enable().clear_bit());
+        while count.status.read().syncbusy().bit_is_set() {}
+
+        // Reset the pair, which returns the slave to 16-bit mode
+        count.ctrla.write(|w| w.swrst().set_bit());
+        while count.status.read().syncbusy().bit_is_set() {}
+        // the SVD erroneously marks swrst as write-only, so we
+        // need to manually read the bit here
+        while count.ctrla.read().bits() & 1 != 0 {}
+
+        count.ctrla.modify(|_, w| {
+            // Pair up with the slave instance
+            w.mode().count32();
+            w.prescaler().div1();
+            // Count up to the maximum value
+            w.wavegen().nfrq();
+            w.enable().set_bit();
+            w.runstdby().set_bit()
+        });
+        while count.status.read().syncbusy().bit_is_set() {}
+
+        // Synchronize COUNT continuously, so that it can be read at any time
+        count.readreq.write(|w| unsafe {
+            w.rcont().set_bit();
+            w.rreq().set_bit();
+            w.addr().bits(COUNT_OFFSET)
+        });
+        while count.status.read().syncbusy().bit_is_set() {}
+    }
+
+    /// Current value of the counter
+    #[inline]
+    pub fn count(&self) -> u32 {
+        self.master.count_32().count.read().count().bits()
+    }
+}
+
+#[cfg(feature = "samd21")]
+impl<M, S> Periodic for TimerCounter32<M, S> {}
+#[cfg(feature = "samd21")]
+impl<M, S> CountDown for TimerCounter32<M, S>
+where
+    M: Count32,
+{
+    type Time = Microseconds;
+
+    fn start<T>(&mut self, timeout: T)
+    where
+        T: Into<Self::Time>,
+    {
+        let timeout = timeout.into();
+        let cycles = timeout.0 as u64 * self.freq.0 as u64 / 1_000_000_u64;
+        let cycles = cycles.min(u32::MAX as u64) as u32;
+
+        let count = self.master.count_32();
+
+        // Disable the timer while we reconfigure it
+        count.ctrla.modify(|_, w| w.enable().clear_bit());
+        while count.status.read().syncbusy().bit_is_set() {}
+
+        // Reset the pair, which returns the slave to 16-bit mode
+        count.ctrla.write(|w| w.swrst().set_bit());
+        while count.status.read().syncbusy().bit_is_set() {}
+        // the SVD erroneously marks swrst as write-only, so we
+        // need to manually read the bit here
+        while count.ctrla.read().bits() & 1 != 0 {}
+
+        count.ctrlbset.write(|w| {
+            // Count up when the direction bit is zero
+            w.dir().clear_bit();
+            // Periodic
+            w.oneshot().clear_bit()
+        });
+
+        // Set TOP value for mfrq mode
+        count.cc[0].write(|w| unsafe { w.cc().bits(cycles) });
+
+        count.ctrla.modify(|_, w| {
+            // Pair up with the slave instance
+            w.mode().count32();
+            w.prescaler().div1();
+            // Enable Match Frequency Waveform generation
+            w.wavegen().mfrq();
+            w.enable().set_bit();
+            w.runstdby().set_bit()
+        });
+    }
+
+    fn wait(&mut self) -> nb::Result<(), Void> {
+        let count = self.master.count_32();
+        if count.intflag.read().ovf().bit_is_set() {
+            // Writing a 1 clears the flag
+            count.intflag.modify(|_, w| w.ovf().set_bit());
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+#[cfg(feature = "samd21")]
+impl<M, S> TimerCounter32<M, S>
+where
+    M: Count32,
+{
+    /// Enable the interrupt generation for this hardware timer.
+    /// The interrupt is raised by the master instance.
+    ///
+    /// [`InterruptDrivenTimer`] isn't implemented, as it requires
+    /// timeouts in nanoseconds.
+    pub fn enable_interrupt(&mut self) {
+        self.master.count_32().intenset.write(|w| w.ovf().set_bit());
+    }
+
+    /// Disables interrupt generation for this hardware timer.
+    pub fn disable_interrupt(&mut self) {
+        self.master.count_32().intenclr.write(|w| w.ovf().set_bit());
+    }
+}
+
+#[cfg(feature = "samd21")]
+macro_rules! tc32 {
+    ($($TYPE:ident: ($M:ident, $S:ident, $ctor:ident, $clock:ident),)+) => {
+        $(
+pub type $TYPE = TimerCounter32<$M, $S>;
+
+impl Count32 for $M {
+    fn count_32(&self) -> &COUNT32 {
+        self.count32()
+    }
+}
+
+impl TimerCounter32<$M, $S>
+{
+    /// Configure this pair of timer counter instances.
+    /// The clock is obtained from the `GenericClockController` instance
+    /// and its frequency impacts the resolution and maximum range of
+    /// the timeout values that can be passed to the `start` method.
+    pub fn $ctor(clock: &clock::$clock, master: $M, slave: $S, pm: &mut PM) -> Self {
+        $M::enable_bus_clock(pm);
+        $S::enable_bus_clock(pm);
+        {
+            let count = master.count_32();
+
+            // Disable the timer while we reconfigure it
+            count.ctrla.modify(|_, w| w.enable().clear_bit());
+            while count.status.read().syncbusy().bit_is_set() {}
+        }
+        Self {
+            freq: clock.freq(),
+            master,
+            slave,
+        }
+    }
+}
+        )+
+    }
+}
+
+#[cfg(feature = "samd21")]
+tc32! {
+    TimerCounter4_5: (TC4, TC5, tc4_tc5, Tc4Tc5Clock),
+}
+
 #[deprecated(
     since = "0.13.0",
     note = "`SpinTimer` is deprecated, and will be removed in a subsequent release."
@@ -6,6 +6,7 @@
 // http://ww1.microchip.com/downloads/en/AppNotes/Atmel-42261-SAM-D21-USB_Application-Note_AT06475.pdf
 
 use super::Descriptors;
+use crate::bus_clock::BusClock;
 use crate::calibration::{usb_transn_cal, usb_transp_cal, usb_trim_cal};
 use crate::clock;
 use crate::gpio::v2::{AlternateG, AnyPin, Pin, PA24, PA25};
@@ -13,15 +14,19 @@ use crate::pac;
 use crate::pac::usb::DEVICE;
 use crate::pac::{PM, USB};
 use crate::usb::devicedesc::DeviceDescBank;
-use core::cell::{Ref, RefCell, RefMut};
+use core::cell::{Cell, Ref, RefCell, RefMut};
 use core::marker::PhantomData;
 use core::mem;
+use core::sync::atomic::{AtomicU16, AtomicU8, Ordering};
 use cortex_m::interrupt::{free as disable_interrupts, Mutex};
 use cortex_m::singleton;
 use usb_device::bus::PollResult;
 use usb_device::endpoint::{EndpointAddress, EndpointType};
 use usb_device::{Result as UsbResult, UsbDirection, UsbError};
 
+/// `CLKCTRL.ID` value of the USB generic clock
+const CLKCTRL_ID_USB: u8 = 6;
+
 /// EndpointTypeBits represents valid values for the EPTYPE fields in
 /// the EPCFGn registers.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -31,7 +36,8 @@ pub enum EndpointTypeBits {
     Isochronous = 2,
     Bulk = 3,
     Interrupt = 4,
-    #[allow(unused)]
+    /// Both banks hold data of the same direction; the endpoint type is the
+    /// one of the other bank
     DualBank = 5,
 }
 
@@ -82,12 +88,32 @@ impl EPConfig {
 struct EndpointInfo {
     bank0: EPConfig,
     bank1: EPConfig,
+    /// Bank holding the next packet of a double-buffered endpoint
+    next_bank: Cell<usize>,
 }
 
 impl EndpointInfo {
     fn new() -> Self {
         Default::default()
     }
+
+    /// Returns the direction of the endpoint if it is double-buffered, using
+    /// both banks.
+    fn dual_bank(&self) -> Option<UsbDirection> {
+        if self.bank1.ep_type == EndpointTypeBits::DualBank {
+            Some(UsbDirection::Out)
+        } else if self.bank0.ep_type == EndpointTypeBits::DualBank {
+            Some(UsbDirection::In)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the values of the EPTYPE0 and EPTYPE1 fields of the EPCFG
+    /// register.
+    fn eptypes(&self) -> (u8, u8) {
+        (self.bank0.ep_type as u8, self.bank1.ep_type as u8)
+    }
 }
 
 /// AllEndpoints tracks the desired configuration of all endpoints managed
@@ -126,6 +152,52 @@ impl AllEndpoints {
         Err(UsbError::EndpointOverflow)
     }
 
+    /// Counts the endpoints still free in the direction `dir`.
+    fn count_free(&self, dir: UsbDirection) -> usize {
+        self.endpoints[1..]
+            .iter()
+            .filter(|ep| {
+                let bank = match dir {
+                    UsbDirection::Out => &ep.bank0,
+                    UsbDirection::In => &ep.bank1,
+                };
+                bank.ep_type == EndpointTypeBits::Disabled
+            })
+            .count()
+    }
+
+    /// Finds an endpoint whose banks are both free, to be double-buffered.
+    fn find_free_pair(&self) -> Option<usize> {
+        (1..8).find(|&idx| {
+            let ep = &self.endpoints[idx];
+            ep.bank0.ep_type == EndpointTypeBits::Disabled
+                && ep.bank1.ep_type == EndpointTypeBits::Disabled
+        })
+    }
+
+    /// Allocates both banks of the endpoint `idx` to the direction `dir`.
+    fn allocate_dual_endpoint(
+        &mut self,
+        dir: UsbDirection,
+        idx: usize,
+        ep_type: EndpointType,
+        allocated_size: u16,
+        max_packet_size: u16,
+        buffers: [*mut u8; 2],
+    ) -> EndpointAddress {
+        let ep = &mut self.endpoints[idx];
+        ep.bank0 = EPConfig::new(ep_type, allocated_size, max_packet_size, buffers[0]);
+        ep.bank1 = EPConfig::new(ep_type, allocated_size, max_packet_size, buffers[1]);
+        // The bank of the direction holds the endpoint type, while the other
+        // one is flagged as dual bank.
+        match dir {
+            UsbDirection::Out => ep.bank1.ep_type = EndpointTypeBits::DualBank,
+            UsbDirection::In => ep.bank0.ep_type = EndpointTypeBits::DualBank,
+        }
+        ep.next_bank.set(0);
+        EndpointAddress::from_parts(idx, dir)
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn allocate_endpoint(
         &mut self,
@@ -157,6 +229,12 @@ fn buffer() -> &'static mut [u8; BUFFER_SIZE] {
     singleton!(: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE] ).unwrap()
 }
 
+/// Endpoints left in each direction, and bytes of endpoint buffer memory
+/// left, published by [`Inner::alloc_ep`] as the bus is not reachable once
+/// moved into the `UsbBusAllocator`
+static ENDPOINTS_FREE: [AtomicU8; 2] = [AtomicU8::new(7), AtomicU8::new(7)];
+static BUFFER_FREE: AtomicU16 = AtomicU16::new(BUFFER_SIZE as u16);
+
 struct BufferAllocator {
     buffers: &'static mut [u8; BUFFER_SIZE],
     next_buf: u16,
@@ -170,6 +248,11 @@ impl BufferAllocator {
         }
     }
 
+    /// Returns the number of bytes not allocated yet.
+    fn available(&self) -> usize {
+        BUFFER_SIZE - self.next_buf as usize
+    }
+
     fn allocate_buffer(&mut self, size: u16) -> UsbResult<*mut u8> {
         debug_assert!(size & 1 == 0);
 
@@ -202,6 +285,13 @@ struct Inner {
     _dp_pad: Pin<PA25, AlternateG>,
     endpoints: RefCell<AllEndpoints>,
     buffers: RefCell<BufferAllocator>,
+    /// Whether the host has reset the device since it was enabled
+    reset_seen: Cell<bool>,
+    suspended: Cell<bool>,
+    /// Whether bulk endpoints are double-buffered
+    double_buffered: bool,
+    /// Generator of the USB generic clock, while it is stopped
+    clock_gen: Cell<u8>,
 }
 
 pub struct UsbBus {
@@ -254,6 +344,19 @@ impl<'a, T> Bank<'a, T> {
         self.address.index()
     }
 
+    /// Returns true if the bank belongs to an isochronous endpoint, whose
+    /// transfers have no handshake and are never retried.
+    #[inline]
+    fn is_isochronous(&self) -> bool {
+        let ep = &self.endpoints.endpoints[self.address.index()];
+        let config = if self.address.is_out() {
+            &ep.bank0
+        } else {
+            &ep.bank1
+        };
+        config.ep_type == EndpointTypeBits::Isochronous
+    }
+
     #[inline]
     fn config(&mut self) -> &EPConfig {
         let ep = &self.endpoints.endpoints[self.address.index()];
@@ -310,6 +413,13 @@ impl<'a> Bank<'a, InBank> {
         self.epintflag(self.index()).read().trcpt1().bit()
     }
 
+    /// Indicates if the host polled an isochronous endpoint whose bank was
+    /// not ready, in which case a zero length packet was sent for the frame.
+    #[inline]
+    fn is_transfer_failed(&self) -> bool {
+        self.epintflag(self.index()).read().trfail1().bit()
+    }
+
     /// Writes out endpoint configuration to its in-memory descriptor.
     fn flush_config(&mut self) {
         let config = *self.config();
@@ -324,8 +434,9 @@ impl<'a> Bank<'a, InBank> {
 
     /// Enables endpoint-specific interrupts.
     fn setup_ep_interrupts(&mut self) {
+        let isochronous = self.is_isochronous();
         self.epintenset(self.index())
-            .write(|w| w.trcpt1().set_bit());
+            .write(|w| w.trcpt1().set_bit().trfail1().bit(isochronous));
     }
 
     /// Prepares to transfer a series of bytes by copying the data into the
@@ -401,6 +512,24 @@ impl<'a> Bank<'a, OutBank> {
         self.epintflag(self.index()).read().trcpt0().bit()
     }
 
+    /// Indicates if an isochronous packet was received with a CRC error, or
+    /// could not be stored because the bank was still full.
+    #[inline]
+    fn is_transfer_failed(&self) -> bool {
+        self.epintflag(self.index()).read().trfail0().bit()
+    }
+
+    /// Drops the packet held by the bank of an isochronous endpoint after a
+    /// failed transfer, and makes the bank available for the next frame.
+    fn discard_failed(&mut self) {
+        let desc = self.desc_bank();
+        desc.clear_status();
+        desc.set_byte_count(0);
+        desc.set_multi_packet_size(0);
+        self.clear_transfer_complete();
+        self.set_ready(false);
+    }
+
     /// Returns true if a Received Setup interrupt has occurred.
     /// This indicates that the read buffer holds a SETUP packet.
     #[inline]
@@ -430,8 +559,15 @@ impl<'a> Bank<'a, OutBank> {
 
     /// Enables endpoint-specific interrupts.
     fn setup_ep_interrupts(&mut self) {
-        self.epintenset(self.index())
-            .write(|w| w.rxstp().set_bit().trcpt0().set_bit());
+        let isochronous = self.is_isochronous();
+        self.epintenset(self.index()).write(|w| {
+            w.rxstp()
+                .set_bit()
+                .trcpt0()
+                .set_bit()
+                .trfail0()
+                .bit(isochronous)
+        });
     }
 
     /// Copies data from the bank0 buffer to the provided array. The caller
@@ -532,6 +668,42 @@ impl<'a, T> Bank<'a, T> {
 
 impl Inner {
     ep!(epcfg, EPCFG, epcfg0, epcfg1, epcfg2, epcfg3, epcfg4, epcfg5, epcfg6, epcfg7);
+    ep!(
+        epstatusclr,
+        EPSTATUSCLR,
+        epstatusclr0,
+        epstatusclr1,
+        epstatusclr2,
+        epstatusclr3,
+        epstatusclr4,
+        epstatusclr5,
+        epstatusclr6,
+        epstatusclr7
+    );
+    ep!(
+        epstatusset,
+        EPSTATUSSET,
+        epstatusset0,
+        epstatusset1,
+        epstatusset2,
+        epstatusset3,
+        epstatusset4,
+        epstatusset5,
+        epstatusset6,
+        epstatusset7
+    );
+    ep!(
+        epintenset,
+        EPINTENSET,
+        epintenset0,
+        epintenset1,
+        epintenset2,
+        epintenset3,
+        epintenset4,
+        epintenset5,
+        epintenset6,
+        epintenset7
+    );
     ep!(
         epstatus, EPSTATUS, epstatus0, epstatus1, epstatus2, epstatus3, epstatus4, epstatus5,
         epstatus6, epstatus7
@@ -547,7 +719,10 @@ impl Inner {
         }
         let endpoints = self.endpoints.borrow();
 
-        if endpoints.endpoints[ep.index()].bank0.ep_type == EndpointTypeBits::Disabled {
+        let info = &endpoints.endpoints[ep.index()];
+        if info.bank0.ep_type == EndpointTypeBits::Disabled
+            || info.dual_bank() == Some(UsbDirection::In)
+        {
             return Err(UsbError::InvalidEndpoint);
         }
         Ok(Bank {
@@ -565,7 +740,10 @@ impl Inner {
         }
         let endpoints = self.endpoints.borrow();
 
-        if endpoints.endpoints[ep.index()].bank1.ep_type == EndpointTypeBits::Disabled {
+        let info = &endpoints.endpoints[ep.index()];
+        if info.bank1.ep_type == EndpointTypeBits::Disabled
+            || info.dual_bank() == Some(UsbDirection::Out)
+        {
             return Err(UsbError::InvalidEndpoint);
         }
         Ok(Bank {
@@ -587,18 +765,25 @@ impl UsbBus {
         _usb: USB,
     ) -> Self {
         dbgprint!("******** UsbBus::new\n");
-        pm.apbbmask.modify(|_, w| w.usb_().set_bit());
+        USB::enable_bus_clock(pm);
 
         let desc = RefCell::new(Descriptors::new());
+        let buffers = RefCell::new(BufferAllocator::new());
 
         let inner = Inner {
             _dm_pad: dm_pad.into().into_mode::<AlternateG>(),
             _dp_pad: dp_pad.into().into_mode::<AlternateG>(),
             desc,
-            buffers: RefCell::new(BufferAllocator::new()),
+            buffers,
             endpoints: RefCell::new(AllEndpoints::new()),
+            reset_seen: Cell::new(false),
+            suspended: Cell::new(false),
+            double_buffered: false,
+            clock_gen: Cell::new(0),
         };
 
+        inner.publish_usage();
+
         Self {
             inner: Mutex::new(RefCell::new(inner)),
         }
@@ -708,7 +893,12 @@ impl Inner {
         // Clear pending.
         usb.intflag
             .write(|w| unsafe { w.bits(usb.intflag.read().bits()) });
-        usb.intenset.write(|w| w.eorst().set_bit());
+        usb.intenset.write(|w| {
+            w.eorst().set_bit();
+            w.suspend().set_bit();
+            w.wakeup().set_bit();
+            w.eorsm().set_bit()
+        });
 
         // Configure the endpoints before we attach, as hosts may enumerate
         // before attempting a USB protocol reset.
@@ -771,18 +961,35 @@ impl Inner {
         if let Ok(mut bank) = self.bank1(EndpointAddress::from_parts(idx, UsbDirection::In)) {
             bank.flush_config();
         }
+        // The second bank of a double-buffered endpoint has no Bank of its own
+        if let Some(dir) = info.dual_bank() {
+            let (bank, config) = match dir {
+                UsbDirection::Out => (1, &info.bank1),
+                UsbDirection::In => (0, &info.bank0),
+            };
+            let mut desc = self.desc.borrow_mut();
+            let desc = desc.bank(idx, bank);
+            desc.set_address(config.addr as *mut u8);
+            desc.set_endpoint_size(config.max_packet_size);
+            desc.set_multi_packet_size(0);
+            desc.set_byte_count(0);
+            info.next_bank.set(0);
+        }
 
         // Set the endpoint type. At this point, the endpoint is enabled.
-        cfg.modify(|_, w| unsafe {
-            w.eptype0()
-                .bits(info.bank0.ep_type as u8)
-                .eptype1()
-                .bits(info.bank1.ep_type as u8)
-        });
+        let (eptype0, eptype1) = info.eptypes();
+        cfg.modify(|_, w| unsafe { w.eptype0().bits(eptype0).eptype1().bits(eptype1) });
     }
 
     /// setup_ep_interrupts enables interrupts for the given endpoint address.
     fn setup_ep_interrupts(&self, ep_addr: EndpointAddress) {
+        let dual = self.endpoints.borrow().endpoints[ep_addr.index()].dual_bank();
+        if dual == Some(ep_addr.direction()) {
+            // Either bank completes transfers
+            self.epintenset(ep_addr.index())
+                .write(|w| w.trcpt0().set_bit().trcpt1().set_bit());
+            return;
+        }
         if ep_addr.is_out() {
             if let Ok(mut bank) = self.bank0(ep_addr) {
                 bank.setup_ep_interrupts();
@@ -799,11 +1006,171 @@ impl Inner {
         self.flush_eps(FlushConfigMode::ProtocolReset);
     }
 
+    /// suspend is called by the USB HAL once the bus has been idle for 3ms.
+    /// The generic clock of the USB peripheral is stopped; the WAKEUP
+    /// interrupt, which does not need it, restarts it.
     fn suspend(&self) {
         dbgprint!("UsbBus::suspend\n");
+        self.suspended.set(true);
+        self.gate_clock();
     }
+
     fn resume(&self) {
         dbgprint!("UsbBus::resume\n");
+        if self.suspended.get() {
+            self.ungate_clock();
+            self.suspended.set(false);
+        }
+    }
+
+    /// Signal remote wakeup to the host, if the bus is suspended
+    fn remote_wakeup(&self) -> bool {
+        if !self.suspended.get() {
+            return false;
+        }
+        dbgprint!("UsbBus::remote_wakeup\n");
+        self.ungate_clock();
+        self.usb().ctrlb.modify(|_, w| w.uprsm().set_bit());
+        true
+    }
+
+    /// Stop the generic clock of the USB peripheral, remembering its
+    /// generator
+    fn gate_clock(&self) {
+        // SAFETY: Only the USB channel is accessed, and it belongs to the USB
+        // peripheral
+        let gclk = unsafe { &*pac::GCLK::ptr() };
+        // Select the USB channel with an 8-bit write to CLKCTRL.ID, to read
+        // its configuration (ref 15.6.4.1 Indirect Access)
+        unsafe { *(gclk.clkctrl.as_ptr() as *mut u8) = CLKCTRL_ID_USB };
+        while gclk.status.read().syncbusy().bit_is_set() {}
+        let gen = gclk.clkctrl.read().gen().bits();
+        self.clock_gen.set(gen);
+        gclk.clkctrl.write(|w| unsafe {
+            w.id().bits(CLKCTRL_ID_USB);
+            w.gen().bits(gen);
+            w.clken().clear_bit()
+        });
+        while gclk.status.read().syncbusy().bit_is_set() {}
+    }
+
+    /// Restart the generic clock of the USB peripheral
+    fn ungate_clock(&self) {
+        // SAFETY: Only the USB channel is accessed, and it belongs to the USB
+        // peripheral
+        let gclk = unsafe { &*pac::GCLK::ptr() };
+        gclk.clkctrl.write(|w| unsafe {
+            w.id().bits(CLKCTRL_ID_USB);
+            w.gen().bits(self.clock_gen.get());
+            w.clken().set_bit()
+        });
+        while gclk.status.read().syncbusy().bit_is_set() {}
+    }
+
+    /// Returns whether bank `bank` of the endpoint `idx` is ready: holding
+    /// data to send for IN endpoints, or received data for OUT endpoints.
+    fn is_bank_ready(&self, idx: usize, bank: usize) -> bool {
+        let status = self.epstatus(idx).read();
+        match bank {
+            0 => status.bk0rdy().bit(),
+            _ => status.bk1rdy().bit(),
+        }
+    }
+
+    /// Sets or clears the ready bit of bank `bank` of the endpoint `idx`,
+    /// acknowledging its previous transfer.
+    fn set_bank_ready(&self, idx: usize, bank: usize, ready: bool) {
+        self.epintflag(idx).write(|w| match bank {
+            0 => w.trcpt0().set_bit(),
+            _ => w.trcpt1().set_bit(),
+        });
+        if ready {
+            self.epstatusset(idx).write(|w| match bank {
+                0 => w.bk0rdy().set_bit(),
+                _ => w.bk1rdy().set_bit(),
+            });
+        } else {
+            self.epstatusclr(idx).write(|w| match bank {
+                0 => w.bk0rdy().set_bit(),
+                _ => w.bk1rdy().set_bit(),
+            });
+        }
+    }
+
+    /// Writes a packet to the next bank of a double-buffered IN endpoint.
+    /// Packets are sent in the order they are written.
+    fn write_dual(&self, ep: EndpointAddress, buf: &[u8]) -> UsbResult<usize> {
+        let idx = ep.index();
+        let endpoints = self.endpoints.borrow();
+        let info = &endpoints.endpoints[idx];
+        let bank = info.next_bank.get();
+        if self.is_bank_ready(idx, bank) {
+            // Both banks are waiting for the host
+            return Err(UsbError::WouldBlock);
+        }
+        let config = if bank == 0 { &info.bank0 } else { &info.bank1 };
+        let size = buf.len().min(config.allocated_size as usize);
+        {
+            let mut desc = self.desc.borrow_mut();
+            let desc = desc.bank(idx, bank);
+            unsafe {
+                buf.as_ptr()
+                    .copy_to_nonoverlapping(desc.get_address(), size);
+            }
+            desc.set_multi_packet_size(0);
+            desc.set_byte_count(size as u16);
+        }
+        self.set_bank_ready(idx, bank, true);
+        info.next_bank.set(bank ^ 1);
+        dbgprint!(
+            "UsbBus::write {} bytes to ep {:?} bank {}\n",
+            size,
+            ep,
+            bank
+        );
+        Ok(size)
+    }
+
+    /// Reads the oldest packet held by the banks of a double-buffered OUT
+    /// endpoint, and frees its bank for the next packet.
+    fn read_dual(&self, ep: EndpointAddress, buf: &mut [u8]) -> UsbResult<usize> {
+        let idx = ep.index();
+        let endpoints = self.endpoints.borrow();
+        let info = &endpoints.endpoints[idx];
+        let mut bank = info.next_bank.get();
+        if !self.is_bank_ready(idx, bank) {
+            // Follow the hardware if it filled the other bank first
+            if !self.is_bank_ready(idx, bank ^ 1) {
+                return Err(UsbError::WouldBlock);
+            }
+            bank ^= 1;
+        }
+        let size = {
+            let mut desc = self.desc.borrow_mut();
+            let desc = desc.bank(idx, bank);
+            let size = desc.get_byte_count() as usize;
+            if size <= buf.len() {
+                unsafe {
+                    desc.get_address()
+                        .copy_to_nonoverlapping(buf.as_mut_ptr(), size);
+                }
+            }
+            desc.set_byte_count(0);
+            desc.set_multi_packet_size(0);
+            size
+        };
+        self.set_bank_ready(idx, bank, false);
+        info.next_bank.set(bank ^ 1);
+        dbgprint!(
+            "UsbBus::read {} bytes from ep {:?} bank {}\n",
+            size,
+            ep,
+            bank
+        );
+        if size > buf.len() {
+            return Err(UsbError::BufferOverflow);
+        }
+        Ok(size)
     }
 
     fn alloc_ep(
@@ -831,6 +1198,27 @@ impl Inner {
 
         let buffer = self.buffers.borrow_mut().allocate_buffer(allocated_size)?;
 
+        // Bulk endpoints use both banks of an endpoint if one is free, so
+        // that a packet can be transferred while the previous one is handled.
+        if self.double_buffered && ep_type == EndpointType::Bulk && addr.is_none() {
+            let idx = self.endpoints.borrow().find_free_pair();
+            if let Some(idx) = idx {
+                if let Ok(second) = self.buffers.borrow_mut().allocate_buffer(allocated_size) {
+                    let addr = self.endpoints.borrow_mut().allocate_dual_endpoint(
+                        dir,
+                        idx,
+                        ep_type,
+                        allocated_size,
+                        max_packet_size,
+                        [buffer, second],
+                    );
+                    dbgprint!("alloc_ep double-buffered -> {:?}\n", addr);
+                    self.publish_usage();
+                    return Ok(addr);
+                }
+            }
+        }
+
         dbgprint!(
             "UsbBus::alloc_ep dir={:?} addr={:?} type={:?} max_packet_size={} interval={}\n",
             dir,
@@ -858,10 +1246,22 @@ impl Inner {
         )?;
 
         dbgprint!("alloc_ep -> {:?}\n", addr);
+        drop(endpoints);
+        self.publish_usage();
 
         Ok(addr)
     }
 
+    /// Publishes the endpoints and buffer memory left, for
+    /// [`UsbBus::endpoints_available`] and [`UsbBus::buffer_available`].
+    fn publish_usage(&self) {
+        let endpoints = self.endpoints.borrow();
+        for (idx, dir) in [UsbDirection::Out, UsbDirection::In].iter().enumerate() {
+            ENDPOINTS_FREE[idx].store(endpoints.count_free(*dir) as u8, Ordering::Relaxed);
+        }
+        BUFFER_FREE.store(self.buffers.borrow().available() as u16, Ordering::Relaxed);
+    }
+
     fn set_device_address(&self, addr: u8) {
         dbgprint!("UsbBus::set_device_address addr={}\n", addr);
         self.usb()
@@ -879,15 +1279,36 @@ impl Inner {
 
     fn poll(&self) -> PollResult {
         let intflags = self.usb().intflag.read();
+        if intflags.wakeup().bit() || intflags.eorsm().bit() {
+            // The generic clock must run before the flags can be cleared
+            if self.suspended.get() {
+                self.ungate_clock();
+            }
+            self.usb()
+                .intflag
+                .write(|w| w.wakeup().set_bit().eorsm().set_bit());
+            if self.suspended.get() {
+                dbgprint!("PollResult::Resume\n");
+                return PollResult::Resume;
+            }
+        }
         if intflags.eorst().bit() {
             // end of reset interrupt
             self.usb().intflag.write(|w| w.eorst().set_bit());
+            self.reset_seen.set(true);
             dbgprint!("PollResult::Reset\n");
             return PollResult::Reset;
         }
-        // As the suspend & wakup interrupts/states cannot distinguish between
-        // unconnected & unsuspended, we do not handle them to avoid spurious
-        // transitions.
+        if intflags.suspend().bit() {
+            self.usb().intflag.write(|w| w.suspend().set_bit());
+            // The bus is idle both when the host suspends it and when no host
+            // is connected, so only report a suspension once the host has
+            // reset the device
+            if self.reset_seen.get() && !self.suspended.get() {
+                dbgprint!("PollResult::Suspend\n");
+                return PollResult::Suspend;
+            }
+        }
 
         let intbits = self.usb().epintsmry.read().bits();
         if intbits == 0 {
@@ -906,28 +1327,60 @@ impl Inner {
 
             let idx = ep as usize;
 
-            let bank1 = self
-                .bank1(EndpointAddress::from_parts(idx, UsbDirection::In))
-                .unwrap();
-            if bank1.is_transfer_complete() {
-                bank1.clear_transfer_complete();
-                dbgprint!("ep {} WRITE DONE\n", ep);
-                ep_in_complete |= mask;
-                // Continuing (and hence not setting masks to indicate complete
-                // OUT transfers) is necessary for operation to proceed beyond
-                // the device-address + descriptor stage. The authors suspect a
-                // deadlock caused by waiting on a write when handling a read
-                // somewhere in an underlying class or control crate, but we
-                // can't be sure. Either way, if a write has finished, we only
-                // set the flag for a completed write on that endpoint index.
-                // Future polls will handle the reads.
+            let dual = self.endpoints.borrow().endpoints[idx].dual_bank();
+            if let Some(dir) = dual {
+                let flags = self.epintflag(idx).read();
+                if flags.trcpt0().bit() || flags.trcpt1().bit() {
+                    match dir {
+                        UsbDirection::In => {
+                            self.epintflag(idx)
+                                .write(|w| w.trcpt0().set_bit().trcpt1().set_bit());
+                            dbgprint!("ep {} WRITE DONE\n", ep);
+                            ep_in_complete |= mask;
+                        }
+                        UsbDirection::Out => {
+                            // Cleared by read_dual, once the bank is read
+                            dbgprint!("ep {} READABLE\n", ep);
+                            ep_out |= mask;
+                        }
+                    }
+                }
                 continue;
             }
-            drop(bank1);
 
-            let bank0 = self
-                .bank0(EndpointAddress::from_parts(idx, UsbDirection::Out))
-                .unwrap();
+            // Either bank may be disabled, if the endpoint number is only
+            // used in one direction.
+            if let Ok(bank1) = self.bank1(EndpointAddress::from_parts(idx, UsbDirection::In)) {
+                // An isochronous IN transfer that failed still used up its frame,
+                // so the class is told it can queue the data of the next one.
+                if bank1.is_transfer_complete()
+                    || (bank1.is_isochronous() && bank1.is_transfer_failed())
+                {
+                    bank1.clear_transfer_complete();
+                    dbgprint!("ep {} WRITE DONE\n", ep);
+                    ep_in_complete |= mask;
+                    // Continuing (and hence not setting masks to indicate complete
+                    // OUT transfers) is necessary for operation to proceed beyond
+                    // the device-address + descriptor stage. The authors suspect a
+                    // deadlock caused by waiting on a write when handling a read
+                    // somewhere in an underlying class or control crate, but we
+                    // can't be sure. Either way, if a write has finished, we only
+                    // set the flag for a completed write on that endpoint index.
+                    // Future polls will handle the reads.
+                    continue;
+                }
+            }
+
+            let mut bank0 = match self.bank0(EndpointAddress::from_parts(idx, UsbDirection::Out)) {
+                Ok(bank0) => bank0,
+                Err(_) => continue,
+            };
+            if bank0.is_isochronous() && bank0.is_transfer_failed() {
+                // Corrupted isochronous packets are never retried, drop them
+                dbgprint!("ep {} ISO OUT FAILED\n", ep);
+                bank0.discard_failed();
+                continue;
+            }
             if bank0.received_setup_interrupt() {
                 dbgprint!("ep {} GOT SETUP\n", ep);
                 ep_setup |= mask;
@@ -951,9 +1404,21 @@ impl Inner {
     }
 
     fn write(&self, ep: EndpointAddress, buf: &[u8]) -> UsbResult<usize> {
+        if ep.is_in()
+            && self.endpoints.borrow().endpoints[ep.index()]
+                .dual_bank()
+                .is_some()
+        {
+            return self.write_dual(ep, buf);
+        }
         let mut bank = self.bank1(ep)?;
 
-        if bank.is_ready() {
+        if bank.is_ready() && bank.is_isochronous() {
+            // The host did not pick up the data during its frame. Isochronous
+            // data is only useful in its own frame, so replace it.
+            dbgprint!("UsbBus::write ep {:?} dropping stale frame\n", ep);
+            bank.set_ready(false);
+        } else if bank.is_ready() {
             // Waiting for the host to pick up the existing data
             dbgprint!(
                 "UsbBus::write {} bytes {:?} to ep {:?} -> BUSY trcpt1={}\n",
@@ -982,6 +1447,13 @@ impl Inner {
     }
 
     fn read(&self, ep: EndpointAddress, buf: &mut [u8]) -> UsbResult<usize> {
+        if ep.is_out()
+            && self.endpoints.borrow().endpoints[ep.index()]
+                .dual_bank()
+                .is_some()
+        {
+            return self.read_dual(ep, buf);
+        }
         let mut bank = self.bank0(ep)?;
         let rxstp = bank.received_setup_interrupt();
 
@@ -1035,6 +1507,58 @@ impl Inner {
 }
 
 impl UsbBus {
+    /// Signal remote wakeup to the host
+    ///
+    /// While the bus is suspended, the device can wake the host up, such as
+    /// when a key is pressed, if the host enabled this feature: see
+    /// `UsbDevice::remote_wakeup_enabled`. Returns `false` if the bus is not
+    /// suspended. The host then resumes the bus, and `UsbDevice::poll`
+    /// reports the device as configured again.
+    pub fn remote_wakeup(&self) -> bool {
+        disable_interrupts(|cs| self.inner.borrow(cs).borrow().remote_wakeup())
+    }
+
+    /// Enables or disables the double-buffering of bulk endpoints
+    ///
+    /// Double-buffered endpoints use both banks of an endpoint number, one
+    /// transferring a packet while the other is read or written, raising the
+    /// throughput of bulk transfers. This halves the number of endpoints
+    /// available, so it is disabled by default. It must be enabled before the
+    /// classes are created.
+    pub fn set_double_buffered(&self, enable: bool) {
+        disable_interrupts(|cs| self.inner.borrow(cs).borrow_mut().double_buffered = enable)
+    }
+
+    /// Returns the number of endpoints still available in the direction
+    /// `dir`, besides the control endpoint 0
+    ///
+    /// Classes panic when they can't allocate their endpoints, so composite
+    /// devices can check that enough endpoints remain before creating them.
+    /// Each double-buffered bulk endpoint uses up an endpoint in both
+    /// directions. This function can be called while the bus is owned by the
+    /// `UsbBusAllocator`.
+    pub fn endpoints_available(dir: UsbDirection) -> usize {
+        let idx = match dir {
+            UsbDirection::Out => 0,
+            UsbDirection::In => 1,
+        };
+        ENDPOINTS_FREE[idx].load(Ordering::Relaxed) as usize
+    }
+
+    /// Returns the number of bytes of endpoint buffer memory still available
+    ///
+    /// Each endpoint takes its maximum packet size, rounded up to a power of
+    /// two, twice if it is double-buffered. This function can be called while
+    /// the bus is owned by the `UsbBusAllocator`.
+    pub fn buffer_available() -> usize {
+        BUFFER_FREE.load(Ordering::Relaxed) as usize
+    }
+
+    /// Returns whether the host has suspended the bus
+    pub fn is_suspended(&self) -> bool {
+        disable_interrupts(|cs| self.inner.borrow(cs).borrow().suspended.get())
+    }
+
     /// Enables the Start Of Frame (SOF) interrupt
     pub fn enable_sof_interrupt(&self) {
         disable_interrupts(|cs| self.inner.borrow(cs).borrow_mut().sof_interrupt(true))
@@ -1111,3 +1635,21 @@ impl usb_device::bus::UsbBus for UsbBus {
         disable_interrupts(|cs| self.inner.borrow(cs).borrow().is_stalled(ep))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dual_bank_eptypes() {
+        let mut endpoints = AllEndpoints::new();
+        let buffers = [core::ptr::null_mut(); 2];
+        endpoints.allocate_dual_endpoint(UsbDirection::Out, 1, EndpointType::Bulk, 64, 64, buffers);
+        endpoints.allocate_dual_endpoint(UsbDirection::In, 2, EndpointType::Bulk, 64, 64, buffers);
+        // The type goes in the field of the direction, the other one is 0x5
+        assert_eq!(endpoints.endpoints[1].eptypes(), (3, 5));
+        assert_eq!(endpoints.endpoints[1].dual_bank(), Some(UsbDirection::Out));
+        assert_eq!(endpoints.endpoints[2].eptypes(), (5, 3));
+        assert_eq!(endpoints.endpoints[2].dual_bank(), Some(UsbDirection::In));
+    }
+}
@@ -0,0 +1,1186 @@
+//! USB host mode
+//!
+//! In host mode, the USB peripheral drives the bus of a single full speed or
+//! low speed device, such as a keyboard or a MIDI controller. Hubs are not
+//! supported. The board must supply the 5 V `VBUS` of the device; [`UsbHost`]
+//! only signals to the peripheral that `VBUS` is present.
+//!
+//! Transfers go through up to 8 pipes, each bound to an endpoint of the
+//! device. Control transfers are blocking, and time out after
+//! [`TIMEOUT_FRAMES`] frames, while bulk and interrupt transfers are
+//! non-blocking, to be polled from the main loop.
+//!
+//! ```no_run
+//! use atsamd_hal::usb::host::{descriptors, Descriptor, Event, UsbHost};
+//!
+//! let mut host = UsbHost::new(&usb_clock, &mut peripherals.PM, pins.usb_dm, pins.usb_dp, peripherals.USB);
+//! host.enable();
+//!
+//! loop {
+//!     if let Event::Attached = host.poll() {
+//!         delay.delay_ms(100u8);
+//!         host.reset_bus().unwrap();
+//!         let mut device = host.enumerate(1).unwrap();
+//!         let mut config = [0; 256];
+//!         let len = host.configuration_descriptor(&mut device, &mut config).unwrap();
+//!         for descriptor in descriptors(&config[..len]) {
+//!             if let Descriptor::Endpoint(endpoint) = descriptor {
+//!                 let pipe = host.endpoint_pipe(&device, &endpoint).unwrap();
+//!             }
+//!         }
+//!         host.set_configuration(&mut device, config[5]).unwrap();
+//!     }
+//! }
+//! ```
+
+use crate::bus_clock::BusClock;
+use crate::calibration::{usb_transn_cal, usb_transp_cal, usb_trim_cal};
+use crate::clock;
+use crate::gpio::v2::{AlternateG, AnyPin, Pin, PA24, PA25};
+use crate::pac;
+use crate::pac::usb::HOST;
+use crate::pac::{PM, USB};
+use bitfield::bitfield;
+use core::mem;
+use core::ptr::null_mut;
+use cortex_m::singleton;
+
+/// Number of frames, lasting 1 ms each, after which a control transfer is
+/// abandoned
+pub const TIMEOUT_FRAMES: u16 = 500;
+
+/// Number of polls after which a bus reset, lasting 50 ms, is abandoned,
+/// leaving a wide margin at the highest CPU frequency
+const RESET_TIMEOUT_POLLS: u32 = 10_000_000;
+
+/// Number of pipes of the peripheral
+const NUM_PIPES: usize = 8;
+
+/// Size of the memory shared by the pipe buffers
+const BUFFER_SIZE: usize = 1024;
+
+/// `PSTATUS.DTGL` bit
+const PSTATUS_DTGL: u8 = 0x01;
+/// `PSTATUS.PFREEZE` bit
+const PSTATUS_PFREEZE: u8 = 0x10;
+/// `PSTATUS.BK0RDY` bit
+const PSTATUS_BK0RDY: u8 = 0x40;
+
+/// All the `PINTFLAG` bits
+const PINTFLAG_ALL: u8 = 0x3f;
+
+bitfield! {
+    struct PckSize(u32);
+    impl Debug;
+    pub byte_count, set_byte_count: 13, 0;
+    pub multi_packet_size, set_multi_packet_size: 27, 14;
+    pub size, set_size: 30, 28;
+    pub auto_zlp, set_auto_zlp : 31;
+}
+
+bitfield! {
+    struct CtrlPipe(u16);
+    impl Debug;
+    pub pdaddr, set_pdaddr: 6, 0;
+    pub pepnum, set_pepnum: 11, 8;
+    pub permax, set_permax: 15, 12;
+}
+
+bitfield! {
+    struct StatusPipe(u16);
+    impl Debug;
+    pub dtgler, set_dtgler: 0;
+    pub dapider, set_dapider: 1;
+    pub pider, set_pider: 2;
+    pub touter, set_touter: 3;
+    pub crc16er, set_crc16er: 4;
+    pub ercnt, set_ercnt: 7, 5;
+}
+
+/// Bank of a pipe descriptor, read by the peripheral from RAM
+#[repr(C)]
+#[derive(Debug)]
+struct PipeDescBank {
+    /// pipe data buffer, must be 32-bit aligned
+    addr: *mut u8,
+    pcksize: PckSize,
+    extreg: u16,
+    status_bk: u8,
+    _reserved: u8,
+    ctrl_pipe: CtrlPipe,
+    status_pipe: StatusPipe,
+}
+
+impl PipeDescBank {
+    fn new() -> Self {
+        debug_assert_eq!(16, mem::size_of::<PipeDescBank>());
+        Self {
+            addr: null_mut(),
+            pcksize: PckSize(0),
+            extreg: 0,
+            status_bk: 0,
+            _reserved: 0,
+            ctrl_pipe: CtrlPipe(0),
+            status_pipe: StatusPipe(0),
+        }
+    }
+}
+
+// SAFETY: The buffer pointed to by `addr` is only accessed through the pipe
+// owning the descriptor
+unsafe impl Send for PipeDescBank {}
+
+/// Pipe descriptor, made of two banks; only bank 0 is used
+#[repr(C)]
+#[derive(Debug)]
+struct PipeDescriptor {
+    bank: [PipeDescBank; 2],
+}
+
+impl PipeDescriptor {
+    fn new() -> Self {
+        Self {
+            bank: [PipeDescBank::new(), PipeDescBank::new()],
+        }
+    }
+}
+
+fn descriptors_table() -> &'static mut [PipeDescriptor; NUM_PIPES] {
+    singleton!(: [PipeDescriptor; NUM_PIPES] = [
+        PipeDescriptor::new(),
+        PipeDescriptor::new(),
+        PipeDescriptor::new(),
+        PipeDescriptor::new(),
+        PipeDescriptor::new(),
+        PipeDescriptor::new(),
+        PipeDescriptor::new(),
+        PipeDescriptor::new(),
+    ])
+    .unwrap()
+}
+
+fn buffer() -> &'static mut [u8; BUFFER_SIZE] {
+    singleton!(: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE] ).unwrap()
+}
+
+/// Errors of the USB host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The device refused the request with a `STALL` handshake
+    Stall,
+    /// The transfer failed after retries, because of CRC, PID or data toggle
+    /// errors, or because the device did not answer
+    Transfer,
+    /// The transfer did not complete before [`TIMEOUT_FRAMES`] frames, or the
+    /// bus reset never completed
+    Timeout,
+    /// The device was disconnected
+    Detached,
+    /// The device returned more data than requested
+    BufferOverflow,
+    /// All pipes, or the memory of their buffers, are in use
+    NoPipe,
+    /// The device returned an invalid descriptor
+    InvalidDescriptor,
+    /// The maximum packet size is not supported
+    Unsupported,
+}
+
+/// Events reported by [`UsbHost::poll`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// Nothing happened
+    None,
+    /// A device was connected; reset the bus with [`UsbHost::reset_bus`]
+    /// after a debounce delay of 100 ms, then enumerate the device
+    Attached,
+    /// The device was disconnected. Its pipes should be freed.
+    Detached,
+}
+
+/// Speed of the connected device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Speed {
+    /// 12 Mbit/s
+    Full,
+    /// 1.5 Mbit/s
+    Low,
+}
+
+/// Transfer type of a pipe, the values of `PCFG.PTYPE`
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipeType {
+    Control = 1,
+    Isochronous = 2,
+    Bulk = 3,
+    Interrupt = 4,
+}
+
+impl PipeType {
+    /// Decode the `bmAttributes` field of an endpoint descriptor
+    pub fn from_attributes(attributes: u8) -> PipeType {
+        match attributes & 0x03 {
+            0 => PipeType::Control,
+            1 => PipeType::Isochronous,
+            2 => PipeType::Bulk,
+            _ => PipeType::Interrupt,
+        }
+    }
+}
+
+/// Token of a transaction, the values of `PCFG.PTOKEN`
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Setup = 0,
+    In = 1,
+    Out = 2,
+}
+
+/// Standard request `GET_DESCRIPTOR`
+pub const GET_DESCRIPTOR: u8 = 6;
+/// Standard request `SET_ADDRESS`
+pub const SET_ADDRESS: u8 = 5;
+/// Standard request `SET_CONFIGURATION`
+pub const SET_CONFIGURATION: u8 = 9;
+
+/// Descriptor type of device descriptors
+pub const DESCRIPTOR_DEVICE: u8 = 1;
+/// Descriptor type of configuration descriptors
+pub const DESCRIPTOR_CONFIGURATION: u8 = 2;
+/// Descriptor type of interface descriptors
+pub const DESCRIPTOR_INTERFACE: u8 = 4;
+/// Descriptor type of endpoint descriptors
+pub const DESCRIPTOR_ENDPOINT: u8 = 5;
+
+/// Setup packet, starting a control transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetupPacket {
+    /// `bmRequestType`; bit 7 is set for device-to-host requests
+    pub request_type: u8,
+    /// `bRequest`
+    pub request: u8,
+    /// `wValue`
+    pub value: u16,
+    /// `wIndex`
+    pub index: u16,
+    /// `wLength`, the length of the data stage
+    pub length: u16,
+}
+
+impl SetupPacket {
+    /// Standard `GET_DESCRIPTOR` request, addressed to the device
+    pub fn get_descriptor(descriptor_type: u8, index: u8, length: u16) -> Self {
+        Self {
+            request_type: 0x80,
+            request: GET_DESCRIPTOR,
+            value: (descriptor_type as u16) << 8 | index as u16,
+            index: 0,
+            length,
+        }
+    }
+
+    /// Standard `SET_ADDRESS` request
+    pub fn set_address(address: u8) -> Self {
+        Self {
+            request_type: 0x00,
+            request: SET_ADDRESS,
+            value: address as u16,
+            index: 0,
+            length: 0,
+        }
+    }
+
+    /// Standard `SET_CONFIGURATION` request
+    pub fn set_configuration(value: u8) -> Self {
+        Self {
+            request_type: 0x00,
+            request: SET_CONFIGURATION,
+            value: value as u16,
+            index: 0,
+            length: 0,
+        }
+    }
+
+    /// Return whether the data stage goes from the device to the host
+    pub fn is_in(&self) -> bool {
+        self.request_type & 0x80 != 0
+    }
+
+    /// Encode the packet, in little endian order
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let value = self.value.to_le_bytes();
+        let index = self.index.to_le_bytes();
+        let length = self.length.to_le_bytes();
+        [
+            self.request_type,
+            self.request,
+            value[0],
+            value[1],
+            index[0],
+            index[1],
+            length[0],
+            length[1],
+        ]
+    }
+}
+
+fn le16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+/// Device descriptor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceDescriptor {
+    /// `bcdUSB`
+    pub usb_version: u16,
+    /// `bDeviceClass`
+    pub class: u8,
+    /// `bDeviceSubClass`
+    pub subclass: u8,
+    /// `bDeviceProtocol`
+    pub protocol: u8,
+    /// `bMaxPacketSize0`, the maximum packet size of endpoint 0
+    pub max_packet_size: u8,
+    /// `idVendor`
+    pub vendor_id: u16,
+    /// `idProduct`
+    pub product_id: u16,
+    /// `bcdDevice`
+    pub device_version: u16,
+    /// `bNumConfigurations`
+    pub num_configurations: u8,
+}
+
+impl DeviceDescriptor {
+    /// Length of a device descriptor
+    pub const LENGTH: usize = 18;
+
+    /// Decode a device descriptor
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < Self::LENGTH || bytes[1] != DESCRIPTOR_DEVICE {
+            return Err(Error::InvalidDescriptor);
+        }
+        Ok(Self {
+            usb_version: le16(bytes, 2),
+            class: bytes[4],
+            subclass: bytes[5],
+            protocol: bytes[6],
+            max_packet_size: bytes[7],
+            vendor_id: le16(bytes, 8),
+            product_id: le16(bytes, 10),
+            device_version: le16(bytes, 12),
+            num_configurations: bytes[17],
+        })
+    }
+}
+
+/// Interface descriptor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterfaceDescriptor {
+    /// `bInterfaceNumber`
+    pub number: u8,
+    /// `bAlternateSetting`
+    pub alternate_setting: u8,
+    /// `bNumEndpoints`
+    pub num_endpoints: u8,
+    /// `bInterfaceClass`
+    pub class: u8,
+    /// `bInterfaceSubClass`
+    pub subclass: u8,
+    /// `bInterfaceProtocol`
+    pub protocol: u8,
+}
+
+/// Endpoint descriptor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EndpointDescriptor {
+    /// `bEndpointAddress`; bit 7 is set for IN endpoints
+    pub address: u8,
+    /// Transfer type, from `bmAttributes`
+    pub pipe_type: PipeType,
+    /// `wMaxPacketSize`
+    pub max_packet_size: u16,
+    /// `bInterval`, the polling interval of interrupt endpoints, in frames
+    pub interval: u8,
+}
+
+impl EndpointDescriptor {
+    /// Return whether data flows from the device to the host
+    pub fn is_in(&self) -> bool {
+        self.address & 0x80 != 0
+    }
+}
+
+/// Descriptor found in a configuration descriptor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Descriptor<'a> {
+    /// Interface descriptor
+    Interface(InterfaceDescriptor),
+    /// Endpoint descriptor
+    Endpoint(EndpointDescriptor),
+    /// Any other descriptor, with its type and its bytes
+    Other(u8, &'a [u8]),
+}
+
+/// Iterator over the descriptors making a configuration descriptor
+///
+/// Iteration stops at the first truncated descriptor.
+#[derive(Debug, Clone)]
+pub struct Descriptors<'a> {
+    bytes: &'a [u8],
+}
+
+/// Iterate over the descriptors of a configuration descriptor, as returned
+/// by [`UsbHost::configuration_descriptor`]
+pub fn descriptors(bytes: &[u8]) -> Descriptors<'_> {
+    Descriptors { bytes }
+}
+
+impl<'a> Iterator for Descriptors<'a> {
+    type Item = Descriptor<'a>;
+
+    fn next(&mut self) -> Option<Descriptor<'a>> {
+        let len = *self.bytes.first()? as usize;
+        if len < 2 || len > self.bytes.len() {
+            return None;
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        let descriptor = match bytes[1] {
+            DESCRIPTOR_INTERFACE if len >= 9 => Descriptor::Interface(InterfaceDescriptor {
+                number: bytes[2],
+                alternate_setting: bytes[3],
+                num_endpoints: bytes[4],
+                class: bytes[5],
+                subclass: bytes[6],
+                protocol: bytes[7],
+            }),
+            DESCRIPTOR_ENDPOINT if len >= 7 => Descriptor::Endpoint(EndpointDescriptor {
+                address: bytes[2],
+                pipe_type: PipeType::from_attributes(bytes[3]),
+                max_packet_size: le16(bytes, 4) & 0x7ff,
+                interval: bytes[6],
+            }),
+            descriptor_type => Descriptor::Other(descriptor_type, bytes),
+        };
+        Some(descriptor)
+    }
+}
+
+/// Encode a maximum packet size in `PCKSIZE.SIZE`, returning the code and the
+/// size of the buffer receiving the packets
+fn size_code(max_packet_size: u16) -> Option<(u32, u16)> {
+    let code = match max_packet_size {
+        1..=8 => (0, 8),
+        9..=16 => (1, 16),
+        17..=32 => (2, 32),
+        33..=64 => (3, 64),
+        65..=128 => (4, 128),
+        129..=256 => (5, 256),
+        257..=512 => (6, 512),
+        513..=1023 => (7, 1024),
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Pipe bound to an endpoint of the device
+///
+/// Pipes are allocated by [`UsbHost::alloc_pipe`], and returned with
+/// [`UsbHost::free_pipe`].
+#[derive(Debug)]
+pub struct Pipe {
+    index: usize,
+    pipe_type: PipeType,
+    endpoint: u8,
+    max_packet_size: u16,
+    capacity: u16,
+    buffer: *mut u8,
+    token: Token,
+    busy: bool,
+}
+
+// SAFETY: The buffer is owned by the pipe, and only accessed through it
+unsafe impl Send for Pipe {}
+
+impl Pipe {
+    /// Transfer type of the pipe
+    pub fn pipe_type(&self) -> PipeType {
+        self.pipe_type
+    }
+
+    /// Address of the endpoint, with bit 7 set for IN endpoints
+    pub fn endpoint(&self) -> u8 {
+        self.endpoint
+    }
+
+    /// Maximum packet size of the endpoint
+    pub fn max_packet_size(&self) -> u16 {
+        self.max_packet_size
+    }
+
+    /// Largest multiple of the maximum packet size fitting in the buffer
+    fn chunk(&self) -> u16 {
+        self.capacity - self.capacity % self.max_packet_size
+    }
+}
+
+/// Device enumerated by [`UsbHost::enumerate`]
+#[derive(Debug)]
+pub struct Device {
+    /// Address assigned to the device
+    pub address: u8,
+    /// Speed of the device
+    pub speed: Speed,
+    /// Device descriptor
+    pub descriptor: DeviceDescriptor,
+    /// Pipe of the default control endpoint
+    pub control: Pipe,
+}
+
+/// Generate a method that allows returning the pipe register for a given
+/// pipe index.
+macro_rules! pipe {
+    ($name:ident, $type:ident, $e0:ident, $e1:ident, $e2:ident,
+     $e3:ident, $e4:ident, $e5:ident, $e6:ident, $e7:ident) => {
+        #[allow(unused)]
+        #[inline]
+        fn $name(&self, pipe: usize) -> &pac::usb::host::$type {
+            match pipe {
+                0 => &self.usb().$e0,
+                1 => &self.usb().$e1,
+                2 => &self.usb().$e2,
+                3 => &self.usb().$e3,
+                4 => &self.usb().$e4,
+                5 => &self.usb().$e5,
+                6 => &self.usb().$e6,
+                7 => &self.usb().$e7,
+                _ => unreachable!(),
+            }
+        }
+    };
+}
+
+/// USB peripheral in host mode
+pub struct UsbHost {
+    _usb: USB,
+    _dm_pad: Pin<PA24, AlternateG>,
+    _dp_pad: Pin<PA25, AlternateG>,
+    desc: &'static mut [PipeDescriptor; NUM_PIPES],
+    buffers: &'static mut [u8; BUFFER_SIZE],
+    next_buf: usize,
+    allocated: u8,
+}
+
+impl UsbHost {
+    /// Take the USB peripheral, for host mode
+    ///
+    /// Like [`UsbBus`](super::UsbBus), the peripheral needs a 48 MHz
+    /// [`UsbClock`](clock::UsbClock).
+    ///
+    /// # Panics
+    ///
+    /// The descriptors and buffers of the pipes are statically allocated, so
+    /// this function panics if it is called more than once.
+    pub fn new(
+        _clock: &clock::UsbClock,
+        pm: &mut PM,
+        dm_pad: impl AnyPin<Id = PA24>,
+        dp_pad: impl AnyPin<Id = PA25>,
+        usb: USB,
+    ) -> Self {
+        USB::enable_bus_clock(pm);
+        Self {
+            _usb: usb,
+            _dm_pad: dm_pad.into().into_mode::<AlternateG>(),
+            _dp_pad: dp_pad.into().into_mode::<AlternateG>(),
+            desc: descriptors_table(),
+            buffers: buffer(),
+            next_buf: 0,
+            allocated: 0,
+        }
+    }
+
+    fn usb(&self) -> &HOST {
+        unsafe { (*USB::ptr()).host() }
+    }
+
+    pipe!(pcfg, PCFG, pcfg0, pcfg1, pcfg2, pcfg3, pcfg4, pcfg5, pcfg6, pcfg7);
+    pipe!(
+        binterval, BINTERVAL, binterval0, binterval1, binterval2, binterval3, binterval4,
+        binterval5, binterval6, binterval7
+    );
+    pipe!(
+        pstatusclr,
+        PSTATUSCLR,
+        pstatusclr0,
+        pstatusclr1,
+        pstatusclr2,
+        pstatusclr3,
+        pstatusclr4,
+        pstatusclr5,
+        pstatusclr6,
+        pstatusclr7
+    );
+    pipe!(
+        pstatusset,
+        PSTATUSSET,
+        pstatusset0,
+        pstatusset1,
+        pstatusset2,
+        pstatusset3,
+        pstatusset4,
+        pstatusset5,
+        pstatusset6,
+        pstatusset7
+    );
+    pipe!(
+        pintflag, PINTFLAG, pintflag0, pintflag1, pintflag2, pintflag3, pintflag4, pintflag5,
+        pintflag6, pintflag7
+    );
+
+    /// Enable the peripheral in host mode, and start detecting devices
+    pub fn enable(&mut self) {
+        let usb = self.usb();
+        usb.ctrla.modify(|_, w| w.swrst().set_bit());
+        while usb.syncbusy.read().swrst().bit_is_set() {}
+
+        let addr = self.desc.as_ptr() as u32;
+        usb.descadd.write(|w| unsafe { w.descadd().bits(addr) });
+        usb.padcal.modify(|_, w| unsafe {
+            w.transn().bits(usb_transn_cal());
+            w.transp().bits(usb_transp_cal());
+            w.trim().bits(usb_trim_cal())
+        });
+        usb.qosctrl.modify(|_, w| {
+            w.dqos().bits(0b11);
+            w.cqos().bits(0b11)
+        });
+        usb.ctrla.modify(|_, w| {
+            w.mode().host();
+            w.runstdby().set_bit()
+        });
+        usb.ctrlb.modify(|_, w| w.spdconf().normal());
+
+        usb.ctrla.modify(|_, w| w.enable().set_bit());
+        while usb.syncbusy.read().enable().bit_is_set() {}
+
+        // Clear pending.
+        usb.intflag
+            .write(|w| unsafe { w.bits(usb.intflag.read().bits()) });
+        usb.intenset
+            .write(|w| w.dconn().set_bit().ddisc().set_bit());
+
+        // The connection of devices is only detected once VBUS is reported
+        usb.ctrlb.modify(|_, w| w.vbusok().set_bit());
+    }
+
+    /// Disable the peripheral
+    pub fn disable(&mut self) {
+        let usb = self.usb();
+        usb.ctrlb.modify(|_, w| w.vbusok().clear_bit());
+        usb.ctrla.modify(|_, w| w.enable().clear_bit());
+        while usb.syncbusy.read().enable().bit_is_set() {}
+    }
+
+    /// Report the connection and disconnection of devices
+    ///
+    /// This function can be called from the USB interrupt handler.
+    pub fn poll(&mut self) -> Event {
+        let usb = self.usb();
+        let flags = usb.intflag.read();
+        if flags.ddisc().bit() {
+            usb.intflag.write(|w| w.ddisc().set_bit().dconn().set_bit());
+            usb.ctrlb.modify(|_, w| w.sofe().clear_bit());
+            return Event::Detached;
+        }
+        if flags.dconn().bit() {
+            usb.intflag.write(|w| w.dconn().set_bit());
+            return Event::Attached;
+        }
+        Event::None
+    }
+
+    /// Speed of the connected device
+    pub fn speed(&self) -> Speed {
+        match self.usb().status.read().speed().bits() {
+            1 => Speed::Low,
+            _ => Speed::Full,
+        }
+    }
+
+    /// Current frame number, on 11 bits
+    pub fn frame_number(&self) -> u16 {
+        self.usb().fnum.read().fnum().bits()
+    }
+
+    /// Reset the bus, blocking until the reset completes, and start sending
+    /// Start Of Frame packets
+    ///
+    /// Returns [`Error::Timeout`] if the peripheral never signals the end of
+    /// the reset.
+    pub fn reset_bus(&mut self) -> Result<(), Error> {
+        let usb = self.usb();
+        usb.intflag.write(|w| w.rst().set_bit());
+        usb.ctrlb.modify(|_, w| w.busreset().set_bit());
+        let mut polls = 0;
+        while usb.intflag.read().rst().bit_is_clear() {
+            polls += 1;
+            if polls == RESET_TIMEOUT_POLLS {
+                return Err(Error::Timeout);
+            }
+        }
+        usb.intflag.write(|w| w.rst().set_bit());
+        usb.ctrlb.modify(|_, w| w.sofe().set_bit());
+        // Leave the device 20 ms, twice its minimum recovery time of 10 ms
+        self.wait_frames(20);
+        Ok(())
+    }
+
+    /// Wait for `frames` Start Of Frame packets to be sent
+    pub fn wait_frames(&self, frames: u16) {
+        let start = self.frame_number();
+        while self.frame_number().wrapping_sub(start) & 0x7ff < frames {
+            if self.usb().ctrlb.read().sofe().bit_is_clear() {
+                break;
+            }
+        }
+    }
+
+    /// Bind a pipe to an endpoint of the device at `address`
+    ///
+    /// `interval` is the polling interval of interrupt pipes, in frames.
+    pub fn alloc_pipe(
+        &mut self,
+        address: u8,
+        endpoint: u8,
+        pipe_type: PipeType,
+        max_packet_size: u16,
+        interval: u8,
+    ) -> Result<Pipe, Error> {
+        let (code, allocated_size) = size_code(max_packet_size).ok_or(Error::Unsupported)?;
+        // Control pipes receive whole descriptors at once
+        let capacity = if pipe_type == PipeType::Control {
+            allocated_size.max(64)
+        } else {
+            allocated_size
+        };
+        let index = (0..NUM_PIPES)
+            .find(|&i| self.allocated & 1 << i == 0)
+            .ok_or(Error::NoPipe)?;
+
+        // The address must be 32-bit aligned
+        let start = (self.next_buf + 3) & !3;
+        let end = start + capacity as usize;
+        if end > BUFFER_SIZE {
+            return Err(Error::NoPipe);
+        }
+        let buffer = unsafe { self.buffers.as_mut_ptr().add(start) };
+        self.next_buf = end;
+        self.allocated |= 1 << index;
+
+        let bank = &mut self.desc[index].bank[0];
+        bank.addr = buffer;
+        bank.pcksize = PckSize(0);
+        bank.pcksize.set_size(code);
+        bank.status_pipe = StatusPipe(0);
+        bank.ctrl_pipe = CtrlPipe(0);
+        bank.ctrl_pipe.set_pdaddr(address as u16);
+        bank.ctrl_pipe.set_pepnum((endpoint & 0x0f) as u16);
+        bank.ctrl_pipe.set_permax(3);
+
+        self.pstatusset(index)
+            .write(|w| unsafe { w.bits(PSTATUS_PFREEZE) });
+        self.pcfg(index)
+            .write(|w| unsafe { w.ptype().bits(pipe_type as u8) });
+        self.binterval(index)
+            .write(|w| unsafe { w.bitinterval().bits(interval) });
+        self.pintflag(index)
+            .write(|w| unsafe { w.bits(PINTFLAG_ALL) });
+
+        Ok(Pipe {
+            index,
+            pipe_type,
+            endpoint,
+            max_packet_size,
+            capacity,
+            buffer,
+            token: Token::Setup,
+            busy: false,
+        })
+    }
+
+    /// Disable a pipe
+    ///
+    /// The memory of the pipe buffers is reclaimed once all pipes are freed.
+    pub fn free_pipe(&mut self, pipe: Pipe) {
+        self.pstatusset(pipe.index)
+            .write(|w| unsafe { w.bits(PSTATUS_PFREEZE) });
+        self.pcfg(pipe.index).write(|w| unsafe { w.bits(0) });
+        self.allocated &= !(1 << pipe.index);
+        if self.allocated == 0 {
+            self.next_buf = 0;
+        }
+    }
+
+    /// Change the device address of a pipe, and its maximum packet size
+    fn rebind_pipe(&mut self, pipe: &mut Pipe, address: u8, max_packet_size: u16) {
+        let bank = &mut self.desc[pipe.index].bank[0];
+        bank.ctrl_pipe.set_pdaddr(address as u16);
+        if let Some((code, _)) = size_code(max_packet_size) {
+            if max_packet_size <= pipe.capacity {
+                bank.pcksize.set_size(code);
+                pipe.max_packet_size = max_packet_size;
+            }
+        }
+    }
+
+    /// Start a transaction of `len` bytes on `pipe`
+    fn start(&mut self, pipe: &mut Pipe, token: Token, len: u16) {
+        let idx = pipe.index;
+        self.pcfg(idx)
+            .modify(|_, w| unsafe { w.ptoken().bits(token as u8) });
+        let bank = &mut self.desc[idx].bank[0];
+        if token == Token::In {
+            bank.pcksize.set_byte_count(0);
+            bank.pcksize.set_multi_packet_size(len as u32);
+        } else {
+            bank.pcksize.set_byte_count(len as u32);
+            bank.pcksize.set_multi_packet_size(0);
+        }
+        bank.status_pipe = StatusPipe(0);
+        self.pintflag(idx)
+            .write(|w| unsafe { w.bits(PINTFLAG_ALL) });
+        if token == Token::In {
+            // An empty bank is ready to receive
+            self.pstatusclr(idx)
+                .write(|w| unsafe { w.bits(PSTATUS_BK0RDY) });
+        } else {
+            self.pstatusset(idx)
+                .write(|w| unsafe { w.bits(PSTATUS_BK0RDY) });
+        }
+        self.pstatusclr(idx)
+            .write(|w| unsafe { w.bits(PSTATUS_PFREEZE) });
+        pipe.token = token;
+        pipe.busy = true;
+    }
+
+    /// Check the completion of the transaction started on `pipe`, returning
+    /// the number of bytes transferred
+    fn check(&mut self, pipe: &mut Pipe) -> nb::Result<u16, Error> {
+        let idx = pipe.index;
+        let flags = self.pintflag(idx).read();
+        let result = if self.usb().intflag.read().ddisc().bit() {
+            Err(Error::Detached)
+        } else if flags.stall().bit() {
+            Err(Error::Stall)
+        } else if flags.perr().bit() || flags.trfail().bit() {
+            Err(Error::Transfer)
+        } else if match pipe.token {
+            Token::Setup => flags.txstp().bit(),
+            _ => flags.trcpt0().bit(),
+        } {
+            Ok(self.desc[idx].bank[0].pcksize.byte_count() as u16)
+        } else {
+            return Err(nb::Error::WouldBlock);
+        };
+        self.pstatusset(idx)
+            .write(|w| unsafe { w.bits(PSTATUS_PFREEZE) });
+        self.pintflag(idx)
+            .write(|w| unsafe { w.bits(PINTFLAG_ALL) });
+        pipe.busy = false;
+        result.map_err(nb::Error::Other)
+    }
+
+    /// Block until the transaction started on `pipe` completes
+    fn wait(&mut self, pipe: &mut Pipe) -> Result<u16, Error> {
+        let start = self.frame_number();
+        loop {
+            match self.check(pipe) {
+                Ok(len) => return Ok(len),
+                Err(nb::Error::Other(e)) => return Err(e),
+                Err(nb::Error::WouldBlock) => {}
+            }
+            // The frame number stops once the detachment has been handled
+            if self.usb().ctrlb.read().sofe().bit_is_clear() {
+                self.abort(pipe);
+                return Err(Error::Detached);
+            }
+            if self.frame_number().wrapping_sub(start) & 0x7ff >= TIMEOUT_FRAMES {
+                self.abort(pipe);
+                return Err(Error::Timeout);
+            }
+        }
+    }
+
+    /// Abort the transaction in progress on `pipe`
+    pub fn abort(&mut self, pipe: &mut Pipe) {
+        self.pstatusset(pipe.index)
+            .write(|w| unsafe { w.bits(PSTATUS_PFREEZE) });
+        self.pintflag(pipe.index)
+            .write(|w| unsafe { w.bits(PINTFLAG_ALL) });
+        pipe.busy = false;
+    }
+
+    /// Set the data toggle of the next transaction on `pipe`
+    fn set_toggle(&mut self, pipe: &Pipe, toggle: bool) {
+        if toggle {
+            self.pstatusset(pipe.index)
+                .write(|w| unsafe { w.bits(PSTATUS_DTGL) });
+        } else {
+            self.pstatusclr(pipe.index)
+                .write(|w| unsafe { w.bits(PSTATUS_DTGL) });
+        }
+    }
+
+    /// Perform a control transfer on `pipe`
+    ///
+    /// The data stage reads into `data` if `setup` is a device-to-host
+    /// request, or writes the first `setup.length` bytes of `data`
+    /// otherwise. Returns the length of the data stage.
+    pub fn control(
+        &mut self,
+        pipe: &mut Pipe,
+        setup: &SetupPacket,
+        data: &mut [u8],
+    ) -> Result<usize, Error> {
+        let length = (setup.length as usize).min(data.len());
+
+        // Setup stage
+        let bytes = setup.to_bytes();
+        unsafe {
+            bytes
+                .as_ptr()
+                .copy_to_nonoverlapping(pipe.buffer, bytes.len())
+        };
+        self.set_toggle(pipe, false);
+        self.start(pipe, Token::Setup, bytes.len() as u16);
+        self.wait(pipe)?;
+
+        // Data stage
+        self.set_toggle(pipe, true);
+        let mut done = 0;
+        if setup.is_in() {
+            while done < length {
+                let chunk = pipe.chunk().min((length - done) as u16);
+                self.start(pipe, Token::In, chunk);
+                let len = self.wait(pipe)? as usize;
+                if len > length - done {
+                    return Err(Error::BufferOverflow);
+                }
+                unsafe {
+                    pipe.buffer
+                        .copy_to_nonoverlapping(data[done..].as_mut_ptr(), len)
+                };
+                done += len;
+                if len < chunk as usize {
+                    break;
+                }
+            }
+        } else {
+            while done < length {
+                let len = (pipe.max_packet_size as usize).min(length - done);
+                unsafe {
+                    data[done..]
+                        .as_ptr()
+                        .copy_to_nonoverlapping(pipe.buffer, len)
+                };
+                self.start(pipe, Token::Out, len as u16);
+                self.wait(pipe)?;
+                done += len;
+            }
+        }
+
+        // Status stage, a zero length packet in the other direction
+        self.set_toggle(pipe, true);
+        let token = if setup.is_in() { Token::Out } else { Token::In };
+        self.start(pipe, token, 0);
+        self.wait(pipe)?;
+        Ok(done)
+    }
+
+    /// Read a packet from an IN bulk or interrupt pipe
+    ///
+    /// The first call starts the transaction; the following ones return
+    /// [`WouldBlock`](nb::Error::WouldBlock) until the device answers.
+    /// Interrupt pipes are polled by the peripheral at their interval.
+    pub fn read(&mut self, pipe: &mut Pipe, buf: &mut [u8]) -> nb::Result<usize, Error> {
+        if !pipe.busy {
+            let len = pipe.max_packet_size.min(pipe.capacity);
+            self.start(pipe, Token::In, len);
+            return Err(nb::Error::WouldBlock);
+        }
+        let len = self.check(pipe)? as usize;
+        if len > buf.len() {
+            return Err(nb::Error::Other(Error::BufferOverflow));
+        }
+        unsafe { pipe.buffer.copy_to_nonoverlapping(buf.as_mut_ptr(), len) };
+        Ok(len)
+    }
+
+    /// Write a packet to an OUT bulk or interrupt pipe
+    ///
+    /// Up to the maximum packet size of the pipe is sent from `buf`. The
+    /// first call starts the transaction; the following ones return
+    /// [`WouldBlock`](nb::Error::WouldBlock) until the device accepts it, and
+    /// must pass the same data.
+    pub fn write(&mut self, pipe: &mut Pipe, buf: &[u8]) -> nb::Result<usize, Error> {
+        if !pipe.busy {
+            let len = buf.len().min(pipe.max_packet_size as usize);
+            unsafe { buf.as_ptr().copy_to_nonoverlapping(pipe.buffer, len) };
+            self.start(pipe, Token::Out, len as u16);
+            return Err(nb::Error::WouldBlock);
+        }
+        Ok(self.check(pipe)? as usize)
+    }
+
+    /// Enumerate the device connected, after a bus reset, assigning it
+    /// `address`
+    ///
+    /// The device descriptor is read, and a control pipe allocated for the
+    /// default endpoint of the device.
+    pub fn enumerate(&mut self, address: u8) -> Result<Device, Error> {
+        let speed = self.speed();
+        let mut control = self.alloc_pipe(0, 0, PipeType::Control, 8, 0)?;
+        let result = self.enumerate_with(&mut control, address);
+        match result {
+            Ok(descriptor) => Ok(Device {
+                address,
+                speed,
+                descriptor,
+                control,
+            }),
+            Err(e) => {
+                self.free_pipe(control);
+                Err(e)
+            }
+        }
+    }
+
+    fn enumerate_with(
+        &mut self,
+        control: &mut Pipe,
+        address: u8,
+    ) -> Result<DeviceDescriptor, Error> {
+        // Only the first 8 bytes are known to fit in a packet of endpoint 0
+        let mut bytes = [0; DeviceDescriptor::LENGTH];
+        let setup = SetupPacket::get_descriptor(DESCRIPTOR_DEVICE, 0, 8);
+        self.control(control, &setup, &mut bytes)?;
+        let max_packet_size = bytes[7] as u16;
+        if !matches!(max_packet_size, 8 | 16 | 32 | 64) {
+            return Err(Error::InvalidDescriptor);
+        }
+        self.rebind_pipe(control, 0, max_packet_size);
+
+        self.control(control, &SetupPacket::set_address(address), &mut [])?;
+        // Leave the device a recovery interval of 2 ms
+        self.wait_frames(2);
+        self.rebind_pipe(control, address, max_packet_size);
+
+        let setup = SetupPacket::get_descriptor(DESCRIPTOR_DEVICE, 0, bytes.len() as u16);
+        let len = self.control(control, &setup, &mut bytes)?;
+        DeviceDescriptor::parse(&bytes[..len])
+    }
+
+    /// Read the first configuration descriptor of `device`, along with its
+    /// interface and endpoint descriptors, returning their length
+    ///
+    /// The descriptors are truncated to the length of `buf`.
+    pub fn configuration_descriptor(
+        &mut self,
+        device: &mut Device,
+        buf: &mut [u8],
+    ) -> Result<usize, Error> {
+        let mut header = [0; 9];
+        let setup = SetupPacket::get_descriptor(DESCRIPTOR_CONFIGURATION, 0, 9);
+        let len = self.control(&mut device.control, &setup, &mut header)?;
+        if len < 4 || header[1] != DESCRIPTOR_CONFIGURATION {
+            return Err(Error::InvalidDescriptor);
+        }
+        let total = le16(&header, 2).min(buf.len() as u16);
+        let setup = SetupPacket::get_descriptor(DESCRIPTOR_CONFIGURATION, 0, total);
+        self.control(&mut device.control, &setup, buf)
+    }
+
+    /// Select the configuration `value` of `device`, given by the
+    /// `bConfigurationValue` field of its configuration descriptor
+    pub fn set_configuration(&mut self, device: &mut Device, value: u8) -> Result<(), Error> {
+        let setup = SetupPacket::set_configuration(value);
+        self.control(&mut device.control, &setup, &mut [])?;
+        Ok(())
+    }
+
+    /// Bind a pipe to an endpoint of `device`, described by its endpoint
+    /// descriptor
+    pub fn endpoint_pipe(
+        &mut self,
+        device: &Device,
+        endpoint: &EndpointDescriptor,
+    ) -> Result<Pipe, Error> {
+        self.alloc_pipe(
+            device.address,
+            endpoint.address,
+            endpoint.pipe_type,
+            endpoint.max_packet_size,
+            endpoint.interval,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setup_packets() {
+        let setup = SetupPacket::get_descriptor(DESCRIPTOR_CONFIGURATION, 0, 0x109);
+        assert_eq!(setup.to_bytes(), [0x80, 6, 0, 2, 0, 0, 0x09, 0x01]);
+        assert!(setup.is_in());
+        assert!(!SetupPacket::set_address(3).is_in());
+    }
+
+    #[test]
+    fn device_descriptor() {
+        let bytes = [
+            18, 1, 0x00, 0x02, 0, 0, 0, 64, 0xc0, 0x16, 0xdd, 0x27, 0x00, 0x01, 1, 2, 3, 1,
+        ];
+        let descriptor = DeviceDescriptor::parse(&bytes).unwrap();
+        assert_eq!(descriptor.usb_version, 0x0200);
+        assert_eq!(descriptor.max_packet_size, 64);
+        assert_eq!(descriptor.vendor_id, 0x16c0);
+        assert_eq!(descriptor.product_id, 0x27dd);
+        assert_eq!(descriptor.num_configurations, 1);
+        assert_eq!(
+            DeviceDescriptor::parse(&bytes[..8]),
+            Err(Error::InvalidDescriptor)
+        );
+    }
+
+    #[test]
+    fn configuration_descriptors() {
+        let bytes = [
+            9, 2, 25, 0, 1, 1, 0, 0xa0, 50, // configuration
+            9, 4, 0, 0, 1, 3, 1, 1, 0, // HID keyboard interface
+            7, 5, 0x81, 3, 8, 0, 10, // interrupt IN endpoint
+            2,  // truncated
+        ];
+        let mut iter = descriptors(&bytes);
+        assert_eq!(iter.next(), Some(Descriptor::Other(2, &bytes[..9])));
+        match iter.next() {
+            Some(Descriptor::Interface(interface)) => assert_eq!(interface.class, 3),
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(
+            iter.next(),
+            Some(Descriptor::Endpoint(EndpointDescriptor {
+                address: 0x81,
+                pipe_type: PipeType::Interrupt,
+                max_packet_size: 8,
+                interval: 10,
+            }))
+        );
+        assert_eq!(iter.next(), None);
+    }
+}
@@ -20,6 +20,11 @@ pub enum WatchdogTimeout {
     Cycles16K,
 }
 
+/// Error of the configuration of an always-on watchdog, which can't be
+/// modified until the next reset
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct AlwaysOnError;
+
 pub struct Watchdog {
     wdt: WDT,
 }
@@ -28,6 +33,84 @@ impl Watchdog {
     pub fn new(wdt: WDT) -> Self {
         Self { wdt }
     }
+
+    /// Returns whether the watchdog was enabled in always-on mode by the
+    /// user fuses at startup. An always-on watchdog cannot be disabled or
+    /// reconfigured until the next reset.
+    pub fn is_always_on(&self) -> bool {
+        self.wdt.ctrl.read().alwayson().bit_is_set()
+    }
+
+    /// Enables the watchdog in window mode. Feeding the watchdog during the
+    /// `closed` period, which starts with each feed, resets the processor
+    /// just like not feeding it before the end of the following `open`
+    /// period.
+    ///
+    /// A running watchdog is disabled first, as its configuration can only
+    /// be written while it is disabled. Returns [`AlwaysOnError`] if the
+    /// watchdog is always-on.
+    pub fn start_windowed(
+        &mut self,
+        closed: WatchdogTimeout,
+        open: WatchdogTimeout,
+    ) -> Result<(), AlwaysOnError> {
+        if self.is_always_on() {
+            return Err(AlwaysOnError);
+        }
+        self.wdt.ctrl.write(|w| w.enable().clear_bit());
+        while self.wdt.status.read().syncbusy().bit_is_set() {}
+        self.wdt
+            .config
+            .write(|w| unsafe { w.per().bits(open as u8).window().bits(closed as u8) });
+        self.wdt
+            .ctrl
+            .write(|w| w.wen().set_bit().enable().set_bit());
+        while self.wdt.status.read().syncbusy().bit_is_set() {}
+        Ok(())
+    }
+
+    /// Enables the early warning interrupt, raised `offset` cycles after
+    /// each feed, or after the start of the open period in window mode.
+    ///
+    /// The `WDT` interrupt handler can then save state before the processor
+    /// is reset, and clear the flag with
+    /// [`clear_early_warning`](Self::clear_early_warning).
+    ///
+    /// A running watchdog is briefly disabled to write the offset, then
+    /// enabled again. Returns [`AlwaysOnError`] if the watchdog is
+    /// always-on.
+    pub fn enable_early_warning(&mut self, offset: WatchdogTimeout) -> Result<(), AlwaysOnError> {
+        if self.is_always_on() {
+            return Err(AlwaysOnError);
+        }
+        let ctrl = self.wdt.ctrl.read().bits();
+        self.wdt.ctrl.write(|w| w.enable().clear_bit());
+        while self.wdt.status.read().syncbusy().bit_is_set() {}
+        self.wdt
+            .ewctrl
+            .write(|w| unsafe { w.ewoffset().bits(offset as u8) });
+        // SAFETY: The previous value of the register is restored
+        self.wdt.ctrl.write(|w| unsafe { w.bits(ctrl) });
+        while self.wdt.status.read().syncbusy().bit_is_set() {}
+        self.wdt.intflag.write(|w| w.ew().set_bit());
+        self.wdt.intenset.write(|w| w.ew().set_bit());
+        Ok(())
+    }
+
+    /// Disables the early warning interrupt.
+    pub fn disable_early_warning(&mut self) {
+        self.wdt.intenclr.write(|w| w.ew().set_bit());
+    }
+
+    /// Returns whether the early warning flag is set.
+    pub fn is_early_warning(&self) -> bool {
+        self.wdt.intflag.read().ew().bit_is_set()
+    }
+
+    /// Clears the early warning flag.
+    pub fn clear_early_warning(&mut self) {
+        self.wdt.intflag.write(|w| w.ew().set_bit());
+    }
 }
 
 impl watchdog::Watchdog for Watchdog {
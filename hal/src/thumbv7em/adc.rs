@@ -1,4 +1,5 @@
 //! Analogue-to-Digital Conversion
+use crate::bus_clock::{self, BusClock};
 use crate::clock::GenericClockController;
 #[rustfmt::skip]
 #[allow(deprecated)]
@@ -7,10 +8,18 @@ use crate::gpio::v2::*;
 use crate::hal::adc::{Channel, OneShot};
 use crate::pac::gclk::genctrl::SRC_A::DFLL;
 use crate::pac::gclk::pchctrl::GEN_A;
-use crate::pac::{adc0, ADC0, ADC1, MCLK};
+use crate::pac::{adc0, ADC0, ADC1, MCLK, SUPC};
 
 use crate::calibration;
 
+#[cfg(feature = "dma")]
+use crate::dmac::{
+    self,
+    channel::{AnyChannel, Busy, InterruptFlags, Ready},
+    transfer::BufferPair,
+    Buffer, ChId, LinkedDescriptor, LinkedTransfer, Transfer, TriggerAction, TriggerSource,
+};
+
 /// Samples per reading
 pub use adc0::avgctrl::SAMPLENUM_A as SampleRate;
 /// Clock frequency relative to the system clock
@@ -20,6 +29,134 @@ pub use adc0::ctrlb::RESSEL_A as Resolution;
 /// Reference voltage (or its source)
 pub use adc0::refctrl::REFSEL_A as Reference;
 
+/// How conversions are accumulated into a result
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Accumulation {
+    /// A single conversion per result, at the given resolution
+    Single(Resolution),
+    /// Average the given number of conversions into a 12-bit result
+    Average(SampleRate),
+    /// Oversample and decimate conversions, increasing the effective
+    /// resolution
+    Oversample(Oversampling),
+}
+
+/// Effective resolution of oversampled results
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Oversampling {
+    /// 13-bit results, from 4 conversions
+    Bits13,
+    /// 14-bit results, from 16 conversions
+    Bits14,
+    /// 15-bit results, from 64 conversions
+    Bits15,
+    /// 16-bit results, from 256 conversions
+    Bits16,
+}
+
+impl Accumulation {
+    /// Resolution, number of accumulated samples and result adjustment
+    fn settings(self) -> (Resolution, SampleRate, u8) {
+        match self {
+            Accumulation::Single(resolution) => (resolution, SampleRate::_1, 0),
+            Accumulation::Average(samples) => {
+                // Table 45-3 (45.6.2.10) specifies the adjres values necessary for each
+                // SAMPLENUM value.
+                let adjres = match samples {
+                    SampleRate::_1 => 0,
+                    SampleRate::_2 => 1,
+                    SampleRate::_4 => 2,
+                    SampleRate::_8 => 3,
+                    _ => 4,
+                };
+                (Resolution::_16BIT, samples, adjres)
+            }
+            Accumulation::Oversample(oversampling) => {
+                let (samples, adjres) = match oversampling {
+                    Oversampling::Bits13 => (SampleRate::_4, 1),
+                    Oversampling::Bits14 => (SampleRate::_16, 2),
+                    Oversampling::Bits15 => (SampleRate::_64, 1),
+                    Oversampling::Bits16 => (SampleRate::_256, 0),
+                };
+                (Resolution::_16BIT, samples, adjres)
+            }
+        }
+    }
+}
+
+/// ADC configuration, applied with [`Adc::configure`]
+///
+/// ```no_run
+/// use atsamd_hal::adc::{Accumulation, AdcConfig, Oversampling, Prescaler, Reference};
+///
+/// let config = AdcConfig::new()
+///     .prescaler(Prescaler::DIV16)
+///     .sample_length(10)
+///     .accumulation(Accumulation::Oversample(Oversampling::Bits16))
+///     .reference(Reference::AREFA);
+/// adc.configure(config);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct AdcConfig {
+    prescaler: Prescaler,
+    sample_length: u8,
+    accumulation: Accumulation,
+    reference: Reference,
+}
+
+impl AdcConfig {
+    /// Create the configuration set by the constructor of [`Adc`]
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            prescaler: Prescaler::DIV32,
+            sample_length: 5,
+            accumulation: Accumulation::Single(Resolution::_12BIT),
+            reference: Reference::INTVCC1,
+        }
+    }
+
+    /// Set the prescaler of the ADC clock
+    #[inline]
+    pub fn prescaler(mut self, prescaler: Prescaler) -> Self {
+        self.prescaler = prescaler;
+        self
+    }
+
+    /// Set the sampling time, in ADC clock cycles, minus one
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample_length` is greater than 63.
+    #[inline]
+    pub fn sample_length(mut self, sample_length: u8) -> Self {
+        assert!(sample_length < 64);
+        self.sample_length = sample_length;
+        self
+    }
+
+    /// Set how conversions are accumulated into a result
+    #[inline]
+    pub fn accumulation(mut self, accumulation: Accumulation) -> Self {
+        self.accumulation = accumulation;
+        self
+    }
+
+    /// Set the voltage reference
+    #[inline]
+    pub fn reference(mut self, reference: Reference) -> Self {
+        self.reference = reference;
+        self
+    }
+}
+
+impl Default for AdcConfig {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// An ADC where results are accessible via interrupt servicing.
 pub struct InterruptAdc<ADC, C>
 where
@@ -46,11 +183,11 @@ pub struct SingleConversion;
 pub struct FreeRunning;
 
 macro_rules! adc_hal {
-    ($($ADC:ident: ($init:ident, $mclk:ident, $apmask:ident, $compcal:ident, $refcal:ident, $r2rcal:ident),)+) => {
+    ($($ADC:ident: ($init:ident, $compcal:ident, $refcal:ident, $r2rcal:ident),)+) => {
         $(
 impl Adc<$ADC> {
     pub fn $init(adc: $ADC, mclk: &mut MCLK, clocks: &mut GenericClockController, gclk:GEN_A) -> Self {
-        mclk.$mclk.modify(|_, w| w.$apmask().set_bit());
+        $ADC::enable_bus_clock(mclk);
         // set to 1/(1/(48000000/32) * 6) = 250000 SPS
         let adc_clock = clocks.configure_gclk_divider_and_source(gclk, 1, DFLL, false)
             .expect("adc clock setup failed");
@@ -120,6 +257,77 @@ impl Adc<$ADC> {
         while self.adc.syncbusy.read().ctrlb().bit_is_set() {}
     }
 
+    /// Keep converting while the device is in standby, for conversions
+    /// triggered by events or free-running conversions waking the device
+    pub fn set_run_in_standby(&mut self, enabled: bool) {
+        self.adc.ctrla.modify(|_, w| w.runstdby().bit(enabled));
+        // Note there is no syncbusy for ctrla
+    }
+
+    /// Apply `config`, replacing the prescaler, sample length, accumulation
+    /// and reference
+    pub fn configure(&mut self, config: AdcConfig) {
+        let (resolution, samples, adjres) = config.accumulation.settings();
+        self.prescaler(config.prescaler);
+        self.resolution(resolution);
+        // SAFETY: The sample length is checked when building the configuration
+        self.adc.sampctrl.modify(|_, w| unsafe { w.samplen().bits(config.sample_length) });
+        while self.adc.syncbusy.read().sampctrl().bit_is_set() {}
+        self.adc.avgctrl.write(|w| {
+            w.samplenum().variant(samples);
+            // SAFETY: The adjustment matches the number of samples
+            unsafe { w.adjres().bits(adjres) }
+        });
+        while self.adc.syncbusy.read().avgctrl().bit_is_set() {}
+        self.reference(config.reference);
+    }
+
+    /// Reset the ADC, disable its bus clock and release it
+    ///
+    /// The pins converted by the ADC are borrowed by each conversion, so
+    /// their ownership is not affected.
+    pub fn free(self) -> $ADC {
+        self.adc.ctrla.write(|w| w.swrst().set_bit());
+        while self.adc.syncbusy.read().swrst().bit_is_set() {}
+        bus_clock::release::<$ADC>();
+        self.adc
+    }
+
+    /// Convert the voltage difference between `positive` and `negative`
+    ///
+    /// The result is signed, and uses the configured resolution.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `negative` is not one of the ADC inputs 0 to 7, which are the
+    /// only ones available as a negative input.
+    pub fn read_differential<P, N>(&mut self, _positive: &mut P, _negative: &mut N) -> i16
+    where
+        P: Channel<$ADC, ID = u8>,
+        N: Channel<$ADC, ID = u8>,
+    {
+        let (positive, negative) = (P::channel(), N::channel());
+        assert!(negative < 8);
+        while self.adc.syncbusy.read().inputctrl().bit_is_set() {}
+        self.adc.inputctrl.modify(|_, w| unsafe {
+            w.muxpos().bits(positive);
+            w.muxneg().bits(negative);
+            w.diffmode().set_bit()
+        });
+        self.power_up();
+        // The first conversion after the inputs are changed must not be used
+        self.synchronous_convert();
+        let result = self.synchronous_convert();
+        self.power_down();
+        while self.adc.syncbusy.read().inputctrl().bit_is_set() {}
+        self.adc.inputctrl.modify(|_, w| {
+            w.diffmode().clear_bit();
+            w.muxneg().gnd()
+        });
+        while self.adc.syncbusy.read().inputctrl().bit_is_set() {}
+        result as i16
+    }
+
     fn power_up(&mut self) {
         while self.adc.syncbusy.read().enable().bit_is_set() {}
         self.adc.ctrla.modify(|_, w| w.enable().set_bit());
@@ -268,8 +476,371 @@ where
 }
 
 adc_hal! {
-    ADC0: (adc0, apbdmask, adc0_, adc0_biascomp_scale_cal, adc0_biasref_scale_cal, adc0_biasr2r_scale_cal),
-    ADC1: (adc1, apbdmask, adc1_, adc1_biascomp_scale_cal, adc1_biasref_scale_cal, adc1_biasr2r_scale_cal),
+    ADC0: (adc0, adc0_biascomp_scale_cal, adc0_biasref_scale_cal, adc0_biasr2r_scale_cal),
+    ADC1: (adc1, adc1_biascomp_scale_cal, adc1_biasref_scale_cal, adc1_biasr2r_scale_cal),
+}
+
+//=============================================================================
+// DMA streaming
+//=============================================================================
+
+/// Result register of an ADC, used as the source of DMA transfers
+#[cfg(feature = "dma")]
+#[doc(hidden)]
+pub struct AdcResult<A>(core::marker::PhantomData<A>);
+
+/// Free-running conversions streamed by the DMAC into a ring of buffer
+/// segments
+///
+/// The DMAC fills the segments in order and loops back to the first one
+/// after the last one. The channel interrupt is raised every time a segment
+/// has been filled, see [`AdcStream::segment_complete`]. With two segments,
+/// this is a double buffer signalling every half.
+///
+/// A filled segment must be processed before the DMAC loops back to it.
+#[cfg(feature = "dma")]
+pub struct AdcStream<A, Id>
+where
+    Id: ChId,
+    AdcResult<A>: Buffer<Beat = u16>,
+{
+    adc: Adc<A>,
+    transfer: LinkedTransfer<dmac::Channel<Id, Busy>, &'static mut [u16], AdcResult<A>>,
+    next: usize,
+}
+
+/// DMA sequencing data register of an ADC, used as the destination of DMA
+/// transfers
+#[cfg(feature = "dma")]
+#[doc(hidden)]
+pub struct SequenceData<A>(core::marker::PhantomData<A>);
+
+#[cfg(feature = "dma")]
+type SequenceTransfer<A, Id> =
+    Transfer<dmac::Channel<Id, Busy>, BufferPair<&'static mut [u32], SequenceData<A>>>;
+
+/// Hardware sequenced conversion of a list of inputs, with the results
+/// transferred by the DMAC
+///
+/// Two DMA channels are used: the first one writes the input of every
+/// conversion into the DMA sequencing register of the ADC, which then starts
+/// the conversion automatically, and the second one reads the results. The
+/// CPU is not involved until the whole list has been converted.
+#[cfg(feature = "dma")]
+pub struct AdcScan<A, SeqId, ResId, B>
+where
+    SeqId: ChId,
+    ResId: ChId,
+    AdcResult<A>: Buffer<Beat = u16>,
+    SequenceData<A>: Buffer<Beat = u32>,
+    B: Buffer<Beat = u16> + 'static,
+{
+    adc: Adc<A>,
+    sequence: SequenceTransfer<A, SeqId>,
+    results: Transfer<dmac::Channel<ResId, Busy>, BufferPair<AdcResult<A>, B>>,
+}
+
+#[cfg(feature = "dma")]
+macro_rules! adc_dma {
+    ($($ADC:ident: ($TRIGGER:ident, $SEQUENCE:ident),)+) => {
+        $(
+unsafe impl Buffer for AdcResult<$ADC> {
+    type Beat = u16;
+
+    #[inline]
+    fn dma_ptr(&mut self) -> *mut u16 {
+        // SAFETY: The result register is only read by the DMAC
+        unsafe { (*$ADC::ptr()).result.as_ptr() }
+    }
+
+    #[inline]
+    fn incrementing(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn buffer_len(&self) -> usize {
+        1
+    }
+}
+
+impl Adc<$ADC> {
+    /// Start converting `pin` in free-running mode, and stream the results
+    /// into `segments` with the DMAC
+    ///
+    /// `descriptors` must hold at least one descriptor per segment after the
+    /// first, see the [`linked`](crate::dmac::linked) module. The sample rate
+    /// depends on the prescaler, the sample length and the averaging.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer than two segments, or not enough
+    /// descriptors.
+    pub fn stream_with_dma<PIN, Ch>(
+        mut self,
+        pin: &mut PIN,
+        mut channel: Ch,
+        segments: &'static mut [&'static mut [u16]],
+        descriptors: &'static mut [LinkedDescriptor],
+    ) -> AdcStream<$ADC, Ch::Id>
+    where
+        PIN: Channel<$ADC, ID = u8>,
+        Ch: AnyChannel<Status = Ready>,
+    {
+        assert!(segments.len() >= 2);
+        channel
+            .as_mut()
+            .enable_interrupts(InterruptFlags::new().with_tcmpl(true));
+        let transfer = LinkedTransfer::new_scatter(
+            channel,
+            AdcResult(core::marker::PhantomData),
+            segments,
+            descriptors,
+            true,
+        )
+        .expect("not enough descriptors for the segments")
+        .with_block_interrupts()
+        .begin(TriggerSource::$TRIGGER, TriggerAction::BURST);
+
+        self.mux(pin);
+        self.enable_freerunning();
+        self.power_up();
+        self.start_conversion();
+
+        AdcStream {
+            adc: self,
+            transfer,
+            next: 0,
+        }
+    }
+}
+
+unsafe impl Buffer for SequenceData<$ADC> {
+    type Beat = u32;
+
+    #[inline]
+    fn dma_ptr(&mut self) -> *mut u32 {
+        // SAFETY: The sequencing data register is only written by the DMAC
+        unsafe { (*$ADC::ptr()).dseqdata.as_ptr() }
+    }
+
+    #[inline]
+    fn incrementing(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn buffer_len(&self) -> usize {
+        1
+    }
+}
+
+impl Adc<$ADC> {
+    /// Convert every input of `channels`, in order, and transfer the results
+    /// into `results`
+    ///
+    /// `channels` holds the ADC channel numbers, as returned by
+    /// [`Channel::channel`]; the pins must already be in alternate function
+    /// B. `sequence` is the storage for the DMA sequencing data, which must
+    /// be as long as `channels`, as must `results`. Use [`AdcScan::wait`] to
+    /// wait for the results.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channels` is empty, or if `sequence` or `results` don't
+    /// have the same length as `channels`.
+    pub fn scan_with_dma<SeqCh, ResCh, B>(
+        mut self,
+        channels: &[u8],
+        sequence: &'static mut [u32],
+        results: B,
+        sequence_channel: SeqCh,
+        result_channel: ResCh,
+    ) -> AdcScan<$ADC, SeqCh::Id, ResCh::Id, B>
+    where
+        SeqCh: AnyChannel<Status = Ready>,
+        ResCh: AnyChannel<Status = Ready>,
+        B: Buffer<Beat = u16> + 'static,
+    {
+        assert!(!channels.is_empty());
+        assert_eq!(sequence.len(), channels.len());
+        assert_eq!(results.buffer_len(), channels.len());
+
+        // Single-ended inputs, the negative input being the internal ground
+        const MUXNEG_GND: u32 = 0x18 << 8;
+        for (data, channel) in sequence.iter_mut().zip(channels) {
+            *data = u32::from(*channel) | MUXNEG_GND;
+        }
+
+        // Only update INPUTCTRL, and start a conversion after every update
+        self.adc.dseqctrl.write(|w| {
+            w.inputctrl().set_bit();
+            w.autostart().set_bit()
+        });
+
+        let results = Transfer::new(result_channel, AdcResult(core::marker::PhantomData), results, false)
+            .expect("the result register accepts any buffer length")
+            .begin(TriggerSource::$TRIGGER, TriggerAction::BURST);
+        let sequence = Transfer::new(sequence_channel, sequence, SequenceData(core::marker::PhantomData), false)
+            .expect("the sequencing register accepts any buffer length")
+            .begin(TriggerSource::$SEQUENCE, TriggerAction::BURST);
+        self.power_up();
+
+        AdcScan {
+            adc: self,
+            sequence,
+            results,
+        }
+    }
+}
+
+impl<SeqId, ResId, B> AdcScan<$ADC, SeqId, ResId, B>
+where
+    SeqId: ChId,
+    ResId: ChId,
+    B: Buffer<Beat = u16> + 'static,
+{
+    /// Check whether every input has been converted
+    #[inline]
+    pub fn complete(&mut self) -> bool {
+        self.results.complete()
+    }
+
+    /// Wait for every input to be converted, and return the [`Adc`], the DMA
+    /// channels, the sequencing storage and the results
+    #[allow(clippy::type_complexity)]
+    pub fn wait(
+        mut self,
+    ) -> (
+        Adc<$ADC>,
+        dmac::Channel<SeqId, Ready>,
+        dmac::Channel<ResId, Ready>,
+        &'static mut [u32],
+        B,
+    ) {
+        while !self.results.complete() {}
+        let (result_channel, _, results) = self.results.stop();
+        let (sequence_channel, sequence, _) = self.sequence.wait();
+        let mut adc = self.adc;
+        adc.power_down();
+        adc.adc.dseqctrl.write(|w| {
+            w.inputctrl().clear_bit();
+            w.autostart().clear_bit()
+        });
+        (adc, sequence_channel, result_channel, sequence, results)
+    }
+}
+
+impl<Id: ChId> AdcStream<$ADC, Id> {
+    /// Clear the segment complete flag, and return the index of the oldest
+    /// filled segment not returned yet
+    ///
+    /// This is normally called from the DMAC interrupt handler, until it
+    /// returns `None`. The filled segments are found from the segment the
+    /// DMAC is filling, so that none is missed when the handler runs after
+    /// more than one segment was filled.
+    pub fn segment_complete(&mut self) -> Option<usize> {
+        self.transfer.block_transfer_interrupt();
+        if self.next == self.transfer.active_segment() {
+            return None;
+        }
+        let index = self.next;
+        self.next = (index + 1) % self.transfer.segments().len();
+        Some(index)
+    }
+
+    /// Copy the samples of segment `index` into `samples`, and return how
+    /// many were copied
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of range.
+    pub fn read_segment(&self, index: usize, samples: &mut [u16]) -> usize {
+        core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+        let segment = &self.transfer.segments()[index];
+        let count = segment.len().min(samples.len());
+        for (i, sample) in samples[..count].iter_mut().enumerate() {
+            // SAFETY: The index is within the segment
+            *sample = unsafe { core::ptr::read_volatile(segment.as_ptr().add(i)) };
+        }
+        count
+    }
+
+    /// Stop converting and streaming, and return the [`Adc`], the DMA
+    /// channel, the segments and the descriptors
+    #[allow(clippy::type_complexity)]
+    pub fn stop(
+        self,
+    ) -> (
+        Adc<$ADC>,
+        dmac::Channel<Id, Ready>,
+        &'static mut [&'static mut [u16]],
+        &'static mut [LinkedDescriptor],
+    ) {
+        let mut adc = self.adc;
+        adc.power_down();
+        adc.adc.ctrlb.modify(|_, w| w.freerun().clear_bit());
+        while adc.adc.syncbusy.read().ctrlb().bit_is_set() {}
+        let (channel, segments, _, descriptors) = self.transfer.stop();
+        (adc, channel, segments, descriptors)
+    }
+}
+        )+
+    };
+}
+
+#[cfg(feature = "dma")]
+adc_dma! {
+    ADC0: (ADC0_RESRDY, ADC0_SEQ),
+    ADC1: (ADC1_RESRDY, ADC1_SEQ),
+}
+
+impl Adc<ADC0> {
+    /// Read the internal temperature sensors, in hundredths of a degree
+    /// Celsius
+    ///
+    /// The temperature sensors are enabled, and both the PTAT and CTAT
+    /// sensors are converted and combined with the factory calibration
+    /// values. The ADC is temporarily switched to the configuration used
+    /// during calibration: 12-bit resolution and internal reference. The
+    /// internal reference is left set to 1.0V.
+    pub fn read_temperature(&mut self, supc: &mut SUPC) -> i32 {
+        supc.vref.modify(|_, w| {
+            w.sel()._1v0();
+            w.ondemand().set_bit();
+            w.tsen().set_bit()
+        });
+
+        let ctrlb = self.adc.ctrlb.read().bits();
+        let refctrl = self.adc.refctrl.read().bits();
+        let inputctrl = self.adc.inputctrl.read().bits();
+        self.resolution(Resolution::_12BIT);
+        self.reference(Reference::INTREF);
+
+        let mut convert = |muxpos| {
+            while self.adc.syncbusy.read().inputctrl().bit_is_set() {}
+            self.adc.inputctrl.modify(|_, w| w.muxpos().variant(muxpos));
+            self.power_up();
+            // The first conversion after the reference is changed must not be
+            // used
+            self.synchronous_convert();
+            let result = self.synchronous_convert();
+            self.power_down();
+            result
+        };
+        let ptat = convert(adc0::inputctrl::MUXPOS_A::PTAT);
+        let ctat = convert(adc0::inputctrl::MUXPOS_A::CTAT);
+
+        // SAFETY: The registers are restored to their previous values
+        self.adc.ctrlb.write(|w| unsafe { w.bits(ctrlb) });
+        while self.adc.syncbusy.read().ctrlb().bit_is_set() {}
+        self.adc.refctrl.write(|w| unsafe { w.bits(refctrl) });
+        while self.adc.syncbusy.read().refctrl().bit_is_set() {}
+        self.adc.inputctrl.write(|w| unsafe { w.bits(inputctrl) });
+        while self.adc.syncbusy.read().inputctrl().bit_is_set() {}
+
+        calibration::temperature_cal().temperature(ptat, ctat)
+    }
 }
 
 macro_rules! adc_pins {
@@ -0,0 +1,261 @@
+//! # AES - Advanced Encryption Standard
+//!
+//! The AES peripheral encrypts and decrypts 128-bit blocks using 128, 192 or
+//! 256-bit keys.
+//!
+//! This module exposes the peripheral as a set of hardware block ciphers,
+//! [`Aes128`], [`Aes192`] and [`Aes256`], implementing the RustCrypto
+//! [`cipher`] traits. Every block is processed by the peripheral, while the
+//! block modes are provided by the RustCrypto mode crates, which are
+//! re-exported through type aliases:
+//!
+//! * ECB: use [`BlockEncrypt`](cipher::BlockEncrypt) and
+//!   [`BlockDecrypt`](cipher::BlockDecrypt) directly on the block ciphers
+//! * CBC: [`Aes128CbcEnc`], [`Aes128CbcDec`] and friends, implementing
+//!   [`BlockEncryptMut`](cipher::BlockEncryptMut) and
+//!   [`BlockDecryptMut`](cipher::BlockDecryptMut)
+//! * CTR: [`Aes128Ctr`] and friends, implementing
+//!   [`StreamCipher`](cipher::StreamCipher)
+//! * GCM: [`Aes128Gcm`] and friends, implementing
+//!   [`AeadInPlace`](aead::AeadInPlace)
+//!
+//! The block ciphers borrow the [`Aes`] peripheral rather than owning it, so
+//! they cannot be built through [`KeyInit`](cipher::KeyInit). The mode types
+//! are instead created from an existing block cipher through
+//! [`InnerIvInit`](cipher::InnerIvInit) or [`From`].
+//!
+//! This module is only available when the `aes` feature is enabled.
+//!
+//! ```no_run
+//! use atsamd_hal::aes::{aead::AeadInPlace, Aes, Aes128, Aes128Gcm};
+//!
+//! let aes = Aes::new(&mut peripherals.MCLK, peripherals.AES);
+//! let key = [0x42; 16].into();
+//! let gcm = Aes128Gcm::from(Aes128::new(&aes, &key));
+//!
+//! let nonce = [0; 12].into();
+//! let mut payload = *b"hello, world";
+//! let tag = gcm
+//!     .encrypt_in_place_detached(&nonce, b"header", &mut payload)
+//!     .unwrap();
+//! ```
+//!
+//! ```no_run
+//! use atsamd_hal::aes::{cipher::{InnerIvInit, StreamCipher}, Aes, Aes256, Aes256Ctr};
+//!
+//! let aes = Aes::new(&mut peripherals.MCLK, peripherals.AES);
+//! let key = [0x42; 32].into();
+//! let iv = [0; 16].into();
+//! let mut ctr = Aes256Ctr::inner_iv_init(Aes256::new(&aes, &key), &iv);
+//!
+//! let mut payload = *b"hello, world";
+//! ctr.apply_keystream(&mut payload);
+//! ```
+
+use crate::bus_clock::BusClock;
+use crate::pac::{aes::ctrla::KEYSIZE_A, AES, MCLK};
+
+use cipher::{
+    consts::{U1, U12, U16, U24, U32},
+    generic_array::GenericArray,
+    inout::InOut,
+    Block, BlockBackend, BlockCipher, BlockClosure, BlockDecrypt, BlockEncrypt, BlockSizeUser, Key,
+    KeySizeUser, ParBlocksSizeUser,
+};
+
+pub use aes_gcm::aead;
+pub use cipher;
+
+/// Size of an AES block, in bytes
+pub const BLOCK_SIZE: usize = 16;
+
+/// Direction of an AES operation
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Encrypt,
+    Decrypt,
+}
+
+/// AES peripheral
+pub struct Aes {
+    aes: AES,
+}
+
+impl Aes {
+    /// Enable the AES peripheral clock and reset the peripheral
+    #[inline]
+    pub fn new(mclk: &mut MCLK, aes: AES) -> Self {
+        AES::enable_bus_clock(mclk);
+        aes.ctrla.write(|w| w.swrst().set_bit());
+        while aes.ctrla.read().swrst().bit_is_set() {}
+        Self { aes }
+    }
+
+    /// Disable the AES peripheral and return the register block
+    #[inline]
+    pub fn free(self, mclk: &mut MCLK) -> AES {
+        self.aes.ctrla.write(|w| w.swrst().set_bit());
+        while self.aes.ctrla.read().swrst().bit_is_set() {}
+        AES::disable_bus_clock(mclk);
+        self.aes
+    }
+
+    /// Configure the peripheral for ECB operation and load the key.
+    ///
+    /// CTRLA is enable-protected, so the peripheral is disabled while it is
+    /// reconfigured.
+    #[inline]
+    fn configure(&self, key: &[u8], key_size: KEYSIZE_A, direction: Direction) {
+        self.aes.ctrla.write(|w| w.enable().clear_bit());
+        self.aes.ctrla.write(|w| {
+            w.aesmode().ecb();
+            w.keysize().variant(key_size);
+            w.startmode().manual();
+            match direction {
+                Direction::Encrypt => w.cipher().enc(),
+                Direction::Decrypt => w.cipher().dec(),
+            }
+        });
+        self.aes.ctrla.modify(|_, w| w.enable().set_bit());
+
+        for (keyword, chunk) in self.aes.keyword.iter().zip(key.chunks_exact(4)) {
+            let word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            // SAFETY: Any 32-bit value is a valid key word
+            keyword.write(|w| unsafe { w.bits(word) });
+        }
+    }
+
+    /// Process a single block with the current configuration
+    #[inline]
+    fn process_block(&self, input: &[u8; BLOCK_SIZE], output: &mut [u8; BLOCK_SIZE]) {
+        // The data buffer pointer is incremented on every INDATA access
+        // SAFETY: Buffer pointer 0 is the first word of the block
+        self.aes
+            .databufptr
+            .write(|w| unsafe { w.indataptr().bits(0) });
+        for chunk in input.chunks_exact(4) {
+            let word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            // SAFETY: Any 32-bit value is valid input data
+            self.aes.indata.write(|w| unsafe { w.bits(word) });
+        }
+
+        self.aes.intflag.write(|w| w.enccmp().set_bit());
+        self.aes.ctrlb.write(|w| w.start().set_bit());
+        while self.aes.intflag.read().enccmp().bit_is_clear() {}
+
+        // SAFETY: Buffer pointer 0 is the first word of the block
+        self.aes
+            .databufptr
+            .write(|w| unsafe { w.indataptr().bits(0) });
+        for chunk in output.chunks_exact_mut(4) {
+            chunk.copy_from_slice(&self.aes.indata.read().bits().to_le_bytes());
+        }
+    }
+}
+
+/// Block backend used by the RustCrypto traits
+struct Backend<'a> {
+    aes: &'a Aes,
+}
+
+impl BlockSizeUser for Backend<'_> {
+    type BlockSize = U16;
+}
+
+impl ParBlocksSizeUser for Backend<'_> {
+    type ParBlocksSize = U1;
+}
+
+impl BlockBackend for Backend<'_> {
+    #[inline]
+    fn proc_block(&mut self, mut block: InOut<'_, '_, Block<Self>>) {
+        let input: [u8; BLOCK_SIZE] = block.clone_in().into();
+        let mut output = [0; BLOCK_SIZE];
+        self.aes.process_block(&input, &mut output);
+        *block.get_out() = GenericArray::from(output);
+    }
+}
+
+macro_rules! aes_cipher {
+    ($Type:ident, $KeySize:ty, $variant:ident, $bits:literal) => {
+        #[doc = concat!("Hardware AES-", $bits, " block cipher")]
+        ///
+        /// The key is kept in RAM and loaded into the peripheral at the start
+        /// of every operation, so several ciphers can share one [`Aes`].
+        #[derive(Clone)]
+        pub struct $Type<'a> {
+            aes: &'a Aes,
+            key: Key<Self>,
+        }
+
+        impl<'a> $Type<'a> {
+            #[doc = concat!("Create a new AES-", $bits, " block cipher")]
+            #[inline]
+            pub fn new(aes: &'a Aes, key: &Key<Self>) -> Self {
+                Self { aes, key: *key }
+            }
+
+            #[inline]
+            fn backend(&self, direction: Direction) -> Backend<'a> {
+                self.aes
+                    .configure(&self.key, KEYSIZE_A::$variant, direction);
+                Backend { aes: self.aes }
+            }
+        }
+
+        impl KeySizeUser for $Type<'_> {
+            type KeySize = $KeySize;
+        }
+
+        impl BlockSizeUser for $Type<'_> {
+            type BlockSize = U16;
+        }
+
+        impl BlockCipher for $Type<'_> {}
+
+        impl BlockEncrypt for $Type<'_> {
+            #[inline]
+            fn encrypt_with_backend(&self, f: impl BlockClosure<BlockSize = U16>) {
+                f.call(&mut self.backend(Direction::Encrypt))
+            }
+        }
+
+        impl BlockDecrypt for $Type<'_> {
+            #[inline]
+            fn decrypt_with_backend(&self, f: impl BlockClosure<BlockSize = U16>) {
+                f.call(&mut self.backend(Direction::Decrypt))
+            }
+        }
+    };
+}
+
+aes_cipher!(Aes128, U16, _128BIT, "128");
+aes_cipher!(Aes192, U24, _192BIT, "192");
+aes_cipher!(Aes256, U32, _256BIT, "256");
+
+/// AES-128 CBC encryptor
+pub type Aes128CbcEnc<'a> = cbc::Encryptor<Aes128<'a>>;
+/// AES-128 CBC decryptor
+pub type Aes128CbcDec<'a> = cbc::Decryptor<Aes128<'a>>;
+/// AES-192 CBC encryptor
+pub type Aes192CbcEnc<'a> = cbc::Encryptor<Aes192<'a>>;
+/// AES-192 CBC decryptor
+pub type Aes192CbcDec<'a> = cbc::Decryptor<Aes192<'a>>;
+/// AES-256 CBC encryptor
+pub type Aes256CbcEnc<'a> = cbc::Encryptor<Aes256<'a>>;
+/// AES-256 CBC decryptor
+pub type Aes256CbcDec<'a> = cbc::Decryptor<Aes256<'a>>;
+
+/// AES-128 CTR stream cipher, with a 128-bit big-endian counter
+pub type Aes128Ctr<'a> = ctr::Ctr128BE<Aes128<'a>>;
+/// AES-192 CTR stream cipher, with a 128-bit big-endian counter
+pub type Aes192Ctr<'a> = ctr::Ctr128BE<Aes192<'a>>;
+/// AES-256 CTR stream cipher, with a 128-bit big-endian counter
+pub type Aes256Ctr<'a> = ctr::Ctr128BE<Aes256<'a>>;
+
+/// AES-128 GCM authenticated cipher, with a 96-bit nonce
+pub type Aes128Gcm<'a> = aes_gcm::AesGcm<Aes128<'a>, U12>;
+/// AES-192 GCM authenticated cipher, with a 96-bit nonce
+pub type Aes192Gcm<'a> = aes_gcm::AesGcm<Aes192<'a>, U12>;
+/// AES-256 GCM authenticated cipher, with a 96-bit nonce
+pub type Aes256Gcm<'a> = aes_gcm::AesGcm<Aes256<'a>, U12>;
@@ -30,6 +30,11 @@ pub fn usb_trim_cal() -> u8 {
     cal(4, 10, 0b111) as u8
 }
 
+/// AC BIAS0 calibration value. Should be written to AC CALIB register.
+pub fn ac_bias_cal() -> u8 {
+    cal(0, 0, 0b11) as u8
+}
+
 /// ADC0 BIASCOMP calibration value. Should be written to ADC0 CALIB register.
 pub fn adc0_biascomp_scale_cal() -> u8 {
     cal(0, 2, 0b111) as u8
@@ -59,3 +64,84 @@ pub fn adc1_biasref_scale_cal() -> u8 {
 pub fn adc1_biasr2r_scale_cal() -> u8 {
     cal(3, 0, 0b111) as u8
 }
+
+/// Factory calibration of the temperature sensors, read from the temperature
+/// log row
+///
+/// The calibration values were measured with the ADC in 12-bit mode, using
+/// the 1.0V internal reference.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TemperatureCalibration {
+    /// Low calibration temperature, in hundredths of a degree Celsius
+    pub low_temperature: i32,
+    /// High calibration temperature, in hundredths of a degree Celsius
+    pub high_temperature: i32,
+    /// PTAT sensor conversion at the low temperature
+    pub ptat_low: u16,
+    /// PTAT sensor conversion at the high temperature
+    pub ptat_high: u16,
+    /// CTAT sensor conversion at the low temperature
+    pub ctat_low: u16,
+    /// CTAT sensor conversion at the high temperature
+    pub ctat_high: u16,
+}
+
+impl TemperatureCalibration {
+    /// Compute the temperature, in hundredths of a degree Celsius, from the
+    /// PTAT and CTAT sensor conversions
+    ///
+    /// See 45.6.3.1 Device Temperature Measurement.
+    pub fn temperature(&self, ptat: u16, ctat: u16) -> i32 {
+        let tl = i64::from(self.low_temperature);
+        let th = i64::from(self.high_temperature);
+        let vpl = i64::from(self.ptat_low);
+        let vph = i64::from(self.ptat_high);
+        let vcl = i64::from(self.ctat_low);
+        let vch = i64::from(self.ctat_high);
+        let tp = i64::from(ptat);
+        let tc = i64::from(ctat);
+
+        let numerator = tl * vph * tc - vpl * th * tc - tl * vch * tp + th * vcl * tp;
+        let denominator = vcl * tp - vch * tp - vpl * tc + vph * tc;
+        (numerator / denominator) as i32
+    }
+}
+
+/// Temperature sensor calibration values
+pub fn temperature_cal() -> TemperatureCalibration {
+    // The temperature log row is at address 0x00800100
+    let temperature = |int_shift, dec_shift| {
+        cal(0x80, int_shift, 0xff) as i32 * 100 + cal(0x80, dec_shift, 0xf) as i32 * 10
+    };
+    TemperatureCalibration {
+        low_temperature: temperature(0, 8),
+        high_temperature: temperature(12, 20),
+        ptat_low: cal(0x84, 8, 0xfff) as u16,
+        ptat_high: cal(0x84, 20, 0xfff) as u16,
+        ctat_low: cal(0x88, 0, 0xfff) as u16,
+        ctat_high: cal(0x88, 12, 0xfff) as u16,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TemperatureCalibration;
+
+    #[test]
+    fn temperature_interpolates_between_calibration_points() {
+        // PTAT conversions rising and CTAT conversions falling by 10 per
+        // degree
+        let cal = TemperatureCalibration {
+            low_temperature: 2500,
+            high_temperature: 8500,
+            ptat_low: 1250,
+            ptat_high: 1850,
+            ctat_low: 2750,
+            ctat_high: 2150,
+        };
+        assert_eq!(cal.temperature(1250, 2750), 2500);
+        assert_eq!(cal.temperature(1550, 2450), 5500);
+        // A reference voltage error scales both conversions and cancels out
+        assert_eq!(cal.temperature(1565, 2474), 5498);
+    }
+}
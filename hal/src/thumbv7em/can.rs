@@ -0,0 +1,890 @@
+//! # CAN - Controller Area Network
+//!
+//! The SAME5x chips embed one or two Bosch MCAN controllers supporting
+//! classic CAN 2.0 and CAN FD frames. This module drives them through the
+//! `embedded-can` traits: [`Frame`] implements [`embedded_can::Frame`] and
+//! [`Can`] implements [`embedded_can::nb::Can`].
+//!
+//! The controller keeps its filters, FIFOs and buffers in a message RAM
+//! located in system RAM. The driver uses a fixed layout, defined by
+//! [`MessageRam`]:
+//!
+//! * [`STANDARD_FILTERS`] standard ID filters and [`EXTENDED_FILTERS`]
+//!   extended ID filters
+//! * Two RX FIFOs of [`RX_FIFO_LEN`] elements each
+//! * A TX FIFO of [`TX_FIFO_LEN`] elements
+//!
+//! Every element holds up to 64 data bytes, so CAN FD frames can be used with
+//! any configuration. The message RAM must be located in the first 64 KiB of
+//! RAM, as the controller only stores the lower 16 bits of its addresses.
+//!
+//! Frames that do not match any filter are stored in RX FIFO 0 by default,
+//! see [`Config::non_matching`].
+//!
+//! This module is only available when the `can` feature is enabled.
+//!
+//! ```no_run
+//! use atsamd_hal::can::{BitTiming, Can, Config, Frame, MessageRam};
+//! use atsamd_hal::can::embedded_can::{nb::Can as _, Frame as _, StandardId};
+//!
+//! static mut MESSAGE_RAM: MessageRam = MessageRam::new();
+//!
+//! let gclk0 = clocks.gclk0();
+//! let can_clock = clocks.can1(&gclk0).unwrap();
+//! let timing = BitTiming::nominal(can_clock.freq(), 500_000).unwrap();
+//!
+//! let mut can = Can::new(
+//!     peripherals.CAN1,
+//!     pins.pb14,
+//!     pins.pb15,
+//!     unsafe { &mut MESSAGE_RAM },
+//!     &mut peripherals.MCLK,
+//!     Config::new(timing),
+//! );
+//!
+//! let frame = Frame::new(StandardId::new(0x123).unwrap(), &[1, 2, 3]).unwrap();
+//! nb::block!(can.transmit(&frame)).unwrap();
+//! let received = nb::block!(can.receive()).unwrap();
+//! ```
+
+use crate::{
+    bus_clock::BusClock,
+    gpio::v2::{Alternate, AlternateConfig, AnyPin, Pin, PinId},
+    gpio::v2::{PA22, PA23, PA24, PA25},
+    pac::{can0::RegisterBlock, CAN0, MCLK},
+    time::Hertz,
+    typelevel::Sealed,
+};
+#[cfg(feature = "min-samd51j")]
+use crate::{
+    gpio::v2::{PB12, PB13, PB14, PB15},
+    pac::CAN1,
+};
+use core::ops::Deref;
+
+use embedded_can::{ErrorKind, ExtendedId, Id, StandardId};
+
+pub use embedded_can;
+
+/// Number of standard ID filters in the message RAM
+pub const STANDARD_FILTERS: usize = 32;
+/// Number of extended ID filters in the message RAM
+pub const EXTENDED_FILTERS: usize = 16;
+/// Number of elements of each RX FIFO
+pub const RX_FIFO_LEN: usize = 8;
+/// Number of elements of the TX FIFO
+pub const TX_FIFO_LEN: usize = 8;
+
+/// Size of an RX or TX element, in words: two header words and 64 data bytes
+const ELEMENT_WORDS: usize = 2 + 64 / 4;
+
+/// Message RAM used by the controller
+///
+/// Create it with [`MessageRam::new`] in a `static`, located in the first
+/// 64 KiB of RAM.
+#[repr(C)]
+pub struct MessageRam {
+    standard_filters: [u32; STANDARD_FILTERS],
+    extended_filters: [[u32; 2]; EXTENDED_FILTERS],
+    rx_fifo0: [[u32; ELEMENT_WORDS]; RX_FIFO_LEN],
+    rx_fifo1: [[u32; ELEMENT_WORDS]; RX_FIFO_LEN],
+    tx_fifo: [[u32; ELEMENT_WORDS]; TX_FIFO_LEN],
+}
+
+impl MessageRam {
+    /// Create a zeroed message RAM
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        Self {
+            standard_filters: [0; STANDARD_FILTERS],
+            extended_filters: [[0; 2]; EXTENDED_FILTERS],
+            rx_fifo0: [[0; ELEMENT_WORDS]; RX_FIFO_LEN],
+            rx_fifo1: [[0; ELEMENT_WORDS]; RX_FIFO_LEN],
+            tx_fifo: [[0; ELEMENT_WORDS]; TX_FIFO_LEN],
+        }
+    }
+}
+
+/// Errors reported by the CAN driver
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Error {
+    /// An RX FIFO was full and frames were lost
+    Overrun,
+    /// The controller is in the bus off state
+    BusOff,
+    /// The filter index is out of range
+    FilterIndex,
+    /// A CAN FD frame was queued while CAN FD operation is disabled, as the
+    /// [`Config`] has no `data_timing`
+    FdDisabled,
+}
+
+impl embedded_can::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Overrun => ErrorKind::Overrun,
+            Error::BusOff | Error::FilterIndex | Error::FdDisabled => ErrorKind::Other,
+        }
+    }
+}
+
+/// Bit timing of a CAN bit, in time quanta
+///
+/// The bit consists of the synchronization segment, which lasts one time
+/// quantum, followed by `seg1` and `seg2`. The sample point is located
+/// between `seg1` and `seg2`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BitTiming {
+    /// Clock prescaler, dividing the peripheral clock into time quanta
+    pub prescaler: u16,
+    /// Time quanta before the sample point, excluding the synchronization
+    /// segment
+    pub seg1: u16,
+    /// Time quanta after the sample point
+    pub seg2: u8,
+    /// Synchronization jump width
+    pub sjw: u8,
+}
+
+impl BitTiming {
+    /// Compute a nominal bit timing for `bitrate`, with a sample point at
+    /// about 80%.
+    ///
+    /// Returns `None` if `bitrate` cannot be derived exactly from `clock`.
+    pub fn nominal(clock: Hertz, bitrate: u32) -> Option<Self> {
+        Self::compute(clock.0, bitrate, 512, 256, 128)
+    }
+
+    /// Compute a CAN FD data bit timing for `bitrate`, with a sample point at
+    /// about 80%.
+    ///
+    /// Returns `None` if `bitrate` cannot be derived exactly from `clock`.
+    pub fn data(clock: Hertz, bitrate: u32) -> Option<Self> {
+        Self::compute(clock.0, bitrate, 32, 32, 16)
+    }
+
+    fn compute(
+        clock: u32,
+        bitrate: u32,
+        max_prescaler: u32,
+        max_seg1: u32,
+        max_seg2: u32,
+    ) -> Option<Self> {
+        if bitrate == 0 {
+            return None;
+        }
+        // Use the smallest prescaler, which gives the finest resolution
+        (1..=max_prescaler).find_map(|prescaler| {
+            let divider = prescaler * bitrate;
+            if clock % divider != 0 {
+                return None;
+            }
+            let quanta = clock / divider;
+            if !(8..=1 + max_seg1 + max_seg2).contains(&quanta) {
+                return None;
+            }
+            let seg2 = core::cmp::max(quanta / 5, 1);
+            let seg1 = quanta - 1 - seg2;
+            if seg1 > max_seg1 || seg2 > max_seg2 {
+                return None;
+            }
+            Some(Self {
+                prescaler: prescaler as u16,
+                seg1: seg1 as u16,
+                seg2: seg2 as u8,
+                sjw: seg2 as u8,
+            })
+        })
+    }
+}
+
+/// Operating mode of the controller
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Mode {
+    /// Normal operation
+    Normal,
+    /// Only listen to the bus, without sending acknowledgements or frames
+    BusMonitoring,
+    /// Transmitted frames are received back while also being sent on the
+    /// bus
+    ExternalLoopback,
+    /// Transmitted frames are received back without reaching the bus
+    InternalLoopback,
+}
+
+/// Destination of frames accepted by a filter
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Fifo {
+    /// RX FIFO 0
+    Fifo0,
+    /// RX FIFO 1
+    Fifo1,
+}
+
+/// Action taken on frames matching a filter, or frames matching no filter
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FilterAction {
+    /// Store the frame in the given FIFO
+    Store(Fifo),
+    /// Drop the frame
+    Reject,
+}
+
+/// ID filter
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Filter<I> {
+    /// Match IDs in the inclusive range `from..=to`
+    Range {
+        /// First matching ID
+        from: I,
+        /// Last matching ID
+        to: I,
+    },
+    /// Match either of two IDs
+    Dual(I, I),
+    /// Match IDs that are equal to `id` for every bit set in `mask`
+    Mask {
+        /// ID to match
+        id: I,
+        /// Bits of the ID that are compared
+        mask: I,
+    },
+}
+
+/// Controller configuration
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Config {
+    /// Bit timing of the arbitration phase, and of the whole frame for
+    /// classic frames
+    pub nominal_timing: BitTiming,
+    /// Bit timing of the data phase of CAN FD frames. CAN FD is enabled when
+    /// this is set, with bit rate switching. Otherwise, CAN FD frames are
+    /// rejected by `transmit`.
+    pub data_timing: Option<BitTiming>,
+    /// Operating mode
+    pub mode: Mode,
+    /// Action taken on frames that match no filter
+    pub non_matching: FilterAction,
+}
+
+impl Config {
+    /// Classic CAN configuration in normal mode, storing all frames in RX
+    /// FIFO 0 until filters are configured
+    pub fn new(nominal_timing: BitTiming) -> Self {
+        Self {
+            nominal_timing,
+            data_timing: None,
+            mode: Mode::Normal,
+            non_matching: FilterAction::Store(Fifo::Fifo0),
+        }
+    }
+}
+
+/// Length of the data field for every DLC value
+const DLC_LENGTHS: [u8; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 12, 16, 20, 24, 32, 48, 64];
+
+/// Smallest DLC able to carry `len` bytes
+fn len_to_dlc(len: usize) -> Option<u8> {
+    DLC_LENGTHS
+        .iter()
+        .position(|&l| l as usize >= len)
+        .map(|dlc| dlc as u8)
+}
+
+/// CAN frame, either classic or FD
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+    id: Id,
+    remote: bool,
+    fd: bool,
+    bit_rate_switch: bool,
+    dlc: u8,
+    data: [u8; 64],
+}
+
+impl Frame {
+    /// Create a CAN FD frame.
+    ///
+    /// Data lengths that cannot be encoded are padded with zeros up to the
+    /// next valid length. Returns `None` if `data` is longer than 64 bytes.
+    /// With `bit_rate_switch` set, the data phase uses the data bit timing.
+    pub fn new_fd(id: impl Into<Id>, data: &[u8], bit_rate_switch: bool) -> Option<Self> {
+        let dlc = len_to_dlc(data.len())?;
+        let mut frame = Self {
+            id: id.into(),
+            remote: false,
+            fd: true,
+            bit_rate_switch,
+            dlc,
+            data: [0; 64],
+        };
+        frame.data[..data.len()].copy_from_slice(data);
+        Some(frame)
+    }
+
+    /// Returns `true` for CAN FD frames
+    #[inline]
+    pub fn is_fd(&self) -> bool {
+        self.fd
+    }
+
+    /// Returns `true` if the data phase of this CAN FD frame uses the data
+    /// bit timing
+    #[inline]
+    pub fn bit_rate_switch(&self) -> bool {
+        self.bit_rate_switch
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        if self.remote {
+            0
+        } else if !self.fd {
+            // Classic frames carry at most 8 bytes, DLCs above 8 included
+            (self.dlc as usize).min(8)
+        } else {
+            DLC_LENGTHS[self.dlc as usize] as usize
+        }
+    }
+
+    /// Encode the first two words of a TX element
+    fn header(&self) -> [u32; 2] {
+        let (xtd, id) = match self.id {
+            Id::Standard(id) => (0, (id.as_raw() as u32) << 18),
+            Id::Extended(id) => (1, id.as_raw()),
+        };
+        let t0 = (xtd << 30) | ((self.remote as u32) << 29) | id;
+        let t1 = ((self.fd as u32) << 21)
+            | ((self.bit_rate_switch as u32) << 20)
+            | ((self.dlc as u32) << 16);
+        [t0, t1]
+    }
+
+    /// Decode an RX element
+    fn from_element(element: &[u32; ELEMENT_WORDS]) -> Self {
+        let r0 = element[0];
+        let r1 = element[1];
+        let id = if r0 & (1 << 30) != 0 {
+            // SAFETY: The ID is masked to 29 bits
+            Id::Extended(unsafe { ExtendedId::new_unchecked(r0 & 0x1FFF_FFFF) })
+        } else {
+            // SAFETY: The ID is masked to 11 bits
+            Id::Standard(unsafe { StandardId::new_unchecked(((r0 >> 18) & 0x7FF) as u16) })
+        };
+
+        let mut frame = Self {
+            id,
+            remote: r0 & (1 << 29) != 0,
+            fd: r1 & (1 << 21) != 0,
+            bit_rate_switch: r1 & (1 << 20) != 0,
+            dlc: ((r1 >> 16) & 0xF) as u8,
+            data: [0; 64],
+        };
+        let len = frame.len();
+        for (bytes, word) in frame.data[..len].chunks_mut(4).zip(element[2..].iter()) {
+            bytes.copy_from_slice(&word.to_le_bytes()[..bytes.len()]);
+        }
+        frame
+    }
+}
+
+impl embedded_can::Frame for Frame {
+    fn new(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
+        if data.len() > 8 {
+            return None;
+        }
+        let mut frame = Self::new_fd(id, data, false)?;
+        frame.fd = false;
+        Some(frame)
+    }
+
+    fn new_remote(id: impl Into<Id>, dlc: usize) -> Option<Self> {
+        if dlc > 8 {
+            return None;
+        }
+        Some(Self {
+            id: id.into(),
+            remote: true,
+            fd: false,
+            bit_rate_switch: false,
+            dlc: dlc as u8,
+            data: [0; 64],
+        })
+    }
+
+    #[inline]
+    fn is_extended(&self) -> bool {
+        matches!(self.id, Id::Extended(_))
+    }
+
+    #[inline]
+    fn is_remote_frame(&self) -> bool {
+        self.remote
+    }
+
+    #[inline]
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    #[inline]
+    fn dlc(&self) -> usize {
+        self.dlc as usize
+    }
+
+    #[inline]
+    fn data(&self) -> &[u8] {
+        &self.data[..self.len()]
+    }
+}
+
+/// CAN peripheral instance
+pub trait Instance: Sealed + Deref<Target = RegisterBlock> {
+    #[doc(hidden)]
+    fn enable_mclk(mclk: &mut MCLK);
+    #[doc(hidden)]
+    fn disable_mclk(mclk: &mut MCLK);
+}
+
+/// Pin usable as the TX pin of CAN instance `I`
+pub trait TxPin<I: Instance>: PinId {
+    /// Alternate function of the pin
+    type Mode: AlternateConfig;
+}
+
+/// Pin usable as the RX pin of CAN instance `I`
+pub trait RxPin<I: Instance>: PinId {
+    /// Alternate function of the pin
+    type Mode: AlternateConfig;
+}
+
+macro_rules! can_instance {
+    ($Can:ident, $Mode:ident, [$($tx:ident),+], [$($rx:ident),+]) => {
+        impl Sealed for $Can {}
+
+        impl Instance for $Can {
+            #[inline]
+            fn enable_mclk(mclk: &mut MCLK) {
+                $Can::enable_bus_clock(mclk);
+            }
+
+            #[inline]
+            fn disable_mclk(mclk: &mut MCLK) {
+                $Can::disable_bus_clock(mclk);
+            }
+        }
+
+        $(
+            impl TxPin<$Can> for $tx {
+                type Mode = crate::gpio::v2::$Mode;
+            }
+        )+
+
+        $(
+            impl RxPin<$Can> for $rx {
+                type Mode = crate::gpio::v2::$Mode;
+            }
+        )+
+    };
+}
+
+can_instance!(CAN0, I, [PA22, PA24], [PA23, PA25]);
+#[cfg(feature = "min-samd51j")]
+can_instance!(CAN1, H, [PB12, PB14], [PB13, PB15]);
+
+/// TX pin of a [`Can`], configured for its alternate function
+pub type CanTx<I, Tx> = Pin<Tx, Alternate<<Tx as TxPin<I>>::Mode>>;
+
+/// RX pin of a [`Can`], configured for its alternate function
+pub type CanRx<I, Rx> = Pin<Rx, Alternate<<Rx as RxPin<I>>::Mode>>;
+
+/// CAN controller
+pub struct Can<I: Instance, Tx: TxPin<I>, Rx: RxPin<I>> {
+    can: I,
+    tx: CanTx<I, Tx>,
+    rx: CanRx<I, Rx>,
+    ram: &'static mut MessageRam,
+}
+
+impl<I: Instance, Tx: TxPin<I>, Rx: RxPin<I>> Can<I, Tx, Rx> {
+    /// Configure the controller and join the bus
+    ///
+    /// All filters are disabled. Don't forget to configure the generic clock
+    /// of the peripheral beforehand.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ram` is not located in the first 64 KiB of RAM.
+    pub fn new(
+        can: I,
+        tx: impl AnyPin<Id = Tx>,
+        rx: impl AnyPin<Id = Rx>,
+        ram: &'static mut MessageRam,
+        mclk: &mut MCLK,
+        config: Config,
+    ) -> Self {
+        let start = ram as *mut MessageRam as usize;
+        let end = start + core::mem::size_of::<MessageRam>();
+        assert!(start >= 0x2000_0000 && end <= 0x2001_0000);
+        *ram = MessageRam::new();
+
+        I::enable_mclk(mclk);
+
+        let mut can = Self {
+            can,
+            tx: tx.into().into_alternate(),
+            rx: rx.into().into_alternate(),
+            ram,
+        };
+        can.configure(config);
+        can
+    }
+
+    /// Leave the bus and disable the peripheral
+    pub fn free(self, mclk: &mut MCLK) -> (I, CanTx<I, Tx>, CanRx<I, Rx>) {
+        self.enter_init();
+        I::disable_mclk(mclk);
+        (self.can, self.tx, self.rx)
+    }
+
+    /// Apply a new configuration.
+    ///
+    /// The controller leaves the bus while it is reconfigured, which drops
+    /// any pending transmission.
+    pub fn configure(&mut self, config: Config) {
+        self.enter_init();
+        self.can.cccr.modify(|_, w| w.cce().set_bit());
+
+        let ram = &*self.ram;
+        let address = |ptr: *const u32| ptr as u32 as u16;
+
+        self.can.sidfc.write(|w| unsafe {
+            w.flssa().bits(address(ram.standard_filters.as_ptr()));
+            w.lss().bits(STANDARD_FILTERS as u8)
+        });
+        self.can.xidfc.write(|w| unsafe {
+            w.flesa()
+                .bits(address(ram.extended_filters.as_ptr().cast()));
+            w.lse().bits(EXTENDED_FILTERS as u8)
+        });
+        self.can.rxf0c.write(|w| unsafe {
+            w.f0sa().bits(address(ram.rx_fifo0.as_ptr().cast()));
+            w.f0s().bits(RX_FIFO_LEN as u8)
+        });
+        self.can.rxf1c.write(|w| unsafe {
+            w.f1sa().bits(address(ram.rx_fifo1.as_ptr().cast()));
+            w.f1s().bits(RX_FIFO_LEN as u8)
+        });
+        self.can.rxesc.write(|w| {
+            w.f0ds().data64();
+            w.f1ds().data64();
+            w.rbds().data64()
+        });
+        self.can.txbc.write(|w| unsafe {
+            w.tbsa().bits(address(ram.tx_fifo.as_ptr().cast()));
+            w.ndtb().bits(0);
+            w.tfqs().bits(TX_FIFO_LEN as u8);
+            w.tfqm().clear_bit()
+        });
+        self.can.txesc.write(|w| w.tbds().data64());
+
+        let nominal = config.nominal_timing;
+        // SAFETY: The register fields hold the values minus one
+        self.can.nbtp.write(|w| unsafe {
+            w.nbrp().bits(nominal.prescaler - 1);
+            w.ntseg1().bits((nominal.seg1 - 1) as u8);
+            w.ntseg2().bits(nominal.seg2 - 1);
+            w.nsjw().bits(nominal.sjw - 1)
+        });
+        if let Some(data) = config.data_timing {
+            // SAFETY: The register fields hold the values minus one
+            self.can.dbtp.write(|w| unsafe {
+                w.dbrp().bits((data.prescaler - 1) as u8);
+                w.dtseg1().bits((data.seg1 - 1) as u8);
+                w.dtseg2().bits(data.seg2 - 1);
+                w.dsjw().bits(data.sjw - 1)
+            });
+        }
+
+        self.can.gfc.write(|w| {
+            match config.non_matching {
+                FilterAction::Store(Fifo::Fifo0) => {
+                    w.anfs().rxf0();
+                    w.anfe().rxf0()
+                }
+                FilterAction::Store(Fifo::Fifo1) => {
+                    w.anfs().rxf1();
+                    w.anfe().rxf1()
+                }
+                FilterAction::Reject => {
+                    w.anfs().reject();
+                    w.anfe().reject()
+                }
+            };
+            w.rrfs().clear_bit();
+            w.rrfe().clear_bit()
+        });
+
+        let fd = config.data_timing.is_some();
+        let test = matches!(config.mode, Mode::ExternalLoopback | Mode::InternalLoopback);
+        self.can.cccr.modify(|_, w| {
+            w.fdoe().bit(fd);
+            w.brse().bit(fd);
+            w.mon().bit(matches!(
+                config.mode,
+                Mode::BusMonitoring | Mode::InternalLoopback
+            ));
+            w.test().bit(test)
+        });
+        self.can.test.write(|w| w.lbck().bit(test));
+
+        self.can.cccr.modify(|_, w| {
+            w.cce().clear_bit();
+            w.init().clear_bit()
+        });
+        while self.can.cccr.read().init().bit_is_set() {}
+    }
+
+    /// Set the standard ID filter at `index`.
+    ///
+    /// Frames are compared against the filters in index order, and the first
+    /// matching filter decides their fate.
+    pub fn set_standard_filter(
+        &mut self,
+        index: usize,
+        filter: Filter<StandardId>,
+        action: FilterAction,
+    ) -> Result<(), Error> {
+        let slot = self
+            .ram
+            .standard_filters
+            .get_mut(index)
+            .ok_or(Error::FilterIndex)?;
+        let (sft, id1, id2) = match filter {
+            Filter::Range { from, to } => (0, from, to),
+            Filter::Dual(a, b) => (1, a, b),
+            Filter::Mask { id, mask } => (2, id, mask),
+        };
+        let element = (sft << 30)
+            | ((filter_config(action) as u32) << 27)
+            | ((id1.as_raw() as u32) << 16)
+            | id2.as_raw() as u32;
+        // SAFETY: The slot is a valid message RAM location
+        unsafe { core::ptr::write_volatile(slot, element) };
+        Ok(())
+    }
+
+    /// Disable the standard ID filter at `index`
+    pub fn disable_standard_filter(&mut self, index: usize) -> Result<(), Error> {
+        let slot = self
+            .ram
+            .standard_filters
+            .get_mut(index)
+            .ok_or(Error::FilterIndex)?;
+        // SAFETY: The slot is a valid message RAM location
+        unsafe { core::ptr::write_volatile(slot, 0) };
+        Ok(())
+    }
+
+    /// Set the extended ID filter at `index`.
+    ///
+    /// Frames are compared against the filters in index order, and the first
+    /// matching filter decides their fate.
+    pub fn set_extended_filter(
+        &mut self,
+        index: usize,
+        filter: Filter<ExtendedId>,
+        action: FilterAction,
+    ) -> Result<(), Error> {
+        let slot = self
+            .ram
+            .extended_filters
+            .get_mut(index)
+            .ok_or(Error::FilterIndex)?;
+        // Range filters use EFT = 3, which ignores the global ID mask
+        let (eft, id1, id2) = match filter {
+            Filter::Range { from, to } => (3, from, to),
+            Filter::Dual(a, b) => (1, a, b),
+            Filter::Mask { id, mask } => (2, id, mask),
+        };
+        let f0 = ((filter_config(action) as u32) << 29) | id1.as_raw();
+        let f1 = (eft << 30) | id2.as_raw();
+        // SAFETY: The slot is a valid message RAM location
+        unsafe { core::ptr::write_volatile(slot, [f0, f1]) };
+        Ok(())
+    }
+
+    /// Disable the extended ID filter at `index`
+    pub fn disable_extended_filter(&mut self, index: usize) -> Result<(), Error> {
+        let slot = self
+            .ram
+            .extended_filters
+            .get_mut(index)
+            .ok_or(Error::FilterIndex)?;
+        // SAFETY: The slot is a valid message RAM location
+        unsafe { core::ptr::write_volatile(slot, [0, 0]) };
+        Ok(())
+    }
+
+    /// Receive a frame from a specific RX FIFO
+    pub fn receive_from(&mut self, fifo: Fifo) -> nb::Result<Frame, Error> {
+        let ir = self.can.ir.read();
+        let lost = match fifo {
+            Fifo::Fifo0 => ir.rf0l().bit_is_set(),
+            Fifo::Fifo1 => ir.rf1l().bit_is_set(),
+        };
+        if lost {
+            self.can.ir.write(|w| match fifo {
+                Fifo::Fifo0 => w.rf0l().set_bit(),
+                Fifo::Fifo1 => w.rf1l().set_bit(),
+            });
+            return Err(nb::Error::Other(Error::Overrun));
+        }
+
+        let (level, index) = match fifo {
+            Fifo::Fifo0 => {
+                let status = self.can.rxf0s.read();
+                (status.f0fl().bits(), status.f0gi().bits())
+            }
+            Fifo::Fifo1 => {
+                let status = self.can.rxf1s.read();
+                (status.f1fl().bits(), status.f1gi().bits())
+            }
+        };
+        if level == 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        let elements = match fifo {
+            Fifo::Fifo0 => &self.ram.rx_fifo0,
+            Fifo::Fifo1 => &self.ram.rx_fifo1,
+        };
+        // SAFETY: The controller is done writing the element until it is
+        // acknowledged
+        let element = unsafe { core::ptr::read_volatile(&elements[index as usize]) };
+        let frame = Frame::from_element(&element);
+
+        // SAFETY: The get index is a valid acknowledge index
+        match fifo {
+            Fifo::Fifo0 => self.can.rxf0a.write(|w| unsafe { w.f0ai().bits(index) }),
+            Fifo::Fifo1 => self.can.rxf1a.write(|w| unsafe { w.f1ai().bits(index) }),
+        }
+        Ok(frame)
+    }
+
+    /// Returns `true` if the controller is in the bus off state
+    #[inline]
+    pub fn is_bus_off(&self) -> bool {
+        self.can.psr.read().bo().bit_is_set()
+    }
+
+    /// Transmit and receive error counters
+    #[inline]
+    pub fn error_counters(&self) -> (u8, u8) {
+        let ecr = self.can.ecr.read();
+        (ecr.tec().bits(), ecr.rec().bits())
+    }
+
+    /// Stop participating in bus activity, as required for configuration
+    fn enter_init(&self) {
+        self.can.cccr.modify(|_, w| w.init().set_bit());
+        while self.can.cccr.read().init().bit_is_clear() {}
+    }
+}
+
+/// Encode a filter action as a filter element configuration
+fn filter_config(action: FilterAction) -> u8 {
+    match action {
+        FilterAction::Store(Fifo::Fifo0) => 1,
+        FilterAction::Store(Fifo::Fifo1) => 2,
+        FilterAction::Reject => 3,
+    }
+}
+
+impl<I: Instance, Tx: TxPin<I>, Rx: RxPin<I>> embedded_can::nb::Can for Can<I, Tx, Rx> {
+    type Frame = Frame;
+    type Error = Error;
+
+    /// Queue a frame in the TX FIFO
+    ///
+    /// Frames are sent in the order they were queued, so no frame is ever
+    /// replaced and `Ok(None)` is returned on success. CAN FD frames are
+    /// rejected with [`Error::FdDisabled`] unless the [`Config`] has a
+    /// `data_timing`.
+    fn transmit(&mut self, frame: &Frame) -> nb::Result<Option<Frame>, Error> {
+        if self.is_bus_off() {
+            return Err(nb::Error::Other(Error::BusOff));
+        }
+        if frame.is_fd() && self.can.cccr.read().fdoe().bit_is_clear() {
+            return Err(nb::Error::Other(Error::FdDisabled));
+        }
+        let status = self.can.txfqs.read();
+        if status.tfqf().bit_is_set() {
+            return Err(nb::Error::WouldBlock);
+        }
+        let index = status.tfqpi().bits() as usize;
+
+        let mut element = [0u32; ELEMENT_WORDS];
+        element[..2].copy_from_slice(&frame.header());
+        for (word, bytes) in element[2..]
+            .iter_mut()
+            .zip(embedded_can::Frame::data(frame).chunks(4))
+        {
+            let mut padded = [0; 4];
+            padded[..bytes.len()].copy_from_slice(bytes);
+            *word = u32::from_le_bytes(padded);
+        }
+        // SAFETY: The put index points to a free element of the TX FIFO
+        unsafe { core::ptr::write_volatile(&mut self.ram.tx_fifo[index], element) };
+
+        // SAFETY: Requesting the transmission of the element just written
+        self.can.txbar.write(|w| unsafe { w.bits(1 << index) });
+        Ok(None)
+    }
+
+    /// Receive a frame from RX FIFO 0, or RX FIFO 1 if FIFO 0 is empty
+    fn receive(&mut self) -> nb::Result<Frame, Error> {
+        match self.receive_from(Fifo::Fifo0) {
+            Err(nb::Error::WouldBlock) => self.receive_from(Fifo::Fifo1),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dlc_rounds_up_to_valid_lengths() {
+        assert_eq!(len_to_dlc(0), Some(0));
+        assert_eq!(len_to_dlc(8), Some(8));
+        assert_eq!(len_to_dlc(9), Some(9));
+        assert_eq!(len_to_dlc(33), Some(14));
+        assert_eq!(len_to_dlc(64), Some(15));
+        assert_eq!(len_to_dlc(65), None);
+    }
+
+    #[test]
+    fn classic_frame_len_is_at_most_8() {
+        let mut frame = Frame::new_fd(StandardId::ZERO, &[0; 12], false).unwrap();
+        assert_eq!(frame.len(), 12);
+        frame.fd = false;
+        assert_eq!(frame.len(), 8);
+    }
+
+    #[test]
+    fn bit_timing_matches_bitrate() {
+        let timing = BitTiming::nominal(Hertz(48_000_000), 500_000).unwrap();
+        let quanta = 1 + timing.seg1 as u32 + timing.seg2 as u32;
+        assert_eq!(48_000_000 / (timing.prescaler as u32 * quanta), 500_000);
+        assert_eq!(timing.prescaler, 1);
+
+        let timing = BitTiming::data(Hertz(48_000_000), 2_000_000).unwrap();
+        let quanta = 1 + timing.seg1 as u32 + timing.seg2 as u32;
+        assert_eq!(48_000_000 / (timing.prescaler as u32 * quanta), 2_000_000);
+
+        assert_eq!(BitTiming::nominal(Hertz(48_000_000), 0), None);
+        assert_eq!(BitTiming::data(Hertz(48_000_000), 7_000_000), None);
+    }
+}
@@ -3,6 +3,13 @@
 //! before you can set up most of the peripherals on the atsamd51 device.
 //! The other types in this module are used to enforce at compile time
 //! that the peripherals have been correctly configured.
+
+pub mod cfd;
+pub mod dpll;
+pub mod gclk_io;
+pub mod osc32k;
+
+use crate::bus_clock::BusClock;
 use crate::pac::gclk::genctrl::SRC_A::*;
 use crate::pac::gclk::pchctrl::GEN_A::*;
 use crate::pac::{self, GCLK, MCLK, NVMCTRL, OSC32KCTRL, OSCCTRL};
@@ -136,6 +143,14 @@ impl State {
         self.wait_for_sync();
     }
 
+    /// Return whether a clock generator other than gclk0 is fed by `src`
+    fn uses_source(&self, src: ClockSource) -> bool {
+        self.gclk.genctrl[1..].iter().any(|genctrl| {
+            let genctrl = genctrl.read();
+            genctrl.genen().bit_is_set() && genctrl.src().bits() == src as u8
+        })
+    }
+
     fn enable_clock_generator(&mut self, clock: ClockId, generator: ClockGenId) {
         self.gclk.pchctrl[u8::from(clock) as usize].write(|w| unsafe {
             w.gen().bits(generator.into());
@@ -159,6 +174,7 @@ impl State {
 pub struct GenericClockController {
     state: State,
     gclks: [Hertz; 12],
+    dplls: [Hertz; 2],
     used_clocks: u64,
 }
 
@@ -259,6 +275,7 @@ impl GenericClockController {
                 Hertz(0),
                 Hertz(0),
             ],
+            dplls: [OSC120M_FREQ, Hertz(0)],
             used_clocks: 1u64 << u8::from(ClockId::FDPLL0),
         }
     }
@@ -320,8 +337,9 @@ impl GenericClockController {
             XOSC32K | OSCULP32K => OSC32K_FREQ,
             GCLKGEN1 => self.gclks[1],
             DFLL => OSC48M_FREQ,
-            DPLL0 => OSC120M_FREQ,
-            XOSC0 | XOSC1 | GCLKIN | DPLL1 => unimplemented!(),
+            DPLL0 => self.dplls[0],
+            DPLL1 => self.dplls[1],
+            XOSC0 | XOSC1 | GCLKIN => unimplemented!(),
         };
         self.gclks[idx] = Hertz(freq.0 / divider as u32);
         Some(GClock { gclk, freq })
@@ -331,6 +349,102 @@ impl GenericClockController {
     pub fn configure_standby(&mut self, gclk: ClockGenId, enable: bool) {
         self.state.configure_standby(gclk, enable)
     }
+
+    /// Change the frequency of gclk0, the CPU clock, at runtime.
+    ///
+    /// `freq` must be 48MHz or the frequency of DPLL0, 120MHz unless
+    /// reconfigured with [`configure_dpll`](Self::configure_dpll), divided by
+    /// an integer below 256, such as 12MHz. gclk0 is fed by the DFLL48M
+    /// whenever possible; DPLL0 is stopped while no clock generator uses it,
+    /// and restarted when needed. The flash wait states are adjusted,
+    /// assuming VDD above 2.7V.
+    ///
+    /// The peripherals clocked by gclk0 run at the new frequency as well;
+    /// each of `listeners` is notified of it.
+    /// Returns `None`, leaving the clocks untouched, if `freq` can't be
+    /// produced.
+    pub fn set_cpu_frequency(
+        &mut self,
+        nvmctrl: &mut NVMCTRL,
+        oscctrl: &mut OSCCTRL,
+        freq: Hertz,
+        listeners: &mut [&mut dyn CpuFrequencyListener],
+    ) -> Option<GClock> {
+        let (src, divider) = cpu_clock_source(freq, self.dplls[0])?;
+        let raising = freq.0 > self.gclks[0].0;
+
+        if src == DPLL0 && oscctrl.dpll[0].dpllctrla.read().enable().bit_is_clear() {
+            // Restart DPLL0 with its last configuration
+            oscctrl.dpll[0]
+                .dpllctrla
+                .modify(|_, w| w.enable().set_bit());
+            wait_for_dpllrdy(oscctrl);
+        }
+        // Add wait states before speeding up, remove them after slowing down
+        if raising {
+            set_flash_wait_states(nvmctrl, flash_wait_states(freq));
+        }
+        self.state.gclk.genctrl[0].modify(|_, w| unsafe {
+            w.src().variant(src);
+            w.div().bits(divider)
+        });
+        while self.state.gclk.syncbusy.read().genctrl().is_gclk0() {}
+        if !raising {
+            set_flash_wait_states(nvmctrl, flash_wait_states(freq));
+        }
+        if src != DPLL0 && !self.state.uses_source(DPLL0) {
+            oscctrl.dpll[0]
+                .dpllctrla
+                .modify(|_, w| w.enable().clear_bit());
+            while oscctrl.dpll[0].dpllsyncbusy.read().enable().bit_is_set() {}
+        }
+
+        self.gclks[0] = freq;
+        for listener in listeners.iter_mut() {
+            listener.cpu_frequency_changed(freq);
+        }
+        Some(self.gclk0())
+    }
+}
+
+/// A driver whose timings depend on the frequency of gclk0, such as a
+/// delay counting CPU cycles
+///
+/// It is implemented by [`Delay`](crate::delay::Delay). See
+/// [`GenericClockController::set_cpu_frequency`].
+pub trait CpuFrequencyListener {
+    /// Called after gclk0 switched to `freq`
+    fn cpu_frequency_changed(&mut self, freq: Hertz);
+}
+
+/// Number of flash wait states needed at `freq`, with VDD above 2.7V
+pub fn flash_wait_states(freq: Hertz) -> u8 {
+    const MAX_FREQS: [u32; 5] = [24_000_000, 51_000_000, 77_000_000, 101_000_000, 119_000_000];
+    MAX_FREQS.iter().take_while(|&&max| freq.0 > max).count() as u8
+}
+
+/// Find the source of gclk0, and its divider, producing `freq`, with DPLL0
+/// running at `dpll0_freq`
+fn cpu_clock_source(freq: Hertz, dpll0_freq: Hertz) -> Option<(ClockSource, u16)> {
+    if freq.0 == 0 {
+        return None;
+    }
+    [(DFLL, OSC48M_FREQ), (DPLL0, dpll0_freq)]
+        .iter()
+        .find_map(|&(src, source_freq)| {
+            let divider = source_freq.0 / freq.0;
+            if source_freq.0 % freq.0 == 0 && (1..256).contains(&divider) {
+                Some((src, divider as u16))
+            } else {
+                None
+            }
+        })
+}
+
+fn set_flash_wait_states(nvmctrl: &mut NVMCTRL, wait_states: u8) {
+    nvmctrl
+        .ctrla
+        .modify(|_, w| unsafe { w.rws().bits(wait_states) });
 }
 
 macro_rules! clock_generator {
@@ -465,7 +579,7 @@ fn set_flash_to_half_auto_wait_state(nvmctrl: &mut NVMCTRL) {
 }
 
 fn enable_gclk_apb(mclk: &mut MCLK) {
-    mclk.apbamask.modify(|_, w| w.gclk_().set_bit());
+    GCLK::enable_bus_clock(mclk);
 }
 
 /// Turn on the internal 32hkz oscillator
@@ -543,3 +657,30 @@ fn configure_usb_correction(oscctrl: &mut OSCCTRL) {
     });
     while oscctrl.dfllsync.read().dfllctrlb().bit_is_set() {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_clock_sources() {
+        assert_eq!(cpu_clock_source(Hertz(12_000_000), OSC120M_FREQ), Some((DFLL, 4)));
+        assert_eq!(cpu_clock_source(Hertz(48_000_000), OSC120M_FREQ), Some((DFLL, 1)));
+        assert_eq!(cpu_clock_source(Hertz(60_000_000), OSC120M_FREQ), Some((DPLL0, 2)));
+        assert_eq!(cpu_clock_source(Hertz(120_000_000), OSC120M_FREQ), Some((DPLL0, 1)));
+        assert_eq!(cpu_clock_source(Hertz(50_000_000), OSC120M_FREQ), None);
+        assert_eq!(cpu_clock_source(Hertz(0), OSC120M_FREQ), None);
+        // DPLL0 reconfigured to 100MHz
+        assert_eq!(
+            cpu_clock_source(Hertz(50_000_000), Hertz(100_000_000)),
+            Some((DPLL0, 2))
+        );
+    }
+
+    #[test]
+    fn wait_states() {
+        assert_eq!(flash_wait_states(Hertz(12_000_000)), 0);
+        assert_eq!(flash_wait_states(Hertz(48_000_000)), 1);
+        assert_eq!(flash_wait_states(Hertz(120_000_000)), 5);
+    }
+}
@@ -0,0 +1,263 @@
+//! Clock failure detection
+//!
+//! The clock failure detectors monitor the external oscillators, XOSC0,
+//! XOSC1 and XOSC32K, against a safe clock. When the oscillator stops, for
+//! example because its crystal is damaged, its output is switched to the safe
+//! clock, so that the clocks derived from it keep running, and the failure
+//! interrupt is raised:
+//!
+//! - XOSC0 and XOSC1 switch to the DFLL48M, divided by a prescaler computed
+//!   by [`xosc_prescaler`]. They can switch back to the oscillator
+//!   automatically once it runs again.
+//! - XOSC32K switches to the OSCULP32K. Switching back is requested with
+//!   [`ClockFailureDetector::switch_back_xosc32k`].
+//!
+//! [`ClockFailureDetector::on_interrupt`] clears the failure flags and calls
+//! the callback set with [`ClockFailureDetector::on_failure`]. Call it from
+//! the `OSCCTRL_XOSC0`, `OSCCTRL_XOSC1` and `OSC32KCTRL` interrupt handlers,
+//! unmasked in the NVIC.
+//!
+//! ```no_run
+//! use atsamd_hal::clock::cfd::{ClockFailureDetector, Oscillator};
+//!
+//! let mut clocks = GenericClockController::with_external_32kosc(
+//!     peripherals.GCLK,
+//!     &mut peripherals.MCLK,
+//!     &mut peripherals.OSC32KCTRL,
+//!     &mut peripherals.OSCCTRL,
+//!     &mut peripherals.NVMCTRL,
+//! );
+//! let mut cfd = ClockFailureDetector::new().on_failure(|osc| {
+//!     // Log the failure, and lower the expectations on timing accuracy
+//! });
+//! cfd.enable_xosc32k(&mut peripherals.OSC32KCTRL);
+//!
+//! // Later, in the main loop
+//! if cfd.is_switched(Oscillator::Xosc32k) {
+//!     cfd.switch_back_xosc32k(&mut peripherals.OSC32KCTRL);
+//! }
+//! ```
+
+use crate::pac::{OSC32KCTRL, OSCCTRL};
+use crate::time::Hertz;
+
+use super::OSC48M_FREQ;
+
+/// External high-frequency oscillator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Xosc {
+    /// XOSC0
+    Xosc0,
+    /// XOSC1
+    Xosc1,
+}
+
+/// Oscillator monitored by a clock failure detector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Oscillator {
+    /// XOSC0
+    Xosc0,
+    /// XOSC1
+    Xosc1,
+    /// XOSC32K
+    Xosc32k,
+}
+
+impl From<Xosc> for Oscillator {
+    #[inline]
+    fn from(xosc: Xosc) -> Self {
+        match xosc {
+            Xosc::Xosc0 => Oscillator::Xosc0,
+            Xosc::Xosc1 => Oscillator::Xosc1,
+        }
+    }
+}
+
+/// Compute the prescaler of the safe clock of XOSC0 and XOSC1, for an
+/// oscillator running at `freq`
+///
+/// The DFLL48M is divided by `2^prescaler`, which must give a frequency lower
+/// than the one of the oscillator.
+#[inline]
+pub fn xosc_prescaler(freq: Hertz) -> u8 {
+    (0..15)
+        .find(|&prescaler| OSC48M_FREQ.0 >> prescaler < freq.0)
+        .unwrap_or(15)
+}
+
+/// Clock failure detectors of the external oscillators
+///
+/// See the [module-level documentation](self) for more details.
+#[derive(Default)]
+pub struct ClockFailureDetector {
+    on_failure: Option<fn(Oscillator)>,
+}
+
+impl ClockFailureDetector {
+    /// Create a detector without callback
+    ///
+    /// No oscillator is monitored until enabled.
+    #[inline]
+    pub fn new() -> Self {
+        Self { on_failure: None }
+    }
+
+    /// Call `callback` when an oscillator fails (builder pattern version)
+    #[inline]
+    pub fn on_failure(mut self, callback: fn(Oscillator)) -> Self {
+        self.set_on_failure(Some(callback));
+        self
+    }
+
+    /// Call `callback` when an oscillator fails (setter version)
+    #[inline]
+    pub fn set_on_failure(&mut self, callback: Option<fn(Oscillator)>) {
+        self.on_failure = callback;
+    }
+
+    /// Monitor `xosc`, running at `freq`, and enable its failure interrupt
+    ///
+    /// With `switch_back`, the output returns to the oscillator as soon as it
+    /// runs again; otherwise it stays on the safe clock until the oscillator
+    /// is restarted.
+    #[inline]
+    pub fn enable_xosc(
+        &mut self,
+        oscctrl: &mut OSCCTRL,
+        xosc: Xosc,
+        freq: Hertz,
+        switch_back: bool,
+    ) {
+        let prescaler = xosc_prescaler(freq);
+        oscctrl.xoscctrl[xosc as usize].modify(|_, w| {
+            // SAFETY: The prescaler is 4 bits wide
+            unsafe { w.cfdpresc().bits(prescaler) };
+            w.swben().bit(switch_back);
+            w.cfden().set_bit()
+        });
+        match xosc {
+            Xosc::Xosc0 => oscctrl.intenset.write(|w| w.xoscfail0().set_bit()),
+            Xosc::Xosc1 => oscctrl.intenset.write(|w| w.xoscfail1().set_bit()),
+        }
+    }
+
+    /// Stop monitoring `xosc`, and disable its failure interrupt
+    #[inline]
+    pub fn disable_xosc(&mut self, oscctrl: &mut OSCCTRL, xosc: Xosc) {
+        match xosc {
+            Xosc::Xosc0 => oscctrl.intenclr.write(|w| w.xoscfail0().set_bit()),
+            Xosc::Xosc1 => oscctrl.intenclr.write(|w| w.xoscfail1().set_bit()),
+        }
+        oscctrl.xoscctrl[xosc as usize].modify(|_, w| w.cfden().clear_bit());
+    }
+
+    /// Monitor XOSC32K, and enable its failure interrupt
+    #[inline]
+    pub fn enable_xosc32k(&mut self, osc32kctrl: &mut OSC32KCTRL) {
+        osc32kctrl.cfdctrl.modify(|_, w| {
+            w.cfdpresc().clear_bit();
+            w.cfden().set_bit()
+        });
+        osc32kctrl.intenset.write(|w| w.xosc32kfail().set_bit());
+    }
+
+    /// Stop monitoring XOSC32K, and disable its failure interrupt
+    #[inline]
+    pub fn disable_xosc32k(&mut self, osc32kctrl: &mut OSC32KCTRL) {
+        osc32kctrl.intenclr.write(|w| w.xosc32kfail().set_bit());
+        osc32kctrl.cfdctrl.modify(|_, w| w.cfden().clear_bit());
+    }
+
+    /// Switch the output of XOSC32K back to the crystal, after a failure
+    ///
+    /// The crystal must be running again; check with
+    /// [`is_switched`](Self::is_switched) that the switch happened.
+    #[inline]
+    pub fn switch_back_xosc32k(&mut self, osc32kctrl: &mut OSC32KCTRL) {
+        osc32kctrl.cfdctrl.modify(|_, w| w.swback().set_bit());
+    }
+
+    /// Return whether the clock failure detector reports `osc` as failed
+    #[inline]
+    pub fn has_failed(&self, osc: Oscillator) -> bool {
+        match osc {
+            Oscillator::Xosc0 => oscctrl().status.read().xoscfail0().bit_is_set(),
+            Oscillator::Xosc1 => oscctrl().status.read().xoscfail1().bit_is_set(),
+            Oscillator::Xosc32k => osc32kctrl().status.read().xosc32kfail().bit_is_set(),
+        }
+    }
+
+    /// Return whether the output of `osc` is switched to the safe clock
+    #[inline]
+    pub fn is_switched(&self, osc: Oscillator) -> bool {
+        match osc {
+            Oscillator::Xosc0 => oscctrl().status.read().xosccksw0().bit_is_set(),
+            Oscillator::Xosc1 => oscctrl().status.read().xosccksw1().bit_is_set(),
+            Oscillator::Xosc32k => osc32kctrl().status.read().xosc32ksw().bit_is_set(),
+        }
+    }
+
+    /// Clear the failure flags, and call the callback for each failed
+    /// oscillator
+    ///
+    /// Call this function from the `OSCCTRL_XOSC0`, `OSCCTRL_XOSC1` and
+    /// `OSC32KCTRL` interrupt handlers.
+    #[inline]
+    pub fn on_interrupt(&mut self) {
+        let oscctrl = oscctrl();
+        let osc32kctrl = osc32kctrl();
+        let flags = oscctrl.intflag.read();
+        let failed = [
+            (Oscillator::Xosc0, flags.xoscfail0().bit_is_set()),
+            (Oscillator::Xosc1, flags.xoscfail1().bit_is_set()),
+            (
+                Oscillator::Xosc32k,
+                osc32kctrl.intflag.read().xosc32kfail().bit_is_set(),
+            ),
+        ];
+        // INTFLAG bits are cleared by writing 1, so other bits are unaffected
+        oscctrl.intflag.write(|w| {
+            w.xoscfail0().bit(failed[0].1);
+            w.xoscfail1().bit(failed[1].1)
+        });
+        osc32kctrl
+            .intflag
+            .write(|w| w.xosc32kfail().bit(failed[2].1));
+
+        if let Some(callback) = self.on_failure {
+            for &(osc, _) in failed.iter().filter(|(_, failed)| *failed) {
+                callback(osc);
+            }
+        }
+    }
+}
+
+/// Access the OSCCTRL registers
+///
+/// Only the STATUS and INTFLAG registers are accessed this way, whose reads
+/// have no side effect and whose bits are cleared independently.
+fn oscctrl() -> &'static crate::pac::oscctrl::RegisterBlock {
+    // SAFETY: See above
+    unsafe { &*OSCCTRL::ptr() }
+}
+
+/// Access the OSC32KCTRL registers
+///
+/// See [`oscctrl`].
+fn osc32kctrl() -> &'static crate::pac::osc32kctrl::RegisterBlock {
+    // SAFETY: See above
+    unsafe { &*OSC32KCTRL::ptr() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prescalers() {
+        assert_eq!(xosc_prescaler(Hertz(12_000_000)), 3);
+        assert_eq!(xosc_prescaler(Hertz(16_000_000)), 2);
+        assert_eq!(xosc_prescaler(Hertz(32_000_000)), 1);
+        assert_eq!(xosc_prescaler(Hertz(8_000_000)), 3);
+    }
+}
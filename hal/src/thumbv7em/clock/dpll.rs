@@ -0,0 +1,454 @@
+//! Fractional digital PLLs
+//!
+//! FDPLL0 and FDPLL1 multiply a reference clock of 32kHz to 3.2MHz by a
+//! fractional ratio, giving a frequency of 96MHz to 200MHz:
+//!
+//! `f = f_ref * (LDR + 1 + LDRFRAC / 32)`
+//!
+//! The reference is XOSC32K, XOSC0 or XOSC1, divided by `2 * (div + 1)`, or a
+//! clock generator. [`DpllConfig::new`] finds the ratio closest to a target
+//! frequency, and [`DpllConfig::with_ratio`] takes explicit LDR and LDRFRAC
+//! values.
+//!
+//! [`GenericClockController::configure_dpll`] restarts the DPLL with the
+//! configuration, waits for its lock and records its frequency, so that clock
+//! generators fed by it, configured with
+//! [`GenericClockController::configure_gclk_divider_and_source`], know their
+//! frequency. A DPLL feeding a clock generator can't be reconfigured; switch
+//! gclk0 to the DFLL48M first with
+//! [`GenericClockController::set_cpu_frequency`] to reconfigure DPLL0.
+//!
+//! ```no_run
+//! use atsamd_hal::clock::dpll::{Dpll, DpllConfig, LockTimeout, Reference};
+//!
+//! // 119.808MHz, a multiple of the 48kHz audio sample rate, from XOSC32K
+//! let config = DpllConfig::new(Reference::Xosc32k, Hertz(119_808_000))
+//!     .unwrap()
+//!     .lock_timeout(LockTimeout::Ms1);
+//! let freq = clocks
+//!     .configure_dpll(&mut peripherals.OSCCTRL, Dpll::Dpll1, config)
+//!     .unwrap();
+//! let gclk2 = clocks
+//!     .configure_gclk_divider_and_source(GCLK2, 1, DPLL1, false)
+//!     .unwrap();
+//! ```
+
+use crate::pac::OSCCTRL;
+use crate::time::Hertz;
+
+use super::{ClockId, ClockSource, GClock, GenericClockController, OSC32K_FREQ};
+
+/// Lowest reference frequency
+pub const MIN_REF_FREQ: Hertz = Hertz(32_000);
+/// Highest reference frequency
+pub const MAX_REF_FREQ: Hertz = Hertz(3_200_000);
+/// Lowest output frequency
+pub const MIN_FREQ: Hertz = Hertz(96_000_000);
+/// Highest output frequency
+pub const MAX_FREQ: Hertz = Hertz(200_000_000);
+
+/// Highest value of LDR
+const MAX_LDR: u16 = (1 << 13) - 1;
+/// Highest value of LDRFRAC
+const MAX_LDRFRAC: u8 = 31;
+/// Highest value of the divider of XOSC0 and XOSC1
+const MAX_DIV: u16 = (1 << 11) - 1;
+
+/// One of the two DPLLs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dpll {
+    /// FDPLL0
+    Dpll0,
+    /// FDPLL1
+    Dpll1,
+}
+
+impl Dpll {
+    /// Clock generator source fed by the DPLL
+    #[inline]
+    pub fn source(self) -> ClockSource {
+        match self {
+            Dpll::Dpll0 => ClockSource::DPLL0,
+            Dpll::Dpll1 => ClockSource::DPLL1,
+        }
+    }
+
+    fn clock_id(self) -> ClockId {
+        match self {
+            Dpll::Dpll0 => ClockId::FDPLL0,
+            Dpll::Dpll1 => ClockId::FDPLL1,
+        }
+    }
+}
+
+/// Reference clock of a DPLL
+pub enum Reference {
+    /// A clock generator, connected through the FDPLL0 or FDPLL1 peripheral
+    /// channel
+    Gclk(GClock),
+    /// XOSC32K, which must be running
+    Xosc32k,
+    /// XOSC0, running at `freq`, divided by `2 * (div + 1)`
+    Xosc0 {
+        /// Frequency of the oscillator
+        freq: Hertz,
+        /// Divider, up to 2047
+        div: u16,
+    },
+    /// XOSC1, running at `freq`, divided by `2 * (div + 1)`
+    Xosc1 {
+        /// Frequency of the oscillator
+        freq: Hertz,
+        /// Divider, up to 2047
+        div: u16,
+    },
+}
+
+impl Reference {
+    /// Frequency seen by the DPLL, after the divider of XOSC0 and XOSC1
+    #[inline]
+    pub fn freq(&self) -> Hertz {
+        match self {
+            Reference::Gclk(gclk) => gclk.freq,
+            Reference::Xosc32k => OSC32K_FREQ,
+            Reference::Xosc0 { freq, div } | Reference::Xosc1 { freq, div } => {
+                Hertz(freq.0 / (2 * (*div as u32 + 1)))
+            }
+        }
+    }
+
+    fn div(&self) -> u16 {
+        match self {
+            Reference::Xosc0 { div, .. } | Reference::Xosc1 { div, .. } => *div,
+            _ => 0,
+        }
+    }
+}
+
+/// Time to wait for the lock before raising the lock timeout
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockTimeout {
+    /// Wait forever
+    None = 0,
+    /// 800µs
+    Us800 = 4,
+    /// 900µs
+    Us900 = 5,
+    /// 1ms
+    Ms1 = 6,
+    /// 1.1ms
+    Ms1_1 = 7,
+}
+
+/// Errors of the DPLL configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The reference frequency isn't within 32kHz to 3.2MHz, or the divider
+    /// is above 2047
+    Reference,
+    /// LDR is above 8191 or LDRFRAC above 31
+    Ratio,
+    /// The output frequency isn't within 96MHz to 200MHz
+    Frequency,
+    /// The DPLL feeds a clock generator
+    InUse,
+    /// The DPLL didn't lock within the lock timeout
+    LockTimeout,
+}
+
+/// Compute the LDR and LDRFRAC values giving the frequency closest to
+/// `freq`, from a reference running at `ref_freq`
+///
+/// Returns `None` if the ratio is out of range.
+pub fn ratio_for(ref_freq: Hertz, freq: Hertz) -> Option<(u16, u8)> {
+    if ref_freq.0 == 0 {
+        return None;
+    }
+    // Ratio in 1/32 steps, rounded to the closest
+    let ratio = (freq.0 as u64 * 32 + ref_freq.0 as u64 / 2) / ref_freq.0 as u64;
+    let ldr = (ratio / 32).checked_sub(1)?;
+    if ldr > MAX_LDR as u64 {
+        return None;
+    }
+    Some((ldr as u16, (ratio % 32) as u8))
+}
+
+/// Output frequency of a DPLL with a reference at `ref_freq`, LDR and
+/// LDRFRAC
+#[inline]
+pub fn output_freq(ref_freq: Hertz, ldr: u16, ldrfrac: u8) -> Hertz {
+    let ratio = (ldr as u64 + 1) * 32 + ldrfrac as u64;
+    Hertz((ref_freq.0 as u64 * ratio / 32) as u32)
+}
+
+/// Configuration of a DPLL
+///
+/// See the [module-level documentation](self) for more details.
+pub struct DpllConfig {
+    reference: Reference,
+    ldr: u16,
+    ldrfrac: u8,
+    lock_timeout: LockTimeout,
+    wake_up_fast: bool,
+    run_standby: bool,
+}
+
+impl DpllConfig {
+    /// Multiply `reference` by the ratio closest to `freq`
+    pub fn new(reference: Reference, freq: Hertz) -> Result<Self, Error> {
+        let (ldr, ldrfrac) = ratio_for(reference.freq(), freq).ok_or(Error::Ratio)?;
+        Self::with_ratio(reference, ldr, ldrfrac)
+    }
+
+    /// Multiply `reference` by `LDR + 1 + LDRFRAC / 32`
+    pub fn with_ratio(reference: Reference, ldr: u16, ldrfrac: u8) -> Result<Self, Error> {
+        let ref_freq = reference.freq();
+        if reference.div() > MAX_DIV || ref_freq.0 < MIN_REF_FREQ.0 || ref_freq.0 > MAX_REF_FREQ.0 {
+            return Err(Error::Reference);
+        }
+        if ldr > MAX_LDR || ldrfrac > MAX_LDRFRAC {
+            return Err(Error::Ratio);
+        }
+        let freq = output_freq(ref_freq, ldr, ldrfrac);
+        if freq.0 < MIN_FREQ.0 || freq.0 > MAX_FREQ.0 {
+            return Err(Error::Frequency);
+        }
+        Ok(Self {
+            reference,
+            ldr,
+            ldrfrac,
+            lock_timeout: LockTimeout::None,
+            wake_up_fast: false,
+            run_standby: false,
+        })
+    }
+
+    /// Get the reference clock
+    #[inline]
+    pub fn get_reference(&self) -> &Reference {
+        &self.reference
+    }
+
+    /// Get the LDR and LDRFRAC values
+    #[inline]
+    pub fn get_ratio(&self) -> (u16, u8) {
+        (self.ldr, self.ldrfrac)
+    }
+
+    /// Output frequency of the DPLL
+    #[inline]
+    pub fn freq(&self) -> Hertz {
+        output_freq(self.reference.freq(), self.ldr, self.ldrfrac)
+    }
+
+    /// Set the lock timeout (builder pattern version)
+    ///
+    /// The default is to wait forever.
+    #[inline]
+    pub fn lock_timeout(mut self, timeout: LockTimeout) -> Self {
+        self.set_lock_timeout(timeout);
+        self
+    }
+
+    /// Set the lock timeout (setter version)
+    ///
+    /// The default is to wait forever.
+    #[inline]
+    pub fn set_lock_timeout(&mut self, timeout: LockTimeout) {
+        self.lock_timeout = timeout;
+    }
+
+    /// Get the lock timeout
+    #[inline]
+    pub fn get_lock_timeout(&self) -> LockTimeout {
+        self.lock_timeout
+    }
+
+    /// Output the clock right after startup, before the lock (builder
+    /// pattern version)
+    #[inline]
+    pub fn wake_up_fast(mut self, enable: bool) -> Self {
+        self.set_wake_up_fast(enable);
+        self
+    }
+
+    /// Output the clock right after startup, before the lock (setter
+    /// version)
+    #[inline]
+    pub fn set_wake_up_fast(&mut self, enable: bool) {
+        self.wake_up_fast = enable;
+    }
+
+    /// Get whether the clock is output before the lock
+    #[inline]
+    pub fn get_wake_up_fast(&self) -> bool {
+        self.wake_up_fast
+    }
+
+    /// Keep the DPLL running in standby (builder pattern version)
+    #[inline]
+    pub fn run_standby(mut self, enable: bool) -> Self {
+        self.set_run_standby(enable);
+        self
+    }
+
+    /// Keep the DPLL running in standby (setter version)
+    #[inline]
+    pub fn set_run_standby(&mut self, enable: bool) {
+        self.run_standby = enable;
+    }
+
+    /// Get whether the DPLL runs in standby
+    #[inline]
+    pub fn get_run_standby(&self) -> bool {
+        self.run_standby
+    }
+}
+
+impl GenericClockController {
+    /// Configure and enable `dpll`, and wait for its lock
+    ///
+    /// Returns the frequency of the DPLL, which clock generators fed by it
+    /// are configured with from now on. The DPLL is left disabled on a lock
+    /// timeout.
+    pub fn configure_dpll(
+        &mut self,
+        oscctrl: &mut OSCCTRL,
+        dpll: Dpll,
+        config: DpllConfig,
+    ) -> Result<Hertz, Error> {
+        let src = dpll.source();
+        let gclk0_src = self.state.gclk.genctrl[0].read().src().bits();
+        if gclk0_src == src as u8 || self.state.uses_source(src) {
+            return Err(Error::InUse);
+        }
+
+        let regs = &oscctrl.dpll[dpll as usize];
+        regs.dpllctrla.modify(|_, w| w.enable().clear_bit());
+        while regs.dpllsyncbusy.read().enable().bit_is_set() {}
+
+        if let Reference::Gclk(gclk) = &config.reference {
+            self.state
+                .enable_clock_generator(dpll.clock_id(), gclk.gclk);
+            self.used_clocks |= 1u64 << u8::from(dpll.clock_id());
+        }
+
+        regs.dpllratio.write(|w| unsafe {
+            w.ldr().bits(config.ldr);
+            w.ldrfrac().bits(config.ldrfrac)
+        });
+        while regs.dpllsyncbusy.read().dpllratio().bit_is_set() {}
+        regs.dpllctrlb.write(|w| {
+            match config.reference {
+                Reference::Gclk(_) => w.refclk().gclk(),
+                Reference::Xosc32k => w.refclk().xosc32(),
+                Reference::Xosc0 { .. } => w.refclk().xosc0(),
+                Reference::Xosc1 { .. } => w.refclk().xosc1(),
+            };
+            // SAFETY: The lock timeout values are valid, and the divider is
+            // checked by `DpllConfig::with_ratio`
+            unsafe {
+                w.ltime().bits(config.lock_timeout as u8);
+                w.div().bits(config.reference.div());
+            }
+            w.wuf().bit(config.wake_up_fast)
+        });
+
+        clear_lock_timeout(oscctrl, dpll);
+        regs.dpllctrla.write(|w| {
+            w.runstdby().bit(config.run_standby);
+            w.ondemand().clear_bit();
+            w.enable().set_bit()
+        });
+        while regs.dpllsyncbusy.read().enable().bit_is_set() {}
+
+        loop {
+            let status = regs.dpllstatus.read();
+            if status.lock().bit_is_set() && status.clkrdy().bit_is_set() {
+                break;
+            }
+            if lock_timed_out(oscctrl, dpll) {
+                clear_lock_timeout(oscctrl, dpll);
+                regs.dpllctrla.modify(|_, w| w.enable().clear_bit());
+                while regs.dpllsyncbusy.read().enable().bit_is_set() {}
+                self.dplls[dpll as usize] = Hertz(0);
+                return Err(Error::LockTimeout);
+            }
+        }
+
+        let freq = config.freq();
+        self.dplls[dpll as usize] = freq;
+        Ok(freq)
+    }
+
+    /// Frequency of `dpll`, as last configured
+    ///
+    /// DPLL0 runs at 120MHz unless reconfigured; DPLL1 is off at first and
+    /// reported as 0Hz.
+    #[inline]
+    pub fn dpll_freq(&self, dpll: Dpll) -> Hertz {
+        self.dplls[dpll as usize]
+    }
+}
+
+fn lock_timed_out(oscctrl: &OSCCTRL, dpll: Dpll) -> bool {
+    let flags = oscctrl.intflag.read();
+    match dpll {
+        Dpll::Dpll0 => flags.dpll0lto().bit_is_set(),
+        Dpll::Dpll1 => flags.dpll1lto().bit_is_set(),
+    }
+}
+
+fn clear_lock_timeout(oscctrl: &OSCCTRL, dpll: Dpll) {
+    // INTFLAG bits are cleared by writing 1, so other bits are unaffected
+    match dpll {
+        Dpll::Dpll0 => oscctrl.intflag.write(|w| w.dpll0lto().set_bit()),
+        Dpll::Dpll1 => oscctrl.intflag.write(|w| w.dpll1lto().set_bit()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ratios() {
+        // 120MHz from the 2MHz gclk5, as configured at startup
+        assert_eq!(
+            ratio_for(Hertz(2_000_000), Hertz(120_000_000)),
+            Some((59, 0))
+        );
+        // 119.808MHz from XOSC32K
+        let (ldr, ldrfrac) = ratio_for(OSC32K_FREQ, Hertz(119_808_000)).unwrap();
+        assert_eq!((ldr, ldrfrac), (3655, 8));
+        assert_eq!(output_freq(OSC32K_FREQ, ldr, ldrfrac), Hertz(119_808_000));
+        // 100MHz from a 12MHz crystal divided by 6
+        assert_eq!(
+            ratio_for(Hertz(2_000_000), Hertz(100_000_000)),
+            Some((49, 0))
+        );
+        assert_eq!(ratio_for(Hertz(2_000_000), Hertz(1_000_000)), None);
+        assert_eq!(ratio_for(Hertz(0), Hertz(100_000_000)), None);
+    }
+
+    #[test]
+    fn configs() {
+        let xosc = Reference::Xosc0 {
+            freq: Hertz(12_000_000),
+            div: 2,
+        };
+        assert_eq!(xosc.freq(), Hertz(2_000_000));
+        let config = DpllConfig::new(xosc, Hertz(100_000_000)).unwrap();
+        assert_eq!(config.get_ratio(), (49, 0));
+        assert_eq!(config.freq(), Hertz(100_000_000));
+        assert_eq!(
+            DpllConfig::new(Reference::Xosc32k, Hertz(48_000_000)).err(),
+            Some(Error::Frequency)
+        );
+        assert_eq!(
+            DpllConfig::with_ratio(Reference::Xosc32k, 8192, 0).err(),
+            Some(Error::Ratio)
+        );
+    }
+}
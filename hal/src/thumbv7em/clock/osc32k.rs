@@ -0,0 +1,108 @@
+//! 32kHz oscillator trimming
+//!
+//! The OSCULP32K is calibrated at the factory; its calibration is loaded from
+//! the NVM at reset. Read it with [`osculp32k_calibration`] before trimming,
+//! to store it or restore it later. [`set_osculp32k_calibration`] trims the
+//! oscillator at runtime, for example to minimize the drift of the RTC
+//! measured during production test.
+//!
+//! [`configure_xosc32k`] sets the start-up time and gain of the XOSC32K,
+//! matching the crystal. The start-up time applies the next time the
+//! oscillator starts.
+//!
+//! Both registers can be locked until the next reset with [`lock_osculp32k`]
+//! and [`lock_xosc32k`].
+
+use crate::pac::OSC32KCTRL;
+
+/// Highest calibration value of the OSCULP32K
+pub const OSCULP32K_CALIB_MAX: u8 = 0x3f;
+
+/// Errors of the 32kHz oscillator configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The register is locked until the next reset
+    Locked,
+    /// The calibration value is above [`OSCULP32K_CALIB_MAX`]
+    OutOfRange,
+}
+
+/// Start-up time of the XOSC32K, in cycles of the OSCULP32K
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Xosc32kStartup {
+    /// 62.6ms
+    Cycle2048 = 0,
+    /// 125ms
+    Cycle4096 = 1,
+    /// 500ms
+    Cycle16384 = 2,
+    /// 1s
+    Cycle32768 = 3,
+    /// 2s
+    Cycle65536 = 4,
+    /// 4s
+    Cycle131072 = 5,
+    /// 8s
+    Cycle262144 = 6,
+}
+
+/// Gain of the XOSC32K
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Xosc32kGain {
+    /// Standard mode, used at startup
+    Standard = 1,
+    /// High-speed mode, for crystals needing a higher drive
+    HighSpeed = 2,
+}
+
+/// Current calibration of the OSCULP32K
+#[inline]
+pub fn osculp32k_calibration(osc32kctrl: &OSC32KCTRL) -> u8 {
+    osc32kctrl.osculp32k.read().calib().bits()
+}
+
+/// Trim the OSCULP32K; a higher value raises its frequency
+pub fn set_osculp32k_calibration(osc32kctrl: &mut OSC32KCTRL, calib: u8) -> Result<(), Error> {
+    if calib > OSCULP32K_CALIB_MAX {
+        return Err(Error::OutOfRange);
+    }
+    if osc32kctrl.osculp32k.read().wrtlock().bit_is_set() {
+        return Err(Error::Locked);
+    }
+    // SAFETY: The value is checked above
+    osc32kctrl
+        .osculp32k
+        .modify(|_, w| unsafe { w.calib().bits(calib) });
+    Ok(())
+}
+
+/// Lock the OSCULP32K configuration until the next reset
+#[inline]
+pub fn lock_osculp32k(osc32kctrl: &mut OSC32KCTRL) {
+    osc32kctrl.osculp32k.modify(|_, w| w.wrtlock().set_bit());
+}
+
+/// Set the start-up time and gain of the XOSC32K
+pub fn configure_xosc32k(
+    osc32kctrl: &mut OSC32KCTRL,
+    startup: Xosc32kStartup,
+    gain: Xosc32kGain,
+) -> Result<(), Error> {
+    if osc32kctrl.xosc32k.read().wrtlock().bit_is_set() {
+        return Err(Error::Locked);
+    }
+    // SAFETY: The values come from the enums above
+    osc32kctrl.xosc32k.modify(|_, w| unsafe {
+        w.startup().bits(startup as u8);
+        w.cgm().bits(gain as u8)
+    });
+    Ok(())
+}
+
+/// Lock the XOSC32K configuration until the next reset
+#[inline]
+pub fn lock_xosc32k(osc32kctrl: &mut OSC32KCTRL) {
+    osc32kctrl.xosc32k.modify(|_, w| w.wrtlock().set_bit());
+}
@@ -0,0 +1,96 @@
+//! Interrupt handling for `async` external interrupts
+//!
+//! The external interrupts implement the [`embedded_hal_async`]
+//! [`Wait`](embedded_hal_async::digital::Wait) trait, so portable `async`
+//! drivers can wait on an interrupt pin. Any pin is turned into its external
+//! interrupt with the [`EicPin`](super::pin::EicPin) methods, which pick the
+//! EXTINT line of the pin.
+//!
+//! The `wait_for` method and the `Wait` methods of the external interrupts
+//! enable the interrupt of their line and yield until it fires. Each line
+//! has its own interrupt (`EIC_EXTINT_0` to `EIC_EXTINT_15`), and the
+//! application must call [`on_interrupt`] from the handler of every line
+//! awaited. It disables every enabled interrupt that has fired and wakes the
+//! tasks waiting on them.
+//!
+//! ```
+//! use atsamd_hal::eic::async_api;
+//! use atsamd_hal::pac::interrupt;
+//!
+//! #[interrupt]
+//! fn EIC_EXTINT_4() {
+//!     async_api::on_interrupt();
+//! }
+//! ```
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use crate::async_hal::InterruptWaker;
+use crate::pac;
+
+use super::pin::Sense;
+
+/// Number of external interrupt lines
+const NUM_LINES: usize = 16;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NEW_WAKER: InterruptWaker = InterruptWaker::new();
+
+static WAKERS: [InterruptWaker; NUM_LINES] = [NEW_WAKER; NUM_LINES];
+
+/// EIC interrupt handler for the `async` external interrupts
+///
+/// Must be called from the interrupt handler of every line awaited. See the
+/// [module-level documentation](self) for more details.
+#[inline]
+pub fn on_interrupt() {
+    // SAFETY: Only interrupts that already fired are disabled, which the
+    // waiting futures expect
+    let eic = unsafe { &*pac::EIC::ptr() };
+    let fired = eic.intflag.read().bits() & eic.intenset.read().bits();
+    if fired == 0 {
+        return;
+    }
+    eic.intenclr.write(|w| unsafe { w.bits(fired) });
+
+    for (num, waker) in WAKERS.iter().enumerate() {
+        if fired & (1 << num) != 0 {
+            waker.wake();
+        }
+    }
+}
+
+/// Wait until external interrupt `num` detects `sense`
+pub(super) async fn wait(num: usize, sense: Sense) {
+    super::set_sense(num, sense);
+    // SAFETY: Only the flag of `num` is cleared
+    let eic = unsafe { &*pac::EIC::ptr() };
+    eic.intflag.write(|w| unsafe { w.bits(1 << num) });
+    ExtIntFuture { num }.await
+}
+
+/// Future resolving once the flag of external interrupt `num` is set
+struct ExtIntFuture {
+    num: usize,
+}
+
+impl Future for ExtIntFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mask = 1 << self.num;
+        // SAFETY: Only the flag and interrupt of `num` are accessed
+        let eic = unsafe { &*pac::EIC::ptr() };
+        WAKERS[self.num].register(cx.waker());
+        eic.intenset.write(|w| unsafe { w.bits(mask) });
+        if eic.intflag.read().bits() & mask == 0 {
+            Poll::Pending
+        } else {
+            eic.intenclr.write(|w| unsafe { w.bits(mask) });
+            eic.intflag.write(|w| unsafe { w.bits(mask) });
+            Poll::Ready(())
+        }
+    }
+}
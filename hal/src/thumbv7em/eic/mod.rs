@@ -1,8 +1,12 @@
+use crate::bus_clock::BusClock;
 use crate::clock::EicClock;
 use crate::pac;
 
 pub mod pin;
 
+#[cfg(feature = "async")]
+pub mod async_api;
+
 /// An External Interrupt Controller which is being configured.
 pub struct ConfigurableEIC {
     eic: pac::EIC,
@@ -42,7 +46,7 @@ impl ConfigurableEIC {
 /// ultra-low-power 32kHz clock source. finalize() must be called
 /// before the EIC is ready for use.
 pub fn init_with_ulp32k(mclk: &mut pac::MCLK, _clock: EicClock, eic: pac::EIC) -> ConfigurableEIC {
-    mclk.apbamask.modify(|_, w| w.eic_().set_bit());
+    pac::EIC::enable_bus_clock(mclk);
 
     eic.ctrla.modify(|_, w| w.swrst().set_bit());
     while eic.syncbusy.read().swrst().bit_is_set() {
@@ -70,3 +74,38 @@ impl From<ConfigurableEIC> for EIC {
         Self { _eic: eic.eic }
     }
 }
+
+/// Replace the sense configuration of external interrupt `num`
+///
+/// The configuration is enable-protected, so the EIC is briefly disabled if
+/// needed.
+fn set_sense(num: usize, sense: pin::Sense) {
+    let shift = 4 * (num & 0b111);
+    cortex_m::interrupt::free(|_| {
+        // SAFETY: Only the sense field of `num` is modified, in a critical
+        // section, and the EIC is enabled again afterwards
+        let eic = unsafe { &*pac::EIC::ptr() };
+        let enabled = eic.ctrla.read().enable().bit_is_set();
+        if enabled {
+            eic.ctrla.modify(|_, w| w.enable().clear_bit());
+            while eic.syncbusy.read().enable().bit_is_set() {}
+        }
+        eic.config[num >> 3].modify(|r, w| unsafe {
+            w.bits(r.bits() & !(0b111 << shift) | (sense as u32) << shift)
+        });
+        if enabled {
+            eic.ctrla.modify(|_, w| w.enable().set_bit());
+            while eic.syncbusy.read().enable().bit_is_set() {}
+        }
+    });
+}
+
+/// Block until external interrupt `num` detects `sense`
+fn wait(num: usize, sense: pin::Sense) {
+    set_sense(num, sense);
+    // SAFETY: Only the flag of `num` is cleared
+    let eic = unsafe { &*pac::EIC::ptr() };
+    eic.intflag.write(|w| unsafe { w.bits(1 << num) });
+    while eic.intflag.read().bits() & (1 << num) == 0 {}
+    eic.intflag.write(|w| unsafe { w.bits(1 << num) });
+}
@@ -0,0 +1,157 @@
+//! # FREQM - Frequency Meter
+//!
+//! The FREQM counts the edges of a measured clock during a number of periods
+//! of a reference clock. Both clocks are generic clocks: the measured clock
+//! is the `FREQM_MSR` generic clock which, like any generic clock, can be fed
+//! by the DFLL, a crystal oscillator or another GCLK, and the reference clock
+//! is the `FREQM_REF` generic clock.
+//!
+//! The measured frequency is `VALUE * f_ref / reference_periods`, so the
+//! measurement is only as accurate as the reference clock. Longer
+//! measurements, using more reference periods, have a better resolution, as
+//! long as the counter doesn't overflow. A measurement never completes if the
+//! reference clock is stopped, so use [`Freqm::start`] and poll
+//! [`Freqm::result`] with a timeout when the reference clock may be faulty.
+//!
+//! ```no_run
+//! use atsamd_hal::freqm::Freqm;
+//!
+//! let mut clocks = GenericClockController::with_external_32kosc(/* ... */);
+//! // Measure the 120 MHz GCLK0 against the 32 kHz crystal on GCLK1
+//! let gclk0 = clocks.gclk0();
+//! let gclk1 = clocks.gclk1();
+//! let msr_clock = clocks.freq_m_msr(&gclk0).unwrap();
+//! let ref_clock = clocks.freq_m_ref(&gclk1).unwrap();
+//!
+//! let mut freqm = Freqm::new(peripherals.FREQM, &mut peripherals.MCLK, &msr_clock, &ref_clock);
+//! let frequency = freqm.measure(255).unwrap();
+//! ```
+
+use crate::bus_clock::BusClock;
+use crate::clock::{FreqmMsrClock, FreqmRefClock};
+use crate::pac::{FREQM, MCLK};
+use crate::time::Hertz;
+
+/// FREQM errors
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The measured clock is too fast for the number of reference periods,
+    /// and the counter overflowed
+    Overflow,
+}
+
+/// Compute the measured frequency from the counter value
+#[inline]
+fn frequency(value: u32, reference_periods: u8, reference: Hertz) -> Hertz {
+    let frequency = u64::from(value) * u64::from(reference.0) / u64::from(reference_periods);
+    Hertz(frequency as u32)
+}
+
+/// Frequency Meter
+pub struct Freqm {
+    freqm: FREQM,
+    reference: Hertz,
+    reference_periods: u8,
+}
+
+impl Freqm {
+    /// Enable the FREQM clock and the FREQM
+    ///
+    /// The clock tokens prove that both generic clocks are configured.
+    pub fn new(
+        freqm: FREQM,
+        mclk: &mut MCLK,
+        _msr_clock: &FreqmMsrClock,
+        ref_clock: &FreqmRefClock,
+    ) -> Self {
+        FREQM::enable_bus_clock(mclk);
+        freqm.ctrla.write(|w| w.swrst().set_bit());
+        while freqm.syncbusy.read().swrst().bit_is_set() {}
+        freqm.ctrla.write(|w| w.enable().set_bit());
+        while freqm.syncbusy.read().enable().bit_is_set() {}
+
+        Self {
+            freqm,
+            reference: ref_clock.freq(),
+            reference_periods: 1,
+        }
+    }
+
+    /// Disable the FREQM and its clock and return the register block
+    pub fn free(self, mclk: &mut MCLK) -> FREQM {
+        self.freqm.ctrla.write(|w| w.enable().clear_bit());
+        while self.freqm.syncbusy.read().enable().bit_is_set() {}
+        FREQM::disable_bus_clock(mclk);
+        self.freqm
+    }
+
+    /// Start a measurement lasting `reference_periods` periods of the
+    /// reference clock
+    ///
+    /// The result is available with [`Freqm::result`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reference_periods` is zero.
+    pub fn start(&mut self, reference_periods: u8) {
+        assert!(reference_periods > 0);
+        self.reference_periods = reference_periods;
+        // SAFETY: Any non-zero count is valid
+        self.freqm
+            .cfga
+            .write(|w| unsafe { w.refnum().bits(reference_periods) });
+        self.freqm.status.write(|w| w.ovf().set_bit());
+        self.freqm.intflag.write(|w| w.done().set_bit());
+        self.freqm.ctrlb.write(|w| w.start().set_bit());
+    }
+
+    /// Return the measured frequency once the measurement started by
+    /// [`Freqm::start`] is complete
+    pub fn result(&mut self) -> nb::Result<Hertz, Error> {
+        if self.freqm.intflag.read().done().bit_is_clear() {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.freqm.intflag.write(|w| w.done().set_bit());
+        if self.freqm.status.read().ovf().bit_is_set() {
+            return Err(nb::Error::Other(Error::Overflow));
+        }
+        let value = self.freqm.value.read().value().bits();
+        Ok(frequency(value, self.reference_periods, self.reference))
+    }
+
+    /// Measure the frequency during `reference_periods` periods of the
+    /// reference clock, blocking until the measurement is complete
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reference_periods` is zero.
+    pub fn measure(&mut self, reference_periods: u8) -> Result<Hertz, Error> {
+        self.start(reference_periods);
+        nb::block!(self.result())
+    }
+
+    /// Enable the measurement done interrupt
+    #[inline]
+    pub fn enable_interrupt(&mut self) {
+        self.freqm.intenset.write(|w| w.done().set_bit());
+    }
+
+    /// Disable the measurement done interrupt
+    #[inline]
+    pub fn disable_interrupt(&mut self) {
+        self.freqm.intenclr.write(|w| w.done().set_bit());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frequency_scales_with_reference() {
+        // 48 MHz measured against 32.768 kHz during 255 periods
+        assert_eq!(frequency(373_535, 255, Hertz(32_768)).0, 47_999_979);
+        // 32.768 kHz measured against 1 kHz during 255 periods
+        assert_eq!(frequency(8_355, 255, Hertz(1_000)).0, 32_764);
+    }
+}
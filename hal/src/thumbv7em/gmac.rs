@@ -0,0 +1,582 @@
+//! # GMAC - Ethernet MAC
+//!
+//! The SAME53 and SAME54 chips embed a 10/100 Mbps Ethernet MAC, connected
+//! to an external PHY through an MII or RMII interface. This module drives
+//! the MAC through descriptor rings and implements the
+//! [`smoltcp::phy::Device`] trait, so [`Gmac`] can be used as a network
+//! interface by the `smoltcp` TCP/IP stack.
+//!
+//! The descriptors and frame buffers are accessed by the GMAC DMA and live
+//! in a [`DescriptorRings`] struct provided by the user:
+//!
+//! * [`RX_DESCRIPTORS`] receive buffers
+//! * [`TX_DESCRIPTORS`] transmit buffers
+//!
+//! Every buffer is [`BUFFER_SIZE`] bytes long and holds a complete Ethernet
+//! frame. The frame check sequence is generated and stripped by the MAC.
+//!
+//! The external PHY is configured through the management interface, see
+//! [`Gmac::read_phy`] and [`Gmac::write_phy`]. Once the PHY reports a link,
+//! report the negotiated speed and duplex mode to the MAC with
+//! [`Gmac::set_link`].
+//!
+//! The driver does not configure the GMAC pins, which must be set to
+//! alternate function L beforehand. On the SAME54 Xplained Pro, the RMII
+//! interface uses PA12-PA15, PA17-PA19 and PC20, and the management
+//! interface uses PC11 and PC12.
+//!
+//! This module is only available when the `gmac` feature is enabled.
+//!
+//! ```no_run
+//! use atsamd_hal::gmac::{Config, DescriptorRings, Duplex, Gmac, Speed};
+//!
+//! static mut RINGS: DescriptorRings = DescriptorRings::new();
+//!
+//! let mut gmac = Gmac::new(
+//!     peripherals.GMAC,
+//!     unsafe { &mut RINGS },
+//!     &mut peripherals.MCLK,
+//!     clocks.gclk0(),
+//!     Config::new([0x02, 0x00, 0x00, 0x00, 0x00, 0x01]),
+//! );
+//!
+//! // Wait for the PHY to complete auto-negotiation
+//! while gmac.read_phy(0, 1) & (1 << 2) == 0 {}
+//! gmac.set_link(Speed::Mbps100, Duplex::Full);
+//! ```
+
+use core::sync::atomic::{self, Ordering};
+
+use crate::{
+    bus_clock::BusClock,
+    pac::{GMAC, MCLK},
+    time::Hertz,
+};
+
+use smoltcp::{
+    phy::{self, DeviceCapabilities, Medium},
+    time::Instant,
+};
+
+pub use smoltcp;
+
+/// Number of receive descriptors and buffers
+pub const RX_DESCRIPTORS: usize = 8;
+
+/// Number of transmit descriptors and buffers
+pub const TX_DESCRIPTORS: usize = 4;
+
+/// Size of every frame buffer, in bytes
+pub const BUFFER_SIZE: usize = 1536;
+
+/// Maximum transmission unit, the size of an Ethernet frame without its
+/// frame check sequence
+pub const MTU: usize = 1514;
+
+/// Maximum frequency of the management data clock
+const MDC_MAX_FREQ: u32 = 2_500_000;
+
+/// Main clock dividers available for the management data clock, indexed by
+/// the value of NCFGR.CLK
+const MDC_DIVIDERS: [u32; 6] = [8, 16, 32, 48, 64, 96];
+
+// Receive descriptor, address word
+const RX_OWNERSHIP: u32 = 1 << 0;
+const RX_WRAP: u32 = 1 << 1;
+const RX_ADDRESS_MASK: u32 = !0x3;
+// Receive descriptor, status word
+const RX_LENGTH_MASK: u32 = 0x1FFF;
+const RX_START_OF_FRAME: u32 = 1 << 14;
+const RX_END_OF_FRAME: u32 = 1 << 15;
+
+// Transmit descriptor, status word
+const TX_LENGTH_MASK: u32 = 0x3FFF;
+const TX_LAST_BUFFER: u32 = 1 << 15;
+const TX_WRAP: u32 = 1 << 30;
+const TX_USED: u32 = 1 << 31;
+
+/// GMAC buffer descriptor
+#[repr(C, align(8))]
+#[derive(Clone, Copy)]
+struct Descriptor {
+    address: u32,
+    status: u32,
+}
+
+impl Descriptor {
+    const EMPTY: Self = Self {
+        address: 0,
+        status: 0,
+    };
+
+    #[inline]
+    fn address(&self) -> u32 {
+        // SAFETY: The reference is valid, the GMAC may write it concurrently
+        unsafe { core::ptr::read_volatile(&self.address) }
+    }
+
+    #[inline]
+    fn set_address(&mut self, address: u32) {
+        // SAFETY: The reference is valid, the GMAC may read it concurrently
+        unsafe { core::ptr::write_volatile(&mut self.address, address) }
+    }
+
+    #[inline]
+    fn status(&self) -> u32 {
+        // SAFETY: The reference is valid, the GMAC may write it concurrently
+        unsafe { core::ptr::read_volatile(&self.status) }
+    }
+
+    #[inline]
+    fn set_status(&mut self, status: u32) {
+        // SAFETY: The reference is valid, the GMAC may read it concurrently
+        unsafe { core::ptr::write_volatile(&mut self.status, status) }
+    }
+}
+
+/// Descriptor rings and frame buffers used by the GMAC DMA
+///
+/// The rings are usually placed in a `static`, as they must outlive the
+/// [`Gmac`].
+#[repr(C, align(8))]
+pub struct DescriptorRings {
+    rx_descriptors: [Descriptor; RX_DESCRIPTORS],
+    tx_descriptors: [Descriptor; TX_DESCRIPTORS],
+    rx_buffers: [[u8; BUFFER_SIZE]; RX_DESCRIPTORS],
+    tx_buffers: [[u8; BUFFER_SIZE]; TX_DESCRIPTORS],
+}
+
+impl DescriptorRings {
+    /// Create empty descriptor rings
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            rx_descriptors: [Descriptor::EMPTY; RX_DESCRIPTORS],
+            tx_descriptors: [Descriptor::EMPTY; TX_DESCRIPTORS],
+            rx_buffers: [[0; BUFFER_SIZE]; RX_DESCRIPTORS],
+            tx_buffers: [[0; BUFFER_SIZE]; TX_DESCRIPTORS],
+        }
+    }
+}
+
+impl Default for DescriptorRings {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Interface between the MAC and the PHY
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interface {
+    /// Media independent interface
+    Mii,
+    /// Reduced media independent interface
+    Rmii,
+}
+
+/// Link speed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Speed {
+    /// 10 Mbps
+    Mbps10,
+    /// 100 Mbps
+    Mbps100,
+}
+
+/// Link duplex mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Duplex {
+    /// Half duplex
+    Half,
+    /// Full duplex
+    Full,
+}
+
+/// GMAC configuration
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// MAC address of the interface
+    pub mac_address: [u8; 6],
+    /// Interface to the external PHY
+    pub interface: Interface,
+    /// Accept every frame, regardless of its destination address
+    pub promiscuous: bool,
+}
+
+impl Config {
+    /// Create a configuration for an RMII PHY, that only accepts frames sent
+    /// to `mac_address` or broadcast
+    #[inline]
+    pub fn new(mac_address: [u8; 6]) -> Self {
+        Self {
+            mac_address,
+            interface: Interface::Rmii,
+            promiscuous: false,
+        }
+    }
+}
+
+/// Compute the NCFGR.CLK value keeping the management data clock below
+/// 2.5 MHz
+fn mdc_divider(mck: u32) -> u8 {
+    MDC_DIVIDERS
+        .iter()
+        .position(|&divider| mck <= MDC_MAX_FREQ * divider)
+        .unwrap_or(MDC_DIVIDERS.len() - 1) as u8
+}
+
+/// Ethernet MAC driver
+pub struct Gmac {
+    gmac: GMAC,
+    rings: &'static mut DescriptorRings,
+    rx_index: usize,
+    tx_index: usize,
+}
+
+impl Gmac {
+    /// Reset the MAC, set up the descriptor rings and enable reception and
+    /// transmission
+    ///
+    /// `mck` is the frequency of the main clock, used to derive the
+    /// management data clock. The link defaults to 100 Mbps full duplex, see
+    /// [`Gmac::set_link`].
+    pub fn new(
+        gmac: GMAC,
+        rings: &'static mut DescriptorRings,
+        mclk: &mut MCLK,
+        mck: impl Into<Hertz>,
+        config: Config,
+    ) -> Self {
+        GMAC::enable_bus_clock(mclk);
+
+        gmac.ncr.write(|w| w.clrstat().set_bit());
+        gmac.tsr.write(|w| {
+            w.ubr().set_bit();
+            w.col().set_bit();
+            w.rle().set_bit();
+            w.tfc().set_bit();
+            w.txcomp().set_bit();
+            w.hresp().set_bit()
+        });
+        gmac.rsr.write(|w| {
+            w.bna().set_bit();
+            w.rec().set_bit();
+            w.rxovr().set_bit();
+            w.hno().set_bit()
+        });
+        // SAFETY: Writing ones disables every interrupt
+        gmac.idr.write(|w| unsafe { w.bits(u32::MAX) });
+        // Reading the status register clears it
+        gmac.isr.read();
+
+        let clk = mdc_divider(mck.into().0);
+        gmac.ncfgr.write(|w| {
+            // SAFETY: `mdc_divider` only returns valid dividers
+            unsafe { w.clk().bits(clk) };
+            w.spd().set_bit();
+            w.fd().set_bit();
+            w.rfcs().set_bit();
+            w.caf().bit(config.promiscuous)
+        });
+        gmac.ur
+            .write(|w| w.mii().bit(config.interface == Interface::Mii));
+        gmac.dcfgr.write(|w| unsafe {
+            // INCR4 bursts, full size receive and transmit packet buffers
+            w.fbldo().bits(4);
+            w.rxbms().bits(3);
+            w.txpbms().set_bit();
+            // The receive buffer size is expressed in multiples of 64 bytes
+            w.drbs().bits((BUFFER_SIZE / 64) as u8)
+        });
+
+        let mut gmac = Self {
+            gmac,
+            rings,
+            rx_index: 0,
+            tx_index: 0,
+        };
+        gmac.init_rings();
+        gmac.set_mac_address(config.mac_address);
+
+        gmac.gmac.ncr.write(|w| {
+            w.mpe().set_bit();
+            w.rxen().set_bit();
+            w.txen().set_bit()
+        });
+        gmac
+    }
+
+    /// Disable the MAC and return the peripheral and the descriptor rings
+    pub fn free(self, mclk: &mut MCLK) -> (GMAC, &'static mut DescriptorRings) {
+        self.gmac.ncr.write(|w| w.thalt().set_bit());
+        GMAC::disable_bus_clock(mclk);
+        (self.gmac, self.rings)
+    }
+
+    /// Hand every receive buffer to the GMAC and mark every transmit buffer
+    /// as available
+    fn init_rings(&mut self) {
+        let rings = &mut *self.rings;
+        for (i, (descriptor, buffer)) in rings
+            .rx_descriptors
+            .iter_mut()
+            .zip(rings.rx_buffers.iter())
+            .enumerate()
+        {
+            let mut address = buffer.as_ptr() as u32 & RX_ADDRESS_MASK;
+            if i == RX_DESCRIPTORS - 1 {
+                address |= RX_WRAP;
+            }
+            descriptor.set_status(0);
+            descriptor.set_address(address);
+        }
+        for (i, (descriptor, buffer)) in rings
+            .tx_descriptors
+            .iter_mut()
+            .zip(rings.tx_buffers.iter())
+            .enumerate()
+        {
+            let mut status = TX_USED;
+            if i == TX_DESCRIPTORS - 1 {
+                status |= TX_WRAP;
+            }
+            descriptor.set_address(buffer.as_ptr() as u32);
+            descriptor.set_status(status);
+        }
+        self.rx_index = 0;
+        self.tx_index = 0;
+
+        atomic::fence(Ordering::Release);
+        // SAFETY: The rings are 8-byte aligned and outlive the driver
+        self.gmac
+            .rbqb
+            .write(|w| unsafe { w.bits(rings.rx_descriptors.as_ptr() as u32) });
+        self.gmac
+            .tbqb
+            .write(|w| unsafe { w.bits(rings.tx_descriptors.as_ptr() as u32) });
+    }
+
+    /// Set the MAC address used to filter received frames
+    pub fn set_mac_address(&mut self, address: [u8; 6]) {
+        let bottom = u32::from_le_bytes([address[0], address[1], address[2], address[3]]);
+        let top = u16::from_le_bytes([address[4], address[5]]);
+        // Writing SAB disables the address until SAT is written
+        // SAFETY: Any value is a valid address
+        self.gmac.sa[0].sab.write(|w| unsafe { w.bits(bottom) });
+        self.gmac.sa[0].sat.write(|w| unsafe { w.bits(top as u32) });
+    }
+
+    /// Report the link speed and duplex mode negotiated by the PHY
+    pub fn set_link(&mut self, speed: Speed, duplex: Duplex) {
+        self.gmac.ncfgr.modify(|_, w| {
+            w.spd().bit(speed == Speed::Mbps100);
+            w.fd().bit(duplex == Duplex::Full)
+        });
+    }
+
+    #[inline]
+    fn wait_phy_idle(&self) {
+        while self.gmac.nsr.read().idle().bit_is_clear() {}
+    }
+
+    /// Read a PHY register through the management interface
+    ///
+    /// Uses clause 22 frames, `phy` and `register` are 5-bit addresses.
+    pub fn read_phy(&mut self, phy: u8, register: u8) -> u16 {
+        self.wait_phy_idle();
+        // SAFETY: The addresses are masked to their field widths
+        self.gmac.man.write(|w| unsafe {
+            w.cltto().set_bit();
+            w.op().bits(0b10);
+            w.phya().bits(phy & 0x1F);
+            w.rega().bits(register & 0x1F);
+            w.wtn().bits(0b10)
+        });
+        self.wait_phy_idle();
+        self.gmac.man.read().data().bits()
+    }
+
+    /// Write a PHY register through the management interface
+    ///
+    /// Uses clause 22 frames, `phy` and `register` are 5-bit addresses.
+    pub fn write_phy(&mut self, phy: u8, register: u8, value: u16) {
+        self.wait_phy_idle();
+        // SAFETY: The addresses are masked to their field widths
+        self.gmac.man.write(|w| unsafe {
+            w.cltto().set_bit();
+            w.op().bits(0b01);
+            w.phya().bits(phy & 0x1F);
+            w.rega().bits(register & 0x1F);
+            w.wtn().bits(0b10);
+            w.data().bits(value)
+        });
+        self.wait_phy_idle();
+    }
+
+    /// Split the driver into the receive and transmit ring state
+    #[inline]
+    fn split(&mut self) -> (Receiver<'_>, Transmitter<'_>) {
+        let rings = &mut *self.rings;
+        (
+            Receiver {
+                descriptors: &mut rings.rx_descriptors,
+                buffers: &mut rings.rx_buffers,
+                index: &mut self.rx_index,
+            },
+            Transmitter {
+                gmac: &self.gmac,
+                descriptors: &mut rings.tx_descriptors,
+                buffers: &mut rings.tx_buffers,
+                index: &mut self.tx_index,
+            },
+        )
+    }
+}
+
+/// Receive half of the descriptor rings
+struct Receiver<'a> {
+    descriptors: &'a mut [Descriptor; RX_DESCRIPTORS],
+    buffers: &'a mut [[u8; BUFFER_SIZE]; RX_DESCRIPTORS],
+    index: &'a mut usize,
+}
+
+impl Receiver<'_> {
+    /// Return the length of the next complete frame, dropping any frame
+    /// that does not fit in a single buffer
+    fn next_frame(&mut self) -> Option<usize> {
+        loop {
+            let descriptor = &self.descriptors[*self.index];
+            if descriptor.address() & RX_OWNERSHIP == 0 {
+                return None;
+            }
+            atomic::fence(Ordering::Acquire);
+
+            let status = descriptor.status();
+            if status & RX_START_OF_FRAME != 0 && status & RX_END_OF_FRAME != 0 {
+                return Some((status & RX_LENGTH_MASK) as usize);
+            }
+            self.release();
+        }
+    }
+
+    /// Return the current buffer to the GMAC and move to the next one
+    fn release(&mut self) {
+        atomic::fence(Ordering::Release);
+        let descriptor = &mut self.descriptors[*self.index];
+        descriptor.set_address(descriptor.address() & !RX_OWNERSHIP);
+        *self.index = (*self.index + 1) % RX_DESCRIPTORS;
+    }
+}
+
+/// Transmit half of the descriptor rings
+struct Transmitter<'a> {
+    gmac: &'a GMAC,
+    descriptors: &'a mut [Descriptor; TX_DESCRIPTORS],
+    buffers: &'a mut [[u8; BUFFER_SIZE]; TX_DESCRIPTORS],
+    index: &'a mut usize,
+}
+
+impl Transmitter<'_> {
+    /// Check whether the GMAC is done with the current buffer
+    #[inline]
+    fn is_available(&self) -> bool {
+        self.descriptors[*self.index].status() & TX_USED != 0
+    }
+}
+
+/// Token used by `smoltcp` to consume a received frame
+pub struct RxToken<'a> {
+    receiver: Receiver<'a>,
+    length: usize,
+}
+
+impl phy::RxToken for RxToken<'_> {
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let index = *self.receiver.index;
+        let result = f(&mut self.receiver.buffers[index][..self.length]);
+        self.receiver.release();
+        result
+    }
+}
+
+/// Token used by `smoltcp` to transmit a frame
+pub struct TxToken<'a> {
+    transmitter: Transmitter<'a>,
+}
+
+impl phy::TxToken for TxToken<'_> {
+    /// # Panics
+    ///
+    /// Panics if `len` is larger than [`MTU`].
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        assert!(len <= MTU);
+        let tx = self.transmitter;
+        let index = *tx.index;
+        let result = f(&mut tx.buffers[index][..len]);
+
+        let mut status = (len as u32 & TX_LENGTH_MASK) | TX_LAST_BUFFER;
+        if index == TX_DESCRIPTORS - 1 {
+            status |= TX_WRAP;
+        }
+        // Make sure the frame is in memory before the GMAC owns the buffer
+        atomic::fence(Ordering::Release);
+        tx.descriptors[index].set_status(status);
+        atomic::fence(Ordering::SeqCst);
+        tx.gmac.ncr.modify(|_, w| w.tstart().set_bit());
+
+        *tx.index = (index + 1) % TX_DESCRIPTORS;
+        result
+    }
+}
+
+impl phy::Device for Gmac {
+    type RxToken<'a> = RxToken<'a>;
+    type TxToken<'a> = TxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(RxToken<'_>, TxToken<'_>)> {
+        let (mut receiver, transmitter) = self.split();
+        if !transmitter.is_available() {
+            return None;
+        }
+        let length = receiver.next_frame()?;
+        Some((RxToken { receiver, length }, TxToken { transmitter }))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<TxToken<'_>> {
+        let (_, transmitter) = self.split();
+        if transmitter.is_available() {
+            Some(TxToken { transmitter })
+        } else {
+            None
+        }
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut capabilities = DeviceCapabilities::default();
+        capabilities.medium = Medium::Ethernet;
+        capabilities.max_transmission_unit = MTU;
+        capabilities.max_burst_size = Some(TX_DESCRIPTORS);
+        capabilities
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mdc_divider_limits_the_clock() {
+        assert_eq!(mdc_divider(20_000_000), 0);
+        assert_eq!(mdc_divider(40_000_000), 1);
+        assert_eq!(mdc_divider(48_000_000), 2);
+        assert_eq!(mdc_divider(120_000_000), 3);
+        assert_eq!(mdc_divider(200_000_000), 5);
+    }
+}
@@ -153,7 +153,7 @@
 //!
 //! // Enable ICM apb clock
 //! // Clock v1
-//! //mclk.apbcmask.modify(|_, w| w.icm_().set_bit());
+//! //ICM::enable_bus_clock(&mclk);
 //! // Clock v2
 //! //tokens.apbs.icm.enable();
 //!
@@ -394,6 +394,11 @@ use core::marker::PhantomData;
 /// Reexport the User SHA Algorithm
 pub use crate::icm::cfg::UALGO_A as icm_algorithm;
 
+#[cfg(feature = "digest")]
+mod sha;
+#[cfg(feature = "digest")]
+pub use sha::*;
+
 // Convenient bitflags representing select parts of
 // the status interrupt register `ICM->ISR`
 
@@ -892,7 +897,7 @@ impl Icm {
     /// `AHB` bus is on by default at reset
     ///
     /// Clock::v1
-    /// `mclk.apbcmask.modify(|_, w| w.icm_().set_bit());`
+    /// `ICM::enable_bus_clock(&mclk);`, see [`BusClock`](crate::bus_clock::BusClock)
     ///
     /// Clock::v2
     /// `tokens.apbs.icm.enable();`
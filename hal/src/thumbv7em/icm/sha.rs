@@ -0,0 +1,293 @@
+//! # ICM SHA engine
+//!
+//! Use the ICM as a hardware SHA-1, SHA-224 and SHA-256 digest engine
+//! implementing the RustCrypto [`digest::Digest`] trait.
+//!
+//! Convert an [`Icm`] into a [`ShaEngine`] to make the hardware available to
+//! the [`Sha1`], [`Sha224`] and [`Sha256`] hashers. The hashers buffer
+//! incoming data in 64-byte blocks, let the ICM compress them, and perform
+//! the final padding in software. Updating or finalizing a hasher while no
+//! [`ShaEngine`] exists panics.
+//!
+//! Every hardware operation runs to completion within a critical section, so
+//! several hashers can be used at the same time, including from interrupt
+//! handlers.
+//!
+//! This module is only available when the `digest` feature is enabled.
+//!
+//! ```no_run
+//! use atsamd_hal::icm::{Icm, ShaEngine, Sha256, digest::Digest};
+//!
+//! let engine = ShaEngine::new(Icm::new(peripherals.ICM));
+//!
+//! let mut hasher = Sha256::new();
+//! hasher.update(b"hello, ");
+//! hasher.update(b"world");
+//! let digest = hasher.finalize();
+//! ```
+
+use core::sync::atomic::{self, AtomicBool, Ordering};
+
+use digest::{
+    block_buffer::Eager,
+    core_api::{
+        AlgorithmName, Block, BlockSizeUser, Buffer, BufferKindUser, CoreWrapper, FixedOutputCore,
+        OutputSizeUser, UpdateCore,
+    },
+    generic_array::GenericArray,
+    typenum::{U20, U28, U32, U64},
+    HashMarker, Output, Reset,
+};
+
+use super::{icm_algorithm, HashArea, Icm, RegionDesc, Regions};
+
+pub use digest;
+
+/// Size of a SHA block, in bytes
+const BLOCK_SIZE: usize = 64;
+
+/// Maximum number of blocks the ICM can process in a single region
+const MAX_BLOCKS: usize = u16::MAX as usize + 1;
+
+/// Set while a [`ShaEngine`] owns the ICM
+static ENGINE_AVAILABLE: AtomicBool = AtomicBool::new(false);
+
+/// ICM configured as a SHA digest engine
+///
+/// While this struct exists, the [`Sha1`], [`Sha224`] and [`Sha256`] hashers
+/// can use the ICM hardware.
+pub struct ShaEngine {
+    icm: Icm,
+}
+
+impl ShaEngine {
+    /// Take ownership of the ICM and make it available to the hashers
+    ///
+    /// Don't forget to enable the `APB` clock of the ICM, see [`Icm::new`].
+    #[inline]
+    pub fn new(mut icm: Icm) -> Self {
+        icm.disable();
+        icm.swrst();
+        ENGINE_AVAILABLE.store(true, Ordering::Release);
+        Self { icm }
+    }
+
+    /// Stop providing the SHA engine and return the [`Icm`]
+    #[inline]
+    pub fn free(self) -> Icm {
+        ENGINE_AVAILABLE.store(false, Ordering::Release);
+        self.icm
+    }
+}
+
+/// Compress `blocks` into `state` using the ICM hardware.
+///
+/// `state` holds the intermediate hash value in big-endian word order, as
+/// defined by FIPS 180-4.
+fn compress(algorithm: icm_algorithm, state: &mut [u32; 8], blocks: &[GenericArray<u8, U64>]) {
+    assert!(
+        ENGINE_AVAILABLE.load(Ordering::Acquire),
+        "the ICM SHA engine is not available"
+    );
+
+    let aligned = blocks.as_ptr() as usize % 4 == 0;
+    if aligned {
+        for chunk in blocks.chunks(MAX_BLOCKS) {
+            cortex_m::interrupt::free(|_| {
+                // SAFETY: The chunk is word aligned and `BLOCK_SIZE` bytes long
+                // per block
+                unsafe { compress_raw(algorithm, state, chunk.as_ptr() as *const u32, chunk.len()) }
+            });
+        }
+    } else {
+        // The ICM reads whole words, so unaligned data is copied first
+        let mut buffer = [0u32; BLOCK_SIZE / 4];
+        for block in blocks {
+            for (word, bytes) in buffer.iter_mut().zip(block.chunks_exact(4)) {
+                *word = u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            }
+            cortex_m::interrupt::free(|_| {
+                // SAFETY: The buffer is word aligned and one block long
+                unsafe { compress_raw(algorithm, state, buffer.as_ptr(), 1) }
+            });
+        }
+    }
+}
+
+/// # Safety
+///
+/// `data` must be word aligned and point to `count` blocks of readable
+/// memory. Must be called from within a critical section.
+unsafe fn compress_raw(
+    algorithm: icm_algorithm,
+    state: &mut [u32; 8],
+    data: *const u32,
+    count: usize,
+) {
+    // SAFETY: Only the hashers use the ICM while a `ShaEngine` exists
+    let icm = &*crate::pac::ICM::ptr();
+
+    let mut regions = Regions::default();
+    let mut hash = HashArea::default();
+
+    let region = &mut regions.region0;
+    region.set_region_address(data);
+    region.rcfg.set_cdwbn(false);
+    region.rcfg.set_wrap(false);
+    region.rcfg.set_eom(true);
+    // Let the ICM set the RHC flag once the region has been hashed
+    region.rcfg.set_rhien(false);
+    region.rctrl.trsize = (count - 1) as u16;
+
+    icm.ctrl.write(|w| w.swrst().set_bit());
+
+    // The ICM uses the user initial hash value instead of its defaults, which
+    // lets successive updates chain. The hash area stores the digest as a
+    // byte stream, hence the byte swapping.
+    icm.cfg
+        .write(|w| w.uihash().set_bit().ualgo().variant(algorithm));
+    for (uihval, word) in icm.uihval.iter().zip(state.iter()) {
+        uihval.write(|w| w.val().bits(word.swap_bytes()));
+    }
+
+    icm.dscr
+        .write(|w| w.dasa().bits((&regions.region0 as *const _) as u32 / 64));
+    // The ICM writes the digest to the hash area behind the compiler's back
+    let hash_ptr = &mut hash as *mut HashArea;
+    icm.hash.write(|w| w.hasa().bits(hash_ptr as u32 / 128));
+
+    // Make sure the descriptor is in memory before the ICM fetches it
+    atomic::fence(Ordering::Release); // ▲
+    icm.ctrl.write(|w| w.enable().set_bit());
+
+    while icm.isr.read().rhc().bits() & 0x1 == 0 {}
+    icm.ctrl.write(|w| w.disable().set_bit());
+    atomic::fence(Ordering::Acquire); // ▼
+
+    let written = core::ptr::read_volatile(core::ptr::addr_of!((*hash_ptr).region0));
+    for (word, hash) in state.iter_mut().zip(written.iter()) {
+        *word = hash.swap_bytes();
+    }
+}
+
+macro_rules! sha_core {
+    (
+        $Core:ident, $Hasher:ident, $OutputSize:ty, $algorithm:ident, $name:literal,
+        $iv:expr
+    ) => {
+        #[doc = concat!("Core ", $name, " hasher state, compressed by the ICM")]
+        ///
+        #[doc = concat!("Use through the [`", stringify!($Hasher), "`] type alias.")]
+        #[derive(Clone)]
+        pub struct $Core {
+            state: [u32; 8],
+            block_len: u64,
+        }
+
+        #[doc = concat!("Hardware ", $name, " hasher")]
+        pub type $Hasher = CoreWrapper<$Core>;
+
+        impl $Core {
+            const IV: [u32; 8] = $iv;
+        }
+
+        impl Default for $Core {
+            #[inline]
+            fn default() -> Self {
+                Self {
+                    state: Self::IV,
+                    block_len: 0,
+                }
+            }
+        }
+
+        impl HashMarker for $Core {}
+
+        impl BlockSizeUser for $Core {
+            type BlockSize = U64;
+        }
+
+        impl BufferKindUser for $Core {
+            type BufferKind = Eager;
+        }
+
+        impl OutputSizeUser for $Core {
+            type OutputSize = $OutputSize;
+        }
+
+        impl UpdateCore for $Core {
+            #[inline]
+            fn update_blocks(&mut self, blocks: &[Block<Self>]) {
+                self.block_len += blocks.len() as u64;
+                compress(icm_algorithm::$algorithm, &mut self.state, blocks);
+            }
+        }
+
+        impl FixedOutputCore for $Core {
+            #[inline]
+            fn finalize_fixed_core(&mut self, buffer: &mut Buffer<Self>, out: &mut Output<Self>) {
+                let bit_len = 8 * (buffer.get_pos() as u64 + self.block_len * BLOCK_SIZE as u64);
+                let mut state = self.state;
+                buffer.len64_padding_be(bit_len, |block| {
+                    compress(
+                        icm_algorithm::$algorithm,
+                        &mut state,
+                        core::slice::from_ref(block),
+                    )
+                });
+                for (chunk, word) in out.chunks_exact_mut(4).zip(state.iter()) {
+                    chunk.copy_from_slice(&word.to_be_bytes());
+                }
+            }
+        }
+
+        impl Reset for $Core {
+            #[inline]
+            fn reset(&mut self) {
+                *self = Self::default();
+            }
+        }
+
+        impl AlgorithmName for $Core {
+            fn write_alg_name(f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.write_str($name)
+            }
+        }
+    };
+}
+
+sha_core!(
+    Sha1Core,
+    Sha1,
+    U20,
+    SHA1,
+    "SHA-1",
+    [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0, 0, 0, 0]
+);
+
+// SHA-224 uses the SHA-256 compression function with a different initial
+// value. Compressing with SHA-256 keeps all eight state words in the hash
+// area, which successive updates need.
+sha_core!(
+    Sha224Core,
+    Sha224,
+    U28,
+    SHA256,
+    "SHA-224",
+    [
+        0xC1059ED8, 0x367CD507, 0x3070DD17, 0xF70E5939, 0xFFC00B31, 0x68581511, 0x64F98FA7,
+        0xBEFA4FA4
+    ]
+);
+
+sha_core!(
+    Sha256Core,
+    Sha256,
+    U32,
+    SHA256,
+    "SHA-256",
+    [
+        0x6A09E667, 0xBB67AE85, 0x3C6EF372, 0xA54FF53A, 0x510E527F, 0x9B05688C, 0x1F83D9AB,
+        0x5BE0CD19
+    ]
+);
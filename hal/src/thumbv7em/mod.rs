@@ -1,8 +1,26 @@
+#[cfg(feature = "aes")]
+pub mod aes;
+
+#[cfg(all(
+    feature = "can",
+    any(feature = "same51", feature = "same53", feature = "same54")
+))]
+pub mod can;
+
+pub mod bootloader;
 pub mod calibration;
 pub mod clock;
 pub mod eic;
+pub mod freqm;
+
+#[cfg(all(feature = "gmac", any(feature = "same53", feature = "same54")))]
+pub mod gmac;
+
+pub mod pcc;
+pub mod pdec;
 pub mod pukcc;
 pub mod qspi;
+pub mod sdhc;
 pub(crate) mod sercom;
 pub mod timer;
 pub mod trng;
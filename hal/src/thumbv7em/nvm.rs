@@ -17,14 +17,26 @@
 //!
 //! Module features:
 //! - Erase & write over non-volatile memory in a device.
-//! - Swap banks
+//! - Erase and write the inactive bank, then swap banks, for A/B firmware
+//!   updates
+//! - Program the user page fuses through the typed [`Userpage`] struct
+//! - Lock and unlock flash regions, chip erase and the security bit
+//! - [`FlashRegion`], an `embedded_storage` `NorFlash` implementation over
+//!   a part of the flash, when the `embedded-storage` feature is enabled
 #![warn(missing_docs)]
 
 pub mod smart_eeprom;
 
+#[cfg(feature = "embedded-storage")]
+#[path = "../nvm/storage.rs"]
+mod storage;
+#[cfg(feature = "embedded-storage")]
+pub use storage::FlashRegion;
+
 pub use crate::target_device::nvmctrl::ctrla::PRM_A;
 use crate::target_device::nvmctrl::ctrlb::CMD_AW;
 use crate::target_device::NVMCTRL;
+use core::convert::Infallible;
 use core::num::NonZeroU32;
 use core::ops::Range;
 
@@ -67,6 +79,15 @@ pub const PAGESIZE: u32 = 512;
 /// Size of one block
 pub const BLOCKSIZE: u32 = 512 * 16;
 
+/// Address of the NVM user page
+pub const USERPAGE_ADDR: u32 = 0x0080_4000;
+
+/// Number of lock regions the flash is divided into
+pub const LOCK_REGIONS: u32 = 32;
+
+/// Size of a quad word in bytes, the user page programming granularity
+const QUAD_WORD_SIZE: u32 = 16;
+
 /// Non-volatile memory controller
 pub struct Nvm {
     /// PAC peripheral
@@ -108,6 +129,8 @@ pub enum Error {
     Dsu(super::dsu::Error),
     /// An alignment requirement was not fulfilled
     Alignment,
+    /// The inactive bank does not hold a bootable application
+    NotBootable,
 }
 
 /// Physical flash banks
@@ -179,6 +202,69 @@ impl Nvm {
         unreachable!();
     }
 
+    /// Erase the inactive bank, up to the SmartEEPROM if it is allocated
+    ///
+    /// The code and data of the application are linked into the active bank,
+    /// so erasing the inactive bank can't affect them.
+    #[inline]
+    pub fn erase_inactive_bank(&mut self) -> Result<()> {
+        let start = Bank::Inactive.address();
+        let blocks = (self.smart_eeprom_start() - start) / BLOCKSIZE;
+        if blocks == 0 {
+            return Ok(());
+        }
+        // SAFETY: The inactive bank holds no code or data in use
+        unsafe { self.erase(start, blocks, EraseGranularity::Block) }
+    }
+
+    /// Write `data` to the inactive bank, `offset` bytes from its start
+    ///
+    /// An image written at offset 0 boots after [`Nvm::swap_banks`]. The
+    /// blocks written must have been erased, see
+    /// [`Nvm::erase_inactive_bank`].
+    #[inline]
+    pub fn write_inactive_bank(&mut self, offset: u32, data: &[u32]) -> Result<()> {
+        let length = data.len() as u32 * 4;
+        match offset.checked_add(length) {
+            Some(end) if end <= Bank::Inactive.length() => {}
+            _ => return Err(Error::NonFlash),
+        }
+        // SAFETY: The inactive bank holds no code or data in use
+        unsafe { self.write_from_slice(Bank::Inactive.address() + offset, data) }
+    }
+
+    /// Check that the inactive bank starts with the vector table of an
+    /// application linked at address 0
+    ///
+    /// See [`Application::is_valid`](crate::bootloader::Application::is_valid)
+    /// for the checks performed.
+    #[inline]
+    pub fn is_inactive_bank_bootable(&self) -> bool {
+        let vectors = Bank::Inactive.address() as *const u32;
+        // SAFETY: The whole flash is readable
+        let (sp, reset) = unsafe {
+            (
+                core::ptr::read_volatile(vectors),
+                core::ptr::read_volatile(vectors.add(1)),
+            )
+        };
+        crate::bootloader::is_valid_vector_table(0, Bank::Inactive.length(), sp, reset)
+    }
+
+    /// Swap the flash banks if the inactive bank is bootable, see
+    /// [`Nvm::is_inactive_bank_bootable`]
+    ///
+    /// The processor is reset, after which the inactive bank becomes the
+    /// active bank. Returns [`Error::NotBootable`] otherwise.
+    #[inline]
+    pub fn swap_banks(&mut self) -> Result<Infallible> {
+        if !self.is_inactive_bank_bootable() {
+            return Err(Error::NotBootable);
+        }
+        // SAFETY: The inactive bank holds a vector table, checked above
+        unsafe { self.bank_swap() }
+    }
+
     /// Set the power reduction mode
     #[inline]
     pub fn power_reduction_mode(&mut self, prm: PRM_A) {
@@ -191,6 +277,30 @@ impl Nvm {
         !self.nvm.status.read().bpdis().bit()
     }
 
+    /// Read the `BOOTPROT` value applied at reset, selecting the size of the
+    /// boot protected area
+    ///
+    /// * 15 = no boot protection
+    /// * (15 - value) * 8KiB = protected size
+    #[inline]
+    pub fn bootprot(&self) -> u8 {
+        self.nvm.status.read().bootprot().bits()
+    }
+
+    /// Program the `BOOTPROT` fuse of the user page
+    ///
+    /// The other fuses are preserved. The new protection is applied on the
+    /// next reset.
+    ///
+    /// # Safety
+    /// See [`Nvm::write_user_page`].
+    #[inline]
+    pub unsafe fn set_bootprot(&mut self, bootprot: u8) -> Result<()> {
+        let mut userpage = self.user_page();
+        userpage.set_nvm_bootloader_size(bootprot as u32 & 0xf);
+        self.write_user_page(userpage)
+    }
+
     /// Get first bank
     #[inline]
     pub fn first_bank(&self) -> PhysicalBank {
@@ -257,7 +367,7 @@ impl Nvm {
     #[inline]
     pub fn user_page(&self) -> Userpage {
         let mut buffer = 0_u128;
-        let base_addr: *const u8 = 0x0080_4000 as *const u8;
+        let base_addr: *const u8 = USERPAGE_ADDR as *const u8;
 
         for i in 0..16 {
             buffer |= unsafe { core::ptr::read_volatile(base_addr.offset(i as isize)) as u128 }
@@ -322,6 +432,129 @@ impl Nvm {
         }
     }
 
+    /// Program the fuses stored at the start of the user page
+    ///
+    /// The rest of the user page is preserved. Start from the value returned
+    /// by [`Nvm::user_page`] and only change the fields that need updating:
+    /// the factory calibration values must be written back unchanged.
+    ///
+    /// The new fuse values are applied on the next reset.
+    ///
+    /// # Safety
+    /// Invalid fuse values can leave the device unable to boot, for example
+    /// with a brown-out detector level above the supply voltage or an
+    /// always-on watchdog that the application does not service.
+    pub unsafe fn write_user_page(&mut self, userpage: Userpage) -> Result<()> {
+        let mut buffer = [0_u32; (PAGESIZE / 4) as usize];
+        let page = USERPAGE_ADDR as *const u32;
+        for (i, word) in buffer.iter_mut().enumerate() {
+            *word = core::ptr::read_volatile(page.add(i));
+        }
+
+        let fuses = userpage.0.to_le_bytes();
+        for (word, bytes) in buffer.iter_mut().zip(fuses.chunks_exact(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+
+        while !self.is_ready() {}
+        self.set_address(USERPAGE_ADDR);
+        self.command_sync(CMD_AW::EP);
+        self.manage_error_states()?;
+
+        // The user page can only be programmed one quad word at a time
+        for (index, quad_word) in buffer.chunks_exact(4).enumerate() {
+            // Erased flash reads as all ones, so there is nothing to write
+            if quad_word.iter().all(|&word| word == u32::MAX) {
+                continue;
+            }
+
+            let address = USERPAGE_ADDR + index as u32 * QUAD_WORD_SIZE;
+            self.command_sync(CMD_AW::PBC);
+            for (offset, &word) in quad_word.iter().enumerate() {
+                core::ptr::write_volatile((address as *mut u32).add(offset), word);
+            }
+
+            while !self.is_ready() {}
+            self.set_address(address);
+            self.command_sync(CMD_AW::WQW);
+            self.manage_error_states()?;
+        }
+
+        Ok(())
+    }
+
+    /// Size of a single lock region in bytes
+    #[inline]
+    pub fn lock_region_size(&self) -> u32 {
+        retrieve_flash_size() / LOCK_REGIONS
+    }
+
+    /// Check if the lock region containing `address` is locked
+    ///
+    /// Returns [`Error::NonFlash`] if `address` is outside of the flash.
+    #[inline]
+    pub fn is_region_locked(&self, address: u32) -> Result<bool> {
+        let region = address / self.lock_region_size();
+        if region >= LOCK_REGIONS {
+            return Err(Error::NonFlash);
+        }
+        Ok(self.nvm.runlock.read().runlock().bits() & (1 << region) == 0)
+    }
+
+    /// Lock the region containing `address` against writes and erases
+    ///
+    /// The lock lasts until the region is unlocked or the device is reset.
+    /// To lock a region from reset onwards, clear its bit in
+    /// [`Userpage::nvm_locks`] instead.
+    #[inline]
+    pub fn lock_region(&mut self, address: u32) -> Result<()> {
+        self.region_lock_command(address, CMD_AW::LR)
+    }
+
+    /// Unlock the region containing `address`
+    #[inline]
+    pub fn unlock_region(&mut self, address: u32) -> Result<()> {
+        self.region_lock_command(address, CMD_AW::UR)
+    }
+
+    fn region_lock_command(&mut self, address: u32, command: CMD_AW) -> Result<()> {
+        if self.contains_non_flash_memory_area(&(address..address + 1)) {
+            return Err(Error::NonFlash);
+        }
+
+        while !self.is_ready() {}
+        self.set_address(address);
+        self.command_sync(command);
+        self.manage_error_states()
+    }
+
+    /// Enable/disable the DSU chip erase command
+    ///
+    /// Locking chip erase prevents a debugger from erasing the device, and
+    /// lasts until the next reset.
+    #[inline]
+    pub fn chip_erase_lock(&mut self, lock: bool) -> Result<()> {
+        while !self.is_ready() {}
+        if lock {
+            self.command_sync(CMD_AW::CELCK);
+        } else {
+            self.command_sync(CMD_AW::CEULCK);
+        }
+        self.manage_error_states()
+    }
+
+    /// Set the security bit, preventing debugger access to the device
+    ///
+    /// # Safety
+    /// The security bit can only be cleared by a chip erase through the DSU,
+    /// which erases the whole flash.
+    #[inline]
+    pub unsafe fn set_security_bit(&mut self) -> Result<()> {
+        while !self.is_ready() {}
+        self.command_sync(CMD_AW::SSB);
+        self.manage_error_states()
+    }
+
     /// Write to flash memory from a slice
     ///
     /// If `destination_address` is not word-aligned, an error is returned.
@@ -464,11 +697,17 @@ impl Nvm {
         self.is_boot_protected() && range_overlap(input, boot)
     }
 
-    fn contains_smart_eeprom(&self, input: &Range<u32>) -> bool {
+    /// Address of the first block allocated to the SmartEEPROM, at the end
+    /// of the flash
+    fn smart_eeprom_start(&self) -> u32 {
         let smart_eeprom_allocated_blocks = self.nvm.seestat.read().sblk().bits() as u32;
         let smart_eeprom_end = Bank::Inactive.address() + Bank::Inactive.length();
-        let smart_eeprom_start = smart_eeprom_end - smart_eeprom_allocated_blocks * BLOCKSIZE;
-        let smart_eeprom = &(smart_eeprom_start..smart_eeprom_end);
+        smart_eeprom_end - smart_eeprom_allocated_blocks * BLOCKSIZE
+    }
+
+    fn contains_smart_eeprom(&self, input: &Range<u32>) -> bool {
+        let smart_eeprom_end = Bank::Inactive.address() + Bank::Inactive.length();
+        let smart_eeprom = &(self.smart_eeprom_start()..smart_eeprom_end);
         range_overlap(input, smart_eeprom)
     }
 
@@ -515,26 +754,50 @@ fn range_overlap(a: &Range<u32>, b: &Range<u32>) -> bool {
 bitfield! {
     #[derive(Copy, Clone, Default)]
     /// POD-style struct representing NVM user page
+    ///
+    /// Read it with [`Nvm::user_page`], modify it through the setters and
+    /// program it with [`Nvm::write_user_page`]. The BOD12 calibration
+    /// parameters are factory settings and have no setter.
     pub struct Userpage(u128);
     impl Debug;
     u32;
-    bod33_disable, _: 0;
-    bod33_level, _: 8, 1;
-    bod33_action, _: 10, 9;
-    bod33_hysteresis, _: 14, 11;
-    bod12_calibration_parameters, _: 25, 12;
-    nvm_bootloader_size, _: 29, 26;
-    see_sblk, _: 35, 32;
-    see_psz, _: 38, 36;
-    ram_ecc_disable, _: 39;
-    wdt_enable, _: 48;
-    wdt_always_on, _: 49;
-    wdt_period, _: 53, 50;
-    wdt_window, _: 57, 54;
-    wdt_ewoffset, _: 61, 58;
-    wdt_wen, _: 62;
-    nvm_locks, _: 95, 64;
-    user_page, _: 127, 96;
+    /// BOD33 disable at power-on
+    pub bod33_disable, set_bod33_disable: 0;
+    /// BOD33 threshold level at power-on
+    pub bod33_level, set_bod33_level: 8, 1;
+    /// BOD33 action at power-on
+    pub bod33_action, set_bod33_action: 10, 9;
+    /// BOD33 hysteresis at power-on
+    pub bod33_hysteresis, set_bod33_hysteresis: 14, 11;
+    /// BOD12 factory calibration parameters
+    pub bod12_calibration_parameters, _: 25, 12;
+    /// Size of the boot protected area, see `NVMCTRL.STATUS.BOOTPROT`
+    ///
+    /// * 15 = no boot protection
+    /// * (15 - value) * 8KiB = protected size
+    pub nvm_bootloader_size, set_nvm_bootloader_size: 29, 26;
+    /// Number of NVM blocks allocated to the SmartEEPROM
+    pub see_sblk, set_see_sblk: 35, 32;
+    /// SmartEEPROM virtual page size
+    pub see_psz, set_see_psz: 38, 36;
+    /// RAM ECC disable
+    pub ram_ecc_disable, set_ram_ecc_disable: 39;
+    /// Watchdog enable at power-on
+    pub wdt_enable, set_wdt_enable: 48;
+    /// Watchdog always-on at power-on
+    pub wdt_always_on, set_wdt_always_on: 49;
+    /// Watchdog period at power-on
+    pub wdt_period, set_wdt_period: 53, 50;
+    /// Watchdog window at power-on
+    pub wdt_window, set_wdt_window: 57, 54;
+    /// Watchdog early warning offset at power-on
+    pub wdt_ewoffset, set_wdt_ewoffset: 61, 58;
+    /// Watchdog window mode enable at power-on
+    pub wdt_wen, set_wdt_wen: 62;
+    /// Region lock bits at power-on, a cleared bit locks the region
+    pub nvm_locks, set_nvm_locks: 95, 64;
+    /// User-defined word
+    pub user_page, set_user_page: 127, 96;
 }
 
 bitfield! {
@@ -21,13 +21,26 @@
 //! populated from proper bits in NVM controller user page on power-on-reset. By
 //! default, `SBLK` property is set to `0`, effectively disabling SmartEEPROM.
 //!
-//! One of possible safe ways to change user page content is to use `OpenOCD`
+//! The user page can be changed from the firmware itself, by setting
+//! [`Userpage::set_see_sblk`] and [`Userpage::set_see_psz`] and programming
+//! it with [`Nvm::write_user_page`]. Another way is to use `OpenOCD`
 //! custom commmands. `atsame5x`'s `OpenOCD` driver supports `atsame5 userpage`
 //! command. To access it from GDB, it has to be preceded with a `monitor`
 //! clause.
 //!
 //! To access [`SmartEeprom`] struct, call [`Nvm::smart_eeprom`] method to
 //! retrieve its instance.
+//!
+//! Writes are either committed to flash immediately
+//! ([`WriteMode::UNBUFFERED`]), or collected in the SmartEEPROM page buffer
+//! until a page boundary is crossed ([`WriteMode::BUFFERED`]). When buffered,
+//! call [`SmartEeprom::flush`] to commit the pending data, for example before
+//! entering a low-power mode. Check [`SmartEeprom::is_busy`] to find out if
+//! the controller is still committing data.
+//!
+//! [`Userpage::set_see_sblk`]: super::Userpage::set_see_sblk
+//! [`Userpage::set_see_psz`]: super::Userpage::set_see_psz
+//! [`Nvm::write_user_page`]: super::Nvm::write_user_page
 
 use core::marker::PhantomData;
 
@@ -35,6 +48,8 @@ use super::Nvm;
 use crate::pac::{nvmctrl::ctrlb::CMD_AW, NVMCTRL};
 use crate::typelevel::Sealed;
 
+pub use crate::pac::nvmctrl::seecfg::WMODE_A as WriteMode;
+
 /// Struct representing a SmartEEPROM instance.
 ///
 /// It is generic over:
@@ -69,8 +84,6 @@ pub enum SmartEepromRetrievalFailure {
     Disabled,
     /// Support for disabled automatic page reallocation is not implemented.
     DisabledAutomaticPageReallocationNotSupported,
-    /// Support for buffered writes to NVM is not implemented.
-    BufferedWritesNotSupported,
     /// `SBLK` must be in range `1..=10`. `SBLK` is represented by 4 bits in a
     /// user page which means that it can be between `0` and `15`. Documentation
     /// does not cover cases for `11..=15`, therefore API considers them
@@ -109,9 +122,6 @@ impl<'a> SmartEepromMode<'a> {
         if nvm.nvm.seecfg.read().aprdis().bit_is_set() {
             return Err(DisabledAutomaticPageReallocationNotSupported);
         }
-        if nvm.nvm.seecfg.read().wmode().is_buffered() {
-            return Err(BufferedWritesNotSupported);
-        }
         let sblk = nvm.nvm.seestat.read().sblk().bits() as u32;
         let psz = nvm.nvm.seestat.read().psz().bits() as u32;
         let virtual_size = match (sblk, psz) {
@@ -190,6 +200,32 @@ impl<'a, T: SmartEepromState> SmartEeprom<'a, T> {
             });
     }
 
+    /// Size of the SmartEEPROM address space in bytes
+    #[inline]
+    pub fn virtual_size(&self) -> usize {
+        self.virtual_size
+    }
+
+    /// Returns `true` while the controller is committing data to flash or
+    /// reallocating pages. Accesses stall until it is done.
+    #[inline]
+    pub fn is_busy(&self) -> bool {
+        self.nvm.nvm.seestat.read().busy().bit_is_set()
+    }
+
+    /// Returns `true` if the page buffer holds data that has not been
+    /// committed to flash yet. Only happens in [`WriteMode::BUFFERED`].
+    #[inline]
+    pub fn has_pending_data(&self) -> bool {
+        self.nvm.nvm.seestat.read().load().bit_is_set()
+    }
+
+    /// Current write mode
+    #[inline]
+    pub fn write_mode(&self) -> WriteMode {
+        self.nvm.nvm.seecfg.read().wmode().variant()
+    }
+
     /// Returns an  iterator over SmartEEPROM address space.
     pub fn iter<TP: SmartEepromPointableSize>(&'a self) -> SmartEepromIter<'a, TP> {
         SmartEepromIter {
@@ -244,8 +280,30 @@ impl<'a> SmartEeprom<'a, Unlocked> {
         }
     }
 
+    /// Select whether writes are committed to flash immediately or buffered
+    #[inline]
+    pub fn set_write_mode(&mut self, mode: WriteMode) {
+        wait_if_busy();
+        self.nvm.nvm.seecfg.modify(|_, w| w.wmode().variant(mode));
+    }
+
+    /// Commit data pending in the page buffer to flash
+    ///
+    /// Does nothing if there is no pending data.
+    #[inline]
+    pub fn flush(&mut self) {
+        wait_if_busy();
+        if self.has_pending_data() {
+            self.nvm.command_sync(CMD_AW::SEEFLUSH);
+        }
+        wait_if_busy();
+    }
+
     /// Locks SmartEEPROM, allowing only to perform read operations
-    pub fn lock(self) -> SmartEeprom<'a, Locked> {
+    ///
+    /// Pending buffered data is flushed first.
+    pub fn lock(mut self) -> SmartEeprom<'a, Locked> {
+        self.flush();
         self.nvm.command_sync(CMD_AW::LSEE);
         let Self {
             nvm, virtual_size, ..
@@ -0,0 +1,224 @@
+//! # PCC - Parallel Capture Controller
+//!
+//! The PCC samples an 8 to 14-bit parallel data bus on the edges of an
+//! external clock, typically the pixel clock of a camera sensor such as the
+//! OV7670. Two data enable inputs, usually wired to VSYNC and HSYNC, qualify
+//! the samples, so only the active part of a frame is captured.
+//!
+//! Samples are packed into 32-bit words: a word holds four 8-bit samples, or
+//! two samples wider than 8 bits, the first sample in the least significant
+//! bits.
+//!
+//! Words can be read one at a time with [`Pcc::read`], but a frame is usually
+//! captured into SRAM with the DMAC, see [`Pcc::capture_with_dma`]. The
+//! waker of the transfer is called once the buffer has been filled, which
+//! signals the end of the frame when the buffer is sized to hold exactly one
+//! frame. Another frame can then be captured with
+//! [`Transfer::recycle_source`](crate::dmac::Transfer::recycle_source).
+//!
+//! The driver does not configure the PCC pins, which must be set to alternate
+//! function K beforehand: PA14 for the clock, PA12 and PA13 for the data
+//! enables, and PA16-PA23 for the lower 8 data bits.
+//!
+//! ```no_run
+//! use atsamd_hal::pcc::{Config, Pcc};
+//!
+//! // A QVGA frame, two bytes per pixel
+//! static mut FRAME: [u32; 320 * 240 / 2] = [0; 320 * 240 / 2];
+//!
+//! let mut pcc = Pcc::new(&mut peripherals.MCLK, peripherals.PCC, Config::default());
+//! pcc.enable();
+//! let transfer = pcc.capture_with_dma(unsafe { &mut FRAME }, channel, |_| {
+//!     // Frame complete
+//! });
+//! ```
+
+use crate::bus_clock::BusClock;
+use crate::pac::{MCLK, PCC};
+
+#[cfg(feature = "dma")]
+use crate::dmac::{
+    channel::{AnyChannel, Busy, CallbackStatus, Channel, InterruptFlags, Ready},
+    transfer::BufferPair,
+    Buffer, Transfer, TriggerAction, TriggerSource,
+};
+
+/// Width of the parallel data bus
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataWidth {
+    /// 8-bit samples, four per word
+    Bits8,
+    /// 10-bit samples, two per word
+    Bits10,
+    /// 12-bit samples, two per word
+    Bits12,
+    /// 14-bit samples, two per word
+    Bits14,
+}
+
+/// Data enable inputs qualifying the samples
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Qualifier {
+    /// Sample while both data enables are high
+    DataEnable,
+    /// Sample on every clock edge, ignoring the data enables
+    AlwaysSample,
+}
+
+/// PCC configuration
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Width of the data bus
+    pub data_width: DataWidth,
+    /// How the samples are qualified
+    pub qualifier: Qualifier,
+    /// Only capture every other sample, which keeps the luminance of a
+    /// YCbCr stream
+    pub half_sampling: bool,
+}
+
+impl Default for Config {
+    /// 8-bit data bus, qualified by the data enables
+    #[inline]
+    fn default() -> Self {
+        Self {
+            data_width: DataWidth::Bits8,
+            qualifier: Qualifier::DataEnable,
+            half_sampling: false,
+        }
+    }
+}
+
+/// PCC errors
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A word was captured before the previous one was read
+    Overrun,
+}
+
+/// Parallel Capture Controller
+pub struct Pcc {
+    pcc: PCC,
+}
+
+impl Pcc {
+    /// Enable the PCC clock and configure the PCC
+    ///
+    /// Capture is disabled, see [`Pcc::enable`].
+    pub fn new(mclk: &mut MCLK, pcc: PCC, config: Config) -> Self {
+        PCC::enable_bus_clock(mclk);
+
+        let isize = match config.data_width {
+            DataWidth::Bits8 => 0,
+            DataWidth::Bits10 => 1,
+            DataWidth::Bits12 => 2,
+            DataWidth::Bits14 => 3,
+        };
+        pcc.mr.write(|w| unsafe {
+            // Always pack samples into whole words
+            w.dsize().bits(2);
+            w.isize().bits(isize);
+            w.alwys().bit(config.qualifier == Qualifier::AlwaysSample);
+            w.halfs().bit(config.half_sampling)
+        });
+        Self { pcc }
+    }
+
+    /// Disable the PCC and its clock and return the register block
+    pub fn free(self, mclk: &mut MCLK) -> PCC {
+        self.disable();
+        PCC::disable_bus_clock(mclk);
+        self.pcc
+    }
+
+    /// Start capturing
+    #[inline]
+    pub fn enable(&mut self) {
+        self.pcc.mr.modify(|_, w| w.pcen().set_bit());
+    }
+
+    /// Stop capturing
+    #[inline]
+    pub fn disable(&self) {
+        self.pcc.mr.modify(|_, w| w.pcen().clear_bit());
+    }
+
+    /// Enable the data ready interrupt
+    #[inline]
+    pub fn enable_interrupt(&mut self) {
+        self.pcc.ier.write(|w| w.drdy().set_bit());
+    }
+
+    /// Disable the data ready interrupt
+    #[inline]
+    pub fn disable_interrupt(&mut self) {
+        self.pcc.idr.write(|w| w.drdy().set_bit());
+    }
+
+    /// Read the next captured word without blocking
+    ///
+    /// Returns [`Error::Overrun`] if words were lost since the last read.
+    /// Reading the status clears the overrun flag.
+    pub fn read(&mut self) -> nb::Result<u32, Error> {
+        let status = self.pcc.isr.read();
+        if status.ovre().bit_is_set() {
+            return Err(nb::Error::Other(Error::Overrun));
+        }
+        if status.drdy().bit_is_clear() {
+            return Err(nb::Error::WouldBlock);
+        }
+        Ok(self.pcc.rhr.read().bits())
+    }
+}
+
+#[cfg(feature = "dma")]
+unsafe impl Buffer for Pcc {
+    type Beat = u32;
+
+    #[inline]
+    fn dma_ptr(&mut self) -> *mut u32 {
+        self.pcc.rhr.as_ptr()
+    }
+
+    #[inline]
+    fn incrementing(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn buffer_len(&self) -> usize {
+        1
+    }
+}
+
+#[cfg(feature = "dma")]
+impl Pcc {
+    /// Transform the [`Pcc`] into a DMA [`Transfer`] and start capturing
+    /// into the provided buffer
+    ///
+    /// `waker` is called from the DMAC interrupt handler once the buffer has
+    /// been filled.
+    #[inline]
+    pub fn capture_with_dma<Ch, B, W>(
+        self,
+        buf: B,
+        mut channel: Ch,
+        waker: W,
+    ) -> Transfer<Channel<Ch::Id, Busy>, BufferPair<Self, B>, W>
+    where
+        Ch: AnyChannel<Status = Ready>,
+        B: Buffer<Beat = u32> + 'static,
+        W: FnOnce(CallbackStatus) + 'static,
+    {
+        channel
+            .as_mut()
+            .enable_interrupts(InterruptFlags::new().with_tcmpl(true));
+
+        // SAFETY: We use new_unchecked to avoid having to pass a 'static self as the
+        // source buffer. This is safe as long as we guarantee the destination
+        // buffer is static.
+        unsafe { Transfer::new_unchecked(channel, self, buf, false) }
+            .with_waker(waker)
+            .begin(TriggerSource::PCC_RX, TriggerAction::BURST)
+    }
+}
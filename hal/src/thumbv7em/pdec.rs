@@ -0,0 +1,307 @@
+//! # PDEC - Position Decoder
+//!
+//! The PDEC decodes the signals of a quadrature encoder in hardware, so
+//! encoder edges don't need to be counted by interrupt handlers. Its 16-bit
+//! counter holds both the angular position, in the lower bits, and the
+//! number of revolutions, in the upper bits.
+//!
+//! The angular position can either use the whole angular field, or wrap
+//! after a given number of counts per revolution. When the index input is
+//! enabled, the angular position is also reset on every index pulse, which
+//! keeps the position aligned with the mechanical zero of the encoder.
+//!
+//! The driver does not configure the PDEC pins, which must be set to
+//! alternate function G beforehand. Don't forget to configure the generic
+//! clock of the PDEC as well.
+//!
+//! ```no_run
+//! use atsamd_hal::pdec::{Config, Pdec};
+//!
+//! let gclk0 = clocks.gclk0();
+//! let _pdec_clock = clocks.pdec(&gclk0).unwrap();
+//!
+//! let mut config = Config::default();
+//! config.counts_per_revolution = Some(2048);
+//! config.index = true;
+//! let mut pdec = Pdec::new(peripherals.PDEC, &mut peripherals.MCLK, config);
+//!
+//! let angle = pdec.position();
+//! // Counts travelled since the previous call, use a fixed period to
+//! // measure the velocity
+//! let delta = pdec.delta();
+//! ```
+
+use crate::bus_clock::BusClock;
+use crate::pac::{
+    pdec::{ctrla::CONF_A, ctrlbset},
+    MCLK, PDEC,
+};
+
+/// Quadrature decoding configuration
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decoding {
+    /// Count on every edge of both phases
+    X4,
+    /// Count on every edge of both phases, with phase error detection
+    X4Secure,
+    /// Count on every edge of phase A
+    X2,
+    /// Count on every edge of phase A, with phase error detection
+    X2Secure,
+    /// Count on every edge of both phases, correcting the position on index
+    /// pulses
+    AutoCorrection,
+}
+
+impl From<Decoding> for CONF_A {
+    #[inline]
+    fn from(decoding: Decoding) -> Self {
+        match decoding {
+            Decoding::X4 => CONF_A::X4,
+            Decoding::X4Secure => CONF_A::X4S,
+            Decoding::X2 => CONF_A::X2,
+            Decoding::X2Secure => CONF_A::X2S,
+            Decoding::AutoCorrection => CONF_A::AUTOC,
+        }
+    }
+}
+
+/// Rotation direction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// The counter is incrementing
+    Forward,
+    /// The counter is decrementing
+    Backward,
+}
+
+/// PDEC configuration
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Quadrature decoding configuration
+    pub decoding: Decoding,
+    /// Size of the angular position field, between 9 and 16 bits. The
+    /// remaining bits of the counter count revolutions.
+    pub angular_bits: u8,
+    /// Wrap the angular position after this many counts, instead of using
+    /// the whole angular field
+    pub counts_per_revolution: Option<u16>,
+    /// Enable the index input, resetting the angular position on every index
+    /// pulse
+    pub index: bool,
+    /// Swap the phase A and phase B inputs
+    pub swap: bool,
+    /// Length of the input filter, in generic clock cycles. Zero disables the
+    /// filter.
+    pub filter: u8,
+}
+
+impl Default for Config {
+    /// X4 decoding, 12-bit angular position and 4-bit revolution counter,
+    /// without index
+    #[inline]
+    fn default() -> Self {
+        Self {
+            decoding: Decoding::X4,
+            angular_bits: 12,
+            counts_per_revolution: None,
+            index: false,
+            swap: false,
+            filter: 0,
+        }
+    }
+}
+
+/// Errors reported by the PDEC
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Errors {
+    /// Invalid transition between the phases
+    pub quadrature: bool,
+    /// The index pulse didn't occur at the expected position
+    pub index: bool,
+}
+
+/// Compute the signed distance from `previous` to `current`, for a counter
+/// wrapping after `range` counts
+fn wrapping_delta(previous: u32, current: u32, range: u32) -> i32 {
+    let delta = (current + range - previous) % range;
+    if delta > range / 2 {
+        delta as i32 - range as i32
+    } else {
+        delta as i32
+    }
+}
+
+/// Position Decoder in quadrature decoding mode
+pub struct Pdec {
+    pdec: PDEC,
+    angular_bits: u8,
+    counts_per_revolution: u32,
+    previous: u32,
+}
+
+impl Pdec {
+    /// Enable the PDEC clock, configure the decoder and start counting
+    ///
+    /// # Panics
+    ///
+    /// Panics if `angular_bits` is not between 9 and 16, or if
+    /// `counts_per_revolution` doesn't fit in the angular field.
+    pub fn new(pdec: PDEC, mclk: &mut MCLK, config: Config) -> Self {
+        let angular_bits = config.angular_bits;
+        assert!((9..=16).contains(&angular_bits));
+        let counts_per_revolution = match config.counts_per_revolution {
+            Some(counts) => {
+                assert!(counts > 0 && u32::from(counts) <= 1 << angular_bits);
+                u32::from(counts)
+            }
+            None => 1 << angular_bits,
+        };
+
+        PDEC::enable_bus_clock(mclk);
+        pdec.ctrla.write(|w| w.swrst().set_bit());
+        while pdec.syncbusy.read().swrst().bit_is_set() {}
+
+        pdec.ctrla.write(|w| {
+            w.mode().qdec();
+            w.conf().variant(config.decoding.into());
+            w.swap().bit(config.swap);
+            w.peren().bit(config.counts_per_revolution.is_some());
+            w.pinen0().set_bit();
+            w.pinen1().set_bit();
+            w.pinen2().bit(config.index);
+            // SAFETY: The angular field size was checked above
+            unsafe { w.angular().bits(angular_bits - 9) }
+        });
+        // SAFETY: Any filter length is valid
+        pdec.filter
+            .write(|w| unsafe { w.filter().bits(config.filter) });
+        // SAFETY: The period fits in the angular field
+        let period = (counts_per_revolution - 1) as u16;
+        pdec.cc[0].write(|w| unsafe { w.cc().bits(period) });
+        while pdec.syncbusy.read().bits() != 0 {}
+
+        pdec.ctrla.modify(|_, w| w.enable().set_bit());
+        while pdec.syncbusy.read().enable().bit_is_set() {}
+
+        let mut pdec = Self {
+            pdec,
+            angular_bits,
+            counts_per_revolution,
+            previous: 0,
+        };
+        pdec.command(|w| w.start());
+        pdec.previous = pdec.total();
+        pdec
+    }
+
+    /// Stop counting, disable the PDEC clock and return the register block
+    pub fn free(self, mclk: &mut MCLK) -> PDEC {
+        self.pdec.ctrla.write(|w| w.swrst().set_bit());
+        while self.pdec.syncbusy.read().swrst().bit_is_set() {}
+        PDEC::disable_bus_clock(mclk);
+        self.pdec
+    }
+
+    #[inline]
+    fn command(&mut self, command: impl FnOnce(ctrlbset::CMD_W) -> &mut ctrlbset::W) {
+        self.pdec.ctrlbset.write(|w| command(w.cmd()));
+        while self.pdec.syncbusy.read().ctrlb().bit_is_set() {}
+    }
+
+    /// Read the raw counter, holding the revolutions in the upper bits and
+    /// the angular position in the lower bits
+    #[inline]
+    pub fn count(&mut self) -> u16 {
+        self.command(|w| w.readsync());
+        while self.pdec.syncbusy.read().count().bit_is_set() {}
+        self.pdec.count.read().count().bits()
+    }
+
+    /// Angular position, between zero and the number of counts per
+    /// revolution
+    #[inline]
+    pub fn position(&mut self) -> u16 {
+        let mask = ((1u32 << self.angular_bits) - 1) as u16;
+        self.count() & mask
+    }
+
+    /// Number of revolutions, wrapping according to the size of the
+    /// revolution field
+    #[inline]
+    pub fn revolutions(&mut self) -> u16 {
+        if self.angular_bits == 16 {
+            0
+        } else {
+            self.count() >> self.angular_bits
+        }
+    }
+
+    /// Position in counts since the start of the revolution counter
+    #[inline]
+    fn total(&mut self) -> u32 {
+        let count = u32::from(self.count());
+        let angular = count & ((1 << self.angular_bits) - 1);
+        let revolutions = count >> self.angular_bits;
+        revolutions * self.counts_per_revolution + angular
+    }
+
+    /// Signed number of counts travelled since the previous call
+    ///
+    /// Calling this method at a fixed period measures the velocity of the
+    /// encoder. The period must be short enough for the encoder to travel
+    /// less than half of the counter range between two calls.
+    pub fn delta(&mut self) -> i32 {
+        let range = self.counts_per_revolution << (16 - self.angular_bits);
+        let current = self.total();
+        let delta = wrapping_delta(self.previous, current, range);
+        self.previous = current;
+        delta
+    }
+
+    /// Direction of the last count
+    #[inline]
+    pub fn direction(&self) -> Direction {
+        if self.pdec.status.read().dir().bit_is_set() {
+            Direction::Backward
+        } else {
+            Direction::Forward
+        }
+    }
+
+    /// Read and clear the errors detected by the decoder
+    pub fn errors(&mut self) -> Errors {
+        let status = self.pdec.status.read();
+        let errors = Errors {
+            quadrature: status.qerr().bit_is_set(),
+            index: status.idxerr().bit_is_set(),
+        };
+        self.pdec.status.write(|w| {
+            w.qerr().bit(errors.quadrature);
+            w.idxerr().bit(errors.index)
+        });
+        errors
+    }
+
+    /// Reset the counter to zero
+    #[inline]
+    pub fn reset_count(&mut self) {
+        self.command(|w| w.retrigger());
+        self.previous = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::wrapping_delta;
+
+    #[test]
+    fn delta_handles_wrapping() {
+        assert_eq!(wrapping_delta(10, 25, 1 << 16), 15);
+        assert_eq!(wrapping_delta(25, 10, 1 << 16), -15);
+        assert_eq!(wrapping_delta(65530, 4, 1 << 16), 10);
+        assert_eq!(wrapping_delta(4, 65530, 1 << 16), -10);
+        // 2000 counts per revolution and 16 revolutions
+        assert_eq!(wrapping_delta(31_990, 5, 32_000), 15);
+    }
+}
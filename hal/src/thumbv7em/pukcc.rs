@@ -21,9 +21,10 @@
 pub mod c_abi;
 pub mod curves;
 
+use crate::bus_clock::{BusClock, BusClockRegisters};
 use crate::pac::MCLK;
 use c_abi::{u4, CryptoRamSlice, Service};
-use curves::Curve;
+use curves::{BinaryCurve, Curve};
 
 use rand_core::{CryptoRng, RngCore};
 
@@ -67,6 +68,23 @@ pub struct Pukcc {
     __: (),
 }
 
+impl BusClock for Pukcc {
+    #[inline]
+    fn enable_bus_clock(regs: &BusClockRegisters) {
+        cortex_m::interrupt::free(|_| regs.ahbmask.modify(|_, w| w.pukcc_().set_bit()));
+    }
+
+    #[inline]
+    fn disable_bus_clock(regs: &BusClockRegisters) {
+        cortex_m::interrupt::free(|_| regs.ahbmask.modify(|_, w| w.pukcc_().clear_bit()));
+    }
+
+    #[inline]
+    fn is_bus_clock_enabled(regs: &BusClockRegisters) -> bool {
+        regs.ahbmask.read().pukcc_().bit_is_set()
+    }
+}
+
 impl Pukcc {
     /// Constructor.
     ///
@@ -76,7 +94,7 @@ impl Pukcc {
         unsafe {
             c_abi::wait_for_crypto_ram_clear_process();
         }
-        mclk.ahbmask.modify(|_, w| w.pukcc_().set_bit());
+        Self::enable_bus_clock(mclk);
         let pukcc = Self { __: () };
         pukcc.self_test().map(|_| pukcc)
     }
@@ -118,7 +136,8 @@ impl Pukcc {
 
     /// Service generating an ECDSA signature.
     ///
-    /// GF(p) service. GF(2^n) variant is not implemented -- use low-level API.
+    /// GF(p) service. For GF(2^n) variant see
+    /// [`Pukcc::gf2n_ecdsa_sign_with_entropy`].
     ///
     /// Input parameters:
     /// - `hash`: `&[u8]` of length [`Curve::SCALAR_LENGTH`]
@@ -166,7 +185,8 @@ impl Pukcc {
     ///
     /// Unsafe: `k` value must be cryptograhically secure.
     ///
-    /// GF(p) service. GF(2^n) variant is not implemented -- use low-level API.
+    /// GF(p) service. For GF(2^n) variant see
+    /// [`Pukcc::gf2n_ecdsa_sign_with_raw_k`].
     ///
     /// Input parameters:
     /// - `hash`: `&[u8]` of length [`Curve::SCALAR_LENGTH`]
@@ -342,7 +362,8 @@ impl Pukcc {
 
     /// Service verifying an ECDSA signature.
     ///
-    /// GF(p) service. GF(2^n) variant is not implemented -- use low-level API.
+    /// GF(p) service. For GF(2^n) variant see
+    /// [`Pukcc::gf2n_ecdsa_verify_signature`].
     ///
     /// Input parameters:
     /// - `signature`: `&[u8]` of length `2 * `[`Curve::SCALAR_LENGTH`]
@@ -478,6 +499,719 @@ impl Pukcc {
             )),
         }
     }
+
+    /// Service performing a modular exponentiation (RSA primitive).
+    ///
+    /// Calculates `result = base ^ exponent mod modulus`. Modulus reduction
+    /// constant (CNS) required by the underlying service is computed
+    /// internally, so the same call covers both private key operations
+    /// (signing, decryption) and public key operations (signature
+    /// verification, encryption).
+    ///
+    /// Input parameters:
+    /// - `base`: `&[u8]` of length smaller or equal to `modulus.len()`
+    ///     - Number being exponentiated. It is zero extended to the length of
+    ///       `modulus`. Must be smaller than `modulus`.
+    /// - `exponent`: `&[u8]` of length aligned to 4
+    ///     - Exponent. For RSA public key operations it is usually `65537`
+    ///       (`&[0x00, 0x01, 0x00, 0x01]`).
+    /// - `modulus`: `&[u8]` of length aligned to 4 and at least 12 bytes
+    ///     - Odd modulus, eg. RSA-2048 public modulus is 256 bytes long.
+    /// - `mode`: [`ExpModMode`]
+    ///     - [`ExpModMode::Regular`] should be used whenever `exponent` is a
+    ///       secret.
+    /// - `window_size`: [`ExpModWindowSize`]
+    ///     - Bigger window speeds up the computation at the cost of CryptoRAM
+    ///       usage.
+    ///
+    /// Output parameters:
+    /// - `result`: `&mut [u8]` of length `modulus.len()`
+    ///     - Mutable slice that result will be copied to from CryptoRAM after
+    ///       the computation is finished.
+    ///
+    /// All parameters are in big endian format.
+    ///
+    /// Return value:
+    /// - `Result::Ok`
+    ///     - Modular exponentiation was calculated successfully
+    /// - `Result::Err`
+    ///     - Possible failure scenarios are encapsulated in a
+    ///       [`ExpModFailure`] enum type
+    ///
+    /// Note: Chinese Remainder Theorem (CRT) based variant is not
+    /// implemented -- use low-level API.
+    pub fn rsa_mod_exp(
+        &self,
+        result: &mut [u8],
+        base: &[u8],
+        exponent: &[u8],
+        modulus: &[u8],
+        mode: ExpModMode,
+        window_size: ExpModWindowSize,
+    ) -> Result<(), ExpModFailure> {
+        let mod_length = modulus.len();
+        if mod_length < 12 || mod_length % 4 != 0 {
+            return Err(ExpModFailure::WrongInputParameter {
+                faulty_slice: "modulus",
+                expected_length: core::cmp::max(12, (mod_length + 3) / 4 * 4),
+                actual_length: mod_length,
+            });
+        }
+        if modulus.last().map_or(true, |&lsb| lsb & 1 == 0) {
+            return Err(ExpModFailure::ModulusIsEven);
+        }
+        if exponent.is_empty() || exponent.len() % 4 != 0 {
+            return Err(ExpModFailure::WrongInputParameter {
+                faulty_slice: "exponent",
+                expected_length: (exponent.len() + 3) / 4 * 4,
+                actual_length: exponent.len(),
+            });
+        }
+        if base.len() > mod_length {
+            return Err(ExpModFailure::WrongInputParameter {
+                faulty_slice: "base",
+                expected_length: mod_length,
+                actual_length: base.len(),
+            });
+        }
+        if result.len() != mod_length {
+            return Err(ExpModFailure::WrongInputParameter {
+                faulty_slice: "result",
+                expected_length: mod_length,
+                actual_length: result.len(),
+            });
+        }
+
+        // CryptoRAM layout (every parameter is little endian and zero padded on
+        // the MSB side):
+        //   [ modulus: mod_length + 4 ]
+        //   [ cns: mod_length + 12 ]
+        //   [ x: 2 * mod_length + 16 ] <- base on input, result on output
+        //   [ precomputation workspace: depends on a window size ]
+        //   [ exponent: exponent.len() + 4 ]
+        let modulus_offset = 0;
+        let cns_offset = modulus_offset + mod_length + 4;
+        let x_offset = cns_offset + mod_length + 12;
+        let precomp_offset = x_offset + 2 * mod_length + 16;
+        let exponent_offset = precomp_offset + window_size.precomp_length(mod_length);
+        let end = exponent_offset + exponent.len() + 4;
+
+        let mut crypto_ram = unsafe { c_abi::CryptoRam::new() };
+        if end > crypto_ram.len() {
+            return Err(ExpModFailure::NotEnoughCryptoRam {
+                required: end,
+                available: crypto_ram.len(),
+            });
+        }
+        crypto_ram[..end].iter_mut().for_each(|el| *el = 0);
+        copy_reversed(&mut crypto_ram[modulus_offset..], modulus);
+
+        // Modulo reduction constant setup. Its workspace overlaps with `x` and
+        // precomputation areas which are not in use yet.
+        calculate_cns(
+            &crypto_ram,
+            modulus_offset,
+            cns_offset,
+            x_offset,
+            mod_length,
+            false,
+        )
+        .map_err(ExpModFailure::CnsSetupFailure)?;
+
+        crypto_ram[x_offset..exponent_offset]
+            .iter_mut()
+            .for_each(|el| *el = 0);
+        copy_reversed(&mut crypto_ram[x_offset..], base);
+        copy_reversed(&mut crypto_ram[exponent_offset..], exponent);
+
+        let mut pukcl_params = c_abi::PukclParams::default();
+        pukcl_params.header.u2Option =
+            mode.option() | window_size.option() | c_abi::ExpMod::EXP_IN_PUKCC_RAM;
+        unsafe {
+            let service_params = &mut pukcl_params.params.ExpMod;
+            service_params.nu1XBase = (&crypto_ram[x_offset..]).pukcc_base();
+            service_params.nu1ModBase = (&crypto_ram[modulus_offset..]).pukcc_base();
+            service_params.nu1CnsBase = (&crypto_ram[cns_offset..]).pukcc_base();
+            service_params.nu1PrecompBase = (&crypto_ram[precomp_offset..]).pukcc_base();
+            service_params.pfu1ExpBase = crypto_ram[exponent_offset..].as_ptr() as _;
+            service_params.u2ModLength = mod_length as u16;
+            service_params.u2ExpLength = exponent.len() as u16;
+            service_params.u1Blinding = 0;
+        }
+
+        unsafe { c_abi::ExpMod::call(&mut pukcl_params) };
+
+        match pukcl_params.header.u2Status.into() {
+            PukclReturnCode::Ok => {}
+            error_code => return Err(ExpModFailure::ServiceFailure(error_code)),
+        };
+
+        // Copying result back from the CryptoRAM while ignoring irrelevant padding.
+        result
+            .iter_mut()
+            .zip(crypto_ram[x_offset..x_offset + mod_length].iter().rev())
+            .for_each(|(target_iter, source_iter)| *target_iter = *source_iter);
+
+        Ok(())
+    }
+
+    /// Service multiplying a point on a binary field curve by a scalar.
+    ///
+    /// GF(2^n) service.
+    ///
+    /// Input parameters:
+    /// - `point`: `&[u8]` of length `2 * `[`BinaryCurve::MOD_LENGTH`]
+    ///     - Point on a curve in affine coordinates. First
+    ///       [`BinaryCurve::MOD_LENGTH`] bytes contain `X` coordinate. Last
+    ///       [`BinaryCurve::MOD_LENGTH`] bytes contain `Y` coordinate.
+    /// - `k`: `&[u8]` of length [`BinaryCurve::SCALAR_LENGTH`]
+    ///     - Scalar that point is multiplied by
+    ///
+    /// Output parameters:
+    /// - `result`: `&mut [u8]` of length `2 * `[`BinaryCurve::MOD_LENGTH`]
+    ///     - Mutable slice that resulting point (in affine coordinates, same
+    ///       layout as `point`) will be copied to from CryptoRAM after
+    ///       computation is finished.
+    ///
+    /// Return value:
+    /// - `Result::Ok`
+    ///     - Multiplication was calculated successfully
+    /// - `Result::Err`
+    ///     - Possible failure scenarios are encapsulated in a
+    ///       [`EccMulFailure`] enum type
+    pub fn gf2n_ecc_mul<C: BinaryCurve>(
+        &self,
+        result: &mut [u8],
+        point: &[u8],
+        k: &[u8],
+    ) -> Result<(), EccMulFailure> {
+        if let Err(e) = C::verify_curve() {
+            return Err(EccMulFailure::InvalidCurve(e));
+        }
+
+        let mod_length: usize = C::MOD_LENGTH.into();
+        let scalar_length: usize = C::SCALAR_LENGTH.into();
+        if result.len() != 2 * mod_length {
+            return Err(EccMulFailure::WrongInputParameter {
+                faulty_slice: "result",
+                expected_length: 2 * mod_length,
+                actual_length: result.len(),
+            });
+        }
+        if point.len() != 2 * mod_length {
+            return Err(EccMulFailure::WrongInputParameter {
+                faulty_slice: "point",
+                expected_length: 2 * mod_length,
+                actual_length: point.len(),
+            });
+        }
+        if k.len() != scalar_length {
+            return Err(EccMulFailure::WrongInputParameter {
+                faulty_slice: "k",
+                expected_length: scalar_length,
+                actual_length: k.len(),
+            });
+        }
+
+        let mut crypto_ram = unsafe { c_abi::CryptoRam::new() };
+        let domain = BinaryDomainLayout::new::<C>();
+        // Service specific layout:
+        //   [ point X, Y, Z: 3 * (mod_length + 4) ]
+        //   [ k: scalar_length + 4 ]
+        //   [ workspace ]
+        let point_offset = domain.end;
+        let k_offset = point_offset + 3 * (mod_length + 4);
+        let workspace_offset = k_offset + scalar_length + 4;
+        domain
+            .setup::<C>(&mut crypto_ram)
+            .map_err(EccMulFailure::CnsSetupFailure)?;
+        copy_point_to_cryptoram(&mut crypto_ram[point_offset..], point, mod_length);
+        copy_reversed(&mut crypto_ram[k_offset..], k);
+
+        let mut pukcl_params = c_abi::PukclParams::default();
+        pukcl_params.header.Specific.set_gf2n(true);
+        unsafe {
+            let service_params = &mut pukcl_params.params.GF2NEccMulFast;
+            service_params.nu1PointBase = (&crypto_ram[point_offset..]).pukcc_base();
+            service_params.nu1ModBase = (&crypto_ram[domain.modulus..]).pukcc_base();
+            service_params.nu1CnsBase = (&crypto_ram[domain.cns..]).pukcc_base();
+            service_params.nu1KBase = (&crypto_ram[k_offset..]).pukcc_base();
+            service_params.nu1ABase = (&crypto_ram[domain.a_curve..]).pukcc_base();
+            service_params.nu1Workspace = (&crypto_ram[workspace_offset..]).pukcc_base();
+            service_params.u2ModLength = C::MOD_LENGTH;
+            service_params.u2KLength = C::SCALAR_LENGTH;
+        }
+
+        unsafe { c_abi::GF2NEccMulFast::call(&mut pukcl_params) };
+
+        match pukcl_params.header.u2Status.into() {
+            PukclReturnCode::Ok => {}
+            error_code => return Err(EccMulFailure::ServiceFailure(error_code)),
+        };
+
+        // Resulting point is in projective coordinates
+        let mut pukcl_params = c_abi::PukclParams::default();
+        pukcl_params.header.Specific.set_gf2n(true);
+        unsafe {
+            let service_params = &mut pukcl_params.params.GF2NEcConvProjToAffine;
+            service_params.nu1ModBase = (&crypto_ram[domain.modulus..]).pukcc_base();
+            service_params.nu1CnsBase = (&crypto_ram[domain.cns..]).pukcc_base();
+            service_params.u2ModLength = C::MOD_LENGTH;
+            service_params.nu1PointABase = (&crypto_ram[point_offset..]).pukcc_base();
+            service_params.nu1Workspace = (&crypto_ram[workspace_offset..]).pukcc_base();
+        }
+
+        unsafe { c_abi::GF2NEcConvProjToAffine::call(&mut pukcl_params) };
+
+        match pukcl_params.header.u2Status.into() {
+            PukclReturnCode::Ok => {}
+            error_code => return Err(EccMulFailure::ServiceFailure(error_code)),
+        };
+
+        copy_point_from_cryptoram(result, &crypto_ram[point_offset..], mod_length);
+
+        Ok(())
+    }
+
+    /// Service generating an ECDSA signature over a binary field curve.
+    ///
+    /// GF(2^n) variant of [`Pukcc::zp_ecdsa_sign_with_entropy`].
+    ///
+    /// Input parameters:
+    /// - `hash`: `&[u8]` of length [`BinaryCurve::SCALAR_LENGTH`]
+    ///     - Hash of a message that is supposed to be signed.
+    /// - `private_key`: `&[u8]` of length [`BinaryCurve::SCALAR_LENGTH`]
+    ///     - Private key used for signing.
+    /// - `k_buffer`: `&mut [u8]` of length [`BinaryCurve::SCALAR_LENGTH`]
+    ///     - Mutable buffer that is being populated by an entropy source and
+    ///       then used for signing.
+    /// - `k_entropy_source`: `&mut (impl RngCore + CryptoRng)`
+    ///     - Generic source of cryptographically secure randomness.
+    ///
+    /// Output parameters:
+    /// - `signature`: `&mut [u8]` of length `2 * `[`BinaryCurve::SCALAR_LENGTH`]
+    ///     - First [`BinaryCurve::SCALAR_LENGTH`] bytes contain `R` part of a
+    ///       signature. Last [`BinaryCurve::SCALAR_LENGTH`] bytes contain `S`
+    ///       part of a signature.
+    pub fn gf2n_ecdsa_sign_with_entropy<C: BinaryCurve>(
+        &self,
+        signature: &mut [u8],
+        hash: &[u8],
+        private_key: &[u8],
+        k_buffer: &mut [u8],
+        k_entropy_source: &mut (impl RngCore + CryptoRng),
+    ) -> Result<(), EcdsaSignFailure> {
+        k_entropy_source.fill_bytes(k_buffer);
+        self.gf2n_ecdsa_sign::<C>(signature, hash, private_key, k_buffer)
+    }
+
+    /// Service generating an ECDSA signature over a binary field curve.
+    ///
+    /// # Safety
+    /// `k` value must be cryptograhically secure.
+    ///
+    /// GF(2^n) variant of [`Pukcc::zp_ecdsa_sign_with_raw_k`]. Parameters
+    /// follow [`Pukcc::gf2n_ecdsa_sign_with_entropy`], with `k` being a
+    /// random number of length [`BinaryCurve::SCALAR_LENGTH`] that must never
+    /// be reused.
+    pub unsafe fn gf2n_ecdsa_sign_with_raw_k<C: BinaryCurve>(
+        &self,
+        signature: &mut [u8],
+        hash: &[u8],
+        private_key: &[u8],
+        k: &[u8],
+    ) -> Result<(), EcdsaSignFailure> {
+        self.gf2n_ecdsa_sign::<C>(signature, hash, private_key, k)
+    }
+
+    fn gf2n_ecdsa_sign<C: BinaryCurve>(
+        &self,
+        signature: &mut [u8],
+        hash: &[u8],
+        private_key: &[u8],
+        k: &[u8],
+    ) -> Result<(), EcdsaSignFailure> {
+        if let Err(e) = C::verify_curve() {
+            return Err(EcdsaSignFailure::InvalidCurve(e));
+        }
+
+        let mod_length: usize = C::MOD_LENGTH.into();
+        let scalar_length: usize = C::SCALAR_LENGTH.into();
+        if signature.len() != 2 * scalar_length {
+            return Err(EcdsaSignFailure::WrongInputParameter {
+                faulty_slice: "signature",
+                expected_length: 2 * scalar_length,
+                actual_length: signature.len(),
+            });
+        }
+        for (faulty_slice, slice) in [("hash", hash), ("private_key", private_key), ("k", k)] {
+            if slice.len() != scalar_length {
+                return Err(EcdsaSignFailure::WrongInputParameter {
+                    faulty_slice,
+                    expected_length: scalar_length,
+                    actual_length: slice.len(),
+                });
+            }
+        }
+
+        let mut crypto_ram = unsafe { c_abi::CryptoRam::new() };
+        let domain = BinaryDomainLayout::new::<C>();
+        // Service specific layout:
+        //   [ hash: scalar_length + 4 ]
+        //   [ private key: scalar_length + 4 ]
+        //   [ k: scalar_length + 4 ]
+        //   [ workspace ]
+        let hash_offset = domain.end;
+        let private_key_offset = hash_offset + scalar_length + 4;
+        let k_offset = private_key_offset + scalar_length + 4;
+        let workspace_offset = k_offset + scalar_length + 4;
+        domain
+            .setup::<C>(&mut crypto_ram)
+            .map_err(EcdsaSignFailure::CnsSetupFailure)?;
+        copy_reversed(&mut crypto_ram[hash_offset..], hash);
+        copy_reversed(&mut crypto_ram[private_key_offset..], private_key);
+        copy_reversed(&mut crypto_ram[k_offset..], k);
+
+        let mut pukcl_params = c_abi::PukclParams::default();
+        pukcl_params.header.Specific.set_gf2n(true);
+        unsafe {
+            let service_params = &mut pukcl_params.params.GF2NEcDsaGenerateFast;
+            service_params.nu1ModBase = (&crypto_ram[domain.modulus..]).pukcc_base();
+            service_params.nu1CnsBase = (&crypto_ram[domain.cns..]).pukcc_base();
+            service_params.u2ModLength = C::MOD_LENGTH;
+            service_params.nu1ScalarNumber = (&crypto_ram[k_offset..]).pukcc_base();
+            service_params.nu1OrderPointBase = (&crypto_ram[domain.order_point..]).pukcc_base();
+            service_params.nu1PrivateKey = (&crypto_ram[private_key_offset..]).pukcc_base();
+            service_params.nu1HashBase = (&crypto_ram[hash_offset..]).pukcc_base();
+            service_params.u2ScalarLength = C::SCALAR_LENGTH;
+            service_params.nu1PointABase = (&crypto_ram[domain.base_point..]).pukcc_base();
+            service_params.nu1ABase = (&crypto_ram[domain.a_curve..]).pukcc_base();
+            service_params.nu1Workspace = (&crypto_ram[workspace_offset..]).pukcc_base();
+        }
+
+        unsafe { c_abi::GF2NEcDsaGenerateFast::call(&mut pukcl_params) };
+
+        match pukcl_params.header.u2Status.into() {
+            PukclReturnCode::Ok => {}
+            error_code => return Err(EcdsaSignFailure::ServiceFailure(error_code)),
+        };
+
+        // Generated signature R part is written to base point X coordinate memory.
+        // Generated signature S part is written to base point Y coordinate memory.
+        // Base point Z coordinate should be zero.
+        let base_point_z = domain.base_point + 2 * (mod_length + 4);
+        if !crypto_ram[base_point_z..base_point_z + mod_length + 4]
+            .iter()
+            .all(|&el| el == 0)
+        {
+            return Err(EcdsaSignFailure::BasePointZCoordinateIsNotZero);
+        }
+
+        let (r, s) = signature.split_at_mut(scalar_length);
+        copy_point_coordinate(r, &crypto_ram[domain.base_point..]);
+        copy_point_coordinate(s, &crypto_ram[domain.base_point + mod_length + 4..]);
+
+        Ok(())
+    }
+
+    /// Service verifying an ECDSA signature over a binary field curve.
+    ///
+    /// GF(2^n) variant of [`Pukcc::zp_ecdsa_verify_signature`].
+    ///
+    /// Input parameters:
+    /// - `signature`: `&[u8]` of length `2 * `[`BinaryCurve::SCALAR_LENGTH`]
+    ///     - Signature that is being verified
+    /// - `hash`: `&[u8]` of length [`BinaryCurve::SCALAR_LENGTH`]
+    ///     - Hash of a message that is signed.
+    /// - `public_key`: `&[u8]` of length `2 * `[`BinaryCurve::MOD_LENGTH`]
+    ///     - Public key used for a signature verification.
+    ///
+    /// In case of an invalid signature the returned error type will be
+    /// [`EcdsaSignatureVerificationFailure::ServiceFailure`]`(`
+    /// [`Warning`][`PukclReturnCode::Warning`]`(`
+    /// [`WrongSignature`][`PukclReturnCodeWarning::WrongSignature`]`))`
+    pub fn gf2n_ecdsa_verify_signature<C: BinaryCurve>(
+        &self,
+        signature: &[u8],
+        hash: &[u8],
+        public_key: &[u8],
+    ) -> Result<(), EcdsaSignatureVerificationFailure> {
+        if let Err(e) = C::verify_curve() {
+            return Err(EcdsaSignatureVerificationFailure::InvalidCurve(e));
+        }
+
+        let mod_length: usize = C::MOD_LENGTH.into();
+        let scalar_length: usize = C::SCALAR_LENGTH.into();
+        if signature.len() != 2 * scalar_length {
+            return Err(EcdsaSignatureVerificationFailure::WrongInputParameter {
+                faulty_slice: "signature",
+                expected_length: 2 * scalar_length,
+                actual_length: signature.len(),
+            });
+        }
+        if hash.len() != scalar_length {
+            return Err(EcdsaSignatureVerificationFailure::WrongInputParameter {
+                faulty_slice: "hash",
+                expected_length: scalar_length,
+                actual_length: hash.len(),
+            });
+        }
+        if public_key.len() != 2 * mod_length {
+            return Err(EcdsaSignatureVerificationFailure::WrongInputParameter {
+                faulty_slice: "public_key",
+                expected_length: 2 * mod_length,
+                actual_length: public_key.len(),
+            });
+        }
+
+        let mut crypto_ram = unsafe { c_abi::CryptoRam::new() };
+        let domain = BinaryDomainLayout::new::<C>();
+        // Service specific layout:
+        //   [ signature R, S: 2 * (scalar_length + 4) ]
+        //   [ hash: scalar_length + 4 ]
+        //   [ public key X, Y, Z: 3 * (mod_length + 4) ]
+        //   [ workspace ]
+        let signature_offset = domain.end;
+        let hash_offset = signature_offset + 2 * (scalar_length + 4);
+        let public_key_offset = hash_offset + scalar_length + 4;
+        let workspace_offset = public_key_offset + 3 * (mod_length + 4);
+        domain
+            .setup::<C>(&mut crypto_ram)
+            .map_err(EcdsaSignatureVerificationFailure::CnsSetupFailure)?;
+        copy_reversed(
+            &mut crypto_ram[signature_offset..],
+            &signature[..scalar_length],
+        );
+        copy_reversed(
+            &mut crypto_ram[signature_offset + scalar_length + 4..],
+            &signature[scalar_length..],
+        );
+        copy_reversed(&mut crypto_ram[hash_offset..], hash);
+        copy_point_to_cryptoram(&mut crypto_ram[public_key_offset..], public_key, mod_length);
+
+        let mut pukcl_params = c_abi::PukclParams::default();
+        pukcl_params.header.Specific.set_gf2n(true);
+        unsafe {
+            let service_params = &mut pukcl_params.params.GF2NEcDsaVerifyFast;
+            service_params.nu1ModBase = (&crypto_ram[domain.modulus..]).pukcc_base();
+            service_params.nu1CnsBase = (&crypto_ram[domain.cns..]).pukcc_base();
+            service_params.u2ModLength = C::MOD_LENGTH;
+            service_params.nu1OrderPointBase = (&crypto_ram[domain.order_point..]).pukcc_base();
+            service_params.nu1PointSignature = (&crypto_ram[signature_offset..]).pukcc_base();
+            service_params.nu1HashBase = (&crypto_ram[hash_offset..]).pukcc_base();
+            service_params.u2ScalarLength = C::SCALAR_LENGTH;
+            service_params.nu1PointABase = (&crypto_ram[domain.base_point..]).pukcc_base();
+            service_params.nu1PointPublicKeyGen = (&crypto_ram[public_key_offset..]).pukcc_base();
+            service_params.nu1ABase = (&crypto_ram[domain.a_curve..]).pukcc_base();
+            service_params.nu1Workspace = (&crypto_ram[workspace_offset..]).pukcc_base();
+        }
+
+        unsafe { c_abi::GF2NEcDsaVerifyFast::call(&mut pukcl_params) };
+
+        match pukcl_params.header.u2Status.into() {
+            PukclReturnCode::Ok => Ok(()),
+            error_code => Err(EcdsaSignatureVerificationFailure::ServiceFailure(
+                error_code,
+            )),
+        }
+    }
+}
+
+/// Offsets of binary field curve domain parameters placed in CryptoRAM by
+/// GF(2^n) services
+///
+/// Layout (every parameter is little endian and zero padded on the MSB side):
+///   [ reduction polynomial: mod_length + 4 ]
+///   [ cns: mod_length + 12 ]
+///   [ a, b: 2 * (mod_length + 4) ]
+///   [ base point X, Y, Z: 3 * (mod_length + 4) ]
+///   [ order point: scalar_length + 4 ]
+struct BinaryDomainLayout {
+    modulus: usize,
+    cns: usize,
+    a_curve: usize,
+    base_point: usize,
+    order_point: usize,
+    end: usize,
+}
+
+impl BinaryDomainLayout {
+    fn new<C: BinaryCurve>() -> Self {
+        let mod_length: usize = C::MOD_LENGTH.into();
+        let scalar_length: usize = C::SCALAR_LENGTH.into();
+        let modulus = 0;
+        let cns = modulus + mod_length + 4;
+        let a_curve = cns + mod_length + 12;
+        let base_point = a_curve + 2 * (mod_length + 4);
+        let order_point = base_point + 3 * (mod_length + 4);
+        let end = order_point + scalar_length + 4;
+        Self {
+            modulus,
+            cns,
+            a_curve,
+            base_point,
+            order_point,
+            end,
+        }
+    }
+
+    /// Clears CryptoRAM, copies curve parameters to it and calculates CNS
+    fn setup<C: BinaryCurve>(
+        &self,
+        crypto_ram: &mut c_abi::CryptoRam,
+    ) -> Result<(), PukclReturnCode> {
+        let mod_length: usize = C::MOD_LENGTH.into();
+        crypto_ram.iter_mut().for_each(|el| *el = 0);
+        // Curve parameters already include zero padding
+        let mut offset = self.a_curve;
+        for parameter in [
+            C::A_CURVE,
+            C::B_CURVE,
+            C::BASE_POINT_A_X,
+            C::BASE_POINT_A_Y,
+            C::BASE_POINT_A_Z,
+            C::ORDER_POINT,
+        ] {
+            copy_reversed(&mut crypto_ram[offset..], parameter);
+            offset += parameter.len();
+        }
+        copy_reversed(&mut crypto_ram[self.modulus..], C::MODULO_P);
+        calculate_cns(
+            crypto_ram,
+            self.modulus,
+            self.cns,
+            self.end,
+            mod_length,
+            true,
+        )?;
+        // Clearing CNS calculation workspace
+        crypto_ram[self.end..].iter_mut().for_each(|el| *el = 0);
+        Ok(())
+    }
+}
+
+/// Calculates a modulo reduction constant (CNS) for a modulus already placed
+/// in CryptoRAM using a RedMod service in a SetupConstant mode
+///
+/// Workspace requires `2 * mod_length + 72` bytes of CryptoRAM. For a binary
+/// field (`gf2n == true`) the modulus is a reduction polynomial.
+fn calculate_cns(
+    crypto_ram: &c_abi::CryptoRam,
+    modulus_offset: usize,
+    cns_offset: usize,
+    workspace_offset: usize,
+    mod_length: usize,
+    gf2n: bool,
+) -> Result<(), PukclReturnCode> {
+    let mut pukcl_params = c_abi::PukclParams::default();
+    pukcl_params.header.u2Option = c_abi::RedMod::SETUP;
+    pukcl_params.header.Specific.set_gf2n(gf2n);
+    unsafe {
+        let service_params = &mut pukcl_params.params.RedMod;
+        service_params.nu1ModBase = (&crypto_ram[modulus_offset..]).pukcc_base();
+        service_params.nu1CnsBase = (&crypto_ram[cns_offset..]).pukcc_base();
+        service_params.u2ModLength = mod_length as u16;
+        service_params.nu1RBase = (&crypto_ram[workspace_offset..]).pukcc_base();
+        service_params.nu1XBase = (&crypto_ram[workspace_offset + 64..]).pukcc_base();
+    }
+
+    unsafe { c_abi::RedMod::call(&mut pukcl_params) };
+
+    match pukcl_params.header.u2Status.into() {
+        PukclReturnCode::Ok => Ok(()),
+        error_code => Err(error_code),
+    }
+}
+
+/// Copies big endian `data` to the beginning of `target` in little endian
+/// format, as required by PUKCC algorithms
+fn copy_reversed(target: &mut [u8], data: &[u8]) {
+    target
+        .iter_mut()
+        .zip(data.iter().rev())
+        .for_each(|(target_iter, data_iter)| *target_iter = *data_iter);
+}
+
+/// Copies a big endian affine point (`X` followed by `Y`) to the beginning of
+/// `target` as a little endian projective point with `Z` coordinate equal to 1
+fn copy_point_to_cryptoram(target: &mut [u8], point: &[u8], mod_length: usize) {
+    let (x, y) = point.split_at(mod_length);
+    copy_reversed(target, x);
+    copy_reversed(&mut target[mod_length + 4..], y);
+    target[2 * (mod_length + 4)] = 1;
+}
+
+/// Copies a little endian affine point from the beginning of `source` to
+/// `point` in big endian format (`X` followed by `Y`)
+fn copy_point_from_cryptoram(point: &mut [u8], source: &[u8], mod_length: usize) {
+    let (x, y) = point.split_at_mut(mod_length);
+    copy_point_coordinate(x, source);
+    copy_point_coordinate(y, &source[mod_length + 4..]);
+}
+
+/// Copies `target.len()` little endian bytes from the beginning of `source`
+/// to `target` in big endian format
+fn copy_point_coordinate(target: &mut [u8], source: &[u8]) {
+    let length = target.len();
+    target
+        .iter_mut()
+        .zip(source[..length].iter().rev())
+        .for_each(|(target_iter, source_iter)| *target_iter = *source_iter);
+}
+
+/// Mode of a [`Pukcc::rsa_mod_exp`] service
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExpModMode {
+    /// Regular (constant time) algorithm. Should be used with secret exponents
+    Regular,
+    /// Fast algorithm. Execution time depends on the exponent, so it should
+    /// only be used with public exponents
+    Fast,
+}
+
+impl ExpModMode {
+    fn option(self) -> c_abi::u2 {
+        match self {
+            ExpModMode::Regular => c_abi::ExpMod::REGULAR_RSA,
+            ExpModMode::Fast => c_abi::ExpMod::FAST_RSA,
+        }
+    }
+}
+
+/// Window size used by a [`Pukcc::rsa_mod_exp`] service
+///
+/// Bigger windows make computation faster but require more space in CryptoRAM
+/// for precomputed values
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExpModWindowSize {
+    One,
+    Two,
+    Three,
+    Four,
+}
+
+impl ExpModWindowSize {
+    fn option(self) -> c_abi::u2 {
+        match self {
+            ExpModWindowSize::One => c_abi::ExpMod::WINDOW_SIZE_1,
+            ExpModWindowSize::Two => c_abi::ExpMod::WINDOW_SIZE_2,
+            ExpModWindowSize::Three => c_abi::ExpMod::WINDOW_SIZE_3,
+            ExpModWindowSize::Four => c_abi::ExpMod::WINDOW_SIZE_4,
+        }
+    }
+
+    /// Length of a precomputation workspace required by a window size for a
+    /// given modulus length (bytes)
+    fn precomp_length(self, mod_length: usize) -> usize {
+        let factor = match self {
+            ExpModWindowSize::One => 3,
+            ExpModWindowSize::Two => 4,
+            ExpModWindowSize::Three => 6,
+            ExpModWindowSize::Four => 10,
+        };
+        factor * (mod_length + 4) + 8
+    }
 }
 
 /// An error type representing failure modes a [`Pukcc::self_test`] service
@@ -487,6 +1221,7 @@ pub struct SelfTestFailure(c_abi::SelfTest);
 /// An error type representing failure modes for a [`Pukcc::zp_ecdsa_sign`]
 /// service
 #[allow(missing_docs)]
+#[non_exhaustive]
 #[derive(Debug)]
 pub enum EcdsaSignFailure {
     WrongInputParameter {
@@ -496,12 +1231,14 @@ pub enum EcdsaSignFailure {
     },
     InvalidCurve(curves::CurveVerficationFailure),
     BasePointZCoordinateIsNotZero,
+    CnsSetupFailure(PukclReturnCode),
     ServiceFailure(PukclReturnCode),
 }
 
 /// An error type representing failure modes for a
 /// [`Pukcc::zp_ecdsa_verify_signature`] service
 #[allow(missing_docs)]
+#[non_exhaustive]
 #[derive(Debug)]
 pub enum EcdsaSignatureVerificationFailure {
     WrongInputParameter {
@@ -510,6 +1247,43 @@ pub enum EcdsaSignatureVerificationFailure {
         actual_length: usize,
     },
     InvalidCurve(curves::CurveVerficationFailure),
+    CnsSetupFailure(PukclReturnCode),
+    ServiceFailure(PukclReturnCode),
+}
+
+/// An error type representing failure modes for a [`Pukcc::gf2n_ecc_mul`]
+/// service
+#[allow(missing_docs)]
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum EccMulFailure {
+    WrongInputParameter {
+        faulty_slice: &'static str,
+        expected_length: usize,
+        actual_length: usize,
+    },
+    InvalidCurve(curves::CurveVerficationFailure),
+    CnsSetupFailure(PukclReturnCode),
+    ServiceFailure(PukclReturnCode),
+}
+
+/// An error type representing failure modes for a [`Pukcc::rsa_mod_exp`]
+/// service
+#[allow(missing_docs)]
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum ExpModFailure {
+    WrongInputParameter {
+        faulty_slice: &'static str,
+        expected_length: usize,
+        actual_length: usize,
+    },
+    ModulusIsEven,
+    NotEnoughCryptoRam {
+        required: usize,
+        available: usize,
+    },
+    CnsSetupFailure(PukclReturnCode),
     ServiceFailure(PukclReturnCode),
 }
 
@@ -79,6 +79,15 @@ pub struct RedMod {
     pub nu1XBase: nu1,
 }
 
+impl RedMod {
+    /// `u2Option` value selecting a CNS setup subservice
+    pub const SETUP: u2 = 0x0100;
+    /// `u2Option` value selecting a modular reduction subservice
+    pub const REDUCTION: u2 = 0x0200;
+    /// `u2Option` value selecting a normalization subservice
+    pub const NORMALIZE: u2 = 0x0400;
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Default, Debug)]
 pub struct Swap {
@@ -379,7 +388,7 @@ bitfield! {
     carry_in, _: 0;
     carry_out, _: 1;
     zero, _: 2;
-    gf2n, _: 3;
+    pub gf2n, set_gf2n: 3;
     violation, _: 4;
 }
 
@@ -535,6 +544,20 @@ pub struct ExpMod {
     pub __Padding1: u2,
 }
 
+impl ExpMod {
+    /// `u2Option` flag selecting a regular (constant time) algorithm
+    pub const REGULAR_RSA: u2 = 0x01;
+    /// `u2Option` flag signaling that an exponent is placed in CryptoRAM
+    pub const EXP_IN_PUKCC_RAM: u2 = 0x02;
+    /// `u2Option` flag selecting a fast algorithm
+    pub const FAST_RSA: u2 = 0x04;
+    /// `u2Option` window size flags
+    pub const WINDOW_SIZE_1: u2 = 0x00;
+    pub const WINDOW_SIZE_2: u2 = 0x08;
+    pub const WINDOW_SIZE_3: u2 = 0x10;
+    pub const WINDOW_SIZE_4: u2 = 0x18;
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Default, Debug)]
 pub struct GF2NEcConvProjToAffine {
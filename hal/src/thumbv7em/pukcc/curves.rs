@@ -173,8 +173,138 @@ pub trait Curve {
     }
 }
 
+/// A type representing a standard Koblitz curve over a binary field
+/// defined by Standards for Efficient Cryptography Group (variant 283k1)
+///
+/// Reduction polynomial: `f(x) = x^283 + x^12 + x^7 + x^5 + 1`
+pub enum Sect283k1 {}
+
+impl BinaryCurve for Sect283k1 {
+    const MOD_LENGTH: super::c_abi::u2 = 36;
+    const SCALAR_LENGTH: super::c_abi::u2 = 36;
+    const MODULO_P: &'static [u8] = &[
+        0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0xa1,
+    ];
+
+    const A_CURVE: &'static [u8] = &[
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    const B_CURVE: &'static [u8] = &[
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+    ];
+
+    const BASE_POINT_A_X: &'static [u8] = &[
+        0x00, 0x00, 0x00, 0x00, 0x05, 0x03, 0x21, 0x3f, 0x78, 0xca, 0x44, 0x88, 0x3f, 0x1a, 0x3b,
+        0x81, 0x62, 0xf1, 0x88, 0xe5, 0x53, 0xcd, 0x26, 0x5f, 0x23, 0xc1, 0x56, 0x7a, 0x16, 0x87,
+        0x69, 0x13, 0xb0, 0xc2, 0xac, 0x24, 0x58, 0x49, 0x28, 0x36,
+    ];
+
+    const BASE_POINT_A_Y: &'static [u8] = &[
+        0x00, 0x00, 0x00, 0x00, 0x01, 0xcc, 0xda, 0x38, 0x0f, 0x1c, 0x9e, 0x31, 0x8d, 0x90, 0xf9,
+        0x5d, 0x07, 0xe5, 0x42, 0x6f, 0xe8, 0x7e, 0x45, 0xc0, 0xe8, 0x18, 0x46, 0x98, 0xe4, 0x59,
+        0x62, 0x36, 0x4e, 0x34, 0x11, 0x61, 0x77, 0xdd, 0x22, 0x59,
+    ];
+
+    const BASE_POINT_A_Z: &'static [u8] = &[
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+    ];
+
+    const ORDER_POINT: &'static [u8] = &[
+        0x00, 0x00, 0x00, 0x00, 0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xe9, 0xae, 0x2e, 0xd0, 0x75, 0x77, 0x26, 0x5d,
+        0xff, 0x7f, 0x94, 0x45, 0x1e, 0x06, 0x1e, 0x16, 0x3c, 0x61,
+    ];
+}
+
+/// A trait that generalizes over a curve concept over a binary field
+/// GF(2^n).
+///
+/// General equation of a curve is:
+/// `y^2 + x*y = x^3 + a*x^2 + b`
+///
+/// Provides all the parametrizations through associated constants.
+///
+/// Associated constant slices must incorporate zero padding required by PUKCC.
+///
+/// Unlike [`Curve`], modulo reduction constant (CNS) is not a part of a
+/// parametrization. For polynomials GF(2^n) it is generated by the PUKCC
+/// itself before every service call.
+pub trait BinaryCurve {
+    /// Length of a reduction polynomial (bytes)
+    const MOD_LENGTH: super::c_abi::u2;
+    /// Length of the scalar (bytes)
+    const SCALAR_LENGTH: super::c_abi::u2;
+    /// Reduction polynomial `f(x)` in a binary representation
+    /// Length: MOD_LENGTH + 4
+    const MODULO_P: &'static [u8];
+    /// A parameter of a curve
+    /// Length: MOD_LENGTH + 4
+    const A_CURVE: &'static [u8];
+    /// B parameter of a curve
+    /// Length: MOD_LENGTH + 4
+    const B_CURVE: &'static [u8];
+    /// X coordinate of a base point (point of origin on a curve)
+    /// Length: MOD_LENGTH + 4
+    const BASE_POINT_A_X: &'static [u8];
+    /// Y coordinate of a base point (point of origin on a curve)
+    /// Length: MOD_LENGTH + 4
+    const BASE_POINT_A_Y: &'static [u8];
+    /// Z coordinate of a base point (point of origin on a curve)
+    /// It is equal to 1
+    /// Length: MOD_LENGTH + 4
+    const BASE_POINT_A_Z: &'static [u8];
+    /// Order point of the curve
+    /// Length: SCALAR_LENGTH + 4
+    const ORDER_POINT: &'static [u8];
+    /// Function that can be used during runtime to verify if a curve is
+    /// correctly defined.
+    ///
+    /// That is:
+    /// - lengths of slices are following the requirements
+    /// - slices are 4 aligned
+    fn verify_curve() -> Result<(), CurveVerficationFailure> {
+        if Self::MOD_LENGTH % 4 != 0 || Self::SCALAR_LENGTH % 4 != 0 {
+            return Err(CurveVerficationFailure::LengthsAreNotAlignedTo4);
+        }
+        let mod_length_slices = [
+            ("MODULO_P", Self::MODULO_P),
+            ("A_CURVE", Self::A_CURVE),
+            ("B_CURVE", Self::B_CURVE),
+            ("BASE_POINT_A_X", Self::BASE_POINT_A_X),
+            ("BASE_POINT_A_Y", Self::BASE_POINT_A_Y),
+            ("BASE_POINT_A_Z", Self::BASE_POINT_A_Z),
+        ];
+        for (faulty_slice, slice) in mod_length_slices {
+            if slice.len() != (Self::MOD_LENGTH + 4).into() {
+                return Err(CurveVerficationFailure::IncorrectSliceLength {
+                    faulty_slice,
+                    expected_length: (Self::MOD_LENGTH + 4).into(),
+                    actual_length: slice.len(),
+                });
+            }
+        }
+        if Self::ORDER_POINT.len() != (Self::SCALAR_LENGTH + 4).into() {
+            return Err(CurveVerficationFailure::IncorrectSliceLength {
+                faulty_slice: "ORDER_POINT",
+                expected_length: (Self::SCALAR_LENGTH + 4).into(),
+                actual_length: Self::ORDER_POINT.len(),
+            });
+        }
+        Ok(())
+    }
+}
+
 /// An error type representing failure modes for a
-/// [`Curve::verify_curve`] function
+/// [`Curve::verify_curve`] and [`BinaryCurve::verify_curve`] functions
 #[allow(missing_docs)]
 #[derive(Debug)]
 pub enum CurveVerficationFailure {
@@ -1,5 +1,6 @@
 #![allow(non_snake_case)]
 
+use crate::bus_clock::{self, BusClock};
 use crate::clock;
 use crate::gpio::v2::{AlternateE, AnyPin, Pin};
 use crate::gpio::*;
@@ -44,6 +45,14 @@ macro_rules! impl_tc_pinout {
             _pin: Pin<I, AlternateE>,
         }
 
+        impl<I: PinId> $Type<I> {
+            /// Release the pin
+            #[inline]
+            pub fn free(self) -> Pin<I, AlternateE> {
+                self._pin
+            }
+        }
+
         $(
             $( #[$attr] )?
             impl $Type<v2::$Id> {
@@ -76,7 +85,7 @@ impl_tc_pinout!(TC6Pinout: [(Pb3, PB03), (Pb17, PB17), (Pa31, PA31)]);
 impl_tc_pinout!(TC7Pinout: [(Pa21, PA21), (Pb23, PB23), (Pb1, PB01)]);
 
 macro_rules! pwm {
-    ($($TYPE:ident: ($TC:ident, $pinout:ident, $clock:ident, $apmask:ident, $apbits:ident, $wrapper:ident),)+) => {
+    ($($TYPE:ident: ($TC:ident, $pinout:ident, $clock:ident, $wrapper:ident),)+) => {
         $(
 
 pub struct $TYPE<I: PinId> {
@@ -84,7 +93,6 @@ pub struct $TYPE<I: PinId> {
     /// Used to calculate the period of the pwm.
     clock_freq: Hertz,
     tc: $TC,
-    #[allow(dead_code)]
     pinout: $pinout<I>,
 }
 
@@ -100,7 +108,7 @@ impl<I: PinId> $TYPE<I> {
         {
             let count = tc.count16();
             let params = TimerParams::new(freq, clock.freq().0);
-            mclk.$apmask.modify(|_, w| w.$apbits().set_bit());
+            $TC::enable_bus_clock(mclk);
             count.ctrla.write(|w| w.swrst().set_bit());
             while count.ctrla.read().bits() & 1 != 0 {}
             count.ctrla.modify(|_, w| w.enable().clear_bit());
@@ -132,11 +140,28 @@ impl<I: PinId> $TYPE<I> {
         }
     }
 
+    /// Reset the TC, stopping the output, disable its bus clock and release
+    /// it with its pinout
+    pub fn free(self) -> ($TC, $pinout<I>) {
+        let count = self.tc.count16();
+        count.ctrla.write(|w| w.swrst().set_bit());
+        while count.syncbusy.read().swrst().bit_is_set() {}
+        bus_clock::release::<$TC>();
+        (self.tc, self.pinout)
+    }
+
     pub fn get_period(&self) -> Hertz {
         let count = self.tc.count16();
-        let divisor = count.ctrla.read().prescaler().bits();
+        let divisor = TimerParams::prescaler_divider(count.ctrla.read().prescaler().bits());
         let top = count.cc[0].read().cc().bits();
-        Hertz(self.clock_freq.0 / divisor as u32 / (top + 1) as u32)
+        Hertz(self.clock_freq.0 / divisor / (top as u32 + 1))
+    }
+
+    /// Returns the number of duty cycle steps achieved at the current
+    /// period, which depends on the prescaler computed for that period.
+    pub fn get_resolution(&self) -> u32 {
+        let count = self.tc.count16();
+        count.cc[0].read().cc().bits() as u32 + 1
     }
 
     pub fn set_period<P>(&mut self, period: P)
@@ -198,25 +223,42 @@ impl<I: PinId> PwmPin for $TYPE<I> {
     }
 }
 
+#[cfg(feature = "ehal1")]
+impl<I: PinId> embedded_hal_1::pwm::ErrorType for $TYPE<I> {
+    type Error = core::convert::Infallible;
+}
+
+#[cfg(feature = "ehal1")]
+impl<I: PinId> embedded_hal_1::pwm::SetDutyCycle for $TYPE<I> {
+    fn max_duty_cycle(&self) -> u16 {
+        PwmPin::get_max_duty(self)
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+        PwmPin::set_duty(self, duty);
+        Ok(())
+    }
+}
+
 )+}}
 
 pwm! {
-    Pwm0: (TC0, TC0Pinout, Tc0Tc1Clock, apbamask, tc0_, Pwm0Wrapper),
-    Pwm1: (TC1, TC1Pinout, Tc0Tc1Clock, apbamask, tc1_, Pwm1Wrapper),
-    Pwm2: (TC2, TC2Pinout, Tc2Tc3Clock, apbbmask, tc2_, Pwm2Wrapper),
-    Pwm3: (TC3, TC3Pinout, Tc2Tc3Clock, apbbmask, tc3_, Pwm3Wrapper),
+    Pwm0: (TC0, TC0Pinout, Tc0Tc1Clock, Pwm0Wrapper),
+    Pwm1: (TC1, TC1Pinout, Tc0Tc1Clock, Pwm1Wrapper),
+    Pwm2: (TC2, TC2Pinout, Tc2Tc3Clock, Pwm2Wrapper),
+    Pwm3: (TC3, TC3Pinout, Tc2Tc3Clock, Pwm3Wrapper),
 }
 
 #[cfg(feature = "min-samd51j")]
 pwm! {
-    Pwm4: (TC4, TC4Pinout, Tc4Tc5Clock, apbcmask, tc4_, Pwm4Wrapper),
-    Pwm5: (TC5, TC5Pinout, Tc4Tc5Clock, apbcmask, tc5_, Pwm5Wrapper),
+    Pwm4: (TC4, TC4Pinout, Tc4Tc5Clock, Pwm4Wrapper),
+    Pwm5: (TC5, TC5Pinout, Tc4Tc5Clock, Pwm5Wrapper),
 }
 
 #[cfg(feature = "min-samd51n")]
 pwm! {
-    Pwm6: (TC6, TC6Pinout, Tc6Tc7Clock, apbdmask, tc6_, Pwm6Wrapper),
-    Pwm7: (TC7, TC7Pinout, Tc6Tc7Clock, apbdmask, tc7_, Pwm7Wrapper),
+    Pwm6: (TC6, TC6Pinout, Tc6Tc7Clock, Pwm6Wrapper),
+    Pwm7: (TC7, TC7Pinout, Tc6Tc7Clock, Pwm7Wrapper),
 }
 
 // Timer/Counter for Control Applications (TCCx)
@@ -233,6 +275,68 @@ pub enum Channel {
     _7,
 }
 
+/// Single channel of a TCC PWM, implementing the embedded-hal 1.0
+/// [`SetDutyCycle`](embedded_hal_1::pwm::SetDutyCycle) trait
+///
+/// The TCC counters are wider than the 16-bit duty cycles of the trait, so
+/// duty cycles are scaled to the period when it exceeds 16 bits.
+#[cfg(feature = "ehal1")]
+pub struct PwmChannel<'a, P> {
+    pwm: &'a mut P,
+    channel: Channel,
+}
+
+#[cfg(feature = "ehal1")]
+impl<'a, P: Pwm<Channel = Channel, Duty = u32>> PwmChannel<'a, P> {
+    /// Borrows `channel` of `pwm`
+    pub fn new(pwm: &'a mut P, channel: Channel) -> Self {
+        Self { pwm, channel }
+    }
+}
+
+#[cfg(feature = "ehal1")]
+impl<P> embedded_hal_1::pwm::ErrorType for PwmChannel<'_, P> {
+    type Error = core::convert::Infallible;
+}
+
+#[cfg(feature = "ehal1")]
+impl<P: Pwm<Channel = Channel, Duty = u32>> embedded_hal_1::pwm::SetDutyCycle
+    for PwmChannel<'_, P>
+{
+    fn max_duty_cycle(&self) -> u16 {
+        self.pwm.get_max_duty().min(u16::MAX as u32) as u16
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+        let top = self.pwm.get_max_duty();
+        let duty = if top <= u16::MAX as u32 {
+            duty as u32
+        } else {
+            (duty as u64 * top as u64 / u16::MAX as u64) as u32
+        };
+        self.pwm.set_duty(self.channel, duty);
+        Ok(())
+    }
+}
+
+/// Recoverable fault of a TCC, triggered by the match/capture event input
+/// of channel 0 (fault A) or channel 1 (fault B)
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum RecoverableFault {
+    A,
+    B,
+}
+
+/// Output behaviour while a recoverable fault is active
+pub use crate::pac::tcc0::fctrla::HALT_A as FaultHalt;
+
+/// Non-recoverable fault of a TCC, triggered by the event input 0 or 1
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum NonRecoverableFault {
+    Input0,
+    Input1,
+}
+
 /// This is a major syntax hack.
 ///
 /// The previous Pinout types were enums that took specific v1::Pin types. As a
@@ -262,6 +366,14 @@ macro_rules! impl_tcc_pinout {
             _pin: Pin<I, M>,
         }
 
+        impl<I: PinId, M: PinMode> $Type<I, M> {
+            /// Release the pin
+            #[inline]
+            pub fn free(self) -> Pin<I, M> {
+                self._pin
+            }
+        }
+
         $(
             $( #[$attr] )?
             impl $Type<v2::$Id, v2::$Mode> {
@@ -429,7 +541,7 @@ impl_tcc_pinout!(TCC4Pinout: [
 ]);
 
 macro_rules! pwm_tcc {
-    ($($TYPE:ident: ($TCC:ident, $pinout:ident, $clock:ident, $apmask:ident, $apbits:ident, $wrapper:ident),)+) => {
+    ($($TYPE:ident: ($TCC:ident, $pinout:ident, $clock:ident, $wrapper:ident),)+) => {
         $(
 
 pub struct $TYPE<I: PinId, M: PinMode> {
@@ -437,7 +549,6 @@ pub struct $TYPE<I: PinId, M: PinMode> {
     /// Used to calculate the period of the pwm.
     clock_freq: Hertz,
     tcc: $TCC,
-    #[allow(dead_code)]
     pinout: $pinout<I, M>,
 }
 
@@ -452,7 +563,7 @@ impl<I: PinId, M: PinMode> $TYPE<I, M> {
         let freq = freq.into();
         {
             let params = TimerParams::new(freq, clock.freq().0);
-            mclk.$apmask.modify(|_, w| w.$apbits().set_bit());
+            $TCC::enable_bus_clock(mclk);
             tcc.ctrla.write(|w| w.swrst().set_bit());
             while tcc.syncbusy.read().swrst().bit_is_set() {}
             tcc.ctrlbclr.write(|w| w.dir().set_bit() );
@@ -484,6 +595,174 @@ impl<I: PinId, M: PinMode> $TYPE<I, M> {
             pinout,
         }
     }
+
+    /// Reset the TCC, stopping the outputs, disable its bus clock and
+    /// release it with its pinout
+    pub fn free(self) -> ($TCC, $pinout<I, M>) {
+        self.tcc.ctrla.write(|w| w.swrst().set_bit());
+        while self.tcc.syncbusy.read().swrst().bit_is_set() {}
+        bus_clock::release::<$TCC>();
+        (self.tcc, self.pinout)
+    }
+
+    /// Inserts dead time between the complementary outputs `WO[n]` and
+    /// `WO[n + WO_NUM / 2]` of the channels set in `channels`, bit `n` for
+    /// channel `n` up to channel 3. The low side output is delayed by
+    /// `low_side` cycles, and the high side output by `high_side` cycles.
+    /// Not every TCC instance implements dead-time insertion, see the
+    /// datasheet. The TCC is briefly disabled, as the configuration is
+    /// enable-protected.
+    pub fn set_dead_time(&mut self, channels: u8, low_side: u8, high_side: u8) {
+        self.disable_protected(|tcc| {
+            tcc.wexctrl.modify(|r, w| unsafe {
+                // SAFETY: DTIEN0-3 are the bits 8 to 11
+                w.bits(r.bits() & !(0xf << 8) | ((channels & 0xf) as u32) << 8);
+                w.dtls().bits(low_side).dths().bits(high_side)
+            });
+        });
+    }
+
+    /// Overrides the outputs set in `outputs`, bit `n` for `WO[n]`, with the
+    /// corresponding bits in `values`. Outputs cleared in `outputs` follow
+    /// their waveform again.
+    pub fn set_pattern(&mut self, outputs: u8, values: u8) {
+        // SAFETY: PGE0-7 are the bits 0 to 7, PGV0-7 the bits 8 to 15
+        self.tcc
+            .patt
+            .write(|w| unsafe { w.bits(outputs as u16 | (values as u16) << 8) });
+        while self.tcc.syncbusy.read().patt().bit_is_set() {}
+    }
+
+    /// Enables a recoverable fault, triggered by the event routed to the
+    /// match/capture event input of channel 0 for fault A, or of channel 1
+    /// for fault B, such as the output of an analog comparator.
+    /// With `restart`, the counter restarts when the fault is released.
+    /// The TCC is briefly disabled, as the configuration is
+    /// enable-protected.
+    pub fn enable_recoverable_fault(
+        &mut self,
+        fault: RecoverableFault,
+        halt: FaultHalt,
+        restart: bool,
+    ) {
+        self.disable_protected(|tcc| match fault {
+            RecoverableFault::A => {
+                tcc.fctrla.write(|w| {
+                    w.src().enable();
+                    w.halt().variant(halt);
+                    w.restart().bit(restart)
+                });
+                tcc.evctrl.modify(|_, w| w.mcei0().set_bit());
+            }
+            RecoverableFault::B => {
+                // Both fault registers encode the halt action with the same
+                // values
+                tcc.fctrlb.write(|w| {
+                    w.src().enable();
+                    w.halt().bits(halt as u8);
+                    w.restart().bit(restart)
+                });
+                tcc.evctrl.modify(|_, w| w.mcei1().set_bit());
+            }
+        });
+    }
+
+    /// Enables the non-recoverable `fault`, triggered by the event routed
+    /// to its event input. On a fault, the outputs set in `outputs` are
+    /// driven to the levels set in `values` until the fault is cleared: bit
+    /// `n` of `outputs` selects `WO[n]`, and bit `n` of `values` is the
+    /// level of `WO[n]`, high when set. The TCC is briefly disabled, as the
+    /// configuration is enable-protected.
+    pub fn enable_non_recoverable_fault(
+        &mut self,
+        fault: NonRecoverableFault,
+        outputs: u8,
+        values: u8,
+    ) {
+        self.disable_protected(|tcc| {
+            tcc.drvctrl.modify(|r, w| unsafe {
+                // SAFETY: NRE0-7 are the bits 0 to 7, NRV0-7 the bits 8 to 15
+                w.bits(r.bits() & !0xffff | outputs as u32 | (values as u32) << 8)
+            });
+            tcc.evctrl.modify(|_, w| match fault {
+                NonRecoverableFault::Input0 => w.evact0().fault().tcei0().set_bit(),
+                NonRecoverableFault::Input1 => w.evact1().fault().tcei1().set_bit(),
+            });
+        });
+    }
+
+    /// Returns the fault states, bit 0 for fault A, bit 1 for fault B, and
+    /// bits 2 and 3 for the non-recoverable faults 0 and 1.
+    pub fn faults(&self) -> u8 {
+        (self.tcc.status.read().bits() >> 12) as u8 & 0xf
+    }
+
+    /// Clears the fault states set in `faults`, as returned by
+    /// [`faults`](Self::faults), once the fault inputs are released.
+    pub fn clear_faults(&mut self, faults: u8) {
+        // SAFETY: Writing a 1 clears a fault state
+        self.tcc
+            .status
+            .write(|w| unsafe { w.bits(((faults & 0xf) as u32) << 12) });
+    }
+
+    /// Sets the duty cycle of `channel` through its buffer register. The new
+    /// value is copied to the channel at the next update condition, at the
+    /// end of the period, unless updates are locked with
+    /// [`lock_update`](Self::lock_update), so the output never glitches.
+    pub fn set_duty_buffered(&mut self, channel: Channel, duty: u32) {
+        let ccb = self.tcc.ccbuf();
+        ccb[channel as usize].write(|w| unsafe { w.ccbuf().bits(duty) });
+    }
+
+    /// Sets the period, in counter cycles, through its buffer register. The
+    /// new value is copied to the counter top at the next update condition.
+    /// `cycles` should keep the prescaler selected for the current period.
+    pub fn set_top_buffered(&mut self, cycles: u32) {
+        self.tcc.perbuf().write(|w| unsafe { w.bits(cycles) });
+    }
+
+    /// Holds back the buffer registers written by
+    /// [`set_duty_buffered`](Self::set_duty_buffered) and
+    /// [`set_top_buffered`](Self::set_top_buffered), until
+    /// [`unlock_update`](Self::unlock_update) is called.
+    pub fn lock_update(&mut self) {
+        self.tcc.ctrlbset.write(|w| w.lupd().set_bit());
+        while self.tcc.syncbusy.read().ctrlb().bit_is_set() {}
+    }
+
+    /// Releases the buffer registers, which are all copied together at the
+    /// next update condition.
+    pub fn unlock_update(&mut self) {
+        self.tcc.ctrlbclr.write(|w| w.lupd().set_bit());
+        while self.tcc.syncbusy.read().ctrlb().bit_is_set() {}
+    }
+
+    /// Runs `f` with updates locked, so that every buffer register it writes
+    /// takes effect at the same period boundary.
+    pub fn update_atomically<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        self.lock_update();
+        let result = f(self);
+        self.unlock_update();
+        result
+    }
+
+    /// Returns the number of duty cycle steps achieved at the current
+    /// period, which depends on the prescaler computed for that period.
+    pub fn get_resolution(&self) -> u32 {
+        self.tcc.per().read().bits() + 1
+    }
+
+    fn disable_protected(&mut self, f: impl FnOnce(&$TCC)) {
+        let enabled = self.tcc.ctrla.read().enable().bit_is_set();
+        self.tcc.ctrla.modify(|_, w| w.enable().clear_bit());
+        while self.tcc.syncbusy.read().enable().bit_is_set() {}
+        f(&self.tcc);
+        if enabled {
+            self.tcc.ctrla.modify(|_, w| w.enable().set_bit());
+            while self.tcc.syncbusy.read().enable().bit_is_set() {}
+        }
+    }
 }
 
 impl<I: PinId, M: PinMode> Pwm for $TYPE<I, M> {
@@ -502,9 +781,9 @@ impl<I: PinId, M: PinMode> Pwm for $TYPE<I, M> {
     }
 
     fn get_period(&self) -> Self::Time {
-        let divisor = self.tcc.ctrla.read().prescaler().bits();
+        let divisor = TimerParams::prescaler_divider(self.tcc.ctrla.read().prescaler().bits());
         let top = self.tcc.per().read().bits();
-        Hertz(self.clock_freq.0 / divisor as u32 / (top + 1) as u32)
+        Hertz(self.clock_freq.0 / divisor / (top + 1))
     }
 
     fn get_duty(&self, channel: Self::Channel) -> Self::Duty {
@@ -556,13 +835,13 @@ impl<I: PinId, M: PinMode> Pwm for $TYPE<I, M> {
 }
 
 pwm_tcc! {
-    Tcc0Pwm: (TCC0, TCC0Pinout, Tcc0Tcc1Clock, apbbmask, tcc0_, TccPwm0Wrapper),
-    Tcc1Pwm: (TCC1, TCC1Pinout, Tcc0Tcc1Clock, apbbmask, tcc1_, TccPwm1Wrapper),
-    Tcc2Pwm: (TCC2, TCC2Pinout, Tcc2Tcc3Clock, apbcmask, tcc2_, TccPwm2Wrapper),
+    Tcc0Pwm: (TCC0, TCC0Pinout, Tcc0Tcc1Clock, TccPwm0Wrapper),
+    Tcc1Pwm: (TCC1, TCC1Pinout, Tcc0Tcc1Clock, TccPwm1Wrapper),
+    Tcc2Pwm: (TCC2, TCC2Pinout, Tcc2Tcc3Clock, TccPwm2Wrapper),
 }
 
 #[cfg(feature = "min-samd51j")]
 pwm_tcc! {
-    Tcc3Pwm: (TCC3, TCC3Pinout, Tcc2Tcc3Clock, apbcmask, tcc3_, TccPwm3Wrapper),
-    Tcc4Pwm: (TCC4, TCC4Pinout, Tcc4Clock,     apbdmask, tcc4_, TccPwm4Wrapper),
+    Tcc3Pwm: (TCC3, TCC3Pinout, Tcc2Tcc3Clock, TccPwm3Wrapper),
+    Tcc4Pwm: (TCC4, TCC4Pinout, Tcc4Clock,     TccPwm4Wrapper),
 }
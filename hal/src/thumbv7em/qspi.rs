@@ -1,16 +1,84 @@
 use crate::{
+    bus_clock::BusClock,
     gpio::v2::{AlternateH, AnyPin, Pin, PA08, PA09, PA10, PA11, PB10, PB11},
     pac::qspi::instrframe,
     pac::{MCLK, QSPI},
 };
 use core::marker::PhantomData;
 
+#[cfg(feature = "dma")]
+use crate::dmac::{
+    transfer::{RawBuffer, MAX_TRANSFER_LENGTH},
+    Beat, ChId, Channel, Ready, Transfer, TriggerAction, TriggerSource,
+};
+
+#[non_exhaustive]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum Error {
     /// The command you selected cannot be performed by this function
     CommandFunctionMismatch,
+    /// The requested number of dummy cycles cannot be generated
+    DummyCyclesOutOfRange,
+    /// The memory range does not fit in the memory-mapped window
+    OutOfRange,
+}
+
+/// Bus widths used by memory reads
+///
+/// The notation gives the number of data lines used for the instruction,
+/// the address and the data, respectively.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ReadMode {
+    /// 1-1-4: instruction and address on one line, data on four lines, using
+    /// [`Command::QuadRead`]
+    QuadOutput,
+    /// 1-4-4: instruction on one line, address and data on four lines, using
+    /// [`Command::QuadIoRead`].
+    ///
+    /// The mode bits following the address are sent as `0xFF`, which keeps
+    /// the device out of continuous read mode.
+    QuadIo,
+}
+
+impl ReadMode {
+    fn command(self) -> Command {
+        match self {
+            ReadMode::QuadOutput => Command::QuadRead,
+            ReadMode::QuadIo => Command::QuadIoRead,
+        }
+    }
+}
+
+/// Bus widths used by memory writes
+///
+/// The notation gives the number of data lines used for the instruction,
+/// the address and the data, respectively.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum WriteMode {
+    /// 1-1-4: instruction and address on one line, data on four lines, using
+    /// [`Command::QuadPageProgram`]
+    QuadInput,
+    /// 1-4-4: instruction on one line, address and data on four lines, using
+    /// [`Command::QuadIoWrite`]. This is the quad write command of PSRAM
+    /// devices such as the APS6404.
+    QuadIo,
 }
 
+impl WriteMode {
+    fn command(self) -> Command {
+        match self {
+            WriteMode::QuadInput => Command::QuadPageProgram,
+            WriteMode::QuadIo => Command::QuadIoWrite,
+        }
+    }
+}
+
+/// Maximum number of dummy cycles supported by the peripheral
+pub const MAX_DUMMY_CYCLES: u8 = 31;
+
+/// Size of the memory-mapped window, in bytes
+pub const XIP_WINDOW_SIZE: usize = 0x0100_0000;
+
 /// Qspi used for read/write of fixed-size octet buffers
 pub struct OneShot;
 /// Qspi is memory-mapped as read/execute
@@ -24,6 +92,9 @@ pub struct Qspi<MODE> {
     _io1: Pin<PA09, AlternateH>,
     _io2: Pin<PA10, AlternateH>,
     _io3: Pin<PA11, AlternateH>,
+    read_mode: ReadMode,
+    dummy_cycles: u8,
+    write_mode: WriteMode,
     _mode: PhantomData<MODE>,
 }
 
@@ -40,12 +111,9 @@ impl Qspi<OneShot> {
         _io2: impl AnyPin<Id = PA10>,
         _io3: impl AnyPin<Id = PA11>,
     ) -> Qspi<OneShot> {
-        mclk.apbcmask.modify(|_, w| w.qspi_().set_bit());
         // Enable the clocks for the qspi peripheral in single data rate mode.
-        mclk.ahbmask.modify(|_, w| {
-            w.qspi_().set_bit();
-            w.qspi_2x_().clear_bit()
-        });
+        QSPI::enable_bus_clock(mclk);
+        cortex_m::interrupt::free(|_| mclk.ahbmask.modify(|_, w| w.qspi_2x_().clear_bit()));
 
         let _sck = _sck.into().into_alternate();
         let _cs = _cs.into().into_alternate();
@@ -82,6 +150,9 @@ impl Qspi<OneShot> {
             _io1,
             _io2,
             _io3,
+            read_mode: ReadMode::QuadOutput,
+            dummy_cycles: 8,
+            write_mode: WriteMode::QuadInput,
             _mode: PhantomData,
         }
     }
@@ -181,50 +252,56 @@ impl Qspi<OneShot> {
         Ok(())
     }
 
-    /// Quad Fast Read a sequential block of memory to buf
-    /// Note: Hardcodes 8 dummy cycles
+    /// Select the command and number of dummy cycles used by
+    /// [`read_memory`](Self::read_memory) and [`into_xip`](Self::into_xip).
+    ///
+    /// Defaults to [`ReadMode::QuadOutput`] with 8 dummy cycles. Check your
+    /// device datasheet for the number of dummy cycles required at the
+    /// selected clock frequency. For [`ReadMode::QuadIo`], the two clock
+    /// cycles used by the mode bits are not included in `dummy_cycles`. PSRAM
+    /// devices such as the APS6404 expect 6 wait cycles after the address in
+    /// this mode, which is achieved with 4 dummy cycles.
+    pub fn set_read_mode(&mut self, mode: ReadMode, dummy_cycles: u8) -> Result<(), Error> {
+        if dummy_cycles > MAX_DUMMY_CYCLES {
+            return Err(Error::DummyCyclesOutOfRange);
+        }
+        self.read_mode = mode;
+        self.dummy_cycles = dummy_cycles;
+        Ok(())
+    }
+
+    /// Fast Read a sequential block of memory to buf, using the mode selected
+    /// by [`set_read_mode`](Self::set_read_mode)
     pub fn read_memory(&mut self, addr: u32, buf: &mut [u8]) {
-        let tfm = TransferMode {
-            quad_width: true,
-            address_enable: true,
-            data_enable: true,
-            instruction_enable: true,
-            dummy_cycles: 8,
-            ..TransferMode::default()
-        };
-        unsafe { self.run_read_instruction(Command::QuadRead, tfm, addr, buf, true) };
+        let tfm = self.read_transfer_mode();
+        unsafe { self.run_read_instruction(self.read_mode.command(), tfm, addr, buf, true) };
     }
 
-    /// Page Program a sequential block of memory to addr.
+    /// Select the command used by [`write_memory`](Self::write_memory).
+    ///
+    /// Defaults to [`WriteMode::QuadInput`].
+    pub fn set_write_mode(&mut self, mode: WriteMode) {
+        self.write_mode = mode;
+    }
+
+    /// Page Program a sequential block of memory to addr, using the mode
+    /// selected by [`set_write_mode`](Self::set_write_mode).
     ///
     /// Note more than page size bytes are sent to the device, some bytes will
     /// be discarded. Check your device for specific handling.
     pub fn write_memory(&mut self, addr: u32, buf: &[u8]) {
-        let tfm = TransferMode {
-            quad_width: true,
-            address_enable: true,
-            data_enable: true,
-            instruction_enable: true,
-            ..TransferMode::default()
-        };
-        unsafe { self.run_write_instruction(Command::QuadPageProgram, tfm, addr, buf) };
+        let tfm = self.write_transfer_mode();
+        unsafe { self.run_write_instruction(self.write_mode.command(), tfm, addr, buf) };
     }
 
     /// Latches the peripheral in a read/execute state, so it can be used to
     /// read or execute directly from flash.
     ///
-    /// Note: Hardcodes 8 dummy cycles.
+    /// Reads use the mode selected by [`set_read_mode`](Self::set_read_mode).
     pub fn into_xip(self) -> Qspi<XIP> {
-        let tfm = TransferMode {
-            quad_width: true,
-            address_enable: true,
-            data_enable: true,
-            instruction_enable: true,
-            dummy_cycles: 8,
-            ..TransferMode::default()
-        };
+        let tfm = self.read_transfer_mode();
         unsafe {
-            self.run_read_instruction(Command::QuadRead, tfm, 0, &mut [], false);
+            self.run_read_instruction(self.read_mode.command(), tfm, 0, &mut [], false);
         }
 
         Qspi::<XIP> {
@@ -235,13 +312,153 @@ impl Qspi<OneShot> {
             _io1: self._io1,
             _io2: self._io2,
             _io3: self._io3,
+            read_mode: self.read_mode,
+            dummy_cycles: self.dummy_cycles,
+            write_mode: self.write_mode,
             _mode: PhantomData,
         }
     }
 }
 
+#[cfg(feature = "dma")]
+/// DMA transfers, available in OneShot mode
+impl Qspi<OneShot> {
+    /// Read a sequential block of memory to buf, using a DMA channel.
+    ///
+    /// The memory is read with the mode selected by
+    /// [`set_read_mode`](Self::set_read_mode). The beat type `T` sets the
+    /// width of every DMA access; use `u32` for the best throughput. This
+    /// method blocks until the transfer has completed.
+    ///
+    /// Returns [`Error::OutOfRange`], along with the unused channel, if the
+    /// memory read doesn't fit in [`XIP_WINDOW_SIZE`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `addr` is not aligned to the size of `T`.
+    pub fn read_memory_dma<Id: ChId, T: Beat>(
+        &mut self,
+        channel: Channel<Id, Ready>,
+        addr: u32,
+        buf: &mut [T],
+    ) -> Result<Channel<Id, Ready>, (Error, Channel<Id, Ready>)> {
+        assert_eq!(addr as usize % core::mem::size_of::<T>(), 0);
+        if !fits_in_window(addr, core::mem::size_of_val(buf)) {
+            return Err((Error::OutOfRange, channel));
+        }
+
+        let tfm = self.read_transfer_mode();
+        unsafe {
+            self.run_read_instruction(self.read_mode.command(), tfm, addr, &mut [], false);
+        }
+        let channel = dma_copy(
+            channel,
+            (QSPI_AHB + addr) as *mut T,
+            buf.as_mut_ptr(),
+            buf.len(),
+        );
+        unsafe { self.finalize() };
+        Ok(channel)
+    }
+
+    /// Write a sequential block of memory to addr, using a DMA channel.
+    ///
+    /// The memory is written with the mode selected by
+    /// [`set_write_mode`](Self::set_write_mode). The beat type `T` sets the
+    /// width of every DMA access; use `u32` for the best throughput. This
+    /// method blocks until the transfer has completed.
+    ///
+    /// Returns [`Error::OutOfRange`], along with the unused channel, if the
+    /// memory written doesn't fit in [`XIP_WINDOW_SIZE`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `addr` is not aligned to the size of `T`.
+    pub fn write_memory_dma<Id: ChId, T: Beat>(
+        &mut self,
+        channel: Channel<Id, Ready>,
+        addr: u32,
+        buf: &[T],
+    ) -> Result<Channel<Id, Ready>, (Error, Channel<Id, Ready>)> {
+        assert_eq!(addr as usize % core::mem::size_of::<T>(), 0);
+        if !fits_in_window(addr, core::mem::size_of_val(buf)) {
+            return Err((Error::OutOfRange, channel));
+        }
+
+        let tfm = self.write_transfer_mode();
+        unsafe { self.start_write_instruction(self.write_mode.command(), tfm, addr) };
+        let channel = dma_copy(
+            channel,
+            buf.as_ptr() as *mut T,
+            (QSPI_AHB + addr) as *mut T,
+            buf.len(),
+        );
+        unsafe { self.finalize() };
+        Ok(channel)
+    }
+}
+
+/// Copy `len` beats from `source` to `destination` through software
+/// triggered block transfers, and wait for completion
+#[cfg(feature = "dma")]
+fn dma_copy<Id: ChId, T: Beat>(
+    mut channel: Channel<Id, Ready>,
+    source: *mut T,
+    destination: *mut T,
+    len: usize,
+) -> Channel<Id, Ready> {
+    let mut done = 0;
+    while done < len {
+        let beats = core::cmp::min(len - done, MAX_TRANSFER_LENGTH);
+        // SAFETY: Both pointers are valid for `len` beats, and the caller
+        // borrows the underlying memory until the transfer has completed,
+        // since this function waits for it.
+        let (chan, _, _) = unsafe {
+            let src = RawBuffer::slice(source.add(done), beats);
+            let dst = RawBuffer::slice(destination.add(done), beats);
+            Transfer::new_unchecked(channel, src, dst, false)
+        }
+        .begin(TriggerSource::DISABLE, TriggerAction::BLOCK)
+        .wait();
+        channel = chan;
+        done += beats;
+    }
+    channel
+}
+
+/// Check that `len` bytes starting at `addr` fit in the memory-mapped window
+#[inline]
+fn fits_in_window(addr: u32, len: usize) -> bool {
+    let addr = addr as usize;
+    addr <= XIP_WINDOW_SIZE && len <= XIP_WINDOW_SIZE - addr
+}
+
 /// Operations available in XIP mode
 impl Qspi<XIP> {
+    /// Borrow `len` bytes of the external memory, starting at `addr`, as a
+    /// slice.
+    ///
+    /// Accesses to the slice are translated to memory reads by the
+    /// peripheral, so the memory can be read with plain loads or
+    /// `core::ptr::read`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range does not fit in the memory-mapped window.
+    pub fn as_slice(&self, addr: u32, len: usize) -> &[u8] {
+        assert!(fits_in_window(addr, len));
+        let addr = addr as usize;
+        // SAFETY: The range lies within the memory-mapped window. The
+        // external memory cannot be modified while the peripheral is in XIP
+        // mode, as the write commands are only available in OneShot mode.
+        unsafe { core::slice::from_raw_parts((QSPI_AHB as usize + addr) as *const u8, len) }
+    }
+
+    /// Read a sequential block of memory to buf
+    pub fn read_memory(&self, addr: u32, buf: &mut [u8]) {
+        buf.copy_from_slice(self.as_slice(addr, buf.len()));
+    }
+
     /// Latches the peripheral in a read/execute state, so it can be used to
     /// read or execute directly from flash.
     pub fn into_oneshot(self) -> Qspi<OneShot> {
@@ -255,6 +472,9 @@ impl Qspi<XIP> {
             _io1: self._io1,
             _io2: self._io2,
             _io3: self._io3,
+            read_mode: self.read_mode,
+            dummy_cycles: self.dummy_cycles,
+            write_mode: self.write_mode,
             _mode: PhantomData,
         }
     }
@@ -262,6 +482,29 @@ impl Qspi<XIP> {
 
 // (Mostly internal) methods available in any mode.
 impl<MODE> Qspi<MODE> {
+    fn read_transfer_mode(&self) -> TransferMode {
+        TransferMode {
+            quad_width: true,
+            quad_address: self.read_mode == ReadMode::QuadIo,
+            address_enable: true,
+            data_enable: true,
+            opcode_enable: self.read_mode == ReadMode::QuadIo,
+            instruction_enable: true,
+            dummy_cycles: self.dummy_cycles,
+        }
+    }
+
+    fn write_transfer_mode(&self) -> TransferMode {
+        TransferMode {
+            quad_width: true,
+            quad_address: self.write_mode == WriteMode::QuadIo,
+            address_enable: true,
+            data_enable: true,
+            instruction_enable: true,
+            ..TransferMode::default()
+        }
+    }
+
     unsafe fn finalize(&self) {
         self.qspi.ctrla.write(|w| {
             w.enable().set_bit();
@@ -281,6 +524,16 @@ impl<MODE> Qspi<MODE> {
         addr: u32,
         buf: &[u8],
     ) {
+        self.start_write_instruction(command, tfm, addr);
+
+        if !buf.is_empty() {
+            core::ptr::copy(buf.as_ptr(), (QSPI_AHB + addr) as *mut u8, buf.len());
+        }
+
+        self.finalize();
+    }
+
+    unsafe fn start_write_instruction(&self, command: Command, tfm: TransferMode, addr: u32) {
         if command == Command::EraseSector || command == Command::EraseBlock {
             self.qspi.instraddr.write(|w| w.addr().bits(addr));
         }
@@ -290,7 +543,7 @@ impl<MODE> Qspi<MODE> {
         self.qspi.instrframe.write(|w| {
             tfm.instrframe(
                 w,
-                if command == Command::QuadPageProgram {
+                if command == Command::QuadPageProgram || command == Command::QuadIoWrite {
                     instrframe::TFRTYPE_A::WRITEMEMORY
                 } else {
                     instrframe::TFRTYPE_A::WRITE
@@ -298,12 +551,6 @@ impl<MODE> Qspi<MODE> {
             )
         });
         self.qspi.instrframe.read().bits();
-
-        if !buf.is_empty() {
-            core::ptr::copy(buf.as_ptr(), (QSPI_AHB + addr) as *mut u8, buf.len());
-        }
-
-        self.finalize();
     }
 
     unsafe fn run_read_instruction(
@@ -314,13 +561,15 @@ impl<MODE> Qspi<MODE> {
         buf: &mut [u8],
         finalize: bool,
     ) {
-        self.qspi
-            .instrctrl
-            .modify(|_, w| w.instr().bits(command.bits()));
+        self.qspi.instrctrl.modify(|_, w| {
+            w.instr().bits(command.bits());
+            // Mode bits that keep the device out of continuous read mode
+            w.optcode().bits(0xFF)
+        });
         self.qspi.instrframe.write(|w| {
             tfm.instrframe(
                 w,
-                if command == Command::QuadRead {
+                if command == Command::QuadRead || command == Command::QuadIoRead {
                     instrframe::TFRTYPE_A::READMEMORY
                 } else {
                     instrframe::TFRTYPE_A::READ
@@ -358,6 +607,7 @@ impl<MODE> Qspi<MODE> {
 #[derive(Default, Debug, Copy, Clone)]
 struct TransferMode {
     quad_width: bool,
+    quad_address: bool,
     data_enable: bool,
     opcode_enable: bool,
     address_enable: bool,
@@ -371,7 +621,9 @@ impl TransferMode {
         instrframe: &mut instrframe::W,
         tfrtype: instrframe::TFRTYPE_A,
     ) -> &mut instrframe::W {
-        if self.quad_width {
+        if self.quad_address {
+            instrframe.width().quad_io();
+        } else if self.quad_width {
             instrframe.width().quad_output();
         } else {
             instrframe.width().single_bit_spi();
@@ -381,7 +633,8 @@ impl TransferMode {
             instrframe.dataen().set_bit();
         }
         if self.opcode_enable {
-            instrframe.dataen().set_bit();
+            instrframe.optcodeen().set_bit();
+            instrframe.optcodelen()._8bits();
         }
         if self.address_enable {
             instrframe.addren().set_bit();
@@ -394,7 +647,6 @@ impl TransferMode {
             instrframe.dummylen().bits(self.dummy_cycles);
         }
         instrframe.addrlen()._24bits();
-        instrframe.optcodeen().clear_bit();
         instrframe.tfrtype().variant(tfrtype);
         instrframe
     }
@@ -405,9 +657,11 @@ impl TransferMode {
 pub enum Command {
     Read = 0x03,
     QuadRead = 0x6B,
+    QuadIoRead = 0xEB,
     ReadId = 0x9F,
     PageProgram = 0x02,
     QuadPageProgram = 0x32,
+    QuadIoWrite = 0x38,
     ReadStatus = 0x05,
     ReadStatus2 = 0x35,
     WriteStatus = 0x01,
@@ -1,20 +1,39 @@
 /// ResetCause represents the reason the MCU was reset.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ResetCause {
+    /// No reset cause flag is set
     Unknown,
+    /// Power-on reset
     POR,
+    /// Brown-out of the core supply
     BOD12,
+    /// Brown-out of the I/O supply
     BOD33,
+    /// NVM controller reset
     NVM,
+    /// External reset, through the RESET pin
     External,
+    /// Watchdog timeout
     Watchdog,
+    /// System reset request, such as `SCB::sys_reset`
     System,
+    /// Wake up from the backup or hibernate sleep modes
     Backup,
 }
 
+impl ResetCause {
+    /// Returns whether the chip lost power, or its supply dropped below the
+    /// brown-out threshold, so the RAM content is lost.
+    pub fn is_power_cycle(&self) -> bool {
+        matches!(self, Self::POR | Self::BOD12 | Self::BOD33)
+    }
+}
+
 impl From<u8> for ResetCause {
+    /// Decodes the `RCAUSE` register. A power-on reset may set the brown-out
+    /// flags too, so the flag of the lowest bit gives the cause.
     fn from(rcause_val: u8) -> ResetCause {
-        match rcause_val {
+        match rcause_val & rcause_val.wrapping_neg() {
             1 => Self::POR,
             2 => Self::BOD12,
             4 => Self::BOD33,
@@ -32,3 +51,17 @@ impl From<u8> for ResetCause {
 pub fn reset_cause(rstc: &crate::pac::RSTC) -> ResetCause {
     ResetCause::from(rstc.rcause.read().bits())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        assert_eq!(ResetCause::from(0b111), ResetCause::POR);
+        assert_eq!(ResetCause::from(0b110), ResetCause::BOD12);
+        assert_eq!(ResetCause::from(32), ResetCause::Watchdog);
+        assert_eq!(ResetCause::from(0), ResetCause::Unknown);
+        assert!(!ResetCause::Watchdog.is_power_cycle());
+    }
+}
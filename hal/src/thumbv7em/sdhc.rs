@@ -0,0 +1,742 @@
+//! # SDHC - SD/MMC Host Controller
+//!
+//! The SDHC peripherals drive SD memory cards over the 4-bit SD bus. This
+//! module initializes SD version 2 cards, both standard capacity (SDSC) and
+//! high capacity (SDHC/SDXC), and performs single and multiple block reads
+//! and writes. Version 1 cards are supported as well, but MMC cards are not.
+//!
+//! Data is moved through the buffer data port by the CPU. The controller
+//! generates the CMD12 that ends multiple block transfers by itself.
+//!
+//! The card detect and write protect pins are not used. The controller is
+//! told that a card is always present, so [`Sdhc::init_card`] simply fails if
+//! there is none.
+//!
+//! When the `sdmmc` feature is enabled, [`Sdhc`] implements the
+//! `embedded_sdmmc::BlockDevice` trait, so it can be used with the
+//! `embedded-sdmmc` FAT filesystem.
+//!
+//! ```no_run
+//! use atsamd_hal::sdhc::{Sdhc, Sdhc0Pins, BLOCK_SIZE};
+//!
+//! let gclk0 = clocks.gclk0();
+//! let sdhc_clock = clocks.sdhc0(&gclk0).unwrap();
+//! let pins = Sdhc0Pins::new(
+//!     pins.pa08, pins.pb11, pins.pa09, pins.pa10, pins.pa11, pins.pb10,
+//! );
+//!
+//! let mut sdhc = Sdhc::new(peripherals.SDHC0, pins, &mut peripherals.MCLK, &sdhc_clock);
+//! let card = sdhc.init_card().unwrap();
+//!
+//! let mut blocks = [[0; BLOCK_SIZE]; 2];
+//! sdhc.read_blocks(0, &mut blocks).unwrap();
+//! ```
+
+use crate::{
+    bus_clock::BusClock,
+    gpio::v2::{AlternateI, AnyPin, Pin, PA08, PA09, PA10, PA11, PB10, PB11},
+    pac::{sdhc0::RegisterBlock, MCLK, SDHC0},
+    thumbv7em::clock::Sdhc0Clock,
+    time::Hertz,
+    typelevel::Sealed,
+};
+#[cfg(feature = "min-samd51n")]
+use crate::{
+    gpio::v2::{PA20, PA21, PB18, PB19, PB20, PB21},
+    pac::SDHC1,
+    thumbv7em::clock::Sdhc1Clock,
+};
+use core::ops::Deref;
+
+#[cfg(feature = "sdmmc")]
+use embedded_sdmmc::{Block, BlockCount, BlockDevice, BlockIdx};
+
+/// Size of a block, in bytes
+pub const BLOCK_SIZE: usize = 512;
+
+/// SD clock frequency used during card identification
+const IDENTIFICATION_FREQ: u32 = 400_000;
+
+/// SD clock frequency used in default speed mode
+const DEFAULT_SPEED_FREQ: u32 = 25_000_000;
+
+/// Number of ACMD41 attempts before giving up on card initialization.
+///
+/// At 400 kHz, every attempt takes a little more than half a millisecond,
+/// which adds up to the one second allowed by the specification.
+const INIT_ATTEMPTS: u32 = 2000;
+
+/// Error bits of the R1 card status
+const R1_ERRORS: u32 = 0xFDF9_8008;
+
+/// Errors reported by the SDHC driver
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Error {
+    /// The card did not respond to a command
+    CommandTimeout,
+    /// The CRC of a command response is wrong
+    CommandCrc,
+    /// The end bit of a command response is wrong
+    CommandEndBit,
+    /// The index of a command response is wrong
+    CommandIndex,
+    /// The card did not send or accept data in time
+    DataTimeout,
+    /// The CRC of a data block is wrong
+    DataCrc,
+    /// The end bit of a data block is wrong
+    DataEndBit,
+    /// The automatic CMD12 failed
+    AutoCmd,
+    /// The card draws too much current
+    CurrentLimit,
+    /// The card status reported an error, see the SD specification for the
+    /// meaning of the individual bits
+    CardStatus(u32),
+    /// The card did not leave its busy state during initialization
+    InitTimeout,
+    /// The card does not support the 3.3 V supply or is not an SD card
+    UnsupportedCard,
+    /// No card has been initialized
+    NoCard,
+    /// The SDHC clock is too fast to be divided down to the SD clock
+    /// frequency
+    ClockDivider,
+}
+
+/// Information about an initialized card
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct CardInfo {
+    /// The card version is 2.0 or later
+    pub version2: bool,
+    /// The card is block addressed (SDHC or SDXC)
+    pub high_capacity: bool,
+    /// Relative card address
+    pub rca: u16,
+    /// Capacity of the card, in blocks of [`BLOCK_SIZE`] bytes
+    pub num_blocks: u32,
+}
+
+/// SDHC peripheral instance
+pub trait Instance: Sealed + Deref<Target = RegisterBlock> {
+    /// Pins used by this instance
+    type Pins;
+    /// Generic clock feeding this instance
+    type Clock;
+
+    #[doc(hidden)]
+    fn clock_freq(clock: &Self::Clock) -> Hertz;
+    #[doc(hidden)]
+    fn enable_mclk(mclk: &mut MCLK);
+    #[doc(hidden)]
+    fn disable_mclk(mclk: &mut MCLK);
+}
+
+macro_rules! sdhc_instance {
+    (
+        $(#[$attr:meta])*
+        $Sdhc:ident, $Pins:ident, $Clock:ident,
+        $cmd:ident, $clk:ident, $dat0:ident, $dat1:ident, $dat2:ident, $dat3:ident
+    ) => {
+        $(#[$attr])*
+        #[doc = concat!("Pins used by [`", stringify!($Sdhc), "`]")]
+        pub struct $Pins {
+            _cmd: Pin<$cmd, AlternateI>,
+            _clk: Pin<$clk, AlternateI>,
+            _dat0: Pin<$dat0, AlternateI>,
+            _dat1: Pin<$dat1, AlternateI>,
+            _dat2: Pin<$dat2, AlternateI>,
+            _dat3: Pin<$dat3, AlternateI>,
+        }
+
+        $(#[$attr])*
+        impl $Pins {
+            /// Configure the pins for the SD bus
+            #[inline]
+            pub fn new(
+                cmd: impl AnyPin<Id = $cmd>,
+                clk: impl AnyPin<Id = $clk>,
+                dat0: impl AnyPin<Id = $dat0>,
+                dat1: impl AnyPin<Id = $dat1>,
+                dat2: impl AnyPin<Id = $dat2>,
+                dat3: impl AnyPin<Id = $dat3>,
+            ) -> Self {
+                Self {
+                    _cmd: cmd.into().into_alternate(),
+                    _clk: clk.into().into_alternate(),
+                    _dat0: dat0.into().into_alternate(),
+                    _dat1: dat1.into().into_alternate(),
+                    _dat2: dat2.into().into_alternate(),
+                    _dat3: dat3.into().into_alternate(),
+                }
+            }
+        }
+
+        $(#[$attr])*
+        impl Sealed for $Sdhc {}
+
+        $(#[$attr])*
+        impl Instance for $Sdhc {
+            type Pins = $Pins;
+            type Clock = $Clock;
+
+            #[inline]
+            fn clock_freq(clock: &Self::Clock) -> Hertz {
+                clock.freq()
+            }
+
+            #[inline]
+            fn enable_mclk(mclk: &mut MCLK) {
+                $Sdhc::enable_bus_clock(mclk);
+            }
+
+            #[inline]
+            fn disable_mclk(mclk: &mut MCLK) {
+                $Sdhc::disable_bus_clock(mclk);
+            }
+        }
+    };
+}
+
+sdhc_instance!(SDHC0, Sdhc0Pins, Sdhc0Clock, PA08, PB11, PA09, PA10, PA11, PB10);
+sdhc_instance!(
+    #[cfg(feature = "min-samd51n")]
+    SDHC1,
+    Sdhc1Pins,
+    Sdhc1Clock,
+    PA20,
+    PA21,
+    PB18,
+    PB19,
+    PB20,
+    PB21
+);
+
+/// Response expected from a command
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Response {
+    None,
+    R1,
+    R1b,
+    R2,
+    R3,
+    R6,
+    R7,
+}
+
+/// Direction of a data transfer
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Read,
+    Write,
+}
+
+/// SD/MMC host controller
+pub struct Sdhc<I: Instance> {
+    sdhc: I,
+    pins: I::Pins,
+    base_freq: u32,
+    card: Option<CardInfo>,
+}
+
+impl<I: Instance> Sdhc<I> {
+    /// Enable the peripheral and power the SD bus
+    ///
+    /// The card must be initialized with [`init_card`](Self::init_card)
+    /// before blocks can be transferred.
+    #[inline]
+    pub fn new(sdhc: I, pins: I::Pins, mclk: &mut MCLK, clock: &I::Clock) -> Self {
+        I::enable_mclk(mclk);
+
+        sdhc.srr.write(|w| w.swrstall().reset());
+        while sdhc.srr.read().swrstall().bit_is_set() {}
+
+        // SAFETY: 0xE selects the longest data timeout
+        sdhc.tcr.write(|w| unsafe { w.dtcval().bits(0xE) });
+        sdhc.pcr.write(|w| {
+            w.sdbvsel()._3v3();
+            w.sdbpwr().on()
+        });
+        // The card detect pin is not used, so report the card as inserted
+        sdhc.hc1r().write(|w| {
+            w.carddsel().test();
+            w.carddtl().yes();
+            w.dw()._1bit()
+        });
+
+        // Latch all status flags, without generating interrupts
+        // SAFETY: Every bit of the status enable registers is valid
+        sdhc.nister().write(|w| unsafe { w.bits(0xFFFF) });
+        sdhc.eister().write(|w| unsafe { w.bits(0xFFFF) });
+
+        Self {
+            sdhc,
+            pins,
+            base_freq: I::clock_freq(clock).0,
+            card: None,
+        }
+    }
+
+    /// Power down the SD bus and disable the peripheral
+    #[inline]
+    pub fn free(self, mclk: &mut MCLK) -> (I, I::Pins) {
+        self.sdhc.ccr.write(|w| w.sdclken().disable());
+        self.sdhc.pcr.write(|w| w.sdbpwr().off());
+        I::disable_mclk(mclk);
+        (self.sdhc, self.pins)
+    }
+
+    /// Information about the initialized card, if any
+    #[inline]
+    pub fn card(&self) -> Option<CardInfo> {
+        self.card
+    }
+
+    /// Identify and initialize the card.
+    ///
+    /// Once initialized, the card is selected, the bus is switched to 4-bit
+    /// mode and the clock is raised to 25 MHz. Returns
+    /// [`Error::ClockDivider`] if the SDHC clock is faster than 818.4 MHz,
+    /// which the 10-bit clock divider can't bring down to 400 kHz.
+    pub fn init_card(&mut self) -> Result<CardInfo, Error> {
+        self.card = None;
+        self.set_clock(IDENTIFICATION_FREQ)?;
+        self.sdhc.hc1r().modify(|_, w| w.dw()._1bit());
+
+        // The card needs at least 74 clock cycles after power up
+        cortex_m::asm::delay(40_000);
+
+        self.command(0, 0, Response::None)?;
+
+        // Cards that do not answer CMD8 are version 1 cards
+        let version2 = match self.command(8, 0x1AA, Response::R7) {
+            Ok(()) => {
+                if self.response() & 0xFFF != 0x1AA {
+                    return Err(Error::UnsupportedCard);
+                }
+                true
+            }
+            Err(Error::CommandTimeout) => false,
+            Err(e) => return Err(e),
+        };
+
+        // Voltage window 2.7 to 3.6 V, and host capacity support for
+        // version 2 cards
+        let arg = if version2 { 0x40FF_8000 } else { 0x00FF_8000 };
+        let mut ocr = 0;
+        for _ in 0..INIT_ATTEMPTS {
+            self.command(55, 0, Response::R1)?;
+            self.command(41, arg, Response::R3)?;
+            ocr = self.response();
+            if ocr & (1 << 31) != 0 {
+                break;
+            }
+        }
+        if ocr & (1 << 31) == 0 {
+            return Err(Error::InitTimeout);
+        }
+        if ocr & 0x00FF_8000 == 0 {
+            return Err(Error::UnsupportedCard);
+        }
+        let high_capacity = ocr & (1 << 30) != 0;
+
+        self.command(2, 0, Response::R2)?;
+        self.command(3, 0, Response::R6)?;
+        let rca = (self.response() >> 16) as u16;
+        let rca_arg = (rca as u32) << 16;
+
+        self.command(9, rca_arg, Response::R2)?;
+        let num_blocks = csd_num_blocks(self.response_long()).ok_or(Error::UnsupportedCard)?;
+
+        self.command(7, rca_arg, Response::R1b)?;
+        self.check_card_status()?;
+
+        if !high_capacity {
+            self.command(16, BLOCK_SIZE as u32, Response::R1)?;
+            self.check_card_status()?;
+        }
+
+        // Switch the card, then the controller, to the 4-bit bus
+        self.command(55, rca_arg, Response::R1)?;
+        self.command(6, 0b10, Response::R1)?;
+        self.check_card_status()?;
+        self.sdhc.hc1r().modify(|_, w| w.dw()._4bit());
+
+        self.set_clock(DEFAULT_SPEED_FREQ)?;
+
+        let card = CardInfo {
+            version2,
+            high_capacity,
+            rca,
+            num_blocks,
+        };
+        self.card = Some(card);
+        Ok(card)
+    }
+
+    /// Read consecutive blocks, starting with block `start_block`
+    #[inline]
+    pub fn read_blocks(
+        &self,
+        start_block: u32,
+        blocks: &mut [[u8; BLOCK_SIZE]],
+    ) -> Result<(), Error> {
+        self.read_blocks_iter(start_block, blocks.len(), blocks.iter_mut())
+    }
+
+    /// Write consecutive blocks, starting with block `start_block`
+    #[inline]
+    pub fn write_blocks(&self, start_block: u32, blocks: &[[u8; BLOCK_SIZE]]) -> Result<(), Error> {
+        self.write_blocks_iter(start_block, blocks.len(), blocks.iter())
+    }
+
+    fn read_blocks_iter<'a>(
+        &self,
+        start_block: u32,
+        count: usize,
+        mut blocks: impl Iterator<Item = &'a mut [u8; BLOCK_SIZE]>,
+    ) -> Result<(), Error> {
+        let card = self.card.ok_or(Error::NoCard)?;
+        let mut address = start_block;
+        let mut remaining = count;
+
+        while remaining > 0 {
+            let chunk = core::cmp::min(remaining, u16::MAX as usize);
+            let (index, multiple) = if chunk > 1 { (18, true) } else { (17, false) };
+            self.start_data_command(card, index, address, chunk, Direction::Read, multiple)?;
+
+            for block in blocks.by_ref().take(chunk) {
+                self.wait_status(|s| s.brdrdy().bit_is_set())?;
+                self.sdhc.nistr().write(|w| w.brdrdy().yes());
+                for bytes in block.chunks_exact_mut(4) {
+                    bytes.copy_from_slice(&self.sdhc.bdpr.read().bits().to_le_bytes());
+                }
+            }
+            self.end_data_command()?;
+
+            address += chunk as u32;
+            remaining -= chunk;
+        }
+        Ok(())
+    }
+
+    fn write_blocks_iter<'a>(
+        &self,
+        start_block: u32,
+        count: usize,
+        mut blocks: impl Iterator<Item = &'a [u8; BLOCK_SIZE]>,
+    ) -> Result<(), Error> {
+        let card = self.card.ok_or(Error::NoCard)?;
+        let mut address = start_block;
+        let mut remaining = count;
+
+        while remaining > 0 {
+            let chunk = core::cmp::min(remaining, u16::MAX as usize);
+            let (index, multiple) = if chunk > 1 { (25, true) } else { (24, false) };
+            self.start_data_command(card, index, address, chunk, Direction::Write, multiple)?;
+
+            for block in blocks.by_ref().take(chunk) {
+                self.wait_status(|s| s.bwrrdy().bit_is_set())?;
+                self.sdhc.nistr().write(|w| w.bwrrdy().yes());
+                for bytes in block.chunks_exact(4) {
+                    let word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                    // SAFETY: Any 32-bit value is valid data
+                    self.sdhc.bdpr.write(|w| unsafe { w.bits(word) });
+                }
+            }
+            self.end_data_command()?;
+
+            address += chunk as u32;
+            remaining -= chunk;
+        }
+        Ok(())
+    }
+
+    /// Configure the transfer, then issue the data command
+    fn start_data_command(
+        &self,
+        card: CardInfo,
+        index: u8,
+        block: u32,
+        count: usize,
+        direction: Direction,
+        multiple: bool,
+    ) -> Result<(), Error> {
+        let arg = if card.high_capacity {
+            block
+        } else {
+            block * BLOCK_SIZE as u32
+        };
+
+        // SAFETY: Block size and count fit their fields
+        self.sdhc
+            .bsr
+            .write(|w| unsafe { w.blocksize().bits(BLOCK_SIZE as u16) });
+        self.sdhc
+            .bcr
+            .write(|w| unsafe { w.bcnt().bits(count as u16) });
+        self.sdhc.tmr.write(|w| {
+            w.dmaen().disable();
+            w.bcen().enable();
+            match direction {
+                Direction::Read => w.dtdsel().read(),
+                Direction::Write => w.dtdsel().write(),
+            };
+            if multiple {
+                w.msbsel().multiple();
+                w.acmden().cmd12()
+            } else {
+                w.msbsel().single();
+                w.acmden().disabled()
+            }
+        });
+
+        self.send_command(index, arg, Response::R1, true)?;
+        self.check_card_status()
+    }
+
+    /// Wait for the end of the data transfer
+    fn end_data_command(&self) -> Result<(), Error> {
+        self.wait_status(|s| s.trfc().bit_is_set())?;
+        self.sdhc.nistr().write(|w| w.trfc().yes());
+        Ok(())
+    }
+
+    /// Issue a command that does not transfer data
+    fn command(&self, index: u8, arg: u32, response: Response) -> Result<(), Error> {
+        self.send_command(index, arg, response, false)?;
+        if response == Response::R1b {
+            // The end of the busy signal is reported as a transfer completion
+            self.end_data_command()?;
+        }
+        Ok(())
+    }
+
+    fn send_command(
+        &self,
+        index: u8,
+        arg: u32,
+        response: Response,
+        data: bool,
+    ) -> Result<(), Error> {
+        let uses_dat = data || response == Response::R1b;
+        while self.sdhc.psr.read().cmdinhc().bit_is_set()
+            || (uses_dat && self.sdhc.psr.read().cmdinhd().bit_is_set())
+        {}
+
+        // SAFETY: Writing ones clears every status flag
+        self.sdhc.nistr().write(|w| unsafe { w.bits(0xFFFF) });
+        self.sdhc.eistr().write(|w| unsafe { w.bits(0xFFFF) });
+
+        // SAFETY: Any 32-bit value is a valid argument
+        self.sdhc.arg1r.write(|w| unsafe { w.bits(arg) });
+        self.sdhc.cr.write(|w| {
+            match response {
+                Response::None => w.resptyp().none(),
+                Response::R2 => w.resptyp()._136_bit(),
+                Response::R1b => w.resptyp()._48_bit_busy(),
+                Response::R1 | Response::R3 | Response::R6 | Response::R7 => w.resptyp()._48_bit(),
+            };
+            w.cmdccen()
+                .bit(!matches!(response, Response::None | Response::R3));
+            w.cmdicen().bit(!matches!(
+                response,
+                Response::None | Response::R2 | Response::R3
+            ));
+            if data {
+                w.dpsel().data();
+            } else {
+                w.dpsel().no_data();
+            }
+            w.cmdtyp().normal();
+            // SAFETY: Command indices are 6 bits wide
+            unsafe { w.cmdidx().bits(index) }
+        });
+
+        self.wait_status(|s| s.cmdc().bit_is_set())?;
+        self.sdhc.nistr().write(|w| w.cmdc().yes());
+        Ok(())
+    }
+
+    /// Wait until `done` returns `true` for the normal interrupt status, or
+    /// an error is reported
+    fn wait_status<F>(&self, done: F) -> Result<(), Error>
+    where
+        F: Fn(&crate::pac::sdhc0::nistr::R) -> bool,
+    {
+        loop {
+            let status = self.sdhc.nistr().read();
+            if status.errint().bit_is_set() {
+                return Err(self.handle_error());
+            }
+            if done(&status) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Decode and clear the error status, then reset the command and data
+    /// lines
+    fn handle_error(&self) -> Error {
+        let status = self.sdhc.eistr().read();
+        let error = if status.cmdteo().bit_is_set() {
+            Error::CommandTimeout
+        } else if status.cmdcrc().bit_is_set() {
+            Error::CommandCrc
+        } else if status.cmdend().bit_is_set() {
+            Error::CommandEndBit
+        } else if status.cmdidx().bit_is_set() {
+            Error::CommandIndex
+        } else if status.datteo().bit_is_set() {
+            Error::DataTimeout
+        } else if status.datcrc().bit_is_set() {
+            Error::DataCrc
+        } else if status.datend().bit_is_set() {
+            Error::DataEndBit
+        } else if status.curlim().bit_is_set() {
+            Error::CurrentLimit
+        } else {
+            Error::AutoCmd
+        };
+
+        // SAFETY: Writing ones clears every status flag
+        self.sdhc.eistr().write(|w| unsafe { w.bits(0xFFFF) });
+        self.sdhc.nistr().write(|w| unsafe { w.bits(0xFFFF) });
+
+        self.sdhc.srr.write(|w| {
+            w.swrstcmd().reset();
+            w.swrstdat().reset()
+        });
+        while self.sdhc.srr.read().bits() != 0 {}
+        error
+    }
+
+    /// Check the R1 card status of the last command
+    fn check_card_status(&self) -> Result<(), Error> {
+        let status = self.response();
+        if status & R1_ERRORS != 0 {
+            Err(Error::CardStatus(status))
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline]
+    fn response(&self) -> u32 {
+        self.sdhc.rr[0].read().cmdresp().bits()
+    }
+
+    /// 136-bit response, without the CRC and end bit
+    #[inline]
+    fn response_long(&self) -> u128 {
+        self.sdhc.rr.iter().rev().fold(0, |acc, rr| {
+            (acc << 32) | rr.read().cmdresp().bits() as u128
+        })
+    }
+
+    /// Set the SD clock to the highest frequency not above `freq`
+    fn set_clock(&mut self, freq: u32) -> Result<(), Error> {
+        let divider = clock_divider(self.base_freq, freq).ok_or(Error::ClockDivider)?;
+
+        self.sdhc.ccr.write(|w| w.sdclken().disable());
+        // SAFETY: The divider is split into its 8 lower and 2 upper bits
+        self.sdhc.ccr.write(|w| unsafe {
+            w.sdclkfsel().bits(divider as u8);
+            w.usdclkfsel().bits((divider >> 8) as u8);
+            w.clkgsel().div();
+            w.intclken().on()
+        });
+        while self.sdhc.ccr.read().intclks().bit_is_clear() {}
+        self.sdhc.ccr.modify(|_, w| w.sdclken().enable());
+        Ok(())
+    }
+}
+
+/// Compute the 10-bit divider N for which `base / (2 * N)` does not exceed
+/// `freq`. A divider of zero passes the base clock through. Returns `None` if
+/// no divider is large enough.
+fn clock_divider(base: u32, freq: u32) -> Option<u16> {
+    if base <= freq {
+        Some(0)
+    } else {
+        let divider = (base + 2 * freq - 1) / (2 * freq);
+        (divider <= 0x3FF).then(|| divider as u16)
+    }
+}
+
+/// Extract the capacity in blocks from the CSD register, given as the
+/// 136-bit response without its CRC byte
+fn csd_num_blocks(response: u128) -> Option<u32> {
+    let csd = response << 8;
+    let bits = |high: u32, low: u32| ((csd >> low) & ((1 << (high - low + 1)) - 1)) as u32;
+
+    match bits(127, 126) {
+        0 => {
+            let c_size = bits(73, 62);
+            let c_size_mult = bits(49, 47);
+            let read_bl_len = bits(83, 80);
+            let bytes = ((c_size + 1) as u64) << (c_size_mult + 2 + read_bl_len);
+            Some((bytes / BLOCK_SIZE as u64) as u32)
+        }
+        1 => Some((bits(69, 48) + 1) * 1024),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "sdmmc")]
+impl<I: Instance> BlockDevice for Sdhc<I> {
+    type Error = Error;
+
+    fn read(
+        &self,
+        blocks: &mut [Block],
+        start_block_idx: BlockIdx,
+        _reason: &str,
+    ) -> Result<(), Self::Error> {
+        self.read_blocks_iter(
+            start_block_idx.0,
+            blocks.len(),
+            blocks.iter_mut().map(|b| &mut b.contents),
+        )
+    }
+
+    fn write(&self, blocks: &[Block], start_block_idx: BlockIdx) -> Result<(), Self::Error> {
+        self.write_blocks_iter(
+            start_block_idx.0,
+            blocks.len(),
+            blocks.iter().map(|b| &b.contents),
+        )
+    }
+
+    fn num_blocks(&self) -> Result<BlockCount, Self::Error> {
+        self.card
+            .map(|card| BlockCount(card.num_blocks))
+            .ok_or(Error::NoCard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_divider_does_not_exceed_frequency() {
+        assert_eq!(clock_divider(100_000_000, 400_000), Some(125));
+        assert_eq!(clock_divider(120_000_000, 25_000_000), Some(3));
+        assert_eq!(clock_divider(48_000_000, 25_000_000), Some(1));
+        assert_eq!(clock_divider(20_000_000, 25_000_000), Some(0));
+        assert_eq!(clock_divider(818_400_000, 400_000), Some(0x3FF));
+        assert_eq!(clock_divider(1_000_000_000, 400_000), None);
+    }
+
+    #[test]
+    fn csd_capacity_is_decoded() {
+        // 32 GiB SDHC card, C_SIZE = 0xEDC7, response without the CRC byte
+        let csd_v2: u128 = 0x400E_0032_5B59_0000_EDC7_7F80_0A40_4000 >> 8;
+        assert_eq!(csd_num_blocks(csd_v2), Some((0xEDC7 + 1) * 1024));
+
+        // 2 GiB SDSC card, C_SIZE = 0xF08, C_SIZE_MULT = 7, READ_BL_LEN = 10
+        let csd_v1: u128 = 0x005E_0032_5F5A_83C2_3FFF_FF80_1680_0000 >> 8;
+        assert_eq!(csd_num_blocks(csd_v1), Some((0xF08 + 1) * 1024));
+    }
+}
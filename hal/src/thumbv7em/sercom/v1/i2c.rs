@@ -1,12 +1,20 @@
 // Note: section 7.2.3 shows which pins support I2C Hs mode
 
+use crate::bus_clock::{self, BusClock};
 use crate::clock;
+use crate::ehal::blocking::delay::DelayUs;
+use crate::ehal::digital::v2::{InputPin, OutputPin};
+use crate::gpio::v2::{DynPin, PinId, PinMode};
 use crate::hal::blocking::i2c::{Read, Write, WriteRead};
+#[cfg(feature = "ehal1")]
+use embedded_hal_1::i2c::{ErrorKind, NoAcknowledgeSource, Operation};
 use crate::pac::sercom0::I2CM;
 use crate::pac::{MCLK, SERCOM0, SERCOM1, SERCOM2, SERCOM3, SERCOM4, SERCOM5};
 #[cfg(feature = "min-samd51n")]
 use crate::pac::{SERCOM6, SERCOM7};
+use crate::sercom::v1::i2c_speed::{baud_config, SPEED_HIGH};
 use crate::sercom::v1::pads::CompatiblePad;
+use crate::sercom::v1::smbus::BlockRead;
 use crate::sercom::v2::{Pad0, Pad1};
 use crate::time::Hertz;
 
@@ -16,6 +24,18 @@ const BUS_STATE_OWNED: u8 = 2;
 const MASTER_ACT_READ: u8 = 2;
 const MASTER_ACT_STOP: u8 = 3;
 
+/// Number of bytes read by the read operations of `segment`
+#[cfg(feature = "ehal1")]
+fn read_len(segment: &[Operation<'_>]) -> usize {
+    segment
+        .iter()
+        .map(|op| match op {
+            Operation::Read(buffer) => buffer.len(),
+            _ => 0,
+        })
+        .sum()
+}
+
 /// Define an I2C master type for the given SERCOM and pad pair.
 macro_rules! i2c {
     ([
@@ -24,9 +44,9 @@ macro_rules! i2c {
                 $pad0:ident,  // No longer used
                 $pad1:ident,  // No longer used
                 $SERCOM:ident,
-                $powermask:ident,
+                $powermask:ident, // No longer used
                 $clock:ident,
-                $apmask:ident
+                $apmask:ident // No longer used
             ),
         )+
     ]) => {
@@ -53,12 +73,11 @@ where
     /// Configures the sercom instance to work as an I2C Master.
     /// The clock is obtained via the `GenericClockGenerator` type.
     /// `freq` specifies the bus frequency to use for I2C communication.
-    /// There are typically a handful of values that tend to be supported;
-    /// standard mode is 100.khz(), full speed mode is 400.khz().
-    /// The hardware in the atsamd device supports fast mode at 1.mhz()
-    /// and fast mode, but there may be additional hardware configuration
-    /// missing from the current software implementation that prevents that
-    /// from working as-written today.
+    /// The speed mode is selected from `freq`, and
+    /// [`Speed`](crate::sercom::v1::i2c_speed::Speed) provides the
+    /// standard, fast, fast plus and high speed presets. Fast mode plus and
+    /// high speed mode require pins supporting them, and high speed mode a
+    /// generic clock at least twice as fast as the bus.
     ///
     /// ```no_run
     /// let mut i2c = I2CMaster3::new(
@@ -80,8 +99,7 @@ where
         scl: P1,
     ) -> Self {
         // Power up the peripheral bus clock.
-        // safe because we're exclusively owning SERCOM
-        mclk.$apmask.modify(|_, w| w.$powermask().set_bit());
+        $SERCOM::enable_bus_clock(mclk);
 
         unsafe {
             // reset the sercom instance
@@ -96,10 +114,14 @@ where
             // wait for configuration to take effect
             while sercom.i2cm().syncbusy.read().enable().bit_is_set() {}
 
-            // set the baud rate
-            let gclk = clock.freq();
-            let baud = (gclk.0 / (2 * freq.into().0) - 1) as u8;
-            sercom.i2cm().baud.modify(|_, w| w.baud().bits(baud));
+            // set the speed mode and the baud rate
+            let config = baud_config(clock.freq(), freq.into());
+            sercom.i2cm().ctrla.modify(|_, w| {
+                // High speed mode requires the SCL stretch mode after ACK
+                w.sclsm().bit(config.speed == SPEED_HIGH);
+                w.speed().bits(config.speed)
+            });
+            sercom.i2cm().baud.write(|w| w.bits(config.baud));
 
             sercom.i2cm().ctrla.modify(|_, w| w.enable().set_bit());
             // wait for configuration to take effect
@@ -118,11 +140,161 @@ where
     }
 
     /// Breaks the sercom device up into its constituent pins and the SERCOM
-    /// instance.  Does not make any changes to power management.
+    /// instance.  The bus clock of the SERCOM is disabled.
     pub fn free(self) -> (P0, P1, $SERCOM) {
+        bus_clock::release::<$SERCOM>();
         (self.sda, self.scl, self.sercom)
     }
 
+    /// Trim the SCL high and low periods
+    ///
+    /// The period of SCL is `10 + high + low` generic clock cycles in the
+    /// standard, fast and fast plus modes, and `2 + high + low` cycles in high
+    /// speed mode, ignoring the rise time of the bus. The I2C specification
+    /// requires a low period about twice as long as the high period in fast
+    /// mode and above, which lets faster slaves run closer to the nominal
+    /// frequency than the symmetric periods configured by `new`.
+    pub fn set_scl_periods(&mut self, high: u8, low: u8) {
+        self.disable();
+        let hs = self.high_speed();
+        // SAFETY: Every field of BAUD accepts any byte
+        self.i2cm().baud.modify(|_, w| unsafe {
+            if hs {
+                w.hsbaud().bits(high).hsbaudlow().bits(low)
+            } else {
+                w.baud().bits(high).baudlow().bits(low)
+            }
+        });
+        self.enable();
+    }
+
+    /// Return whether the SERCOM is configured in high speed mode, in which
+    /// case each address is preceded by the master code
+    fn high_speed(&mut self) -> bool {
+        self.i2cm().ctrla.read().speed().bits() == SPEED_HIGH
+    }
+
+    /// Returns `true` if SCL is stretched after the ACK bit, in high speed
+    /// mode
+    fn after_ack_stretch(&mut self) -> bool {
+        self.i2cm().ctrla.read().sclsm().bit_is_set()
+    }
+
+    /// Enable or disable the SCL low timeout
+    ///
+    /// When enabled, a transaction is aborted with [`I2CError::Timeout`]
+    /// once SCL is held low for 25 to 35 ms, such as by a slave stretching
+    /// the clock forever. SMBus devices require this timeout. It is only
+    /// accurate when the SERCOM slow clock is configured at 32 kHz.
+    pub fn set_low_timeout(&mut self, enabled: bool) {
+        self.disable();
+        self.i2cm().ctrla.modify(|_, w| w.lowtouten().bit(enabled));
+        self.enable();
+    }
+
+    /// Release a slave holding SDA low, then reset the SERCOM
+    ///
+    /// A slave reset or disturbed in the middle of a transaction may keep
+    /// driving SDA low, waiting for clock pulses that never come, which
+    /// hangs the bus. The pins are temporarily switched to GPIO to clock SCL
+    /// until the slave releases SDA, at most 9 times, then to generate a
+    /// `STOP` condition. Finally the SERCOM is reset, keeping its
+    /// configuration, and the bus is forced to the idle state.
+    ///
+    /// `delay` paces the recovery clock at 100 kHz. Returns
+    /// [`I2CError::BusStuck`] if SDA or SCL is still held low afterwards.
+    pub fn bus_clear<D: DelayUs<u8>>(&mut self, delay: &mut D) -> Result<(), I2CError> {
+        self.disable();
+
+        // SAFETY: The pins are owned by the pads, and thus by `self`, so no
+        // other code can access them. They are returned to their pad
+        // configuration before the `DynPin`s are dropped.
+        let mut sda =
+            unsafe { DynPin::new(<P0::Id as PinId>::DYN, <P0::Mode as PinMode>::DYN) };
+        let mut scl =
+            unsafe { DynPin::new(<P1::Id as PinId>::DYN, <P1::Mode as PinMode>::DYN) };
+
+        // The pins emulate open drain outputs. Once their output is set low,
+        // they drive the line low as outputs, and release it as inputs.
+        scl.into_push_pull_output();
+        let _ = scl.set_low();
+        sda.into_push_pull_output();
+        let _ = sda.set_low();
+        sda.into_floating_input();
+        delay.delay_us(5);
+
+        for _ in 0..9 {
+            scl.into_floating_input();
+            delay.delay_us(5);
+            if sda.is_high().unwrap_or(false) {
+                break;
+            }
+            scl.into_push_pull_output();
+            delay.delay_us(5);
+        }
+
+        // STOP condition: SDA rises while SCL is high
+        scl.into_push_pull_output();
+        delay.delay_us(5);
+        sda.into_push_pull_output();
+        delay.delay_us(5);
+        scl.into_floating_input();
+        delay.delay_us(5);
+        sda.into_floating_input();
+        delay.delay_us(5);
+        let released = sda.is_high().unwrap_or(false) && scl.is_high().unwrap_or(false);
+
+        sda.into_mode(<P0::Mode as PinMode>::DYN);
+        scl.into_mode(<P1::Mode as PinMode>::DYN);
+        self.reset();
+
+        if released {
+            Ok(())
+        } else {
+            Err(I2CError::BusStuck)
+        }
+    }
+
+    /// Reset the SERCOM, keeping its configuration and enabled interrupts,
+    /// and enable it
+    fn reset(&mut self) {
+        // Every CTRLA bit but SWRST and ENABLE
+        let ctrla = self.i2cm().ctrla.read().bits() & !0b11;
+        // Every CTRLB bit but the CMD command
+        let ctrlb = self.i2cm().ctrlb.read().bits() & !(0b11 << 16);
+        let ctrlc = self.i2cm().ctrlc.read().bits();
+        let baud = self.i2cm().baud.read().bits();
+        let intenset = self.i2cm().intenset.read().bits();
+        self.i2cm().ctrla.write(|w| w.swrst().set_bit());
+        while self.i2cm().syncbusy.read().swrst().bit_is_set() {}
+        // SAFETY: The values were read from the same registers
+        unsafe {
+            self.i2cm().ctrla.write(|w| w.bits(ctrla));
+            self.i2cm().ctrlb.write(|w| w.bits(ctrlb));
+            self.i2cm().ctrlc.write(|w| w.bits(ctrlc));
+            self.i2cm().baud.write(|w| w.bits(baud));
+            self.i2cm().intenset.write(|w| w.bits(intenset));
+        }
+        self.wait_sync();
+        self.enable();
+    }
+
+    fn disable(&mut self) {
+        self.i2cm().ctrla.modify(|_, w| w.enable().clear_bit());
+        while self.i2cm().syncbusy.read().enable().bit_is_set() {}
+    }
+
+    /// Enable the SERCOM, and force the bus to the idle state
+    fn enable(&mut self) {
+        self.i2cm().ctrla.modify(|_, w| w.enable().set_bit());
+        while self.i2cm().syncbusy.read().enable().bit_is_set() {}
+        // SAFETY: BUS_STATE_IDLE is a valid bus state
+        self.i2cm()
+            .status
+            .modify(|_, w| unsafe { w.busstate().bits(BUS_STATE_IDLE) });
+        self.wait_sync();
+    }
+
     fn start_tx_write(&mut self, addr: u8) -> Result<(), I2CError> {
         loop {
             match self.i2cm().status.read().busstate().bits() {
@@ -132,16 +304,17 @@ where
         }
 
         // Signal start and transmit encoded address.
+        let hs = self.high_speed();
         unsafe {
             self.i2cm()
                 .addr
-                .write(|w| w.addr().bits((addr as u16) << 1));
+                .write(|w| w.addr().bits((addr as u16) << 1).hs().bit(hs));
         }
 
         // wait for transmission to complete
         while !self.i2cm().intflag.read().mb().bit_is_set() {}
 
-        self.status_to_err()
+        self.status_to_err().map_err(address_nack)
     }
 
     fn status_to_err(&mut self) -> Result<(), I2CError> {
@@ -164,7 +337,7 @@ where
         Ok(())
     }
 
-    fn start_tx_read(&mut self, addr: u8) -> Result<(), I2CError> {
+    fn start_tx_read(&mut self, addr: u8, len: usize) -> Result<(), I2CError> {
         loop {
             match self.i2cm().status.read().busstate().bits() {
                 BUS_STATE_IDLE | BUS_STATE_OWNED => break,
@@ -172,14 +345,20 @@ where
             }
         }
 
+        // With SCL stretched after the ACK, a single byte is NACKed as it is
+        // received along with the address
+        let nack = len == 1 && self.after_ack_stretch();
+        self.i2cm().ctrlb.modify(|_, w| w.ackact().bit(nack));
+
         self.i2cm().intflag.modify(|_, w| w.error().clear_bit());
 
         // Signal start (or rep start if appropriate)
         // and transmit encoded address.
+        let hs = self.high_speed();
         unsafe {
             self.i2cm()
                 .addr
-                .write(|w| w.addr().bits(((addr as u16) << 1) | 1));
+                .write(|w| w.addr().bits(((addr as u16) << 1) | 1).hs().bit(hs));
         }
 
         // wait for transmission to complete
@@ -194,7 +373,7 @@ where
             }
         }
 
-        self.status_to_err()
+        self.status_to_err().map_err(address_nack)
     }
 
     fn wait_sync(&mut self) {
@@ -212,11 +391,14 @@ where
         self.cmd(MASTER_ACT_STOP)
     }
 
-    fn cmd_read(&mut self) {
+    fn cmd_read(&mut self, last: bool) {
+        // With SCL stretched after the ACK, the acknowledge action applies to
+        // the byte read next, so the last byte is NACKed as it is received
+        let nack = last && self.after_ack_stretch();
         unsafe {
             self.i2cm().ctrlb.modify(|_, w| {
                 // clear bit means send ack
-                w.ackact().clear_bit();
+                w.ackact().bit(nack);
                 w.cmd().bits(MASTER_ACT_READ)
             });
         }
@@ -251,15 +433,16 @@ where
 
     fn fill_buffer(&mut self, buffer: &mut [u8]) -> Result<(), I2CError> {
         // Some manual iterator gumph because we need to ack bytes after the first.
-        let mut iter = buffer.iter_mut();
-        *iter.next().expect("buffer len is at least 1") = self.read_one();
+        let last = buffer.len() - 1;
+        let mut iter = buffer.iter_mut().enumerate();
+        *iter.next().expect("buffer len is at least 1").1 = self.read_one();
 
         loop {
             match iter.next() {
                 None => break,
-                Some(dest) => {
+                Some((index, dest)) => {
                     // Ack the last byte so that we can receive another one
-                    self.cmd_read();
+                    self.cmd_read(index == last);
                     *dest = self.read_one();
                 }
             }
@@ -278,16 +461,119 @@ where
     }
 
     fn do_read(&mut self, addr: u8, buffer: &mut [u8]) -> Result<(), I2CError> {
-        self.start_tx_read(addr)?;
+        self.start_tx_read(addr, buffer.len())?;
         self.fill_buffer(buffer)
     }
 
     fn do_write_read(&mut self, addr: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), I2CError> {
         self.start_tx_write(addr)?;
         self.send_bytes(bytes)?;
-        self.start_tx_read(addr)?;
+        self.start_tx_read(addr, buffer.len())?;
         self.fill_buffer(buffer)
     }
+
+    fn do_write_read_block(
+        &mut self,
+        addr: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+        extra: usize,
+    ) -> Result<usize, I2CError> {
+        self.start_tx_write(addr)?;
+        self.send_bytes(bytes)?;
+        // The length byte is followed by the block, so it is acknowledged
+        self.start_tx_read(addr, 2)?;
+        buffer[0] = self.read_one();
+        let last = (buffer[0] as usize + extra).min(buffer.len() - 1);
+        for index in 1..=last {
+            self.cmd_read(index == last);
+            buffer[index] = self.read_one();
+        }
+
+        // arrange to send nack on next command to
+        // stop slave from transmitting more data
+        self.i2cm().ctrlb.modify(|_, w| w.ackact().set_bit());
+
+        Ok(last + 1)
+    }
+
+    #[cfg(feature = "ehal1")]
+    fn fill_buffers(&mut self, segment: &mut [Operation<'_>]) {
+        // The first byte is received along with the address, the following
+        // ones once the previous byte is acknowledged
+        let mut remaining = read_len(segment);
+        let mut first = true;
+        for op in segment.iter_mut() {
+            if let Operation::Read(buffer) = op {
+                for dest in buffer.iter_mut() {
+                    remaining -= 1;
+                    if !first {
+                        self.cmd_read(remaining == 0);
+                    }
+                    first = false;
+                    *dest = self.read_one();
+                }
+            }
+        }
+
+        // arrange to send nack on next command to
+        // stop slave from transmitting more data
+        self.i2cm().ctrlb.modify(|_, w| w.ackact().set_bit());
+    }
+
+    #[cfg(feature = "ehal1")]
+    fn do_transaction(&mut self, addr: u8, operations: &mut [Operation<'_>]) -> Result<(), I2CError> {
+        // Consecutive operations of the same kind form one segment delimited by
+        // (repeated) STARTs
+        let mut start = 0;
+        while start < operations.len() {
+            let is_read = matches!(operations[start], Operation::Read(_));
+            let end = operations[start..]
+                .iter()
+                .position(|op| matches!(op, Operation::Read(_)) != is_read)
+                .map_or(operations.len(), |offset| start + offset);
+            let segment = &mut operations[start..end];
+            if is_read {
+                // Zero length reads are impossible on the bus, skip them
+                let len = read_len(segment);
+                if len != 0 {
+                    self.start_tx_read(addr, len)?;
+                    self.fill_buffers(segment);
+                }
+            } else {
+                self.start_tx_write(addr)?;
+                for op in segment.iter() {
+                    if let Operation::Write(bytes) = op {
+                        self.send_bytes(bytes)?;
+                    }
+                }
+            }
+            start = end;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl<P0, P1> crate::typelevel::Sealed for $Type<P0, P1>
+where
+    P0: CompatiblePad<Sercom = $SERCOM, PadNum = Pad0>,
+    P1: CompatiblePad<Sercom = $SERCOM, PadNum = Pad1>,
+{
+}
+
+#[cfg(feature = "async")]
+impl<P0, P1> crate::sercom::v1::i2c_future::I2cMaster for $Type<P0, P1>
+where
+    P0: CompatiblePad<Sercom = $SERCOM, PadNum = Pad0>,
+    P1: CompatiblePad<Sercom = $SERCOM, PadNum = Pad1>,
+{
+    type Sercom = $SERCOM;
+
+    #[inline]
+    fn i2cm_regs(&self) -> &I2CM {
+        self.sercom.i2cm()
+    }
 }
 
 impl<P0, P1> Write for $Type<P0, P1>
@@ -333,6 +619,51 @@ where
     }
 }
 
+impl<P0, P1> BlockRead for $Type<P0, P1>
+where
+    P0: CompatiblePad<Sercom = $SERCOM, PadNum = Pad0>,
+    P1: CompatiblePad<Sercom = $SERCOM, PadNum = Pad1>,
+{
+    type Error = I2CError;
+
+    fn write_read_block(
+        &mut self,
+        addr: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+        extra: usize,
+    ) -> Result<usize, Self::Error> {
+        let res = self.do_write_read_block(addr, bytes, buffer, extra);
+        self.cmd_stop();
+        res
+    }
+}
+
+#[cfg(feature = "ehal1")]
+impl<P0, P1> embedded_hal_1::i2c::ErrorType for $Type<P0, P1>
+where
+    P0: CompatiblePad<Sercom = $SERCOM, PadNum = Pad0>,
+    P1: CompatiblePad<Sercom = $SERCOM, PadNum = Pad1>,
+{
+    type Error = I2CError;
+}
+
+#[cfg(feature = "ehal1")]
+impl<P0, P1> embedded_hal_1::i2c::I2c for $Type<P0, P1>
+where
+    P0: CompatiblePad<Sercom = $SERCOM, PadNum = Pad0>,
+    P1: CompatiblePad<Sercom = $SERCOM, PadNum = Pad1>,
+{
+    /// Consecutive operations of the same kind are merged, a repeated
+    /// `START` is sent between operations of a different kind and a `STOP`
+    /// is sent at the end, or as soon as an error occurs.
+    fn transaction(&mut self, addr: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        let res = self.do_transaction(addr, operations);
+        self.cmd_stop();
+        res
+    }
+}
+
         )+
 
     };
@@ -417,11 +748,46 @@ i2c!([
         ),
 ]);
 
-#[derive(Debug)]
+/// Report a `NACK` received right after the address as an
+/// [`I2CError::AddressError`]
+fn address_nack(err: I2CError) -> I2CError {
+    match err {
+        I2CError::Nack => I2CError::AddressError,
+        err => err,
+    }
+}
+
+/// I2C master error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum I2CError {
+    /// Another master took over the bus
     ArbitrationLost,
+    /// The slave did not acknowledge its address
     AddressError,
+    /// An illegal bus condition occurred, such as a misplaced `START` or
+    /// `STOP`, or the bus was busy
     BusError,
+    /// SCL was held low longer than the SCL low timeout, or the SMBus
+    /// cumulative timeouts were exceeded
     Timeout,
+    /// The slave did not acknowledge a data byte
     Nack,
+    /// A slave still holds the bus after [`bus_clear`](I2CMaster0::bus_clear)
+    BusStuck,
+}
+
+#[cfg(feature = "ehal1")]
+impl embedded_hal_1::i2c::Error for I2CError {
+    #[inline]
+    fn kind(&self) -> ErrorKind {
+        match self {
+            I2CError::ArbitrationLost => ErrorKind::ArbitrationLoss,
+            I2CError::BusError => ErrorKind::Bus,
+            I2CError::BusStuck => ErrorKind::Bus,
+            I2CError::AddressError => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+            I2CError::Nack => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data),
+            I2CError::Timeout => ErrorKind::Other,
+        }
+    }
 }
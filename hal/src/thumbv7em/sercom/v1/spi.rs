@@ -6,6 +6,7 @@
 
 use core::marker::PhantomData;
 
+use crate::bus_clock::{self, BusClock};
 use crate::clock;
 use crate::hal::spi::{FullDuplex, Mode, Phase, Polarity};
 use crate::pac::sercom0::SPIM;
@@ -110,7 +111,7 @@ where
 /// that construction is restricted to correct configurations.
 macro_rules! spi_master {
     (
-        $Type:ident: ($Sercom:ident, $SERCOM:ident, $powermask:ident, $clock:ident, $apmask:ident)
+        $Type:ident: ($Sercom:ident, $SERCOM:ident, $clock:ident)
     ) => {
         $crate::paste::item! {
             pub type [<$Type Padout>]<MISO, MOSI, SCLK> = Padout<$Sercom, MISO, MOSI, SCLK>;
@@ -160,8 +161,7 @@ macro_rules! spi_master {
                 let padout = padout.into();
 
                 // Power up the peripheral bus clock.
-                // safe because we're exclusively owning SERCOM
-                mclk.$apmask.modify(|_, w| w.$powermask().set_bit());
+                $SERCOM::enable_bus_clock(mclk);
 
                 // reset the sercom instance
                 sercom.spim().ctrla.modify(|_, w| w.swrst().set_bit());
@@ -226,8 +226,10 @@ macro_rules! spi_master {
             }
 
             /// Tear down the SPI instance and yield the constituent pins and
-            /// SERCOM instance.  No explicit de-initialization is performed.
+            /// SERCOM instance.  Only the bus clock of the SERCOM is
+            /// disabled.
             pub fn free(self) -> (Padout<$Sercom, MISO, MOSI, SCK>, $SERCOM) {
+                bus_clock::release::<$SERCOM>();
                 (self.padout, self.sercom)
             }
         }
@@ -281,13 +283,13 @@ macro_rules! spi_master {
     };
 }
 
-spi_master!(SPIMaster0: (Sercom0, SERCOM0, sercom0_, Sercom0CoreClock, apbamask));
-spi_master!(SPIMaster1: (Sercom1, SERCOM1, sercom1_, Sercom1CoreClock, apbamask));
-spi_master!(SPIMaster2: (Sercom2, SERCOM2, sercom2_, Sercom2CoreClock, apbbmask));
-spi_master!(SPIMaster3: (Sercom3, SERCOM3, sercom3_, Sercom3CoreClock, apbbmask));
-spi_master!(SPIMaster4: (Sercom4, SERCOM4, sercom4_, Sercom4CoreClock, apbdmask));
-spi_master!(SPIMaster5: (Sercom5, SERCOM5, sercom5_, Sercom5CoreClock, apbdmask));
+spi_master!(SPIMaster0: (Sercom0, SERCOM0, Sercom0CoreClock));
+spi_master!(SPIMaster1: (Sercom1, SERCOM1, Sercom1CoreClock));
+spi_master!(SPIMaster2: (Sercom2, SERCOM2, Sercom2CoreClock));
+spi_master!(SPIMaster3: (Sercom3, SERCOM3, Sercom3CoreClock));
+spi_master!(SPIMaster4: (Sercom4, SERCOM4, Sercom4CoreClock));
+spi_master!(SPIMaster5: (Sercom5, SERCOM5, Sercom5CoreClock));
 #[cfg(feature = "min-samd51n")]
-spi_master!(SPIMaster6: (Sercom6, SERCOM6, sercom6_, Sercom6CoreClock, apbdmask));
+spi_master!(SPIMaster6: (Sercom6, SERCOM6, Sercom6CoreClock));
 #[cfg(feature = "min-samd51n")]
-spi_master!(SPIMaster7: (Sercom7, SERCOM7, sercom7_, Sercom7CoreClock, apbdmask));
+spi_master!(SPIMaster7: (Sercom7, SERCOM7, Sercom7CoreClock));
@@ -4,6 +4,7 @@
     Please use the `sercom::v2::uart` module instead."
 )]
 
+use crate::bus_clock::{self, BusClock};
 use crate::clock;
 use crate::hal::blocking::serial::{write::Default, Write};
 use crate::hal::serial;
@@ -206,9 +207,9 @@ macro_rules! uart {
     ($Type:ident: (
         $Sercom:ident,
         $SERCOM:ident,
-        $powermask:ident,
+        $powermask:ident, // No longer used
         $clock:ident,
-        $apmask:ident,
+        $apmask:ident, // No longer used
         $int0: ident,
         $int1: ident,
         $int2: ident)
@@ -243,7 +244,7 @@ macro_rules! uart {
                     Padout<$Sercom, RX, TX, RTS, CTS>: RxpoTxpo {
                     let padout = padout.into();
 
-                    mclk.$apmask.modify(|_, w| w.$powermask().set_bit());
+                    $SERCOM::enable_bus_clock(mclk);
 
                     // Lots of union fields which require unsafe access
                     unsafe {
@@ -318,10 +319,33 @@ macro_rules! uart {
                     }
                 }
 
+                /// Disable the bus clock of the SERCOM and release it with
+                /// the pads
                 pub fn free(self) -> (Padout<$Sercom, RX, TX, RTS, CTS>, $SERCOM) {
+                    bus_clock::release::<$SERCOM>();
                     (self.padout, self.sercom)
                 }
 
+                /// Enable or disable IrDA encoding
+                ///
+                /// With IrDA encoding, the transmitter sends each 0 bit as a
+                /// pulse lasting 3/16 of the bit period, suitable to drive an
+                /// infrared transceiver directly. The receiver ignores
+                /// pulses shorter than `pulse_length + 2` GCLK cycles.
+                pub fn set_irda_encoding(&mut self, pulse_length: Option<u8>) {
+                    let usart = self.usart();
+                    usart.ctrla.modify(|_, w| w.enable().clear_bit());
+                    while usart.syncbusy.read().enable().bit_is_set() {}
+                    if let Some(pulse_length) = pulse_length {
+                        // SAFETY: RXPL accepts any byte
+                        usart.rxpl.write(|w| unsafe { w.rxpl().bits(pulse_length) });
+                    }
+                    usart.ctrlb.modify(|_, w| w.enc().bit(pulse_length.is_some()));
+                    while usart.syncbusy.read().ctrlb().bit_is_set() {}
+                    usart.ctrla.modify(|_, w| w.enable().set_bit());
+                    while usart.syncbusy.read().enable().bit_is_set() {}
+                }
+
                 /// Splits the UART into transmit and receive halves
                 pub fn split(self) -> ([<$Type Tx>]<TX, RTS>, [<$Type Rx>]<RX, CTS>) {
                     let (tx_pads, rx_pads) = self.padout.split();
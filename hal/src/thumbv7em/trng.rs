@@ -1,43 +1,103 @@
+//! # TRNG - True Random Number Generator
+//!
+//! The TRNG produces a new 32-bit random word every 84 clock cycles.
+//!
+//! Words can be read in a blocking fashion with [`Trng::random_u32`] and
+//! friends, or through the [`RngCore`] and [`CryptoRng`] traits, which lets
+//! the [`Trng`] be used directly by `rand`, `ecdsa` and TLS stacks.
+//!
+//! To generate words from an interrupt handler instead, call
+//! [`Trng::enable_interrupt`] and read each word with the non-blocking
+//! [`Trng::read`] when the `TRNG` interrupt fires. Reading the data register
+//! clears the interrupt flag.
+
+use crate::bus_clock::BusClock;
 use crate::pac::{MCLK, TRNG};
 
 use rand_core::{CryptoRng, RngCore};
+use void::{ResultVoidExt, Void};
 
 #[cfg(feature = "unproven")]
 use embedded_hal::blocking::rng::Read;
 
+/// True Random Number Generator
 pub struct Trng(TRNG);
 
 impl Trng {
+    /// Enable the TRNG clock and start generating random words
     pub fn new(mclk: &mut MCLK, trng: TRNG) -> Trng {
-        mclk.apbcmask.modify(|_, w| w.trng_().set_bit());
+        TRNG::enable_bus_clock(mclk);
         trng.ctrla.modify(|_, w| w.enable().set_bit());
         Self(trng)
     }
 
+    /// Disable the TRNG and its clock and return the register block
+    pub fn free(mut self, mclk: &mut MCLK) -> TRNG {
+        self.disable_interrupt();
+        self.0.ctrla.modify(|_, w| w.enable().clear_bit());
+        TRNG::disable_bus_clock(mclk);
+        self.0
+    }
+
+    /// Keep generating random words while the device is in standby
+    pub fn run_standby(&mut self, enable: bool) {
+        self.0.ctrla.modify(|_, w| w.runstdby().bit(enable));
+    }
+
+    /// Enable the data ready interrupt
+    pub fn enable_interrupt(&mut self) {
+        self.0.intenset.write(|w| w.datardy().set_bit());
+    }
+
+    /// Disable the data ready interrupt
+    pub fn disable_interrupt(&mut self) {
+        self.0.intenclr.write(|w| w.datardy().set_bit());
+    }
+
+    /// Returns `true` if a new random word is ready to be read
+    pub fn is_ready(&self) -> bool {
+        self.0.intflag.read().datardy().bit_is_set()
+    }
+
+    /// Read a random word without blocking.
+    ///
+    /// Returns [`nb::Error::WouldBlock`] if no new word has been generated
+    /// since the last read. Reading clears the data ready interrupt flag.
+    pub fn read(&self) -> nb::Result<u32, Void> {
+        if self.is_ready() {
+            Ok(self.0.data.read().bits())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Fill `buf` with random bytes, blocking until enough words have been
+    /// generated
     pub fn random(&self, buf: &mut [u8]) {
         for chunk in buf.chunks_mut(4) {
             chunk.copy_from_slice(&self.random_u32().to_le_bytes()[..chunk.len()]);
         }
     }
 
+    /// Block until a random `u8` is available
     pub fn random_u8(&self) -> u8 {
         self.random_u32() as u8
     }
 
+    /// Block until a random `u16` is available
     pub fn random_u16(&self) -> u16 {
         self.random_u32() as u16
     }
 
+    /// Block until a random `u32` is available
     pub fn random_u32(&self) -> u32 {
-        while self.0.intflag.read().datardy().bit_is_clear() {}
-        self.0.data.read().bits()
+        nb::block!(self.read()).void_unwrap()
     }
 
+    /// Block until a random `u64` is available
     pub fn random_u64(&self) -> u64 {
-        while self.0.intflag.read().datardy().bit_is_clear() {}
-        let lower_half = self.0.data.read().bits() as u64;
-        while self.0.intflag.read().datardy().bit_is_clear() {}
-        let upper_half = self.0.data.read().bits() as u64;
+        let lower_half = self.random_u32() as u64;
+        let upper_half = self.random_u32() as u64;
         (upper_half << 32) | lower_half
     }
 }
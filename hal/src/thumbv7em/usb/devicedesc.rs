@@ -175,6 +175,11 @@ impl DeviceDescBank {
         self.status_bk.crc_error()
     }
 
+    /// Clears the CRC error and error flow status of the bank.
+    pub fn clear_status(&mut self) {
+        self.status_bk = StatusBk(0);
+    }
+
     pub fn set_address(&mut self, address: *mut u8) {
         self.addr = address;
     }
@@ -1,4 +1,26 @@
-//! USB Device support
+//! USB Device and host support
+//!
+//! # Composite devices
+//!
+//! A composite device, such as a serial port along with a keyboard, creates
+//! several classes from the same `UsbBusAllocator`, and passes all of them to
+//! `UsbDevice::poll`. The classes share the resources of [`UsbBus`]:
+//!
+//! * Endpoint 0 is the control endpoint; endpoints 1 to 7 each provide an IN
+//!   and an OUT endpoint. A CDC-ACM serial port uses 3 endpoints, a HID
+//!   keyboard 1 or 2, and a mass storage device 2.
+//! * Bulk endpoints can be double-buffered with
+//!   [`UsbBus::set_double_buffered`], using up the IN and OUT endpoints of
+//!   their number.
+//! * The endpoint buffers share 2048 bytes of RAM.
+//!
+//! Classes panic when they can't allocate their endpoints;
+//! [`UsbBus::endpoints_available`] and [`UsbBus::buffer_available`] report
+//! what is left before creating the next class.
+//!
+//! The USB device is polled from the `USB_OTHER`, `USB_SOF_HSOF`, `USB_TRCPT0`
+//! and `USB_TRCPT1` interrupts, and from nowhere else. Give them all the same
+//! priority, so that their handlers never preempt each other.
 
 use crate::gpio;
 
@@ -7,6 +29,10 @@ pub use usb_device;
 mod bus;
 pub use self::bus::UsbBus;
 
+#[path = "../../usb/dfu.rs"]
+pub mod dfu;
+pub mod host;
+
 mod devicedesc;
 use self::devicedesc::Descriptors;
 
@@ -244,6 +244,40 @@ impl From<MegaHertz> for Nanoseconds {
     }
 }
 
+// fugit interoperability
+//
+// Each unit converts from and to the `u32` fugit type of the same unit, so
+// APIs taking `Into<Unit>` accept fugit durations and rates of any unit, like
+// `timer.start(5.millis())`, without type annotations.
+
+macro_rules! fugit_conversions {
+    ($($unit:ident <=> $fugit:ident: $get:ident, $new:ident),+) => {
+        $(
+            impl From<fugit::$fugit> for $unit {
+                fn from(item: fugit::$fugit) -> Self {
+                    $unit(item.$get())
+                }
+            }
+
+            impl From<$unit> for fugit::$fugit {
+                fn from(item: $unit) -> Self {
+                    fugit::$fugit::$new(item.0)
+                }
+            }
+        )+
+    };
+}
+
+fugit_conversions!(
+    Hertz <=> HertzU32: raw, from_raw,
+    KiloHertz <=> KilohertzU32: raw, from_raw,
+    MegaHertz <=> MegahertzU32: raw, from_raw,
+    Seconds <=> SecsDurationU32: ticks, from_ticks,
+    Milliseconds <=> MillisDurationU32: ticks, from_ticks,
+    Microseconds <=> MicrosDurationU32: ticks, from_ticks,
+    Nanoseconds <=> NanosDurationU32: ticks, from_ticks
+);
+
 #[cfg(test)]
 mod tests {
     use crate::time::*;
@@ -277,4 +311,24 @@ mod tests {
         let as_ns: Nanoseconds = 2.mhz().into();
         assert_eq!(as_ns.0, 500_u32);
     }
+
+    #[test]
+    fn convert_from_fugit() {
+        use fugit::{ExtU32, RateExtU32};
+
+        fn as_ns<T: Into<Nanoseconds>>(time: T) -> u32 {
+            time.into().0
+        }
+        fn as_hz<T: Into<Hertz>>(freq: T) -> u32 {
+            freq.into().0
+        }
+        assert_eq!(as_ns(5.millis()), 5_000_000_u32);
+        assert_eq!(as_hz(48.MHz()), 48_000_000_u32);
+    }
+
+    #[test]
+    fn convert_to_fugit() {
+        let as_ms: fugit::MillisDurationU32 = 3.ms().into();
+        assert_eq!(as_ms.ticks(), 3_u32);
+    }
 }
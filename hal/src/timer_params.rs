@@ -19,6 +19,20 @@ impl TimerParams {
         Self::new_from_ticks(ticks)
     }
 
+    /// calculates TimerParams from a given frequency, or returns `None` if
+    /// the frequency is 0 or too low for a 16-bit counter, even with the
+    /// largest divider.
+    pub fn checked_new(freq: Hertz, src_freq: u32) -> Option<Self> {
+        if freq.0 == 0 {
+            return None;
+        }
+        let ticks: u32 = src_freq / freq.0;
+        if ticks / 1024 > u16::MAX as u32 {
+            return None;
+        }
+        Some(Self::new_from_ticks(ticks))
+    }
+
     /// calculates TimerParams from a given period based timeout.
     pub fn new_us<T>(timeout: T, src_freq: u32) -> Self
     where
@@ -29,6 +43,13 @@ impl TimerParams {
         Self::new_from_ticks(ticks)
     }
 
+    /// Returns the divider selected by the `prescaler` field value of a
+    /// timer counter.
+    pub fn prescaler_divider(prescaler: u8) -> u32 {
+        const DIVIDERS: [u32; 8] = [1, 2, 4, 8, 16, 64, 256, 1024];
+        DIVIDERS[prescaler as usize & 0b111]
+    }
+
     fn new_from_ticks(ticks: u32) -> Self {
         let divider = ((ticks >> 16) + 1).next_power_of_two();
         let divider = match divider {
@@ -45,7 +66,7 @@ impl TimerParams {
 
         let cycles: u32 = ticks / divider as u32;
 
-        if cycles > u16::max_value() as u32 {
+        if cycles > u16::MAX as u32 {
             panic!("cycles {} is out of range for a 16 bit counter", cycles);
         }
 
@@ -80,4 +101,21 @@ mod tests {
         assert_eq!(tp_from_hz.divider, tp_from_us.divider);
         assert!((tp_from_hz.cycles as i32 - tp_from_us.cycles as i32).abs() <= 1);
     }
+
+    #[test]
+    fn checked_new_rejects_out_of_range_frequencies() {
+        let tp = TimerParams::checked_new(1_u32.hz(), 48_000_000_u32).unwrap();
+        assert_eq!(tp.divider, 1024);
+        assert_eq!(tp.cycles, 46_875);
+        assert!(TimerParams::checked_new(0_u32.hz(), 48_000_000_u32).is_none());
+        assert!(TimerParams::checked_new(1_u32.hz(), 120_000_000_u32).is_none());
+    }
+
+    #[test]
+    fn prescaler_divider_matches_timer_params() {
+        let tp = TimerParams::new(50_u32.hz(), 48_000_000_u32);
+        assert_eq!(TimerParams::prescaler_divider(4), 16);
+        assert_eq!(tp.divider as u32, TimerParams::prescaler_divider(4));
+        assert_eq!(TimerParams::prescaler_divider(7), 1024);
+    }
 }
@@ -0,0 +1,250 @@
+//! DFU runtime interface and reset into the bootloader
+//!
+//! The UF2 and BOSSA bootloaders stay in bootloader mode, instead of starting
+//! the application, when they find a magic value in the last word of RAM
+//! after a reset, as written when the reset button is double tapped.
+//! [`DoubleTap::reset`] writes the magic value and resets the chip, so that
+//! firmware can be updated without touching the button.
+//!
+//! [`DfuRuntime`] is a USB class announcing the DFU runtime interface, which
+//! lets tools such as `dfu-util` request the switch to the bootloader. The
+//! host sends a `DFU_DETACH` request, then resets the bus; the class then
+//! resets the chip into the bootloader.
+//!
+//! ```no_run
+//! use atsamd_hal::usb::dfu::{DfuRuntime, DoubleTap};
+//!
+//! // 32 KiB of RAM, as on the SAMD21G18, with a UF2 bootloader
+//! let bootloader = DoubleTap::uf2(0x2000_8000);
+//! let mut dfu = DfuRuntime::new(&bus_allocator, bootloader);
+//! // Pass `dfu` to `UsbDevice::poll` along with the other classes
+//! ```
+
+use usb_device::class_prelude::*;
+use usb_device::control::{Recipient, RequestType};
+use usb_device::Result;
+
+/// Magic value of the UF2 bootloader
+pub const UF2_MAGIC: u32 = 0xf016_69ef;
+
+/// Magic value of the BOSSA bootloader of the Arduino and Adafruit SAMD21
+/// boards
+pub const BOSSA_MAGIC: u32 = 0x0773_8135;
+
+/// Interface class of DFU
+const CLASS_APPLICATION_SPECIFIC: u8 = 0xfe;
+/// Interface subclass of DFU
+const SUBCLASS_DFU: u8 = 0x01;
+/// Interface protocol of the DFU runtime interface
+const PROTOCOL_RUNTIME: u8 = 0x01;
+/// Descriptor type of the DFU functional descriptor
+const DESC_DFU_FUNCTIONAL: u8 = 0x21;
+
+const DFU_DETACH: u8 = 0;
+const DFU_GETSTATUS: u8 = 3;
+const DFU_GETSTATE: u8 = 5;
+
+/// `bitCanDnload` attribute
+const ATTR_CAN_DOWNLOAD: u8 = 0x01;
+/// `bcdDFUVersion` of DFU 1.1
+const DFU_VERSION: u16 = 0x0110;
+
+/// Magic value and address checked by a bootloader after reset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DoubleTap {
+    /// Address of the magic value
+    pub address: u32,
+    /// Magic value
+    pub magic: u32,
+}
+
+impl DoubleTap {
+    /// Check for [`UF2_MAGIC`] in the last word of RAM, ending at `ram_end`
+    #[inline]
+    pub const fn uf2(ram_end: u32) -> Self {
+        Self {
+            address: ram_end - 4,
+            magic: UF2_MAGIC,
+        }
+    }
+
+    /// Check for [`BOSSA_MAGIC`] in the last word of RAM, ending at
+    /// `ram_end`
+    #[inline]
+    pub const fn bossa(ram_end: u32) -> Self {
+        Self {
+            address: ram_end - 4,
+            magic: BOSSA_MAGIC,
+        }
+    }
+
+    /// Write the magic value, then reset the chip into the bootloader
+    ///
+    /// # Safety
+    ///
+    /// `address` must be a valid word of RAM. It may be used by the stack or
+    /// the statics, as the chip resets right after the write.
+    #[inline]
+    pub unsafe fn reset(self) -> ! {
+        core::ptr::write_volatile(self.address as *mut u32, self.magic);
+        cortex_m::peripheral::SCB::sys_reset()
+    }
+}
+
+/// State of the DFU runtime interface
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// Running the application
+    AppIdle = 0,
+    /// Detach requested, waiting for the bus reset
+    AppDetach = 1,
+}
+
+/// USB class of the DFU runtime interface
+///
+/// See the [module-level documentation](self) for more details.
+pub struct DfuRuntime {
+    interface: InterfaceNumber,
+    bootloader: DoubleTap,
+    detach_timeout: u16,
+    state: State,
+}
+
+impl DfuRuntime {
+    /// Allocate the interface, resetting the chip into `bootloader` when
+    /// requested by the host
+    pub fn new<B: UsbBus>(alloc: &UsbBusAllocator<B>, bootloader: DoubleTap) -> Self {
+        Self {
+            interface: alloc.interface(),
+            bootloader,
+            detach_timeout: 1000,
+            state: State::AppIdle,
+        }
+    }
+
+    /// Set the time, in milliseconds, the host waits for its bus reset after
+    /// a `DFU_DETACH` request (builder pattern version)
+    ///
+    /// The default timeout is 1000 ms.
+    #[inline]
+    pub fn detach_timeout(mut self, timeout: u16) -> Self {
+        self.set_detach_timeout(timeout);
+        self
+    }
+
+    /// Set the time, in milliseconds, the host waits for its bus reset after
+    /// a `DFU_DETACH` request (setter version)
+    ///
+    /// The default timeout is 1000 ms.
+    #[inline]
+    pub fn set_detach_timeout(&mut self, timeout: u16) {
+        self.detach_timeout = timeout;
+    }
+
+    /// Get the detach timeout, in milliseconds
+    #[inline]
+    pub fn get_detach_timeout(&self) -> u16 {
+        self.detach_timeout
+    }
+
+    /// Return the state of the interface
+    #[inline]
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Contents of the DFU functional descriptor
+    fn functional_descriptor(&self) -> [u8; 7] {
+        let timeout = self.detach_timeout.to_le_bytes();
+        let version = DFU_VERSION.to_le_bytes();
+        [
+            ATTR_CAN_DOWNLOAD,
+            timeout[0],
+            timeout[1],
+            // wTransferSize, unused by the runtime interface
+            64,
+            0,
+            version[0],
+            version[1],
+        ]
+    }
+
+    /// Response to `DFU_GETSTATUS`
+    fn status(&self) -> [u8; 6] {
+        // bStatus OK, bwPollTimeout 0, bState, iString 0
+        [0, 0, 0, 0, self.state as u8, 0]
+    }
+}
+
+impl<B: UsbBus> UsbClass<B> for DfuRuntime {
+    fn get_configuration_descriptors(&self, writer: &mut DescriptorWriter) -> Result<()> {
+        writer.interface(
+            self.interface,
+            CLASS_APPLICATION_SPECIFIC,
+            SUBCLASS_DFU,
+            PROTOCOL_RUNTIME,
+        )?;
+        writer.write(DESC_DFU_FUNCTIONAL, &self.functional_descriptor())
+    }
+
+    fn reset(&mut self) {
+        if self.state == State::AppDetach {
+            // SAFETY: See the documentation of `DoubleTap::reset`
+            unsafe { self.bootloader.reset() }
+        }
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        let req = xfer.request();
+        if !(req.request_type == RequestType::Class
+            && req.recipient == Recipient::Interface
+            && req.index == u8::from(self.interface) as u16)
+        {
+            return;
+        }
+        match req.request {
+            DFU_DETACH => {
+                self.state = State::AppDetach;
+                xfer.accept().ok();
+            }
+            _ => {
+                xfer.reject().ok();
+            }
+        }
+    }
+
+    fn control_in(&mut self, xfer: ControlIn<B>) {
+        let req = xfer.request();
+        if !(req.request_type == RequestType::Class
+            && req.recipient == Recipient::Interface
+            && req.index == u8::from(self.interface) as u16)
+        {
+            return;
+        }
+        match req.request {
+            DFU_GETSTATUS => {
+                xfer.accept_with(&self.status()).ok();
+            }
+            DFU_GETSTATE => {
+                xfer.accept_with(&[self.state as u8]).ok();
+            }
+            _ => {
+                xfer.reject().ok();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn double_tap() {
+        let uf2 = DoubleTap::uf2(0x2000_8000);
+        assert_eq!(uf2.address, 0x2000_7ffc);
+        assert_eq!(uf2.magic, UF2_MAGIC);
+        assert_eq!(DoubleTap::bossa(0x2000_8000).magic, BOSSA_MAGIC);
+    }
+}